@@ -1,22 +1,55 @@
 use async_trait::async_trait;
 use etwin_core::auth::{
-  AuthStore, CreateSessionOptions, CreateValidatedEmailVerificationOptions, RawSession, SessionId,
+  AuthStore, CountLoginFailuresOptions, CreateLoginFailureOptions, CreateLoginLockoutOptions,
+  CreateMfaLoginChallengeOptions, CreateSessionOptions, CreateValidatedEmailVerificationOptions, LoginThrottleKey,
+  MfaLoginChallenge, MfaLoginChallengeId, RawSession, RevokeUserSessionsOptions, SessionId,
 };
 use etwin_core::clock::Clock;
 use etwin_core::core::Instant;
+use etwin_core::oauth::OauthClientIdRef;
 use etwin_core::types::AnyError;
+use etwin_core::user::UserIdRef;
 use etwin_core::uuid::UuidGenerator;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::RwLock;
 
+struct MemLoginFailure {
+  time: Instant,
+  user: Option<UserIdRef>,
+  oauth_client: Option<OauthClientIdRef>,
+  ip: Option<IpAddr>,
+}
+
+impl MemLoginFailure {
+  fn matches(&self, key: LoginThrottleKey) -> bool {
+    match key {
+      LoginThrottleKey::User(user) => self.user == Some(user),
+      LoginThrottleKey::OauthClient(client) => self.oauth_client == Some(client),
+      LoginThrottleKey::Ip(ip) => self.ip == Some(ip),
+    }
+  }
+}
+
+struct MemMfaLoginChallenge {
+  challenge: MfaLoginChallenge,
+  consumed_at: Option<Instant>,
+}
+
 struct StoreState {
   sessions: HashMap<SessionId, RawSession>,
+  login_failures: Vec<MemLoginFailure>,
+  login_lockouts: HashMap<LoginThrottleKey, Instant>,
+  mfa_login_challenges: HashMap<MfaLoginChallengeId, MemMfaLoginChallenge>,
 }
 
 impl StoreState {
   fn new() -> Self {
     Self {
       sessions: HashMap::new(),
+      login_failures: Vec::new(),
+      login_lockouts: HashMap::new(),
+      mfa_login_challenges: HashMap::new(),
     }
   }
 
@@ -51,6 +84,87 @@ impl StoreState {
       }
     }
   }
+
+  pub(crate) fn revoke_user_sessions(&mut self, options: &RevokeUserSessionsOptions) {
+    self
+      .sessions
+      .retain(|id, session| session.user != options.user || Some(*id) == options.except);
+  }
+
+  pub(crate) fn create_login_failure(&mut self, now: Instant, options: &CreateLoginFailureOptions) {
+    self.login_failures.push(MemLoginFailure {
+      time: now,
+      user: options.user,
+      oauth_client: options.oauth_client,
+      ip: options.ip,
+    });
+  }
+
+  pub(crate) fn count_login_failures(&self, options: &CountLoginFailuresOptions) -> u32 {
+    let count = self
+      .login_failures
+      .iter()
+      .filter(|failure| failure.time >= options.since && failure.matches(options.key))
+      .count();
+    u32::try_from(count).unwrap_or(u32::MAX)
+  }
+
+  pub(crate) fn create_login_lockout(&mut self, options: &CreateLoginLockoutOptions) {
+    let until = self.login_lockouts.entry(options.key).or_insert(options.until);
+    if *until < options.until {
+      *until = options.until;
+    }
+  }
+
+  pub(crate) fn get_login_lockout(&self, now: Instant, key: LoginThrottleKey) -> Option<Instant> {
+    self.login_lockouts.get(&key).copied().filter(|until| *until > now)
+  }
+
+  pub(crate) fn create_mfa_login_challenge(
+    &mut self,
+    now: Instant,
+    uuid_generator: &impl UuidGenerator,
+    options: &CreateMfaLoginChallengeOptions,
+  ) -> MfaLoginChallenge {
+    let challenge = MfaLoginChallenge {
+      id: MfaLoginChallengeId::from_uuid(uuid_generator.next()),
+      user: options.user,
+      ctime: now,
+      expires_at: options.expires_at,
+      attempts: 0,
+    };
+    self.mfa_login_challenges.insert(
+      challenge.id,
+      MemMfaLoginChallenge {
+        challenge: challenge.clone(),
+        consumed_at: None,
+      },
+    );
+    challenge
+  }
+
+  pub(crate) fn touch_mfa_login_challenge(
+    &mut self,
+    now: Instant,
+    challenge: MfaLoginChallengeId,
+  ) -> Option<MfaLoginChallenge> {
+    let challenge = self.mfa_login_challenges.get_mut(&challenge)?;
+    if challenge.consumed_at.is_some() || challenge.challenge.expires_at <= now {
+      return None;
+    }
+    challenge.challenge.attempts = challenge.challenge.attempts.saturating_add(1);
+    Some(challenge.challenge.clone())
+  }
+
+  pub(crate) fn consume_mfa_login_challenge(&mut self, now: Instant, challenge: MfaLoginChallengeId) -> bool {
+    match self.mfa_login_challenges.get_mut(&challenge) {
+      Some(challenge) if challenge.consumed_at.is_none() => {
+        challenge.consumed_at = Some(now);
+        true
+      }
+      _ => false,
+    }
+  }
 }
 
 pub struct MemAuthStore<TyClock, TyUuidGenerator>
@@ -87,7 +201,7 @@ where
     &self,
     _options: &CreateValidatedEmailVerificationOptions,
   ) -> Result<(), AnyError> {
-    eprintln!("Warning: PgAuthStore#create_validated_email_verification is a no-op stub");
+    // Verifications are an audit log which is never read back: only the Postgres store keeps them
     Ok(())
   }
 
@@ -102,6 +216,60 @@ where
     let mut state = self.state.write().unwrap();
     state.get_and_touch_session(now, session)
   }
+
+  async fn revoke_user_sessions(&self, options: &RevokeUserSessionsOptions) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.revoke_user_sessions(options);
+    Ok(())
+  }
+
+  async fn create_login_failure(&self, options: &CreateLoginFailureOptions) -> Result<(), AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    state.create_login_failure(now, options);
+    Ok(())
+  }
+
+  async fn count_login_failures(&self, options: &CountLoginFailuresOptions) -> Result<u32, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.count_login_failures(options))
+  }
+
+  async fn create_login_lockout(&self, options: &CreateLoginLockoutOptions) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.create_login_lockout(options);
+    Ok(())
+  }
+
+  async fn get_login_lockout(&self, key: LoginThrottleKey) -> Result<Option<Instant>, AnyError> {
+    let now = self.clock.now();
+    let state = self.state.read().unwrap();
+    Ok(state.get_login_lockout(now, key))
+  }
+
+  async fn create_mfa_login_challenge(
+    &self,
+    options: &CreateMfaLoginChallengeOptions,
+  ) -> Result<MfaLoginChallenge, AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    Ok(state.create_mfa_login_challenge(now, &self.uuid_generator, options))
+  }
+
+  async fn touch_mfa_login_challenge(
+    &self,
+    challenge: MfaLoginChallengeId,
+  ) -> Result<Option<MfaLoginChallenge>, AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    Ok(state.touch_mfa_login_challenge(now, challenge))
+  }
+
+  async fn consume_mfa_login_challenge(&self, challenge: MfaLoginChallengeId) -> Result<bool, AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    Ok(state.consume_mfa_login_challenge(now, challenge))
+  }
 }
 
 #[cfg(feature = "neon")]
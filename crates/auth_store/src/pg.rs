@@ -1,7 +1,9 @@
 use async_trait::async_trait;
 use etwin_core::api::ApiRef;
 use etwin_core::auth::{
-  AuthStore, CreateSessionOptions, CreateValidatedEmailVerificationOptions, RawSession, SessionId,
+  AuthStore, CountLoginFailuresOptions, CreateLoginFailureOptions, CreateLoginLockoutOptions,
+  CreateMfaLoginChallengeOptions, CreateSessionOptions, CreateValidatedEmailVerificationOptions, LoginThrottleKey,
+  MfaLoginChallenge, MfaLoginChallengeId, RawSession, RevokeUserSessionsOptions, SessionId,
 };
 use etwin_core::clock::Clock;
 use etwin_core::core::{Instant, Secret};
@@ -19,7 +21,6 @@ where
   clock: TyClock,
   database: TyDatabase,
   uuid_generator: TyUuidGenerator,
-  #[allow(unused)]
   database_secret: Secret,
 }
 
@@ -48,9 +49,28 @@ where
 {
   async fn create_validated_email_verification(
     &self,
-    _options: &CreateValidatedEmailVerificationOptions,
+    options: &CreateValidatedEmailVerificationOptions,
   ) -> Result<(), AnyError> {
-    eprintln!("Warning: PgAuthStore#create_validated_email_verification is a no-op stub");
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+          INSERT INTO email_verifications(
+            user_id, email_address, ctime, validation_time
+          )
+          VALUES (
+            $1::USER_ID, pgp_sym_encrypt($2::EMAIL_ADDRESS, $3::TEXT), $4::INSTANT, $5::INSTANT
+          );
+          ",
+    )
+    .bind(options.user.id)
+    .bind(&options.email)
+    .bind(self.database_secret.as_str())
+    .bind(options.token_issued_at)
+    .bind(now)
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
     Ok(())
   }
 
@@ -117,6 +137,208 @@ where
       atime: row.atime,
     }))
   }
+  async fn revoke_user_sessions(&self, options: &RevokeUserSessionsOptions) -> Result<(), AnyError> {
+    sqlx::query(
+      r"
+      DELETE FROM sessions
+      WHERE user_id = $1::USER_ID AND ($2::SESSION_ID IS NULL OR session_id <> $2::SESSION_ID);
+      ",
+    )
+    .bind(options.user.id)
+    .bind(options.except)
+    .execute(self.database.as_ref())
+    .await?;
+    Ok(())
+  }
+
+  async fn create_login_failure(&self, options: &CreateLoginFailureOptions) -> Result<(), AnyError> {
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+      INSERT INTO login_failures(
+        time, user_id, oauth_client_id, ip, reason
+      )
+      VALUES (
+        $1::INSTANT, $2::USER_ID, $3::OAUTH_CLIENT_ID, $4::TEXT::INET, $5::LOGIN_FAILURE_REASON
+      );
+      ",
+    )
+    .bind(now)
+    .bind(options.user.map(|user| user.id))
+    .bind(options.oauth_client.map(|client| client.id))
+    .bind(options.ip.map(|ip| ip.to_string()))
+    .bind(options.reason)
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+    Ok(())
+  }
+
+  async fn count_login_failures(&self, options: &CountLoginFailuresOptions) -> Result<u32, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      count: i64,
+    }
+
+    let query = match options.key {
+      LoginThrottleKey::User(user) => sqlx::query_as::<_, Row>(
+        r"
+        SELECT COUNT(*) AS count
+        FROM login_failures
+        WHERE user_id = $1::USER_ID AND time >= $2::INSTANT;
+        ",
+      )
+      .bind(user.id),
+      LoginThrottleKey::OauthClient(client) => sqlx::query_as::<_, Row>(
+        r"
+        SELECT COUNT(*) AS count
+        FROM login_failures
+        WHERE oauth_client_id = $1::OAUTH_CLIENT_ID AND time >= $2::INSTANT;
+        ",
+      )
+      .bind(client.id),
+      LoginThrottleKey::Ip(ip) => sqlx::query_as::<_, Row>(
+        r"
+        SELECT COUNT(*) AS count
+        FROM login_failures
+        WHERE ip = $1::TEXT::INET AND time >= $2::INSTANT;
+        ",
+      )
+      .bind(ip.to_string()),
+    };
+    let row: Row = query.bind(options.since).fetch_one(self.database.as_ref()).await?;
+    Ok(u32::try_from(row.count)?)
+  }
+
+  async fn create_login_lockout(&self, options: &CreateLoginLockoutOptions) -> Result<(), AnyError> {
+    sqlx::query(
+      r"
+      INSERT INTO login_lockouts(key, until)
+      VALUES ($1::LOGIN_THROTTLE_KEY, $2::INSTANT)
+      ON CONFLICT (key) DO UPDATE SET until = GREATEST(login_lockouts.until, excluded.until);
+      ",
+    )
+    .bind(options.key.to_string())
+    .bind(options.until)
+    .execute(self.database.as_ref())
+    .await?;
+    Ok(())
+  }
+
+  async fn get_login_lockout(&self, key: LoginThrottleKey) -> Result<Option<Instant>, AnyError> {
+    let now = self.clock.now();
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      until: Instant,
+    }
+
+    let row: Option<Row> = sqlx::query_as::<_, Row>(
+      r"
+      SELECT until
+      FROM login_lockouts
+      WHERE key = $1::LOGIN_THROTTLE_KEY AND until > $2::INSTANT;
+      ",
+    )
+    .bind(key.to_string())
+    .bind(now)
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    Ok(row.map(|row| row.until))
+  }
+
+  async fn create_mfa_login_challenge(
+    &self,
+    options: &CreateMfaLoginChallengeOptions,
+  ) -> Result<MfaLoginChallenge, AnyError> {
+    let challenge_id = MfaLoginChallengeId::from_uuid(self.uuid_generator.next());
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+      INSERT INTO mfa_login_challenges(
+        mfa_login_challenge_id, user_id, ctime, expires_at, attempts, consumed_at
+      )
+      VALUES (
+        $1::MFA_LOGIN_CHALLENGE_ID, $2::USER_ID, $3::INSTANT, $4::INSTANT, 0, NULL
+      );
+      ",
+    )
+    .bind(challenge_id)
+    .bind(options.user.id)
+    .bind(now)
+    .bind(options.expires_at)
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+
+    Ok(MfaLoginChallenge {
+      id: challenge_id,
+      user: options.user,
+      ctime: now,
+      expires_at: options.expires_at,
+      attempts: 0,
+    })
+  }
+
+  async fn touch_mfa_login_challenge(
+    &self,
+    challenge: MfaLoginChallengeId,
+  ) -> Result<Option<MfaLoginChallenge>, AnyError> {
+    let now = self.clock.now();
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      user_id: UserId,
+      ctime: Instant,
+      expires_at: Instant,
+      attempts: i64,
+    }
+
+    let row: Option<Row> = sqlx::query_as::<_, Row>(
+      r"
+      UPDATE mfa_login_challenges
+      SET attempts = attempts + 1
+      WHERE mfa_login_challenge_id = $1::MFA_LOGIN_CHALLENGE_ID AND consumed_at IS NULL AND expires_at > $2::INSTANT
+      RETURNING user_id, ctime, expires_at, attempts;
+      ",
+    )
+    .bind(challenge)
+    .bind(now)
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    row
+      .map(|row| {
+        Ok(MfaLoginChallenge {
+          id: challenge,
+          user: row.user_id.into(),
+          ctime: row.ctime,
+          expires_at: row.expires_at,
+          attempts: u32::try_from(row.attempts)?,
+        })
+      })
+      .transpose()
+  }
+
+  async fn consume_mfa_login_challenge(&self, challenge: MfaLoginChallengeId) -> Result<bool, AnyError> {
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+      UPDATE mfa_login_challenges
+      SET consumed_at = $2::INSTANT
+      WHERE mfa_login_challenge_id = $1::MFA_LOGIN_CHALLENGE_ID AND consumed_at IS NULL;
+      ",
+    )
+    .bind(challenge)
+    .bind(now)
+    .execute(self.database.as_ref())
+    .await?;
+    Ok(res.rows_affected() == 1)
+  }
 }
 
 #[cfg(feature = "neon")]
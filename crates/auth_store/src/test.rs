@@ -1,14 +1,23 @@
 use chrono::Duration;
 use etwin_core::api::ApiRef;
-use etwin_core::auth::{AuthStore, CreateSessionOptions, RawSession};
+use etwin_core::auth::{
+  AuthStore, CountLoginFailuresOptions, CreateLoginFailureOptions, CreateLoginLockoutOptions,
+  CreateMfaLoginChallengeOptions, CreateSessionOptions, LoginFailureReason, LoginThrottleKey, MfaLoginChallenge,
+  RawSession, RevokeUserSessionsOptions,
+};
 use etwin_core::clock::VirtualClock;
 use etwin_core::core::Instant;
-use etwin_core::user::{CreateUserOptions, UserStore};
+use etwin_core::user::{CreateUserOptions, UserIdRef, UserStore};
+use std::net::{IpAddr, Ipv4Addr};
 
 #[macro_export]
 macro_rules! test_dinoparc_store {
   ($(#[$meta:meta])* || $api:expr) => {
     register_test!($(#[$meta])*, $api, test_create_session);
+    register_test!($(#[$meta])*, $api, test_revoke_user_sessions);
+    register_test!($(#[$meta])*, $api, test_count_login_failures);
+    register_test!($(#[$meta])*, $api, test_login_lockout);
+    register_test!($(#[$meta])*, $api, test_mfa_login_challenge);
   };
 }
 
@@ -67,3 +76,245 @@ pub(crate) async fn test_create_session<TyAuthStore, TyClock, TyUserStore>(
   };
   assert_eq!(actual, expected);
 }
+
+pub(crate) async fn test_revoke_user_sessions<TyAuthStore, TyClock, TyUserStore>(
+  api: TestApi<TyAuthStore, TyClock, TyUserStore>,
+) where
+  TyAuthStore: AuthStore,
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: None,
+      password: None,
+    })
+    .await
+    .unwrap();
+  let bob = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Bob".parse().unwrap(),
+      username: Some("bob".parse().unwrap()),
+      email: None,
+      password: None,
+    })
+    .await
+    .unwrap();
+  let create_session = |user: UserIdRef| {
+    let auth_store = &api.auth_store;
+    async move {
+      auth_store
+        .create_session(&CreateSessionOptions { user })
+        .await
+        .unwrap()
+        .id
+    }
+  };
+  let alice1 = create_session(alice.id.into()).await;
+  let alice2 = create_session(alice.id.into()).await;
+  let alice3 = create_session(alice.id.into()).await;
+  let bob1 = create_session(bob.id.into()).await;
+
+  api
+    .auth_store
+    .revoke_user_sessions(&RevokeUserSessionsOptions {
+      user: alice.id.into(),
+      except: Some(alice2),
+    })
+    .await
+    .unwrap();
+  assert!(api.auth_store.get_and_touch_session(alice1).await.unwrap().is_none());
+  assert!(api.auth_store.get_and_touch_session(alice2).await.unwrap().is_some());
+  assert!(api.auth_store.get_and_touch_session(alice3).await.unwrap().is_none());
+  assert!(api.auth_store.get_and_touch_session(bob1).await.unwrap().is_some());
+
+  api
+    .auth_store
+    .revoke_user_sessions(&RevokeUserSessionsOptions {
+      user: alice.id.into(),
+      except: None,
+    })
+    .await
+    .unwrap();
+  assert!(api.auth_store.get_and_touch_session(alice2).await.unwrap().is_none());
+  assert!(api.auth_store.get_and_touch_session(bob1).await.unwrap().is_some());
+}
+
+pub(crate) async fn test_count_login_failures<TyAuthStore, TyClock, TyUserStore>(
+  api: TestApi<TyAuthStore, TyClock, TyUserStore>,
+) where
+  TyAuthStore: AuthStore,
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let user = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: None,
+      password: None,
+    })
+    .await
+    .unwrap();
+  let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1));
+
+  api
+    .auth_store
+    .create_login_failure(&CreateLoginFailureOptions {
+      user: Some(user.id.into()),
+      oauth_client: None,
+      ip: Some(ip),
+      reason: LoginFailureReason::WrongPassword,
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .auth_store
+    .create_login_failure(&CreateLoginFailureOptions {
+      user: None,
+      oauth_client: None,
+      ip: Some(ip),
+      reason: LoginFailureReason::UnknownLogin,
+    })
+    .await
+    .unwrap();
+
+  let count = |key: LoginThrottleKey, since: Instant| {
+    let auth_store = &api.auth_store;
+    async move {
+      auth_store
+        .count_login_failures(&CountLoginFailuresOptions { key, since })
+        .await
+        .unwrap()
+    }
+  };
+  assert_eq!(
+    count(
+      LoginThrottleKey::User(user.id.into()),
+      Instant::ymd_hms(2021, 1, 1, 0, 0, 0)
+    )
+    .await,
+    1
+  );
+  assert_eq!(
+    count(LoginThrottleKey::Ip(ip), Instant::ymd_hms(2021, 1, 1, 0, 0, 0)).await,
+    2
+  );
+  assert_eq!(
+    count(LoginThrottleKey::Ip(ip), Instant::ymd_hms(2021, 1, 1, 0, 0, 1)).await,
+    1
+  );
+  assert_eq!(
+    count(
+      LoginThrottleKey::Ip(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 2))),
+      Instant::ymd_hms(2021, 1, 1, 0, 0, 0)
+    )
+    .await,
+    0
+  );
+}
+
+pub(crate) async fn test_login_lockout<TyAuthStore, TyClock, TyUserStore>(
+  api: TestApi<TyAuthStore, TyClock, TyUserStore>,
+) where
+  TyAuthStore: AuthStore,
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let key = LoginThrottleKey::Ip(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)));
+
+  assert_eq!(api.auth_store.get_login_lockout(key).await.unwrap(), None);
+  api
+    .auth_store
+    .create_login_lockout(&CreateLoginLockoutOptions {
+      key,
+      until: Instant::ymd_hms(2021, 1, 1, 0, 15, 0),
+    })
+    .await
+    .unwrap();
+  // An earlier end does not shorten the lockout
+  api
+    .auth_store
+    .create_login_lockout(&CreateLoginLockoutOptions {
+      key,
+      until: Instant::ymd_hms(2021, 1, 1, 0, 5, 0),
+    })
+    .await
+    .unwrap();
+  assert_eq!(
+    api.auth_store.get_login_lockout(key).await.unwrap(),
+    Some(Instant::ymd_hms(2021, 1, 1, 0, 15, 0))
+  );
+
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 15, 0));
+  assert_eq!(api.auth_store.get_login_lockout(key).await.unwrap(), None);
+}
+
+pub(crate) async fn test_mfa_login_challenge<TyAuthStore, TyClock, TyUserStore>(
+  api: TestApi<TyAuthStore, TyClock, TyUserStore>,
+) where
+  TyAuthStore: AuthStore,
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let user = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: None,
+      password: None,
+    })
+    .await
+    .unwrap();
+
+  let challenge = api
+    .auth_store
+    .create_mfa_login_challenge(&CreateMfaLoginChallengeOptions {
+      user: user.id.into(),
+      expires_at: Instant::ymd_hms(2021, 1, 1, 0, 5, 0),
+    })
+    .await
+    .unwrap();
+  let expected = MfaLoginChallenge {
+    id: challenge.id,
+    user: user.id.into(),
+    ctime: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+    expires_at: Instant::ymd_hms(2021, 1, 1, 0, 5, 0),
+    attempts: 0,
+  };
+  assert_eq!(challenge, expected);
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let first = api.auth_store.touch_mfa_login_challenge(challenge.id).await.unwrap();
+  assert_eq!(first.map(|c| c.attempts), Some(1));
+  let second = api.auth_store.touch_mfa_login_challenge(challenge.id).await.unwrap();
+  assert_eq!(second.map(|c| c.attempts), Some(2));
+
+  assert!(api.auth_store.consume_mfa_login_challenge(challenge.id).await.unwrap());
+  assert!(!api.auth_store.consume_mfa_login_challenge(challenge.id).await.unwrap());
+  let consumed = api.auth_store.touch_mfa_login_challenge(challenge.id).await.unwrap();
+  assert_eq!(consumed, None);
+
+  let expiring = api
+    .auth_store
+    .create_mfa_login_challenge(&CreateMfaLoginChallengeOptions {
+      user: user.id.into(),
+      expires_at: Instant::ymd_hms(2021, 1, 1, 0, 5, 0),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 5, 0));
+  let expired = api.auth_store.touch_mfa_login_challenge(expiring.id).await.unwrap();
+  assert_eq!(expired, None);
+}
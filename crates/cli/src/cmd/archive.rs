@@ -0,0 +1,104 @@
+use clap::Clap;
+use etwin_config::Config;
+use etwin_core::clock::SystemClock;
+use etwin_core::core::Secret;
+use etwin_core::types::AnyError;
+use etwin_core::uuid::Uuid4Generator;
+use etwin_dinoparc_client::http::HttpDinoparcClient;
+use etwin_dinoparc_store::pg::PgDinoparcStore;
+use etwin_hammerfest_client::HttpHammerfestClient;
+use etwin_hammerfest_store::pg::PgHammerfestStore;
+use etwin_log::NoopLogger;
+use etwin_services::archive_refresh::ArchiveRefreshService;
+use etwin_token_store::pg::PgTokenStore;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Arguments to the `archive` task.
+#[derive(Debug, Clap)]
+pub struct ArchiveArgs {
+  #[clap(subcommand)]
+  command: ArchiveCommand,
+}
+
+#[derive(Debug, Clap)]
+pub enum ArchiveCommand {
+  /// Fetch again the data of users with stale archived data
+  #[clap(name = "refresh")]
+  Refresh(RefreshArgs),
+}
+
+/// Arguments to the `archive refresh` task.
+#[derive(Debug, Clap)]
+pub struct RefreshArgs {
+  /// Age in days after which archived data is stale
+  #[clap(long, default_value = "30")]
+  max_age: i64,
+  /// Minimum delay in hours between two refresh attempts for the same user
+  #[clap(long, default_value = "24")]
+  retry_delay: i64,
+  /// Maximum number of users refreshed per server
+  #[clap(long, default_value = "100")]
+  limit: u32,
+  /// Minimum delay in milliseconds between two requests to the same server
+  #[clap(long, default_value = "1000")]
+  interval: u64,
+}
+
+pub async fn run(args: &ArchiveArgs) -> Result<(), AnyError> {
+  match &args.command {
+    ArchiveCommand::Refresh(ref args) => refresh(args).await,
+  }
+}
+
+async fn refresh(args: &RefreshArgs) -> Result<(), AnyError> {
+  let config: Config = etwin_config::find_config(env::current_dir()?)?;
+  let database = Arc::new(crate::pg::connect(&config.db).await?);
+  let database_secret = Secret::new(config.etwin.secret.clone());
+
+  let clock = Arc::new(SystemClock);
+  let dinoparc_client = HttpDinoparcClient::new(Arc::clone(&clock), NoopLogger)?;
+  let dinoparc_store = PgDinoparcStore::new(Arc::clone(&clock), Arc::clone(&database), Uuid4Generator)
+    .await
+    .map_err(|e| -> AnyError { e.to_string().into() })?;
+  let hammerfest_client = HttpHammerfestClient::new(Arc::clone(&clock))?;
+  let hammerfest_store = PgHammerfestStore::new(
+    Arc::clone(&clock),
+    Arc::clone(&database),
+    database_secret.clone(),
+    Uuid4Generator,
+  )
+  .await
+  .map_err(|e| -> AnyError { e.to_string().into() })?;
+  let token_store = PgTokenStore::new(Arc::clone(&clock), Arc::clone(&database), database_secret)
+    .await
+    .map_err(|e| -> AnyError { e.to_string().into() })?;
+
+  let refresh = ArchiveRefreshService::new(
+    clock,
+    dinoparc_client,
+    dinoparc_store,
+    hammerfest_client,
+    hammerfest_store,
+    token_store,
+  )
+  .with_max_age(chrono::Duration::days(args.max_age))
+  .with_retry_delay(chrono::Duration::hours(args.retry_delay))
+  .with_limit(args.limit)
+  .with_request_interval(Duration::from_millis(args.interval));
+
+  let report = refresh.refresh_stale_users().await?;
+  for user in report.refreshed.iter() {
+    eprintln!("Refreshed: {}", user);
+  }
+  for failure in report.failures.iter() {
+    eprintln!("RefreshFailure: {}: {}", failure.user, failure.error);
+  }
+  eprintln!(
+    "Refreshed {} users, {} failures",
+    report.refreshed.len(),
+    report.failures.len()
+  );
+  Ok(())
+}
@@ -0,0 +1,375 @@
+use clap::Clap;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use etwin_config::Config;
+use etwin_core::types::AnyError;
+use etwin_squirrel::{MigrationDirection, MigrationProgress, SchemaMigration, SchemaStateRef, SchemaVersionRef};
+use sqlx::PgPool;
+use std::env;
+use std::error::Error;
+use std::num::NonZeroU32;
+
+/// Arguments to the `db` task.
+#[derive(Debug, Clap)]
+pub struct DbArgs {
+  #[clap(subcommand)]
+  command: DbCommand,
+}
+
+#[derive(Debug, Clap)]
+pub enum DbCommand {
+  /// Print the current and latest schema versions
+  #[clap(name = "status")]
+  Status(StatusArgs),
+  /// Upgrade the schema, to the latest version by default
+  #[clap(name = "upgrade")]
+  Upgrade(UpgradeArgs),
+  /// Downgrade the schema to an older version
+  #[clap(name = "downgrade")]
+  Downgrade(DowngradeArgs),
+  /// Drop all the data and create the latest schema
+  #[clap(name = "reset")]
+  Reset(ResetArgs),
+  /// Check that the schema matches the scripts of its version, using a temporary database (requires `CREATEDB`)
+  #[clap(name = "verify")]
+  Verify(VerifyArgs),
+  /// Print a squashed creation script, for the latest version by default
+  #[clap(name = "squash")]
+  Squash(SquashArgs),
+}
+
+/// Arguments to the `db status` task.
+#[derive(Debug, Clap)]
+pub struct StatusArgs {}
+
+/// Arguments to the `db upgrade` task.
+#[derive(Debug, Clap)]
+pub struct UpgradeArgs {
+  /// Target version
+  #[clap(long)]
+  to: Option<NonZeroU32>,
+  /// Print the SQL scripts of the migration instead of applying them
+  #[clap(long)]
+  dry_run: bool,
+}
+
+/// Arguments to the `db downgrade` task.
+#[derive(Debug, Clap)]
+pub struct DowngradeArgs {
+  /// Target version, 026 or later
+  #[clap(long)]
+  to: NonZeroU32,
+  /// Print the SQL scripts of the migration instead of applying them
+  #[clap(long)]
+  dry_run: bool,
+  /// Skip the confirmation prompt
+  #[clap(long)]
+  yes: bool,
+}
+
+/// Arguments to the `db reset` task.
+#[derive(Debug, Clap)]
+pub struct ResetArgs {
+  /// Print the SQL scripts creating the latest schema instead of applying them
+  #[clap(long)]
+  dry_run: bool,
+  /// Skip the confirmation prompt
+  #[clap(long)]
+  yes: bool,
+}
+
+/// Arguments to the `db verify` task.
+#[derive(Debug, Clap)]
+pub struct VerifyArgs {}
+
+/// Arguments to the `db squash` task.
+#[derive(Debug, Clap)]
+pub struct SquashArgs {
+  /// Target version
+  #[clap(long)]
+  to: Option<NonZeroU32>,
+}
+
+pub async fn run(args: &DbArgs) -> Result<(), AnyError> {
+  if let DbCommand::Squash(ref args) = &args.command {
+    return squash(args);
+  }
+  let config: Config = etwin_config::find_config(env::current_dir()?)?;
+  let database = crate::pg::connect_admin(&config.db).await?;
+  let res = match &args.command {
+    DbCommand::Status(ref args) => status(&database, args).await,
+    DbCommand::Upgrade(ref args) => upgrade(&database, args).await,
+    DbCommand::Downgrade(ref args) => downgrade(&database, args).await,
+    DbCommand::Reset(ref args) => reset(&database, args).await,
+    DbCommand::Verify(ref args) => verify(&database, &config, args).await,
+    DbCommand::Squash(_) => unreachable!("`db squash` does not use the database"),
+  };
+  database.close().await;
+  res
+}
+
+async fn status(database: &PgPool, _args: &StatusArgs) -> Result<(), AnyError> {
+  let resolver = etwin_db_schema::resolver();
+  let current = get_state(database).await?;
+  let latest = resolver.get_latest();
+  println!("Current version: {}", display_state(current));
+  println!("Latest version: {}", display_version(latest));
+  if current == latest.into() {
+    println!("Status: up to date");
+  } else {
+    match resolver.create_migration(current, latest, MigrationDirection::UpgradeOnly) {
+      Some(migration) => println!("Status: {} pending upgrade script(s)", migration.steps().count()),
+      None => println!("Status: no upgrade path to the latest version"),
+    }
+  }
+  Ok(())
+}
+
+async fn upgrade(database: &PgPool, args: &UpgradeArgs) -> Result<(), AnyError> {
+  let resolver = etwin_db_schema::resolver();
+  let current = get_state(database).await?;
+  let target = match args.to {
+    Some(to) => get_version(to)?,
+    None => resolver.get_latest(),
+  };
+  if current == target.into() {
+    eprintln!("Already at version {}", display_version(target));
+    return Ok(());
+  }
+  let migration = resolver
+    .create_migration(current, target, MigrationDirection::UpgradeOnly)
+    .ok_or_else(|| -> AnyError {
+      format!(
+        "No upgrade path from {} to {}",
+        display_state(current),
+        display_version(target)
+      )
+      .into()
+    })?;
+  if args.dry_run {
+    print_migration(&migration);
+    return Ok(());
+  }
+  apply_migration(database, &migration).await?;
+  eprintln!("Upgraded to version {}", display_version(target));
+  Ok(())
+}
+
+async fn downgrade(database: &PgPool, args: &DowngradeArgs) -> Result<(), AnyError> {
+  let resolver = etwin_db_schema::resolver();
+  if args.to.get() < etwin_db_schema::LOWEST_DOWNGRADE_VERSION {
+    return Err(
+      format!(
+        "Downgrades to versions before {:03} are not supported",
+        etwin_db_schema::LOWEST_DOWNGRADE_VERSION
+      )
+      .into(),
+    );
+  }
+  let current = get_state(database).await?;
+  let target = get_version(args.to)?;
+  if current == target.into() {
+    eprintln!("Already at version {}", display_version(target));
+    return Ok(());
+  }
+  let migration = resolver
+    .create_migration(current, target, MigrationDirection::DowngradeOnly)
+    .ok_or_else(|| -> AnyError {
+      format!(
+        "No downgrade path from {} to {}",
+        display_state(current),
+        display_version(target)
+      )
+      .into()
+    })?;
+  if args.dry_run {
+    print_migration(&migration);
+    return Ok(());
+  }
+  confirm(
+    &format!(
+      "Downgrade the database from {} to {}? Data in the removed schema objects is lost",
+      display_state(current),
+      display_version(target)
+    ),
+    args.yes,
+  )?;
+  apply_migration(database, &migration).await?;
+  eprintln!("Downgraded to version {}", display_version(target));
+  Ok(())
+}
+
+async fn reset(database: &PgPool, args: &ResetArgs) -> Result<(), AnyError> {
+  let resolver = etwin_db_schema::resolver();
+  let latest = resolver.get_latest();
+  if args.dry_run {
+    let migration = resolver
+      .create_migration(resolver.get_empty(), latest, MigrationDirection::UpgradeOnly)
+      .expect("Unreachable latest version from empty DB");
+    print_migration(&migration);
+    return Ok(());
+  }
+  confirm("Drop all the data and create the latest schema?", args.yes)?;
+  etwin_db_schema::force_create_latest(database, false)
+    .await
+    .map_err(into_any_error)?;
+  eprintln!("Reset to version {}", display_version(latest));
+  Ok(())
+}
+
+async fn verify(database: &PgPool, config: &Config, _args: &VerifyArgs) -> Result<(), AnyError> {
+  // The expected schema is built in a temporary database, next to the verified one
+  let scratch_name = format!("{}_verify", config.db.name);
+  let verification = etwin_db_schema::verify(database, crate::pg::admin_options(&config.db), &scratch_name)
+    .await
+    .map_err(into_any_error)?;
+  println!("Current version: {}", display_opt_version(verification.version));
+  match (&verification.recorded_checksum, &verification.expected_checksum) {
+    (_, None) => println!("Checksum: none"),
+    (None, Some(_)) => println!("Checksum: not recorded"),
+    (Some(recorded), Some(expected)) if recorded == expected => println!("Checksum: {}", recorded),
+    (Some(recorded), Some(expected)) => println!(
+      "Checksum: mismatch, recorded {} but the scripts have {}",
+      recorded, expected
+    ),
+  }
+  for line in &verification.missing {
+    println!("-{}", line);
+  }
+  for line in &verification.unexpected {
+    println!("+{}", line);
+  }
+  if verification.is_ok() {
+    println!("Status: ok");
+    Ok(())
+  } else if verification.has_drift() {
+    Err("The schema differs from the scripts".into())
+  } else {
+    Err("The scripts changed since the current version was reached".into())
+  }
+}
+
+fn squash(args: &SquashArgs) -> Result<(), AnyError> {
+  let resolver = etwin_db_schema::resolver();
+  let target = match args.to {
+    Some(to) => get_version(to)?,
+    None => resolver.get_latest(),
+  };
+  let squashed = resolver.squash(target).ok_or_else(|| -> AnyError {
+    format!(
+      "Version {} depends on Rust data migrations and can't be squashed",
+      display_version(target)
+    )
+    .into()
+  })?;
+  print!("{}", squashed);
+  Ok(())
+}
+
+async fn get_state(database: &PgPool) -> Result<SchemaStateRef<'static>, AnyError> {
+  etwin_db_schema::get_state(database).await.map_err(into_any_error)
+}
+
+fn get_version(version: NonZeroU32) -> Result<SchemaVersionRef<'static>, AnyError> {
+  etwin_db_schema::resolver()
+    .get_version(version)
+    .ok_or_else(|| format!("Unknown schema version: {}", version).into())
+}
+
+async fn apply_migration(database: &PgPool, migration: &SchemaMigration<'_>) -> Result<(), AnyError> {
+  etwin_db_schema::resolver()
+    .apply_migration_with_progress(database, migration, &print_progress)
+    .await
+    .map_err(into_any_error)
+}
+
+/// Ask for confirmation before a destructive operation, unless `yes` is set.
+fn confirm(prompt: &str, yes: bool) -> Result<(), AnyError> {
+  if yes {
+    return Ok(());
+  }
+  if !console::user_attended() {
+    return Err("Confirmation required, but the terminal is not interactive: use `--yes`".into());
+  }
+  let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+    .with_prompt(prompt)
+    .default(false)
+    .interact()?;
+  if confirmed {
+    Ok(())
+  } else {
+    Err("Aborted".into())
+  }
+}
+
+fn print_migration(migration: &SchemaMigration<'_>) {
+  for step in migration.steps() {
+    println!(
+      "-- Migration step: {} -> {}",
+      display_opt_version(step.start),
+      display_opt_version(step.end)
+    );
+    println!("{}", step.schema.trim_end());
+    if let Some(data) = step.data {
+      println!("-- Data migration");
+      println!("{}", data.trim_end());
+    }
+    if step.has_rust_data {
+      println!("-- Rust data migration (not printable)");
+    }
+  }
+}
+
+fn print_progress(progress: MigrationProgress) {
+  match progress {
+    MigrationProgress::Schema { start, end } => {
+      eprintln!(
+        "{} -> {}: schema",
+        display_opt_version(start),
+        display_opt_version(Some(end))
+      )
+    }
+    MigrationProgress::SqlData { start, end } => {
+      eprintln!(
+        "{} -> {}: data",
+        display_opt_version(start),
+        display_opt_version(Some(end))
+      )
+    }
+    MigrationProgress::RustData {
+      start,
+      end,
+      done,
+      total,
+    } => {
+      let total = total.map(|t| format!("/{}", t)).unwrap_or_default();
+      eprintln!(
+        "{} -> {}: data {}{}",
+        display_opt_version(start),
+        display_opt_version(Some(end)),
+        done,
+        total
+      )
+    }
+  }
+}
+
+fn display_state(state: SchemaStateRef<'_>) -> String {
+  display_opt_version(state.version())
+}
+
+fn display_version(version: SchemaVersionRef<'_>) -> String {
+  display_opt_version(Some(version.get()))
+}
+
+fn display_opt_version(version: Option<NonZeroU32>) -> String {
+  match version {
+    None => "empty".to_string(),
+    Some(v) => format!("{:03}", v),
+  }
+}
+
+// Squirrel errors are not `Send`
+fn into_any_error(e: Box<dyn Error>) -> AnyError {
+  e.to_string().into()
+}
@@ -0,0 +1,12 @@
+use clap::Clap;
+use etwin_core::types::AnyError;
+use etwin_rest::openapi::openapi;
+
+/// Arguments to the `openapi` task.
+#[derive(Debug, Clap)]
+pub struct OpenapiArgs {}
+
+pub async fn run(_args: &OpenapiArgs) -> Result<(), AnyError> {
+  println!("{}", serde_json::to_string_pretty(&openapi())?);
+  Ok(())
+}
@@ -3,9 +3,10 @@ use dialoguer::{theme::ColorfulTheme, Input};
 use etwin_core::clock::SystemClock;
 use etwin_core::oauth::RfcOauthAccessTokenKey;
 use etwin_core::twinoid::api::ConstUserQuery;
-use etwin_core::twinoid::{TwinoidApiAuth, TwinoidClient};
+use etwin_core::twinoid::{TwinoidApiAuth, TwinoidClient, TwinoidUserProfile};
 use etwin_core::types::AnyError;
 use etwin_twinoid_client::http::HttpTwinoidClient;
+use std::convert::TryFrom;
 
 /// Arguments to the `twinoid` task.
 #[derive(Debug, Clap)]
@@ -25,9 +26,17 @@ pub async fn run(_args: &TwinoidArgs) -> Result<(), AnyError> {
   let twinoid_client = HttpTwinoidClient::new(clock).unwrap();
 
   eprintln!("Fetching `me`");
-  let me = twinoid_client.get_me(auth, &ConstUserQuery::<true, true>).await?;
+  let me = twinoid_client
+    .get_me(auth.clone(), &ConstUserQuery::<true, true>)
+    .await?;
   eprintln!("Fetched `me`:");
   eprintln!("{:#?}", &me);
 
+  eprintln!("Fetching profile");
+  let user = twinoid_client.get_me(auth, &TwinoidUserProfile::fields()).await?;
+  let profile = TwinoidUserProfile::try_from(user)?;
+  eprintln!("Fetched profile:");
+  eprintln!("{:#?}", &profile);
+
   Ok(())
 }
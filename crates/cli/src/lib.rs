@@ -3,10 +3,14 @@ use clap::Clap;
 use etwin_core::types::AnyError;
 
 pub mod cmd {
+  pub mod archive;
+  pub mod db;
   pub mod dinoparc;
   pub mod dump;
+  pub mod openapi;
   pub mod twinoid;
 }
+mod pg;
 pub mod rest;
 
 #[derive(Debug, Clap)]
@@ -18,12 +22,21 @@ pub struct CliArgs {
 
 #[derive(Debug, Clap)]
 pub enum CliCommand {
+  /// Maintain the archive of remote game data
+  #[clap(name = "archive")]
+  Archive(cmd::archive::ArchiveArgs),
+  /// Manage the database schema
+  #[clap(name = "db")]
+  Db(cmd::db::DbArgs),
   /// Run the Dinoparc client demo
   #[clap(name = "dinoparc")]
   Dinoparc(cmd::dinoparc::DinoparcArgs),
   /// Dump the DB state into a directory
   #[clap(name = "dump")]
   Dump(cmd::dump::DumpArgs),
+  /// Print the OpenAPI document of the REST API
+  #[clap(name = "openapi")]
+  Openapi(cmd::openapi::OpenapiArgs),
   /// Start REST server
   #[clap(name = "rest")]
   Rest(RestArgs),
@@ -34,8 +47,11 @@ pub enum CliCommand {
 
 pub async fn run(args: &CliArgs) -> Result<(), AnyError> {
   match &args.command {
+    CliCommand::Archive(ref args) => cmd::archive::run(args).await,
+    CliCommand::Db(ref args) => cmd::db::run(args).await,
     CliCommand::Dinoparc(ref args) => cmd::dinoparc::run(args).await,
     CliCommand::Dump(ref args) => cmd::dump::run(args).await,
+    CliCommand::Openapi(ref args) => cmd::openapi::run(args).await,
     CliCommand::Rest(ref args) => crate::rest::run(args).await,
     CliCommand::Twinoid(ref args) => cmd::twinoid::run(args).await,
   }
@@ -0,0 +1,36 @@
+use etwin_config::DbConfig;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+
+/// Connect to the database with the regular runtime user.
+pub(crate) async fn connect(config: &DbConfig) -> Result<PgPool, sqlx::Error> {
+  PgPoolOptions::new()
+    .max_connections(5)
+    .connect_with(
+      PgConnectOptions::new()
+        .host(&config.host)
+        .port(config.port)
+        .database(&config.name)
+        .username(&config.user)
+        .password(&config.password),
+    )
+    .await
+}
+
+/// Connection options for the admin user, owning the schema.
+pub(crate) fn admin_options(config: &DbConfig) -> PgConnectOptions {
+  PgConnectOptions::new()
+    .host(&config.host)
+    .port(config.port)
+    .database(&config.name)
+    .username(&config.admin_user)
+    .password(&config.admin_password)
+}
+
+/// Connect to the database with the admin user, owning the schema.
+pub(crate) async fn connect_admin(config: &DbConfig) -> Result<PgPool, sqlx::Error> {
+  PgPoolOptions::new()
+    .max_connections(1)
+    .connect_with(admin_options(config))
+    .await
+}
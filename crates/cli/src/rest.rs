@@ -1,59 +1,416 @@
 use clap::Clap;
-use etwin_core::clock::VirtualClock;
-use etwin_core::core::Instant;
-use etwin_core::dinoparc::DinoparcStore;
+use etwin_auth_store::mem::MemAuthStore;
+use etwin_auth_store::pg::PgAuthStore;
+use etwin_config::{Config, FileMailerFormat as FileMailerFormatConfig, LogConfig, MailerConfig, MailerHeader};
+use etwin_core::auth::AuthStore;
+use etwin_core::clock::{Clock, SystemClock};
+use etwin_core::core::Secret;
+use etwin_core::dinoparc::{DinoparcClient, DinoparcStore};
+use etwin_core::email::{EmailFormatter, Mailer, OutboxStore};
+use etwin_core::forum::ForumStore;
 use etwin_core::hammerfest::{HammerfestClient, HammerfestStore};
 use etwin_core::link::LinkStore;
+use etwin_core::oauth::OauthProviderStore;
+use etwin_core::password::PasswordService;
+use etwin_core::token::TokenStore;
+use etwin_core::twinoid::{TwinoidClient, TwinoidOauthClient, TwinoidStore};
 use etwin_core::types::AnyError;
 use etwin_core::user::UserStore;
-use etwin_core::uuid::Uuid4Generator;
+use etwin_core::uuid::{Uuid4Generator, UuidGenerator};
+use etwin_dinoparc_client::http::HttpDinoparcClient;
 use etwin_dinoparc_store::mem::MemDinoparcStore;
+use etwin_dinoparc_store::pg::PgDinoparcStore;
+use etwin_email_formatter::html::HtmlEmailFormatter;
+use etwin_forum_store::mem::MemForumStore;
+use etwin_forum_store::pg::PgForumStore;
 use etwin_hammerfest_client::HttpHammerfestClient;
 use etwin_hammerfest_store::mem::MemHammerfestStore;
+use etwin_hammerfest_store::pg::PgHammerfestStore;
 use etwin_link_store::mem::MemLinkStore;
+use etwin_link_store::pg::PgLinkStore;
+use etwin_log::file::RotatingFile;
+use etwin_log::json::JsonLogger;
+use etwin_log::{Logger, NoopLogger};
+use etwin_mailer::file::{FileMailer, FileMailerFormat};
+use etwin_mailer::noop::NoopMailer;
+use etwin_mailer::smtp::{HeaderName, RawHeader, SmtpMailerBuilder};
+use etwin_oauth_provider_store::mem::MemOauthProviderStore;
+use etwin_oauth_provider_store::pg::PgOauthProviderStore;
+use etwin_outbox_store::mem::MemOutboxStore;
+use etwin_outbox_store::pg::PgOutboxStore;
+use etwin_password::argon2::Argon2Params;
+use etwin_password::multi::MultiPasswordService;
 use etwin_rest::{create_rest_filter, RouterApi};
+use etwin_services::archive::ArchiveWorker;
+use etwin_services::auth::{AuthEvent, AuthService};
 use etwin_services::dinoparc::DinoparcService;
-use etwin_services::hammerfest::HammerfestService;
+use etwin_services::forum::{ForumEvent, ForumService};
+use etwin_services::hammerfest::{HammerfestEvent, HammerfestService};
+use etwin_services::outbox::{OutboxEvent, OutboxMailer, OutboxService};
+use etwin_services::twinoid_oauth::{TwinoidOauthEvent, TwinoidOauthService};
+use etwin_token_store::mem::MemTokenStore;
+use etwin_token_store::pg::PgTokenStore;
+use etwin_twinoid_client::http::{HttpTwinoidClient, HttpTwinoidOauthClient};
+use etwin_twinoid_store::mem::MemTwinoidStore;
+use etwin_twinoid_store::pg::PgTwinoidStore;
 use etwin_user_store::mem::MemUserStore;
-use std::net::{SocketAddr, SocketAddrV6};
+use etwin_user_store::pg::PgUserStore;
+use std::env;
+use std::io::{self, Write};
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::str::FromStr;
 use std::sync::Arc;
 
+/// Maximum number of game sessions waiting to be archived, further logins skip the archival.
+const ARCHIVE_QUEUE_CAPACITY: usize = 100;
+
 /// Arguments to the `rest` task.
 #[derive(Debug, Clap)]
-pub struct RestArgs {}
+pub struct RestArgs {
+  /// Store implementation: `pg` uses the database from the config, `mem` keeps everything in memory (for demos)
+  #[clap(long, default_value = "pg")]
+  backend: Backend,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+  Mem,
+  Pg,
+}
+
+impl FromStr for Backend {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "mem" => Ok(Self::Mem),
+      "pg" => Ok(Self::Pg),
+      _ => Err(format!("unknown backend {:?}, expected `mem` or `pg`", s)),
+    }
+  }
+}
+
+struct Stores {
+  auth_store: Arc<dyn AuthStore>,
+  dinoparc_store: Arc<dyn DinoparcStore>,
+  forum_store: Arc<dyn ForumStore>,
+  hammerfest_store: Arc<dyn HammerfestStore>,
+  link_store: Arc<dyn LinkStore>,
+  oauth_provider_store: Arc<dyn OauthProviderStore>,
+  outbox_store: Arc<dyn OutboxStore>,
+  token_store: Arc<dyn TokenStore>,
+  twinoid_store: Arc<dyn TwinoidStore>,
+  user_store: Arc<dyn UserStore>,
+}
+
+fn create_mem_stores(
+  clock: Arc<dyn Clock>,
+  password_service: Arc<dyn PasswordService>,
+  uuid_generator: Arc<dyn UuidGenerator>,
+) -> Stores {
+  Stores {
+    auth_store: Arc::new(MemAuthStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator))),
+    dinoparc_store: Arc::new(MemDinoparcStore::new(Arc::clone(&clock))),
+    forum_store: Arc::new(MemForumStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator))),
+    hammerfest_store: Arc::new(MemHammerfestStore::new(Arc::clone(&clock))),
+    link_store: Arc::new(MemLinkStore::new(Arc::clone(&clock))),
+    oauth_provider_store: Arc::new(MemOauthProviderStore::new(
+      Arc::clone(&clock),
+      password_service,
+      Arc::clone(&uuid_generator),
+    )),
+    outbox_store: Arc::new(MemOutboxStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator))),
+    token_store: Arc::new(MemTokenStore::new(Arc::clone(&clock))),
+    twinoid_store: Arc::new(MemTwinoidStore::new(Arc::clone(&clock))),
+    user_store: Arc::new(MemUserStore::new(clock, uuid_generator)),
+  }
+}
+
+async fn create_pg_stores(
+  config: &Config,
+  clock: Arc<dyn Clock>,
+  password_service: Arc<dyn PasswordService>,
+  uuid_generator: Arc<dyn UuidGenerator>,
+) -> Result<Stores, AnyError> {
+  let database = Arc::new(crate::pg::connect(&config.db).await?);
+  let database_secret = Secret::new(config.etwin.secret.clone());
+  Ok(Stores {
+    auth_store: Arc::new(PgAuthStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Arc::clone(&uuid_generator),
+      database_secret.clone(),
+    )),
+    dinoparc_store: Arc::new(
+      PgDinoparcStore::new(Arc::clone(&clock), Arc::clone(&database), Arc::clone(&uuid_generator))
+        .await
+        .map_err(|e| -> AnyError { e.to_string().into() })?,
+    ),
+    forum_store: Arc::new(PgForumStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Arc::clone(&uuid_generator),
+    )),
+    hammerfest_store: Arc::new(
+      PgHammerfestStore::new(
+        Arc::clone(&clock),
+        Arc::clone(&database),
+        database_secret.clone(),
+        Arc::clone(&uuid_generator),
+      )
+      .await
+      .map_err(|e| -> AnyError { e.to_string().into() })?,
+    ),
+    link_store: Arc::new(PgLinkStore::new(Arc::clone(&clock), Arc::clone(&database))),
+    oauth_provider_store: Arc::new(PgOauthProviderStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      password_service,
+      Arc::clone(&uuid_generator),
+      database_secret.clone(),
+    )),
+    outbox_store: Arc::new(PgOutboxStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      database_secret.clone(),
+      Arc::clone(&uuid_generator),
+    )),
+    token_store: Arc::new(
+      PgTokenStore::new(Arc::clone(&clock), Arc::clone(&database), database_secret.clone())
+        .await
+        .map_err(|e| -> AnyError { e.to_string().into() })?,
+    ),
+    twinoid_store: Arc::new(PgTwinoidStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Arc::clone(&uuid_generator),
+    )),
+    user_store: Arc::new(PgUserStore::new(clock, database, database_secret, uuid_generator)),
+  })
+}
+
+/// Mailer from the config, or a mailer discarding emails if no mailer is configured.
+fn create_mailer(
+  config: &Config,
+  clock: Arc<dyn Clock>,
+  uuid_generator: Arc<dyn UuidGenerator>,
+) -> Result<Arc<dyn Mailer>, AnyError> {
+  match config.mailer.as_ref() {
+    None => Ok(Arc::new(NoopMailer)),
+    Some(MailerConfig::Smtp(mailer_config)) => {
+      let mut builder = SmtpMailerBuilder::new(
+        mailer_config.host.clone(),
+        mailer_config.username.clone(),
+        mailer_config.password.clone(),
+        mailer_config.sender.clone(),
+      );
+      for header in mailer_config.headers.iter().flatten() {
+        builder.header(create_header(header)?);
+      }
+      Ok(Arc::new(builder.build()))
+    }
+    Some(MailerConfig::File(mailer_config)) => {
+      let format = match mailer_config.format {
+        None | Some(FileMailerFormatConfig::Eml) => FileMailerFormat::Eml,
+        Some(FileMailerFormatConfig::Maildir) => FileMailerFormat::Maildir,
+      };
+      let mut mailer = FileMailer::new(
+        clock,
+        uuid_generator,
+        mailer_config.directory.clone(),
+        format,
+        mailer_config.sender.parse()?,
+      );
+      for header in mailer_config.headers.iter().flatten() {
+        mailer = mailer.with_header(create_header(header)?);
+      }
+      Ok(Arc::new(mailer))
+    }
+  }
+}
+
+fn create_header(header: &MailerHeader) -> Result<RawHeader, AnyError> {
+  let name = HeaderName::new_from_ascii(header.name.clone())?;
+  Ok(RawHeader::new(name, header.value.clone()))
+}
+
+/// Logger writing the events of the services as JSON lines
+type EventLogger = JsonLogger<Arc<dyn Clock>, Box<dyn Write + Send>>;
+
+/// Event log from the config, shared by the services, or `None` if no log is configured.
+fn create_event_logger(config: &Config, clock: Arc<dyn Clock>) -> Result<Option<Arc<EventLogger>>, AnyError> {
+  let writer: Box<dyn Write + Send> = match config.log.as_ref() {
+    None => return Ok(None),
+    Some(LogConfig::Stdout) => Box::new(io::stdout()),
+    Some(LogConfig::File(log_config)) => {
+      let mut file = RotatingFile::open(log_config.path.clone(), Arc::clone(&clock))?;
+      if let Some(max_size) = log_config.max_size {
+        file = file.with_max_size(max_size);
+      }
+      if let Some(max_age) = log_config.max_age {
+        file = file.with_max_age(chrono::Duration::hours(i64::from(max_age)));
+      }
+      Box::new(file)
+    }
+  };
+  Ok(Some(Arc::new(JsonLogger::new(clock, writer))))
+}
+
+/// Logger of a service: the shared event log, or a logger discarding the events if no log is configured.
+fn service_logger<T>(event_logger: &Option<Arc<EventLogger>>) -> Arc<dyn Logger<T>>
+where
+  EventLogger: Logger<T>,
+{
+  match event_logger {
+    Some(event_logger) => Arc::clone(event_logger) as Arc<dyn Logger<T>>,
+    None => Arc::new(NoopLogger),
+  }
+}
+
+fn create_password_service(config: &Config) -> Result<Arc<dyn PasswordService>, AnyError> {
+  let params = match config.password.as_ref() {
+    Some(password_config) => Argon2Params::new(
+      password_config.argon2_memory_cost,
+      password_config.argon2_time_cost,
+      password_config.argon2_parallelism,
+    )
+    .map_err(|e| -> AnyError { e.to_string().into() })?,
+    None => Argon2Params::recommended(),
+  };
+  Ok(Arc::new(MultiPasswordService::with_os_rng(params)))
+}
+
+async fn create_api(config: &Config, backend: Backend) -> Result<RouterApi, AnyError> {
+  let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+  let uuid_generator: Arc<dyn UuidGenerator> = Arc::new(Uuid4Generator);
+  let dinoparc_client: Arc<dyn DinoparcClient> = Arc::new(HttpDinoparcClient::new(Arc::clone(&clock), NoopLogger)?);
+  let hammerfest_client: Arc<dyn HammerfestClient> = Arc::new(HttpHammerfestClient::new(Arc::clone(&clock))?);
+  let twinoid_client: Arc<dyn TwinoidClient> = Arc::new(HttpTwinoidClient::new(Arc::clone(&clock))?);
+  let email_formatter: Arc<dyn EmailFormatter> = Arc::new(HtmlEmailFormatter::new(config.etwin.external_uri.clone()));
+  let mailer = create_mailer(config, Arc::clone(&clock), Arc::clone(&uuid_generator))?;
+  let password_service = create_password_service(config)?;
+  let event_logger = create_event_logger(config, Arc::clone(&clock))?;
+  let stores = match backend {
+    Backend::Mem => create_mem_stores(
+      Arc::clone(&clock),
+      Arc::clone(&password_service),
+      Arc::clone(&uuid_generator),
+    ),
+    Backend::Pg => {
+      create_pg_stores(
+        config,
+        Arc::clone(&clock),
+        Arc::clone(&password_service),
+        Arc::clone(&uuid_generator),
+      )
+      .await?
+    }
+  };
+
+  let forum = Arc::new(ForumService::new(
+    Arc::clone(&clock),
+    stores.forum_store,
+    service_logger::<ForumEvent>(&event_logger),
+    Arc::clone(&stores.user_store),
+  ));
+
+  // Services queue their emails in the outbox, the outbox service delivers them in the background
+  let outbox = OutboxService::new(
+    Arc::clone(&clock),
+    service_logger::<OutboxEvent>(&event_logger),
+    mailer,
+    Arc::clone(&stores.outbox_store),
+  );
+  tokio::spawn(async move { outbox.run(std::time::Duration::from_secs(10)).await });
+
+  // Keep the stored Twinoid access tokens usable by refreshing them before they expire
+  if let Some(auth_config) = config.auth.as_ref() {
+    let twinoid_oauth_client = HttpTwinoidOauthClient::new(
+      auth_config.twinoid.client_id.clone(),
+      Secret::new(auth_config.twinoid.secret.clone()),
+      config.etwin.external_uri.join("oauth/callback")?,
+    )?;
+    let twinoid_oauth = TwinoidOauthService::new(
+      Arc::clone(&clock),
+      service_logger::<TwinoidOauthEvent>(&event_logger),
+      Arc::clone(&stores.token_store),
+      Arc::new(twinoid_oauth_client) as Arc<dyn TwinoidOauthClient>,
+    );
+    tokio::spawn(async move { twinoid_oauth.run(std::time::Duration::from_secs(60)).await });
+  }
 
-fn create_api() -> RouterApi {
-  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
-  let hammerfest_client: Arc<dyn HammerfestClient> = Arc::new(HttpHammerfestClient::new(Arc::clone(&clock)).unwrap());
-  let hammerfest_store: Arc<dyn HammerfestStore> = Arc::new(MemHammerfestStore::new(Arc::clone(&clock)));
-  let dinoparc_store: Arc<dyn DinoparcStore> = Arc::new(MemDinoparcStore::new(Arc::clone(&clock)));
-  let link_store: Arc<dyn LinkStore> = Arc::new(MemLinkStore::new(Arc::clone(&clock)));
-  let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Uuid4Generator));
+  let auth_logger: Arc<dyn Logger<AuthEvent>> = service_logger(&event_logger);
+  let auth = AuthService::new(
+    stores.auth_store,
+    clock,
+    Arc::clone(&dinoparc_client),
+    Arc::clone(&stores.dinoparc_store),
+    email_formatter,
+    Arc::clone(&hammerfest_client),
+    Arc::clone(&stores.hammerfest_store),
+    Arc::clone(&stores.link_store),
+    Arc::clone(&auth_logger),
+    Arc::new(OutboxMailer::new(stores.outbox_store)) as Arc<dyn Mailer>,
+    stores.oauth_provider_store,
+    password_service,
+    stores.token_store,
+    Arc::clone(&stores.user_store),
+    twinoid_client,
+    stores.twinoid_store,
+    uuid_generator,
+    config.etwin.secret.as_bytes().to_vec(),
+  );
+  let archive_on_login = config.archive.as_ref().map(|a| a.on_login).unwrap_or(false);
+  let auth = if archive_on_login {
+    // Logins push the game sessions to the queue, the worker archives them in the background
+    let (archive_queue, archive_worker) = ArchiveWorker::new(
+      ARCHIVE_QUEUE_CAPACITY,
+      Arc::clone(&dinoparc_client),
+      Arc::clone(&stores.dinoparc_store),
+      Arc::clone(&hammerfest_client),
+      Arc::clone(&stores.hammerfest_store),
+      auth_logger,
+    );
+    tokio::spawn(archive_worker.run());
+    auth.with_archive_queue(archive_queue)
+  } else {
+    auth
+  };
+  let auth = Arc::new(auth);
 
   let dinoparc = Arc::new(DinoparcService::new(
-    dinoparc_store,
-    Arc::clone(&link_store),
-    Arc::clone(&user_store),
+    stores.dinoparc_store,
+    Arc::clone(&stores.link_store),
+    Arc::clone(&stores.user_store),
   ));
 
   let hammerfest = Arc::new(HammerfestService::new(
     hammerfest_client,
-    hammerfest_store,
-    Arc::clone(&link_store),
-    Arc::clone(&user_store),
+    stores.hammerfest_store,
+    Arc::clone(&stores.link_store),
+    service_logger::<HammerfestEvent>(&event_logger),
+    Arc::clone(&stores.user_store),
   ));
 
-  RouterApi { dinoparc, hammerfest }
+  Ok(RouterApi {
+    auth,
+    dinoparc,
+    forum,
+    hammerfest,
+    user_store: stores.user_store,
+  })
 }
 
-pub async fn run(_args: &RestArgs) -> Result<(), AnyError> {
-  let api = create_api();
+pub async fn run(args: &RestArgs) -> Result<(), AnyError> {
+  let config: Config = etwin_config::find_config(env::current_dir()?)?;
+  let api = create_api(&config, args.backend).await?;
   let routes = create_rest_filter(api);
 
-  eprintln!("Started at http://localhost:3030");
+  let port = config.etwin.http_port;
+  eprintln!("Started at http://localhost:{} ({:?} backend)", port, args.backend);
 
   warp::serve(routes)
-    .run(SocketAddr::V6(SocketAddrV6::new(1.into(), 3030, 0, 0)))
+    .run(SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, port, 0, 0)))
     .await;
 
   Ok(())
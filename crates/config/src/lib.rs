@@ -1,5 +1,6 @@
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -10,10 +11,16 @@ pub struct Config {
   pub etwin: EtwinConfig,
   pub db: DbConfig,
   pub mailer: Option<MailerConfig>,
+  pub password: Option<PasswordConfig>,
+  pub archive: Option<ArchiveConfig>,
+  pub auth: Option<AuthConfig>,
+  pub log: Option<LogConfig>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 pub struct EtwinConfig {
+  /// Main secret key, used to derive the secrets protecting tokens and encrypted database columns
+  pub secret: String,
   pub http_port: u16,
   pub external_uri: Url,
 }
@@ -29,8 +36,18 @@ pub struct DbConfig {
   pub password: String,
 }
 
+/// Mailer used to deliver the emails, selected by the `type` field.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
-pub struct MailerConfig {
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MailerConfig {
+  /// Send the emails through an SMTP relay
+  Smtp(SmtpMailerConfig),
+  /// Write the emails to local files
+  File(FileMailerConfig),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct SmtpMailerConfig {
   pub host: String,
   pub username: String,
   pub password: String,
@@ -44,6 +61,76 @@ pub struct MailerHeader {
   pub value: String,
 }
 
+/// Mailer writing the emails to local files, for development.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct FileMailerConfig {
+  /// Directory receiving the emails, created if missing (relative to the working directory)
+  pub directory: PathBuf,
+  /// Layout of the directory, `eml` by default
+  pub format: Option<FileMailerFormat>,
+  pub sender: String,
+  pub headers: Option<Vec<MailerHeader>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMailerFormat {
+  /// One `.eml` file per email
+  Eml,
+  /// Maildir layout (`tmp`, `new`, `cur`)
+  Maildir,
+}
+
+/// Argon2id cost parameters used to hash new passwords.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct PasswordConfig {
+  /// Memory cost, in KiB
+  pub argon2_memory_cost: u32,
+  /// Number of passes
+  pub argon2_time_cost: u32,
+  /// Degree of parallelism
+  pub argon2_parallelism: u32,
+}
+
+/// Archival of the game data reachable through the sessions of logged-in users.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct ArchiveConfig {
+  /// Archive the Dinoparc and Hammerfest data in the background when a user logs in with game credentials
+  pub on_login: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct AuthConfig {
+  pub twinoid: TwinoidAuthConfig,
+}
+
+/// Credentials of the Eternaltwin application on Twinoid.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct TwinoidAuthConfig {
+  pub client_id: String,
+  pub secret: String,
+}
+
+/// Log receiving the events of the services, as JSON lines.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LogConfig {
+  /// Write the events to the standard output
+  Stdout,
+  /// Write the events to a file, rotated when it gets too large or too old
+  File(FileLogConfig),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct FileLogConfig {
+  /// Log file, created if missing (relative to the working directory)
+  pub path: PathBuf,
+  /// Size limit of the file, in bytes
+  pub max_size: Option<u64>,
+  /// Age limit of the file, in hours
+  pub max_age: Option<u32>,
+}
+
 #[derive(Debug)]
 pub enum FindConfigFileError {
   NotFound(PathBuf),
@@ -57,6 +144,31 @@ pub enum FindConfigError {
   Other(PathBuf, io::Error),
 }
 
+impl fmt::Display for FindConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotFound(dir) => write!(f, "config file `etwin.toml` not found from {}", dir.display()),
+      Self::ParseError(e) => write!(f, "invalid config file `etwin.toml`: {}", e),
+      Self::Other(dir, e) => write!(
+        f,
+        "failed to read config file `etwin.toml` from {}: {}",
+        dir.display(),
+        e
+      ),
+    }
+  }
+}
+
+impl std::error::Error for FindConfigError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::NotFound(_) => None,
+      Self::ParseError(e) => Some(e),
+      Self::Other(_, e) => Some(e),
+    }
+  }
+}
+
 fn find_config_file(dir: PathBuf) -> Result<(PathBuf, String), FindConfigFileError> {
   for d in dir.ancestors() {
     let config_path = d.join("etwin.toml");
@@ -87,6 +199,7 @@ pub fn find_config(dir: PathBuf) -> Result<Config, FindConfigError> {
 
 pub static DEFAULT: Lazy<Config> = Lazy::new(|| Config {
   etwin: EtwinConfig {
+    secret: "dev_secret".to_string(),
     http_port: 50320,
     external_uri: Url::parse("http://localhost:50320/").unwrap(),
   },
@@ -100,16 +213,25 @@ pub static DEFAULT: Lazy<Config> = Lazy::new(|| Config {
     password: "dev".to_string(),
   },
   mailer: None,
+  password: None,
+  archive: None,
+  auth: None,
+  log: None,
 });
 
 #[cfg(test)]
 mod test {
-  use crate::{parse_config, DEFAULT};
+  use crate::{
+    parse_config, ArchiveConfig, AuthConfig, FileLogConfig, FileMailerConfig, FileMailerFormat, LogConfig,
+    MailerConfig, MailerHeader, PasswordConfig, SmtpMailerConfig, TwinoidAuthConfig, DEFAULT,
+  };
+  use std::path::PathBuf;
 
   #[test]
   fn test_default_config() {
     const INPUT: &str = r#"
 [etwin]
+secret = "dev_secret"
 http_port = 50320
 external_uri = "http://localhost:50320"
 
@@ -127,4 +249,198 @@ password = "dev"
     let expected = Ok(DEFAULT.clone());
     assert_eq!(actual, expected);
   }
+
+  #[test]
+  fn test_password_config() {
+    const INPUT: &str = r#"
+[etwin]
+secret = "dev_secret"
+http_port = 50320
+external_uri = "http://localhost:50320"
+
+[db]
+host = "localhost"
+port = 5432
+name = "etwin.dev"
+admin_user = "etwin.dev.admin"
+admin_password = "dev"
+user = "etwin.dev.admin"
+password = "dev"
+
+[password]
+argon2_memory_cost = 19456
+argon2_time_cost = 2
+argon2_parallelism = 1
+    "#;
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, INPUT).unwrap().password;
+    let expected = Some(PasswordConfig {
+      argon2_memory_cost: 19456,
+      argon2_time_cost: 2,
+      argon2_parallelism: 1,
+    });
+    assert_eq!(actual, expected);
+  }
+
+  const BASE: &str = r#"
+[etwin]
+secret = "dev_secret"
+http_port = 50320
+external_uri = "http://localhost:50320"
+
+[db]
+host = "localhost"
+port = 5432
+name = "etwin.dev"
+admin_user = "etwin.dev.admin"
+admin_password = "dev"
+user = "etwin.dev.admin"
+password = "dev"
+"#;
+
+  #[test]
+  fn test_smtp_mailer_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[mailer]
+type = "smtp"
+host = "smtp.example.com"
+username = "etwin"
+password = "dev"
+sender = "Eternaltwin <noreply@example.com>"
+headers = [{ name = "X-PM-Message-Stream", value = "outbound" }]
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().mailer;
+    let expected = Some(MailerConfig::Smtp(SmtpMailerConfig {
+      host: "smtp.example.com".to_string(),
+      username: "etwin".to_string(),
+      password: "dev".to_string(),
+      sender: "Eternaltwin <noreply@example.com>".to_string(),
+      headers: Some(vec![MailerHeader {
+        name: "X-PM-Message-Stream".to_string(),
+        value: "outbound".to_string(),
+      }]),
+    }));
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_file_mailer_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[mailer]
+type = "file"
+directory = "./mail"
+format = "maildir"
+sender = "Eternaltwin <noreply@localhost>"
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().mailer;
+    let expected = Some(MailerConfig::File(FileMailerConfig {
+      directory: PathBuf::from("./mail"),
+      format: Some(FileMailerFormat::Maildir),
+      sender: "Eternaltwin <noreply@localhost>".to_string(),
+      headers: None,
+    }));
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_mailer_config_requires_type() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[mailer]
+directory = "./mail"
+sender = "Eternaltwin <noreply@localhost>"
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    assert!(parse_config(&path, &input).is_err());
+  }
+
+  #[test]
+  fn test_archive_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[archive]
+on_login = true
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().archive;
+    let expected = Some(ArchiveConfig { on_login: true });
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_auth_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[auth.twinoid]
+client_id = "380"
+secret = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().auth;
+    let expected = Some(AuthConfig {
+      twinoid: TwinoidAuthConfig {
+        client_id: "380".to_string(),
+        secret: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+      },
+    });
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_stdout_log_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[log]
+type = "stdout"
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().log;
+    let expected = Some(LogConfig::Stdout);
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_file_log_config() {
+    let input = format!(
+      "{}{}",
+      BASE,
+      r#"
+[log]
+type = "file"
+path = "./log/events.jsonl"
+max_size = 10485760
+max_age = 24
+"#
+    );
+    let path = std::env::current_dir().unwrap().join("etwin.toml");
+    let actual = parse_config(&path, &input).unwrap().log;
+    let expected = Some(LogConfig::File(FileLogConfig {
+      path: PathBuf::from("./log/events.jsonl"),
+      max_size: Some(10485760),
+      max_age: Some(24),
+    }));
+    assert_eq!(actual, expected);
+  }
 }
@@ -1,6 +1,7 @@
 use crate::core::{Instant, LocaleId};
 use crate::email::EmailAddress;
-use crate::oauth::{OauthClientId, OauthClientKey, ShortOauthClient};
+use crate::mfa::MfaProof;
+use crate::oauth::{OauthClientId, OauthClientIdRef, OauthClientKey, ShortOauthClient};
 use crate::password::Password;
 use crate::types::AnyError;
 use crate::user::{ShortUser, UserDisplayName, UserDisplayNameVersions, UserId, UserIdRef, Username};
@@ -8,7 +9,10 @@ use async_trait::async_trait;
 use auto_impl::auto_impl;
 #[cfg(feature = "_serde")]
 use etwin_serde_tools::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
 use std::str::FromStr;
+use thiserror::Error;
 use uuid::Uuid;
 
 // TODO: Deserialization is _very_ weak here and relies on the order of the fields...
@@ -66,6 +70,12 @@ declare_new_uuid! {
   const SQL_NAME = "etwin_oauth_access_token_key";
 }
 
+declare_new_uuid! {
+  pub struct MfaLoginChallengeId(Uuid);
+  pub type ParseError = MfaLoginChallengeIdParseError;
+  const SQL_NAME = "mfa_login_challenge_id";
+}
+
 declare_new_enum!(
   pub enum AuthScope {
     #[str("Default")]
@@ -88,6 +98,14 @@ pub struct CreateSessionOptions {
   pub user: UserIdRef,
 }
 
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RevokeUserSessionsOptions {
+  pub user: UserIdRef,
+  /// Session kept open, usually the one of the request revoking the others
+  pub except: Option<SessionId>,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RawSession {
@@ -128,6 +146,32 @@ pub struct UserAndSession {
   pub session: Session,
 }
 
+/// Result of the first login step, using the password of the user.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "_serde", serde(tag = "type"))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoginResult {
+  /// The user does not use a second factor: the session is created.
+  Session(UserAndSession),
+  /// The user must provide a second factor before the session is created.
+  PendingMfa(PendingMfaLogin),
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PendingMfaLogin {
+  /// Short-lived token proving that the first factor was validated.
+  pub token: String,
+  pub expires_at: Instant,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompleteMfaLoginOptions {
+  pub token: String,
+  pub proof: MfaProof,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RawUserCredentials {
@@ -218,6 +262,100 @@ impl FromStr for Login {
   }
 }
 
+declare_new_enum!(
+  pub enum LoginFailureReason {
+    #[str("UnknownLogin")]
+    /// No user or OAuth client matches the login.
+    UnknownLogin,
+    #[str("NoPassword")]
+    /// The user exists but has no password.
+    NoPassword,
+    #[str("WrongPassword")]
+    WrongPassword,
+    #[str("WrongSecondFactor")]
+    /// The password was valid but the TOTP or recovery code was not.
+    WrongSecondFactor,
+  }
+  pub type ParseError = LoginFailureReasonParseError;
+  const SQL_NAME = "login_failure_reason";
+);
+
+/// Subject of a login throttle: failed attempts are counted separately for each key.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoginThrottleKey {
+  User(UserIdRef),
+  OauthClient(OauthClientIdRef),
+  Ip(IpAddr),
+}
+
+impl fmt::Display for LoginThrottleKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::User(user) => write!(f, "user:{}", user.id),
+      Self::OauthClient(client) => write!(f, "oauth_client:{}", client.id),
+      Self::Ip(ip) => write!(f, "ip:{}", ip),
+    }
+  }
+}
+
+/// Audit record for a failed password check.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CreateLoginFailureOptions {
+  pub user: Option<UserIdRef>,
+  pub oauth_client: Option<OauthClientIdRef>,
+  pub ip: Option<IpAddr>,
+  pub reason: LoginFailureReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CountLoginFailuresOptions {
+  pub key: LoginThrottleKey,
+  /// Start of the sliding window (inclusive)
+  pub since: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CreateLoginLockoutOptions {
+  pub key: LoginThrottleKey,
+  pub until: Instant,
+}
+
+/// Error returned when the credentials do not match: unknown login, missing password or wrong password or secret.
+///
+/// The reason is only meant for logs: it must not be exposed to the client.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Error)]
+#[error("{reason}")]
+pub struct InvalidCredentialsError {
+  pub reason: &'static str,
+}
+
+/// Error returned when a login is rejected because of too many failed attempts.
+///
+/// It is returned before the password is checked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Error)]
+#[error("too many failed login attempts, retry after {retry_after}")]
+pub struct LoginThrottledError {
+  pub retry_after: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CreateMfaLoginChallengeOptions {
+  pub user: UserIdRef,
+  pub expires_at: Instant,
+}
+
+/// Second login step waiting for a proof.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MfaLoginChallenge {
+  pub id: MfaLoginChallengeId,
+  pub user: UserIdRef,
+  pub ctime: Instant,
+  pub expires_at: Instant,
+  /// Number of proofs submitted for this challenge, including the current one
+  pub attempts: u32,
+}
+
 #[async_trait]
 #[auto_impl(&, Arc)]
 pub trait AuthStore: Send + Sync {
@@ -229,6 +367,35 @@ pub trait AuthStore: Send + Sync {
   async fn create_session(&self, options: &CreateSessionOptions) -> Result<RawSession, AnyError>;
 
   async fn get_and_touch_session(&self, session: SessionId) -> Result<Option<RawSession>, AnyError>;
+
+  /// Deletes all the sessions of a user, except the one provided.
+  async fn revoke_user_sessions(&self, options: &RevokeUserSessionsOptions) -> Result<(), AnyError>;
+
+  async fn create_login_failure(&self, options: &CreateLoginFailureOptions) -> Result<(), AnyError>;
+
+  async fn count_login_failures(&self, options: &CountLoginFailuresOptions) -> Result<u32, AnyError>;
+
+  /// Creates or extends a lockout: the end of an existing lockout is never moved backwards.
+  async fn create_login_lockout(&self, options: &CreateLoginLockoutOptions) -> Result<(), AnyError>;
+
+  /// Returns the end of the active lockout for this key, if any.
+  async fn get_login_lockout(&self, key: LoginThrottleKey) -> Result<Option<Instant>, AnyError>;
+
+  async fn create_mfa_login_challenge(
+    &self,
+    options: &CreateMfaLoginChallengeOptions,
+  ) -> Result<MfaLoginChallenge, AnyError>;
+
+  /// Counts a proof attempt against a pending challenge.
+  ///
+  /// Returns `None` if the challenge does not exist, expired or was already consumed.
+  async fn touch_mfa_login_challenge(
+    &self,
+    challenge: MfaLoginChallengeId,
+  ) -> Result<Option<MfaLoginChallenge>, AnyError>;
+
+  /// Marks a challenge as consumed; returns `false` if it was already consumed.
+  async fn consume_mfa_login_challenge(&self, challenge: MfaLoginChallengeId) -> Result<bool, AnyError>;
 }
 
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
@@ -276,6 +443,21 @@ pub struct RegisterWithUsernameOptions {
   pub password: Password,
 }
 
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestEmailChangeOptions {
+  /// New email address for the current user (may be potentially invalid).
+  pub email: EmailAddress,
+  /// Preferred locale for the verification email.
+  pub locale: Option<LocaleId>,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeEmailWithTokenOptions {
+  pub email_token: String,
+}
+
 #[cfg(test)]
 mod test {
   use crate::auth::{AuthContext, AuthScope, GuestAuthContext, UserAuthContext};
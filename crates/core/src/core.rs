@@ -141,6 +141,14 @@ impl std::ops::Add<chrono::Duration> for Instant {
   }
 }
 
+impl std::ops::Sub<chrono::Duration> for Instant {
+  type Output = Instant;
+
+  fn sub(self, rhs: Duration) -> Self::Output {
+    Self::new_round_down(self.into_chrono() - rhs)
+  }
+}
+
 /// Private type used to serialize PeriodLower and its variants.
 #[cfg(feature = "_serde")]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -24,6 +24,17 @@ pub struct GetDinoparcUserOptions {
   pub time: Option<Instant>,
 }
 
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GetStaleDinoparcUsersOptions {
+  pub server: DinoparcServer,
+  /// Users whose latest collection was retrieved strictly before this time are stale
+  pub retrieved_before: Instant,
+  /// Stale users whose latest refresh attempt is at or after this time are skipped
+  pub attempted_before: Instant,
+  pub limit: u32,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GetDinoparcDinozOptions {
@@ -746,6 +757,15 @@ pub trait DinoparcStore: Send + Sync {
   async fn get_dinoz(&self, options: &GetDinoparcDinozOptions) -> Result<Option<ArchivedDinoparcDinoz>, AnyError>;
 
   async fn get_user(&self, options: &GetDinoparcUserOptions) -> Result<Option<ArchivedDinoparcUser>, AnyError>;
+
+  /// Get the users of a server whose collection is stale.
+  ///
+  /// Users whose collection was never retrieved use their archival time.
+  /// Users never attempted come first, then the least recently attempted ones.
+  async fn get_stale_users(&self, options: &GetStaleDinoparcUsersOptions) -> Result<Vec<ShortDinoparcUser>, AnyError>;
+
+  /// Record an attempt to refresh the archived data of a user, successful or not.
+  async fn touch_refresh_attempt(&self, user: DinoparcUserIdRef) -> Result<(), AnyError>;
 }
 
 #[derive(Debug, Error)]
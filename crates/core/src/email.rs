@@ -1,6 +1,6 @@
-use crate::core::{HtmlFragment, LocaleId};
+use crate::core::{HtmlFragment, Instant, LocaleId};
 #[cfg(feature = "sqlx")]
-use crate::core::{Instant, Secret};
+use crate::core::Secret;
 use crate::types::AnyError;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
@@ -79,6 +79,20 @@ pub struct VerifyRegistrationEmail {
   pub token: String,
 }
 
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VerifyEmailChangeEmail {
+  // TODO: Use `new_string` wrapper
+  pub token: String,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NotifyEmailChangeEmail {
+  /// Time when the new email address was confirmed.
+  pub changed_at: Instant,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EmailContent {
@@ -95,6 +109,20 @@ pub trait EmailFormatter: Send + Sync {
     locale: LocaleId,
     data: &VerifyRegistrationEmail,
   ) -> Result<EmailContent, AnyError>;
+
+  /// Email sent to the new address when a user requests to change their email address.
+  async fn verify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &VerifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError>;
+
+  /// Email sent to the old address once the new address was confirmed.
+  async fn notify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &NotifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError>;
 }
 
 #[async_trait]
@@ -102,3 +130,79 @@ pub trait EmailFormatter: Send + Sync {
 pub trait Mailer: Send + Sync {
   async fn send_email(&self, recipient: &EmailAddress, content: &EmailContent) -> Result<(), AnyError>;
 }
+
+declare_new_uuid! {
+  pub struct OutboundEmailId(Uuid);
+  pub type ParseError = OutboundEmailIdParseError;
+  const SQL_NAME = "outbound_email_id";
+}
+
+declare_new_enum!(
+  pub enum OutboundEmailStatus {
+    #[str("Pending")]
+    /// Waiting for its next delivery attempt.
+    Pending,
+    #[str("Sent")]
+    Sent,
+    #[str("Failed")]
+    /// Delivery failed permanently, the email is no longer retried.
+    Failed,
+  }
+  pub type ParseError = OutboundEmailStatusParseError;
+  const SQL_NAME = "outbound_email_status";
+);
+
+/// Email stored in the outbox until it is delivered.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OutboundEmail {
+  pub id: OutboundEmailId,
+  pub recipient: EmailAddress,
+  pub content: EmailContent,
+  pub ctime: Instant,
+  pub status: OutboundEmailStatus,
+  /// Number of failed delivery attempts
+  pub attempts: u32,
+  /// Earliest time of the next delivery attempt, only meaningful for pending emails.
+  pub next_attempt_at: Instant,
+  pub sent_at: Option<Instant>,
+  /// Error of the latest failed attempt
+  pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QueueEmailOptions {
+  pub recipient: EmailAddress,
+  pub content: EmailContent,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClaimDueEmailsOptions {
+  pub limit: u32,
+  /// Claimed emails are not returned again before this time, so concurrent workers don't send them twice.
+  pub lease_until: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordEmailFailureOptions {
+  pub id: OutboundEmailId,
+  pub error: String,
+  /// Time of the next attempt, or `None` if the failure is permanent.
+  pub retry_at: Option<Instant>,
+}
+
+/// Durable queue of emails waiting for delivery
+#[async_trait]
+#[auto_impl(&, Arc)]
+pub trait OutboxStore: Send + Sync {
+  async fn queue_email(&self, options: &QueueEmailOptions) -> Result<OutboundEmail, AnyError>;
+
+  /// Claim the pending emails whose next attempt is due, oldest first.
+  async fn claim_due_emails(&self, options: &ClaimDueEmailsOptions) -> Result<Vec<OutboundEmail>, AnyError>;
+
+  async fn get_email(&self, id: OutboundEmailId) -> Result<Option<OutboundEmail>, AnyError>;
+
+  async fn record_email_sent(&self, id: OutboundEmailId) -> Result<(), AnyError>;
+
+  async fn record_email_failure(&self, options: &RecordEmailFailureOptions) -> Result<(), AnyError>;
+}
@@ -764,7 +764,8 @@ pub trait ForumStore: Send + Sync {
 
   async fn create_post(&self, options: &RawCreatePostOptions) -> Result<RawCreateForumPostResult, AnyError>;
 
-  async fn get_role_grants(&self, options: &RawGetRoleGrantsOptions) -> Result<Vec<ForumRoleGrant>, AnyError>;
+  /// Role grants of a section, oldest first.
+  async fn get_role_grants(&self, options: &RawGetRoleGrantsOptions) -> Result<Vec<RawForumRoleGrant>, AnyError>;
 
   async fn upsert_system_section(
     &self,
@@ -150,6 +150,25 @@ pub struct HammerfestUser {
   pub profile: Option<StoredHammerfestProfile>,
   pub items: Option<StoredHammerfestItems>,
   pub etwin: VersionedEtwinLink,
+  /// Private data, only provided to the Eternaltwin user linked to this account
+  #[cfg_attr(feature = "_serde", serde(default, skip_serializing_if = "Option::is_none"))]
+  pub private: Option<HammerfestUserPrivateData>,
+}
+
+/// Archived data only visible to the owner of a Hammerfest account.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HammerfestUserPrivateData {
+  /// Latest archived email address
+  ///
+  /// `None`: Email never archived
+  /// `Some(None)`: No email
+  /// `Some(Some(_))`: Email address
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  #[cfg_attr(feature = "_serde", serde(default, deserialize_with = "deserialize_nested_option"))]
+  pub email: Option<Option<EmailAddress>>,
+  /// Latest archived token count, `None` if never archived
+  pub tokens: Option<u32>,
 }
 
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
@@ -645,6 +664,17 @@ pub struct GetHammerfestUserOptions {
   pub time: Option<Instant>,
 }
 
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GetStaleHammerfestUsersOptions {
+  pub server: HammerfestServer,
+  /// Users whose latest profile was retrieved strictly before this time are stale
+  pub retrieved_before: Instant,
+  /// Stale users whose latest refresh attempt is at or after this time are skipped
+  pub attempted_before: Instant,
+  pub limit: u32,
+}
+
 #[async_trait]
 #[auto_impl(&, Arc)]
 pub trait HammerfestClient: Send + Sync {
@@ -700,6 +730,21 @@ pub trait HammerfestStore: Send + Sync {
 
   async fn touch_short_user(&self, options: &ShortHammerfestUser) -> Result<StoredHammerfestUser, AnyError>;
 
+  /// Get the latest archived private data of a user.
+  async fn get_private_data(&self, user: HammerfestUserIdRef) -> Result<HammerfestUserPrivateData, AnyError>;
+
+  /// Get the users of a server whose profile is stale.
+  ///
+  /// Users whose profile was never retrieved use their archival time.
+  /// Users never attempted come first, then the least recently attempted ones.
+  async fn get_stale_users(
+    &self,
+    options: &GetStaleHammerfestUsersOptions,
+  ) -> Result<Vec<ShortHammerfestUser>, AnyError>;
+
+  /// Record an attempt to refresh the archived profile of a user, successful or not.
+  async fn touch_refresh_attempt(&self, user: HammerfestUserIdRef) -> Result<(), AnyError>;
+
   async fn touch_shop(&self, response: &HammerfestShopResponse) -> Result<(), AnyError>;
 
   async fn touch_profile(&self, response: &HammerfestProfileResponse) -> Result<(), AnyError>;
@@ -849,6 +894,7 @@ mod test {
       profile: None,
       items: None,
       etwin: VersionedEtwinLink::default(),
+      private: None,
     }
   }
 
@@ -15,6 +15,7 @@ pub mod email;
 pub mod forum;
 pub mod hammerfest;
 pub mod link;
+pub mod mfa;
 pub mod oauth;
 pub mod password;
 pub mod popotamo;
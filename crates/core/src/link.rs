@@ -1,9 +1,10 @@
 use crate::core::{Instant, RawUserDot, UserDot};
-use crate::dinoparc::DinoparcUserIdRef;
-use crate::hammerfest::HammerfestUserIdRef;
+use crate::dinoparc::{DinoparcCredentials, DinoparcUserIdRef};
+use crate::hammerfest::{HammerfestCredentials, HammerfestUserIdRef};
+use crate::oauth::RfcOauthAccessTokenKey;
 use crate::twinoid::TwinoidUserIdRef;
 use crate::types::AnyError;
-use crate::user::{ShortUser, UserIdRef};
+use crate::user::{ShortUser, UserId, UserIdRef};
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 #[cfg(feature = "_serde")]
@@ -164,6 +165,38 @@ pub struct GetLinksFromEtwinOptions {
   pub time: Option<Instant>,
 }
 
+/// Link a Dinoparc account to an existing user, proving ownership with its credentials.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LinkToDinoparcOptions {
+  pub user_id: UserId,
+  pub credentials: DinoparcCredentials,
+}
+
+/// Link a Hammerfest account to an existing user, proving ownership with its credentials.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LinkToHammerfestOptions {
+  pub user_id: UserId,
+  pub credentials: HammerfestCredentials,
+}
+
+/// Link a Twinoid account to an existing user, proving ownership with an OAuth access token.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LinkToTwinoidOauthOptions {
+  pub user_id: UserId,
+  pub access_token: RfcOauthAccessTokenKey,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnlinkOptions<T: RemoteUserIdRef> {
+  pub user_id: UserId,
+  #[cfg_attr(feature = "_serde", serde(bound(deserialize = "T: RemoteUserIdRef")))]
+  pub remote: T,
+}
+
 #[derive(Error, Debug)]
 pub enum TouchLinkError<T: RemoteUserIdRef> {
   #[error("cannot link as the remote user is already linked to the etwin user {0:?}")]
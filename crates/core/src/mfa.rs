@@ -0,0 +1,106 @@
+use crate::core::Instant;
+use crate::password::PasswordHash;
+use crate::user::UserIdRef;
+#[cfg(feature = "_serde")]
+use etwin_serde_tools::{buffer_to_hex, hex_to_buffer, Deserialize, Serialize};
+use url::Url;
+
+/// Shared secret used to generate RFC 6238 time-based one-time passwords.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TotpSecret(
+  #[cfg_attr(
+    feature = "_serde",
+    serde(serialize_with = "buffer_to_hex", deserialize_with = "hex_to_buffer")
+  )]
+  pub Vec<u8>,
+);
+
+impl TotpSecret {
+  pub fn as_slice(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+declare_new_string! {
+  /// One-time password generated by the authenticator app of the user.
+  pub struct TotpCode(String);
+  pub type ParseError = TotpCodeParseError;
+  const PATTERN = r"^[0-9]{6}$";
+}
+
+declare_new_string! {
+  /// Single-use code allowing to pass the second factor without the authenticator app.
+  pub struct RecoveryCode(String);
+  pub type ParseError = RecoveryCodeParseError;
+  const PATTERN = r"^[0-9a-z]{5}-[0-9a-z]{5}$";
+}
+
+/// TOTP configuration of a user, as stored in the user store.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UserTotp {
+  pub user: UserIdRef,
+  pub secret: TotpSecret,
+  pub enabled_at: Instant,
+  /// Last time step accepted for this secret, used to reject replayed codes.
+  pub last_used_step: Option<u64>,
+  /// Hashes of the recovery codes that were not used yet.
+  pub recovery_codes: Vec<PasswordHash>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SetUserTotpOptions {
+  pub user: UserIdRef,
+  pub secret: TotpSecret,
+  pub recovery_codes: Vec<PasswordHash>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TouchUserTotpStepOptions {
+  pub user: UserIdRef,
+  pub step: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeleteUserRecoveryCodeOptions {
+  pub user: UserIdRef,
+  pub code: PasswordHash,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TotpEnrollment {
+  pub secret: TotpSecret,
+  /// `otpauth://` URI, meant to be displayed as a QR code.
+  pub otpauth_uri: Url,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfirmTotpEnrollmentOptions {
+  pub secret: TotpSecret,
+  pub code: TotpCode,
+  /// Code from the currently enabled TOTP, required to replace it.
+  pub current_code: Option<TotpCode>,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DisableTotpOptions {
+  pub code: TotpCode,
+}
+
+/// Recovery codes in clear text: they are only returned once, when generated.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecoveryCodes {
+  pub recovery_codes: Vec<RecoveryCode>,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "_serde", serde(tag = "type", content = "code"))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MfaProof {
+  Totp(TotpCode),
+  RecoveryCode(RecoveryCode),
+}
@@ -53,6 +53,8 @@ declare_new_enum!(
   pub enum RfcOauthGrantType {
     #[str("authorization_code")]
     AuthorizationCode,
+    #[str("refresh_token")]
+    RefreshToken,
   }
   pub type ParseError = RfcOauthGrantTypeParseError;
 );
@@ -114,6 +116,17 @@ pub struct OauthAccessToken {
   pub refresh_token: Option<RfcOauthRefreshTokenKey>,
 }
 
+/// Access token response from a remote OAuth provider (e.g. Twinoid)
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RfcOauthAccessToken {
+  pub token_type: RfcOauthTokenType,
+  pub access_token: RfcOauthAccessTokenKey,
+  pub expires_in: i64,
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub refresh_token: Option<RfcOauthRefreshTokenKey>,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "_serde", serde(tag = "type", rename = "OauthClient"))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
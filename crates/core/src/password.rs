@@ -110,4 +110,11 @@ pub trait PasswordService: Send + Sync {
 
   /// Verifies if the hash and password match.
   fn verify(&self, hash: PasswordHash, clear_text: Password) -> bool;
+
+  /// Checks if the hash should be replaced by a new one (legacy algorithm or outdated parameters).
+  ///
+  /// This is only meaningful after a successful verification, when the clear text is known.
+  fn needs_rehash(&self, _hash: &PasswordHash) -> bool {
+    false
+  }
 }
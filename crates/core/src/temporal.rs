@@ -208,6 +208,11 @@ impl<T: Eq> Temporal<T> {
     self.current.value()
   }
 
+  /// Replaces the current value in place, without starting a new period.
+  pub fn replace_current_value(&mut self, value: T) -> T {
+    core::mem::replace(&mut self.current.value, value)
+  }
+
   pub fn map<B: Eq, F: FnMut(Snapshot<&T>) -> B>(&self, mut f: F) -> Temporal<B> {
     let mut it = self
       .old
@@ -36,6 +36,8 @@ pub trait TokenStore: Send + Sync {
   async fn revoke_twinoid_access_token(&self, options: &RfcOauthAccessTokenKey) -> Result<(), AnyError>;
   async fn revoke_twinoid_refresh_token(&self, options: &RfcOauthRefreshTokenKey) -> Result<(), AnyError>;
   async fn get_twinoid_oauth(&self, options: TwinoidUserIdRef) -> Result<TwinoidOauth, AnyError>;
+  /// Get the refresh tokens of the Twinoid users without an access token valid until `time`.
+  async fn get_twinoid_oauth_to_refresh(&self, time: Instant) -> Result<Vec<TwinoidRefreshToken>, AnyError>;
   async fn touch_dinoparc(
     &self,
     user: DinoparcUserIdRef,
@@ -1,12 +1,14 @@
 use crate::core::{HtmlFragment, Instant};
-use crate::oauth::RfcOauthAccessTokenKey;
-use crate::twinoid::api::{User, UserQuery};
+use crate::oauth::{RfcOauthAccessToken, RfcOauthAccessTokenKey, RfcOauthRefreshTokenKey};
+use crate::twinoid::api::{PartialUser, User, UserFields};
 use crate::types::AnyError;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 #[cfg(feature = "_serde")]
 use etwin_serde_tools::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::sync::Arc;
+use thiserror::Error;
 
 declare_decimal_id! {
   pub struct TwinoidUserId(u32);
@@ -74,6 +76,138 @@ pub struct GetTwinoidUserOptions {
   pub time: Option<Instant>,
 }
 
+declare_decimal_id! {
+  pub struct TwinoidSiteId(u32);
+  pub type ParseError = TwinoidSiteIdParseError;
+  const BOUNDS = 1..1_000_000_000;
+  const SQL_NAME = "twinoid_site_id";
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwinoidSite {
+  pub id: TwinoidSiteId,
+  pub name: String,
+  pub host: String,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwinoidSiteStat {
+  pub key: String,
+  pub score: i64,
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwinoidSiteAchievement {
+  pub key: String,
+  pub name: String,
+  pub stat: String,
+  pub score: i64,
+  pub points: i64,
+}
+
+/// Profile of a user on a single Twinoid site.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwinoidSiteUser {
+  pub site: TwinoidSite,
+  pub real_id: Option<u32>,
+  pub stats: Vec<TwinoidSiteStat>,
+  pub achievements: Vec<TwinoidSiteAchievement>,
+}
+
+/// Twinoid profile of a user, with their per-site stats and achievements.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TwinoidUserProfile {
+  pub id: TwinoidUserId,
+  pub display_name: TwinoidUserDisplayName,
+  pub title: Option<HtmlFragment>,
+  pub locale: Option<String>,
+  pub sites: Vec<TwinoidSiteUser>,
+}
+
+impl TwinoidUserProfile {
+  /// Graph API fields required to build a profile.
+  pub fn fields() -> UserFields {
+    UserFields::new().name().title().locale().sites(
+      api::SiteUserFields::new()
+        .site(api::SiteFields::new().name().host())
+        .real_id()
+        .stats(api::StatFields::new())
+        .achievements(api::AchievementFields::new().name().stat().score().points()),
+    )
+  }
+
+  /// Sort sites by id, and stats and achievements by key.
+  pub fn normalize(&mut self) {
+    self.sites.sort_by_key(|s| s.site.id);
+    for site in self.sites.iter_mut() {
+      site.stats.sort_by(|a, b| a.key.cmp(&b.key));
+      site.achievements.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+  }
+}
+
+impl TryFrom<PartialUser> for TwinoidUserProfile {
+  type Error = AnyError;
+
+  fn try_from(user: PartialUser) -> Result<Self, Self::Error> {
+    fn required<T>(value: Option<T>, field: &str) -> Result<T, AnyError> {
+      value.ok_or_else(|| format!("missing Twinoid field: {}", field).into())
+    }
+
+    let mut sites = Vec::new();
+    for site_user in required(user.sites, "sites")? {
+      let site = site_user.site;
+      let mut achievements = Vec::new();
+      for a in required(site_user.achievements, "sites.achievements")? {
+        achievements.push(TwinoidSiteAchievement {
+          key: a.id,
+          name: required(a.name, "sites.achievements.name")?,
+          stat: required(a.stat, "sites.achievements.stat")?,
+          score: required(a.score, "sites.achievements.score")?,
+          points: required(a.points, "sites.achievements.points")?,
+        });
+      }
+      sites.push(TwinoidSiteUser {
+        site: TwinoidSite {
+          id: TwinoidSiteId::new(site.id)?,
+          name: required(site.name, "sites.site.name")?,
+          host: required(site.host, "sites.site.host")?,
+        },
+        real_id: site_user.real_id,
+        stats: required(site_user.stats, "sites.stats")?
+          .into_iter()
+          .map(|s| TwinoidSiteStat {
+            key: s.id,
+            score: s.score,
+          })
+          .collect(),
+        achievements,
+      });
+    }
+    let mut profile = Self {
+      id: TwinoidUserId::new(user.id)?,
+      display_name: required(user.name, "name")?,
+      title: user.title,
+      locale: user.locale,
+      sites,
+    };
+    profile.normalize();
+    Ok(profile)
+  }
+}
+
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArchivedTwinoidUserProfile {
+  pub archived_at: Instant,
+  pub profile: TwinoidUserProfile,
+}
+
 #[async_trait]
 #[auto_impl(&, Arc)]
 pub trait TwinoidStore: Send + Sync {
@@ -82,6 +216,15 @@ pub trait TwinoidStore: Send + Sync {
   async fn get_user(&self, options: &GetTwinoidUserOptions) -> Result<Option<ArchivedTwinoidUser>, AnyError>;
 
   async fn touch_short_user(&self, options: &ShortTwinoidUser) -> Result<ArchivedTwinoidUser, AnyError>;
+
+  /// Get the latest archived profile of a user.
+  async fn get_user_profile(
+    &self,
+    options: &GetTwinoidUserOptions,
+  ) -> Result<Option<ArchivedTwinoidUserProfile>, AnyError>;
+
+  /// Archive the full profile of a user, replacing the previous per-site data.
+  async fn touch_user_profile(&self, profile: &TwinoidUserProfile) -> Result<ArchivedTwinoidUserProfile, AnyError>;
 }
 
 // #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
@@ -94,22 +237,33 @@ pub enum TwinoidApiAuth {
 pub mod api {
   use crate::core::HtmlFragment;
   use crate::twinoid::TwinoidUserDisplayName;
+  use crate::types::AnyError;
   #[cfg(feature = "_serde")]
   use etwin_serde_tools::{Deserialize, Serialize};
   #[cfg(feature = "_serde")]
   use serde::de::DeserializeOwned;
+  use std::fmt;
 
   #[cfg(not(feature = "_serde"))]
-  pub trait UserLike {}
+  pub trait UserLike: Sized {
+    /// Extract this user from a user retrieved with the fields of [`UserQuery::to_user_fields`].
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError>;
+  }
 
   #[cfg(feature = "_serde")]
-  pub trait UserLike: DeserializeOwned {}
+  pub trait UserLike: DeserializeOwned {
+    /// Extract this user from a user retrieved with the fields of [`UserQuery::to_user_fields`].
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError>;
+  }
 
   pub trait UserQuery: Send + Sync {
     type Output: UserLike;
     type Fields: AsRef<str>;
     // https://twinoid.com/graph/user/38?fields=id,name,picture,title,like,contacts.fields(user.fields(name,contacts))
     fn to_fields(&self) -> Self::Fields;
+
+    /// Dynamic field selection equivalent to this query.
+    fn to_user_fields(&self) -> UserFields;
   }
 
   #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
@@ -120,11 +274,49 @@ pub mod api {
     pub title: Title,
   }
 
-  #[cfg(not(feature = "_serde"))]
-  impl<Name, Title> UserLike for User<Name, Title> {}
+  fn required<T>(value: Option<T>, field: &str) -> Result<T, AnyError> {
+    value.ok_or_else(|| format!("missing Twinoid field: {}", field).into())
+  }
 
-  #[cfg(feature = "_serde")]
-  impl<Name: DeserializeOwned, Title: DeserializeOwned> UserLike for User<Name, Title> {}
+  impl UserLike for User<(), ()> {
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError> {
+      Ok(Self {
+        id: user.id,
+        name: (),
+        title: (),
+      })
+    }
+  }
+
+  impl UserLike for User<(), Option<HtmlFragment>> {
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError> {
+      Ok(Self {
+        id: user.id,
+        name: (),
+        title: user.title,
+      })
+    }
+  }
+
+  impl UserLike for User<TwinoidUserDisplayName, ()> {
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError> {
+      Ok(Self {
+        id: user.id,
+        name: required(user.name, "name")?,
+        title: (),
+      })
+    }
+  }
+
+  impl UserLike for User<TwinoidUserDisplayName, Option<HtmlFragment>> {
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError> {
+      Ok(Self {
+        id: user.id,
+        name: required(user.name, "name")?,
+        title: user.title,
+      })
+    }
+  }
 
   #[derive(Debug)]
   pub struct ConstUserQuery<const NAME: bool, const TITLE: bool>;
@@ -136,6 +328,10 @@ pub mod api {
     fn to_fields(&self) -> Self::Fields {
       "id"
     }
+
+    fn to_user_fields(&self) -> UserFields {
+      UserFields::new()
+    }
   }
 
   impl UserQuery for ConstUserQuery<false, true> {
@@ -145,6 +341,10 @@ pub mod api {
     fn to_fields(&self) -> Self::Fields {
       "id,title"
     }
+
+    fn to_user_fields(&self) -> UserFields {
+      UserFields::new().title()
+    }
   }
 
   impl UserQuery for ConstUserQuery<true, false> {
@@ -154,6 +354,10 @@ pub mod api {
     fn to_fields(&self) -> Self::Fields {
       "id,name"
     }
+
+    fn to_user_fields(&self) -> UserFields {
+      UserFields::new().name()
+    }
   }
 
   impl UserQuery for ConstUserQuery<true, true> {
@@ -163,6 +367,540 @@ pub mod api {
     fn to_fields(&self) -> Self::Fields {
       "id,name,title"
     }
+
+    fn to_user_fields(&self) -> UserFields {
+      UserFields::new().name().title()
+    }
+  }
+
+  /// User returned for a [`UserFields`] query: fields that were not selected are `None`.
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct PartialUser {
+    pub id: u32,
+    pub name: Option<TwinoidUserDisplayName>,
+    pub title: Option<HtmlFragment>,
+    pub picture: Option<Picture>,
+    pub locale: Option<String>,
+    pub contacts: Option<Vec<Contact>>,
+    pub sites: Option<Vec<SiteUser>>,
+  }
+
+  impl UserLike for PartialUser {
+    fn from_partial(user: PartialUser) -> Result<Self, AnyError> {
+      Ok(user)
+    }
+  }
+
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct Picture {
+    pub url: String,
+  }
+
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct Contact {
+    pub user: PartialUser,
+    pub friend: Option<bool>,
+  }
+
+  /// Participation of a user to a Twinoid site (game).
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[cfg_attr(feature = "_serde", serde(rename_all = "camelCase"))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct SiteUser {
+    pub site: Site,
+    pub real_id: Option<u32>,
+    pub link: Option<String>,
+    pub stats: Option<Vec<Stat>>,
+    pub achievements: Option<Vec<Achievement>>,
+  }
+
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct Site {
+    pub id: u32,
+    pub name: Option<String>,
+    pub host: Option<String>,
+    pub icon: Option<String>,
+    pub lang: Option<String>,
+  }
+
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct Stat {
+    pub id: String,
+    pub score: i64,
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub description: Option<HtmlFragment>,
+    pub rare: Option<i32>,
+    pub social: Option<bool>,
+  }
+
+  #[cfg_attr(feature = "_serde", derive(Deserialize, Serialize))]
+  #[derive(Clone, Debug, PartialEq, Eq)]
+  pub struct Achievement {
+    pub id: String,
+    pub name: Option<String>,
+    pub stat: Option<String>,
+    pub score: Option<i64>,
+    pub points: Option<i64>,
+    pub description: Option<HtmlFragment>,
+    pub date: Option<String>,
+  }
+
+  /// Field selection for a Twinoid user.
+  ///
+  /// The user id is always selected. Formatting this value produces the `fields` parameter
+  /// expected by the Graph API, e.g. `id,name,sites.fields(site.fields(id,name),stats.fields(id,score))`.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct UserFields {
+    pub name: bool,
+    pub title: bool,
+    pub picture: bool,
+    pub locale: bool,
+    pub contacts: Option<ContactFields>,
+    pub sites: Option<SiteUserFields>,
+  }
+
+  impl UserFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn name(mut self) -> Self {
+      self.name = true;
+      self
+    }
+
+    pub fn title(mut self) -> Self {
+      self.title = true;
+      self
+    }
+
+    pub fn picture(mut self) -> Self {
+      self.picture = true;
+      self
+    }
+
+    pub fn locale(mut self) -> Self {
+      self.locale = true;
+      self
+    }
+
+    pub fn contacts(mut self, fields: ContactFields) -> Self {
+      self.contacts = Some(fields);
+      self
+    }
+
+    pub fn sites(mut self, fields: SiteUserFields) -> Self {
+      self.sites = Some(fields);
+      self
+    }
+
+    /// Keep only the selected fields of `user`.
+    pub fn select(&self, user: &PartialUser) -> PartialUser {
+      PartialUser {
+        id: user.id,
+        name: user.name.clone().filter(|_| self.name),
+        title: user.title.clone().filter(|_| self.title),
+        picture: user.picture.clone().filter(|_| self.picture),
+        locale: user.locale.clone().filter(|_| self.locale),
+        contacts: match (&self.contacts, &user.contacts) {
+          (Some(fields), Some(contacts)) => Some(contacts.iter().map(|c| fields.select(c)).collect()),
+          _ => None,
+        },
+        sites: match (&self.sites, &user.sites) {
+          (Some(fields), Some(sites)) => Some(sites.iter().map(|s| fields.select(s)).collect()),
+          _ => None,
+        },
+      }
+    }
+  }
+
+  impl fmt::Display for UserFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("id")?;
+      write_flags(
+        f,
+        &[
+          ("name", self.name),
+          ("title", self.title),
+          ("picture", self.picture),
+          ("locale", self.locale),
+        ],
+      )?;
+      if let Some(contacts) = &self.contacts {
+        write!(f, ",contacts.fields({})", contacts)?;
+      }
+      if let Some(sites) = &self.sites {
+        write!(f, ",sites.fields({})", sites)?;
+      }
+      Ok(())
+    }
+  }
+
+  impl UserQuery for UserFields {
+    type Output = PartialUser;
+    type Fields = String;
+
+    fn to_fields(&self) -> Self::Fields {
+      self.to_string()
+    }
+
+    fn to_user_fields(&self) -> UserFields {
+      self.clone()
+    }
+  }
+
+  /// Field selection for the contacts of a Twinoid user.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct ContactFields {
+    pub user: Box<UserFields>,
+    pub friend: bool,
+  }
+
+  impl ContactFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn user(mut self, fields: UserFields) -> Self {
+      self.user = Box::new(fields);
+      self
+    }
+
+    pub fn friend(mut self) -> Self {
+      self.friend = true;
+      self
+    }
+
+    fn select(&self, contact: &Contact) -> Contact {
+      Contact {
+        user: self.user.select(&contact.user),
+        friend: contact.friend.filter(|_| self.friend),
+      }
+    }
+  }
+
+  impl fmt::Display for ContactFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "user.fields({})", self.user)?;
+      write_flags(f, &[("friend", self.friend)])
+    }
+  }
+
+  /// Field selection for the sites of a Twinoid user.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct SiteUserFields {
+    pub site: SiteFields,
+    pub real_id: bool,
+    pub link: bool,
+    pub stats: Option<StatFields>,
+    pub achievements: Option<AchievementFields>,
+  }
+
+  impl SiteUserFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn site(mut self, fields: SiteFields) -> Self {
+      self.site = fields;
+      self
+    }
+
+    pub fn real_id(mut self) -> Self {
+      self.real_id = true;
+      self
+    }
+
+    pub fn link(mut self) -> Self {
+      self.link = true;
+      self
+    }
+
+    pub fn stats(mut self, fields: StatFields) -> Self {
+      self.stats = Some(fields);
+      self
+    }
+
+    pub fn achievements(mut self, fields: AchievementFields) -> Self {
+      self.achievements = Some(fields);
+      self
+    }
+
+    fn select(&self, site_user: &SiteUser) -> SiteUser {
+      SiteUser {
+        site: self.site.select(&site_user.site),
+        real_id: site_user.real_id.filter(|_| self.real_id),
+        link: site_user.link.clone().filter(|_| self.link),
+        stats: match (&self.stats, &site_user.stats) {
+          (Some(fields), Some(stats)) => Some(stats.iter().map(|s| fields.select(s)).collect()),
+          _ => None,
+        },
+        achievements: match (&self.achievements, &site_user.achievements) {
+          (Some(fields), Some(achievements)) => Some(achievements.iter().map(|a| fields.select(a)).collect()),
+          _ => None,
+        },
+      }
+    }
+  }
+
+  impl fmt::Display for SiteUserFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "site.fields({})", self.site)?;
+      write_flags(f, &[("realId", self.real_id), ("link", self.link)])?;
+      if let Some(stats) = &self.stats {
+        write!(f, ",stats.fields({})", stats)?;
+      }
+      if let Some(achievements) = &self.achievements {
+        write!(f, ",achievements.fields({})", achievements)?;
+      }
+      Ok(())
+    }
+  }
+
+  /// Field selection for a Twinoid site. The site id is always selected.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct SiteFields {
+    pub name: bool,
+    pub host: bool,
+    pub icon: bool,
+    pub lang: bool,
+  }
+
+  impl SiteFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn name(mut self) -> Self {
+      self.name = true;
+      self
+    }
+
+    pub fn host(mut self) -> Self {
+      self.host = true;
+      self
+    }
+
+    pub fn icon(mut self) -> Self {
+      self.icon = true;
+      self
+    }
+
+    pub fn lang(mut self) -> Self {
+      self.lang = true;
+      self
+    }
+
+    fn select(&self, site: &Site) -> Site {
+      Site {
+        id: site.id,
+        name: site.name.clone().filter(|_| self.name),
+        host: site.host.clone().filter(|_| self.host),
+        icon: site.icon.clone().filter(|_| self.icon),
+        lang: site.lang.clone().filter(|_| self.lang),
+      }
+    }
+  }
+
+  impl fmt::Display for SiteFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("id")?;
+      write_flags(
+        f,
+        &[
+          ("name", self.name),
+          ("host", self.host),
+          ("icon", self.icon),
+          ("lang", self.lang),
+        ],
+      )
+    }
+  }
+
+  /// Field selection for the stats of a user on a site. The stat id and score are always selected.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct StatFields {
+    pub name: bool,
+    pub icon: bool,
+    pub description: bool,
+    pub rare: bool,
+    pub social: bool,
+  }
+
+  impl StatFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn name(mut self) -> Self {
+      self.name = true;
+      self
+    }
+
+    pub fn icon(mut self) -> Self {
+      self.icon = true;
+      self
+    }
+
+    pub fn description(mut self) -> Self {
+      self.description = true;
+      self
+    }
+
+    pub fn rare(mut self) -> Self {
+      self.rare = true;
+      self
+    }
+
+    pub fn social(mut self) -> Self {
+      self.social = true;
+      self
+    }
+
+    fn select(&self, stat: &Stat) -> Stat {
+      Stat {
+        id: stat.id.clone(),
+        score: stat.score,
+        name: stat.name.clone().filter(|_| self.name),
+        icon: stat.icon.clone().filter(|_| self.icon),
+        description: stat.description.clone().filter(|_| self.description),
+        rare: stat.rare.filter(|_| self.rare),
+        social: stat.social.filter(|_| self.social),
+      }
+    }
+  }
+
+  impl fmt::Display for StatFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("id,score")?;
+      write_flags(
+        f,
+        &[
+          ("name", self.name),
+          ("icon", self.icon),
+          ("description", self.description),
+          ("rare", self.rare),
+          ("social", self.social),
+        ],
+      )
+    }
+  }
+
+  /// Field selection for the achievements of a user on a site. The achievement id is always selected.
+  #[derive(Clone, Debug, Default, PartialEq, Eq)]
+  pub struct AchievementFields {
+    pub name: bool,
+    pub stat: bool,
+    pub score: bool,
+    pub points: bool,
+    pub description: bool,
+    pub date: bool,
+  }
+
+  impl AchievementFields {
+    pub fn new() -> Self {
+      Self::default()
+    }
+
+    pub fn name(mut self) -> Self {
+      self.name = true;
+      self
+    }
+
+    pub fn stat(mut self) -> Self {
+      self.stat = true;
+      self
+    }
+
+    pub fn score(mut self) -> Self {
+      self.score = true;
+      self
+    }
+
+    pub fn points(mut self) -> Self {
+      self.points = true;
+      self
+    }
+
+    pub fn description(mut self) -> Self {
+      self.description = true;
+      self
+    }
+
+    pub fn date(mut self) -> Self {
+      self.date = true;
+      self
+    }
+
+    fn select(&self, achievement: &Achievement) -> Achievement {
+      Achievement {
+        id: achievement.id.clone(),
+        name: achievement.name.clone().filter(|_| self.name),
+        stat: achievement.stat.clone().filter(|_| self.stat),
+        score: achievement.score.filter(|_| self.score),
+        points: achievement.points.filter(|_| self.points),
+        description: achievement.description.clone().filter(|_| self.description),
+        date: achievement.date.clone().filter(|_| self.date),
+      }
+    }
+  }
+
+  impl fmt::Display for AchievementFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str("id")?;
+      write_flags(
+        f,
+        &[
+          ("name", self.name),
+          ("stat", self.stat),
+          ("score", self.score),
+          ("points", self.points),
+          ("description", self.description),
+          ("date", self.date),
+        ],
+      )
+    }
+  }
+
+  fn write_flags(f: &mut fmt::Formatter<'_>, flags: &[(&str, bool)]) -> fmt::Result {
+    for (name, _) in flags.iter().filter(|(_, selected)| *selected) {
+      write!(f, ",{}", name)?;
+    }
+    Ok(())
+  }
+
+  #[cfg(test)]
+  mod test {
+    use super::{AchievementFields, ContactFields, SiteFields, SiteUserFields, StatFields, UserFields};
+
+    #[test]
+    fn test_user_fields_to_string() {
+      let fields = UserFields::new()
+        .name()
+        .title()
+        .contacts(ContactFields::new().user(UserFields::new().name()).friend())
+        .sites(
+          SiteUserFields::new()
+            .site(SiteFields::new().name().host())
+            .real_id()
+            .stats(StatFields::new().name())
+            .achievements(AchievementFields::new().name().points()),
+        );
+      let actual = fields.to_string();
+      let expected = "id,name,title,contacts.fields(user.fields(id,name),friend),sites.fields(site.fields(id,name,host),realId,stats.fields(id,score,name),achievements.fields(id,name,points))";
+      assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_default_user_fields_to_string() {
+      assert_eq!(UserFields::new().to_string(), "id");
+    }
   }
 }
 
@@ -171,27 +909,94 @@ pub mod api {
 pub trait TwinoidClient: Send + Sync {
   async fn get_me<Query: api::UserQuery>(&self, auth: TwinoidApiAuth, query: &Query) -> Result<Query::Output, AnyError>
   where
-    Self: Sized;
+    Self: Sized,
+  {
+    let user = self.get_me_fields(auth, &query.to_user_fields()).await?;
+    <Query::Output as api::UserLike>::from_partial(user)
+  }
+
+  /// Retrieve the user with the provided id, or `None` if it does not exist.
+  async fn get_user<Query: api::UserQuery>(
+    &self,
+    auth: TwinoidApiAuth,
+    id: TwinoidUserId,
+    query: &Query,
+  ) -> Result<Option<Query::Output>, AnyError>
+  where
+    Self: Sized,
+  {
+    match self.get_user_fields(auth, id, &query.to_user_fields()).await? {
+      Some(user) => Ok(Some(<Query::Output as api::UserLike>::from_partial(user)?)),
+      None => Ok(None),
+    }
+  }
 
   async fn get_me_short(
     &self,
     auth: TwinoidApiAuth,
-  ) -> Result<api::User<TwinoidUserDisplayName, Option<HtmlFragment>>, AnyError>;
+  ) -> Result<api::User<TwinoidUserDisplayName, Option<HtmlFragment>>, AnyError> {
+    let query = api::ConstUserQuery::<true, true>;
+    let user = self
+      .get_me_fields(auth, &api::UserQuery::to_user_fields(&query))
+      .await?;
+    api::UserLike::from_partial(user)
+  }
+
+  /// Object-safe version of [`TwinoidClient::get_me`]
+  async fn get_me_fields(&self, auth: TwinoidApiAuth, fields: &api::UserFields) -> Result<api::PartialUser, AnyError>;
+
+  /// Object-safe version of [`TwinoidClient::get_user`]
+  async fn get_user_fields(
+    &self,
+    auth: TwinoidApiAuth,
+    id: TwinoidUserId,
+    fields: &api::UserFields,
+  ) -> Result<Option<api::PartialUser>, AnyError>;
 }
 
 #[async_trait]
 impl<T: TwinoidClient + ?Sized> TwinoidClient for Arc<T> {
-  async fn get_me<Query: UserQuery>(&self, _auth: TwinoidApiAuth, _query: &Query) -> Result<Query::Output, AnyError>
-  where
-    Self: Sized,
-  {
-    todo!()
-  }
-
   async fn get_me_short(
     &self,
     auth: TwinoidApiAuth,
   ) -> Result<User<TwinoidUserDisplayName, Option<HtmlFragment>>, AnyError> {
     (**self).get_me_short(auth).await
   }
+
+  async fn get_me_fields(&self, auth: TwinoidApiAuth, fields: &UserFields) -> Result<PartialUser, AnyError> {
+    (**self).get_me_fields(auth, fields).await
+  }
+
+  async fn get_user_fields(
+    &self,
+    auth: TwinoidApiAuth,
+    id: TwinoidUserId,
+    fields: &UserFields,
+  ) -> Result<Option<PartialUser>, AnyError> {
+    (**self).get_user_fields(auth, id, fields).await
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum TwinoidOauthError {
+  /// The authorization code or refresh token was rejected by Twinoid (`invalid_grant`): it is invalid, expired or
+  /// revoked.
+  #[error("invalid Twinoid OAuth grant")]
+  InvalidGrant,
+  #[error(transparent)]
+  Other(AnyError),
+}
+
+/// Client for the Twinoid OAuth token endpoint
+#[async_trait]
+#[auto_impl(&, Arc)]
+pub trait TwinoidOauthClient: Send + Sync {
+  /// Exchange an authorization code received on the callback URI for an access token.
+  async fn exchange_code(&self, code: &str) -> Result<RfcOauthAccessToken, TwinoidOauthError>;
+
+  /// Obtain a fresh access token from a refresh token.
+  async fn refresh_token(
+    &self,
+    refresh_token: &RfcOauthRefreshTokenKey,
+  ) -> Result<RfcOauthAccessToken, TwinoidOauthError>;
 }
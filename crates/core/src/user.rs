@@ -1,6 +1,7 @@
 use crate::core::{FinitePeriod, Instant};
 use crate::email::EmailAddress;
-use crate::password::PasswordHash;
+use crate::mfa::{DeleteUserRecoveryCodeOptions, SetUserTotpOptions, TouchUserTotpStepOptions, UserTotp};
+use crate::password::{Password, PasswordHash};
 use crate::types::AnyError;
 use async_trait::async_trait;
 use auto_impl::auto_impl;
@@ -50,6 +51,15 @@ pub struct UpdateUserOptions {
   pub patch: UpdateUserPatch,
 }
 
+/// Replace the hash of the current password, without changing the password itself.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReplaceUserPasswordHashOptions {
+  pub r#ref: UserIdRef,
+  /// Hash expected to be current
+  pub old: PasswordHash,
+  pub new: PasswordHash,
+}
+
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct UpdateUserPatch {
@@ -61,6 +71,28 @@ pub struct UpdateUserPatch {
   #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
   #[cfg_attr(feature = "_serde", serde(default, deserialize_with = "deserialize_nested_option"))]
   pub password: Option<Option<PasswordHash>>,
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  #[cfg_attr(feature = "_serde", serde(default, deserialize_with = "deserialize_nested_option"))]
+  pub email: Option<Option<EmailAddress>>,
+}
+
+/// Changes to a user, as requested through the API.
+///
+/// Unlike `UpdateUserPatch`, the new password is in clear text: it is hashed by the auth service.
+#[cfg_attr(feature = "_serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawUpdateUserPatch {
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub display_name: Option<UserDisplayName>,
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  #[cfg_attr(feature = "_serde", serde(default, deserialize_with = "deserialize_nested_option"))]
+  pub username: Option<Option<Username>>,
+  #[cfg_attr(feature = "_serde", serde(skip_serializing_if = "Option::is_none"))]
+  #[cfg_attr(feature = "_serde", serde(default, deserialize_with = "deserialize_nested_option"))]
+  pub password: Option<Option<Password>>,
+  /// Current password of the user, required when users change their own password.
+  #[cfg_attr(feature = "_serde", serde(default, skip_serializing_if = "Option::is_none"))]
+  pub current_password: Option<Password>,
 }
 
 #[cfg_attr(feature = "_serde", derive(Serialize, Deserialize), serde(tag = "type"))]
@@ -370,7 +402,29 @@ pub trait UserStore: Send + Sync {
 
   async fn update_user(&self, options: &UpdateUserOptions) -> Result<CompleteSimpleUser, UpdateUserError>;
 
+  /// Upgrades the stored hash of the current password (e.g. after a change of algorithm).
+  ///
+  /// This is not a password change: the history and the password lock are left untouched.
+  /// Returns `false` if the current hash is no longer `old` (e.g. the password was changed in the meantime).
+  async fn replace_user_password_hash(&self, options: &ReplaceUserPasswordHashOptions) -> Result<bool, AnyError>;
+
   async fn hard_delete_user(&self, user_ref: UserIdRef) -> Result<(), DeleteUserError>;
+
+  async fn get_user_totp(&self, user_ref: UserIdRef) -> Result<Option<UserTotp>, AnyError>;
+
+  /// Enables TOTP for the user, replacing any previous secret and recovery codes.
+  async fn set_user_totp(&self, options: &SetUserTotpOptions) -> Result<UserTotp, AnyError>;
+
+  /// Marks a time step as used.
+  ///
+  /// Returns `false` if this step (or a later one) was already used: the code must be rejected.
+  async fn touch_user_totp_step(&self, options: &TouchUserTotpStepOptions) -> Result<bool, AnyError>;
+
+  /// Returns `false` if the recovery code was already used.
+  async fn delete_user_recovery_code(&self, options: &DeleteUserRecoveryCodeOptions) -> Result<bool, AnyError>;
+
+  /// Disables TOTP for the user, removing the secret and recovery codes.
+  async fn delete_user_totp(&self, user_ref: UserIdRef) -> Result<(), AnyError>;
 }
 
 #[cfg(test)]
@@ -510,6 +564,7 @@ mod test {
       display_name: None,
       username: None,
       password: None,
+      email: None,
     }
   }
 
@@ -536,6 +591,7 @@ mod test {
       display_name: None,
       username: Some(None),
       password: None,
+      email: None,
     }
   }
 
@@ -564,6 +620,7 @@ mod test {
       display_name: Some("Demurgos".parse().unwrap()),
       username: Some(Some("demurgos".parse().unwrap())),
       password: Some(Some(PasswordHash::from(&hash[..]))),
+      email: None,
     }
   }
 
@@ -590,6 +647,7 @@ mod test {
       display_name: None,
       username: Some(Some("demurgos".parse().unwrap())),
       password: None,
+      email: None,
     }
   }
 
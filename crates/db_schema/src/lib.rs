@@ -1,16 +1,29 @@
-use etwin_squirrel::{SchemaResolver, SchemaStateRef};
+use etwin_squirrel::{SchemaResolver, SchemaStateRef, SchemaVerification};
 use include_dir::{include_dir, Dir};
 use lazy_static::lazy_static;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::PgPool;
 use std::error::Error;
 pub mod schema;
 
 const DB_SCRIPTS: Dir = include_dir!("./scripts");
 
+/// Lowest schema version supported as a downgrade target.
+///
+/// Downgrade scripts are provided for every step from the latest version down to version 026. Older versions were
+/// released before downgrades were supported: they cannot be restored from a newer schema, and downgrades to them are
+/// rejected.
+pub const LOWEST_DOWNGRADE_VERSION: u32 = 26;
+
 lazy_static! {
   static ref SQUIRREL: SchemaResolver = SchemaResolver::new(&DB_SCRIPTS);
 }
 
+/// Resolver for the schema scripts of Eternaltwin.
+pub fn resolver() -> &'static SchemaResolver {
+  &SQUIRREL
+}
+
 pub async fn get_state(db: &PgPool) -> Result<SchemaStateRef<'static>, Box<dyn Error>> {
   SQUIRREL.get_state(db).await
 }
@@ -26,3 +39,216 @@ pub async fn force_create_latest(db: &PgPool, void: bool) -> Result<(), Box<dyn
 pub async fn force_create(db: &PgPool, state: SchemaStateRef<'static>, void: bool) -> Result<(), Box<dyn Error>> {
   SQUIRREL.force_create(db, state, void).await
 }
+
+/// Compare the schema of `db` with the schema produced by the scripts of its current version.
+///
+/// The expected schema is built in a temporary database named `scratch_name`, created through `db` and reached with
+/// `scratch_options`: it requires the `CREATEDB` privilege. The temporary database is dropped afterwards.
+pub async fn verify(
+  db: &PgPool,
+  scratch_options: PgConnectOptions,
+  scratch_name: &str,
+) -> Result<SchemaVerification, Box<dyn Error>> {
+  let quoted_name = format!("\"{}\"", scratch_name.replace('"', "\"\""));
+  sqlx::query(&format!("CREATE DATABASE {};", quoted_name))
+    .execute(db)
+    .await?;
+  let verification: Result<SchemaVerification, Box<dyn Error>> = async {
+    let scratch = PgPoolOptions::new()
+      .max_connections(1)
+      .connect_with(scratch_options.database(scratch_name))
+      .await?;
+    let verification = SQUIRREL.verify(db, &scratch).await;
+    scratch.close().await;
+    verification
+  }
+  .await;
+  sqlx::query(&format!("DROP DATABASE {};", quoted_name))
+    .execute(db)
+    .await?;
+  verification
+}
+
+#[cfg(test)]
+mod test {
+  use crate::{empty, force_create, force_create_latest, resolver, verify, LOWEST_DOWNGRADE_VERSION};
+  use etwin_squirrel::MigrationDirection;
+  use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+  use sqlx::PgPool;
+
+  fn admin_options() -> PgConnectOptions {
+    let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
+    PgConnectOptions::new()
+      .host(&config.db.host)
+      .port(config.db.port)
+      .database(&config.db.name)
+      .username(&config.db.admin_user)
+      .password(&config.db.admin_password)
+  }
+
+  async fn connect_admin() -> PgPool {
+    PgPoolOptions::new()
+      .max_connections(1)
+      .connect_with(admin_options())
+      .await
+      .unwrap()
+  }
+
+  /// Name of the temporary database used by `verify`.
+  const SCRATCH_DATABASE: &str = "etwin_squirrel_verify";
+
+  async fn database_exists(database: &PgPool, name: &str) -> bool {
+    let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS(SELECT 1 FROM pg_database WHERE datname = $1);")
+      .bind(name)
+      .fetch_one(database)
+      .await
+      .unwrap();
+    exists
+  }
+
+  /// Lines of `expected` and `actual` differing at the same position, prefixed by `-` and `+` respectively.
+  fn ordered_snapshot_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+      let (expected, actual) = (expected.get(i), actual.get(i));
+      if expected != actual {
+        if let Some(line) = expected {
+          diff.push_str(&format!("{}: -{}\n", i, line));
+        }
+        if let Some(line) = actual {
+          diff.push_str(&format!("{}: +{}\n", i, line));
+        }
+      }
+    }
+    diff
+  }
+
+  #[test]
+  fn test_downgrade_scripts_reach_lowest_version() {
+    let squirrel = resolver();
+    let steps: Vec<(u32, u32)> = squirrel
+      .get_downgrades()
+      .into_iter()
+      .map(|(start, end)| (start.get().get(), end.get().get()))
+      .collect();
+    let latest = squirrel.get_latest().get().get();
+    let expected: Vec<(u32, u32)> = (LOWEST_DOWNGRADE_VERSION..latest).map(|v| (v + 1, v)).collect();
+    assert_eq!(steps, expected);
+  }
+
+  /// Check that `verify` accepts a freshly created schema and reports manual changes.
+  async fn check_verify(database: &PgPool) {
+    force_create_latest(database, false).await.unwrap();
+    let verification = verify(database, admin_options(), SCRATCH_DATABASE).await.unwrap();
+    assert!(verification.recorded_checksum.is_some());
+    assert!(verification.is_ok(), "{:?}", verification);
+    assert!(!database_exists(database, SCRATCH_DATABASE).await);
+
+    sqlx::query("ALTER TABLE users ADD COLUMN drift INT NULL;")
+      .execute(database)
+      .await
+      .unwrap();
+    let verification = verify(database, admin_options(), SCRATCH_DATABASE).await.unwrap();
+    assert!(verification.checksum_matches());
+    assert!(verification.missing.is_empty());
+    assert_eq!(verification.unexpected.len(), 1);
+    assert!(verification.unexpected[0].starts_with("column\tusers\tdrift\t"));
+  }
+
+  #[test]
+  fn test_squashed_create_scripts_are_up_to_date() {
+    let squirrel = resolver();
+    let latest = squirrel.get_latest();
+    assert!(
+      squirrel.get_create_script(latest).is_some(),
+      "the latest version has no creation script: generate it with `etwin db squash > db/scripts/create/{:03}.sql`",
+      latest.get()
+    );
+    for version in squirrel.get_squashed() {
+      assert!(
+        squirrel.get_create_script(version) == squirrel.squash(version).as_deref(),
+        "create/{0:03}.sql is stale: regenerate it with `etwin db squash --to {0} > db/scripts/create/{0:03}.sql`",
+        version.get()
+      );
+    }
+  }
+
+  /// Check that squashed creation scripts produce the same schema as the full migration they replace.
+  async fn check_squashed_create_scripts(database: &PgPool) {
+    let squirrel = resolver();
+    let squashed = squirrel.get_squashed();
+    assert!(!squashed.is_empty());
+    for version in squashed {
+      force_create(database, version.into(), false).await.unwrap();
+      let squashed_snapshot = squirrel.get_snapshot(database).await.unwrap();
+
+      empty(database).await.unwrap();
+      let full = squirrel.create_full_migration(version).unwrap();
+      assert!(full.steps().count() > 1);
+      squirrel.apply_migration(database, &full).await.unwrap();
+      assert_eq!(squirrel.get_state(database).await.unwrap(), version.into());
+      let snapshot = squirrel.get_snapshot(database).await.unwrap();
+      assert!(
+        snapshot == squashed_snapshot,
+        "create/{:03}.sql must create the same schema as the full migration:\n{}",
+        version.get(),
+        ordered_snapshot_diff(&squashed_snapshot, &snapshot)
+      );
+    }
+  }
+
+  /// For each downgrade script, check that upgrade-downgrade-upgrade round-trips restore identical schemas.
+  async fn check_downgrade_round_trips(database: &PgPool) {
+    let squirrel = resolver();
+    let downgrades = squirrel.get_downgrades();
+    assert!(!downgrades.is_empty());
+    for (start, end) in downgrades {
+      let (start_id, end_id) = (start.get(), end.get());
+      let upgrade = squirrel
+        .create_migration(end.into(), start, MigrationDirection::UpgradeOnly)
+        .unwrap();
+      let downgrade = squirrel
+        .create_migration(start.into(), end, MigrationDirection::DowngradeOnly)
+        .unwrap();
+
+      force_create(database, end.into(), false).await.unwrap();
+      let end_snapshot = squirrel.get_snapshot(database).await.unwrap();
+      squirrel.apply_migration(database, &upgrade).await.unwrap();
+      let start_snapshot = squirrel.get_snapshot(database).await.unwrap();
+      assert_ne!(start_snapshot, end_snapshot);
+
+      squirrel.apply_migration(database, &downgrade).await.unwrap();
+      assert_eq!(squirrel.get_state(database).await.unwrap(), end.into());
+      let snapshot = squirrel.get_snapshot(database).await.unwrap();
+      assert!(
+        snapshot == end_snapshot,
+        "downgrade {} -> {} must restore the schema:\n{}",
+        start_id,
+        end_id,
+        ordered_snapshot_diff(&end_snapshot, &snapshot)
+      );
+
+      squirrel.apply_migration(database, &upgrade).await.unwrap();
+      let snapshot = squirrel.get_snapshot(database).await.unwrap();
+      assert!(
+        snapshot == start_snapshot,
+        "upgrade {} -> {} after a downgrade must restore the schema:\n{}",
+        end_id,
+        start_id,
+        ordered_snapshot_diff(&start_snapshot, &snapshot)
+      );
+    }
+  }
+
+  /// The checks share the database, so they run sequentially in a single test.
+  #[tokio::test]
+  async fn test_schema_migrations() {
+    let database = connect_admin().await;
+    check_downgrade_round_trips(&database).await;
+    check_squashed_create_scripts(&database).await;
+    check_verify(&database).await;
+    force_create_latest(&database, false).await.unwrap();
+  }
+}
@@ -9,6 +9,7 @@ use ::scraper::Html;
 use ::url::Url;
 use async_trait::async_trait;
 use erased_serde::Serialize as ErasedSerialize;
+use etwin_core::auth::InvalidCredentialsError;
 use etwin_core::clock::Clock;
 use etwin_core::dinoparc::{
   DinoparcClient, DinoparcCollectionResponse, DinoparcCredentials, DinoparcDinozId, DinoparcDinozResponse,
@@ -337,8 +338,13 @@ where
     event.bank_response = Some(&bank_res_meta);
     if resp.status() == StatusCode::FOUND {
       // Redirected: it means we are _not_ logged in
-      return Err(ScraperError::InvalidCredentials(options.server, options.username.clone()).into())
-        .log_on_err(event, logger);
+      return Err(
+        InvalidCredentialsError {
+          reason: "WrongDinoparcCredentials",
+        }
+        .into(),
+      )
+      .log_on_err(event, logger);
     }
     let text = resp.error_for_status()?.text().await.log_on_err(event, logger)?;
     event.bank_html = Some(text.as_bytes());
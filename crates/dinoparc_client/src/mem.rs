@@ -1,14 +1,15 @@
 use async_trait::async_trait;
+use etwin_core::auth::InvalidCredentialsError;
 use etwin_core::clock::Clock;
 use etwin_core::core::Instant;
 use etwin_core::dinoparc::{
-  DinoparcClient, DinoparcCollectionResponse, DinoparcCredentials, DinoparcDinozId, DinoparcDinozResponse,
-  DinoparcExchangeWithResponse, DinoparcInventoryResponse, DinoparcPassword, DinoparcServer, DinoparcSession,
-  DinoparcSessionKey, DinoparcUserId, DinoparcUsername, ShortDinoparcUser,
+  DinoparcClient, DinoparcCollection, DinoparcCollectionResponse, DinoparcCredentials, DinoparcDinozId,
+  DinoparcDinozResponse, DinoparcExchangeWithResponse, DinoparcInventoryResponse, DinoparcPassword, DinoparcServer,
+  DinoparcSession, DinoparcSessionKey, DinoparcSessionUser, DinoparcUserId, DinoparcUsername, ShortDinoparcUser,
 };
 use etwin_core::types::AnyError;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::RwLock;
 use thiserror::Error;
@@ -156,6 +157,28 @@ impl<TyClock> MemDinoparcClient<TyClock> {
       .expect("failed to acquire write lock for dinoparc client state");
     state.get_mut(&server).unwrap().create_user(id, username, password)
   }
+
+  /// Resolve the user of an active session.
+  ///
+  /// Mem users have no coins, dinoz or items.
+  fn get_session_user(&self, session: &DinoparcSession) -> Result<DinoparcSessionUser, AnyError> {
+    let state = self
+      .state
+      .read()
+      .expect("failed to acquire read lock for dinoparc client state");
+    let srv = state.get(&session.user.server).unwrap();
+    let s = srv.get_session_by_key(&session.key).ok_or(Error::InvalidSession)?;
+    let user = srv.get_user_by_id(&s.user_id).unwrap();
+    Ok(DinoparcSessionUser {
+      user: ShortDinoparcUser {
+        server: session.user.server,
+        id: user.id,
+        username: user.username.clone(),
+      },
+      coins: 0,
+      dinoz: Vec::new(),
+    })
+  }
 }
 
 #[async_trait]
@@ -188,6 +211,9 @@ where
           },
         })
       }
+      Err(Error::InvalidCredentials) => Err(Box::new(InvalidCredentialsError {
+        reason: "WrongDinoparcCredentials",
+      })),
       Err(e) => Err(Box::new(e)),
     }
   }
@@ -239,12 +265,21 @@ where
     todo!()
   }
 
-  async fn get_inventory(&self, _session: &DinoparcSession) -> Result<DinoparcInventoryResponse, AnyError> {
-    todo!()
+  async fn get_inventory(&self, session: &DinoparcSession) -> Result<DinoparcInventoryResponse, AnyError> {
+    Ok(DinoparcInventoryResponse {
+      session_user: self.get_session_user(session)?,
+      inventory: HashMap::new(),
+    })
   }
 
-  async fn get_collection(&self, _session: &DinoparcSession) -> Result<DinoparcCollectionResponse, AnyError> {
-    todo!()
+  async fn get_collection(&self, session: &DinoparcSession) -> Result<DinoparcCollectionResponse, AnyError> {
+    Ok(DinoparcCollectionResponse {
+      session_user: self.get_session_user(session)?,
+      collection: DinoparcCollection {
+        rewards: HashSet::new(),
+        epic_rewards: HashSet::new(),
+      },
+    })
   }
 }
 
@@ -256,7 +291,7 @@ fn make_session_key() -> DinoparcSessionKey {
 
   let key: String = std::iter::from_fn(|| CHARS.choose(&mut rng).copied())
     .map(char::from)
-    .take(26)
+    .take(32)
     .collect();
 
   DinoparcSessionKey::from_str(&key).expect("invalid session key")
@@ -7,7 +7,8 @@ use etwin_core::dinoparc::{
   DinoparcDinozId, DinoparcDinozIdRef, DinoparcDinozName, DinoparcDinozRace, DinoparcDinozResponse, DinoparcDinozSkin,
   DinoparcExchangeWithResponse, DinoparcInventoryResponse, DinoparcItemId, DinoparcLocationId, DinoparcServer,
   DinoparcSessionUser, DinoparcSkill, DinoparcSkillLevel, DinoparcStore, DinoparcUserId, DinoparcUserIdRef,
-  DinoparcUsername, GetDinoparcDinozOptions, GetDinoparcUserOptions, ShortDinoparcDinozWithLevel, ShortDinoparcUser,
+  DinoparcUsername, GetDinoparcDinozOptions, GetDinoparcUserOptions, GetStaleDinoparcUsersOptions,
+  ShortDinoparcDinozWithLevel, ShortDinoparcUser,
 };
 use etwin_core::temporal::{CheckedSnapshotLog, LatestTemporal, SnapshotLog};
 use etwin_core::types::AnyError;
@@ -17,6 +18,7 @@ use std::sync::RwLock;
 struct StoreState {
   users: HashMap<DinoparcUserIdRef, StoreUser>,
   dinoz: HashMap<DinoparcDinozIdRef, StoreDinoz>,
+  refresh_attempted_at: HashMap<DinoparcUserIdRef, Instant>,
 }
 
 struct StoreUser {
@@ -91,6 +93,7 @@ impl StoreState {
     Self {
       users: HashMap::new(),
       dinoz: HashMap::new(),
+      refresh_attempted_at: HashMap::new(),
     }
   }
 
@@ -102,6 +105,32 @@ impl StoreState {
     self.dinoz.get(id)
   }
 
+  fn get_stale_users(&self, options: &GetStaleDinoparcUsersOptions) -> Vec<ShortDinoparcUser> {
+    let mut stale: Vec<(Option<Instant>, Instant, &StoreUser)> = self
+      .users
+      .iter()
+      .filter(|(id, _)| id.server == options.server)
+      .map(|(id, u)| {
+        let attempted_at = self.refresh_attempted_at.get(id).copied();
+        let retrieved_at = u.collection.latest().map_or(u.archived_at, |c| c.retrieved.latest);
+        (attempted_at, retrieved_at, u)
+      })
+      .filter(|(attempted_at, retrieved_at, _)| {
+        *retrieved_at < options.retrieved_before && !matches!(attempted_at, Some(t) if *t >= options.attempted_before)
+      })
+      .collect();
+    stale.sort_by_key(|(attempted_at, retrieved_at, u)| (*attempted_at, *retrieved_at, u.id));
+    stale
+      .into_iter()
+      .take(usize::try_from(options.limit).unwrap())
+      .map(|(_, _, u)| ShortDinoparcUser {
+        server: u.server,
+        id: u.id,
+        username: u.username.clone(),
+      })
+      .collect()
+  }
+
   fn to_archived_dinoz(&self, dinoz: &StoreDinoz) -> ArchivedDinoparcDinoz {
     ArchivedDinoparcDinoz {
       server: dinoz.server,
@@ -356,6 +385,17 @@ where
     let state = self.state.read().unwrap();
     Ok(state.get_user(&options.id.and_server(options.server)).map(|u| u.into()))
   }
+
+  async fn get_stale_users(&self, options: &GetStaleDinoparcUsersOptions) -> Result<Vec<ShortDinoparcUser>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.get_stale_users(options))
+  }
+
+  async fn touch_refresh_attempt(&self, user: DinoparcUserIdRef) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.refresh_attempted_at.insert(user, self.clock.now());
+    Ok(())
+  }
 }
 
 #[cfg(feature = "neon")]
@@ -9,7 +9,7 @@ use etwin_core::dinoparc::{
   DinoparcEpicRewardKey, DinoparcExchangeWithResponse, DinoparcInventoryResponse, DinoparcItemId, DinoparcLocationId,
   DinoparcRewardId, DinoparcServer, DinoparcSessionUser, DinoparcSkill, DinoparcSkillLevel, DinoparcStore,
   DinoparcUserId, DinoparcUserIdRef, DinoparcUsername, GetDinoparcDinozOptions, GetDinoparcUserOptions,
-  ShortDinoparcDinozWithLevel, ShortDinoparcUser,
+  GetStaleDinoparcUsersOptions, ShortDinoparcDinozWithLevel, ShortDinoparcUser,
 };
 use etwin_core::pg_num::{PgU16, PgU32};
 use etwin_core::temporal::{ForeignRetrieved, ForeignSnapshot, LatestTemporal};
@@ -594,6 +594,74 @@ where
       dinoz,
     }))
   }
+
+  async fn get_stale_users(&self, options: &GetStaleDinoparcUsersOptions) -> Result<Vec<ShortDinoparcUser>, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      dinoparc_server: DinoparcServer,
+      dinoparc_user_id: DinoparcUserId,
+      username: DinoparcUsername,
+    }
+
+    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+      r"
+      WITH latest_dinoparc_collections AS (
+        SELECT DISTINCT ON (dinoparc_server, dinoparc_user_id) dinoparc_server, dinoparc_user_id,
+          retrieved_at[CARDINALITY(retrieved_at)] AS retrieved
+        FROM dinoparc_collections
+        WHERE dinoparc_server = $1::DINOPARC_SERVER
+        ORDER BY dinoparc_server, dinoparc_user_id, lower(period) DESC
+      ),
+      dinoparc_user_retrievals AS (
+        SELECT dinoparc_users.dinoparc_server, dinoparc_users.dinoparc_user_id, username,
+          COALESCE(collection.retrieved, dinoparc_users.archived_at) AS retrieved, attempt.attempted_at AS attempted
+        FROM dinoparc_users
+          LEFT OUTER JOIN latest_dinoparc_collections AS collection USING (dinoparc_server, dinoparc_user_id)
+          LEFT OUTER JOIN dinoparc_user_refresh_attempts AS attempt USING (dinoparc_server, dinoparc_user_id)
+        WHERE dinoparc_users.dinoparc_server = $1::DINOPARC_SERVER
+      )
+      SELECT dinoparc_server, dinoparc_user_id, username
+      FROM dinoparc_user_retrievals
+      WHERE retrieved < $2::INSTANT AND (attempted IS NULL OR attempted < $3::INSTANT)
+      ORDER BY attempted NULLS FIRST, retrieved, dinoparc_user_id
+      LIMIT $4::INT8;
+    ",
+    )
+    .bind(options.server)
+    .bind(options.retrieved_before)
+    .bind(options.attempted_before)
+    .bind(i64::from(options.limit))
+    .fetch_all(self.database.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|r| ShortDinoparcUser {
+          server: r.dinoparc_server,
+          id: r.dinoparc_user_id,
+          username: r.username,
+        })
+        .collect(),
+    )
+  }
+
+  async fn touch_refresh_attempt(&self, user: DinoparcUserIdRef) -> Result<(), AnyError> {
+    let res: PgQueryResult = sqlx::query(
+      r"
+      INSERT INTO dinoparc_user_refresh_attempts(dinoparc_server, dinoparc_user_id, attempted_at)
+      VALUES ($1::DINOPARC_SERVER, $2::DINOPARC_USER_ID, $3::INSTANT)
+      ON CONFLICT (dinoparc_server, dinoparc_user_id) DO UPDATE SET attempted_at = $3::INSTANT;
+    ",
+    )
+    .bind(user.server)
+    .bind(user.id)
+    .bind(self.clock.now())
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+    Ok(())
+  }
 }
 
 fn to_latest_temporal<T>(
@@ -6,8 +6,8 @@ use etwin_core::dinoparc::{
   ArchivedDinoparcDinoz, ArchivedDinoparcUser, DinoparcCollection, DinoparcCollectionResponse, DinoparcDinoz,
   DinoparcDinozElements, DinoparcDinozIdRef, DinoparcDinozRace, DinoparcDinozResponse, DinoparcExchangeWithResponse,
   DinoparcInventoryResponse, DinoparcServer, DinoparcSessionUser, DinoparcSkill, DinoparcSkillLevel, DinoparcStore,
-  GetDinoparcDinozOptions, GetDinoparcUserOptions, NamedDinoparcDinozFields, ShortDinoparcDinozWithLevel,
-  ShortDinoparcDinozWithLocation, ShortDinoparcUser,
+  GetDinoparcDinozOptions, GetDinoparcUserOptions, GetStaleDinoparcUsersOptions, NamedDinoparcDinozFields,
+  ShortDinoparcDinozWithLevel, ShortDinoparcDinozWithLocation, ShortDinoparcUser,
 };
 use etwin_core::temporal::{ForeignRetrieved, ForeignSnapshot, LatestTemporal};
 use std::collections::{HashMap, HashSet};
@@ -27,6 +27,7 @@ macro_rules! test_dinoparc_store {
     register_test!($(#[$meta])*, $api, test_touch_exchange_with_none_admin);
     register_test!($(#[$meta])*, $api, test_touch_exchange_with_extra);
     register_test!($(#[$meta])*, $api, test_touch_exchange_with_extra_then_drop_some);
+    register_test!($(#[$meta])*, $api, test_get_stale_users);
   };
 }
 
@@ -1659,3 +1660,103 @@ pub(crate) async fn test_touch_exchange_with_extra_then_drop_some<TyClock, TyDin
     assert_eq!(actual, expected);
   }
 }
+
+pub(crate) async fn test_get_stale_users<TyClock, TyDinoparcStore>(api: TestApi<TyClock, TyDinoparcStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyDinoparcStore: DinoparcStore,
+{
+  let alice = ShortDinoparcUser {
+    server: DinoparcServer::DinoparcCom,
+    id: "1".parse().unwrap(),
+    username: "alice".parse().unwrap(),
+  };
+  let bob = ShortDinoparcUser {
+    server: DinoparcServer::DinoparcCom,
+    id: "2".parse().unwrap(),
+    username: "bob".parse().unwrap(),
+  };
+  let charlie = ShortDinoparcUser {
+    server: DinoparcServer::EnDinoparcCom,
+    id: "3".parse().unwrap(),
+    username: "charlie".parse().unwrap(),
+  };
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  api.dinoparc_store.touch_short_user(&alice).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 1));
+  api.dinoparc_store.touch_short_user(&bob).await.unwrap();
+  api.dinoparc_store.touch_short_user(&charlie).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 2, 0, 0, 0));
+  api
+    .dinoparc_store
+    .touch_collection(&DinoparcCollectionResponse {
+      session_user: DinoparcSessionUser {
+        user: alice.clone(),
+        coins: 10000,
+        dinoz: vec![],
+      },
+      collection: DinoparcCollection {
+        rewards: HashSet::new(),
+        epic_rewards: HashSet::new(),
+      },
+    })
+    .await
+    .unwrap();
+  {
+    let actual = api
+      .dinoparc_store
+      .get_stale_users(&GetStaleDinoparcUsersOptions {
+        server: DinoparcServer::DinoparcCom,
+        retrieved_before: Instant::ymd_hms(2021, 1, 2, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![bob.clone()];
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .dinoparc_store
+      .get_stale_users(&GetStaleDinoparcUsersOptions {
+        server: DinoparcServer::DinoparcCom,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![bob.clone(), alice.clone()];
+    assert_eq!(actual, expected);
+  }
+  api.dinoparc_store.touch_refresh_attempt(bob.as_ref()).await.unwrap();
+  {
+    let actual = api
+      .dinoparc_store
+      .get_stale_users(&GetStaleDinoparcUsersOptions {
+        server: DinoparcServer::DinoparcCom,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 2, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![alice.clone()];
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .dinoparc_store
+      .get_stale_users(&GetStaleDinoparcUsersOptions {
+        server: DinoparcServer::DinoparcCom,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 1,
+      })
+      .await
+      .unwrap();
+    let expected = vec![alice];
+    assert_eq!(actual, expected);
+  }
+}
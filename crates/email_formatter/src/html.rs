@@ -1,9 +1,82 @@
+mod locales;
+
+use self::locales::{get_catalog, Message};
 use async_trait::async_trait;
 use etwin_core::core::LocaleId;
-use etwin_core::email::{EmailContent, EmailFormatter, VerifyRegistrationEmail};
+use etwin_core::email::{
+  EmailContent, EmailFormatter, NotifyEmailChangeEmail, VerifyEmailChangeEmail, VerifyRegistrationEmail,
+};
 use etwin_core::types::AnyError;
+use url::Url;
+
+/// Email formatter rendering the messages of the locale catalogs, as text and HTML.
+pub struct HtmlEmailFormatter {
+  /// Public URI of the Eternaltwin server, used to build the links
+  external_uri: Url,
+}
+
+impl HtmlEmailFormatter {
+  pub fn new(external_uri: Url) -> Self {
+    Self { external_uri }
+  }
+
+  fn token_uri(&self, path: &str, token: &str) -> Result<Url, AnyError> {
+    let mut uri = self.external_uri.join(path)?;
+    uri.query_pairs_mut().append_pair("token", token);
+    Ok(uri)
+  }
+}
 
-pub struct HtmlEmailFormatter;
+/// Values for the placeholders of a message
+#[derive(Default)]
+struct Args<'a> {
+  link: Option<&'a str>,
+  date: Option<&'a str>,
+}
+
+fn render(lang: &str, message: &Message, args: &Args) -> Result<EmailContent, AnyError> {
+  let mut body_text = String::new();
+  let mut body_html = format!("<div lang=\"{}\">\n", lang);
+  for paragraph in message.paragraphs {
+    let mut text = paragraph.to_string();
+    let mut html = escape_html(paragraph);
+    if let Some(link) = args.link {
+      text = text.replace("{link}", link);
+      let link = escape_html(link);
+      html = html.replace("{link}", &format!("<a href=\"{}\">{}</a>", link, link));
+    }
+    if let Some(date) = args.date {
+      text = text.replace("{date}", date);
+      html = html.replace("{date}", &escape_html(date));
+    }
+    body_text.push_str(&text);
+    body_text.push('\n');
+    body_html.push_str("<p>");
+    body_html.push_str(&html);
+    body_html.push_str("</p>\n");
+  }
+  body_html.push_str("</div>\n");
+  Ok(EmailContent {
+    title: message.title.parse()?,
+    body_text: body_text.parse()?,
+    body_html: Some(body_html),
+  })
+}
+
+fn escape_html(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
 
 #[async_trait]
 impl EmailFormatter for HtmlEmailFormatter {
@@ -12,33 +85,50 @@ impl EmailFormatter for HtmlEmailFormatter {
     locale: LocaleId,
     data: &VerifyRegistrationEmail,
   ) -> Result<EmailContent, AnyError> {
-    let registration_uri = format!(
-      "https://eternal-twin.net/register/verified-email?token={}",
-      data.token.as_str()
-    );
-    let content = match locale {
-      LocaleId::FrFr => EmailContent {
-        title: "Inscription à Eternaltwin".parse().unwrap(),
-        body_text: format!(
-          "Bienvenue sur Eternaltwin !\nVeuillez cliquez sur le lien suivant pour valider votre inscription : {}\n",
-          registration_uri
-        )
-        .parse()
-        .unwrap(),
-        body_html: None,
+    let catalog = get_catalog(locale);
+    let registration_uri = self.token_uri("register/verified-email", data.token.as_str())?;
+    render(
+      catalog.lang,
+      &catalog.verify_registration,
+      &Args {
+        link: Some(registration_uri.as_str()),
+        ..Args::default()
       },
-      _ => EmailContent {
-        title: "Eternaltwin registration".parse().unwrap(),
-        body_text: format!(
-          "Welcome to Eternaltwin!\nPlease click on the following link to complete your registration: {}\n",
-          registration_uri
-        )
-        .parse()
-        .unwrap(),
-        body_html: None,
+    )
+  }
+
+  async fn verify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &VerifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError> {
+    let catalog = get_catalog(locale);
+    let verification_uri = self.token_uri("settings/verified-email", data.token.as_str())?;
+    render(
+      catalog.lang,
+      &catalog.verify_email_change,
+      &Args {
+        link: Some(verification_uri.as_str()),
+        ..Args::default()
       },
-    };
-    Ok(content)
+    )
+  }
+
+  async fn notify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &NotifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError> {
+    let catalog = get_catalog(locale);
+    let changed_at = data.changed_at.to_string();
+    render(
+      catalog.lang,
+      &catalog.notify_email_change,
+      &Args {
+        date: Some(changed_at.as_str()),
+        ..Args::default()
+      },
+    )
   }
 }
 
@@ -48,12 +138,20 @@ impl neon::prelude::Finalize for HtmlEmailFormatter {}
 #[cfg(test)]
 mod test {
   use crate::html::HtmlEmailFormatter;
-  use etwin_core::core::LocaleId;
-  use etwin_core::email::{EmailContent, EmailFormatter, VerifyRegistrationEmail};
+  use enum_iterator::IntoEnumIterator;
+  use etwin_core::core::{Instant, LocaleId};
+  use etwin_core::email::{
+    EmailContent, EmailFormatter, NotifyEmailChangeEmail, VerifyEmailChangeEmail, VerifyRegistrationEmail,
+  };
+  use url::Url;
+
+  fn formatter() -> HtmlEmailFormatter {
+    HtmlEmailFormatter::new(Url::parse("https://eternal-twin.net/").unwrap())
+  }
 
   #[tokio::test]
   async fn verify_registration_en() {
-    let formatter = HtmlEmailFormatter;
+    let formatter = formatter();
 
     let actual = formatter
       .verify_registration_email(
@@ -72,14 +170,21 @@ Please click on the following link to complete your registration: https://eterna
 "#
       .parse()
       .unwrap(),
-      body_html: None,
+      body_html: Some(
+        r#"<div lang="en">
+<p>Welcome to Eternaltwin!</p>
+<p>Please click on the following link to complete your registration: <a href="https://eternal-twin.net/register/verified-email?token=abcdef">https://eternal-twin.net/register/verified-email?token=abcdef</a></p>
+</div>
+"#
+        .to_string(),
+      ),
     };
 
     assert_eq!(actual, expected);
   }
   #[tokio::test]
   async fn verify_registration_fr() {
-    let formatter = HtmlEmailFormatter;
+    let formatter = formatter();
 
     let actual = formatter
       .verify_registration_email(
@@ -94,13 +199,109 @@ Please click on the following link to complete your registration: https://eterna
     let expected = EmailContent {
       title: "Inscription à Eternaltwin".parse().unwrap(),
       body_text: r#"Bienvenue sur Eternaltwin !
-Veuillez cliquez sur le lien suivant pour valider votre inscription : https://eternal-twin.net/register/verified-email?token=abcdef
+Veuillez cliquer sur le lien suivant pour valider votre inscription : https://eternal-twin.net/register/verified-email?token=abcdef
 "#
       .parse()
       .unwrap(),
-      body_html: None,
+      body_html: Some(
+        r#"<div lang="fr">
+<p>Bienvenue sur Eternaltwin !</p>
+<p>Veuillez cliquer sur le lien suivant pour valider votre inscription : <a href="https://eternal-twin.net/register/verified-email?token=abcdef">https://eternal-twin.net/register/verified-email?token=abcdef</a></p>
+</div>
+"#
+        .to_string(),
+      ),
     };
 
     assert_eq!(actual, expected);
   }
+
+  #[tokio::test]
+  async fn links_use_the_external_uri() {
+    let formatter = HtmlEmailFormatter::new(Url::parse("http://localhost:50320").unwrap());
+
+    let actual = formatter
+      .verify_email_change_email(
+        LocaleId::EnUs,
+        &VerifyEmailChangeEmail {
+          token: "a&b".to_string(),
+        },
+      )
+      .await
+      .unwrap();
+
+    assert_eq!(
+      actual.body_text.as_str(),
+      "Please click on the following link to use this address with your Eternaltwin account: http://localhost:50320/settings/verified-email?token=a%26b\n"
+    );
+  }
+
+  #[tokio::test]
+  async fn all_locales_render_all_emails() {
+    let formatter = formatter();
+    let en = formatter
+      .verify_registration_email(
+        LocaleId::EnUs,
+        &VerifyRegistrationEmail {
+          token: "abcdef".to_string(),
+        },
+      )
+      .await
+      .unwrap();
+
+    for locale in LocaleId::into_enum_iter() {
+      let registration = formatter
+        .verify_registration_email(
+          locale,
+          &VerifyRegistrationEmail {
+            token: "abcdef".to_string(),
+          },
+        )
+        .await
+        .unwrap();
+      let email_change = formatter
+        .verify_email_change_email(
+          locale,
+          &VerifyEmailChangeEmail {
+            token: "abcdef".to_string(),
+          },
+        )
+        .await
+        .unwrap();
+      let notification = formatter
+        .notify_email_change_email(
+          locale,
+          &NotifyEmailChangeEmail {
+            changed_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+          },
+        )
+        .await
+        .unwrap();
+
+      for (content, expected) in [
+        (
+          &registration,
+          "https://eternal-twin.net/register/verified-email?token=abcdef",
+        ),
+        (
+          &email_change,
+          "https://eternal-twin.net/settings/verified-email?token=abcdef",
+        ),
+        (&notification, "2021-01-01 00:00:00 UTC"),
+      ] {
+        assert!(
+          content.body_text.as_str().contains(expected),
+          "{} {:?}",
+          locale,
+          content
+        );
+        let body_html = content.body_html.as_deref().unwrap();
+        assert!(body_html.contains(expected), "{} {:?}", locale, content);
+        assert!(!body_html.contains('{'), "{} {:?}", locale, content);
+      }
+      if locale != LocaleId::EnUs {
+        assert_ne!(registration.title, en.title, "{} must not fall back to English", locale);
+      }
+    }
+  }
 }
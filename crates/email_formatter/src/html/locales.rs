@@ -0,0 +1,35 @@
+//! Message catalogs, one per locale.
+//!
+//! Messages are plain text. Each item of `paragraphs` is rendered as a line of the text body and as a `<p>` element
+//! of the HTML body. The `{link}` and `{date}` placeholders are replaced by the values of the email.
+
+mod de_de;
+mod en_us;
+mod eo;
+mod es_sp;
+mod fr_fr;
+
+use etwin_core::core::LocaleId;
+
+pub(crate) struct Catalog {
+  /// Value of the HTML `lang` attribute
+  pub lang: &'static str,
+  pub verify_registration: Message,
+  pub verify_email_change: Message,
+  pub notify_email_change: Message,
+}
+
+pub(crate) struct Message {
+  pub title: &'static str,
+  pub paragraphs: &'static [&'static str],
+}
+
+pub(crate) fn get_catalog(locale: LocaleId) -> &'static Catalog {
+  match locale {
+    LocaleId::DeDe => &de_de::CATALOG,
+    LocaleId::EnUs => &en_us::CATALOG,
+    LocaleId::Eo => &eo::CATALOG,
+    LocaleId::EsSp => &es_sp::CATALOG,
+    LocaleId::FrFr => &fr_fr::CATALOG,
+  }
+}
@@ -0,0 +1,25 @@
+use super::{Catalog, Message};
+
+pub(super) const CATALOG: Catalog = Catalog {
+  lang: "de",
+  verify_registration: Message {
+    title: "Anmeldung bei Eternaltwin",
+    paragraphs: &[
+      "Willkommen bei Eternaltwin!",
+      "Bitte klicke auf den folgenden Link, um deine Anmeldung abzuschließen: {link}",
+    ],
+  },
+  verify_email_change: Message {
+    title: "Änderung der E-Mail-Adresse bei Eternaltwin",
+    paragraphs: &[
+      "Bitte klicke auf den folgenden Link, um diese Adresse mit deinem Eternaltwin-Konto zu verwenden: {link}",
+    ],
+  },
+  notify_email_change: Message {
+    title: "Änderung der E-Mail-Adresse bei Eternaltwin",
+    paragraphs: &[
+      "Die E-Mail-Adresse deines Eternaltwin-Kontos wurde am {date} geändert.",
+      "Diese Adresse ist nicht mehr mit deinem Konto verknüpft.",
+    ],
+  },
+};
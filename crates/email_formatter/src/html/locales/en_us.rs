@@ -0,0 +1,23 @@
+use super::{Catalog, Message};
+
+pub(super) const CATALOG: Catalog = Catalog {
+  lang: "en",
+  verify_registration: Message {
+    title: "Eternaltwin registration",
+    paragraphs: &[
+      "Welcome to Eternaltwin!",
+      "Please click on the following link to complete your registration: {link}",
+    ],
+  },
+  verify_email_change: Message {
+    title: "Eternaltwin email address change",
+    paragraphs: &["Please click on the following link to use this address with your Eternaltwin account: {link}"],
+  },
+  notify_email_change: Message {
+    title: "Eternaltwin email address change",
+    paragraphs: &[
+      "The email address of your Eternaltwin account was changed on {date}.",
+      "This address is no longer associated with your account.",
+    ],
+  },
+};
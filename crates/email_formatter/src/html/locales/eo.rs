@@ -0,0 +1,23 @@
+use super::{Catalog, Message};
+
+pub(super) const CATALOG: Catalog = Catalog {
+  lang: "eo",
+  verify_registration: Message {
+    title: "Aliĝo al Eternaltwin",
+    paragraphs: &[
+      "Bonvenon al Eternaltwin!",
+      "Bonvolu klaki la jenan ligilon por fini vian aliĝon: {link}",
+    ],
+  },
+  verify_email_change: Message {
+    title: "Ŝanĝo de retpoŝtadreso ĉe Eternaltwin",
+    paragraphs: &["Bonvolu klaki la jenan ligilon por uzi ĉi tiun adreson kun via Eternaltwin-konto: {link}"],
+  },
+  notify_email_change: Message {
+    title: "Ŝanĝo de retpoŝtadreso ĉe Eternaltwin",
+    paragraphs: &[
+      "La retpoŝtadreso de via Eternaltwin-konto estis ŝanĝita je {date}.",
+      "Ĉi tiu adreso ne plu estas ligita al via konto.",
+    ],
+  },
+};
@@ -0,0 +1,23 @@
+use super::{Catalog, Message};
+
+pub(super) const CATALOG: Catalog = Catalog {
+  lang: "es",
+  verify_registration: Message {
+    title: "Registro en Eternaltwin",
+    paragraphs: &[
+      "¡Bienvenido a Eternaltwin!",
+      "Haz clic en el siguiente enlace para completar tu registro: {link}",
+    ],
+  },
+  verify_email_change: Message {
+    title: "Cambio de dirección de correo de Eternaltwin",
+    paragraphs: &["Haz clic en el siguiente enlace para usar esta dirección con tu cuenta de Eternaltwin: {link}"],
+  },
+  notify_email_change: Message {
+    title: "Cambio de dirección de correo de Eternaltwin",
+    paragraphs: &[
+      "La dirección de correo de tu cuenta de Eternaltwin fue cambiada el {date}.",
+      "Esta dirección ya no está asociada a tu cuenta.",
+    ],
+  },
+};
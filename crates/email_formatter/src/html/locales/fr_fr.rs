@@ -0,0 +1,25 @@
+use super::{Catalog, Message};
+
+pub(super) const CATALOG: Catalog = Catalog {
+  lang: "fr",
+  verify_registration: Message {
+    title: "Inscription à Eternaltwin",
+    paragraphs: &[
+      "Bienvenue sur Eternaltwin !",
+      "Veuillez cliquer sur le lien suivant pour valider votre inscription : {link}",
+    ],
+  },
+  verify_email_change: Message {
+    title: "Changement d'adresse email Eternaltwin",
+    paragraphs: &[
+      "Veuillez cliquer sur le lien suivant pour utiliser cette adresse avec votre compte Eternaltwin : {link}",
+    ],
+  },
+  notify_email_change: Message {
+    title: "Changement d'adresse email Eternaltwin",
+    paragraphs: &[
+      "L'adresse email de votre compte Eternaltwin a été changée le {date}.",
+      "Cette adresse n'est plus associée à votre compte.",
+    ],
+  },
+};
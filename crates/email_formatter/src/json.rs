@@ -1,6 +1,8 @@
 use async_trait::async_trait;
 use etwin_core::core::LocaleId;
-use etwin_core::email::{EmailContent, EmailFormatter, VerifyRegistrationEmail};
+use etwin_core::email::{
+  EmailContent, EmailFormatter, NotifyEmailChangeEmail, VerifyEmailChangeEmail, VerifyRegistrationEmail,
+};
 use etwin_core::types::AnyError;
 use serde::{Deserialize, Serialize};
 
@@ -27,6 +29,34 @@ impl EmailFormatter for JsonEmailFormatter {
       body_html: None,
     })
   }
+
+  async fn verify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &VerifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError> {
+    let body = serde_json::to_string_pretty(&JsonBody { locale, data })?;
+    let body = format!("{}\n", body);
+    Ok(EmailContent {
+      title: "verifyEmailChangeEmail".parse().unwrap(),
+      body_text: body.parse().unwrap(),
+      body_html: None,
+    })
+  }
+
+  async fn notify_email_change_email(
+    &self,
+    locale: LocaleId,
+    data: &NotifyEmailChangeEmail,
+  ) -> Result<EmailContent, AnyError> {
+    let body = serde_json::to_string_pretty(&JsonBody { locale, data })?;
+    let body = format!("{}\n", body);
+    Ok(EmailContent {
+      title: "notifyEmailChangeEmail".parse().unwrap(),
+      body_text: body.parse().unwrap(),
+      body_html: None,
+    })
+  }
 }
 
 #[cfg(feature = "neon")]
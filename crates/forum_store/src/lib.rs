@@ -1 +1,6 @@
+#[cfg(test)]
+#[macro_use]
+pub(crate) mod test;
+
+pub mod mem;
 pub mod pg;
@@ -0,0 +1,459 @@
+use async_trait::async_trait;
+use etwin_core::clock::Clock;
+use etwin_core::core::{Instant, Listing, ListingCount, LocaleId};
+use etwin_core::forum::{
+  ForumActor, ForumPostId, ForumPostRevisionContent, ForumPostRevisionId, ForumRole, ForumSection,
+  ForumSectionDisplayName, ForumSectionId, ForumSectionKey, ForumSectionRef, ForumSectionSelf, ForumStore,
+  ForumThreadId, ForumThreadKey, ForumThreadListing, ForumThreadMeta, ForumThreadRef, ForumThreadTitle,
+  GetForumSectionMetaOptions, GetSectionMetaError, GetThreadMetaError, RawAddModeratorOptions,
+  RawCreateForumPostResult, RawCreateForumThreadResult, RawCreatePostOptions, RawCreateThreadsOptions, RawForumActor,
+  RawForumPostRevision, RawForumRoleGrant, RawForumSectionMeta, RawForumThreadMeta, RawGetForumThreadMetaOptions,
+  RawGetPostsOptions, RawGetRoleGrantsOptions, RawGetSectionsOptions, RawGetThreadsOptions,
+  RawLatestForumPostRevisionListing, RawShortForumPost, RawUserForumActor, UpsertSystemSectionError,
+  UpsertSystemSectionOptions,
+};
+use etwin_core::types::AnyError;
+use etwin_core::uuid::UuidGenerator;
+use std::convert::TryFrom;
+use std::sync::RwLock;
+
+const THREADS_PER_PAGE: u32 = 20;
+
+struct MemSection {
+  id: ForumSectionId,
+  key: Option<ForumSectionKey>,
+  ctime: Instant,
+  display_name: ForumSectionDisplayName,
+  locale: Option<LocaleId>,
+  role_grants: Vec<RawForumRoleGrant>,
+}
+
+struct MemThread {
+  id: ForumThreadId,
+  key: Option<ForumThreadKey>,
+  section: ForumSectionId,
+  ctime: Instant,
+  title: ForumThreadTitle,
+  is_pinned: bool,
+  is_locked: bool,
+}
+
+struct MemPost {
+  id: ForumPostId,
+  thread: ForumThreadId,
+  ctime: Instant,
+  /// Revisions, oldest first
+  revisions: Vec<RawForumPostRevision>,
+}
+
+struct StoreState {
+  /// Sections, in creation order
+  sections: Vec<MemSection>,
+  /// Threads, in creation order
+  threads: Vec<MemThread>,
+  /// Posts, in creation order
+  posts: Vec<MemPost>,
+}
+
+impl StoreState {
+  fn new() -> Self {
+    Self {
+      sections: Vec::new(),
+      threads: Vec::new(),
+      posts: Vec::new(),
+    }
+  }
+
+  fn get_section(&self, section: &ForumSectionRef) -> Option<&MemSection> {
+    self.sections.iter().find(|s| match section {
+      ForumSectionRef::Id(r) => s.id == r.id,
+      ForumSectionRef::Key(r) => s.key.as_ref() == Some(&r.key),
+    })
+  }
+
+  fn get_thread(&self, thread: &ForumThreadRef) -> Option<&MemThread> {
+    self.threads.iter().find(|t| match thread {
+      ForumThreadRef::Id(r) => t.id == r.id,
+      ForumThreadRef::Key(r) => t.key.as_ref() == Some(&r.key),
+    })
+  }
+
+  fn section_threads(&self, section: ForumSectionId) -> impl Iterator<Item = &MemThread> {
+    self.threads.iter().filter(move |t| t.section == section)
+  }
+
+  fn thread_posts(&self, thread: ForumThreadId) -> impl Iterator<Item = &MemPost> {
+    self.posts.iter().filter(move |p| p.thread == thread)
+  }
+
+  fn section_meta(&self, section: &MemSection) -> RawForumSectionMeta {
+    RawForumSectionMeta {
+      id: section.id,
+      key: section.key.clone(),
+      display_name: section.display_name.clone(),
+      ctime: section.ctime,
+      locale: section.locale,
+      threads: ListingCount {
+        count: count(self.section_threads(section.id)),
+      },
+      role_grants: section.role_grants.clone(),
+    }
+  }
+
+  fn thread_meta(&self, thread: &MemThread) -> ForumThreadMeta {
+    ForumThreadMeta {
+      id: thread.id,
+      key: thread.key.clone(),
+      title: thread.title.clone(),
+      ctime: thread.ctime,
+      is_pinned: thread.is_pinned,
+      is_locked: thread.is_locked,
+      posts: ListingCount {
+        count: count(self.thread_posts(thread.id)),
+      },
+    }
+  }
+
+  fn get_threads(&self, section: ForumSectionId, offset: u32, limit: u32) -> ForumThreadListing {
+    Listing {
+      offset,
+      limit,
+      count: count(self.section_threads(section)),
+      items: self
+        .section_threads(section)
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|t| self.thread_meta(t))
+        .collect(),
+    }
+  }
+}
+
+fn count<T>(iter: impl Iterator<Item = T>) -> u32 {
+  u32::try_from(iter.count()).expect("count overflow")
+}
+
+fn to_raw_actor(actor: &ForumActor) -> Result<RawForumActor, AnyError> {
+  match actor {
+    ForumActor::UserForumActor(a) => Ok(RawForumActor::UserForumActor(RawUserForumActor {
+      role: None,
+      user: a.user.as_ref(),
+    })),
+    _ => Err("UnsupportedForumActor".into()),
+  }
+}
+
+pub struct MemForumStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  clock: TyClock,
+  uuid_generator: TyUuidGenerator,
+  state: RwLock<StoreState>,
+}
+
+impl<TyClock, TyUuidGenerator> MemForumStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  pub fn new(clock: TyClock, uuid_generator: TyUuidGenerator) -> Self {
+    Self {
+      clock,
+      uuid_generator,
+      state: RwLock::new(StoreState::new()),
+    }
+  }
+}
+
+#[async_trait]
+impl<TyClock, TyUuidGenerator> ForumStore for MemForumStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  async fn add_moderator(&self, options: &RawAddModeratorOptions) -> Result<(), AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    let section = state.sections.iter_mut().find(|s| match &options.section {
+      ForumSectionRef::Id(r) => s.id == r.id,
+      ForumSectionRef::Key(r) => s.key.as_ref() == Some(&r.key),
+    });
+    if let Some(section) = section {
+      if !section.role_grants.iter().any(|g| g.user.id == options.grantee.id) {
+        section.role_grants.push(RawForumRoleGrant {
+          role: ForumRole::Moderator,
+          user: options.grantee,
+          start_time: now,
+          granted_by: options.granter,
+        });
+      }
+    }
+    Ok(())
+  }
+
+  async fn get_sections(&self, options: &RawGetSectionsOptions) -> Result<Listing<RawForumSectionMeta>, AnyError> {
+    let state = self.state.read().unwrap();
+    let mut sections: Vec<&MemSection> = state.sections.iter().collect();
+    sections.sort_by(|a, b| (a.ctime, &a.key, a.id).cmp(&(b.ctime, &b.key, b.id)));
+    Ok(Listing {
+      offset: options.offset,
+      limit: options.limit,
+      count: count(sections.iter()),
+      items: sections
+        .into_iter()
+        .skip(options.offset as usize)
+        .take(options.limit as usize)
+        .map(|s| state.section_meta(s))
+        .collect(),
+    })
+  }
+
+  async fn get_section_meta(
+    &self,
+    options: &GetForumSectionMetaOptions,
+  ) -> Result<RawForumSectionMeta, GetSectionMetaError> {
+    let state = self.state.read().unwrap();
+    let section = state
+      .get_section(&options.section)
+      .ok_or(GetSectionMetaError::NotFound)?;
+    Ok(state.section_meta(section))
+  }
+
+  async fn get_threads(&self, options: &RawGetThreadsOptions) -> Result<ForumThreadListing, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(match state.get_section(&options.section) {
+      Some(section) => state.get_threads(section.id, options.offset, options.limit),
+      None => Listing {
+        offset: options.offset,
+        limit: options.limit,
+        count: 0,
+        items: vec![],
+      },
+    })
+  }
+
+  async fn get_thread_meta(
+    &self,
+    options: &RawGetForumThreadMetaOptions,
+  ) -> Result<RawForumThreadMeta, GetThreadMetaError> {
+    let state = self.state.read().unwrap();
+    let thread = state.get_thread(&options.thread).ok_or(GetThreadMetaError::NotFound)?;
+    let meta = state.thread_meta(thread);
+    Ok(RawForumThreadMeta {
+      id: meta.id,
+      key: meta.key,
+      title: meta.title,
+      section: thread.section.into(),
+      ctime: meta.ctime,
+      is_pinned: meta.is_pinned,
+      is_locked: meta.is_locked,
+      posts: meta.posts,
+    })
+  }
+
+  async fn create_thread(&self, options: &RawCreateThreadsOptions) -> Result<RawCreateForumThreadResult, AnyError> {
+    let now = self.clock.now();
+    let author = to_raw_actor(&options.actor)?;
+    let mut state = self.state.write().unwrap();
+    let section = state.get_section(&options.section).ok_or("ForumSectionNotFound")?.id;
+    let thread = MemThread {
+      id: ForumThreadId::from_uuid(self.uuid_generator.next()),
+      key: None,
+      section,
+      ctime: now,
+      title: options.title.clone(),
+      is_pinned: false,
+      is_locked: false,
+    };
+    let revision = RawForumPostRevision {
+      id: ForumPostRevisionId::from_uuid(self.uuid_generator.next()),
+      time: now,
+      author,
+      content: Some(ForumPostRevisionContent {
+        marktwin: options.body_mkt.clone(),
+        html: options.body_html.clone(),
+      }),
+      moderation: None,
+      comment: None,
+    };
+    let post = MemPost {
+      id: ForumPostId::from_uuid(self.uuid_generator.next()),
+      thread: thread.id,
+      ctime: now,
+      revisions: vec![revision.clone()],
+    };
+    let result = RawCreateForumThreadResult {
+      id: thread.id,
+      key: None,
+      title: thread.title.clone(),
+      section: section.into(),
+      ctime: thread.ctime,
+      is_pinned: false,
+      is_locked: false,
+      post_id: post.id,
+      post_revision: revision,
+    };
+    state.threads.push(thread);
+    state.posts.push(post);
+    Ok(result)
+  }
+
+  async fn get_posts(&self, options: &RawGetPostsOptions) -> Result<Listing<RawShortForumPost>, AnyError> {
+    let state = self.state.read().unwrap();
+    let thread = match state.get_thread(&options.thread) {
+      Some(thread) => thread.id,
+      None => {
+        return Ok(Listing {
+          offset: options.offset,
+          limit: options.limit,
+          count: 0,
+          items: vec![],
+        })
+      }
+    };
+    Ok(Listing {
+      offset: options.offset,
+      limit: options.limit,
+      count: count(state.thread_posts(thread)),
+      items: state
+        .thread_posts(thread)
+        .skip(options.offset as usize)
+        .take(options.limit as usize)
+        .map(|post| {
+          let first = post.revisions.first().expect("post without revision");
+          let last = post.revisions.last().expect("post without revision");
+          RawShortForumPost {
+            id: post.id,
+            ctime: post.ctime,
+            author: first.author.clone(),
+            revisions: RawLatestForumPostRevisionListing {
+              count: count(post.revisions.iter()),
+              last: last.clone(),
+            },
+          }
+        })
+        .collect(),
+    })
+  }
+
+  async fn create_post(&self, options: &RawCreatePostOptions) -> Result<RawCreateForumPostResult, AnyError> {
+    let now = self.clock.now();
+    let author = to_raw_actor(&options.actor)?;
+    let mut state = self.state.write().unwrap();
+    let thread = state.get_thread(&options.thread).ok_or("ForumThreadNotFound")?;
+    let (thread, section) = (thread.id, thread.section);
+    let revision = RawForumPostRevision {
+      id: ForumPostRevisionId::from_uuid(self.uuid_generator.next()),
+      time: now,
+      author,
+      content: Some(ForumPostRevisionContent {
+        marktwin: options.body_mkt.clone(),
+        html: options.body_html.clone(),
+      }),
+      moderation: None,
+      comment: None,
+    };
+    let post = MemPost {
+      id: ForumPostId::from_uuid(self.uuid_generator.next()),
+      thread,
+      ctime: now,
+      revisions: vec![revision.clone()],
+    };
+    let result = RawCreateForumPostResult {
+      id: post.id,
+      thread: thread.into(),
+      section: section.into(),
+      revision,
+    };
+    state.posts.push(post);
+    Ok(result)
+  }
+
+  async fn get_role_grants(&self, options: &RawGetRoleGrantsOptions) -> Result<Vec<RawForumRoleGrant>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(
+      state
+        .sections
+        .iter()
+        .find(|s| s.id == options.section.id)
+        .map(|s| s.role_grants.clone())
+        .unwrap_or_default(),
+    )
+  }
+
+  async fn upsert_system_section(
+    &self,
+    options: &UpsertSystemSectionOptions,
+  ) -> Result<ForumSection, UpsertSystemSectionError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    let existing = state.sections.iter_mut().find(|s| s.key.as_ref() == Some(&options.key));
+    let id = match existing {
+      Some(section) => {
+        section.display_name = options.display_name.clone();
+        section.locale = options.locale;
+        section.id
+      }
+      None => {
+        let id = ForumSectionId::from_uuid(self.uuid_generator.next());
+        state.sections.push(MemSection {
+          id,
+          key: Some(options.key.clone()),
+          ctime: now,
+          display_name: options.display_name.clone(),
+          locale: options.locale,
+          role_grants: Vec::new(),
+        });
+        id
+      }
+    };
+    let section = state.sections.iter().find(|s| s.id == id).unwrap();
+    Ok(ForumSection {
+      id: section.id,
+      key: section.key.clone(),
+      display_name: section.display_name.clone(),
+      ctime: section.ctime,
+      locale: section.locale,
+      threads: state.get_threads(section.id, 0, THREADS_PER_PAGE),
+      role_grants: vec![],
+      this: ForumSectionSelf { roles: vec![] },
+    })
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyUuidGenerator> neon::prelude::Finalize for MemForumStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+}
+
+#[cfg(test)]
+mod test {
+  use crate::mem::MemForumStore;
+  use crate::test::TestApi;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use etwin_core::forum::ForumStore;
+  use etwin_core::user::UserStore;
+  use etwin_core::uuid::Uuid4Generator;
+  use etwin_user_store::mem::MemUserStore;
+  use std::sync::Arc;
+
+  fn make_test_api() -> TestApi<Arc<VirtualClock>, Arc<dyn ForumStore>, Arc<dyn UserStore>> {
+    let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+    let forum_store: Arc<dyn ForumStore> = Arc::new(MemForumStore::new(Arc::clone(&clock), Uuid4Generator));
+    let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Uuid4Generator));
+
+    TestApi {
+      clock,
+      forum_store,
+      user_store,
+    }
+  }
+
+  test_forum_store!(|| make_test_api());
+}
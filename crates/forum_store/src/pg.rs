@@ -705,8 +705,36 @@ where
     })
   }
 
-  async fn get_role_grants(&self, _options: &RawGetRoleGrantsOptions) -> Result<Vec<ForumRoleGrant>, AnyError> {
-    todo!()
+  async fn get_role_grants(&self, options: &RawGetRoleGrantsOptions) -> Result<Vec<RawForumRoleGrant>, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      user_id: UserId,
+      start_time: Instant,
+      granted_by: UserId,
+    }
+    // language=PostgreSQL
+    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+      r"
+        SELECT user_id, start_time, granted_by
+        FROM forum_role_grants
+        WHERE forum_section_id = $1::FORUM_SECTION_ID
+        ORDER BY start_time, user_id;
+    ",
+    )
+    .bind(options.section.id)
+    .fetch_all(self.database.as_ref())
+    .await?;
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| RawForumRoleGrant {
+          role: ForumRole::Moderator,
+          user: row.user_id.into(),
+          start_time: row.start_time,
+          granted_by: row.granted_by.into(),
+        })
+        .collect(),
+    )
   }
 
   async fn upsert_system_section(
@@ -851,3 +879,76 @@ async fn get_role_grants_tx() -> Vec<ForumRoleGrant> {
 async fn get_section_self_tx() -> ForumSectionSelf {
   ForumSectionSelf { roles: vec![] }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::pg::PgForumStore;
+  use crate::test::TestApi;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::{Instant, Secret};
+  use etwin_core::forum::ForumStore;
+  use etwin_core::user::UserStore;
+  use etwin_core::uuid::Uuid4Generator;
+  use etwin_db_schema::force_create_latest;
+  use etwin_user_store::pg::PgUserStore;
+  use serial_test::serial;
+  use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+  use sqlx::PgPool;
+  use std::sync::Arc;
+
+  async fn make_test_api() -> TestApi<Arc<VirtualClock>, Arc<dyn ForumStore>, Arc<dyn UserStore>> {
+    let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
+    let admin_database: PgPool = PgPoolOptions::new()
+      .max_connections(5)
+      .connect_with(
+        PgConnectOptions::new()
+          .host(&config.db.host)
+          .port(config.db.port)
+          .database(&config.db.name)
+          .username(&config.db.admin_user)
+          .password(&config.db.admin_password),
+      )
+      .await
+      .unwrap();
+    force_create_latest(&admin_database, true).await.unwrap();
+    admin_database.close().await;
+
+    let database: PgPool = PgPoolOptions::new()
+      .max_connections(5)
+      .connect_with(
+        PgConnectOptions::new()
+          .host(&config.db.host)
+          .port(config.db.port)
+          .database(&config.db.name)
+          .username(&config.db.user)
+          .password(&config.db.password),
+      )
+      .await
+      .unwrap();
+    let database = Arc::new(database);
+
+    let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+    let forum_store: Arc<dyn ForumStore> = Arc::new(PgForumStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Uuid4Generator,
+    ));
+    let user_store: Arc<dyn UserStore> = Arc::new(PgUserStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Secret::new("dev_secret".to_string()),
+      Uuid4Generator,
+    ));
+
+    TestApi {
+      clock,
+      forum_store,
+      user_store,
+    }
+  }
+
+  test_forum_store!(
+    #[serial]
+    || make_test_api().await
+  );
+}
@@ -0,0 +1,159 @@
+use chrono::Duration;
+use etwin_core::api::ApiRef;
+use etwin_core::clock::VirtualClock;
+use etwin_core::core::Instant;
+use etwin_core::forum::{
+  ForumRole, ForumSectionIdRef, ForumStore, RawAddModeratorOptions, RawForumRoleGrant, RawGetRoleGrantsOptions,
+  UpsertSystemSectionOptions,
+};
+use etwin_core::user::{CreateUserOptions, UserStore};
+
+#[macro_export]
+macro_rules! test_forum_store {
+  ($(#[$meta:meta])* || $api:expr) => {
+    register_test!($(#[$meta])*, $api, test_get_role_grants);
+    register_test!($(#[$meta])*, $api, test_get_role_grants_empty);
+  };
+}
+
+macro_rules! register_test {
+  ($(#[$meta:meta])*, $api:expr, $test_name:ident) => {
+    #[tokio::test]
+    $(#[$meta])*
+    async fn $test_name() {
+      crate::test::$test_name($api).await;
+    }
+  };
+}
+
+pub(crate) struct TestApi<TyClock, TyForumStore, TyUserStore>
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyForumStore: ForumStore,
+  TyUserStore: UserStore,
+{
+  pub(crate) clock: TyClock,
+  pub(crate) forum_store: TyForumStore,
+  pub(crate) user_store: TyUserStore,
+}
+
+pub(crate) async fn test_get_role_grants<TyClock, TyForumStore, TyUserStore>(
+  api: TestApi<TyClock, TyForumStore, TyUserStore>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+  TyForumStore: ForumStore,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      email: None,
+      username: Some("alice".parse().unwrap()),
+      password: None,
+    })
+    .await
+    .unwrap();
+  let bob = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Bob".parse().unwrap(),
+      email: None,
+      username: Some("bob".parse().unwrap()),
+      password: None,
+    })
+    .await
+    .unwrap();
+  let section = api
+    .forum_store
+    .upsert_system_section(&UpsertSystemSectionOptions {
+      key: "fr_main".parse().unwrap(),
+      display_name: "Forum Général".parse().unwrap(),
+      locale: Some("fr-FR".parse().unwrap()),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .forum_store
+    .add_moderator(&RawAddModeratorOptions {
+      section: ForumSectionIdRef { id: section.id }.into(),
+      grantee: bob.id.into(),
+      granter: alice.id.into(),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .forum_store
+    .add_moderator(&RawAddModeratorOptions {
+      section: ForumSectionIdRef { id: section.id }.into(),
+      grantee: alice.id.into(),
+      granter: alice.id.into(),
+    })
+    .await
+    .unwrap();
+  // Granting an existing role again keeps the original grant
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .forum_store
+    .add_moderator(&RawAddModeratorOptions {
+      section: ForumSectionIdRef { id: section.id }.into(),
+      grantee: bob.id.into(),
+      granter: bob.id.into(),
+    })
+    .await
+    .unwrap();
+
+  let actual = api
+    .forum_store
+    .get_role_grants(&RawGetRoleGrantsOptions {
+      section: ForumSectionIdRef { id: section.id },
+    })
+    .await
+    .unwrap();
+  let expected = vec![
+    RawForumRoleGrant {
+      role: ForumRole::Moderator,
+      user: bob.id.into(),
+      start_time: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+      granted_by: alice.id.into(),
+    },
+    RawForumRoleGrant {
+      role: ForumRole::Moderator,
+      user: alice.id.into(),
+      start_time: Instant::ymd_hms(2021, 1, 1, 0, 0, 2),
+      granted_by: alice.id.into(),
+    },
+  ];
+  assert_eq!(actual, expected);
+}
+
+pub(crate) async fn test_get_role_grants_empty<TyClock, TyForumStore, TyUserStore>(
+  api: TestApi<TyClock, TyForumStore, TyUserStore>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+  TyForumStore: ForumStore,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let section = api
+    .forum_store
+    .upsert_system_section(&UpsertSystemSectionOptions {
+      key: "fr_main".parse().unwrap(),
+      display_name: "Forum Général".parse().unwrap(),
+      locale: Some("fr-FR".parse().unwrap()),
+    })
+    .await
+    .unwrap();
+
+  let actual = api
+    .forum_store
+    .get_role_grants(&RawGetRoleGrantsOptions {
+      section: ForumSectionIdRef { id: section.id },
+    })
+    .await
+    .unwrap();
+  assert_eq!(actual, vec![]);
+}
@@ -7,6 +7,7 @@ mod url;
 use self::errors::ScraperError;
 use self::url::HammerfestUrls;
 use async_trait::async_trait;
+use etwin_core::auth::InvalidCredentialsError;
 use etwin_core::clock::Clock;
 use etwin_core::dns::DnsResolver;
 use etwin_core::hammerfest::*;
@@ -113,14 +114,14 @@ where
     if resp.status() != StatusCode::FOUND {
       let text = resp.error_for_status()?.text().await?;
       let html = scraper::Html::parse_document(&text);
-      return Err(
-        if scraper::is_login_page_error(&html) {
-          ScraperError::InvalidCredentials(options.server, options.username.clone())
-        } else {
-          ScraperError::UnexpectedResponse(urls.login())
+      return Err(if scraper::is_login_page_error(&html) {
+        InvalidCredentialsError {
+          reason: "WrongHammerfestCredentials",
         }
-        .into(),
-      );
+        .into()
+      } else {
+        ScraperError::UnexpectedResponse(urls.login()).into()
+      });
     }
 
     let session_key = resp
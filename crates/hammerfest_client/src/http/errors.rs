@@ -8,8 +8,6 @@ use thiserror::Error;
 pub enum ScraperError {
   #[error("EVNI page returned from Hammerfest")]
   Evni,
-  #[error("Invalid credentials on {} for username: {}", .0.as_str(), .1.as_str())]
-  InvalidCredentials(HammerfestServer, HammerfestUsername),
   #[error("Missing Hammerfest session cookie from response")]
   MissingSessionCookie,
   #[error("Hammerfest session cookie is invalid or malformed")]
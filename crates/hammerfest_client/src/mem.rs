@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use etwin_core::auth::InvalidCredentialsError;
 use etwin_core::clock::Clock;
 use etwin_core::core::Instant;
 use etwin_core::hammerfest::*;
@@ -15,8 +16,6 @@ type Result<T> = std::result::Result<T, AnyError>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-  #[error("Invalid credentials")]
-  InvalidCredentials,
   #[error("Server not found: {:?}", .0)]
   ServerNotFound(HammerfestServer),
   #[error("Invalid session")]
@@ -373,7 +372,9 @@ where
           None
         }
       })
-      .ok_or(Error::InvalidCredentials)?;
+      .ok_or(InvalidCredentialsError {
+        reason: "WrongHammerfestCredentials",
+      })?;
     if let Some(key) = old_session {
       server.active_sessions.remove(&key);
     }
@@ -1,9 +1,12 @@
 use async_trait::async_trait;
 use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use etwin_core::email::EmailAddress;
 use etwin_core::hammerfest::{
-  GetHammerfestUserOptions, HammerfestForumThemePageResponse, HammerfestForumThreadPageResponse,
-  HammerfestGodchildrenResponse, HammerfestInventoryResponse, HammerfestProfileResponse, HammerfestShopResponse,
-  HammerfestStore, HammerfestUserId, ShortHammerfestUser, StoredHammerfestUser,
+  GetHammerfestUserOptions, GetStaleHammerfestUsersOptions, HammerfestForumThemePageResponse,
+  HammerfestForumThreadPageResponse, HammerfestGodchildrenResponse, HammerfestInventoryResponse,
+  HammerfestProfileResponse, HammerfestSessionUser, HammerfestShopResponse, HammerfestStore, HammerfestUserId,
+  HammerfestUserIdRef, HammerfestUserPrivateData, ShortHammerfestUser, StoredHammerfestUser,
 };
 use etwin_core::types::AnyError;
 use std::collections::HashMap;
@@ -11,11 +14,19 @@ use std::sync::RwLock;
 
 struct StoreState {
   users: HashMap<HammerfestUserId, StoredHammerfestUser>,
+  profile_retrieved_at: HashMap<HammerfestUserId, Instant>,
+  refresh_attempted_at: HashMap<HammerfestUserIdRef, Instant>,
+  private_data: HashMap<HammerfestUserIdRef, HammerfestUserPrivateData>,
 }
 
 impl StoreState {
   fn new() -> Self {
-    Self { users: HashMap::new() }
+    Self {
+      users: HashMap::new(),
+      profile_retrieved_at: HashMap::new(),
+      refresh_attempted_at: HashMap::new(),
+      private_data: HashMap::new(),
+    }
   }
 
   fn get_user(&self, id: &HammerfestUserId) -> Option<&StoredHammerfestUser> {
@@ -25,6 +36,59 @@ impl StoreState {
   fn touch_user(&mut self, user: StoredHammerfestUser) {
     self.users.insert(user.id, user);
   }
+
+  fn touch_profile(&mut self, now: Instant, user: &ShortHammerfestUser) {
+    match self.users.get_mut(&user.id) {
+      Some(stored) => stored.username = user.username.clone(),
+      None => self.touch_user(StoredHammerfestUser {
+        server: user.server,
+        id: user.id,
+        username: user.username.clone(),
+        archived_at: now,
+        profile: None,
+        items: None,
+      }),
+    }
+    self.profile_retrieved_at.insert(user.id, now);
+  }
+
+  fn touch_tokens(&mut self, session: &HammerfestSessionUser) {
+    let data = self.private_data.entry(session.user.as_ref()).or_default();
+    data.tokens = Some(session.tokens);
+  }
+
+  fn touch_email(&mut self, user: HammerfestUserIdRef, email: Option<EmailAddress>) {
+    let data = self.private_data.entry(user).or_default();
+    data.email = Some(email);
+  }
+
+  fn get_stale_users(&self, options: &GetStaleHammerfestUsersOptions) -> Vec<ShortHammerfestUser> {
+    let mut stale: Vec<(Option<Instant>, Instant, &StoredHammerfestUser)> = self
+      .users
+      .values()
+      .filter(|u| u.server == options.server)
+      .map(|u| {
+        let attempted_at = self
+          .refresh_attempted_at
+          .get(&HammerfestUserIdRef {
+            server: u.server,
+            id: u.id,
+          })
+          .copied();
+        let retrieved_at = self.profile_retrieved_at.get(&u.id).copied().unwrap_or(u.archived_at);
+        (attempted_at, retrieved_at, u)
+      })
+      .filter(|(attempted_at, retrieved_at, _)| {
+        *retrieved_at < options.retrieved_before && !matches!(attempted_at, Some(t) if *t >= options.attempted_before)
+      })
+      .collect();
+    stale.sort_by_key(|(attempted_at, retrieved_at, u)| (*attempted_at, *retrieved_at, u.id));
+    stale
+      .into_iter()
+      .take(usize::try_from(options.limit).unwrap())
+      .map(|(_, _, u)| ShortHammerfestUser::from(u.clone()))
+      .collect()
+  }
 }
 
 pub struct MemHammerfestStore<TyClock: Clock> {
@@ -74,23 +138,57 @@ where
     Ok(user)
   }
 
-  async fn touch_shop(&self, _response: &HammerfestShopResponse) -> Result<(), AnyError> {
+  async fn get_private_data(&self, user: HammerfestUserIdRef) -> Result<HammerfestUserPrivateData, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.private_data.get(&user).cloned().unwrap_or_default())
+  }
+
+  async fn get_stale_users(
+    &self,
+    options: &GetStaleHammerfestUsersOptions,
+  ) -> Result<Vec<ShortHammerfestUser>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.get_stale_users(options))
+  }
+
+  async fn touch_refresh_attempt(&self, user: HammerfestUserIdRef) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.refresh_attempted_at.insert(user, self.clock.now());
+    Ok(())
+  }
+
+  async fn touch_shop(&self, response: &HammerfestShopResponse) -> Result<(), AnyError> {
     eprintln!("Stub: Incomplete `MemHammerfestSore::touch_shop` implementation");
+    let mut state = self.state.write().unwrap();
+    state.touch_tokens(&response.session);
     Ok(())
   }
 
-  async fn touch_profile(&self, _response: &HammerfestProfileResponse) -> Result<(), AnyError> {
-    eprintln!("Stub: Incomplete `MemHammerfestSore::touch_profile` implementation");
+  async fn touch_profile(&self, response: &HammerfestProfileResponse) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    if let Some(session) = response.session.as_ref() {
+      state.touch_tokens(session);
+    }
+    if let Some(profile) = response.profile.as_ref() {
+      state.touch_profile(self.clock.now(), &profile.user);
+      if let Some(email) = profile.email.as_ref() {
+        state.touch_email(profile.user.as_ref(), email.clone());
+      }
+    }
     Ok(())
   }
 
-  async fn touch_inventory(&self, _respone: &HammerfestInventoryResponse) -> Result<(), AnyError> {
+  async fn touch_inventory(&self, response: &HammerfestInventoryResponse) -> Result<(), AnyError> {
     eprintln!("Stub: Incomplete `MemHammerfestSore::touch_inventory` implementation");
+    let mut state = self.state.write().unwrap();
+    state.touch_tokens(&response.session);
     Ok(())
   }
 
-  async fn touch_godchildren(&self, _response: &HammerfestGodchildrenResponse) -> Result<(), AnyError> {
+  async fn touch_godchildren(&self, response: &HammerfestGodchildrenResponse) -> Result<(), AnyError> {
     eprintln!("Stub: Incomplete `MemHammerfestSore::touch_godchildren` implementation");
+    let mut state = self.state.write().unwrap();
+    state.touch_tokens(&response.session);
     Ok(())
   }
 
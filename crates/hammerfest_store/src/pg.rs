@@ -2,16 +2,16 @@ use async_trait::async_trait;
 use etwin_core::api::ApiRef;
 use etwin_core::clock::Clock;
 use etwin_core::core::{Instant, Secret};
-use etwin_core::email::touch_email_address;
+use etwin_core::email::{touch_email_address, EmailAddress};
 use etwin_core::hammerfest::{
-  hammerfest_reply_count_to_page_count, GetHammerfestUserOptions, HammerfestDate, HammerfestDateTime,
-  HammerfestForumPostId, HammerfestForumRole, HammerfestForumThemeDescription, HammerfestForumThemeId,
-  HammerfestForumThemeIdRef, HammerfestForumThemePageResponse, HammerfestForumThemeTitle, HammerfestForumThreadIdRef,
-  HammerfestForumThreadKind, HammerfestForumThreadPageResponse, HammerfestForumThreadTitle,
+  hammerfest_reply_count_to_page_count, GetHammerfestUserOptions, GetStaleHammerfestUsersOptions, HammerfestDate,
+  HammerfestDateTime, HammerfestForumPostId, HammerfestForumRole, HammerfestForumThemeDescription,
+  HammerfestForumThemeId, HammerfestForumThemeIdRef, HammerfestForumThemePageResponse, HammerfestForumThemeTitle,
+  HammerfestForumThreadIdRef, HammerfestForumThreadKind, HammerfestForumThreadPageResponse, HammerfestForumThreadTitle,
   HammerfestGodchildrenResponse, HammerfestInventoryResponse, HammerfestItemId, HammerfestLadderLevel,
   HammerfestProfileResponse, HammerfestQuestId, HammerfestQuestStatus, HammerfestServer, HammerfestSessionUser,
-  HammerfestShop, HammerfestShopResponse, HammerfestStore, HammerfestUserId, HammerfestUserIdRef, HammerfestUsername,
-  ShortHammerfestUser, StoredHammerfestUser,
+  HammerfestShop, HammerfestShopResponse, HammerfestStore, HammerfestUserId, HammerfestUserIdRef,
+  HammerfestUserPrivateData, HammerfestUsername, ShortHammerfestUser, StoredHammerfestUser,
 };
 use etwin_core::pg_num::{PgU32, PgU8};
 use etwin_core::types::AnyError;
@@ -1074,6 +1074,124 @@ where
     })
   }
 
+  async fn get_private_data(&self, user: HammerfestUserIdRef) -> Result<HammerfestUserPrivateData, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct EmailRow {
+      email: Option<EmailAddress>,
+    }
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct TokensRow {
+      tokens: PgU32,
+    }
+
+    let email: Option<EmailRow> = sqlx::query_as::<_, EmailRow>(
+      r"
+      SELECT pgp_sym_decrypt(email_addresses.email_address, $3::TEXT) AS email
+      FROM hammerfest_emails
+        LEFT OUTER JOIN email_addresses ON (email_addresses._hash = hammerfest_emails.email)
+      WHERE hammerfest_server = $1::HAMMERFEST_SERVER AND hammerfest_user_id = $2::HAMMERFEST_USER_ID
+      ORDER BY lower(period) DESC
+      LIMIT 1;
+    ",
+    )
+    .bind(user.server)
+    .bind(user.id)
+    .bind(self.database_secret.as_str())
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    let tokens: Option<TokensRow> = sqlx::query_as::<_, TokensRow>(
+      r"
+      SELECT tokens
+      FROM hammerfest_tokens
+      WHERE hammerfest_server = $1::HAMMERFEST_SERVER AND hammerfest_user_id = $2::HAMMERFEST_USER_ID
+      ORDER BY lower(period) DESC
+      LIMIT 1;
+    ",
+    )
+    .bind(user.server)
+    .bind(user.id)
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    Ok(HammerfestUserPrivateData {
+      email: email.map(|r| r.email),
+      tokens: tokens.map(|r| u32::from(r.tokens)),
+    })
+  }
+
+  async fn get_stale_users(
+    &self,
+    options: &GetStaleHammerfestUsersOptions,
+  ) -> Result<Vec<ShortHammerfestUser>, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      hammerfest_server: HammerfestServer,
+      hammerfest_user_id: HammerfestUserId,
+      username: HammerfestUsername,
+    }
+
+    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+      r"
+      WITH latest_hammerfest_profiles AS (
+        SELECT DISTINCT ON (hammerfest_server, hammerfest_user_id) hammerfest_server, hammerfest_user_id,
+          retrieved_at[CARDINALITY(retrieved_at)] AS retrieved
+        FROM hammerfest_profiles
+        WHERE hammerfest_server = $1::HAMMERFEST_SERVER
+        ORDER BY hammerfest_server, hammerfest_user_id, lower(period) DESC
+      ),
+      hammerfest_user_retrievals AS (
+        SELECT hammerfest_users.hammerfest_server, hammerfest_users.hammerfest_user_id, username,
+          COALESCE(profile.retrieved, hammerfest_users.archived_at) AS retrieved, attempt.attempted_at AS attempted
+        FROM hammerfest_users
+          LEFT OUTER JOIN latest_hammerfest_profiles AS profile USING (hammerfest_server, hammerfest_user_id)
+          LEFT OUTER JOIN hammerfest_user_refresh_attempts AS attempt USING (hammerfest_server, hammerfest_user_id)
+        WHERE hammerfest_users.hammerfest_server = $1::HAMMERFEST_SERVER
+      )
+      SELECT hammerfest_server, hammerfest_user_id, username
+      FROM hammerfest_user_retrievals
+      WHERE retrieved < $2::INSTANT AND (attempted IS NULL OR attempted < $3::INSTANT)
+      ORDER BY attempted NULLS FIRST, retrieved, hammerfest_user_id
+      LIMIT $4::INT8;
+    ",
+    )
+    .bind(options.server)
+    .bind(options.retrieved_before)
+    .bind(options.attempted_before)
+    .bind(i64::from(options.limit))
+    .fetch_all(self.database.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|r| ShortHammerfestUser {
+          server: r.hammerfest_server,
+          id: r.hammerfest_user_id,
+          username: r.username,
+        })
+        .collect(),
+    )
+  }
+
+  async fn touch_refresh_attempt(&self, user: HammerfestUserIdRef) -> Result<(), AnyError> {
+    let res: PgQueryResult = sqlx::query(
+      r"
+      INSERT INTO hammerfest_user_refresh_attempts(hammerfest_server, hammerfest_user_id, attempted_at)
+      VALUES ($1::HAMMERFEST_SERVER, $2::HAMMERFEST_USER_ID, $3::INSTANT)
+      ON CONFLICT (hammerfest_server, hammerfest_user_id) DO UPDATE SET attempted_at = $3::INSTANT;
+    ",
+    )
+    .bind(user.server)
+    .bind(user.id)
+    .bind(self.clock.now())
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+    Ok(())
+  }
+
   async fn touch_shop(&self, response: &HammerfestShopResponse) -> Result<(), AnyError> {
     let now = self.clock.now();
     let mut tx = self.database.as_ref().begin().await?;
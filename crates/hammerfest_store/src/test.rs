@@ -3,13 +3,13 @@ use etwin_core::api::ApiRef;
 use etwin_core::clock::VirtualClock;
 use etwin_core::core::Instant;
 use etwin_core::hammerfest::{
-  GetHammerfestUserOptions, HammerfestDate, HammerfestDateTime, HammerfestForumPost, HammerfestForumPostAuthor,
-  HammerfestForumPostListing, HammerfestForumRole, HammerfestForumThemePage, HammerfestForumThemePageResponse,
-  HammerfestForumThread, HammerfestForumThreadKind, HammerfestForumThreadListing, HammerfestForumThreadPage,
-  HammerfestForumThreadPageResponse, HammerfestGodchild, HammerfestGodchildrenResponse, HammerfestInventoryResponse,
-  HammerfestLadderLevel, HammerfestProfile, HammerfestProfileResponse, HammerfestServer, HammerfestSessionUser,
-  HammerfestShop, HammerfestShopResponse, HammerfestStore, ShortHammerfestForumTheme, ShortHammerfestForumThread,
-  ShortHammerfestUser, StoredHammerfestUser,
+  GetHammerfestUserOptions, GetStaleHammerfestUsersOptions, HammerfestDate, HammerfestDateTime, HammerfestForumPost,
+  HammerfestForumPostAuthor, HammerfestForumPostListing, HammerfestForumRole, HammerfestForumThemePage,
+  HammerfestForumThemePageResponse, HammerfestForumThread, HammerfestForumThreadKind, HammerfestForumThreadListing,
+  HammerfestForumThreadPage, HammerfestForumThreadPageResponse, HammerfestGodchild, HammerfestGodchildrenResponse,
+  HammerfestInventoryResponse, HammerfestLadderLevel, HammerfestProfile, HammerfestProfileResponse, HammerfestServer,
+  HammerfestSessionUser, HammerfestShop, HammerfestShopResponse, HammerfestStore, HammerfestUserPrivateData,
+  ShortHammerfestForumTheme, ShortHammerfestForumThread, ShortHammerfestUser, StoredHammerfestUser,
 };
 use std::collections::HashMap;
 use std::convert::TryInto;
@@ -21,6 +21,8 @@ macro_rules! test_hammerfest_store {
     register_test!($(#[$meta])*, $api, test_empty);
     register_test!($(#[$meta])*, $api, test_touch_user);
     register_test!($(#[$meta])*, $api, test_get_missing_user);
+    register_test!($(#[$meta])*, $api, test_get_stale_users);
+    register_test!($(#[$meta])*, $api, test_get_private_data);
   };
 }
 
@@ -191,6 +193,174 @@ where
   }
 }
 
+pub(crate) async fn test_get_stale_users<TyClock, TyHammerfestStore>(api: TestApi<TyClock, TyHammerfestStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyHammerfestStore: HammerfestStore,
+{
+  let alice = ShortHammerfestUser {
+    server: HammerfestServer::HammerfestFr,
+    id: "123".parse().unwrap(),
+    username: "alice".parse().unwrap(),
+  };
+  let bob = ShortHammerfestUser {
+    server: HammerfestServer::HammerfestFr,
+    id: "456".parse().unwrap(),
+    username: "bob".parse().unwrap(),
+  };
+  let charlie = ShortHammerfestUser {
+    server: HammerfestServer::HfestNet,
+    id: "789".parse().unwrap(),
+    username: "charlie".parse().unwrap(),
+  };
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  api.hammerfest_store.touch_short_user(&alice).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 1));
+  api.hammerfest_store.touch_short_user(&bob).await.unwrap();
+  api.hammerfest_store.touch_short_user(&charlie).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 2, 0, 0, 0));
+  api
+    .hammerfest_store
+    .touch_profile(&HammerfestProfileResponse {
+      session: None,
+      profile: Some(HammerfestProfile {
+        user: alice.clone(),
+        email: None,
+        best_score: 0,
+        best_level: 0,
+        has_carrot: false,
+        season_score: 0,
+        ladder_level: 0.try_into().unwrap(),
+        hall_of_fame: None,
+        items: Default::default(),
+        quests: Default::default(),
+      }),
+    })
+    .await
+    .unwrap();
+  {
+    let actual = api
+      .hammerfest_store
+      .get_stale_users(&GetStaleHammerfestUsersOptions {
+        server: HammerfestServer::HammerfestFr,
+        retrieved_before: Instant::ymd_hms(2021, 1, 2, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![bob.clone()];
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .hammerfest_store
+      .get_stale_users(&GetStaleHammerfestUsersOptions {
+        server: HammerfestServer::HammerfestFr,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![bob.clone(), alice.clone()];
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .hammerfest_store
+      .get_stale_users(&GetStaleHammerfestUsersOptions {
+        server: HammerfestServer::HammerfestFr,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 1,
+      })
+      .await
+      .unwrap();
+    let expected = vec![bob.clone()];
+    assert_eq!(actual, expected);
+  }
+  api.hammerfest_store.touch_refresh_attempt(bob.as_ref()).await.unwrap();
+  {
+    let actual = api
+      .hammerfest_store
+      .get_stale_users(&GetStaleHammerfestUsersOptions {
+        server: HammerfestServer::HammerfestFr,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 2, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![alice.clone()];
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .hammerfest_store
+      .get_stale_users(&GetStaleHammerfestUsersOptions {
+        server: HammerfestServer::HammerfestFr,
+        retrieved_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        attempted_before: Instant::ymd_hms(2021, 1, 3, 0, 0, 0),
+        limit: 10,
+      })
+      .await
+      .unwrap();
+    let expected = vec![alice, bob];
+    assert_eq!(actual, expected);
+  }
+}
+
+pub(crate) async fn test_get_private_data<TyClock, TyHammerfestStore>(api: TestApi<TyClock, TyHammerfestStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyHammerfestStore: HammerfestStore,
+{
+  let alice = ShortHammerfestUser {
+    server: HammerfestServer::HammerfestFr,
+    id: "123".parse().unwrap(),
+    username: "alice".parse().unwrap(),
+  };
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  api.hammerfest_store.touch_short_user(&alice).await.unwrap();
+  {
+    let actual = api.hammerfest_store.get_private_data(alice.as_ref()).await.unwrap();
+    let expected = HammerfestUserPrivateData::default();
+    assert_eq!(actual, expected);
+  }
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 1));
+  api
+    .hammerfest_store
+    .touch_profile(&HammerfestProfileResponse {
+      session: Some(HammerfestSessionUser {
+        user: alice.clone(),
+        tokens: 50,
+      }),
+      profile: Some(HammerfestProfile {
+        user: alice.clone(),
+        email: Some(Some("alice@example.com".parse().unwrap())),
+        best_score: 0,
+        best_level: 0,
+        has_carrot: false,
+        season_score: 0,
+        ladder_level: 0.try_into().unwrap(),
+        hall_of_fame: None,
+        items: Default::default(),
+        quests: Default::default(),
+      }),
+    })
+    .await
+    .unwrap();
+  {
+    let actual = api.hammerfest_store.get_private_data(alice.as_ref()).await.unwrap();
+    let expected = HammerfestUserPrivateData {
+      email: Some(Some("alice@example.com".parse().unwrap())),
+      tokens: Some(50),
+    };
+    assert_eq!(actual, expected);
+  }
+}
+
 pub(crate) async fn test_touch_profile_empty<TyClock, TyHammerfestStore>(api: TestApi<TyClock, TyHammerfestStore>)
 where
   TyClock: ApiRef<VirtualClock>,
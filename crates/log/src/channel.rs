@@ -0,0 +1,131 @@
+use crate::Logger;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+/// Non-blocking logger buffering events in a bounded channel, consumed by a background thread.
+///
+/// Logging never waits for the inner logger: when the buffer is full, the event is discarded and counted in
+/// [`ChannelLogger::dropped`]. Dropping the `ChannelLogger` waits for the buffered events to be processed.
+pub struct ChannelLogger<T> {
+  sender: Option<SyncSender<T>>,
+  worker: Option<JoinHandle<()>>,
+  dropped: AtomicU64,
+}
+
+impl<T: Send + 'static> ChannelLogger<T> {
+  /// Forward events to `logger` from a background thread, buffering up to `capacity` events.
+  pub fn new<L>(logger: L, capacity: usize) -> Self
+  where
+    L: Logger<T> + 'static,
+  {
+    let (sender, receiver) = sync_channel::<T>(capacity);
+    let worker = thread::Builder::new()
+      .name("etwin_log".to_string())
+      .spawn(move || {
+        for ev in receiver {
+          logger.log(ev);
+        }
+      })
+      .expect("failed to spawn the logger thread");
+    Self {
+      sender: Some(sender),
+      worker: Some(worker),
+      dropped: AtomicU64::new(0),
+    }
+  }
+}
+
+impl<T> ChannelLogger<T> {
+  /// Number of events discarded because the buffer was full
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+}
+
+impl<T: Send> Logger<T> for ChannelLogger<T> {
+  fn log(&self, ev: T) {
+    if let Some(sender) = self.sender.as_ref() {
+      if let Err(TrySendError::Full(_)) = sender.try_send(ev) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+      }
+    }
+  }
+}
+
+impl<T> Drop for ChannelLogger<T> {
+  fn drop(&mut self) {
+    // Closing the channel stops the worker once the buffer is empty
+    drop(self.sender.take());
+    if let Some(worker) = self.worker.take() {
+      // A panic of the inner logger was already reported by the worker thread
+      let _ = worker.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::channel::ChannelLogger;
+  use crate::Logger;
+  use std::sync::mpsc::{channel, Receiver, Sender};
+  use std::sync::{Arc, Mutex};
+
+  /// Logger collecting events in a shared vector.
+  struct SharedVecLogger(Arc<Mutex<Vec<u32>>>);
+
+  impl Logger<u32> for SharedVecLogger {
+    fn log(&self, ev: u32) {
+      self.0.lock().unwrap().push(ev);
+    }
+  }
+
+  /// Logger notifying each event, then waiting for a permit before returning.
+  struct GatedLogger {
+    events: Mutex<Sender<u32>>,
+    permits: Mutex<Receiver<()>>,
+  }
+
+  impl Logger<u32> for GatedLogger {
+    fn log(&self, ev: u32) {
+      self.events.lock().unwrap().send(ev).unwrap();
+      self.permits.lock().unwrap().recv().unwrap();
+    }
+  }
+
+  #[test]
+  fn test_buffered_events_are_processed() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let logger = ChannelLogger::new(SharedVecLogger(Arc::clone(&events)), 16);
+    for i in 0..10 {
+      logger.log(i);
+    }
+    assert_eq!(logger.dropped(), 0);
+    drop(logger);
+    assert_eq!(*events.lock().unwrap(), (0..10).collect::<Vec<u32>>());
+  }
+
+  #[test]
+  fn test_full_buffer_drops_events() {
+    let (event_sender, event_receiver) = channel();
+    let (permit_sender, permit_receiver) = channel();
+    let logger = ChannelLogger::new(
+      GatedLogger {
+        events: Mutex::new(event_sender),
+        permits: Mutex::new(permit_receiver),
+      },
+      1,
+    );
+    logger.log(1);
+    // The worker holds the first event, the second one fills the buffer
+    assert_eq!(event_receiver.recv().unwrap(), 1);
+    logger.log(2);
+    logger.log(3);
+    assert_eq!(logger.dropped(), 1);
+    for _ in 0..2 {
+      permit_sender.send(()).unwrap();
+    }
+    drop(logger);
+    assert_eq!(event_receiver.try_iter().collect::<Vec<u32>>(), vec![2]);
+  }
+}
@@ -0,0 +1,203 @@
+use crate::json::JsonLogger;
+use chrono::Duration;
+use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// JSON lines logger writing to a rotating file
+pub type RotatingFileLogger<C> = JsonLogger<C, RotatingFile<C>>;
+
+/// Log file rotated once it reaches a maximum size or age.
+///
+/// Rotated files are renamed to `<file_name>.<rotation time>` in the same directory, and logging continues in a new
+/// file at the original path. Rotations only happen between lines, so a line is never split across two files.
+pub struct RotatingFile<C> {
+  path: PathBuf,
+  clock: C,
+  max_size: Option<u64>,
+  max_age: Option<Duration>,
+  file: File,
+  size: u64,
+  opened_at: Instant,
+  at_line_start: bool,
+}
+
+impl<C: Clock> RotatingFile<C> {
+  /// Open the log file at `path` in append mode, creating it if needed.
+  ///
+  /// The age of the file is counted from this call.
+  pub fn open(path: impl Into<PathBuf>, clock: C) -> io::Result<Self> {
+    let path = path.into();
+    let file = open_append(&path)?;
+    let size = file.metadata()?.len();
+    let opened_at = clock.now();
+    Ok(Self {
+      path,
+      clock,
+      max_size: None,
+      max_age: None,
+      file,
+      size,
+      opened_at,
+      at_line_start: true,
+    })
+  }
+
+  /// Rotate the file once its size reaches `max_size` bytes.
+  pub fn with_max_size(mut self, max_size: u64) -> Self {
+    self.max_size = Some(max_size);
+    self
+  }
+
+  /// Rotate the file once it was written for `max_age`.
+  pub fn with_max_age(mut self, max_age: Duration) -> Self {
+    self.max_age = Some(max_age);
+    self
+  }
+
+  fn should_rotate(&self, now: Instant) -> bool {
+    if self.size == 0 {
+      return false;
+    }
+    let too_large = matches!(self.max_size, Some(max_size) if self.size >= max_size);
+    let too_old = matches!(self.max_age, Some(max_age) if now.into_chrono() - self.opened_at.into_chrono() >= max_age);
+    too_large || too_old
+  }
+
+  fn rotate(&mut self, now: Instant) -> io::Result<()> {
+    self.file.flush()?;
+    fs::rename(&self.path, self.rotated_path(now))?;
+    self.file = open_append(&self.path)?;
+    self.size = 0;
+    self.opened_at = now;
+    Ok(())
+  }
+
+  /// Find an unused path for the current file, rotated at `now`.
+  fn rotated_path(&self, now: Instant) -> PathBuf {
+    let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", now.into_chrono().format("%Y%m%dT%H%M%S%.3fZ")));
+    let mut path = self.path.with_file_name(&name);
+    let mut i: u32 = 1;
+    while path.exists() {
+      let mut indexed = name.clone();
+      indexed.push(format!(".{}", i));
+      path = self.path.with_file_name(indexed);
+      i += 1;
+    }
+    path
+  }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+  OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl<C: Clock> Write for RotatingFile<C> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if self.at_line_start {
+      let now = self.clock.now();
+      if self.should_rotate(now) {
+        self.rotate(now)?;
+      }
+    }
+    let written = self.file.write(buf)?;
+    self.size += written as u64;
+    if written > 0 {
+      self.at_line_start = buf[written - 1] == b'\n';
+    }
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::file::{RotatingFile, RotatingFileLogger};
+  use crate::Logger;
+  use chrono::Duration;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use std::fs;
+  use std::path::{Path, PathBuf};
+
+  fn create_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("etwin_log-{}-{}", name, std::process::id()));
+    if dir.exists() {
+      fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  /// Read the files of `dir`, sorted by name.
+  fn read_dir(dir: &Path) -> Vec<(String, String)> {
+    let mut files: Vec<(String, String)> = fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        (name, fs::read_to_string(&path).unwrap())
+      })
+      .collect();
+    files.sort();
+    files
+  }
+
+  #[test]
+  fn test_rotate_by_size() {
+    let dir = create_test_dir("size");
+    let clock = VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+    let file = RotatingFile::open(dir.join("etwin.log"), &clock)
+      .unwrap()
+      .with_max_size(50);
+    let logger: RotatingFileLogger<_> = RotatingFileLogger::new(&clock, file);
+    for i in 0..3 {
+      logger.log(i);
+    }
+    let line = |i: u32| format!("{{\"time\":\"2021-01-01T00:00:00.000Z\",\"event\":{}}}\n", i);
+    assert_eq!(
+      read_dir(&dir),
+      vec![
+        ("etwin.log".to_string(), line(2)),
+        ("etwin.log.20210101T000000.000Z".to_string(), line(0) + &line(1)),
+      ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_rotate_by_age() {
+    let dir = create_test_dir("age");
+    let clock = VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+    let file = RotatingFile::open(dir.join("etwin.log"), &clock)
+      .unwrap()
+      .with_max_age(Duration::hours(1));
+    let logger: RotatingFileLogger<_> = RotatingFileLogger::new(&clock, file);
+    logger.log("a");
+    clock.advance_by(Duration::minutes(30));
+    logger.log("b");
+    clock.advance_by(Duration::minutes(30));
+    logger.log("c");
+    assert_eq!(
+      read_dir(&dir),
+      vec![
+        (
+          "etwin.log".to_string(),
+          "{\"time\":\"2021-01-01T01:00:00.000Z\",\"event\":\"c\"}\n".to_string()
+        ),
+        (
+          "etwin.log.20210101T010000.000Z".to_string(),
+          "{\"time\":\"2021-01-01T00:00:00.000Z\",\"event\":\"a\"}\n{\"time\":\"2021-01-01T00:30:00.000Z\",\"event\":\"b\"}\n"
+            .to_string()
+        ),
+      ]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,142 @@
+use crate::Logger;
+use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Line written by [`JsonLogger`] for each event
+#[derive(Serialize)]
+struct JsonRecord<'a, T> {
+  time: Instant,
+  event: &'a T,
+}
+
+/// Logger sink writing events as JSON lines, with a timestamp from the clock.
+///
+/// Each event is written as `{"time":"2021-01-01T00:00:00.000Z","event":...}` followed by a newline, using a single
+/// write to the underlying writer. Events that can't be serialized or written are discarded and counted in
+/// [`JsonLogger::failed`].
+pub struct JsonLogger<C, W> {
+  clock: C,
+  writer: Mutex<W>,
+  failed: AtomicU64,
+}
+
+impl<C, W> JsonLogger<C, W> {
+  pub fn new(clock: C, writer: W) -> Self {
+    Self {
+      clock,
+      writer: Mutex::new(writer),
+      failed: AtomicU64::new(0),
+    }
+  }
+
+  /// Number of events discarded because they could not be serialized or written
+  pub fn failed(&self) -> u64 {
+    self.failed.load(Ordering::Relaxed)
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer.into_inner().unwrap()
+  }
+}
+
+impl<T, C, W> Logger<T> for JsonLogger<C, W>
+where
+  T: Serialize,
+  C: Clock,
+  W: Write + Send,
+{
+  fn log(&self, ev: T) {
+    let record = JsonRecord {
+      time: self.clock.now(),
+      event: &ev,
+    };
+    let mut line = match serde_json::to_vec(&record) {
+      Ok(line) => line,
+      Err(_) => {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        return;
+      }
+    };
+    line.push(b'\n');
+    let mut writer = self.writer.lock().unwrap();
+    if writer.write_all(&line).and_then(|()| writer.flush()).is_err() {
+      self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::json::JsonLogger;
+  use crate::Logger;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use serde::{Serialize, Serializer};
+  use std::io::{self, Write};
+
+  #[derive(Serialize)]
+  #[serde(tag = "type")]
+  enum Event {
+    Start { name: &'static str },
+    Stop,
+  }
+
+  #[test]
+  fn test_json_lines() {
+    let clock = VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+    let logger = JsonLogger::new(&clock, Vec::new());
+    logger.log(Event::Start { name: "alice" });
+    clock.advance_by(chrono::Duration::milliseconds(1500));
+    logger.log(Event::Stop);
+    let actual = String::from_utf8(logger.into_inner()).unwrap();
+    let expected = r#"{"time":"2021-01-01T00:00:00.000Z","event":{"type":"Start","name":"alice"}}
+{"time":"2021-01-01T00:00:01.500Z","event":{"type":"Stop"}}
+"#;
+    assert_eq!(actual, expected);
+  }
+
+  /// Event failing to serialize
+  struct Unserializable;
+
+  impl Serialize for Unserializable {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+      Err(serde::ser::Error::custom("unserializable"))
+    }
+  }
+
+  /// Writer rejecting every write
+  struct BrokenWriter;
+
+  impl Write for BrokenWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+      Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_count_failures() {
+    let clock = VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+    let logger = JsonLogger::new(&clock, Vec::new());
+    logger.log(Unserializable);
+    logger.log(Event::Stop);
+    assert_eq!(logger.failed(), 1);
+    let actual = String::from_utf8(logger.into_inner()).unwrap();
+    assert_eq!(
+      actual,
+      "{\"time\":\"2021-01-01T00:00:00.000Z\",\"event\":{\"type\":\"Stop\"}}\n"
+    );
+
+    let logger = JsonLogger::new(&clock, BrokenWriter);
+    logger.log(Event::Stop);
+    logger.log(Event::Stop);
+    assert_eq!(logger.failed(), 2);
+  }
+}
@@ -1,5 +1,9 @@
+pub mod channel;
+pub mod file;
+pub mod json;
+
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub trait Sink<T> {
   fn send(&self, item: T);
@@ -174,6 +178,32 @@ where
   }
 }
 
+/// Send each event to all the inner loggers
+pub struct FanOut<L> {
+  loggers: Vec<L>,
+}
+
+impl<L> FanOut<L> {
+  pub fn new(loggers: Vec<L>) -> Self {
+    Self { loggers }
+  }
+}
+
+impl<T, L> Logger<T> for FanOut<L>
+where
+  T: Clone,
+  L: Logger<T>,
+{
+  fn log(&self, ev: T) {
+    if let Some((last, others)) = self.loggers.split_last() {
+      for logger in others {
+        logger.log(ev.clone());
+      }
+      last.log(ev);
+    }
+  }
+}
+
 impl<L, Ev> Logger<Ev> for &L
 where
   L: ?Sized + Logger<Ev>,
@@ -192,6 +222,15 @@ where
   }
 }
 
+impl<L, Ev> Logger<Ev> for Box<L>
+where
+  L: ?Sized + Logger<Ev>,
+{
+  fn log(&self, ev: Ev) {
+    Logger::log(self.as_ref(), ev)
+  }
+}
+
 /// Trivial logger sink discarding all events
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct NoopLogger;
@@ -212,6 +251,39 @@ where
   }
 }
 
+/// Logger sink collecting events in memory, mostly useful in tests
+pub struct VecLogger<T> {
+  events: Mutex<Vec<T>>,
+}
+
+impl<T> VecLogger<T> {
+  pub fn new() -> Self {
+    Self {
+      events: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Remove and return all the events logged so far
+  pub fn take(&self) -> Vec<T> {
+    std::mem::take(&mut *self.events.lock().unwrap())
+  }
+}
+
+impl<T> Default for VecLogger<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> Logger<T> for VecLogger<T>
+where
+  T: Send,
+{
+  fn log(&self, ev: T) {
+    self.events.lock().unwrap().push(ev);
+  }
+}
+
 impl<T> SinkMut<T> for Vec<T> {
   fn send(&mut self, item: T) {
     self.push(item);
@@ -223,3 +295,28 @@ impl<T> SinkMut<T> for Option<T> {
     *self = Some(item);
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::json::JsonLogger;
+  use crate::{FanOut, Logger};
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+
+  #[test]
+  fn test_fan_out() {
+    let clock = VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+    let all = JsonLogger::new(&clock, Vec::new());
+    let even = JsonLogger::new(&clock, Vec::new());
+    {
+      let loggers: Vec<Box<dyn Logger<u32> + '_>> = vec![Box::new(&all), Box::new((&even).filter(|ev| ev % 2 == 0))];
+      let logger = FanOut::new(loggers);
+      for i in 0..4 {
+        logger.log(i);
+      }
+    }
+    let count_lines = |logger: JsonLogger<_, Vec<u8>>| String::from_utf8(logger.into_inner()).unwrap().lines().count();
+    assert_eq!(count_lines(all), 4);
+    assert_eq!(count_lines(even), 2);
+  }
+}
@@ -0,0 +1,195 @@
+use crate::message::{build_message, RawHeader};
+use async_trait::async_trait;
+use etwin_core::clock::Clock;
+use etwin_core::email::{EmailAddress, EmailContent, Mailer};
+use etwin_core::types::AnyError;
+use etwin_core::uuid::UuidGenerator;
+use lettre::message::Mailbox;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Layout of the directory written by the [`FileMailer`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileMailerFormat {
+  /// One `.eml` file per email, directly in the directory
+  Eml,
+  /// Maildir (`tmp`, `new` and `cur` subdirectories), readable by most mail clients
+  Maildir,
+}
+
+/// Mailer writing the emails to local files instead of sending them, for development.
+///
+/// The files contain the same RFC 5322 messages as the ones sent by the `SmtpMailer`.
+pub struct FileMailer<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  clock: TyClock,
+  uuid_generator: TyUuidGenerator,
+  directory: PathBuf,
+  format: FileMailerFormat,
+  sender: Mailbox,
+  headers: Vec<RawHeader>,
+}
+
+impl<TyClock, TyUuidGenerator> FileMailer<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  pub fn new(
+    clock: TyClock,
+    uuid_generator: TyUuidGenerator,
+    directory: PathBuf,
+    format: FileMailerFormat,
+    sender: Mailbox,
+  ) -> Self {
+    Self {
+      clock,
+      uuid_generator,
+      directory,
+      format,
+      sender,
+      headers: Vec::new(),
+    }
+  }
+
+  /// Add a header to every message, as with `SmtpMailerBuilder::header`.
+  pub fn with_header(mut self, header: RawHeader) -> Self {
+    self.headers.push(header);
+    self
+  }
+}
+
+#[async_trait]
+impl<TyClock, TyUuidGenerator> Mailer for FileMailer<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  async fn send_email(&self, recipient: &EmailAddress, content: &EmailContent) -> Result<(), AnyError> {
+    let now = self.clock.now();
+    let email = build_message(
+      &self.sender,
+      &self.headers,
+      Some(SystemTime::from(now.into_chrono())),
+      recipient,
+      content,
+    )?;
+    let email = email.formatted();
+    let id = self.uuid_generator.next();
+
+    match self.format {
+      FileMailerFormat::Eml => {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        // Prefix with the time so that a directory listing is sorted chronologically
+        let name = format!("{}-{}.eml", now.into_chrono().format("%Y%m%dT%H%M%SZ"), id);
+        tokio::fs::write(self.directory.join(name), email).await?;
+      }
+      FileMailerFormat::Maildir => {
+        for dir in ["tmp", "new", "cur"] {
+          tokio::fs::create_dir_all(self.directory.join(dir)).await?;
+        }
+        // Write to `tmp` first so that mail clients never see a partial message in `new`
+        let name = format!("{}.{}.etwin", now.into_posix_timestamp(), id);
+        let tmp = self.directory.join("tmp").join(&name);
+        tokio::fs::write(&tmp, email).await?;
+        tokio::fs::rename(&tmp, self.directory.join("new").join(&name)).await?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyUuidGenerator> neon::prelude::Finalize for FileMailer<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+}
+
+#[cfg(test)]
+mod test {
+  use crate::file::{FileMailer, FileMailerFormat};
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use etwin_core::email::{EmailAddress, EmailContent, Mailer};
+  use etwin_core::uuid::UuidGenerator;
+  use std::path::{Path, PathBuf};
+  use std::sync::Arc;
+  use uuid::Uuid;
+
+  struct FixedUuidGenerator(Uuid);
+
+  impl UuidGenerator for FixedUuidGenerator {
+    fn next(&self) -> Uuid {
+      self.0
+    }
+  }
+
+  fn make_mailer(directory: &Path, format: FileMailerFormat) -> FileMailer<Arc<VirtualClock>, FixedUuidGenerator> {
+    FileMailer::new(
+      Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0))),
+      FixedUuidGenerator("00000000-0000-0000-0000-000000000001".parse().unwrap()),
+      directory.to_path_buf(),
+      format,
+      "Eternaltwin <noreply@eternal-twin.net>".parse().unwrap(),
+    )
+  }
+
+  fn make_directory(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("etwin_mailer_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+  }
+
+  fn content() -> EmailContent {
+    EmailContent {
+      title: "Eternaltwin registration".parse().unwrap(),
+      body_text: "Hi, complete the registration by going to <https://eternal-twin.net>."
+        .parse()
+        .unwrap(),
+      body_html: Some(
+        "Hi, complete the registration by going to <a href=https://eternal-twin.net>Eternaltwin</a>.".to_string(),
+      ),
+    }
+  }
+
+  #[tokio::test]
+  async fn writes_eml_files() {
+    let dir = make_directory("eml");
+    let mailer = make_mailer(&dir, FileMailerFormat::Eml);
+    let alice: EmailAddress = "alice@example.com".parse().unwrap();
+
+    mailer.send_email(&alice, &content()).await.unwrap();
+
+    let path = dir.join("20210101T000000Z-00000000-0000-0000-0000-000000000001.eml");
+    let actual = std::fs::read_to_string(&path).unwrap();
+    assert!(actual.contains("From: Eternaltwin <noreply@eternal-twin.net>\r\n"));
+    assert!(actual.contains("To: alice@example.com\r\n"));
+    assert!(actual.contains("Subject: Eternaltwin registration\r\n"));
+    assert!(actual.contains("Date: Fri, 01 Jan 2021 00:00:00 +0000\r\n"));
+    assert!(actual.contains("Content-Type: multipart/alternative;"));
+    assert!(actual.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+    assert!(actual.contains("Content-Type: text/html; charset=utf-8\r\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn writes_maildir_messages() {
+    let dir = make_directory("maildir");
+    let mailer = make_mailer(&dir, FileMailerFormat::Maildir);
+    let alice: EmailAddress = "alice@example.com".parse().unwrap();
+
+    mailer.send_email(&alice, &content()).await.unwrap();
+
+    let actual =
+      std::fs::read_to_string(dir.join("new/1609459200.00000000-0000-0000-0000-000000000001.etwin")).unwrap();
+    assert!(actual.contains("Subject: Eternaltwin registration\r\n"));
+    assert_eq!(std::fs::read_dir(dir.join("tmp")).unwrap().count(), 0);
+    assert_eq!(std::fs::read_dir(dir.join("cur")).unwrap().count(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -1,2 +1,5 @@
+pub mod file;
 pub mod mem;
+mod message;
+pub mod noop;
 pub mod smtp;
@@ -0,0 +1,95 @@
+use etwin_core::email::{EmailAddress, EmailContent};
+use etwin_core::types::AnyError;
+use lettre::message::header;
+use lettre::message::header::Header;
+pub use lettre::message::header::HeaderName;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+use std::time::SystemTime;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RawHeader {
+  name: HeaderName,
+  value: String,
+}
+
+impl RawHeader {
+  pub fn new(name: HeaderName, value: String) -> Self {
+    Self { name, value }
+  }
+}
+
+enum KnownHeader {
+  From(header::From),
+  PmMessageStream(PmMessageStream),
+  ReplyTo(header::ReplyTo),
+  // ...
+}
+
+impl KnownHeader {
+  pub fn from_raw(raw: RawHeader) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(match raw.name {
+      name if name == header::From::name() => KnownHeader::From(header::From::parse(&raw.value)?),
+      name if name == PmMessageStream::name() => KnownHeader::PmMessageStream(PmMessageStream::parse(&raw.value)?),
+      name if name == header::ReplyTo::name() => KnownHeader::ReplyTo(header::ReplyTo::parse(&raw.value)?),
+      _ => return Err("UnknownHeaderName".into()),
+    })
+  }
+}
+
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+struct PmMessageStream(String);
+
+impl Header for PmMessageStream {
+  fn name() -> HeaderName {
+    HeaderName::new_from_ascii_str("X-PM-Message-Stream")
+  }
+
+  fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(Self(s.into()))
+  }
+
+  fn display(&self) -> String {
+    self.0.clone()
+  }
+}
+
+/// Build the MIME message for an email, shared by all the mailers sending real messages.
+///
+/// The message is `multipart/alternative` (text and HTML) when the content has an HTML body, `text/plain` otherwise.
+pub(crate) fn build_message(
+  sender: &Mailbox,
+  headers: &[RawHeader],
+  date: Option<SystemTime>,
+  recipient: &EmailAddress,
+  content: &EmailContent,
+) -> Result<Message, AnyError> {
+  let mut email = Message::builder()
+    .from(sender.clone())
+    .reply_to(sender.clone())
+    .to(recipient.as_str().parse()?);
+
+  for header in headers.iter() {
+    let header = KnownHeader::from_raw(header.clone())?;
+    email = match header {
+      KnownHeader::From(header) => email.header(header),
+      KnownHeader::PmMessageStream(header) => email.header(header),
+      KnownHeader::ReplyTo(header) => email.header(header),
+    };
+  }
+
+  if let Some(date) = date {
+    email = email.date(date);
+  }
+
+  let email = email.subject(content.title.as_str());
+
+  let email = match content.body_html.as_ref() {
+    None => email.body(content.body_text.to_string())?,
+    Some(body_html) => email.multipart(MultiPart::alternative_plain_html(
+      content.body_text.to_string(),
+      body_html.to_string(),
+    ))?,
+  };
+  Ok(email)
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use etwin_core::email::{EmailAddress, EmailContent, Mailer};
+use etwin_core::types::AnyError;
+
+/// Mailer accepting and discarding every email, for deployments without email delivery.
+pub struct NoopMailer;
+
+#[async_trait]
+impl Mailer for NoopMailer {
+  async fn send_email(&self, _recipient: &EmailAddress, _content: &EmailContent) -> Result<(), AnyError> {
+    Ok(())
+  }
+}
+
+#[cfg(feature = "neon")]
+impl neon::prelude::Finalize for NoopMailer {}
+
+#[cfg(test)]
+mod test {
+  use crate::noop::NoopMailer;
+  use etwin_core::email::{EmailContent, Mailer};
+
+  #[tokio::test]
+  async fn test_accepts_any_recipient() {
+    let actual = NoopMailer
+      .send_email(
+        &"alice@example.com".parse().unwrap(),
+        &EmailContent {
+          title: "Hi".parse().unwrap(),
+          body_text: "Hello Alice!\n".parse().unwrap(),
+          body_html: None,
+        },
+      )
+      .await;
+    assert!(actual.is_ok());
+  }
+}
@@ -1,59 +1,11 @@
+use crate::message::build_message;
+pub use crate::message::{HeaderName, RawHeader};
 use async_trait::async_trait;
 use etwin_core::email::{EmailAddress, EmailContent, Mailer};
 use etwin_core::types::AnyError;
-use lettre::message::header;
-use lettre::message::header::Header;
-pub use lettre::message::header::HeaderName;
-use lettre::message::{Mailbox, MultiPart};
+use lettre::message::Mailbox;
 use lettre::transport::smtp::authentication::Credentials;
-use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
-
-#[derive(Clone, PartialEq, Debug)]
-pub struct RawHeader {
-  name: HeaderName,
-  value: String,
-}
-
-impl RawHeader {
-  pub fn new(name: HeaderName, value: String) -> Self {
-    Self { name, value }
-  }
-}
-
-enum KnownHeader {
-  From(header::From),
-  PmMessageStream(PmMessageStream),
-  ReplyTo(header::ReplyTo),
-  // ...
-}
-
-impl KnownHeader {
-  pub fn from_raw(raw: RawHeader) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(match raw.name {
-      name if name == header::From::name() => KnownHeader::From(header::From::parse(&raw.value)?),
-      name if name == PmMessageStream::name() => KnownHeader::PmMessageStream(PmMessageStream::parse(&raw.value)?),
-      name if name == header::ReplyTo::name() => KnownHeader::ReplyTo(header::ReplyTo::parse(&raw.value)?),
-      _ => return Err("UnknownHeaderName".into()),
-    })
-  }
-}
-
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
-struct PmMessageStream(String);
-
-impl Header for PmMessageStream {
-  fn name() -> HeaderName {
-    HeaderName::new_from_ascii_str("X-PM-Message-Stream")
-  }
-
-  fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(Self(s.into()))
-  }
-
-  fn display(&self) -> String {
-    self.0.clone()
-  }
-}
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
 
 pub struct SmtpMailerBuilder {
   pub relay: String,
@@ -115,33 +67,9 @@ impl SmtpMailer {
 #[async_trait]
 impl Mailer for SmtpMailer {
   async fn send_email(&self, recipient: &EmailAddress, content: &EmailContent) -> Result<(), AnyError> {
-    let mut email = Message::builder()
-      .from(self.sender.clone())
-      .reply_to(self.sender.clone())
-      .to(recipient.as_str().parse().unwrap());
-
-    for header in self.headers.iter() {
-      let header = KnownHeader::from_raw(header.clone())?;
-      email = match header {
-        KnownHeader::From(header) => email.header(header),
-        KnownHeader::PmMessageStream(header) => email.header(header),
-        KnownHeader::ReplyTo(header) => email.header(header),
-      };
-    }
-
-    let email = email.subject(content.title.as_str());
-
-    let email = match content.body_html.as_ref() {
-      None => email.body(content.body_text.to_string()).unwrap(),
-      Some(body_html) => email
-        .multipart(MultiPart::alternative_plain_html(
-          content.body_text.to_string(),
-          body_html.to_string(),
-        ))
-        .unwrap(),
-    };
+    let email = build_message(&self.sender, &self.headers, None, recipient, content)?;
 
-    self.transport.send(email).await.unwrap();
+    self.transport.send(email).await?;
 
     Ok(())
   }
@@ -153,6 +81,7 @@ impl neon::prelude::Finalize for SmtpMailer {}
 #[cfg(test)]
 mod test {
   use crate::smtp::{RawHeader, SmtpMailer};
+  use etwin_config::MailerConfig;
   use etwin_core::email::{EmailAddress, EmailContent, Mailer};
   use lettre::message::header::HeaderName;
 
@@ -160,8 +89,8 @@ mod test {
   async fn verify_registration_en() {
     let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
     let config = match config.mailer {
-      Some(config) => config,
-      None => {
+      Some(MailerConfig::Smtp(config)) => config,
+      _ => {
         eprintln!("Missing SMTP mailer config, skipping test");
         return;
       }
@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use etwin_core::email::{
+  ClaimDueEmailsOptions, OutboundEmail, OutboundEmailId, OutboundEmailStatus, OutboxStore, QueueEmailOptions,
+  RecordEmailFailureOptions,
+};
+use etwin_core::types::AnyError;
+use etwin_core::uuid::UuidGenerator;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+struct StoreState {
+  emails: HashMap<OutboundEmailId, OutboundEmail>,
+}
+
+impl StoreState {
+  fn new() -> Self {
+    Self { emails: HashMap::new() }
+  }
+
+  fn queue_email(&mut self, now: Instant, id: OutboundEmailId, options: &QueueEmailOptions) -> OutboundEmail {
+    let email = OutboundEmail {
+      id,
+      recipient: options.recipient.clone(),
+      content: options.content.clone(),
+      ctime: now,
+      status: OutboundEmailStatus::Pending,
+      attempts: 0,
+      next_attempt_at: now,
+      sent_at: None,
+      last_error: None,
+    };
+    self.emails.insert(id, email.clone());
+    email
+  }
+
+  fn claim_due_emails(&mut self, now: Instant, options: &ClaimDueEmailsOptions) -> Vec<OutboundEmail> {
+    let mut due: Vec<&mut OutboundEmail> = self
+      .emails
+      .values_mut()
+      .filter(|email| email.status == OutboundEmailStatus::Pending && email.next_attempt_at <= now)
+      .collect();
+    due.sort_by_key(|email| (email.next_attempt_at, email.ctime));
+    let mut claimed: Vec<OutboundEmail> = due
+      .into_iter()
+      .take(usize::try_from(options.limit).unwrap_or(usize::MAX))
+      .map(|email| {
+        email.next_attempt_at = options.lease_until;
+        email.clone()
+      })
+      .collect();
+    claimed.sort_by_key(|email| email.ctime);
+    claimed
+  }
+
+  fn get_email(&self, id: OutboundEmailId) -> Option<OutboundEmail> {
+    self.emails.get(&id).cloned()
+  }
+
+  fn get_email_mut(&mut self, id: OutboundEmailId) -> Result<&mut OutboundEmail, AnyError> {
+    self
+      .emails
+      .get_mut(&id)
+      .ok_or_else(|| format!("OutboundEmailNotFound: {}", id).into())
+  }
+
+  fn record_email_sent(&mut self, now: Instant, id: OutboundEmailId) -> Result<(), AnyError> {
+    let email = self.get_email_mut(id)?;
+    email.status = OutboundEmailStatus::Sent;
+    email.sent_at = Some(now);
+    Ok(())
+  }
+
+  fn record_email_failure(&mut self, options: &RecordEmailFailureOptions) -> Result<(), AnyError> {
+    let email = self.get_email_mut(options.id)?;
+    email.attempts += 1;
+    email.last_error = Some(options.error.clone());
+    match options.retry_at {
+      Some(retry_at) => email.next_attempt_at = retry_at,
+      None => email.status = OutboundEmailStatus::Failed,
+    }
+    Ok(())
+  }
+}
+
+pub struct MemOutboxStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  clock: TyClock,
+  uuid_generator: TyUuidGenerator,
+  state: RwLock<StoreState>,
+}
+
+impl<TyClock, TyUuidGenerator> MemOutboxStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  pub fn new(clock: TyClock, uuid_generator: TyUuidGenerator) -> Self {
+    Self {
+      clock,
+      uuid_generator,
+      state: RwLock::new(StoreState::new()),
+    }
+  }
+}
+
+#[async_trait]
+impl<TyClock, TyUuidGenerator> OutboxStore for MemOutboxStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+  async fn queue_email(&self, options: &QueueEmailOptions) -> Result<OutboundEmail, AnyError> {
+    let now = self.clock.now();
+    let id = OutboundEmailId::from_uuid(self.uuid_generator.next());
+    let mut state = self.state.write().unwrap();
+    Ok(state.queue_email(now, id, options))
+  }
+
+  async fn claim_due_emails(&self, options: &ClaimDueEmailsOptions) -> Result<Vec<OutboundEmail>, AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    Ok(state.claim_due_emails(now, options))
+  }
+
+  async fn get_email(&self, id: OutboundEmailId) -> Result<Option<OutboundEmail>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.get_email(id))
+  }
+
+  async fn record_email_sent(&self, id: OutboundEmailId) -> Result<(), AnyError> {
+    let now = self.clock.now();
+    let mut state = self.state.write().unwrap();
+    state.record_email_sent(now, id)
+  }
+
+  async fn record_email_failure(&self, options: &RecordEmailFailureOptions) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.record_email_failure(options)
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyUuidGenerator> neon::prelude::Finalize for MemOutboxStore<TyClock, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyUuidGenerator: UuidGenerator,
+{
+}
+
+#[cfg(test)]
+mod test {
+  use crate::mem::MemOutboxStore;
+  use crate::test::TestApi;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use etwin_core::email::OutboxStore;
+  use etwin_core::uuid::Uuid4Generator;
+  use std::sync::Arc;
+
+  fn make_test_api() -> TestApi<Arc<VirtualClock>, Arc<dyn OutboxStore>> {
+    let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+    let outbox_store: Arc<dyn OutboxStore> = Arc::new(MemOutboxStore::new(Arc::clone(&clock), Uuid4Generator));
+
+    TestApi { clock, outbox_store }
+  }
+
+  test_outbox_store!(|| make_test_api());
+}
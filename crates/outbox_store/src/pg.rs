@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use etwin_core::api::ApiRef;
+use etwin_core::clock::Clock;
+use etwin_core::core::{HtmlFragment, Instant, Secret};
+use etwin_core::email::{
+  ClaimDueEmailsOptions, EmailAddress, EmailBody, EmailContent, EmailTitle, OutboundEmail, OutboundEmailId,
+  OutboundEmailStatus, OutboxStore, QueueEmailOptions, RecordEmailFailureOptions,
+};
+use etwin_core::types::AnyError;
+use etwin_core::uuid::UuidGenerator;
+use sqlx::PgPool;
+
+pub struct PgOutboxStore<TyClock, TyDatabase, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
+{
+  clock: TyClock,
+  database: TyDatabase,
+  database_secret: Secret,
+  uuid_generator: TyUuidGenerator,
+}
+
+impl<TyClock, TyDatabase, TyUuidGenerator> PgOutboxStore<TyClock, TyDatabase, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
+{
+  pub fn new(clock: TyClock, database: TyDatabase, database_secret: Secret, uuid_generator: TyUuidGenerator) -> Self {
+    Self {
+      clock,
+      database,
+      database_secret,
+      uuid_generator,
+    }
+  }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OutboundEmailRow {
+  outbound_email_id: OutboundEmailId,
+  recipient: EmailAddress,
+  title: EmailTitle,
+  body_text: EmailBody,
+  body_html: Option<HtmlFragment>,
+  ctime: Instant,
+  status: OutboundEmailStatus,
+  attempts: i64,
+  next_attempt_at: Instant,
+  sent_at: Option<Instant>,
+  last_error: Option<String>,
+}
+
+impl TryFrom<OutboundEmailRow> for OutboundEmail {
+  type Error = AnyError;
+
+  fn try_from(row: OutboundEmailRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      id: row.outbound_email_id,
+      recipient: row.recipient,
+      content: EmailContent {
+        title: row.title,
+        body_text: row.body_text,
+        body_html: row.body_html,
+      },
+      ctime: row.ctime,
+      status: row.status,
+      attempts: u32::try_from(row.attempts)?,
+      next_attempt_at: row.next_attempt_at,
+      sent_at: row.sent_at,
+      last_error: row.last_error,
+    })
+  }
+}
+
+#[async_trait]
+impl<TyClock, TyDatabase, TyUuidGenerator> OutboxStore for PgOutboxStore<TyClock, TyDatabase, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
+{
+  async fn queue_email(&self, options: &QueueEmailOptions) -> Result<OutboundEmail, AnyError> {
+    let id = OutboundEmailId::from_uuid(self.uuid_generator.next());
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+      INSERT INTO outbound_emails(
+        outbound_email_id, recipient, title, body_text, body_html, ctime, status, attempts, next_attempt_at, sent_at,
+        last_error
+      )
+      VALUES (
+        $1::OUTBOUND_EMAIL_ID, pgp_sym_encrypt($2::EMAIL_ADDRESS, $6::TEXT), $3::TEXT, pgp_sym_encrypt($4::TEXT, $6::TEXT),
+        pgp_sym_encrypt($5::TEXT, $6::TEXT), $7::INSTANT, 'Pending', 0, $7::INSTANT, NULL, NULL
+      );
+      ",
+    )
+    .bind(id)
+    .bind(&options.recipient)
+    .bind(options.content.title.as_str())
+    .bind(options.content.body_text.as_str())
+    .bind(options.content.body_html.as_deref())
+    .bind(self.database_secret.as_str())
+    .bind(now)
+    .execute(self.database.as_ref())
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+
+    Ok(OutboundEmail {
+      id,
+      recipient: options.recipient.clone(),
+      content: options.content.clone(),
+      ctime: now,
+      status: OutboundEmailStatus::Pending,
+      attempts: 0,
+      next_attempt_at: now,
+      sent_at: None,
+      last_error: None,
+    })
+  }
+
+  async fn claim_due_emails(&self, options: &ClaimDueEmailsOptions) -> Result<Vec<OutboundEmail>, AnyError> {
+    let now = self.clock.now();
+
+    // `SKIP LOCKED` lets concurrent workers claim disjoint batches
+    let rows: Vec<OutboundEmailRow> = sqlx::query_as::<_, OutboundEmailRow>(
+      r"
+      WITH due AS (
+        SELECT outbound_email_id
+        FROM outbound_emails
+        WHERE status = 'Pending' AND next_attempt_at <= $1::INSTANT
+        ORDER BY next_attempt_at, ctime
+        LIMIT $2::U32
+        FOR UPDATE SKIP LOCKED
+      )
+      UPDATE outbound_emails
+      SET next_attempt_at = $3::INSTANT
+      FROM due
+      WHERE outbound_emails.outbound_email_id = due.outbound_email_id
+      RETURNING outbound_emails.outbound_email_id, pgp_sym_decrypt(recipient, $4::TEXT)::EMAIL_ADDRESS AS recipient,
+        title, pgp_sym_decrypt(body_text, $4::TEXT) AS body_text, pgp_sym_decrypt(body_html, $4::TEXT) AS body_html,
+        ctime, status, attempts, next_attempt_at, sent_at, last_error;
+      ",
+    )
+    .bind(now)
+    .bind(i64::from(options.limit))
+    .bind(options.lease_until)
+    .bind(self.database_secret.as_str())
+    .fetch_all(self.database.as_ref())
+    .await?;
+
+    let mut emails = rows
+      .into_iter()
+      .map(OutboundEmail::try_from)
+      .collect::<Result<Vec<_>, _>>()?;
+    emails.sort_by_key(|email| email.ctime);
+    Ok(emails)
+  }
+
+  async fn get_email(&self, id: OutboundEmailId) -> Result<Option<OutboundEmail>, AnyError> {
+    let row: Option<OutboundEmailRow> = sqlx::query_as::<_, OutboundEmailRow>(
+      r"
+      SELECT outbound_email_id, pgp_sym_decrypt(recipient, $2::TEXT)::EMAIL_ADDRESS AS recipient, title,
+        pgp_sym_decrypt(body_text, $2::TEXT) AS body_text, pgp_sym_decrypt(body_html, $2::TEXT) AS body_html, ctime,
+        status, attempts, next_attempt_at, sent_at, last_error
+      FROM outbound_emails
+      WHERE outbound_email_id = $1::OUTBOUND_EMAIL_ID;
+      ",
+    )
+    .bind(id)
+    .bind(self.database_secret.as_str())
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    row.map(OutboundEmail::try_from).transpose()
+  }
+
+  async fn record_email_sent(&self, id: OutboundEmailId) -> Result<(), AnyError> {
+    let now = self.clock.now();
+
+    let res = sqlx::query(
+      r"
+      UPDATE outbound_emails
+      SET status = 'Sent', sent_at = $2::INSTANT
+      WHERE outbound_email_id = $1::OUTBOUND_EMAIL_ID;
+      ",
+    )
+    .bind(id)
+    .bind(now)
+    .execute(self.database.as_ref())
+    .await?;
+    if res.rows_affected() != 1 {
+      return Err(format!("OutboundEmailNotFound: {}", id).into());
+    }
+    Ok(())
+  }
+
+  async fn record_email_failure(&self, options: &RecordEmailFailureOptions) -> Result<(), AnyError> {
+    let res = sqlx::query(
+      r"
+      UPDATE outbound_emails
+      SET attempts = attempts + 1, last_error = $2::TEXT,
+        status = CASE WHEN $3::INSTANT IS NULL THEN 'Failed' ELSE status END,
+        next_attempt_at = COALESCE($3::INSTANT, next_attempt_at)
+      WHERE outbound_email_id = $1::OUTBOUND_EMAIL_ID;
+      ",
+    )
+    .bind(options.id)
+    .bind(options.error.as_str())
+    .bind(options.retry_at)
+    .execute(self.database.as_ref())
+    .await?;
+    if res.rows_affected() != 1 {
+      return Err(format!("OutboundEmailNotFound: {}", options.id).into());
+    }
+    Ok(())
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyDatabase, TyUuidGenerator> neon::prelude::Finalize
+  for PgOutboxStore<TyClock, TyDatabase, TyUuidGenerator>
+where
+  TyClock: Clock,
+  TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
+{
+}
+
+#[cfg(test)]
+mod test {
+  use super::PgOutboxStore;
+  use crate::test::TestApi;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::{Instant, Secret};
+  use etwin_core::email::OutboxStore;
+  use etwin_core::uuid::Uuid4Generator;
+  use etwin_db_schema::force_create_latest;
+  use serial_test::serial;
+  use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+  use sqlx::PgPool;
+  use std::sync::Arc;
+
+  async fn make_test_api() -> TestApi<Arc<VirtualClock>, Arc<dyn OutboxStore>> {
+    let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
+    let admin_database: PgPool = PgPoolOptions::new()
+      .max_connections(5)
+      .connect_with(
+        PgConnectOptions::new()
+          .host(&config.db.host)
+          .port(config.db.port)
+          .database(&config.db.name)
+          .username(&config.db.admin_user)
+          .password(&config.db.admin_password),
+      )
+      .await
+      .unwrap();
+    force_create_latest(&admin_database, true).await.unwrap();
+    admin_database.close().await;
+
+    let database: PgPool = PgPoolOptions::new()
+      .max_connections(5)
+      .connect_with(
+        PgConnectOptions::new()
+          .host(&config.db.host)
+          .port(config.db.port)
+          .database(&config.db.name)
+          .username(&config.db.user)
+          .password(&config.db.password),
+      )
+      .await
+      .unwrap();
+    let database = Arc::new(database);
+
+    let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+    let outbox_store: Arc<dyn OutboxStore> = Arc::new(PgOutboxStore::new(
+      Arc::clone(&clock),
+      database,
+      Secret::new("dev_secret".to_string()),
+      Uuid4Generator,
+    ));
+
+    TestApi { clock, outbox_store }
+  }
+
+  test_outbox_store!(
+    #[serial]
+    || make_test_api().await
+  );
+}
@@ -0,0 +1,206 @@
+use chrono::Duration;
+use etwin_core::api::ApiRef;
+use etwin_core::clock::VirtualClock;
+use etwin_core::core::Instant;
+use etwin_core::email::{
+  ClaimDueEmailsOptions, EmailContent, OutboundEmail, OutboundEmailStatus, OutboxStore, QueueEmailOptions,
+  RecordEmailFailureOptions,
+};
+
+#[macro_export]
+macro_rules! test_outbox_store {
+  ($(#[$meta:meta])* || $api:expr) => {
+    register_test!($(#[$meta])*, $api, test_queue_and_claim_emails);
+    register_test!($(#[$meta])*, $api, test_record_email_sent);
+    register_test!($(#[$meta])*, $api, test_record_email_failure);
+  };
+}
+
+macro_rules! register_test {
+  ($(#[$meta:meta])*, $api:expr, $test_name:ident) => {
+    #[tokio::test]
+    $(#[$meta])*
+    async fn $test_name() {
+      crate::test::$test_name($api).await;
+    }
+  };
+}
+
+pub(crate) struct TestApi<TyClock, TyOutboxStore>
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyOutboxStore: OutboxStore,
+{
+  pub(crate) clock: TyClock,
+  pub(crate) outbox_store: TyOutboxStore,
+}
+
+fn queue_options(recipient: &str, body: &str) -> QueueEmailOptions {
+  QueueEmailOptions {
+    recipient: recipient.parse().unwrap(),
+    content: EmailContent {
+      title: "Hello".parse().unwrap(),
+      body_text: body.parse().unwrap(),
+      body_html: Some(format!("<p>{}</p>", body)),
+    },
+  }
+}
+
+pub(crate) async fn test_queue_and_claim_emails<TyClock, TyOutboxStore>(api: TestApi<TyClock, TyOutboxStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyOutboxStore: OutboxStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .outbox_store
+    .queue_email(&queue_options("alice@example.com", "First"))
+    .await
+    .unwrap();
+  {
+    let expected = OutboundEmail {
+      id: alice.id,
+      recipient: "alice@example.com".parse().unwrap(),
+      content: queue_options("alice@example.com", "First").content,
+      ctime: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+      status: OutboundEmailStatus::Pending,
+      attempts: 0,
+      next_attempt_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+      sent_at: None,
+      last_error: None,
+    };
+    assert_eq!(alice, expected);
+    let actual = api.outbox_store.get_email(alice.id).await.unwrap();
+    assert_eq!(actual, Some(expected));
+  }
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let bob = api
+    .outbox_store
+    .queue_email(&queue_options("bob@example.com", "Second"))
+    .await
+    .unwrap();
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let claim = ClaimDueEmailsOptions {
+    limit: 10,
+    lease_until: Instant::ymd_hms(2021, 1, 1, 0, 5, 0),
+  };
+  let actual: Vec<_> = api
+    .outbox_store
+    .claim_due_emails(&claim)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|email| (email.id, email.next_attempt_at))
+    .collect();
+  assert_eq!(
+    actual,
+    vec![
+      (alice.id, Instant::ymd_hms(2021, 1, 1, 0, 5, 0)),
+      (bob.id, Instant::ymd_hms(2021, 1, 1, 0, 5, 0)),
+    ]
+  );
+
+  // Claimed emails are leased
+  let actual = api.outbox_store.claim_due_emails(&claim).await.unwrap();
+  assert_eq!(actual, vec![]);
+
+  // The lease expired without an outcome: the emails are due again
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 5, 0));
+  let actual: Vec<_> = api
+    .outbox_store
+    .claim_due_emails(&ClaimDueEmailsOptions {
+      limit: 1,
+      lease_until: Instant::ymd_hms(2021, 1, 1, 0, 10, 0),
+    })
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|email| email.id)
+    .collect();
+  assert_eq!(actual, vec![alice.id]);
+}
+
+pub(crate) async fn test_record_email_sent<TyClock, TyOutboxStore>(api: TestApi<TyClock, TyOutboxStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyOutboxStore: OutboxStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let email = api
+    .outbox_store
+    .queue_email(&queue_options("alice@example.com", "Hi"))
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api.outbox_store.record_email_sent(email.id).await.unwrap();
+
+  let actual = api.outbox_store.get_email(email.id).await.unwrap().unwrap();
+  assert_eq!(actual.status, OutboundEmailStatus::Sent);
+  assert_eq!(actual.sent_at, Some(Instant::ymd_hms(2021, 1, 1, 0, 0, 1)));
+
+  api.clock.as_ref().advance_by(Duration::hours(1));
+  let actual = api
+    .outbox_store
+    .claim_due_emails(&ClaimDueEmailsOptions {
+      limit: 10,
+      lease_until: Instant::ymd_hms(2021, 1, 1, 2, 0, 0),
+    })
+    .await
+    .unwrap();
+  assert_eq!(actual, vec![]);
+}
+
+pub(crate) async fn test_record_email_failure<TyClock, TyOutboxStore>(api: TestApi<TyClock, TyOutboxStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyOutboxStore: OutboxStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let email = api
+    .outbox_store
+    .queue_email(&queue_options("alice@example.com", "Hi"))
+    .await
+    .unwrap();
+  api
+    .outbox_store
+    .record_email_failure(&RecordEmailFailureOptions {
+      id: email.id,
+      error: "Connection refused".to_string(),
+      retry_at: Some(Instant::ymd_hms(2021, 1, 1, 0, 1, 0)),
+    })
+    .await
+    .unwrap();
+
+  let actual = api.outbox_store.get_email(email.id).await.unwrap().unwrap();
+  assert_eq!(actual.status, OutboundEmailStatus::Pending);
+  assert_eq!(actual.attempts, 1);
+  assert_eq!(actual.next_attempt_at, Instant::ymd_hms(2021, 1, 1, 0, 1, 0));
+  assert_eq!(actual.last_error.as_deref(), Some("Connection refused"));
+
+  let claim = ClaimDueEmailsOptions {
+    limit: 10,
+    lease_until: Instant::ymd_hms(2021, 1, 1, 1, 0, 0),
+  };
+  assert_eq!(api.outbox_store.claim_due_emails(&claim).await.unwrap(), vec![]);
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 1, 0));
+  let actual = api.outbox_store.claim_due_emails(&claim).await.unwrap();
+  assert_eq!(actual.len(), 1);
+
+  api
+    .outbox_store
+    .record_email_failure(&RecordEmailFailureOptions {
+      id: email.id,
+      error: "Mailbox unavailable".to_string(),
+      retry_at: None,
+    })
+    .await
+    .unwrap();
+  let actual = api.outbox_store.get_email(email.id).await.unwrap().unwrap();
+  assert_eq!(actual.status, OutboundEmailStatus::Failed);
+  assert_eq!(actual.attempts, 2);
+  assert_eq!(actual.last_error.as_deref(), Some("Mailbox unavailable"));
+
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 2, 0, 0));
+  assert_eq!(api.outbox_store.claim_due_emails(&claim).await.unwrap(), vec![]);
+}
@@ -0,0 +1,173 @@
+use argon2::{Algorithm, Argon2, Version};
+use etwin_core::password::{Password, PasswordHash, PasswordService};
+use rand_core::{CryptoRng, RngCore};
+use std::convert::TryInto;
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+
+pub use rand_core::OsRng;
+
+pub(crate) const ALG_ID_BYTES: &[u8; 8] = b"argon2id";
+const ALG_VERSION: u8 = 0;
+const HASH_LEN: usize = 85;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+  pub(crate) memory_cost: u32,
+  pub(crate) time_cost: u32,
+  pub(crate) parallelism: u32,
+}
+
+impl Argon2Params {
+  /// Creates the parameters from the memory cost (in KiB), the number of passes and the degree of parallelism.
+  pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Result<Self, argon2::Error> {
+    argon2::Params::new(memory_cost, time_cost, parallelism, Some(32))?;
+    Ok(Self {
+      memory_cost,
+      time_cost,
+      parallelism,
+    })
+  }
+
+  /// Parameters recommended by OWASP for Argon2id.
+  pub fn recommended() -> Self {
+    Self::new(19456, 2, 1).unwrap()
+  }
+
+  /// Cheap parameters, only suitable for tests.
+  pub fn weak_for_tests() -> Self {
+    Self::new(1024, 1, 1).unwrap()
+  }
+
+  fn hasher(&self) -> Argon2<'static> {
+    let params = argon2::Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32)).unwrap();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+  }
+
+  fn read(hash: &[u8]) -> Option<Self> {
+    if hash.len() != HASH_LEN || &hash[0..8] != ALG_ID_BYTES || hash[8] != ALG_VERSION {
+      return None;
+    }
+    let memory_cost = u32::from_be_bytes(hash[9..13].try_into().unwrap());
+    let time_cost = u32::from_be_bytes(hash[13..17].try_into().unwrap());
+    let parallelism = u32::from_be_bytes(hash[17..21].try_into().unwrap());
+    Self::new(memory_cost, time_cost, parallelism).ok()
+  }
+}
+
+pub struct Argon2PasswordService<R: CryptoRng + RngCore + Send + Sync> {
+  params: Argon2Params,
+  rng: Mutex<R>,
+}
+
+impl<R: CryptoRng + RngCore + Send + Sync> Argon2PasswordService<R> {
+  pub fn new(rng: R, params: Argon2Params) -> Self {
+    Self {
+      params,
+      rng: Mutex::new(rng),
+    }
+  }
+}
+
+impl Argon2PasswordService<OsRng> {
+  pub fn with_os_rng(params: Argon2Params) -> Self {
+    Self::new(OsRng, params)
+  }
+
+  pub fn recommended_for_tests() -> Self {
+    Self::new(OsRng, Argon2Params::weak_for_tests())
+  }
+}
+
+impl<R: CryptoRng + RngCore + Send + Sync> PasswordService for Argon2PasswordService<R> {
+  /// Hash layout: `"argon2id" | version: u8 | m_cost: u32 | t_cost: u32 | p_cost: u32 | salt: [u8; 32] | key: [u8; 32]`
+  fn hash(&self, clear_text: Password) -> PasswordHash {
+    let mut out: [u8; HASH_LEN] = [0; HASH_LEN];
+    {
+      let (prefix, key) = out.split_at_mut(53);
+      let (header, salt) = prefix.split_at_mut(21);
+      header[0..8].copy_from_slice(ALG_ID_BYTES);
+      header[8] = ALG_VERSION;
+      header[9..13].copy_from_slice(&self.params.memory_cost.to_be_bytes());
+      header[13..17].copy_from_slice(&self.params.time_cost.to_be_bytes());
+      header[17..21].copy_from_slice(&self.params.parallelism.to_be_bytes());
+      self.rng.lock().unwrap().fill_bytes(salt);
+      self
+        .params
+        .hasher()
+        .hash_password_into(&clear_text.0, salt, key)
+        .unwrap();
+    }
+    PasswordHash(Vec::from(&out[..]))
+  }
+
+  fn verify(&self, hash: PasswordHash, clear_text: Password) -> bool {
+    let hash = hash.0;
+    let params = match Argon2Params::read(&hash) {
+      Some(params) => params,
+      None => return false,
+    };
+    let salt = &hash[21..53];
+    let key = &hash[53..85];
+    let mut actual = [0; 32];
+    if params
+      .hasher()
+      .hash_password_into(&clear_text.0, salt, &mut actual)
+      .is_err()
+    {
+      return false;
+    }
+    actual.ct_eq(key).unwrap_u8() != 0
+  }
+
+  fn needs_rehash(&self, hash: &PasswordHash) -> bool {
+    Argon2Params::read(hash.as_slice()) != Some(self.params)
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<R: CryptoRng + RngCore + Send + Sync> neon::prelude::Finalize for Argon2PasswordService<R> {}
+
+#[cfg(test)]
+mod test {
+  use crate::argon2::{Argon2Params, Argon2PasswordService};
+  use crate::test::TestApi;
+  use etwin_core::password::PasswordService;
+  use rand_core::OsRng;
+
+  fn make_test_api() -> TestApi<Argon2PasswordService<OsRng>> {
+    let password = Argon2PasswordService::recommended_for_tests();
+
+    TestApi { password }
+  }
+
+  #[test]
+  fn test_hash_and_verify() {
+    crate::test::test_hash_and_verify(make_test_api());
+  }
+
+  #[test]
+  fn test_reject_invalid_password() {
+    crate::test::test_reject_invalid_password(make_test_api());
+  }
+
+  #[test]
+  fn test_hashes_are_unique_even_for_same_passwords() {
+    crate::test::test_hashes_are_unique_even_for_same_passwords(make_test_api());
+  }
+
+  #[test]
+  fn test_supports_having_different_passwords() {
+    crate::test::test_supports_having_different_passwords(make_test_api());
+  }
+
+  #[test]
+  fn test_needs_rehash_when_params_change() {
+    let weak = Argon2PasswordService::recommended_for_tests();
+    let strong = Argon2PasswordService::with_os_rng(Argon2Params::new(2048, 2, 1).unwrap());
+    let hash = weak.hash("hunter2".into());
+    assert!(!weak.needs_rehash(&hash));
+    assert!(strong.needs_rehash(&hash));
+    assert!(strong.verify(hash, "hunter2".into()));
+  }
+}
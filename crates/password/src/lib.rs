@@ -1,3 +1,5 @@
+pub mod argon2;
+pub mod multi;
 pub mod scrypt;
 #[cfg(test)]
 pub(crate) mod test;
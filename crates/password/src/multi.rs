@@ -0,0 +1,115 @@
+use crate::argon2::{Argon2Params, Argon2PasswordService};
+use etwin_core::password::{Password, PasswordHash, PasswordService};
+use rand_core::{CryptoRng, RngCore};
+
+pub use rand_core::OsRng;
+
+/// Password service supporting every hash format used by Eternaltwin.
+///
+/// New hashes use Argon2id. Legacy scrypt hashes are still verified, but are
+/// reported as needing a rehash so they can be upgraded on the next login.
+pub struct MultiPasswordService<R: CryptoRng + RngCore + Send + Sync> {
+  argon2: Argon2PasswordService<R>,
+}
+
+impl<R: CryptoRng + RngCore + Send + Sync> MultiPasswordService<R> {
+  pub fn new(rng: R, params: Argon2Params) -> Self {
+    Self {
+      argon2: Argon2PasswordService::new(rng, params),
+    }
+  }
+}
+
+impl MultiPasswordService<OsRng> {
+  pub fn with_os_rng(params: Argon2Params) -> Self {
+    Self::new(OsRng, params)
+  }
+
+  pub fn recommended_for_tests() -> Self {
+    Self::new(OsRng, Argon2Params::weak_for_tests())
+  }
+}
+
+impl<R: CryptoRng + RngCore + Send + Sync> PasswordService for MultiPasswordService<R> {
+  fn hash(&self, clear_text: Password) -> PasswordHash {
+    self.argon2.hash(clear_text)
+  }
+
+  fn verify(&self, hash: PasswordHash, clear_text: Password) -> bool {
+    let bytes = hash.as_slice();
+    if bytes.starts_with(crate::argon2::ALG_ID_BYTES) {
+      self.argon2.verify(hash, clear_text)
+    } else if bytes.starts_with(crate::scrypt::ALG_ID_BYTES) {
+      crate::scrypt::verify(&hash, &clear_text)
+    } else {
+      false
+    }
+  }
+
+  fn needs_rehash(&self, hash: &PasswordHash) -> bool {
+    self.argon2.needs_rehash(hash)
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<R: CryptoRng + RngCore + Send + Sync> neon::prelude::Finalize for MultiPasswordService<R> {}
+
+#[cfg(test)]
+mod test {
+  use crate::multi::MultiPasswordService;
+  use crate::test::TestApi;
+  use etwin_core::password::{PasswordHash, PasswordService};
+  use rand_core::OsRng;
+
+  fn make_test_api() -> TestApi<MultiPasswordService<OsRng>> {
+    let password = MultiPasswordService::recommended_for_tests();
+
+    TestApi { password }
+  }
+
+  #[test]
+  fn test_hash_and_verify() {
+    crate::test::test_hash_and_verify(make_test_api());
+  }
+
+  #[test]
+  fn test_reject_invalid_password() {
+    crate::test::test_reject_invalid_password(make_test_api());
+  }
+
+  #[test]
+  fn test_hashes_are_unique_even_for_same_passwords() {
+    crate::test::test_hashes_are_unique_even_for_same_passwords(make_test_api());
+  }
+
+  #[test]
+  fn test_supports_having_different_passwords() {
+    crate::test::test_supports_having_different_passwords(make_test_api());
+  }
+
+  #[test]
+  fn test_verify_legacy_scrypt_hunter2() {
+    let password = MultiPasswordService::recommended_for_tests();
+    let hash: Vec<u8> = hex::decode("736372797074000c0000000800000001c5ec1067adb434a19cb471dcfc13a8cec8c6e935ec7e14eda9f51a386924eeeb9fce39bb3d36f6101cc06189da63e0513a54553efbee9d2a058bafbda5231093c4ae5e9b3f87a2d002fa49ff75b868fd").unwrap();
+    let hash = PasswordHash::from(&hash[..]);
+    assert!(password.needs_rehash(&hash));
+    assert!(password.verify(hash.clone(), "hunter2".into()));
+    assert!(!password.verify(hash, "foo".into()));
+  }
+
+  #[test]
+  fn test_reject_unknown_format() {
+    let password = MultiPasswordService::recommended_for_tests();
+    let hash = PasswordHash::from(&b"unknown"[..]);
+    assert!(password.needs_rehash(&hash));
+    assert!(!password.verify(hash, "hunter2".into()));
+  }
+
+  #[test]
+  fn test_reject_malformed_scrypt_hash() {
+    let password = MultiPasswordService::recommended_for_tests();
+    let hash = PasswordHash::from(&b"scrypt\x00truncated"[..]);
+    assert!(password.needs_rehash(&hash));
+    assert!(!password.verify(hash, "hunter2".into()));
+  }
+}
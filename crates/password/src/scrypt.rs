@@ -12,7 +12,7 @@ pub use rand_core::OsRng;
 
 type HmacSha256 = Hmac<Sha256>;
 
-const ALG_ID_BYTES: &[u8; 6] = b"scrypt";
+pub(crate) const ALG_ID_BYTES: &[u8; 6] = b"scrypt";
 const ALG_VERSION: u8 = 0;
 
 #[derive(Clone, Copy, Debug)]
@@ -189,41 +189,51 @@ impl<R: CryptoRng + RngCore + Send + Sync> PasswordService for ScryptPasswordSer
   }
 
   fn verify(&self, hash: PasswordHash, clear_text: Password) -> bool {
-    let hash = hash.0;
-    assert_eq!(hash.len(), 96);
-    {
-      let alg_id = &hash[0..6];
-      assert_eq!(alg_id, ALG_ID_BYTES);
-      let v = hash[6];
-      assert_eq!(v, ALG_VERSION);
+    verify(&hash, &clear_text)
+  }
+}
+
+/// Check a password against a scrypt hash.
+///
+/// Malformed hashes and hashes from other algorithms never match.
+pub(crate) fn verify(hash: &PasswordHash, clear_text: &Password) -> bool {
+  let hash = hash.as_slice();
+  if hash.len() != 96 || &hash[0..6] != ALG_ID_BYTES || hash[6] != ALG_VERSION {
+    return false;
+  }
+  let params = {
+    let log_n = hash[7];
+    let r = u32::from_be_bytes([hash[8], hash[9], hash[10], hash[11]]);
+    let p = u32::from_be_bytes([hash[12], hash[13], hash[14], hash[15]]);
+    match scrypt::Params::new(log_n, r, p) {
+      Ok(params) => params,
+      Err(_) => return false,
     }
-    let params = {
-      let log_n = hash[7];
-      let r = u32::from_be_bytes([hash[8], hash[9], hash[10], hash[11]]);
-      let p = u32::from_be_bytes([hash[12], hash[13], hash[14], hash[15]]);
-      scrypt::Params::new(log_n, r, p).unwrap()
-    };
-    let salt = &hash[16..48];
-    let checksum = &hash[48..64];
-    let hmachash = &hash[64..96];
-    {
-      let actual_checksum = &Sha256::digest(&hash[0..48])[0..16];
-      assert_ne!(actual_checksum.ct_eq(checksum).unwrap_u8(), 0);
+  };
+  let salt = &hash[16..48];
+  let checksum = &hash[48..64];
+  let hmachash = &hash[64..96];
+  {
+    let actual_checksum = &Sha256::digest(&hash[0..48])[0..16];
+    if actual_checksum.ct_eq(checksum).unwrap_u8() == 0 {
+      return false;
     }
-    let key = {
-      let mut actual = [0; 64];
-      scrypt::scrypt(&clear_text.0, salt, &params, &mut actual).unwrap();
-      actual
-    };
-    let actual_hmac = {
-      type HmacSha256 = Hmac<Sha256>;
-      let mut mac = HmacSha256::new_from_slice(&key[32..]).unwrap();
-      mac.update(&hash[0..64]);
-      mac.finalize().into_bytes()
-    };
-    let actual_hmac: &[u8] = &actual_hmac;
-    actual_hmac.ct_eq(hmachash).unwrap_u8() != 0
   }
+  let key = {
+    let mut actual = [0; 64];
+    if scrypt::scrypt(&clear_text.0, salt, &params, &mut actual).is_err() {
+      return false;
+    }
+    actual
+  };
+  let actual_hmac = {
+    type HmacSha256 = Hmac<Sha256>;
+    let mut mac = HmacSha256::new_from_slice(&key[32..]).unwrap();
+    mac.update(&hash[0..64]);
+    mac.finalize().into_bytes()
+  };
+  let actual_hmac: &[u8] = &actual_hmac;
+  actual_hmac.ct_eq(hmachash).unwrap_u8() != 0
 }
 
 /// Temporarily import black_box function from `core`
@@ -286,4 +296,18 @@ mod test {
     let hash: Vec<u8> = hex::decode("736372797074000c0000000800000001c5ec1067adb434a19cb471dcfc13a8cec8c6e935ec7e14eda9f51a386924eeeb9fce39bb3d36f6101cc06189da63e0513a54553efbee9d2a058bafbda5231093c4ae5e9b3f87a2d002fa49ff75b868fd").unwrap();
     assert!(password.verify((&hash[..]).into(), "hunter2".into()));
   }
+
+  #[test]
+  fn test_reject_malformed_hash() {
+    let password = ScryptPasswordService::recommended_for_tests();
+    let hash: Vec<u8> = hex::decode("736372797074000c0000000800000001c5ec1067adb434a19cb471dcfc13a8cec8c6e935ec7e14eda9f51a386924eeeb9fce39bb3d36f6101cc06189da63e0513a54553efbee9d2a058bafbda5231093c4ae5e9b3f87a2d002fa49ff75b868fd").unwrap();
+    // Truncated
+    assert!(!password.verify((&hash[..64]).into(), "hunter2".into()));
+    // Corrupted checksum
+    let mut corrupted = hash;
+    corrupted[48] ^= 0xff;
+    assert!(!password.verify((&corrupted[..]).into(), "hunter2".into()));
+    // Other algorithm
+    assert!(!password.verify((&b"$argon2id$v=19$m=19456,t=2,p=1$"[..]).into(), "hunter2".into()));
+  }
 }
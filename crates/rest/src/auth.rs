@@ -0,0 +1,339 @@
+use crate::{RestFilter, RouterApi};
+use etwin_core::auth::{
+  AuthContext, AuthScope, CompleteMfaLoginOptions, EtwinOauthAccessTokenKey, GuestAuthContext, InvalidCredentialsError,
+  LoginResult, LoginThrottledError, RawCredentials, RawUserCredentials, SessionId, UserAndSession, UserAuthContext,
+};
+use etwin_core::dinoparc::DinoparcCredentials;
+use etwin_core::hammerfest::HammerfestCredentials;
+use etwin_core::password::Password;
+use etwin_core::types::AnyError;
+use etwin_core::user::{GetUserOptions, GetUserResult, UserFields, UserIdRef, UserRef, UserStore};
+use etwin_services::auth::DynAuthService;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::reject::Reject;
+use warp::{Filter, Rejection, Reply};
+
+/// Name of the cookie holding the session id.
+pub const SESSION_COOKIE: &str = "sid";
+
+/// Maximum size of a login request body.
+const MAX_LOGIN_BODY_SIZE: u64 = 16 * 1024;
+
+/// Error while resolving the credentials attached to a request.
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(tag = "error")]
+pub enum AuthenticationError {
+  InvalidCredentials,
+  InvalidAccessToken,
+  LoginThrottled,
+  InternalServerError,
+}
+
+impl AuthenticationError {
+  pub fn get_status_code(self) -> StatusCode {
+    match self {
+      Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+      Self::InvalidAccessToken => StatusCode::UNAUTHORIZED,
+      Self::LoginThrottled => StatusCode::TOO_MANY_REQUESTS,
+      Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+
+  /// Attempts rejected because of too many failures get `429 Too Many Requests`, and failures unrelated to the
+  /// credentials (e.g. database errors) get `500 Internal Server Error`.
+  pub(crate) fn from_credentials_error(e: &AnyError) -> Self {
+    if e.is::<LoginThrottledError>() {
+      Self::LoginThrottled
+    } else if e.is::<InvalidCredentialsError>() {
+      Self::InvalidCredentials
+    } else {
+      Self::InternalServerError
+    }
+  }
+}
+
+impl Reject for AuthenticationError {}
+
+/// Resolve the auth context of the request.
+///
+/// The `Authorization` header is checked first (HTTP Basic credentials for users or OAuth clients, or a bearer access
+/// token), then the session cookie. Requests without any of them are handled as guests. Invalid credentials reject the
+/// request with an `AuthenticationError`.
+pub fn authenticate(auth: Arc<DynAuthService>) -> BoxedFilter<(AuthContext,)> {
+  warp::header::optional::<String>("authorization")
+    .and(warp::cookie::optional::<String>(SESSION_COOKIE))
+    .and(warp::addr::remote())
+    .and_then(
+      move |authorization: Option<String>, session: Option<String>, remote: Option<SocketAddr>| {
+        let auth = Arc::clone(&auth);
+        async move {
+          resolve_auth_context(
+            &auth,
+            authorization.as_deref(),
+            session.as_deref(),
+            remote.map(|addr| addr.ip()),
+          )
+          .await
+          .map_err(warp::reject::custom)
+        }
+      },
+    )
+    .boxed()
+}
+
+async fn resolve_auth_context(
+  auth: &DynAuthService,
+  authorization: Option<&str>,
+  session: Option<&str>,
+  ip: Option<IpAddr>,
+) -> Result<AuthContext, AuthenticationError> {
+  if let Some(authorization) = authorization {
+    if let Some(credentials) = parse_basic_credentials(authorization) {
+      return auth
+        .raw_authenticate_credentials(&credentials, ip)
+        .await
+        .map_err(|e| AuthenticationError::from_credentials_error(&e));
+    }
+    if let Some(token) = authorization.strip_prefix("Bearer ") {
+      let token: EtwinOauthAccessTokenKey = token
+        .trim()
+        .parse()
+        .map_err(|_| AuthenticationError::InvalidAccessToken)?;
+      return auth
+        .authenticate_access_token(&token)
+        .await
+        .map_err(|_| AuthenticationError::InvalidAccessToken);
+    }
+  }
+
+  let session: Option<SessionId> = session.and_then(|session| session.parse().ok());
+  if let Some(session) = session {
+    let user_and_session = auth
+      .authenticate_session(session)
+      .await
+      .map_err(|_| AuthenticationError::InternalServerError)?;
+    // Unknown or expired sessions are handled as guests
+    if let Some(UserAndSession {
+      user, is_administrator, ..
+    }) = user_and_session
+    {
+      return Ok(AuthContext::User(UserAuthContext {
+        scope: AuthScope::Default,
+        user,
+        is_administrator,
+      }));
+    }
+  }
+
+  Ok(AuthContext::Guest(GuestAuthContext {
+    scope: AuthScope::Default,
+  }))
+}
+
+/// Parse the value of an `Authorization` header using the HTTP Basic scheme.
+///
+/// Returns `None` if the header uses another scheme or is malformed.
+fn parse_basic_credentials(authorization: &str) -> Option<RawCredentials> {
+  let encoded = authorization.strip_prefix("Basic ")?;
+  let decoded = base64::decode(encoded.trim()).ok()?;
+  let separator = decoded.iter().position(|b| *b == b':')?;
+  let login = std::str::from_utf8(&decoded[..separator]).ok()?;
+  Some(RawCredentials {
+    login: login.to_string(),
+    password: Password::from(&decoded[separator + 1..]),
+  })
+}
+
+/// Error while logging in, with either step.
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(tag = "error")]
+enum LoginError {
+  InvalidMethod,
+  InvalidRequestBody,
+  InvalidCredentials,
+  LoginThrottled,
+  InternalServerError,
+}
+
+impl LoginError {
+  fn get_status_code(self) -> StatusCode {
+    match self {
+      Self::InvalidMethod => StatusCode::UNPROCESSABLE_ENTITY,
+      Self::InvalidRequestBody => StatusCode::UNPROCESSABLE_ENTITY,
+      Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+      Self::LoginThrottled => StatusCode::TOO_MANY_REQUESTS,
+      Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+
+  fn from_credentials_error(e: &AnyError) -> Self {
+    match AuthenticationError::from_credentials_error(e) {
+      AuthenticationError::LoginThrottled => Self::LoginThrottled,
+      AuthenticationError::InternalServerError => Self::InternalServerError,
+      _ => Self::InvalidCredentials,
+    }
+  }
+}
+
+/// Get the user of a new session, with all the fields visible to the user themself.
+async fn get_complete_user(
+  user_store: &dyn UserStore,
+  user_and_session: &UserAndSession,
+) -> Result<GetUserResult, LoginError> {
+  user_store
+    .get_user(&GetUserOptions {
+      r#ref: UserRef::Id(UserIdRef {
+        id: user_and_session.user.id,
+      }),
+      fields: UserFields::Complete,
+      time: None,
+    })
+    .await
+    .map_err(|_| LoginError::InternalServerError)?
+    .ok_or(LoginError::InternalServerError)
+}
+
+fn session_cookie(session: SessionId) -> String {
+  format!("{}={}; Path=/; HttpOnly; SameSite=Lax", SESSION_COOKIE, session)
+}
+
+pub fn create_auth_filter(api: RouterApi) -> RestFilter {
+  let get_self = {
+    let api = api.clone();
+    warp::path!("self")
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .map(|acx: AuthContext| warp::reply::json(&acx).into_response())
+      .boxed()
+  };
+
+  let login = {
+    let api = api.clone();
+
+    enum LoginReply {
+      Session(GetUserResult, SessionId),
+      PendingMfa(LoginResult),
+    }
+
+    async fn handle_login(
+      auth: &DynAuthService,
+      user_store: &dyn UserStore,
+      method: Option<&str>,
+      body: &[u8],
+      ip: Option<IpAddr>,
+    ) -> Result<LoginReply, LoginError> {
+      let user_and_session = match method {
+        Some("Etwin") => {
+          let credentials: RawUserCredentials =
+            serde_json::from_slice(body).map_err(|_| LoginError::InvalidRequestBody)?;
+          match auth
+            .raw_login_with_credentials(&credentials, ip)
+            .await
+            .map_err(|e| LoginError::from_credentials_error(&e))?
+          {
+            LoginResult::Session(user_and_session) => user_and_session,
+            pending @ LoginResult::PendingMfa(_) => return Ok(LoginReply::PendingMfa(pending)),
+          }
+        }
+        Some("Dinoparc") => {
+          let credentials: DinoparcCredentials =
+            serde_json::from_slice(body).map_err(|_| LoginError::InvalidRequestBody)?;
+          auth
+            .register_or_login_with_dinoparc(&credentials)
+            .await
+            .map_err(|e| LoginError::from_credentials_error(&e))?
+        }
+        Some("Hammerfest") => {
+          let credentials: HammerfestCredentials =
+            serde_json::from_slice(body).map_err(|_| LoginError::InvalidRequestBody)?;
+          auth
+            .register_or_login_with_hammerfest(&credentials)
+            .await
+            .map_err(|e| LoginError::from_credentials_error(&e))?
+        }
+        _ => return Err(LoginError::InvalidMethod),
+      };
+      let user = get_complete_user(user_store, &user_and_session).await?;
+      Ok(LoginReply::Session(user, user_and_session.session.id))
+    }
+
+    warp::path!("self")
+      .and(warp::put())
+      .and(warp::query::<HashMap<String, String>>())
+      .and(warp::body::content_length_limit(MAX_LOGIN_BODY_SIZE))
+      .and(warp::body::bytes())
+      .and(warp::addr::remote())
+      .and_then(
+        move |query: HashMap<String, String>, body: Bytes, remote: Option<SocketAddr>| {
+          let auth = Arc::clone(&api.auth);
+          let user_store = Arc::clone(&api.user_store);
+          async move {
+            let method = query.get("method").map(String::as_str);
+            let res = handle_login(&auth, user_store.as_ref(), method, &body, remote.map(|addr| addr.ip())).await;
+            let reply = match res {
+              Ok(LoginReply::Session(user, session)) => warp::reply::with_header(
+                warp::reply::json(&user),
+                warp::http::header::SET_COOKIE,
+                session_cookie(session),
+              )
+              .into_response(),
+              Ok(LoginReply::PendingMfa(result)) => warp::reply::json(&result).into_response(),
+              Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()).into_response(),
+            };
+            Ok::<_, Rejection>(reply)
+          }
+        },
+      )
+      .boxed()
+  };
+
+  let complete_mfa_login = {
+    async fn handle_complete_mfa_login(
+      auth: &DynAuthService,
+      user_store: &dyn UserStore,
+      body: &[u8],
+      ip: Option<IpAddr>,
+    ) -> Result<(GetUserResult, SessionId), LoginError> {
+      let options: CompleteMfaLoginOptions =
+        serde_json::from_slice(body).map_err(|_| LoginError::InvalidRequestBody)?;
+      let user_and_session = auth
+        .complete_mfa_login(&options, ip)
+        .await
+        .map_err(|e| LoginError::from_credentials_error(&e))?;
+      let user = get_complete_user(user_store, &user_and_session).await?;
+      Ok((user, user_and_session.session.id))
+    }
+
+    warp::path!("self" / "mfa")
+      .and(warp::put())
+      .and(warp::body::content_length_limit(MAX_LOGIN_BODY_SIZE))
+      .and(warp::body::bytes())
+      .and(warp::addr::remote())
+      .and_then(move |body: Bytes, remote: Option<SocketAddr>| {
+        let auth = Arc::clone(&api.auth);
+        let user_store = Arc::clone(&api.user_store);
+        async move {
+          let res = handle_complete_mfa_login(&auth, user_store.as_ref(), &body, remote.map(|addr| addr.ip())).await;
+          let reply = match res {
+            Ok((user, session)) => warp::reply::with_header(
+              warp::reply::json(&user),
+              warp::http::header::SET_COOKIE,
+              session_cookie(session),
+            )
+            .into_response(),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()).into_response(),
+          };
+          Ok::<_, Rejection>(reply)
+        }
+      })
+      .boxed()
+  };
+
+  get_self.or(login).unify().or(complete_mfa_login).unify().boxed()
+}
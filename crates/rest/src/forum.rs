@@ -0,0 +1,456 @@
+use crate::auth::authenticate;
+use crate::{RestFilter, RouterApi};
+use etwin_core::auth::AuthContext;
+use etwin_core::forum::{
+  AddModeratorOptions, CreatePostError, CreatePostOptions, CreateThreadOptions, ForumPost, ForumSection,
+  ForumSectionId, ForumSectionIdRef, ForumSectionKey, ForumSectionKeyRef, ForumSectionListing, ForumSectionRef,
+  ForumThread, ForumThreadId, ForumThreadIdRef, ForumThreadKey, ForumThreadKeyRef, ForumThreadRef, ForumThreadTitle,
+  GetForumSectionOptions, GetThreadOptions, MarktwinText,
+};
+use etwin_core::user::UserRef;
+use etwin_services::forum::{
+  AddModeratorError, CreateThreadError, DynForumService, GetSectionError, GetSectionsError, GetThreadError,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// Maximum size of a thread, post or role grant body.
+const MAX_FORUM_BODY_SIZE: u64 = 64 * 1024;
+
+/// Number of threads returned by default when reading a section.
+const DEFAULT_THREAD_LIMIT: u32 = 20;
+
+/// Number of posts returned by default when reading a thread.
+const DEFAULT_POST_LIMIT: u32 = 10;
+
+/// Maximum number of threads or posts returned per page.
+const MAX_PAGE_LIMIT: u32 = 100;
+
+/// Parse a section reference from a path segment: either a section id or a section key.
+fn parse_section_ref(input: &str) -> Option<ForumSectionRef> {
+  if let Ok(id) = input.parse::<ForumSectionId>() {
+    return Some(ForumSectionIdRef::new(id).into());
+  }
+  let key: ForumSectionKey = input.parse().ok()?;
+  Some(ForumSectionKeyRef { key }.into())
+}
+
+/// Parse a thread reference from a path segment: either a thread id or a thread key.
+fn parse_thread_ref(input: &str) -> Option<ForumThreadRef> {
+  if let Ok(id) = input.parse::<ForumThreadId>() {
+    return Some(ForumThreadIdRef { id }.into());
+  }
+  let key: ForumThreadKey = input.parse().ok()?;
+  Some(ForumThreadKeyRef { key }.into())
+}
+
+/// Parse the `offset` and `limit` pagination query parameters.
+///
+/// Missing parameters use the provided defaults. Returns `None` if a parameter is not a valid integer or if the limit
+/// is zero or above `MAX_PAGE_LIMIT`.
+fn parse_page(query: &HashMap<String, String>, default_limit: u32) -> Option<(u32, u32)> {
+  let offset: u32 = match query.get("offset") {
+    Some(offset) => offset.parse().ok()?,
+    None => 0,
+  };
+  let limit: u32 = match query.get("limit") {
+    Some(limit) => limit.parse().ok()?,
+    None => default_limit,
+  };
+  if limit == 0 || limit > MAX_PAGE_LIMIT {
+    return None;
+  }
+  Some((offset, limit))
+}
+
+pub fn create_forum_filter(api: RouterApi) -> RestFilter {
+  let get_sections = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum GetSectionsRestError {
+      InternalServerError,
+    }
+
+    impl GetSectionsRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_get_sections(
+      forum: &DynForumService,
+      acx: &AuthContext,
+    ) -> Result<ForumSectionListing, GetSectionsRestError> {
+      forum.get_sections(acx).await.map_err(|e| match e {
+        GetSectionsError::Other(_) => GetSectionsRestError::InternalServerError,
+      })
+    }
+
+    let api = api.clone();
+    warp::path!("sections")
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and_then(move |acx: AuthContext| {
+        let forum = Arc::clone(&api.forum);
+        async move {
+          let res = handle_get_sections(&forum, &acx).await;
+          let reply = match res {
+            Ok(sections) => warp::reply::with_status(warp::reply::json(&sections), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  let get_section = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum GetSectionRestError {
+      InvalidSectionIdOrKey,
+      InvalidQueryParameters,
+      SectionNotFound,
+      InternalServerError,
+    }
+
+    impl GetSectionRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidSectionIdOrKey => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::InvalidQueryParameters => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::SectionNotFound => StatusCode::NOT_FOUND,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_get_section(
+      forum: &DynForumService,
+      acx: &AuthContext,
+      section: &str,
+      query: &HashMap<String, String>,
+    ) -> Result<ForumSection, GetSectionRestError> {
+      let section = parse_section_ref(section).ok_or(GetSectionRestError::InvalidSectionIdOrKey)?;
+      let (thread_offset, thread_limit) =
+        parse_page(query, DEFAULT_THREAD_LIMIT).ok_or(GetSectionRestError::InvalidQueryParameters)?;
+      let options = GetForumSectionOptions {
+        section,
+        thread_offset,
+        thread_limit,
+      };
+      forum.get_section(acx, &options).await.map_err(|e| match e {
+        GetSectionError::SectionNotFound => GetSectionRestError::SectionNotFound,
+        GetSectionError::Other(_) => GetSectionRestError::InternalServerError,
+      })
+    }
+
+    let api = api.clone();
+    warp::path!("sections" / String)
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::query::<HashMap<String, String>>())
+      .and_then(
+        move |section: String, acx: AuthContext, query: HashMap<String, String>| {
+          let forum = Arc::clone(&api.forum);
+          async move {
+            let res = handle_get_section(&forum, &acx, &section, &query).await;
+            let reply = match res {
+              Ok(section) => warp::reply::with_status(warp::reply::json(&section), StatusCode::OK),
+              Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+            };
+            Ok::<_, Rejection>(reply.into_response())
+          }
+        },
+      )
+      .boxed()
+  };
+
+  let create_thread = {
+    #[derive(Debug, Deserialize)]
+    struct CreateThreadBody {
+      title: ForumThreadTitle,
+      body: MarktwinText,
+    }
+
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum CreateThreadRestError {
+      InvalidSectionIdOrKey,
+      SectionNotFound,
+      Forbidden,
+      FailedToParseBody,
+      FailedToRenderBody,
+      InternalServerError,
+    }
+
+    impl CreateThreadRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidSectionIdOrKey => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::SectionNotFound => StatusCode::NOT_FOUND,
+          Self::Forbidden => StatusCode::FORBIDDEN,
+          Self::FailedToParseBody => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::FailedToRenderBody => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_create_thread(
+      forum: &DynForumService,
+      acx: &AuthContext,
+      section: &str,
+      body: CreateThreadBody,
+    ) -> Result<ForumThread, CreateThreadRestError> {
+      let section = parse_section_ref(section).ok_or(CreateThreadRestError::InvalidSectionIdOrKey)?;
+      let options = CreateThreadOptions {
+        section,
+        title: body.title,
+        body: body.body,
+      };
+      forum.create_thread(acx, &options).await.map_err(|e| match e {
+        CreateThreadError::SectionNotFound => CreateThreadRestError::SectionNotFound,
+        CreateThreadError::Forbidden => CreateThreadRestError::Forbidden,
+        CreateThreadError::FailedToParseBody => CreateThreadRestError::FailedToParseBody,
+        CreateThreadError::FailedToRenderBody => CreateThreadRestError::FailedToRenderBody,
+        CreateThreadError::Other(_) => CreateThreadRestError::InternalServerError,
+      })
+    }
+
+    let api = api.clone();
+    warp::path!("sections" / String)
+      .and(warp::post())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::body::content_length_limit(MAX_FORUM_BODY_SIZE))
+      .and(warp::body::json())
+      .and_then(move |section: String, acx: AuthContext, body: CreateThreadBody| {
+        let forum = Arc::clone(&api.forum);
+        async move {
+          let res = handle_create_thread(&forum, &acx, &section, body).await;
+          let reply = match res {
+            Ok(thread) => warp::reply::with_status(warp::reply::json(&thread), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  let add_moderator = {
+    #[derive(Debug, Deserialize)]
+    struct AddModeratorBody {
+      user: UserRef,
+    }
+
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum AddModeratorRestError {
+      InvalidSectionIdOrKey,
+      SectionNotFound,
+      GranteeNotFound,
+      Forbidden,
+      InternalServerError,
+    }
+
+    impl AddModeratorRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidSectionIdOrKey => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::SectionNotFound => StatusCode::NOT_FOUND,
+          Self::GranteeNotFound => StatusCode::NOT_FOUND,
+          Self::Forbidden => StatusCode::FORBIDDEN,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_add_moderator(
+      forum: &DynForumService,
+      acx: &AuthContext,
+      section: &str,
+      body: AddModeratorBody,
+    ) -> Result<ForumSection, AddModeratorRestError> {
+      let section = parse_section_ref(section).ok_or(AddModeratorRestError::InvalidSectionIdOrKey)?;
+      let options = AddModeratorOptions {
+        section,
+        user: body.user,
+      };
+      forum.add_moderator(acx, &options).await.map_err(|e| match e {
+        AddModeratorError::SectionNotFound => AddModeratorRestError::SectionNotFound,
+        AddModeratorError::GranteeNotFound => AddModeratorRestError::GranteeNotFound,
+        AddModeratorError::Forbidden => AddModeratorRestError::Forbidden,
+        AddModeratorError::Other(_) => AddModeratorRestError::InternalServerError,
+      })
+    }
+
+    let api = api.clone();
+    warp::path!("sections" / String / "moderators")
+      .and(warp::post())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::body::content_length_limit(MAX_FORUM_BODY_SIZE))
+      .and(warp::body::json())
+      .and_then(move |section: String, acx: AuthContext, body: AddModeratorBody| {
+        let forum = Arc::clone(&api.forum);
+        async move {
+          let res = handle_add_moderator(&forum, &acx, &section, body).await;
+          let reply = match res {
+            Ok(section) => warp::reply::with_status(warp::reply::json(&section), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  let get_thread = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum GetThreadRestError {
+      InvalidThreadIdOrKey,
+      InvalidQueryParameters,
+      ThreadNotFound,
+      InternalServerError,
+    }
+
+    impl GetThreadRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidThreadIdOrKey => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::InvalidQueryParameters => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::ThreadNotFound => StatusCode::NOT_FOUND,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_get_thread(
+      forum: &DynForumService,
+      acx: &AuthContext,
+      thread: &str,
+      query: &HashMap<String, String>,
+    ) -> Result<ForumThread, GetThreadRestError> {
+      let thread = parse_thread_ref(thread).ok_or(GetThreadRestError::InvalidThreadIdOrKey)?;
+      let (post_offset, post_limit) =
+        parse_page(query, DEFAULT_POST_LIMIT).ok_or(GetThreadRestError::InvalidQueryParameters)?;
+      let options = GetThreadOptions {
+        thread,
+        post_offset,
+        post_limit,
+      };
+      forum.get_thread(acx, &options).await.map_err(|e| match e {
+        GetThreadError::ThreadNotFound => GetThreadRestError::ThreadNotFound,
+        GetThreadError::Other(_) => GetThreadRestError::InternalServerError,
+      })
+    }
+
+    let api = api.clone();
+    warp::path!("threads" / String)
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::query::<HashMap<String, String>>())
+      .and_then(
+        move |thread: String, acx: AuthContext, query: HashMap<String, String>| {
+          let forum = Arc::clone(&api.forum);
+          async move {
+            let res = handle_get_thread(&forum, &acx, &thread, &query).await;
+            let reply = match res {
+              Ok(thread) => warp::reply::with_status(warp::reply::json(&thread), StatusCode::OK),
+              Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+            };
+            Ok::<_, Rejection>(reply.into_response())
+          }
+        },
+      )
+      .boxed()
+  };
+
+  let create_post = {
+    #[derive(Debug, Deserialize)]
+    struct CreatePostBody {
+      body: MarktwinText,
+    }
+
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum CreatePostRestError {
+      InvalidThreadIdOrKey,
+      ThreadNotFound,
+      Forbidden,
+      FailedToParseBody,
+      FailedToRenderBody,
+      InternalServerError,
+    }
+
+    impl CreatePostRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidThreadIdOrKey => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::ThreadNotFound => StatusCode::NOT_FOUND,
+          Self::Forbidden => StatusCode::FORBIDDEN,
+          Self::FailedToParseBody => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::FailedToRenderBody => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_create_post(
+      forum: &DynForumService,
+      acx: &AuthContext,
+      thread: &str,
+      body: CreatePostBody,
+    ) -> Result<ForumPost, CreatePostRestError> {
+      let thread = parse_thread_ref(thread).ok_or(CreatePostRestError::InvalidThreadIdOrKey)?;
+      let options = CreatePostOptions {
+        thread,
+        body: body.body,
+      };
+      forum.create_post(acx, &options).await.map_err(|e| match e {
+        CreatePostError::ThreadNotFound => CreatePostRestError::ThreadNotFound,
+        CreatePostError::Forbidden => CreatePostRestError::Forbidden,
+        CreatePostError::FailedToParseBody => CreatePostRestError::FailedToParseBody,
+        CreatePostError::FailedToRenderBody => CreatePostRestError::FailedToRenderBody,
+        CreatePostError::Other(_) => CreatePostRestError::InternalServerError,
+      })
+    }
+
+    warp::path!("threads" / String / "posts")
+      .and(warp::post())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::body::content_length_limit(MAX_FORUM_BODY_SIZE))
+      .and(warp::body::json())
+      .and_then(move |thread: String, acx: AuthContext, body: CreatePostBody| {
+        let forum = Arc::clone(&api.forum);
+        async move {
+          let res = handle_create_post(&forum, &acx, &thread, body).await;
+          let reply = match res {
+            Ok(post) => warp::reply::with_status(warp::reply::json(&post), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  get_sections
+    .or(get_section)
+    .unify()
+    .or(create_thread)
+    .unify()
+    .or(add_moderator)
+    .unify()
+    .or(get_thread)
+    .unify()
+    .or(create_post)
+    .unify()
+    .boxed()
+}
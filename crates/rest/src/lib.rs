@@ -1,19 +1,29 @@
-use etwin_core::auth::{AuthContext, AuthScope, GuestAuthContext};
+pub mod auth;
+pub mod forum;
+pub mod oauth;
+pub mod openapi;
+pub mod users;
+
+use crate::auth::{authenticate, AuthenticationError};
+use etwin_core::auth::AuthContext;
 use etwin_core::dinoparc::{
   DinoparcDinozId, DinoparcServer, DinoparcUserId, EtwinDinoparcDinoz, EtwinDinoparcUser, GetDinoparcDinozOptions,
   GetDinoparcUserOptions,
 };
 use etwin_core::hammerfest::{GetHammerfestUserOptions, HammerfestServer, HammerfestUser, HammerfestUserId};
 use etwin_core::types::AnyError;
+use etwin_core::user::UserStore;
+use etwin_services::auth::DynAuthService;
 use etwin_services::dinoparc::DynDinoparcService;
+use etwin_services::forum::DynForumService;
 use etwin_services::hammerfest::DynHammerfestService;
 pub use serde::Serialize;
 use std::sync::Arc;
 use warp::filters::BoxedFilter;
 use warp::http::StatusCode;
 use warp::reject::Reject;
-use warp::reply::{Json, WithStatus};
-use warp::{Filter, Rejection};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
 
 #[derive(Debug)]
 struct ServerError(AnyError);
@@ -22,14 +32,47 @@ impl Reject for ServerError {}
 
 #[derive(Clone)]
 pub struct RouterApi {
+  pub auth: Arc<DynAuthService>,
   pub dinoparc: Arc<DynDinoparcService>,
+  pub forum: Arc<DynForumService>,
   pub hammerfest: Arc<DynHammerfestService>,
+  pub user_store: Arc<dyn UserStore>,
 }
 
-pub type RestFilter = BoxedFilter<(WithStatus<Json>,)>;
+pub type RestFilter = BoxedFilter<(Response,)>;
 
 pub fn create_rest_filter(api: RouterApi) -> RestFilter {
-  warp::path("archive").and(create_archive_filter(api)).boxed()
+  let archive = warp::path("archive").and(create_archive_filter(api.clone()));
+  let auth = warp::path("auth").and(auth::create_auth_filter(api.clone()));
+  let forum = warp::path("forum").and(forum::create_forum_filter(api.clone()));
+  let oauth = warp::path("oauth").and(oauth::create_oauth_filter(api.clone()));
+  let users = warp::path("users").and(users::create_users_filter(api));
+  let openapi = warp::path!("openapi.json")
+    .and(warp::get())
+    .map(|| warp::reply::json(&openapi::openapi()).into_response())
+    .boxed();
+  archive
+    .or(auth)
+    .unify()
+    .or(forum)
+    .unify()
+    .or(oauth)
+    .unify()
+    .or(users)
+    .unify()
+    .or(openapi)
+    .unify()
+    .recover(handle_rejection)
+    .unify()
+    .boxed()
+}
+
+/// Reply to requests rejected because of invalid credentials; other rejections are passed through.
+async fn handle_rejection(rejection: Rejection) -> Result<Response, Rejection> {
+  match rejection.find::<AuthenticationError>() {
+    Some(e) => Ok(warp::reply::with_status(warp::reply::json(e), e.get_status_code()).into_response()),
+    None => Err(rejection),
+  }
 }
 
 pub fn create_archive_filter(api: RouterApi) -> RestFilter {
@@ -58,14 +101,12 @@ pub fn create_archive_dinoparc_filter(api: RouterApi) -> RestFilter {
 
     async fn handle_get_user(
       dinoparc: &DynDinoparcService,
+      acx: &AuthContext,
       server: DinoparcServer,
       id: DinoparcUserId,
     ) -> Result<EtwinDinoparcUser, GetDinoparcUserError> {
-      let acx = AuthContext::Guest(GuestAuthContext {
-        scope: AuthScope::Default,
-      });
       match dinoparc
-        .get_user(&acx, &GetDinoparcUserOptions { server, id, time: None })
+        .get_user(acx, &GetDinoparcUserOptions { server, id, time: None })
         .await
       {
         Ok(Some(user)) => Ok(user),
@@ -76,15 +117,16 @@ pub fn create_archive_dinoparc_filter(api: RouterApi) -> RestFilter {
 
     let api = api.clone();
     warp::path!(DinoparcServer / "users" / DinoparcUserId)
-      .and_then(move |server: DinoparcServer, id: DinoparcUserId| {
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and_then(move |server: DinoparcServer, id: DinoparcUserId, acx: AuthContext| {
         let dinoparc = Arc::clone(&api.dinoparc);
         async move {
-          let res = handle_get_user(&dinoparc, server, id).await;
+          let res = handle_get_user(&dinoparc, &acx, server, id).await;
           let reply = match res {
             Ok(user) => warp::reply::with_status(warp::reply::json(&user), StatusCode::OK),
             Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
           };
-          Ok::<_, Rejection>(reply)
+          Ok::<_, Rejection>(reply.into_response())
         }
       })
       .boxed()
@@ -109,14 +151,12 @@ pub fn create_archive_dinoparc_filter(api: RouterApi) -> RestFilter {
 
     async fn handle_get_dinoz(
       dinoparc: &DynDinoparcService,
+      acx: &AuthContext,
       server: DinoparcServer,
       id: DinoparcDinozId,
     ) -> Result<EtwinDinoparcDinoz, GetDinoparcDinozError> {
-      let acx = AuthContext::Guest(GuestAuthContext {
-        scope: AuthScope::Default,
-      });
       match dinoparc
-        .get_dinoz(&acx, &GetDinoparcDinozOptions { server, id, time: None })
+        .get_dinoz(acx, &GetDinoparcDinozOptions { server, id, time: None })
         .await
       {
         Ok(Some(user)) => Ok(user),
@@ -127,15 +167,16 @@ pub fn create_archive_dinoparc_filter(api: RouterApi) -> RestFilter {
 
     // let api = api.clone();
     warp::path!(DinoparcServer / "dinoz" / DinoparcDinozId)
-      .and_then(move |server: DinoparcServer, id: DinoparcDinozId| {
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and_then(move |server: DinoparcServer, id: DinoparcDinozId, acx: AuthContext| {
         let dinoparc = Arc::clone(&api.dinoparc);
         async move {
-          let res = handle_get_dinoz(&dinoparc, server, id).await;
+          let res = handle_get_dinoz(&dinoparc, &acx, server, id).await;
           let reply = match res {
             Ok(dinoz) => warp::reply::with_status(warp::reply::json(&dinoz), StatusCode::OK),
             Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
           };
-          Ok::<_, Rejection>(reply)
+          Ok::<_, Rejection>(reply.into_response())
         }
       })
       .boxed()
@@ -164,14 +205,12 @@ pub fn create_archive_hammerfest_filter(api: RouterApi) -> RestFilter {
 
     async fn handle_get_user(
       hammerfest: &DynHammerfestService,
+      acx: &AuthContext,
       server: HammerfestServer,
       id: HammerfestUserId,
     ) -> Result<HammerfestUser, GetHammerfestUserError> {
-      let acx = AuthContext::Guest(GuestAuthContext {
-        scope: AuthScope::Default,
-      });
       match hammerfest
-        .get_user(&acx, &GetHammerfestUserOptions { server, id, time: None })
+        .get_user(acx, &GetHammerfestUserOptions { server, id, time: None })
         .await
       {
         Ok(Some(user)) => Ok(user),
@@ -182,17 +221,20 @@ pub fn create_archive_hammerfest_filter(api: RouterApi) -> RestFilter {
 
     // let api = api.clone();
     warp::path!(HammerfestServer / "users" / HammerfestUserId)
-      .and_then(move |server: HammerfestServer, id: HammerfestUserId| {
-        let hammerfest = Arc::clone(&api.hammerfest);
-        async move {
-          let res = handle_get_user(&hammerfest, server, id).await;
-          let reply = match res {
-            Ok(user) => warp::reply::with_status(warp::reply::json(&user), StatusCode::OK),
-            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
-          };
-          Ok::<_, Rejection>(reply)
-        }
-      })
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and_then(
+        move |server: HammerfestServer, id: HammerfestUserId, acx: AuthContext| {
+          let hammerfest = Arc::clone(&api.hammerfest);
+          async move {
+            let res = handle_get_user(&hammerfest, &acx, server, id).await;
+            let reply = match res {
+              Ok(user) => warp::reply::with_status(warp::reply::json(&user), StatusCode::OK),
+              Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+            };
+            Ok::<_, Rejection>(reply.into_response())
+          }
+        },
+      )
       .boxed()
   };
 
@@ -201,30 +243,118 @@ pub fn create_archive_hammerfest_filter(api: RouterApi) -> RestFilter {
 
 #[cfg(test)]
 mod test {
+  use crate::auth::AuthenticationError;
+  use crate::openapi::openapi;
   use crate::{create_archive_dinoparc_filter, create_rest_filter, RouterApi};
-  use etwin_core::clock::VirtualClock;
-  use etwin_core::core::Instant;
-  use etwin_core::dinoparc::DinoparcStore;
-  use etwin_core::hammerfest::{HammerfestClient, HammerfestStore};
+  use chrono::Duration;
+  use etwin_auth_store::mem::MemAuthStore;
+  use etwin_core::auth::{
+    AuthContext, AuthScope, AuthStore, InvalidCredentialsError, LoginThrottledError, RegisterWithUsernameOptions,
+    UserAuthContext,
+  };
+  use etwin_core::clock::{Clock, VirtualClock};
+  use etwin_core::core::{Instant, LocaleId};
+  use etwin_core::dinoparc::{DinoparcClient, DinoparcStore};
+  use etwin_core::email::{EmailFormatter, Mailer};
+  use etwin_core::forum::{ForumStore, UpsertSystemSectionOptions};
+  use etwin_core::hammerfest::{HammerfestClient, HammerfestPassword, HammerfestServer, HammerfestStore};
   use etwin_core::link::LinkStore;
+  use etwin_core::mfa::ConfirmTotpEnrollmentOptions;
+  use etwin_core::oauth::{OauthProviderStore, UpsertSystemClientOptions};
+  use etwin_core::password::{Password, PasswordService};
+  use etwin_core::token::TokenStore;
+  use etwin_core::twinoid::{TwinoidClient, TwinoidStore};
+  use etwin_core::types::AnyError;
   use etwin_core::user::UserStore;
-  use etwin_core::uuid::Uuid4Generator;
+  use etwin_core::uuid::{Uuid4Generator, UuidGenerator};
+  use etwin_dinoparc_client::mem::MemDinoparcClient;
   use etwin_dinoparc_store::mem::MemDinoparcStore;
+  use etwin_email_formatter::json::JsonEmailFormatter;
+  use etwin_forum_store::mem::MemForumStore;
   use etwin_hammerfest_client::MemHammerfestClient;
   use etwin_hammerfest_store::mem::MemHammerfestStore;
   use etwin_link_store::mem::MemLinkStore;
+  use etwin_log::{Logger, NoopLogger};
+  use etwin_mailer::mem::MemMailer;
+  use etwin_oauth_provider_store::mem::MemOauthProviderStore;
+  use etwin_password::multi::MultiPasswordService;
+  use etwin_services::archive::{ArchiveWorker, DynArchiveWorker};
+  use etwin_services::auth::{AuthEvent, AuthService};
   use etwin_services::dinoparc::DinoparcService;
-  use etwin_services::hammerfest::HammerfestService;
+  use etwin_services::forum::{ForumEvent, ForumService};
+  use etwin_services::hammerfest::{HammerfestEvent, HammerfestService};
+  use etwin_services::totp;
+  use etwin_token_store::mem::MemTokenStore;
+  use etwin_twinoid_client::mem::MemTwinoidClient;
+  use etwin_twinoid_store::mem::MemTwinoidStore;
   use etwin_user_store::mem::MemUserStore;
+  use std::collections::{BTreeSet, HashMap};
   use std::sync::Arc;
 
   fn create_api() -> RouterApi {
+    create_api_and_hammerfest_client().0
+  }
+
+  fn create_api_and_hammerfest_client() -> (
+    RouterApi,
+    Arc<MemHammerfestClient<Arc<VirtualClock>>>,
+    Arc<VirtualClock>,
+    DynArchiveWorker,
+    Arc<dyn OauthProviderStore>,
+  ) {
     let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
-    let hammerfest_client: Arc<dyn HammerfestClient> = Arc::new(MemHammerfestClient::new(Arc::clone(&clock)));
+    let uuid_generator: Arc<dyn UuidGenerator> = Arc::new(Uuid4Generator);
+    let password_service: Arc<dyn PasswordService> = Arc::new(MultiPasswordService::recommended_for_tests());
+    let dinoparc_client: Arc<dyn DinoparcClient> = Arc::new(MemDinoparcClient::new(Arc::clone(&clock)));
+    let mem_hammerfest_client = Arc::new(MemHammerfestClient::new(Arc::clone(&clock)));
+    let hammerfest_client: Arc<dyn HammerfestClient> = Arc::clone(&mem_hammerfest_client) as Arc<dyn HammerfestClient>;
+    let twinoid_client: Arc<dyn TwinoidClient> = Arc::new(MemTwinoidClient::new());
+    let auth_store: Arc<dyn AuthStore> = Arc::new(MemAuthStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator)));
     let hammerfest_store: Arc<dyn HammerfestStore> = Arc::new(MemHammerfestStore::new(Arc::clone(&clock)));
     let dinoparc_store: Arc<dyn DinoparcStore> = Arc::new(MemDinoparcStore::new(Arc::clone(&clock)));
+    let forum_store: Arc<dyn ForumStore> =
+      Arc::new(MemForumStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator)));
     let link_store: Arc<dyn LinkStore> = Arc::new(MemLinkStore::new(Arc::clone(&clock)));
-    let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Uuid4Generator));
+    let oauth_provider_store: Arc<dyn OauthProviderStore> = Arc::new(MemOauthProviderStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&password_service),
+      Arc::clone(&uuid_generator),
+    ));
+    let token_store: Arc<dyn TokenStore> = Arc::new(MemTokenStore::new(Arc::clone(&clock)));
+    let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(MemTwinoidStore::new(Arc::clone(&clock)));
+    let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator)));
+
+    let (archive_queue, archive_worker) = ArchiveWorker::new(
+      10,
+      Arc::clone(&dinoparc_client),
+      Arc::clone(&dinoparc_store),
+      Arc::clone(&hammerfest_client),
+      Arc::clone(&hammerfest_store),
+      Arc::new(NoopLogger) as Arc<dyn Logger<AuthEvent>>,
+    );
+    let auth = Arc::new(
+      AuthService::new(
+        auth_store,
+        Arc::clone(&clock) as Arc<dyn Clock>,
+        Arc::clone(&dinoparc_client),
+        Arc::clone(&dinoparc_store),
+        Arc::new(JsonEmailFormatter) as Arc<dyn EmailFormatter>,
+        Arc::clone(&hammerfest_client),
+        Arc::clone(&hammerfest_store),
+        Arc::clone(&link_store),
+        Arc::new(NoopLogger) as Arc<dyn Logger<AuthEvent>>,
+        Arc::new(MemMailer::new()) as Arc<dyn Mailer>,
+        Arc::clone(&oauth_provider_store),
+        password_service,
+        token_store,
+        Arc::clone(&user_store),
+        twinoid_client,
+        twinoid_store,
+        uuid_generator,
+        "dev_secret".as_bytes().to_vec(),
+      )
+      .with_archive_queue(archive_queue),
+    );
 
     let dinoparc = Arc::new(DinoparcService::new(
       dinoparc_store,
@@ -232,14 +362,69 @@ mod test {
       Arc::clone(&user_store),
     ));
 
+    let forum = Arc::new(ForumService::new(
+      Arc::clone(&clock) as Arc<dyn Clock>,
+      forum_store,
+      Arc::new(NoopLogger) as Arc<dyn Logger<ForumEvent>>,
+      Arc::clone(&user_store),
+    ));
+
     let hammerfest = Arc::new(HammerfestService::new(
       hammerfest_client,
       hammerfest_store,
       Arc::clone(&link_store),
+      Arc::new(NoopLogger) as Arc<dyn Logger<HammerfestEvent>>,
       Arc::clone(&user_store),
     ));
 
-    RouterApi { dinoparc, hammerfest }
+    let api = RouterApi {
+      auth,
+      dinoparc,
+      forum,
+      hammerfest,
+      user_store,
+    };
+    (api, mem_hammerfest_client, clock, archive_worker, oauth_provider_store)
+  }
+
+  async fn register_alice(api: &RouterApi) {
+    api
+      .auth
+      .register_with_username(&RegisterWithUsernameOptions {
+        username: "alice".parse().unwrap(),
+        display_name: "Alice".parse().unwrap(),
+        password: Password::from("aaaaaaaaaa"),
+      })
+      .await
+      .unwrap();
+  }
+
+  async fn register_bob(api: &RouterApi) {
+    api
+      .auth
+      .register_with_username(&RegisterWithUsernameOptions {
+        username: "bob".parse().unwrap(),
+        display_name: "Bob".parse().unwrap(),
+        password: Password::from("bbbbbbbbbb"),
+      })
+      .await
+      .unwrap();
+  }
+
+  async fn create_main_section(api: &RouterApi) {
+    api
+      .forum
+      .upsert_system_section(&UpsertSystemSectionOptions {
+        key: "fr_main".parse().unwrap(),
+        display_name: "Forum Général".parse().unwrap(),
+        locale: Some(LocaleId::FrFr),
+      })
+      .await
+      .unwrap();
+  }
+
+  fn basic_auth(login: &str, password: &str) -> String {
+    format!("Basic {}", base64::encode(format!("{}:{}", login, password)))
   }
 
   #[tokio::test]
@@ -284,4 +469,838 @@ mod test {
     let body: &str = std::str::from_utf8(res.body()).unwrap();
     assert_eq!(body, "{\"error\":\"DinoparcDinozNotFound\"}");
   }
+
+  #[tokio::test]
+  async fn test_hammerfest_user_private_data() {
+    let (api, hammerfest_client, _, mut archive_worker, _) = create_api_and_hammerfest_client();
+    hammerfest_client.create_user(
+      HammerfestServer::HammerfestFr,
+      "123".parse().unwrap(),
+      "alice".parse().unwrap(),
+      HammerfestPassword::new("aaaaa".to_string()),
+    );
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self?method=Hammerfest")
+      .json(&serde_json::json!({"server": "hammerfest.fr", "username": "alice", "password": "aaaaa"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let cookie = res.headers()["set-cookie"].to_str().unwrap();
+    let session = cookie.split(';').next().unwrap().to_string();
+    // The login only queued the archival of the Hammerfest session
+    assert_eq!(archive_worker.archive_queued().await, 1);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/archive/hammerfest/hammerfest.fr/users/123")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["username"], "alice");
+    assert!(body.get("private").is_none());
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/archive/hammerfest/hammerfest.fr/users/123")
+      .header("cookie", session.as_str())
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["private"], serde_json::json!({"email": null, "tokens": 0}));
+  }
+
+  #[tokio::test]
+  async fn test_complete_mfa_login() {
+    let (api, _, clock, _, _) = create_api_and_hammerfest_client();
+    let alice = api
+      .auth
+      .register_with_username(&RegisterWithUsernameOptions {
+        username: "alice".parse().unwrap(),
+        display_name: "Alice".parse().unwrap(),
+        password: Password::from("aaaaaaaaaa"),
+      })
+      .await
+      .unwrap();
+    let acx = AuthContext::User(UserAuthContext {
+      scope: AuthScope::Default,
+      user: alice.user.clone(),
+      is_administrator: alice.is_administrator,
+    });
+    let enrollment = api.auth.enroll_totp(&acx).await.unwrap();
+    let current_code = || totp::hotp(&enrollment.secret, totp::time_step(clock.now()));
+    api
+      .auth
+      .confirm_totp_enrollment(
+        &acx,
+        &ConfirmTotpEnrollmentOptions {
+          secret: enrollment.secret.clone(),
+          code: current_code(),
+          current_code: None,
+        },
+      )
+      .await
+      .unwrap();
+    clock.advance_by(Duration::seconds(30));
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self?method=Etwin")
+      .json(&serde_json::json!({"login": "alice", "password": "61616161616161616161"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("set-cookie").is_none());
+    let pending: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(pending["type"], "PendingMfa");
+    let token = pending["token"].as_str().unwrap().to_string();
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self/mfa")
+      .json(&serde_json::json!({"token": token, "proof": {"type": "RecoveryCode", "code": "aaaaa-aaaaa"}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 401);
+    assert!(res.headers().get("set-cookie").is_none());
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidCredentials\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self/mfa")
+      .json(&serde_json::json!({"token": token, "proof": {"type": "Totp", "code": current_code().as_str()}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let user: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(user["username"], "alice");
+    let cookie = res.headers()["set-cookie"].to_str().unwrap();
+    let session = cookie.split(';').next().unwrap().to_string();
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/auth/self")
+      .header("cookie", session.as_str())
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let acx: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(acx["type"], "User");
+    assert_eq!(acx["user"]["display_name"]["current"]["value"], "Alice");
+  }
+
+  #[tokio::test]
+  async fn test_create_oauth_access_token_invalid_grant() {
+    let (api, _, clock, _, oauth_provider_store) = create_api_and_hammerfest_client();
+    register_alice(&api).await;
+    oauth_provider_store
+      .upsert_system_client(&UpsertSystemClientOptions {
+        key: "eternalfest@clients".parse().unwrap(),
+        display_name: "Eternalfest".parse().unwrap(),
+        app_uri: "http://localhost:50313/".parse().unwrap(),
+        callback_uri: "http://localhost:50313/oauth/callback".parse().unwrap(),
+        secret: Password::from("eternalfest_secret"),
+      })
+      .await
+      .unwrap();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/oauth/authorize?client_id=eternalfest@clients&response_type=code")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 302);
+    let location = res.headers()["location"].to_str().unwrap();
+    let (_, code) = location.split_once("?code=").unwrap();
+    let code = code.to_string();
+    let client_auth = basic_auth("eternalfest@clients", "eternalfest_secret");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/oauth/token")
+      .header("authorization", client_auth.as_str())
+      .json(&serde_json::json!({"code": "not_a_code"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 400);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"invalid_grant\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/oauth/token")
+      .header("authorization", client_auth.as_str())
+      .json(&serde_json::json!({ "code": code }))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+
+    clock.advance_by(Duration::minutes(10));
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/oauth/token")
+      .header("authorization", client_auth.as_str())
+      .json(&serde_json::json!({ "code": code }))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 400);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"invalid_grant\"}");
+  }
+
+  #[tokio::test]
+  async fn test_hammerfest_login_wrong_password() {
+    let (api, hammerfest_client, _, _, _) = create_api_and_hammerfest_client();
+    hammerfest_client.create_user(
+      HammerfestServer::HammerfestFr,
+      "123".parse().unwrap(),
+      "alice".parse().unwrap(),
+      HammerfestPassword::new("aaaaa".to_string()),
+    );
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self?method=Hammerfest")
+      .json(&serde_json::json!({"server": "hammerfest.fr", "username": "alice", "password": "bbbbb"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 401);
+    assert!(res.headers().get("set-cookie").is_none());
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidCredentials\"}");
+  }
+
+  #[tokio::test]
+  async fn test_archive_with_invalid_credentials() {
+    let api = create_api();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/archive/hammerfest/hammerfest.fr/users/123")
+      .header("authorization", format!("Basic {}", base64::encode("alice:bbbbbbbbbb")))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 401);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidCredentials\"}");
+  }
+
+  #[tokio::test]
+  async fn test_guest_self() {
+    let api = create_api();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> =
+      warp::test::request().path("/auth/self").reply(&router).await;
+    assert_eq!(res.status(), 200);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"type\":\"Guest\",\"scope\":\"Default\"}");
+  }
+
+  #[tokio::test]
+  async fn test_login_and_use_session() {
+    let api = create_api();
+    register_alice(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PUT")
+      .path("/auth/self?method=Etwin")
+      .body(format!(
+        "{{\"login\":\"alice\",\"password\":\"{}\"}}",
+        hex::encode("aaaaaaaaaa")
+      ))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let cookie = res.headers()["set-cookie"].to_str().unwrap();
+    let session = cookie.split(';').next().unwrap();
+    assert!(session.starts_with("sid="));
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/auth/self")
+      .header("cookie", session)
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["type"], "User");
+    assert_eq!(body["user"]["display_name"]["current"]["value"], "Alice");
+  }
+
+  #[tokio::test]
+  async fn test_basic_auth_wrong_password() {
+    let api = create_api();
+    register_alice(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/auth/self")
+      .header("authorization", format!("Basic {}", base64::encode("alice:bbbbbbbbbb")))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 401);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidCredentials\"}");
+  }
+
+  #[tokio::test]
+  async fn test_unknown_user() {
+    let api = create_api();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/users/00000000-0000-0000-0000-000000000000")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"UserNotFound\"}");
+  }
+
+  #[tokio::test]
+  async fn test_update_user_requires_auth() {
+    let api = create_api();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("PATCH")
+      .path("/users/00000000-0000-0000-0000-000000000000")
+      .json(&serde_json::json!({"display_name": "Bob"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 401);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"Unauthenticated\"}");
+  }
+
+  #[tokio::test]
+  async fn test_forum_sections_as_guest() {
+    let api = create_api();
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> =
+      warp::test::request().path("/forum/sections").reply(&router).await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["count"], 1);
+    assert_eq!(body["items"][0]["key"], "fr_main");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/forum/sections/fr_main?offset=0&limit=5")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["display_name"], "Forum Général");
+    assert_eq!(body["threads"]["limit"], 5);
+  }
+
+  #[tokio::test]
+  async fn test_forum_section_errors() {
+    let api = create_api();
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/forum/sections/en_main")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"SectionNotFound\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> =
+      warp::test::request().path("/forum/sections/Main").reply(&router).await;
+    assert_eq!(res.status(), 422);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidSectionIdOrKey\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/forum/sections/fr_main?limit=0")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 422);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"InvalidQueryParameters\"}");
+  }
+
+  #[tokio::test]
+  async fn test_forum_create_thread_as_guest() {
+    let api = create_api();
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main")
+      .json(&serde_json::json!({"title": "Hello", "body": "World"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 403);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"Forbidden\"}");
+  }
+
+  #[tokio::test]
+  async fn test_forum_thread_and_posts() {
+    let api = create_api();
+    register_alice(&api).await;
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"title": "Hello", "body": "**First** discussion thread"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let thread: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let thread_id = thread["id"].as_str().unwrap().to_string();
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path(&format!("/forum/threads/{}/posts", thread_id))
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"body": "Reply"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path(&format!("/forum/threads/{}?offset=1&limit=1", thread_id))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["title"], "Hello");
+    assert_eq!(body["posts"]["count"], 2);
+    assert_eq!(body["posts"]["offset"], 1);
+    assert_eq!(body["posts"]["items"].as_array().unwrap().len(), 1);
+    assert_eq!(
+      body["posts"]["items"][0]["revisions"]["last"]["content"]["marktwin"],
+      "Reply"
+    );
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/forum/sections/fr_main")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["threads"]["count"], 1);
+    assert_eq!(body["threads"]["items"][0]["id"], thread_id.as_str());
+  }
+
+  #[tokio::test]
+  async fn test_forum_thread_not_found() {
+    let api = create_api();
+    register_alice(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/forum/threads/00000000-0000-0000-0000-000000000000")
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"ThreadNotFound\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/threads/00000000-0000-0000-0000-000000000000/posts")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"body": "Reply"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"ThreadNotFound\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/threads/00000000-0000-0000-0000-000000000000/posts")
+      .json(&serde_json::json!({"body": "Reply"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 403);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"Forbidden\"}");
+  }
+
+  #[tokio::test]
+  async fn test_forum_add_moderator() {
+    let api = create_api();
+    register_alice(&api).await;
+    register_bob(&api).await;
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main/moderators")
+      .header("authorization", basic_auth("bob", "bbbbbbbbbb"))
+      .json(&serde_json::json!({"user": {"type": "User", "username": "bob"}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 403);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"Forbidden\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main/moderators")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"user": {"type": "User", "username": "charlie"}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"GranteeNotFound\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/en_main/moderators")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"user": {"type": "User", "username": "bob"}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 404);
+    let body: &str = std::str::from_utf8(res.body()).unwrap();
+    assert_eq!(body, "{\"error\":\"SectionNotFound\"}");
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main/moderators")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"user": {"type": "User", "username": "bob"}}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["role_grants"][0]["role"], "Moderator");
+    assert_eq!(
+      body["role_grants"][0]["user"]["display_name"]["current"]["value"],
+      "Bob"
+    );
+  }
+
+  /// Check that `value` matches `schema`, resolving references against the components of `doc`.
+  fn check_schema(
+    doc: &serde_json::Value,
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+  ) -> Result<(), String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+      let name = reference.strip_prefix("#/components/schemas/").unwrap();
+      let resolved = &doc["components"]["schemas"][name];
+      assert!(!resolved.is_null(), "unknown schema {}", reference);
+      return check_schema(doc, resolved, value);
+    }
+    if value.is_null() {
+      return if schema["nullable"] == true {
+        Ok(())
+      } else {
+        Err("unexpected null".to_string())
+      };
+    }
+    if let Some(schemas) = schema["anyOf"].as_array() {
+      let errors: Vec<String> = schemas
+        .iter()
+        .filter_map(|s| check_schema(doc, s, value).err())
+        .collect();
+      return if errors.len() < schemas.len() {
+        Ok(())
+      } else {
+        Err(format!("no matching variant: {}", errors.join("; ")))
+      };
+    }
+    if let Some(schemas) = schema["allOf"].as_array() {
+      for s in schemas {
+        check_schema(doc, s, value)?;
+      }
+    }
+    if let Some(values) = schema["enum"].as_array() {
+      if !values.contains(value) {
+        return Err(format!("{} is not one of {:?}", value, values));
+      }
+    }
+    let matches_type = match schema["type"].as_str() {
+      None => true,
+      Some("object") => value.is_object(),
+      Some("array") => value.is_array(),
+      Some("string") => value.is_string(),
+      Some("integer") => value.is_i64() || value.is_u64(),
+      Some("number") => value.is_number(),
+      Some("boolean") => value.is_boolean(),
+      Some(ty) => panic!("unexpected schema type {}", ty),
+    };
+    if !matches_type {
+      return Err(format!("expected {}, got {}", schema["type"], value));
+    }
+    if let Some(required) = schema["required"].as_array() {
+      for key in required {
+        if value.get(key.as_str().unwrap()).is_none() {
+          return Err(format!("missing property {}", key));
+        }
+      }
+    }
+    if let (Some(properties), Some(value)) = (schema["properties"].as_object(), value.as_object()) {
+      for (key, property) in properties {
+        if let Some(v) = value.get(key) {
+          check_schema(doc, property, v).map_err(|e| format!("{}: {}", key, e))?;
+        }
+      }
+    }
+    if let (Some(items), Some(value)) = (schema.get("items"), value.as_array()) {
+      for (i, item) in value.iter().enumerate() {
+        check_schema(doc, items, item).map_err(|e| format!("[{}]: {}", i, e))?;
+      }
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_openapi_document_is_served() {
+    let api = create_api();
+    let router = create_rest_filter(api);
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> =
+      warp::test::request().path("/openapi.json").reply(&router).await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, openapi());
+  }
+
+  /// Sources of the router, without the tests.
+  const ROUTER_SOURCES: &[&str] = &[
+    include_str!("lib.rs"),
+    include_str!("auth.rs"),
+    include_str!("forum.rs"),
+    include_str!("oauth.rs"),
+    include_str!("users.rs"),
+  ];
+
+  /// Routes of `create_rest_filter`, as `(method, path)` pairs with the path parameters replaced by `{}`.
+  ///
+  /// The routes are read from the `warp::path!` filters of the router sources, and the `warp::path` prefixes of the
+  /// `create_*_filter` functions mounting them. Routes without a method filter are reported as `get`.
+  fn router_routes() -> BTreeSet<(String, String)> {
+    let mut mounts: HashMap<String, (String, String)> = HashMap::new();
+    let mut routes: Vec<(String, String, Option<String>)> = Vec::new();
+    for source in ROUTER_SOURCES {
+      let source = source.split("#[cfg(test)]").next().unwrap();
+      let mut current = String::new();
+      for line in source.lines() {
+        if let Some(start) = line.find("fn create_") {
+          let name = &line[start + "fn ".len()..];
+          current = name[..name.find('(').unwrap()].to_string();
+        }
+        if let Some(start) = line.find("warp::path(\"") {
+          let segment = &line[start + "warp::path(\"".len()..];
+          let segment = &segment[..segment.find('"').unwrap()];
+          if let Some(child) = line.find("create_") {
+            let child = &line[child..];
+            let child = &child[..child.find('(').unwrap()];
+            mounts.insert(child.to_string(), (current.clone(), segment.to_string()));
+          }
+        }
+        if let Some(start) = line.find("warp::path!(") {
+          let segments = &line[start + "warp::path!(".len()..];
+          let segments = &segments[..segments.find(')').unwrap()];
+          let path: Vec<&str> = segments
+            .split('/')
+            .map(|segment| segment.trim())
+            .map(|segment| match segment.strip_prefix('"') {
+              Some(segment) => segment.trim_end_matches('"'),
+              None => "{}",
+            })
+            .collect();
+          routes.push((current.clone(), path.join("/"), None));
+        }
+        for method in ["get", "patch", "post", "put", "delete"] {
+          if line.contains(&format!("warp::{}()", method)) {
+            if let Some((_, _, route_method @ None)) = routes.last_mut() {
+              *route_method = Some(method.to_string());
+            }
+          }
+        }
+      }
+    }
+    routes
+      .into_iter()
+      .map(|(mut filter, mut path, method)| {
+        while filter != "create_rest_filter" {
+          let (parent, segment) = mounts
+            .get(&filter)
+            .unwrap_or_else(|| panic!("filter {} is not mounted", filter));
+          path = format!("{}/{}", segment, path);
+          filter = parent.clone();
+        }
+        (method.unwrap_or_else(|| "get".to_string()), format!("/{}", path))
+      })
+      .collect()
+  }
+
+  /// Replace the path parameters of a documented path by `{}`.
+  fn erase_path_parameters(path: &str) -> String {
+    let mut erased = String::new();
+    let mut in_parameter = false;
+    for c in path.chars() {
+      match c {
+        '{' => in_parameter = true,
+        '}' => {
+          in_parameter = false;
+          erased.push_str("{}");
+        }
+        c if !in_parameter => erased.push(c),
+        _ => {}
+      }
+    }
+    erased
+  }
+
+  #[test]
+  fn test_openapi_documents_every_route() {
+    let doc = openapi();
+    let documented: BTreeSet<(String, String)> = doc["paths"]
+      .as_object()
+      .unwrap()
+      .iter()
+      .flat_map(|(path, item)| {
+        item
+          .as_object()
+          .unwrap()
+          .keys()
+          .map(move |method| (method.clone(), erase_path_parameters(path)))
+      })
+      .collect();
+    let routes = router_routes();
+    assert!(!routes.is_empty());
+    let undocumented: Vec<_> = routes.difference(&documented).collect();
+    assert!(undocumented.is_empty(), "undocumented routes: {:?}", undocumented);
+    let unknown: Vec<_> = documented.difference(&routes).collect();
+    assert!(
+      unknown.is_empty(),
+      "documented operations without a route: {:?}",
+      unknown
+    );
+  }
+
+  /// Send a request to every documented operation, and check the reply against the documented responses.
+  #[tokio::test]
+  async fn test_openapi_matches_routes() {
+    let (api, _, clock, _, _) = create_api_and_hammerfest_client();
+    register_alice(&api).await;
+    create_main_section(&api).await;
+    let router = create_rest_filter(api);
+    let doc = openapi();
+
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .method("POST")
+      .path("/forum/sections/fr_main")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .json(&serde_json::json!({"title": "Hello", "body": "Discussion thread"}))
+      .reply(&router)
+      .await;
+    assert_eq!(res.status(), 200);
+    let thread: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let thread_id = thread["id"].as_str().unwrap().to_string();
+    let res: warp::http::Response<warp::hyper::body::Bytes> = warp::test::request()
+      .path("/auth/self")
+      .header("authorization", basic_auth("alice", "aaaaaaaaaa"))
+      .reply(&router)
+      .await;
+    let acx: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let alice_id = acx["user"]["id"].as_str().unwrap().to_string();
+
+    for (path, item) in doc["paths"].as_object().unwrap() {
+      for (method, op) in item.as_object().unwrap() {
+        let mut uri = path.clone();
+        let mut query: Vec<String> = Vec::new();
+        for param in op["parameters"].as_array().into_iter().flatten() {
+          let name = param["name"].as_str().unwrap();
+          let example = match &param["example"] {
+            serde_json::Value::String(example) => example.clone(),
+            example => example.to_string(),
+          };
+          let value = match (path.as_str(), name) {
+            ("/users/{user_id}", "user_id") => alice_id.clone(),
+            (_, "thread") => thread_id.clone(),
+            _ => example,
+          };
+          match param["in"].as_str().unwrap() {
+            "path" => uri = uri.replace(&format!("{{{}}}", name), &value),
+            _ => query.push(format!("{}={}", name, value)),
+          }
+        }
+        if !query.is_empty() {
+          uri = format!("{}?{}", uri, query.join("&"));
+        }
+
+        for authorization in [Some(basic_auth("alice", "aaaaaaaaaa")), None] {
+          clock.advance_by(Duration::seconds(1));
+          let mut req = warp::test::request().method(&method.to_uppercase()).path(&uri);
+          if let Some(body) = op["requestBody"]["content"]["application/json"].get("example") {
+            req = req.json(body);
+          }
+          if let Some(authorization) = authorization.as_deref() {
+            req = req.header("authorization", authorization);
+          }
+          let res: warp::http::Response<warp::hyper::body::Bytes> = req.reply(&router).await;
+          let status = res.status().as_u16().to_string();
+          let response = &op["responses"][&status];
+          assert!(
+            !response.is_null(),
+            "{} {}: undocumented status {}: {}",
+            method,
+            uri,
+            status,
+            std::str::from_utf8(res.body()).unwrap()
+          );
+          if let Some(schema) = response["content"]["application/json"].get("schema") {
+            let body: serde_json::Value = serde_json::from_slice(res.body())
+              .unwrap_or_else(|_| panic!("{} {}: expected a JSON body for status {}", method, uri, status));
+            if let Err(e) = check_schema(&doc, schema, &body) {
+              panic!(
+                "{} {}: body does not match the schema for status {}: {}",
+                method, uri, status, e
+              );
+            }
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_credentials_error_status_code() {
+    let throttled: AnyError = Box::new(LoginThrottledError {
+      retry_after: Instant::ymd_hms(2021, 1, 1, 0, 15, 0),
+    });
+    assert_eq!(
+      AuthenticationError::from_credentials_error(&throttled).get_status_code(),
+      429
+    );
+    let wrong_password: AnyError = Box::new(InvalidCredentialsError {
+      reason: "WrongPassword",
+    });
+    assert_eq!(
+      AuthenticationError::from_credentials_error(&wrong_password).get_status_code(),
+      401
+    );
+    let internal: AnyError = "database unavailable".into();
+    assert_eq!(
+      AuthenticationError::from_credentials_error(&internal).get_status_code(),
+      500
+    );
+  }
 }
@@ -0,0 +1,175 @@
+use crate::auth::authenticate;
+use crate::{RestFilter, RouterApi};
+use etwin_core::auth::{AuthContext, CreateAccessTokenOptions, GrantOauthAuthorizationOptions};
+use etwin_core::oauth::OauthAccessToken;
+use etwin_services::auth::{CreateAccessTokenError, DynAuthService, GrantOauthAuthorizationError, OauthCodeGrant};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Rejection, Reply};
+
+/// Maximum size of a token request body.
+const MAX_TOKEN_BODY_SIZE: u64 = 16 * 1024;
+
+pub fn create_oauth_filter(api: RouterApi) -> RestFilter {
+  let authorize = {
+    #[derive(Debug, Deserialize)]
+    struct AuthorizeQuery {
+      client_id: Option<String>,
+      redirect_uri: Option<String>,
+      response_type: Option<String>,
+      scope: Option<String>,
+      state: Option<String>,
+    }
+
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum AuthorizeError {
+      MissingClientId,
+      InvalidClientId,
+      RedirectUriMismatch,
+      ClientNotFound,
+      Unauthenticated,
+      MissingResponseType,
+      InvalidResponseType,
+      UnsupportedResponseType,
+      InvalidScope,
+      InternalServerError,
+    }
+
+    impl AuthorizeError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::ClientNotFound => StatusCode::NOT_FOUND,
+          Self::Unauthenticated => StatusCode::UNAUTHORIZED,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+          _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+      }
+    }
+
+    async fn handle_authorize(
+      auth: &DynAuthService,
+      acx: &AuthContext,
+      query: AuthorizeQuery,
+    ) -> Result<OauthCodeGrant, AuthorizeError> {
+      let options = GrantOauthAuthorizationOptions {
+        client_ref: query.client_id,
+        redirect_uri: query.redirect_uri,
+        response_type: query.response_type,
+        scope: query.scope,
+        state: query.state,
+      };
+      auth
+        .grant_oauth_authorization(acx, &options)
+        .await
+        .map_err(|e| match e {
+          GrantOauthAuthorizationError::MissingClientId => AuthorizeError::MissingClientId,
+          GrantOauthAuthorizationError::InvalidClientId => AuthorizeError::InvalidClientId,
+          GrantOauthAuthorizationError::RedirectUriMismatch => AuthorizeError::RedirectUriMismatch,
+          GrantOauthAuthorizationError::ClientNotFound(_) => AuthorizeError::ClientNotFound,
+          GrantOauthAuthorizationError::Unauthenticated => AuthorizeError::Unauthenticated,
+          GrantOauthAuthorizationError::MissingResponseType => AuthorizeError::MissingResponseType,
+          GrantOauthAuthorizationError::InvalidResponseType => AuthorizeError::InvalidResponseType,
+          GrantOauthAuthorizationError::UnsupportedResponseType => AuthorizeError::UnsupportedResponseType,
+          GrantOauthAuthorizationError::InvalidScope => AuthorizeError::InvalidScope,
+          GrantOauthAuthorizationError::Other(_) => AuthorizeError::InternalServerError,
+        })
+    }
+
+    let api = api.clone();
+    warp::path!("authorize")
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::query::<AuthorizeQuery>())
+      .and_then(move |acx: AuthContext, query: AuthorizeQuery| {
+        let auth = Arc::clone(&api.auth);
+        async move {
+          let res = handle_authorize(&auth, &acx, query).await;
+          let reply = match res {
+            Ok(grant) => warp::reply::with_header(
+              StatusCode::FOUND,
+              warp::http::header::LOCATION,
+              grant.redirect_uri().as_str(),
+            )
+            .into_response(),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()).into_response(),
+          };
+          Ok::<_, Rejection>(reply)
+        }
+      })
+      .boxed()
+  };
+
+  let create_token = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum CreateTokenError {
+      InvalidRequestBody,
+      Unauthenticated,
+      MissingCode,
+      WrongClient,
+      /// RFC 6749 error code for invalid or expired authorization codes
+      #[serde(rename = "invalid_grant")]
+      InvalidGrant,
+      InternalServerError,
+    }
+
+    impl CreateTokenError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::InvalidRequestBody => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::Unauthenticated => StatusCode::UNAUTHORIZED,
+          Self::MissingCode => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::WrongClient => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::InvalidGrant => StatusCode::BAD_REQUEST,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_create_token(
+      auth: &DynAuthService,
+      acx: &AuthContext,
+      content_type: Option<&str>,
+      body: &[u8],
+    ) -> Result<OauthAccessToken, CreateTokenError> {
+      // OAuth clients usually send the token request as a form, but JSON is accepted too
+      let is_form = matches!(content_type, Some(ct) if ct.starts_with("application/x-www-form-urlencoded"));
+      let options: CreateAccessTokenOptions = if is_form {
+        serde_urlencoded::from_bytes(body).map_err(|_| CreateTokenError::InvalidRequestBody)?
+      } else {
+        serde_json::from_slice(body).map_err(|_| CreateTokenError::InvalidRequestBody)?
+      };
+      auth.create_access_token(acx, &options).await.map_err(|e| match e {
+        CreateAccessTokenError::MissingCode => CreateTokenError::MissingCode,
+        CreateAccessTokenError::Unauthenticated => CreateTokenError::Unauthenticated,
+        CreateAccessTokenError::WrongClient => CreateTokenError::WrongClient,
+        CreateAccessTokenError::InvalidGrant => CreateTokenError::InvalidGrant,
+        CreateAccessTokenError::Other(_) => CreateTokenError::InternalServerError,
+      })
+    }
+
+    warp::path!("token")
+      .and(warp::post())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::header::optional::<String>("content-type"))
+      .and(warp::body::content_length_limit(MAX_TOKEN_BODY_SIZE))
+      .and(warp::body::bytes())
+      .and_then(move |acx: AuthContext, content_type: Option<String>, body: Bytes| {
+        let auth = Arc::clone(&api.auth);
+        async move {
+          let res = handle_create_token(&auth, &acx, content_type.as_deref(), &body).await;
+          let reply = match res {
+            Ok(token) => warp::reply::with_status(warp::reply::json(&token), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  authorize.or(create_token).unify().boxed()
+}
@@ -0,0 +1,1033 @@
+//! OpenAPI 3 description of the REST API.
+//!
+//! Operations are declared in `OPERATIONS`, next to the component schemas describing the JSON representation of the
+//! `etwin_core` types. New routes must be added to the table: the tests of this crate check that every route of the
+//! router is documented, send a request for each documented operation and check the reply against the documented
+//! responses.
+
+use crate::auth::SESSION_COOKIE;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use warp::http::StatusCode;
+
+/// Version of the OpenAPI specification used by the generated document.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Method {
+  Get,
+  Patch,
+  Post,
+  Put,
+}
+
+impl Method {
+  const fn as_str(self) -> &'static str {
+    match self {
+      Self::Get => "get",
+      Self::Patch => "patch",
+      Self::Post => "post",
+      Self::Put => "put",
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ParameterLocation {
+  Path,
+  Query,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ParameterType {
+  String,
+  Integer,
+  Uuid,
+  Enum(&'static [&'static str]),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Parameter {
+  name: &'static str,
+  location: ParameterLocation,
+  ty: ParameterType,
+  description: &'static str,
+  example: &'static str,
+}
+
+const fn path(name: &'static str, ty: ParameterType, description: &'static str, example: &'static str) -> Parameter {
+  Parameter {
+    name,
+    location: ParameterLocation::Path,
+    ty,
+    description,
+    example,
+  }
+}
+
+const fn query(name: &'static str, ty: ParameterType, description: &'static str, example: &'static str) -> Parameter {
+  Parameter {
+    name,
+    location: ParameterLocation::Query,
+    ty,
+    description,
+    example,
+  }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct RequestBody {
+  schema: &'static str,
+  /// Also accept `application/x-www-form-urlencoded` bodies
+  form: bool,
+  /// Example body, as JSON
+  example: &'static str,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Response {
+  /// JSON body described by a component schema
+  Json(u16, &'static str),
+  /// Redirection, without body
+  Redirect(u16),
+  /// JSON error, with the `error` field set to one of the provided names
+  Errors(u16, &'static [&'static str]),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Operation {
+  method: Method,
+  path: &'static str,
+  operation_id: &'static str,
+  tag: &'static str,
+  summary: &'static str,
+  /// The operation resolves the auth context of the caller, and may fail with an `AuthenticationError`
+  authenticated: bool,
+  parameters: &'static [Parameter],
+  request_body: Option<RequestBody>,
+  responses: &'static [Response],
+}
+
+const HAMMERFEST_SERVERS: &[&str] = &["hammerfest.fr", "hfest.net", "hammerfest.es"];
+const DINOPARC_SERVERS: &[&str] = &["dinoparc.com", "en.dinoparc.com", "sp.dinoparc.com"];
+const ZERO_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+const OFFSET: Parameter = query("offset", ParameterType::Integer, "Index of the first item", "0");
+
+const OPERATIONS: &[Operation] = &[
+  Operation {
+    method: Method::Get,
+    path: "/archive/dinoparc/{server}/users/{user_id}",
+    operation_id: "getArchivedDinoparcUser",
+    tag: "archive",
+    summary: "Get an archived Dinoparc user",
+    authenticated: true,
+    parameters: &[
+      path(
+        "server",
+        ParameterType::Enum(DINOPARC_SERVERS),
+        "Dinoparc server",
+        "dinoparc.com",
+      ),
+      path("user_id", ParameterType::String, "Dinoparc user id", "123"),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "DinoparcUser"),
+      Response::Errors(404, &["DinoparcUserNotFound"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/archive/dinoparc/{server}/dinoz/{dinoz_id}",
+    operation_id: "getArchivedDinoparcDinoz",
+    tag: "archive",
+    summary: "Get an archived Dinoparc dinoz",
+    authenticated: true,
+    parameters: &[
+      path(
+        "server",
+        ParameterType::Enum(DINOPARC_SERVERS),
+        "Dinoparc server",
+        "dinoparc.com",
+      ),
+      path("dinoz_id", ParameterType::String, "Dinoparc dinoz id", "123"),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "DinoparcDinoz"),
+      Response::Errors(404, &["DinoparcDinozNotFound"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/archive/hammerfest/{server}/users/{user_id}",
+    operation_id: "getArchivedHammerfestUser",
+    tag: "archive",
+    summary: "Get an archived Hammerfest user",
+    authenticated: true,
+    parameters: &[
+      path(
+        "server",
+        ParameterType::Enum(HAMMERFEST_SERVERS),
+        "Hammerfest server",
+        "hammerfest.fr",
+      ),
+      path("user_id", ParameterType::String, "Hammerfest user id", "123"),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "HammerfestUser"),
+      Response::Errors(404, &["HammerfestUserNotFound"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/auth/self",
+    operation_id: "getSelf",
+    tag: "auth",
+    summary: "Get the auth context of the caller",
+    authenticated: true,
+    parameters: &[],
+    request_body: None,
+    responses: &[Response::Json(200, "AuthContext")],
+  },
+  Operation {
+    method: Method::Put,
+    path: "/auth/self",
+    operation_id: "login",
+    tag: "auth",
+    summary: "Log in and start a session",
+    authenticated: false,
+    parameters: &[query(
+      "method",
+      ParameterType::Enum(&["Etwin", "Dinoparc", "Hammerfest"]),
+      "Kind of credentials in the request body",
+      "Etwin",
+    )],
+    request_body: Some(RequestBody {
+      schema: "LoginCredentials",
+      form: false,
+      example: r#"{"login": "alice", "password": "61616161616161616161"}"#,
+    }),
+    responses: &[
+      Response::Json(200, "LoginResponse"),
+      Response::Errors(401, &["InvalidCredentials"]),
+      Response::Errors(422, &["InvalidMethod", "InvalidRequestBody"]),
+      Response::Errors(429, &["LoginThrottled"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Put,
+    path: "/auth/self/mfa",
+    operation_id: "completeMfaLogin",
+    tag: "auth",
+    summary: "Complete a login with the second factor and start a session",
+    authenticated: false,
+    parameters: &[],
+    request_body: Some(RequestBody {
+      schema: "CompleteMfaLogin",
+      form: false,
+      example: r#"{"token": "eyJhbGciOiJIUzI1NiJ9.e30.ZRrHA1JJJW8opsbCGfG_HACGpVUMN_a9IV7pAx_Zmeo", "proof": {"type": "Totp", "code": "123456"}}"#,
+    }),
+    responses: &[
+      Response::Json(200, "CompleteUser"),
+      Response::Errors(401, &["InvalidCredentials"]),
+      Response::Errors(422, &["InvalidRequestBody"]),
+      Response::Errors(429, &["LoginThrottled"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/forum/sections",
+    operation_id: "getForumSections",
+    tag: "forum",
+    summary: "List the forum sections",
+    authenticated: true,
+    parameters: &[],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "ForumSectionListing"),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/forum/sections/{section}",
+    operation_id: "getForumSection",
+    tag: "forum",
+    summary: "Get a forum section and a page of its threads",
+    authenticated: true,
+    parameters: &[
+      path("section", ParameterType::String, "Section id or key", "fr_main"),
+      OFFSET,
+      query(
+        "limit",
+        ParameterType::Integer,
+        "Maximum number of threads (default 20, max 100)",
+        "20",
+      ),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "ForumSection"),
+      Response::Errors(404, &["SectionNotFound"]),
+      Response::Errors(422, &["InvalidSectionIdOrKey", "InvalidQueryParameters"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Post,
+    path: "/forum/sections/{section}",
+    operation_id: "createForumThread",
+    tag: "forum",
+    summary: "Create a thread in a forum section",
+    authenticated: true,
+    parameters: &[path("section", ParameterType::String, "Section id or key", "fr_main")],
+    request_body: Some(RequestBody {
+      schema: "CreateForumThread",
+      form: false,
+      example: r#"{"title": "Hello", "body": "**First** discussion thread"}"#,
+    }),
+    responses: &[
+      Response::Json(200, "ForumThread"),
+      Response::Errors(403, &["Forbidden"]),
+      Response::Errors(404, &["SectionNotFound"]),
+      Response::Errors(
+        422,
+        &["InvalidSectionIdOrKey", "FailedToParseBody", "FailedToRenderBody"],
+      ),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Post,
+    path: "/forum/sections/{section}/moderators",
+    operation_id: "addForumModerator",
+    tag: "forum",
+    summary: "Grant the moderator role of a forum section",
+    authenticated: true,
+    parameters: &[path("section", ParameterType::String, "Section id or key", "fr_main")],
+    request_body: Some(RequestBody {
+      schema: "AddForumModerator",
+      form: false,
+      example: r#"{"user": {"type": "User", "username": "alice"}}"#,
+    }),
+    responses: &[
+      Response::Json(200, "ForumSection"),
+      Response::Errors(403, &["Forbidden"]),
+      Response::Errors(404, &["SectionNotFound", "GranteeNotFound"]),
+      Response::Errors(422, &["InvalidSectionIdOrKey"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/forum/threads/{thread}",
+    operation_id: "getForumThread",
+    tag: "forum",
+    summary: "Get a forum thread and a page of its posts",
+    authenticated: true,
+    parameters: &[
+      path("thread", ParameterType::String, "Thread id or key", ZERO_UUID),
+      OFFSET,
+      query(
+        "limit",
+        ParameterType::Integer,
+        "Maximum number of posts (default 10, max 100)",
+        "10",
+      ),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "ForumThread"),
+      Response::Errors(404, &["ThreadNotFound"]),
+      Response::Errors(422, &["InvalidThreadIdOrKey", "InvalidQueryParameters"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Post,
+    path: "/forum/threads/{thread}/posts",
+    operation_id: "createForumPost",
+    tag: "forum",
+    summary: "Reply to a forum thread",
+    authenticated: true,
+    parameters: &[path("thread", ParameterType::String, "Thread id or key", ZERO_UUID)],
+    request_body: Some(RequestBody {
+      schema: "CreateForumPost",
+      form: false,
+      example: r#"{"body": "Reply"}"#,
+    }),
+    responses: &[
+      Response::Json(200, "ForumPost"),
+      Response::Errors(403, &["Forbidden"]),
+      Response::Errors(404, &["ThreadNotFound"]),
+      Response::Errors(
+        422,
+        &["InvalidThreadIdOrKey", "FailedToParseBody", "FailedToRenderBody"],
+      ),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/oauth/authorize",
+    operation_id: "authorizeOauthClient",
+    tag: "oauth",
+    summary: "Grant an authorization code to an OAuth client and redirect to it",
+    authenticated: true,
+    parameters: &[
+      query(
+        "client_id",
+        ParameterType::String,
+        "Id or key of the OAuth client",
+        "eternalfest@clients",
+      ),
+      query(
+        "redirect_uri",
+        ParameterType::String,
+        "Callback URI, must match the URI registered for the client",
+        "http://localhost:50313/oauth/callback",
+      ),
+      query("response_type", ParameterType::String, "Must be `code`", "code"),
+      query("scope", ParameterType::String, "Space-separated list of scopes", "base"),
+      query(
+        "state",
+        ParameterType::String,
+        "Opaque value passed back to the client",
+        "state",
+      ),
+    ],
+    request_body: None,
+    responses: &[
+      Response::Redirect(302),
+      Response::Errors(401, &["Unauthenticated"]),
+      Response::Errors(404, &["ClientNotFound"]),
+      Response::Errors(
+        422,
+        &[
+          "MissingClientId",
+          "InvalidClientId",
+          "RedirectUriMismatch",
+          "MissingResponseType",
+          "InvalidResponseType",
+          "UnsupportedResponseType",
+          "InvalidScope",
+        ],
+      ),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Post,
+    path: "/oauth/token",
+    operation_id: "createOauthAccessToken",
+    tag: "oauth",
+    summary: "Exchange an authorization code for an access token",
+    authenticated: true,
+    parameters: &[],
+    request_body: Some(RequestBody {
+      schema: "CreateAccessToken",
+      form: true,
+      example: r#"{"code": "eyJhbGciOiJIUzI1NiJ9.e30.ZRrHA1JJJW8opsbCGfG_HACGpVUMN_a9IV7pAx_Zmeo"}"#,
+    }),
+    responses: &[
+      Response::Json(200, "OauthAccessToken"),
+      Response::Errors(400, &["invalid_grant"]),
+      Response::Errors(401, &["Unauthenticated"]),
+      Response::Errors(422, &["InvalidRequestBody", "MissingCode", "WrongClient"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/openapi.json",
+    operation_id: "getOpenapi",
+    tag: "meta",
+    summary: "Get this OpenAPI document",
+    authenticated: false,
+    parameters: &[],
+    request_body: None,
+    responses: &[Response::Json(200, "OpenapiDocument")],
+  },
+  Operation {
+    method: Method::Get,
+    path: "/users/{user_id}",
+    operation_id: "getUser",
+    tag: "users",
+    summary: "Get a user; private fields are only provided to the user and administrators",
+    authenticated: true,
+    parameters: &[path("user_id", ParameterType::Uuid, "User id", ZERO_UUID)],
+    request_body: None,
+    responses: &[
+      Response::Json(200, "User"),
+      Response::Errors(404, &["UserNotFound"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+  Operation {
+    method: Method::Patch,
+    path: "/users/{user_id}",
+    operation_id: "updateUser",
+    tag: "users",
+    summary: "Update a user",
+    authenticated: true,
+    parameters: &[path("user_id", ParameterType::Uuid, "User id", ZERO_UUID)],
+    request_body: Some(RequestBody {
+      schema: "UpdateUserPatch",
+      form: false,
+      example: r#"{"display_name": "Alicia"}"#,
+    }),
+    responses: &[
+      Response::Json(200, "CompleteUser"),
+      Response::Errors(401, &["Unauthenticated"]),
+      Response::Errors(403, &["Forbidden", "WrongCurrentPassword"]),
+      Response::Errors(404, &["UserNotFound"]),
+      Response::Errors(409, &["LockedDisplayName", "LockedUsername", "LockedPassword"]),
+      Response::Errors(422, &["CurrentPasswordRequired"]),
+      Response::Errors(429, &["LoginThrottled"]),
+      Response::Errors(500, &["InternalServerError"]),
+    ],
+  },
+];
+
+/// Errors of `AuthenticationError`, for operations resolving the auth context of the caller.
+const AUTHENTICATION_ERRORS: &[Response] = &[
+  Response::Errors(401, &["InvalidCredentials", "InvalidAccessToken"]),
+  Response::Errors(429, &["LoginThrottled"]),
+  Response::Errors(500, &["InternalServerError"]),
+];
+
+/// Build the OpenAPI document describing the REST API.
+pub fn openapi() -> Value {
+  let mut paths: BTreeMap<&'static str, Map<String, Value>> = BTreeMap::new();
+  for op in OPERATIONS {
+    paths
+      .entry(op.path)
+      .or_default()
+      .insert(op.method.as_str().to_string(), operation(op));
+  }
+
+  json!({
+    "openapi": OPENAPI_VERSION,
+    "info": {
+      "title": "Eternaltwin",
+      "description": "REST API of Eternaltwin",
+      "version": env!("CARGO_PKG_VERSION"),
+      "license": {
+        "name": "AGPL-3.0-or-later",
+        "url": "https://www.gnu.org/licenses/agpl-3.0.html",
+      },
+    },
+    "paths": paths,
+    "components": {
+      "schemas": schemas(),
+      "securitySchemes": {
+        "basicAuth": {"type": "http", "scheme": "basic"},
+        "bearerAuth": {"type": "http", "scheme": "bearer"},
+        "sessionCookie": {"type": "apiKey", "in": "cookie", "name": SESSION_COOKIE},
+      },
+    },
+  })
+}
+
+fn operation(op: &Operation) -> Value {
+  let mut result = Map::new();
+  result.insert("operationId".to_string(), json!(op.operation_id));
+  result.insert("tags".to_string(), json!([op.tag]));
+  result.insert("summary".to_string(), json!(op.summary));
+  if !op.parameters.is_empty() {
+    result.insert(
+      "parameters".to_string(),
+      Value::Array(op.parameters.iter().map(parameter).collect()),
+    );
+  }
+  if let Some(body) = op.request_body {
+    let schema = schema_ref(body.schema);
+    let example: Value = serde_json::from_str(body.example).expect("invalid request body example");
+    let mut content = Map::new();
+    content.insert(
+      "application/json".to_string(),
+      json!({"schema": schema, "example": example}),
+    );
+    if body.form {
+      content.insert(
+        "application/x-www-form-urlencoded".to_string(),
+        json!({ "schema": schema }),
+      );
+    }
+    result.insert("requestBody".to_string(), json!({"required": true, "content": content}));
+  }
+  if op.authenticated {
+    // Credentials are optional: requests without them are handled as guests
+    result.insert(
+      "security".to_string(),
+      json!([{}, {"basicAuth": []}, {"bearerAuth": []}, {"sessionCookie": []}]),
+    );
+  }
+  result.insert("responses".to_string(), responses(op));
+  Value::Object(result)
+}
+
+fn parameter(param: &Parameter) -> Value {
+  let (location, required) = match param.location {
+    ParameterLocation::Path => ("path", true),
+    ParameterLocation::Query => ("query", false),
+  };
+  let (schema, example) = match param.ty {
+    ParameterType::String => (json!({"type": "string"}), json!(param.example)),
+    ParameterType::Integer => (
+      json!({"type": "integer", "minimum": 0}),
+      json!(param.example.parse::<u32>().expect("invalid integer parameter example")),
+    ),
+    ParameterType::Uuid => (json!({"type": "string", "format": "uuid"}), json!(param.example)),
+    ParameterType::Enum(values) => (json!({"type": "string", "enum": values}), json!(param.example)),
+  };
+  json!({
+    "name": param.name,
+    "in": location,
+    "required": required,
+    "description": param.description,
+    "schema": schema,
+    "example": example,
+  })
+}
+
+fn responses(op: &Operation) -> Value {
+  enum Body {
+    Json(&'static str),
+    Empty,
+    Errors(Vec<&'static str>),
+  }
+
+  let auth_errors = if op.authenticated { AUTHENTICATION_ERRORS } else { &[] };
+  let mut bodies: BTreeMap<u16, Body> = BTreeMap::new();
+  for response in op.responses.iter().chain(auth_errors.iter()) {
+    match *response {
+      Response::Json(status, schema) => {
+        bodies.insert(status, Body::Json(schema));
+      }
+      Response::Redirect(status) => {
+        bodies.insert(status, Body::Empty);
+      }
+      Response::Errors(status, errors) => {
+        let body = bodies.entry(status).or_insert_with(|| Body::Errors(Vec::new()));
+        match body {
+          Body::Errors(ref mut names) => {
+            for error in errors {
+              if !names.contains(error) {
+                names.push(error);
+              }
+            }
+          }
+          _ => panic!(
+            "status {} of {} is used for both errors and success",
+            status, op.operation_id
+          ),
+        }
+      }
+    }
+  }
+
+  let mut result = Map::new();
+  for (status, body) in bodies {
+    let description = StatusCode::from_u16(status)
+      .ok()
+      .and_then(|s| s.canonical_reason())
+      .expect("invalid status code");
+    let response = match body {
+      Body::Json(schema) => json!({
+        "description": description,
+        "content": {"application/json": {"schema": schema_ref(schema)}},
+      }),
+      Body::Empty => json!({
+        "description": description,
+        "headers": {"Location": {"schema": {"type": "string"}}},
+      }),
+      Body::Errors(names) => json!({
+        "description": description,
+        "content": {"application/json": {"schema": {
+          "type": "object",
+          "required": ["error"],
+          "properties": {"error": {"type": "string", "enum": names}},
+        }}},
+      }),
+    };
+    result.insert(status.to_string(), response);
+  }
+  Value::Object(result)
+}
+
+fn schema_ref(name: &str) -> Value {
+  json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Object schema where all the listed properties are required.
+fn object(properties: Value) -> Value {
+  let required: Vec<&String> = properties
+    .as_object()
+    .expect("properties must be an object")
+    .keys()
+    .collect();
+  json!({"type": "object", "required": required, "properties": properties})
+}
+
+fn nullable(schema: Value) -> Value {
+  match schema {
+    Value::Object(mut schema) if !schema.contains_key("$ref") => {
+      schema.insert("nullable".to_string(), Value::Bool(true));
+      Value::Object(schema)
+    }
+    schema => json!({"allOf": [schema], "nullable": true}),
+  }
+}
+
+fn tag(name: &str) -> Value {
+  json!({"type": "string", "enum": [name]})
+}
+
+fn listing(item: &str) -> Value {
+  object(json!({
+    "offset": {"type": "integer", "minimum": 0},
+    "limit": {"type": "integer", "minimum": 0},
+    "count": {"type": "integer", "minimum": 0},
+    "items": {"type": "array", "items": schema_ref(item)},
+  }))
+}
+
+/// Archived value, with the period when it was observed.
+fn latest_temporal() -> Value {
+  nullable(object(json!({
+    "latest": object(json!({
+      "period": {"type": "object"},
+      "retrieved": {"type": "object"},
+      "value": {},
+    })),
+  })))
+}
+
+fn schemas() -> Value {
+  let uuid = json!({"type": "string", "format": "uuid"});
+  let instant = json!({"type": "string", "format": "date-time"});
+  let string = json!({"type": "string"});
+  let boolean = json!({"type": "boolean"});
+  let count = object(json!({"count": {"type": "integer", "minimum": 0}}));
+  let display_name = object(json!({"current": object(json!({ "value": string }))}));
+  let role_list = json!({"type": "array", "items": schema_ref("ForumRole")});
+  let etwin_link = schema_ref("VersionedEtwinLink");
+
+  let short_user = object(json!({
+    "type": tag("User"),
+    "id": uuid,
+    "display_name": display_name,
+  }));
+  let mut simple_user = short_user.clone();
+  extend(
+    &mut simple_user,
+    json!({
+      "created_at": instant,
+      "is_administrator": boolean,
+    }),
+  );
+  let mut complete_user = simple_user.clone();
+  extend(
+    &mut complete_user,
+    json!({
+      "username": nullable(string.clone()),
+      "email_address": nullable(string.clone()),
+    }),
+  );
+
+  let section_meta = object(json!({
+    "type": tag("ForumSection"),
+    "id": uuid,
+    "key": nullable(string.clone()),
+    "display_name": string,
+    "ctime": instant,
+    "locale": nullable(string.clone()),
+    "threads": count,
+    "self": object(json!({ "roles": role_list })),
+  }));
+  let mut section = section_meta.clone();
+  extend(
+    &mut section,
+    json!({
+      "threads": listing("ForumThreadMeta"),
+      "role_grants": {"type": "array", "items": schema_ref("ForumRoleGrant")},
+    }),
+  );
+  let thread_meta = object(json!({
+    "type": tag("ForumThread"),
+    "id": uuid,
+    "key": nullable(string.clone()),
+    "title": string,
+    "ctime": instant,
+    "is_pinned": boolean,
+    "is_locked": boolean,
+    "posts": count,
+  }));
+  let mut thread_meta_with_section = thread_meta.clone();
+  extend(
+    &mut thread_meta_with_section,
+    json!({ "section": schema_ref("ForumSectionMeta") }),
+  );
+  let mut thread = thread_meta_with_section.clone();
+  extend(&mut thread, json!({ "posts": listing("ShortForumPost") }));
+  let short_post = object(json!({
+    "type": tag("ForumPost"),
+    "id": uuid,
+    "ctime": instant,
+    "author": schema_ref("ForumActor"),
+    "revisions": object(json!({
+      "count": {"type": "integer", "minimum": 0},
+      "last": schema_ref("ForumPostRevision"),
+    })),
+  }));
+  let mut post = short_post.clone();
+  extend(&mut post, json!({ "thread": thread_meta_with_section }));
+  let revision_content = nullable(object(json!({"marktwin": string, "html": string})));
+
+  let user_dot = object(json!({"time": instant, "user": schema_ref("ShortUser")}));
+  let mut hammerfest_user = object(json!({
+    "type": tag("HammerfestUser"),
+    "server": {"type": "string", "enum": HAMMERFEST_SERVERS},
+    "id": string,
+    "username": string,
+    "archived_at": instant,
+    "profile": nullable(json!({"type": "object"})),
+    "items": nullable(json!({"type": "object"})),
+    "etwin": etwin_link,
+  }));
+  // Only provided to the Eternaltwin user linked to the account
+  hammerfest_user["properties"]["private"] = json!({
+    "type": "object",
+    "properties": {
+      "email": nullable(string.clone()),
+      "tokens": nullable(json!({"type": "integer", "minimum": 0})),
+    },
+  });
+
+  let mut dinoz_fields = json!({
+    "type": tag("DinoparcDinoz"),
+    "server": {"type": "string", "enum": DINOPARC_SERVERS},
+    "id": string,
+    "archived_at": instant,
+  });
+  for field in [
+    "name",
+    "owner",
+    "location",
+    "race",
+    "skin",
+    "life",
+    "level",
+    "experience",
+    "danger",
+    "in_tournament",
+    "elements",
+    "skills",
+  ] {
+    dinoz_fields[field] = latest_temporal();
+  }
+
+  json!({
+    "AddForumModerator": object(json!({ "user": schema_ref("UserRef") })),
+    "AuthContext": {
+      "anyOf": [
+        object(json!({"type": tag("Guest"), "scope": schema_ref("AuthScope")})),
+        object(json!({
+          "type": tag("User"),
+          "scope": schema_ref("AuthScope"),
+          "user": schema_ref("ShortUser"),
+          "is_administrator": boolean,
+        })),
+        object(json!({
+          "type": tag("OauthClient"),
+          "scope": schema_ref("AuthScope"),
+          "client": schema_ref("ShortOauthClient"),
+        })),
+        object(json!({
+          "type": tag("AccessToken"),
+          "scope": schema_ref("AuthScope"),
+          "client": schema_ref("ShortOauthClient"),
+          "user": schema_ref("ShortUser"),
+        })),
+      ],
+    },
+    "AuthScope": {"type": "string", "enum": ["Default"]},
+    "CompleteMfaLogin": object(json!({
+      "token": {"type": "string", "description": "Token of the pending login, returned by the first step"},
+      "proof": {
+        "anyOf": [
+          object(json!({"type": tag("Totp"), "code": string})),
+          object(json!({"type": tag("RecoveryCode"), "code": string})),
+        ],
+      },
+    })),
+    "CompleteUser": complete_user,
+    "CreateAccessToken": {
+      "type": "object",
+      "properties": {
+        "code": string,
+        "grant_type": string,
+        "client_id": string,
+        "client_secret": string,
+        "redirect_uri": string,
+      },
+    },
+    "CreateForumPost": object(json!({ "body": string })),
+    "CreateForumThread": object(json!({"title": string, "body": string})),
+    "DinoparcDinoz": object(dinoz_fields),
+    "DinoparcUser": object(json!({
+      "type": tag("DinoparcUser"),
+      "server": {"type": "string", "enum": DINOPARC_SERVERS},
+      "id": string,
+      "archived_at": instant,
+      "username": string,
+      "coins": latest_temporal(),
+      "dinoz": latest_temporal(),
+      "inventory": latest_temporal(),
+      "collection": latest_temporal(),
+      "etwin": etwin_link,
+    })),
+    "ForumActor": {
+      "description": "Externally tagged union of `ClientForumActor`, `RoleForumActor` and `UserForumActor`",
+      "type": "object",
+      "properties": {
+        "UserForumActor": object(json!({
+          "role": nullable(schema_ref("ForumRole")),
+          "user": schema_ref("ShortUser"),
+        })),
+      },
+    },
+    "ForumPost": post,
+    "ForumPostRevision": object(json!({
+      "type": tag("ForumPostRevision"),
+      "id": uuid,
+      "time": instant,
+      "author": schema_ref("ForumActor"),
+      "content": revision_content,
+      "moderation": revision_content,
+      "comment": nullable(string.clone()),
+    })),
+    "ForumRole": {"type": "string", "enum": ["Administrator", "Moderator"]},
+    "ForumRoleGrant": object(json!({
+      "role": schema_ref("ForumRole"),
+      "user": schema_ref("ShortUser"),
+      "start_time": instant,
+      "granted_by": schema_ref("ShortUser"),
+    })),
+    "ForumSection": section,
+    "ForumSectionListing": listing("ForumSectionMeta"),
+    "ForumSectionMeta": section_meta,
+    "ForumThread": thread,
+    "ForumThreadMeta": thread_meta,
+    "HammerfestUser": hammerfest_user,
+    "LoginCredentials": {
+      "description": "Body matching the `method` query parameter",
+      "anyOf": [
+        object(json!({
+          "login": string,
+          "password": {"type": "string", "description": "Hex-encoded password bytes"},
+        })),
+        object(json!({
+          "server": {"type": "string", "enum": DINOPARC_SERVERS},
+          "username": string,
+          "password": string,
+        })),
+        object(json!({
+          "server": {"type": "string", "enum": HAMMERFEST_SERVERS},
+          "username": string,
+          "password": string,
+        })),
+      ],
+    },
+    "LoginResponse": {
+      "anyOf": [
+        schema_ref("CompleteUser"),
+        object(json!({
+          "type": tag("PendingMfa"),
+          "token": string,
+          "expires_at": instant,
+        })),
+      ],
+    },
+    "OauthAccessToken": {
+      "type": "object",
+      "required": ["token_type", "access_token", "expires_in"],
+      "properties": {
+        "token_type": {"type": "string", "enum": ["Bearer"]},
+        "access_token": string,
+        "expires_in": {"type": "integer"},
+        "refresh_token": string,
+      },
+    },
+    "OpenapiDocument": {
+      "type": "object",
+      "required": ["openapi", "info", "paths"],
+      "properties": {"openapi": {"type": "string", "enum": [OPENAPI_VERSION]}},
+    },
+    "ShortOauthClient": object(json!({
+      "type": tag("OauthClient"),
+      "id": uuid,
+      "key": nullable(string.clone()),
+      "display_name": string,
+    })),
+    "ShortForumPost": short_post,
+    "ShortUser": short_user,
+    "UpdateUserPatch": {
+      "type": "object",
+      "properties": {
+        "display_name": string,
+        "username": nullable(string.clone()),
+        "password": nullable(json!({"type": "string", "description": "Hex-encoded password bytes"})),
+        "current_password": {
+          "type": "string",
+          "description": "Hex-encoded password bytes; required when users change their own password",
+        },
+      },
+    },
+    "User": {
+      "description": "Complete for the user themself and administrators, simple otherwise",
+      "anyOf": [schema_ref("CompleteUser"), simple_user],
+    },
+    "UserRef": {
+      "anyOf": [
+        object(json!({"type": tag("User"), "id": uuid})),
+        object(json!({"type": tag("User"), "username": string})),
+        object(json!({"type": tag("User"), "email": string})),
+      ],
+    },
+    "VersionedEtwinLink": object(json!({
+      "current": nullable(object(json!({
+        "link": user_dot,
+        "unlink": {"nullable": true},
+        "user": schema_ref("ShortUser"),
+      }))),
+      "old": {"type": "array", "items": object(json!({
+        "link": user_dot,
+        "unlink": user_dot,
+        "user": schema_ref("ShortUser"),
+      }))},
+    })),
+  })
+}
+
+/// Add required properties to an object schema built with `object`.
+fn extend(schema: &mut Value, properties: Value) {
+  for (key, value) in properties.as_object().expect("properties must be an object") {
+    let required = schema["required"]
+      .as_array_mut()
+      .expect("schema must have required fields");
+    if !required.iter().any(|r| r == key) {
+      required.push(Value::String(key.clone()));
+    }
+    schema["properties"][key] = value.clone();
+  }
+}
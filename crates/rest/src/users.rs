@@ -0,0 +1,162 @@
+use crate::auth::{authenticate, SESSION_COOKIE};
+use crate::{RestFilter, RouterApi};
+use etwin_core::auth::{AuthContext, LoginThrottledError, SessionId};
+use etwin_core::user::{
+  CompleteSimpleUser, GetUserOptions, GetUserResult, RawUpdateUserPatch, UpdateUserError, UserFields, UserId,
+  UserIdRef, UserRef, UserStore,
+};
+use etwin_services::auth::{DynAuthService, PatchUserError};
+use serde::Serialize;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// Maximum size of a user patch body.
+const MAX_PATCH_BODY_SIZE: u64 = 16 * 1024;
+
+pub fn create_users_filter(api: RouterApi) -> RestFilter {
+  let get_user = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum GetUserError {
+      UserNotFound,
+      InternalServerError,
+    }
+
+    impl GetUserError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::UserNotFound => StatusCode::NOT_FOUND,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_get_user(
+      user_store: &dyn UserStore,
+      acx: &AuthContext,
+      id: UserId,
+    ) -> Result<GetUserResult, GetUserError> {
+      let fields = match acx {
+        AuthContext::User(acx) if acx.is_administrator => UserFields::Complete,
+        AuthContext::User(acx) => UserFields::CompleteIfSelf {
+          self_user_id: acx.user.id,
+        },
+        _ => UserFields::Default,
+      };
+      match user_store
+        .get_user(&GetUserOptions {
+          r#ref: UserRef::Id(UserIdRef { id }),
+          fields,
+          time: None,
+        })
+        .await
+      {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(GetUserError::UserNotFound),
+        Err(_) => Err(GetUserError::InternalServerError),
+      }
+    }
+
+    let api = api.clone();
+    warp::path!(UserId)
+      .and(warp::get())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and_then(move |id: UserId, acx: AuthContext| {
+        let user_store = Arc::clone(&api.user_store);
+        async move {
+          let res = handle_get_user(user_store.as_ref(), &acx, id).await;
+          let reply = match res {
+            Ok(user) => warp::reply::with_status(warp::reply::json(&user), StatusCode::OK),
+            Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+          };
+          Ok::<_, Rejection>(reply.into_response())
+        }
+      })
+      .boxed()
+  };
+
+  let update_user = {
+    #[derive(Copy, Clone, Debug, Serialize)]
+    #[serde(tag = "error")]
+    enum UpdateUserRestError {
+      Unauthenticated,
+      Forbidden,
+      UserNotFound,
+      LockedDisplayName,
+      LockedUsername,
+      LockedPassword,
+      CurrentPasswordRequired,
+      WrongCurrentPassword,
+      LoginThrottled,
+      InternalServerError,
+    }
+
+    impl UpdateUserRestError {
+      pub fn get_status_code(self) -> StatusCode {
+        match self {
+          Self::Unauthenticated => StatusCode::UNAUTHORIZED,
+          Self::Forbidden => StatusCode::FORBIDDEN,
+          Self::UserNotFound => StatusCode::NOT_FOUND,
+          Self::LockedDisplayName => StatusCode::CONFLICT,
+          Self::LockedUsername => StatusCode::CONFLICT,
+          Self::LockedPassword => StatusCode::CONFLICT,
+          Self::CurrentPasswordRequired => StatusCode::UNPROCESSABLE_ENTITY,
+          Self::WrongCurrentPassword => StatusCode::FORBIDDEN,
+          Self::LoginThrottled => StatusCode::TOO_MANY_REQUESTS,
+          Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+      }
+    }
+
+    async fn handle_update_user(
+      auth: &DynAuthService,
+      acx: &AuthContext,
+      id: UserId,
+      patch: &RawUpdateUserPatch,
+      session: Option<SessionId>,
+    ) -> Result<CompleteSimpleUser, UpdateUserRestError> {
+      auth
+        .update_user(acx, id.into(), patch, session)
+        .await
+        .map_err(|e| match e {
+          PatchUserError::Unauthenticated => UpdateUserRestError::Unauthenticated,
+          PatchUserError::Forbidden => UpdateUserRestError::Forbidden,
+          PatchUserError::Store(UpdateUserError::NotFound(_)) => UpdateUserRestError::UserNotFound,
+          PatchUserError::Store(UpdateUserError::LockedDisplayName(..)) => UpdateUserRestError::LockedDisplayName,
+          PatchUserError::Store(UpdateUserError::LockedUsername(..)) => UpdateUserRestError::LockedUsername,
+          PatchUserError::Store(UpdateUserError::LockedPassword(..)) => UpdateUserRestError::LockedPassword,
+          PatchUserError::Store(UpdateUserError::Other(_)) => UpdateUserRestError::InternalServerError,
+          PatchUserError::CurrentPasswordRequired => UpdateUserRestError::CurrentPasswordRequired,
+          PatchUserError::WrongCurrentPassword => UpdateUserRestError::WrongCurrentPassword,
+          PatchUserError::Other(e) if e.is::<LoginThrottledError>() => UpdateUserRestError::LoginThrottled,
+          PatchUserError::Other(_) => UpdateUserRestError::InternalServerError,
+        })
+    }
+
+    warp::path!(UserId)
+      .and(warp::patch())
+      .and(authenticate(Arc::clone(&api.auth)))
+      .and(warp::body::content_length_limit(MAX_PATCH_BODY_SIZE))
+      .and(warp::body::json())
+      .and(warp::cookie::optional::<String>(SESSION_COOKIE))
+      .and_then(
+        move |id: UserId, acx: AuthContext, patch: RawUpdateUserPatch, session: Option<String>| {
+          let auth = Arc::clone(&api.auth);
+          // Session kept open when users change their own password
+          let session: Option<SessionId> = session.and_then(|session| session.parse().ok());
+          async move {
+            let res = handle_update_user(&auth, &acx, id, &patch, session).await;
+            let reply = match res {
+              Ok(user) => warp::reply::with_status(warp::reply::json(&user), StatusCode::OK),
+              Err(e) => warp::reply::with_status(warp::reply::json(&e), e.get_status_code()),
+            };
+            Ok::<_, Rejection>(reply.into_response())
+          }
+        },
+      )
+      .boxed()
+  };
+
+  get_user.or(update_user).unify().boxed()
+}
@@ -0,0 +1,269 @@
+use crate::auth::AuthEvent;
+use etwin_core::dinoparc::{DinoparcClient, DinoparcDinozId, DinoparcSession, DinoparcStore};
+use etwin_core::hammerfest::{HammerfestClient, HammerfestGetProfileByIdOptions, HammerfestSession, HammerfestStore};
+use etwin_core::types::AnyError;
+use etwin_log::Logger;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Single fetch-and-store step of the archive pipeline
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArchiveStep {
+  HammerfestProfile,
+  HammerfestInventory,
+  HammerfestGodchildren,
+  HammerfestShop,
+  DinoparcInventory,
+  DinoparcCollection,
+  DinoparcDinoz(DinoparcDinozId),
+}
+
+impl fmt::Display for ArchiveStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::HammerfestProfile => f.write_str("HammerfestProfile"),
+      Self::HammerfestInventory => f.write_str("HammerfestInventory"),
+      Self::HammerfestGodchildren => f.write_str("HammerfestGodchildren"),
+      Self::HammerfestShop => f.write_str("HammerfestShop"),
+      Self::DinoparcInventory => f.write_str("DinoparcInventory"),
+      Self::DinoparcCollection => f.write_str("DinoparcCollection"),
+      Self::DinoparcDinoz(id) => write!(f, "DinoparcDinoz({})", id),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct ArchiveFailure {
+  pub step: ArchiveStep,
+  pub error: AnyError,
+}
+
+/// Outcome of an archive run.
+///
+/// Steps are independent: a failing step is recorded here and the remaining
+/// steps still run.
+#[derive(Debug, Default)]
+pub struct ArchiveReport {
+  pub archived: Vec<ArchiveStep>,
+  pub failures: Vec<ArchiveFailure>,
+}
+
+impl ArchiveReport {
+  pub fn is_ok(&self) -> bool {
+    self.failures.is_empty()
+  }
+
+  fn record(&mut self, step: ArchiveStep, result: Result<(), AnyError>) {
+    match result {
+      Ok(()) => self.archived.push(step),
+      Err(error) => self.failures.push(ArchiveFailure { step, error }),
+    }
+  }
+}
+
+/// Archive the profile, inventory, godchildren and shop of the session user.
+pub async fn archive_hammerfest_session<TyClient, TyStore>(
+  client: TyClient,
+  store: TyStore,
+  session: &HammerfestSession,
+) -> ArchiveReport
+where
+  TyClient: HammerfestClient,
+  TyStore: HammerfestStore,
+{
+  let mut report = ArchiveReport::default();
+
+  let profile = async {
+    let options = HammerfestGetProfileByIdOptions {
+      server: session.user.server,
+      user_id: session.user.id,
+    };
+    let response = client.get_profile_by_id(Some(session), &options).await?;
+    store.touch_profile(&response).await
+  };
+  report.record(ArchiveStep::HammerfestProfile, profile.await);
+
+  let inventory = async {
+    let response = client.get_own_items(session).await?;
+    store.touch_inventory(&response).await
+  };
+  report.record(ArchiveStep::HammerfestInventory, inventory.await);
+
+  let godchildren = async {
+    let response = client.get_own_godchildren(session).await?;
+    store.touch_godchildren(&response).await
+  };
+  report.record(ArchiveStep::HammerfestGodchildren, godchildren.await);
+
+  let shop = async {
+    let response = client.get_own_shop(session).await?;
+    store.touch_shop(&response).await
+  };
+  report.record(ArchiveStep::HammerfestShop, shop.await);
+
+  report
+}
+
+/// Archive the inventory, collection and every dinoz of the session user.
+///
+/// The dinoz list is read from the inventory response: if it fails, no dinoz
+/// is archived.
+pub async fn archive_dinoparc_session<TyClient, TyStore>(
+  client: TyClient,
+  store: TyStore,
+  session: &DinoparcSession,
+) -> ArchiveReport
+where
+  TyClient: DinoparcClient,
+  TyStore: DinoparcStore,
+{
+  let mut report = ArchiveReport::default();
+
+  let mut dinoz: Vec<DinoparcDinozId> = Vec::new();
+  let inventory = async {
+    let response = client.get_inventory(session).await?;
+    dinoz.extend(response.session_user.dinoz.iter().map(|d| d.id));
+    store.touch_inventory(&response).await
+  };
+  report.record(ArchiveStep::DinoparcInventory, inventory.await);
+
+  let collection = async {
+    let response = client.get_collection(session).await?;
+    store.touch_collection(&response).await
+  };
+  report.record(ArchiveStep::DinoparcCollection, collection.await);
+
+  for id in dinoz {
+    let result = async {
+      let response = client.get_dinoz(session, id).await?;
+      store.touch_dinoz(&response).await
+    };
+    report.record(ArchiveStep::DinoparcDinoz(id), result.await);
+  }
+
+  report
+}
+
+/// Game session acquired during a login, whose data should be archived
+#[derive(Clone, Debug)]
+pub enum ArchiveRequest {
+  Dinoparc(DinoparcSession),
+  Hammerfest(HammerfestSession),
+}
+
+/// Sending half of the queue consumed by an [`ArchiveWorker`].
+///
+/// Pushing a request never waits for the archival: logins stay fast even
+/// when the game servers are slow.
+#[derive(Clone, Debug)]
+pub struct ArchiveQueue {
+  sender: mpsc::Sender<ArchiveRequest>,
+}
+
+impl ArchiveQueue {
+  /// Queue a request, fails if the queue is full or the worker stopped.
+  pub fn push(&self, request: ArchiveRequest) -> Result<(), AnyError> {
+    self.sender.try_send(request).map_err(|e| -> AnyError {
+      match e {
+        mpsc::error::TrySendError::Full(_) => "ArchiveQueueFull".into(),
+        mpsc::error::TrySendError::Closed(_) => "ArchiveQueueClosed".into(),
+      }
+    })
+  }
+}
+
+/// Archive the game sessions pushed to its [`ArchiveQueue`].
+///
+/// Failed steps are logged as [`AuthEvent::ArchiveOnLoginFailed`].
+pub struct ArchiveWorker<TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyLogger>
+where
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyLogger: Logger<AuthEvent>,
+{
+  dinoparc_client: TyDinoparcClient,
+  dinoparc_store: TyDinoparcStore,
+  hammerfest_client: TyHammerfestClient,
+  hammerfest_store: TyHammerfestStore,
+  logger: TyLogger,
+  receiver: mpsc::Receiver<ArchiveRequest>,
+}
+
+pub type DynArchiveWorker = ArchiveWorker<
+  Arc<dyn DinoparcClient>,
+  Arc<dyn DinoparcStore>,
+  Arc<dyn HammerfestClient>,
+  Arc<dyn HammerfestStore>,
+  Arc<dyn Logger<AuthEvent>>,
+>;
+
+impl<TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyLogger>
+  ArchiveWorker<TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyLogger>
+where
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyLogger: Logger<AuthEvent>,
+{
+  /// Create a worker and the queue feeding it, holding at most `capacity` pending requests.
+  pub fn new(
+    capacity: usize,
+    dinoparc_client: TyDinoparcClient,
+    dinoparc_store: TyDinoparcStore,
+    hammerfest_client: TyHammerfestClient,
+    hammerfest_store: TyHammerfestStore,
+    logger: TyLogger,
+  ) -> (ArchiveQueue, Self) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let worker = Self {
+      dinoparc_client,
+      dinoparc_store,
+      hammerfest_client,
+      hammerfest_store,
+      logger,
+      receiver,
+    };
+    (ArchiveQueue { sender }, worker)
+  }
+
+  /// Archive the queued requests until every [`ArchiveQueue`] is dropped.
+  pub async fn run(mut self) {
+    while let Some(request) = self.receiver.recv().await {
+      self.archive(&request).await;
+    }
+  }
+
+  /// Archive the requests already queued, without waiting for new ones.
+  ///
+  /// Returns the number of archived requests.
+  pub async fn archive_queued(&mut self) -> usize {
+    let mut count = 0;
+    while let Ok(request) = self.receiver.try_recv() {
+      self.archive(&request).await;
+      count += 1;
+    }
+    count
+  }
+
+  pub async fn archive(&self, request: &ArchiveRequest) -> ArchiveReport {
+    let report = match request {
+      ArchiveRequest::Dinoparc(session) => {
+        archive_dinoparc_session(&self.dinoparc_client, &self.dinoparc_store, session).await
+      }
+      ArchiveRequest::Hammerfest(session) => {
+        archive_hammerfest_session(&self.hammerfest_client, &self.hammerfest_store, session).await
+      }
+    };
+    for failure in report.failures.iter() {
+      self.logger.log(AuthEvent::ArchiveOnLoginFailed {
+        step: failure.step.to_string(),
+        error: failure.error.to_string(),
+      });
+    }
+    report
+  }
+}
@@ -0,0 +1,299 @@
+use chrono::Duration;
+use etwin_core::clock::Clock;
+use etwin_core::dinoparc::{
+  DinoparcClient, DinoparcServer, DinoparcSession, DinoparcStore, GetStaleDinoparcUsersOptions, ShortDinoparcUser,
+};
+use etwin_core::hammerfest::{
+  GetStaleHammerfestUsersOptions, HammerfestClient, HammerfestGetProfileByIdOptions, HammerfestServer, HammerfestStore,
+  ShortHammerfestUser,
+};
+use etwin_core::token::TokenStore;
+use etwin_core::types::AnyError;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// Archived user visited by a refresh pass
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArchiveRefreshUser {
+  Dinoparc(ShortDinoparcUser),
+  Hammerfest(ShortHammerfestUser),
+}
+
+impl ArchiveRefreshUser {
+  fn server(&self) -> ArchiveRefreshServer {
+    match self {
+      Self::Dinoparc(user) => ArchiveRefreshServer::Dinoparc(user.server),
+      Self::Hammerfest(user) => ArchiveRefreshServer::Hammerfest(user.server),
+    }
+  }
+}
+
+impl From<ShortDinoparcUser> for ArchiveRefreshUser {
+  fn from(user: ShortDinoparcUser) -> Self {
+    Self::Dinoparc(user)
+  }
+}
+
+impl From<ShortHammerfestUser> for ArchiveRefreshUser {
+  fn from(user: ShortHammerfestUser) -> Self {
+    Self::Hammerfest(user)
+  }
+}
+
+impl fmt::Display for ArchiveRefreshUser {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Dinoparc(user) => write!(f, "{}/{} ({})", user.server.as_str(), user.id, user.username),
+      Self::Hammerfest(user) => write!(f, "{}/{} ({})", user.server.as_str(), user.id, user.username),
+    }
+  }
+}
+
+/// Remote server receiving the requests of a refresh pass
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum ArchiveRefreshServer {
+  Dinoparc(DinoparcServer),
+  Hammerfest(HammerfestServer),
+}
+
+#[derive(Debug)]
+pub struct ArchiveRefreshFailure {
+  pub user: ArchiveRefreshUser,
+  pub error: AnyError,
+}
+
+/// Outcome of a refresh pass over the stale archived users.
+#[derive(Debug, Default)]
+pub struct ArchiveRefreshReport {
+  /// Users whose data was fetched and stored again
+  pub refreshed: Vec<ArchiveRefreshUser>,
+  /// Users whose data could not be fetched or stored: they stay stale and are retried after the retry delay
+  pub failures: Vec<ArchiveRefreshFailure>,
+}
+
+pub struct ArchiveRefreshService<
+  TyClock,
+  TyDinoparcClient,
+  TyDinoparcStore,
+  TyHammerfestClient,
+  TyHammerfestStore,
+  TyTokenStore,
+> where
+  TyClock: Clock,
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyTokenStore: TokenStore,
+{
+  clock: TyClock,
+  dinoparc_client: TyDinoparcClient,
+  dinoparc_store: TyDinoparcStore,
+  hammerfest_client: TyHammerfestClient,
+  hammerfest_store: TyHammerfestStore,
+  token_store: TyTokenStore,
+  max_age: Duration,
+  retry_delay: Duration,
+  limit: u32,
+  request_interval: std::time::Duration,
+}
+
+pub type DynArchiveRefreshService = ArchiveRefreshService<
+  Arc<dyn Clock>,
+  Arc<dyn DinoparcClient>,
+  Arc<dyn DinoparcStore>,
+  Arc<dyn HammerfestClient>,
+  Arc<dyn HammerfestStore>,
+  Arc<dyn TokenStore>,
+>;
+
+impl<TyClock, TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyTokenStore>
+  ArchiveRefreshService<TyClock, TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyTokenStore>
+where
+  TyClock: Clock,
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyTokenStore: TokenStore,
+{
+  pub fn new(
+    clock: TyClock,
+    dinoparc_client: TyDinoparcClient,
+    dinoparc_store: TyDinoparcStore,
+    hammerfest_client: TyHammerfestClient,
+    hammerfest_store: TyHammerfestStore,
+    token_store: TyTokenStore,
+  ) -> Self {
+    Self {
+      clock,
+      dinoparc_client,
+      dinoparc_store,
+      hammerfest_client,
+      hammerfest_store,
+      token_store,
+      max_age: Duration::days(30),
+      retry_delay: Duration::days(1),
+      limit: 100,
+      request_interval: std::time::Duration::from_secs(1),
+    }
+  }
+
+  /// Set the age after which archived data is considered stale.
+  pub fn with_max_age(mut self, max_age: Duration) -> Self {
+    self.max_age = max_age;
+    self
+  }
+
+  /// Set the minimum delay between two refresh attempts for the same user.
+  pub fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+    self.retry_delay = retry_delay;
+    self
+  }
+
+  /// Set the maximum number of users refreshed per server and per pass.
+  pub fn with_limit(mut self, limit: u32) -> Self {
+    self.limit = limit;
+    self
+  }
+
+  /// Set the minimum delay between two requests sent to the same server.
+  pub fn with_request_interval(mut self, request_interval: std::time::Duration) -> Self {
+    self.request_interval = request_interval;
+    self
+  }
+
+  /// Fetch again the data of the users whose latest snapshot is older than the max age.
+  ///
+  /// Hammerfest users get their public profile refreshed. Dinoparc users get their collection
+  /// refreshed, using the stored session of the user: users without a session fail.
+  ///
+  /// Every attempt is recorded, so users who failed are only retried after the retry delay and
+  /// users never attempted go first.
+  /// Servers are visited in turn so a slow server does not hold back the others.
+  pub async fn refresh_stale_users(&self) -> Result<ArchiveRefreshReport, AnyError> {
+    let now = self.clock.now();
+    let retrieved_before = now - self.max_age;
+    let attempted_before = now - self.retry_delay;
+    let mut queues: Vec<VecDeque<ArchiveRefreshUser>> = Vec::new();
+    for server in DinoparcServer::iter() {
+      let stale = self
+        .dinoparc_store
+        .get_stale_users(&GetStaleDinoparcUsersOptions {
+          server,
+          retrieved_before,
+          attempted_before,
+          limit: self.limit,
+        })
+        .await?;
+      queues.push(stale.into_iter().map(ArchiveRefreshUser::from).collect());
+    }
+    for server in HammerfestServer::iter() {
+      let stale = self
+        .hammerfest_store
+        .get_stale_users(&GetStaleHammerfestUsersOptions {
+          server,
+          retrieved_before,
+          attempted_before,
+          limit: self.limit,
+        })
+        .await?;
+      queues.push(stale.into_iter().map(ArchiveRefreshUser::from).collect());
+    }
+
+    let mut rate_limiter = RateLimiter::new(self.request_interval);
+    let mut report = ArchiveRefreshReport::default();
+    while queues.iter().any(|q| !q.is_empty()) {
+      for queue in queues.iter_mut() {
+        let user = match queue.pop_front() {
+          Some(user) => user,
+          None => continue,
+        };
+        rate_limiter.acquire(user.server()).await;
+        let result = match &user {
+          ArchiveRefreshUser::Dinoparc(user) => self.refresh_dinoparc_user(user).await,
+          ArchiveRefreshUser::Hammerfest(user) => self.refresh_hammerfest_user(user).await,
+        };
+        match result {
+          Ok(()) => report.refreshed.push(user),
+          Err(error) => report.failures.push(ArchiveRefreshFailure { user, error }),
+        }
+      }
+    }
+    Ok(report)
+  }
+
+  async fn refresh_dinoparc_user(&self, user: &ShortDinoparcUser) -> Result<(), AnyError> {
+    self.dinoparc_store.touch_refresh_attempt(user.as_ref()).await?;
+    let session = match self.token_store.get_dinoparc(user.as_ref()).await? {
+      Some(session) => session,
+      None => return Err(format!("no session: {}/{}", user.server.as_str(), user.id).into()),
+    };
+    let session = DinoparcSession {
+      ctime: session.ctime,
+      atime: session.atime,
+      key: session.key,
+      user: user.clone(),
+    };
+    let response = self.dinoparc_client.get_collection(&session).await?;
+    self.dinoparc_store.touch_collection(&response).await
+  }
+
+  async fn refresh_hammerfest_user(&self, user: &ShortHammerfestUser) -> Result<(), AnyError> {
+    self.hammerfest_store.touch_refresh_attempt(user.as_ref()).await?;
+    let options = HammerfestGetProfileByIdOptions {
+      server: user.server,
+      user_id: user.id,
+    };
+    let response = self.hammerfest_client.get_profile_by_id(None, &options).await?;
+    if response.profile.is_none() {
+      return Err(format!("profile not found: {}/{}", user.server, user.id).into());
+    }
+    self.hammerfest_store.touch_profile(&response).await
+  }
+}
+
+/// Enforce a minimum delay between two uses of the same key.
+struct RateLimiter<K> {
+  interval: std::time::Duration,
+  next: HashMap<K, tokio::time::Instant>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+  fn new(interval: std::time::Duration) -> Self {
+    Self {
+      interval,
+      next: HashMap::new(),
+    }
+  }
+
+  async fn acquire(&mut self, key: K) {
+    if let Some(next) = self.next.get(&key) {
+      tokio::time::sleep_until(*next).await;
+    }
+    self.next.insert(key, tokio::time::Instant::now() + self.interval);
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyDinoparcClient, TyDinoparcStore, TyHammerfestClient, TyHammerfestStore, TyTokenStore>
+  neon::prelude::Finalize
+  for ArchiveRefreshService<
+    TyClock,
+    TyDinoparcClient,
+    TyDinoparcStore,
+    TyHammerfestClient,
+    TyHammerfestStore,
+    TyTokenStore,
+  >
+where
+  TyClock: Clock,
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyTokenStore: TokenStore,
+{
+}
@@ -1,48 +1,109 @@
+use crate::archive::{ArchiveQueue, ArchiveRequest};
+use crate::totp;
 use chrono::Duration;
 use etwin_core::auth::{
-  AccessTokenAuthContext, AuthContext, AuthScope, AuthStore, CreateAccessTokenOptions, CreateSessionOptions,
-  CreateValidatedEmailVerificationOptions, Credentials, EtwinOauthAccessTokenKey, GrantOauthAuthorizationOptions,
-  Login, OauthClientAuthContext, RawCredentials, RawUserCredentials, RegisterOrLoginWithEmailOptions,
-  RegisterWithUsernameOptions, RegisterWithVerifiedEmailOptions, SessionId, UserAndSession, UserAuthContext,
-  UserCredentials, UserLogin,
+  AccessTokenAuthContext, AuthContext, AuthScope, AuthStore, ChangeEmailWithTokenOptions, CompleteMfaLoginOptions,
+  CountLoginFailuresOptions, CreateAccessTokenOptions, CreateLoginFailureOptions, CreateLoginLockoutOptions,
+  CreateMfaLoginChallengeOptions, CreateSessionOptions, CreateValidatedEmailVerificationOptions, Credentials,
+  EtwinOauthAccessTokenKey, GrantOauthAuthorizationOptions, InvalidCredentialsError, Login, LoginFailureReason,
+  LoginResult, LoginThrottleKey, LoginThrottledError, MfaLoginChallengeId, OauthClientAuthContext, PendingMfaLogin,
+  RawCredentials, RawUserCredentials, RegisterOrLoginWithEmailOptions, RegisterWithUsernameOptions,
+  RegisterWithVerifiedEmailOptions, RequestEmailChangeOptions, RevokeUserSessionsOptions, SessionId, UserAndSession,
+  UserAuthContext, UserCredentials, UserLogin,
 };
 use etwin_core::clock::Clock;
 use etwin_core::core::{Instant, LocaleId};
 use etwin_core::dinoparc::{DinoparcClient, DinoparcCredentials, DinoparcStore, ShortDinoparcUser};
-use etwin_core::email::{EmailAddress, EmailFormatter, Mailer, VerifyRegistrationEmail};
+use etwin_core::email::{
+  EmailAddress, EmailFormatter, Mailer, NotifyEmailChangeEmail, VerifyEmailChangeEmail, VerifyRegistrationEmail,
+};
 use etwin_core::hammerfest::{HammerfestClient, HammerfestCredentials, HammerfestStore, ShortHammerfestUser};
 use etwin_core::link::{GetLinkOptions, LinkStore, TouchLinkOptions};
+use etwin_core::mfa::{
+  ConfirmTotpEnrollmentOptions, DeleteUserRecoveryCodeOptions, DisableTotpOptions, MfaProof, RecoveryCode,
+  RecoveryCodes, SetUserTotpOptions, TotpCode, TotpEnrollment, TouchUserTotpStepOptions, UserTotp,
+};
 use etwin_core::oauth::{
   CreateStoredAccessTokenOptions, EtwinOauthScopes, GetOauthAccessTokenOptions, GetOauthClientError,
-  GetOauthClientOptions, OauthAccessToken, OauthClientId, OauthClientKey, OauthClientRef, OauthProviderStore,
-  RfcOauthAccessTokenKey, RfcOauthResponseType, RfcOauthTokenType, ShortOauthClient, SimpleOauthClient,
+  GetOauthClientOptions, OauthAccessToken, OauthClientId, OauthClientIdRef, OauthClientKey, OauthClientRef,
+  OauthProviderStore, RfcOauthAccessToken, RfcOauthResponseType, RfcOauthTokenType, ShortOauthClient,
+  SimpleOauthClient,
 };
 use etwin_core::password::{Password, PasswordService};
+use etwin_core::token::{TokenStore, TouchOauthTokenOptions};
 use etwin_core::twinoid::{
-  ShortTwinoidUser, TwinoidApiAuth, TwinoidClient, TwinoidStore, TwinoidUserId, TwinoidUserIdRef,
+  ShortTwinoidUser, TwinoidApiAuth, TwinoidClient, TwinoidStore, TwinoidUserId, TwinoidUserIdRef, TwinoidUserProfile,
 };
 use etwin_core::types::AnyError;
 use etwin_core::user::{
-  CreateUserOptions, GetShortUserOptions, GetUserOptions, GetUserResult, SimpleUser, UserDisplayName, UserEmailRef,
-  UserFields, UserId, UserIdRef, UserRef, UserStore, UserUsernameRef,
+  CompleteSimpleUser, CreateUserOptions, GetShortUserOptions, GetUserOptions, GetUserResult, RawUpdateUserPatch,
+  ReplaceUserPasswordHashOptions, SimpleUser, UpdateUserError, UpdateUserOptions, UpdateUserPatch, UserDisplayName,
+  UserEmailRef, UserFields, UserId, UserIdRef, UserRef, UserStore, UserUsernameRef,
 };
 use etwin_core::uuid::UuidGenerator;
+use etwin_log::Logger;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use thiserror::Error;
 use url::Url;
 
+/// Issuer displayed by authenticator apps.
+const TOTP_ISSUER: &str = "Eternaltwin";
+
+/// Number of recovery codes generated when enabling TOTP.
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Audience of the email change tokens, so they can't be used as any other token signed with the same key.
+const EMAIL_CHANGE_AUDIENCE: &str = "etwin_email_change";
+
 #[derive(Debug, Serialize, Deserialize)]
 struct EmailJwtClaims {
   /// Expiration time (Unix timestamp)
   exp: i64,
   /// Issued at (Unix timestamp)
   iat: i64,
+  /// Audience: never set for registration, used to reject the tokens of other flows
+  #[serde(default)]
+  aud: Option<String>,
   /// Custom: Email address to validate
   email: EmailAddress,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailChangeJwtClaims {
+  /// Audience: always `EMAIL_CHANGE_AUDIENCE`
+  aud: String,
+  /// Expiration time (Unix timestamp)
+  exp: i64,
+  /// Issued at (Unix timestamp)
+  iat: i64,
+  /// Subject: user requesting the change
+  sub: UserId,
+  /// Custom: New email address to validate
+  email: EmailAddress,
+  /// Custom: Email address of the user when the change was requested, the token is rejected once it changed
+  old_email: Option<EmailAddress>,
+  /// Custom: Locale requested for the emails about this change
+  #[serde(default)]
+  locale: Option<LocaleId>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MfaLoginJwtClaims {
+  /// Expiration time (Unix timestamp)
+  exp: i64,
+  /// Issued at (Unix timestamp)
+  iat: i64,
+  /// Subject: user who passed the first factor
+  sub: UserId,
+  /// Authentication methods already validated (RFC 8176)
+  amr: Vec<String>,
+  /// JWT ID: challenge stored by the auth store, consumed by the second step
+  jti: MfaLoginChallengeId,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct OauthCodeJwtClaims {
   /// The recipients that the JWT is intended for
@@ -68,6 +129,13 @@ pub struct OauthCodeGrant {
   callback_uri: Url,
 }
 
+impl OauthCodeGrant {
+  /// Client callback, with the authorization code and state in the query.
+  pub fn redirect_uri(&self) -> &Url {
+    &self.redirect_uri
+  }
+}
+
 #[derive(Error, Debug)]
 pub enum GrantOauthAuthorizationError {
   #[error("missing client_id parameter")]
@@ -100,10 +168,71 @@ pub enum CreateAccessTokenError {
   Unauthenticated,
   #[error("code audience does not match authenticated client")]
   WrongClient,
+  #[error("invalid or expired authorization code")]
+  InvalidGrant,
   #[error(transparent)]
   Other(AnyError),
 }
 
+#[derive(Error, Debug)]
+pub enum PatchUserError {
+  #[error("no authenticated user")]
+  Unauthenticated,
+  #[error("only the user or an administrator may update the user")]
+  Forbidden,
+  #[error("the current password is required to change the password")]
+  CurrentPasswordRequired,
+  #[error("wrong current password")]
+  WrongCurrentPassword,
+  #[error(transparent)]
+  Store(UpdateUserError),
+  #[error(transparent)]
+  Other(AnyError),
+}
+
+/// Event emitted by the [`AuthService`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum AuthEvent {
+  /// A new user registered
+  UserRegistered { user: UserIdRef },
+  /// A password check failed for a user or an OAuth client
+  LoginFailed {
+    user: Option<UserIdRef>,
+    oauth_client: Option<OauthClientIdRef>,
+    ip: Option<IpAddr>,
+    reason: LoginFailureReason,
+  },
+  /// A subject reached its limit of failed logins and is locked out
+  LoginLockedOut { key: LoginThrottleKey, until: Instant },
+  /// A login was rejected because the subject is locked out
+  LoginThrottled {
+    key: LoginThrottleKey,
+    retry_after: Instant,
+  },
+  /// A user granted an authorization code to an OAuth client
+  OauthAuthorizationGranted { user: UserIdRef, client: OauthClientIdRef },
+  /// An OAuth client exchanged an authorization code for an access token
+  OauthAccessTokenCreated { user: UserIdRef, client: OauthClientIdRef },
+  /// A user enabled TOTP
+  TotpEnabled { user: UserIdRef },
+  /// A user disabled TOTP
+  TotpDisabled { user: UserIdRef },
+  /// The notice sent to the previous address of a user after an email change could not be sent
+  EmailChangeNoticeFailed { user: UserIdRef, error: String },
+  /// The password hash of a user could not be upgraded after a successful login
+  PasswordRehashFailed { user: UserIdRef, error: String },
+  /// The Twinoid profile of a user could not be archived during an OAuth login
+  TwinoidProfileArchiveFailed {
+    twinoid_user: TwinoidUserIdRef,
+    error: String,
+  },
+  /// The game session acquired during a login could not be queued for archival
+  ArchiveOnLoginQueueFailed { user: UserIdRef, error: String },
+  /// A step failed while archiving the game session acquired during a login
+  ArchiveOnLoginFailed { step: String, error: String },
+}
+
 pub struct AuthService<
   TyAuthStore,
   TyClock,
@@ -113,9 +242,11 @@ pub struct AuthService<
   TyHammerfestClient,
   TyHammerfestStore,
   TyLinkStore,
+  TyLogger,
   TyMailer,
   TyOauthProviderStore,
   TyPasswordService,
+  TyTokenStore,
   TyTwinoidClient,
   TyTwinoidStore,
   TyUserStore,
@@ -129,9 +260,11 @@ pub struct AuthService<
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<AuthEvent>,
   TyMailer: Mailer,
   TyOauthProviderStore: OauthProviderStore,
   TyPasswordService: PasswordService,
+  TyTokenStore: TokenStore,
   TyTwinoidClient: TwinoidClient,
   TyTwinoidStore: TwinoidStore,
   TyUserStore: UserStore,
@@ -145,9 +278,11 @@ pub struct AuthService<
   hammerfest_client: TyHammerfestClient,
   hammerfest_store: TyHammerfestStore,
   link_store: TyLinkStore,
+  logger: TyLogger,
   mailer: TyMailer,
   oauth_provider_store: TyOauthProviderStore,
   password_service: TyPasswordService,
+  token_store: TyTokenStore,
   twinoid_client: TyTwinoidClient,
   twinoid_store: TyTwinoidStore,
   user_store: TyUserStore,
@@ -157,6 +292,14 @@ pub struct AuthService<
   email_verification_validity: Duration,
   authorization_code_validity: Duration,
   access_token_validity: Duration,
+  mfa_login_validity: Duration,
+  max_mfa_login_attempts: u32,
+  login_throttle_window: Duration,
+  login_lockout_duration: Duration,
+  max_user_login_failures: u32,
+  max_oauth_client_login_failures: u32,
+  max_ip_login_failures: u32,
+  archive_queue: Option<ArchiveQueue>,
 }
 
 pub type DynAuthService = AuthService<
@@ -168,9 +311,11 @@ pub type DynAuthService = AuthService<
   Arc<dyn HammerfestClient>,
   Arc<dyn HammerfestStore>,
   Arc<dyn LinkStore>,
+  Arc<dyn Logger<AuthEvent>>,
   Arc<dyn Mailer>,
   Arc<dyn OauthProviderStore>,
   Arc<dyn PasswordService>,
+  Arc<dyn TokenStore>,
   Arc<dyn TwinoidClient>,
   Arc<dyn TwinoidStore>,
   Arc<dyn UserStore>,
@@ -186,9 +331,11 @@ impl<
     TyHammerfestClient,
     TyHammerfestStore,
     TyLinkStore,
+    TyLogger,
     TyMailer,
     TyOauthProviderStore,
     TyPasswordService,
+    TyTokenStore,
     TyTwinoidClient,
     TyTwinoidStore,
     TyUserStore,
@@ -203,9 +350,11 @@ impl<
     TyHammerfestClient,
     TyHammerfestStore,
     TyLinkStore,
+    TyLogger,
     TyMailer,
     TyOauthProviderStore,
     TyPasswordService,
+    TyTokenStore,
     TyTwinoidClient,
     TyTwinoidStore,
     TyUserStore,
@@ -220,9 +369,11 @@ where
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<AuthEvent>,
   TyMailer: Mailer,
   TyOauthProviderStore: OauthProviderStore,
   TyPasswordService: PasswordService,
+  TyTokenStore: TokenStore,
   TyTwinoidClient: TwinoidClient,
   TyTwinoidStore: TwinoidStore,
   TyUserStore: UserStore,
@@ -238,9 +389,11 @@ where
     hammerfest_client: TyHammerfestClient,
     hammerfest_store: TyHammerfestStore,
     link_store: TyLinkStore,
+    logger: TyLogger,
     mailer: TyMailer,
     oauth_provider_store: TyOauthProviderStore,
     password_service: TyPasswordService,
+    token_store: TyTokenStore,
     user_store: TyUserStore,
     twinoid_client: TyTwinoidClient,
     twinoid_store: TyTwinoidStore,
@@ -256,9 +409,11 @@ where
       hammerfest_client,
       hammerfest_store,
       link_store,
+      logger,
       mailer,
       oauth_provider_store,
       password_service,
+      token_store,
       twinoid_client,
       twinoid_store,
       user_store,
@@ -269,9 +424,26 @@ where
       authorization_code_validity: chrono::Duration::minutes(10),
       // TODO: Make it expire!
       access_token_validity: chrono::Duration::seconds(1_000_000_000),
+      mfa_login_validity: chrono::Duration::minutes(5),
+      max_mfa_login_attempts: 3,
+      login_throttle_window: chrono::Duration::minutes(15),
+      login_lockout_duration: chrono::Duration::minutes(15),
+      max_user_login_failures: 5,
+      max_oauth_client_login_failures: 10,
+      max_ip_login_failures: 20,
+      archive_queue: None,
     }
   }
 
+  /// Enable archival of the game data reachable through the session acquired
+  /// when logging in with Dinoparc or Hammerfest credentials.
+  ///
+  /// The sessions are pushed to `archive_queue`, the login does not wait for the archival.
+  pub fn with_archive_queue(mut self, archive_queue: ArchiveQueue) -> Self {
+    self.archive_queue = Some(archive_queue);
+    self
+  }
+
   // TODO: Return enum codeGrant/tokenGrant
   pub async fn grant_oauth_authorization(
     &self,
@@ -331,6 +503,10 @@ where
         let code = self
           .create_authorization_code(acx.user.id.into(), &client, &scopes)
           .map_err(GrantOauthAuthorizationError::Other)?;
+        self.logger.log(AuthEvent::OauthAuthorizationGranted {
+          user: acx.user.id.into(),
+          client: client.id.into(),
+        });
         let redirect_uri = {
           let mut redirect_uri = client.callback_uri.clone();
           {
@@ -363,7 +539,9 @@ where
       AuthContext::OauthClient(ref acx) => &acx.client,
       _ => return Err(CreateAccessTokenError::Unauthenticated),
     };
-    let claims = self.read_code_token(code).map_err(CreateAccessTokenError::Other)?;
+    let claims = self
+      .read_code_token(code)
+      .map_err(|_| CreateAccessTokenError::InvalidGrant)?;
     // TODO: Check if `redirect_uri` matches
     let client_id_str = client.id.to_string();
     if !claims.aud.iter().any(|aud| aud.as_str() == client_id_str.as_str()) {
@@ -382,6 +560,10 @@ where
       })
       .await
       .map_err(CreateAccessTokenError::Other)?;
+    self.logger.log(AuthEvent::OauthAccessTokenCreated {
+      user: claims.sub.into(),
+      client: client.id.into(),
+    });
     Ok(OauthAccessToken {
       token_type: RfcOauthTokenType::Bearer,
       access_token: token.key,
@@ -468,6 +650,7 @@ where
         password: Some(password_hash),
       })
       .await?;
+    self.logger.log(AuthEvent::UserRegistered { user: user.id.into() });
 
     self
       .auth_store
@@ -493,6 +676,135 @@ where
     })
   }
 
+  pub async fn request_email_change(
+    &self,
+    acx: &AuthContext,
+    options: &RequestEmailChangeOptions,
+  ) -> Result<(), AnyError> {
+    let user = match acx {
+      AuthContext::User(acx) => &acx.user,
+      _ => return Err("Unauthorized".into()),
+    };
+    let old_user = self
+      .user_store
+      .get_short_user(&GetShortUserOptions {
+        r#ref: UserRef::Email(UserEmailRef {
+          email: options.email.clone(),
+        }),
+        time: None,
+      })
+      .await?;
+    if old_user.is_some() {
+      return Err("Conflict: EmailAddressAlreadyInUse".into());
+    }
+    let old_email = self.get_user_email(user.id).await?;
+    let locale = options.locale.unwrap_or(self.default_locale);
+    let token = self.create_email_change_token(user.id, &options.email, old_email.as_ref(), locale)?;
+    let email_content = self
+      .email_formatter
+      .verify_email_change_email(locale, &VerifyEmailChangeEmail { token })
+      .await?;
+    self.mailer.send_email(&options.email, &email_content).await?;
+    Ok(())
+  }
+
+  pub async fn change_email_with_token(
+    &self,
+    acx: &AuthContext,
+    options: &ChangeEmailWithTokenOptions,
+  ) -> Result<CompleteSimpleUser, AnyError> {
+    let user = match acx {
+      AuthContext::User(acx) => &acx.user,
+      _ => return Err("Unauthorized".into()),
+    };
+    let email_jwt = self.read_email_change_token(options.email_token.as_str())?;
+    if email_jwt.sub != user.id {
+      return Err("Forbidden: EmailTokenUserMismatch".into());
+    }
+    let email = email_jwt.email;
+    let locale = email_jwt.locale.unwrap_or(self.default_locale);
+
+    let old_email = self.get_user_email(user.id).await?;
+    if old_email != email_jwt.old_email {
+      return Err("Conflict: EmailTokenOutdated".into());
+    }
+
+    let old_user = self
+      .user_store
+      .get_short_user(&GetShortUserOptions {
+        r#ref: UserRef::Email(UserEmailRef { email: email.clone() }),
+        time: None,
+      })
+      .await?;
+    if old_user.is_some() {
+      return Err("Conflict: EmailAddressAlreadyInUse".into());
+    }
+
+    let updated = self
+      .user_store
+      .update_user(&UpdateUserOptions {
+        r#ref: user.id.into(),
+        actor: user.id.into(),
+        patch: UpdateUserPatch {
+          display_name: None,
+          username: None,
+          password: None,
+          email: Some(Some(email.clone())),
+        },
+      })
+      .await?;
+
+    self
+      .auth_store
+      .create_validated_email_verification(&CreateValidatedEmailVerificationOptions {
+        user: user.id.into(),
+        email,
+        token_issued_at: Instant::from_posix_timestamp(email_jwt.iat),
+      })
+      .await?;
+
+    // The change is already committed: failing to notify the old address must not report it as failed
+    if let Some(old_email) = old_email {
+      if let Err(e) = self.notify_email_change(&old_email, locale).await {
+        self.logger.log(AuthEvent::EmailChangeNoticeFailed {
+          user: user.id.into(),
+          error: e.to_string(),
+        });
+      }
+    }
+
+    Ok(updated)
+  }
+
+  async fn get_user_email(&self, user: UserId) -> Result<Option<EmailAddress>, AnyError> {
+    match self
+      .user_store
+      .get_user(&GetUserOptions {
+        r#ref: UserRef::Id(user.into()),
+        fields: UserFields::Complete,
+        time: None,
+      })
+      .await?
+    {
+      Some(GetUserResult::Complete(u)) => Ok(u.email_address),
+      Some(_) => unreachable!("AssertionError: Requested `UserFields::Complete` but got partial response"),
+      None => Err("UserNotFound".into()),
+    }
+  }
+
+  async fn notify_email_change(&self, old_email: &EmailAddress, locale: LocaleId) -> Result<(), AnyError> {
+    let email_content = self
+      .email_formatter
+      .notify_email_change_email(
+        locale,
+        &NotifyEmailChangeEmail {
+          changed_at: self.clock.now(),
+        },
+      )
+      .await?;
+    self.mailer.send_email(old_email, &email_content).await
+  }
+
   pub async fn register_with_username(
     &self,
     options: &RegisterWithUsernameOptions,
@@ -521,6 +833,7 @@ where
         password: Some(password_hash),
       })
       .await?;
+    self.logger.log(AuthEvent::UserRegistered { user: user.id.into() });
 
     let session = self
       .auth_store
@@ -537,22 +850,118 @@ where
     })
   }
 
-  pub async fn raw_login_with_credentials(&self, credentials: &RawUserCredentials) -> Result<UserAndSession, AnyError> {
+  pub async fn raw_login_with_credentials(
+    &self,
+    credentials: &RawUserCredentials,
+    ip: Option<IpAddr>,
+  ) -> Result<LoginResult, AnyError> {
     let credentials = UserCredentials {
-      login: credentials.login.parse().map_err(|()| AnyError::from("BadLogin"))?,
+      login: credentials
+        .login
+        .parse()
+        .map_err(|()| invalid_credentials("BadLogin"))?,
       password: credentials.password.clone(),
     };
-    self.login_with_credentials(&credentials).await
+    self.login_with_credentials(&credentials, ip).await
   }
 
-  pub async fn login_with_credentials(&self, credentials: &UserCredentials) -> Result<UserAndSession, AnyError> {
+  /// Password login for a user.
+  ///
+  /// `ip` is the address of the client, if known: it is used to throttle failed attempts.
+  pub async fn login_with_credentials(
+    &self,
+    credentials: &UserCredentials,
+    ip: Option<IpAddr>,
+  ) -> Result<LoginResult, AnyError> {
     let user_ref = match credentials.login.clone() {
       UserLogin::EmailAddress(email) => UserRef::Email(UserEmailRef { email }),
       UserLogin::Username(username) => UserRef::Username(UserUsernameRef { username }),
     };
     let user = self
-      .authenticate_user_with_password(user_ref, credentials.password.clone())
+      .authenticate_user_with_password(user_ref, credentials.password.clone(), ip)
+      .await?;
+
+    let totp = self.user_store.get_user_totp(user.id.into()).await?;
+    if totp.is_some() {
+      let (token, expires_at) = self.create_mfa_login_token(user.id).await?;
+      return Ok(LoginResult::PendingMfa(PendingMfaLogin { token, expires_at }));
+    }
+
+    let session = self
+      .auth_store
+      .create_session(&CreateSessionOptions { user: user.id.into() })
+      .await?
+      .into_session(user.display_name.clone());
+
+    let is_administrator = user.is_administrator;
+
+    Ok(LoginResult::Session(UserAndSession {
+      user: user.into(),
+      is_administrator,
+      session,
+    }))
+  }
+
+  /// Second login step, for users with a second factor.
+  ///
+  /// Invalid proofs count against the same user and IP limits as wrong passwords. A token accepts at most
+  /// `max_mfa_login_attempts` proofs and is consumed by the first valid one. Invalid tokens and proofs are reported
+  /// as `InvalidCredentialsError`.
+  pub async fn complete_mfa_login(
+    &self,
+    options: &CompleteMfaLoginOptions,
+    ip: Option<IpAddr>,
+  ) -> Result<UserAndSession, AnyError> {
+    let claims = self
+      .read_mfa_login_token(options.token.as_str())
+      .map_err(|_| invalid_credentials("InvalidMfaLoginToken"))?;
+    let user_id = claims.sub;
+    let user_ref = UserIdRef { id: user_id };
+    self.check_login_throttle(ip.map(LoginThrottleKey::Ip)).await?;
+    self
+      .check_login_throttle(Some(LoginThrottleKey::User(user_ref)))
+      .await?;
+
+    let challenge = match self.auth_store.touch_mfa_login_challenge(claims.jti).await? {
+      Some(challenge) if challenge.user == user_ref => challenge,
+      _ => return Err(invalid_credentials("InvalidMfaLoginToken")),
+    };
+    if challenge.attempts > self.max_mfa_login_attempts {
+      return Err(invalid_credentials("TooManyMfaLoginAttempts"));
+    }
+
+    let totp = match self.user_store.get_user_totp(user_ref).await? {
+      Some(totp) => totp,
+      None => return Err(invalid_credentials("TotpNotEnabled")),
+    };
+    let (is_valid, error) = match &options.proof {
+      MfaProof::Totp(code) => (self.consume_totp_code(&totp, code).await?, "InvalidTotpCode"),
+      MfaProof::RecoveryCode(code) => (self.consume_recovery_code(&totp, code).await?, "InvalidRecoveryCode"),
+    };
+    if !is_valid {
+      self
+        .record_login_failure(Some(user_ref), None, ip, LoginFailureReason::WrongSecondFactor)
+        .await?;
+      return Err(invalid_credentials(error));
+    }
+    if !self.auth_store.consume_mfa_login_challenge(challenge.id).await? {
+      return Err(invalid_credentials("InvalidMfaLoginToken"));
+    }
+
+    let user = self
+      .user_store
+      .get_user(&GetUserOptions {
+        r#ref: UserRef::Id(UserIdRef { id: user_id }),
+        fields: UserFields::Default,
+        time: None,
+      })
       .await?;
+    let user = user.ok_or_else(|| AnyError::from("UserNotFound"))?;
+    let user: SimpleUser = match user {
+      GetUserResult::Complete(u) => u.into(),
+      GetUserResult::Default(u) => u,
+      GetUserResult::Short(_) => unreachable!("AssertionError: Requested `UserFields::Default` but got short response"),
+    };
 
     let session = self
       .auth_store
@@ -569,11 +978,193 @@ where
     })
   }
 
+  /// Updates a user, on behalf of the user themself or of an administrator.
+  ///
+  /// Users changing their own password must confirm their current password, administrators do not.
+  /// A password change revokes all the sessions of the user except `current_session`, when the actor updates
+  /// themself.
+  pub async fn update_user(
+    &self,
+    acx: &AuthContext,
+    user: UserIdRef,
+    patch: &RawUpdateUserPatch,
+    current_session: Option<SessionId>,
+  ) -> Result<CompleteSimpleUser, PatchUserError> {
+    let acx = match acx {
+      AuthContext::User(acx) => acx,
+      _ => return Err(PatchUserError::Unauthenticated),
+    };
+    let is_self = acx.user.id == user.id;
+    if !(acx.is_administrator || is_self) {
+      return Err(PatchUserError::Forbidden);
+    }
+    if patch.password.is_some() && !acx.is_administrator {
+      self
+        .check_current_password(user, patch.current_password.clone())
+        .await?;
+    }
+    let password = patch
+      .password
+      .clone()
+      .map(|password| password.map(|password| self.password_service.hash(password)));
+    let updated = self
+      .user_store
+      .update_user(&UpdateUserOptions {
+        r#ref: user,
+        actor: acx.user.id.into(),
+        patch: UpdateUserPatch {
+          display_name: patch.display_name.clone(),
+          username: patch.username.clone(),
+          password,
+          email: None,
+        },
+      })
+      .await
+      .map_err(PatchUserError::Store)?;
+    if patch.password.is_some() {
+      self
+        .auth_store
+        .revoke_user_sessions(&RevokeUserSessionsOptions {
+          user,
+          except: if is_self { current_session } else { None },
+        })
+        .await
+        .map_err(PatchUserError::Other)?;
+    }
+    Ok(updated)
+  }
+
+  /// Checks the current password of a user before a password change.
+  ///
+  /// Users without a password have nothing to confirm. Wrong passwords count as failed logins, so they are
+  /// throttled the same way.
+  async fn check_current_password(
+    &self,
+    user: UserIdRef,
+    current_password: Option<Password>,
+  ) -> Result<(), PatchUserError> {
+    let user_with_password = self
+      .user_store
+      .get_user_with_password(&GetUserOptions {
+        r#ref: UserRef::Id(user),
+        fields: UserFields::Complete,
+        time: None,
+      })
+      .await
+      .map_err(PatchUserError::Other)?;
+    let password_hash = match user_with_password.and_then(|u| u.password) {
+      Some(password_hash) => password_hash,
+      None => return Ok(()),
+    };
+    let current_password = current_password.ok_or(PatchUserError::CurrentPasswordRequired)?;
+    self
+      .check_login_throttle(Some(LoginThrottleKey::User(user)))
+      .await
+      .map_err(PatchUserError::Other)?;
+    if !self.password_service.verify(password_hash, current_password) {
+      self
+        .record_login_failure(Some(user), None, None, LoginFailureReason::WrongPassword)
+        .await
+        .map_err(PatchUserError::Other)?;
+      return Err(PatchUserError::WrongCurrentPassword);
+    }
+    Ok(())
+  }
+
+  /// Generates a new TOTP secret for the current user.
+  ///
+  /// The secret is not stored until it is confirmed with `confirm_totp_enrollment`.
+  pub async fn enroll_totp(&self, acx: &AuthContext) -> Result<TotpEnrollment, AnyError> {
+    let user = match acx {
+      AuthContext::User(acx) => &acx.user,
+      _ => return Err("Unauthorized".into()),
+    };
+    let secret = totp::generate_secret();
+    let otpauth_uri = totp::otpauth_uri(&secret, TOTP_ISSUER, user.display_name.current.value.as_str());
+    Ok(TotpEnrollment { secret, otpauth_uri })
+  }
+
+  /// Enables TOTP for the current user, once they proved that their authenticator is configured.
+  ///
+  /// If TOTP is already enabled, a code from the current authenticator is required to replace it.
+  pub async fn confirm_totp_enrollment(
+    &self,
+    acx: &AuthContext,
+    options: &ConfirmTotpEnrollmentOptions,
+  ) -> Result<RecoveryCodes, AnyError> {
+    let user = match acx {
+      AuthContext::User(acx) => &acx.user,
+      _ => return Err("Unauthorized".into()),
+    };
+    if options.secret.0.len() != totp::SECRET_SIZE {
+      return Err("InvalidTotpSecret".into());
+    }
+    if let Some(current) = self.user_store.get_user_totp(user.id.into()).await? {
+      let current_code = match options.current_code.as_ref() {
+        Some(code) => code,
+        None => return Err("TotpAlreadyEnabled".into()),
+      };
+      if !self.consume_totp_code(&current, current_code).await? {
+        return Err("InvalidTotpCode".into());
+      }
+    }
+    let step = match totp::verify(&options.secret, &options.code, self.clock.now()) {
+      Some(step) => step,
+      None => return Err("InvalidTotpCode".into()),
+    };
+    let recovery_codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+      .map(|_| totp::generate_recovery_code())
+      .collect();
+    self
+      .user_store
+      .set_user_totp(&SetUserTotpOptions {
+        user: user.id.into(),
+        secret: options.secret.clone(),
+        recovery_codes: recovery_codes
+          .iter()
+          .map(|code| self.password_service.hash(Password::from(code.as_str())))
+          .collect(),
+      })
+      .await?;
+    // The confirmation code must not be usable a second time
+    self
+      .user_store
+      .touch_user_totp_step(&TouchUserTotpStepOptions {
+        user: user.id.into(),
+        step,
+      })
+      .await?;
+    self.logger.log(AuthEvent::TotpEnabled { user: user.id.into() });
+    Ok(RecoveryCodes { recovery_codes })
+  }
+
+  pub async fn disable_totp(&self, acx: &AuthContext, options: &DisableTotpOptions) -> Result<(), AnyError> {
+    let user = match acx {
+      AuthContext::User(acx) => &acx.user,
+      _ => return Err("Unauthorized".into()),
+    };
+    let totp = match self.user_store.get_user_totp(user.id.into()).await? {
+      Some(totp) => totp,
+      None => return Err("TotpNotEnabled".into()),
+    };
+    if !self.consume_totp_code(&totp, &options.code).await? {
+      return Err("InvalidTotpCode".into());
+    }
+    self.user_store.delete_user_totp(user.id.into()).await?;
+    self.logger.log(AuthEvent::TotpDisabled { user: user.id.into() });
+    Ok(())
+  }
+
   pub async fn register_or_login_with_dinoparc(
     &self,
     credentials: &DinoparcCredentials,
   ) -> Result<UserAndSession, AnyError> {
     let dparc_session = self.dinoparc_client.create_session(credentials).await?;
+    self.dinoparc_store.touch_short_user(&dparc_session.user).await?;
+    self
+      .token_store
+      .touch_dinoparc(dparc_session.user.as_ref(), &dparc_session.key)
+      .await?;
     let link = self
       .link_store
       .get_link_from_dinoparc(&GetLinkOptions {
@@ -594,7 +1185,7 @@ where
           password: None,
         })
         .await?;
-      self.dinoparc_store.touch_short_user(&dparc_session.user).await?;
+      self.logger.log(AuthEvent::UserRegistered { user: user.id.into() });
       self
         .link_store
         .touch_dinoparc_link(&TouchLinkOptions {
@@ -628,6 +1219,8 @@ where
       .await?
       .into_session(user.display_name.clone());
 
+    self.queue_archive(user.id, ArchiveRequest::Dinoparc(dparc_session));
+
     let is_administrator = user.is_administrator;
 
     Ok(UserAndSession {
@@ -642,6 +1235,11 @@ where
     credentials: &HammerfestCredentials,
   ) -> Result<UserAndSession, AnyError> {
     let hfest_session = self.hammerfest_client.create_session(credentials).await?;
+    self.hammerfest_store.touch_short_user(&hfest_session.user).await?;
+    self
+      .token_store
+      .touch_hammerfest(hfest_session.user.as_ref(), &hfest_session.key)
+      .await?;
     let link = self
       .link_store
       .get_link_from_hammerfest(&GetLinkOptions {
@@ -662,7 +1260,7 @@ where
           password: None,
         })
         .await?;
-      self.hammerfest_store.touch_short_user(&hfest_session.user).await?;
+      self.logger.log(AuthEvent::UserRegistered { user: user.id.into() });
       self
         .link_store
         .touch_hammerfest_link(&TouchLinkOptions {
@@ -696,6 +1294,8 @@ where
       .await?
       .into_session(user.display_name.clone());
 
+    self.queue_archive(user.id, ArchiveRequest::Hammerfest(hfest_session));
+
     let is_administrator = user.is_administrator;
 
     Ok(UserAndSession {
@@ -707,17 +1307,41 @@ where
 
   pub async fn register_or_login_with_twinoid_oauth(
     &self,
-    token: &RfcOauthAccessTokenKey,
+    token: &RfcOauthAccessToken,
   ) -> Result<UserAndSession, AnyError> {
+    let now = self.clock.now();
     let tid_user = self
       .twinoid_client
-      .get_me_short(TwinoidApiAuth::Token(token.clone()))
+      .get_me_short(TwinoidApiAuth::Token(token.access_token.clone()))
       .await?;
     let tid_user = ShortTwinoidUser {
       id: TwinoidUserId::new(tid_user.id)?,
       display_name: tid_user.name,
     };
     let tid_user_ref = TwinoidUserIdRef { id: tid_user.id };
+    self.twinoid_store.touch_short_user(&tid_user).await?;
+    // The full profile is only archived: failing to retrieve it must not prevent the login
+    if let Err(e) = self
+      .archive_twinoid_profile(TwinoidApiAuth::Token(token.access_token.clone()))
+      .await
+    {
+      self.logger.log(AuthEvent::TwinoidProfileArchiveFailed {
+        twinoid_user: tid_user_ref,
+        error: e.to_string(),
+      });
+    }
+    // Tokens can only be stored as a pair: without a refresh token, the access token is not persisted.
+    if let Some(refresh_token) = &token.refresh_token {
+      self
+        .token_store
+        .touch_twinoid_oauth(&TouchOauthTokenOptions {
+          access_token: token.access_token.clone(),
+          refresh_token: refresh_token.clone(),
+          expiration_time: now + Duration::seconds(token.expires_in),
+          twinoid_user_id: tid_user.id,
+        })
+        .await?;
+    }
     let link = self
       .link_store
       .get_link_from_twinoid(&GetLinkOptions {
@@ -738,7 +1362,7 @@ where
           password: None,
         })
         .await?;
-      self.twinoid_store.touch_short_user(&tid_user).await?;
+      self.logger.log(AuthEvent::UserRegistered { user: user.id.into() });
       self
         .link_store
         .touch_twinoid_link(&TouchLinkOptions {
@@ -781,6 +1405,27 @@ where
     })
   }
 
+  fn queue_archive(&self, user: UserId, request: ArchiveRequest) {
+    if let Some(archive_queue) = self.archive_queue.as_ref() {
+      if let Err(e) = archive_queue.push(request) {
+        self.logger.log(AuthEvent::ArchiveOnLoginQueueFailed {
+          user: user.into(),
+          error: e.to_string(),
+        });
+      }
+    }
+  }
+
+  async fn archive_twinoid_profile(&self, auth: TwinoidApiAuth) -> Result<(), AnyError> {
+    let user = self
+      .twinoid_client
+      .get_me_fields(auth, &TwinoidUserProfile::fields())
+      .await?;
+    let profile = TwinoidUserProfile::try_from(user)?;
+    self.twinoid_store.touch_user_profile(&profile).await?;
+    Ok(())
+  }
+
   pub async fn authenticate_session(&self, session: SessionId) -> Result<Option<UserAndSession>, AnyError> {
     let session = self.auth_store.get_and_touch_session(session).await?;
     let session = match session {
@@ -814,15 +1459,29 @@ where
     }))
   }
 
-  pub async fn raw_authenticate_credentials(&self, credentials: &RawCredentials) -> Result<AuthContext, AnyError> {
+  pub async fn raw_authenticate_credentials(
+    &self,
+    credentials: &RawCredentials,
+    ip: Option<IpAddr>,
+  ) -> Result<AuthContext, AnyError> {
     let credentials = Credentials {
-      login: credentials.login.parse().map_err(|()| AnyError::from("BadLogin"))?,
+      login: credentials
+        .login
+        .parse()
+        .map_err(|()| invalid_credentials("BadLogin"))?,
       password: credentials.password.clone(),
     };
-    self.authenticate_credentials(credentials).await
+    self.authenticate_credentials(credentials, ip).await
   }
 
-  pub async fn authenticate_credentials(&self, credentials: Credentials) -> Result<AuthContext, AnyError> {
+  /// Authenticates HTTP Basic credentials, for users or OAuth clients.
+  ///
+  /// Failed attempts are throttled the same way as `login_with_credentials`.
+  pub async fn authenticate_credentials(
+    &self,
+    credentials: Credentials,
+    ip: Option<IpAddr>,
+  ) -> Result<AuthContext, AnyError> {
     fn from_user(user: SimpleUser) -> AuthContext {
       let is_administrator = user.is_administrator;
       AuthContext::User(UserAuthContext {
@@ -842,48 +1501,60 @@ where
     match credentials.login {
       Login::EmailAddress(email) => {
         let user = self
-          .authenticate_user_with_password(UserRef::Email(UserEmailRef { email }), credentials.password)
+          .authenticate_user_with_password_only(UserRef::Email(UserEmailRef { email }), credentials.password, ip)
           .await?;
         Ok(from_user(user))
       }
       Login::Username(username) => {
         let user = self
-          .authenticate_user_with_password(UserRef::Username(UserUsernameRef { username }), credentials.password)
+          .authenticate_user_with_password_only(
+            UserRef::Username(UserUsernameRef { username }),
+            credentials.password,
+            ip,
+          )
           .await?;
         Ok(from_user(user))
       }
       Login::UserId(user_id) => {
         let user = self
-          .authenticate_user_with_password(UserRef::Id(user_id.into()), credentials.password)
+          .authenticate_user_with_password_only(UserRef::Id(user_id.into()), credentials.password, ip)
           .await?;
         Ok(from_user(user))
       }
       Login::OauthClientId(client_id) => {
         let client = self
-          .authenticate_oauth_client(OauthClientRef::Id(client_id.into()), credentials.password)
+          .authenticate_oauth_client(OauthClientRef::Id(client_id.into()), credentials.password, ip)
           .await?;
         Ok(from_client(client))
       }
       Login::OauthClientKey(client_key) => {
         let client = self
-          .authenticate_oauth_client(OauthClientRef::Key(client_key.into()), credentials.password)
+          .authenticate_oauth_client(OauthClientRef::Key(client_key.into()), credentials.password, ip)
           .await?;
         Ok(from_client(client))
       }
       Login::UntypedUuid(id) => {
+        // Resolve the kind of subject first, so that a failed attempt is recorded only once
         let user_id = UserId::from(id);
-        let client_id = OauthClientId::from(id);
-        // TODO: Race both
         let user = self
-          .authenticate_user_with_password(UserRef::Id(user_id.into()), credentials.password.clone())
-          .await;
-        let client = self
-          .authenticate_oauth_client(OauthClientRef::Id(client_id.into()), credentials.password)
-          .await;
-        match (user, client) {
-          (Ok(user), _) => Ok(from_user(user)),
-          (_, Ok(client)) => Ok(from_client(client)),
-          (Err(_), Err(_)) => Err("UserOrClientNotFound".into()),
+          .user_store
+          .get_user(&GetUserOptions {
+            r#ref: UserRef::Id(user_id.into()),
+            fields: UserFields::Short,
+            time: None,
+          })
+          .await?;
+        if user.is_some() {
+          let user = self
+            .authenticate_user_with_password_only(UserRef::Id(user_id.into()), credentials.password, ip)
+            .await?;
+          Ok(from_user(user))
+        } else {
+          let client_id = OauthClientId::from(id);
+          let client = self
+            .authenticate_oauth_client(OauthClientRef::Id(client_id.into()), credentials.password, ip)
+            .await?;
+          Ok(from_client(client))
         }
       }
     }
@@ -893,7 +1564,10 @@ where
     &self,
     user_ref: UserRef,
     password: Password,
+    ip: Option<IpAddr>,
   ) -> Result<SimpleUser, AnyError> {
+    let ip_key = ip.map(LoginThrottleKey::Ip);
+    self.check_login_throttle(ip_key).await?;
     let user_with_password = self
       .user_store
       .get_user_with_password(&GetUserOptions {
@@ -905,15 +1579,49 @@ where
     let user_with_password = if let Some(user_with_password) = user_with_password {
       user_with_password
     } else {
-      return Err("UserNotFound".into());
+      self
+        .record_login_failure(None, None, ip, LoginFailureReason::UnknownLogin)
+        .await?;
+      return Err(invalid_credentials("UserNotFound"));
+    };
+    let user_ref = UserIdRef {
+      id: user_with_password.id,
     };
+    self
+      .check_login_throttle(Some(LoginThrottleKey::User(user_ref)))
+      .await?;
     let password_hash = match user_with_password.password {
       Some(password_hash) => password_hash,
-      None => return Err("NoPassword".into()),
+      None => {
+        self
+          .record_login_failure(Some(user_ref), None, ip, LoginFailureReason::NoPassword)
+          .await?;
+        return Err(invalid_credentials("NoPassword"));
+      }
     };
-    let is_match = self.password_service.verify(password_hash, password);
+    let is_match = self.password_service.verify(password_hash.clone(), password.clone());
     if !is_match {
-      return Err("WrongPassword".into());
+      self
+        .record_login_failure(Some(user_ref), None, ip, LoginFailureReason::WrongPassword)
+        .await?;
+      return Err(invalid_credentials("WrongPassword"));
+    }
+    if self.password_service.needs_rehash(&password_hash) {
+      // Best effort: the upgrade is retried on the next login if it fails
+      let res = self
+        .user_store
+        .replace_user_password_hash(&ReplaceUserPasswordHashOptions {
+          r#ref: user_ref,
+          old: password_hash,
+          new: self.password_service.hash(password),
+        })
+        .await;
+      if let Err(e) = res {
+        self.logger.log(AuthEvent::PasswordRehashFailed {
+          user: user_ref,
+          error: e.to_string(),
+        });
+      }
     }
 
     let user: Option<GetUserResult> = self
@@ -937,20 +1645,58 @@ where
     Ok(user)
   }
 
+  /// Password authentication for contexts without a second login step (e.g. HTTP Basic auth).
+  ///
+  /// Users with a second factor are rejected.
+  async fn authenticate_user_with_password_only(
+    &self,
+    user_ref: UserRef,
+    password: Password,
+    ip: Option<IpAddr>,
+  ) -> Result<SimpleUser, AnyError> {
+    let user = self.authenticate_user_with_password(user_ref, password, ip).await?;
+    if self.user_store.get_user_totp(user.id.into()).await?.is_some() {
+      return Err(invalid_credentials("MfaRequired"));
+    }
+    Ok(user)
+  }
+
   async fn authenticate_oauth_client(
     &self,
     oauth_client_ref: OauthClientRef,
     secret: Password,
+    ip: Option<IpAddr>,
   ) -> Result<SimpleOauthClient, AnyError> {
+    self.check_login_throttle(ip.map(LoginThrottleKey::Ip)).await?;
     let client_with_secret = self
       .oauth_provider_store
       .get_client_with_secret(&GetOauthClientOptions {
         r#ref: oauth_client_ref,
       })
+      .await;
+    let client_with_secret = match client_with_secret {
+      Ok(client_with_secret) => client_with_secret,
+      // The store reports unknown clients with a `NotFound` error, anything else is an internal failure
+      Err(e) if e.to_string() == "NotFound" => {
+        self
+          .record_login_failure(None, None, ip, LoginFailureReason::UnknownLogin)
+          .await?;
+        return Err(invalid_credentials("NotFound"));
+      }
+      Err(e) => return Err(e),
+    };
+    let client_ref = OauthClientIdRef {
+      id: client_with_secret.id,
+    };
+    self
+      .check_login_throttle(Some(LoginThrottleKey::OauthClient(client_ref)))
       .await?;
     let is_match = self.password_service.verify(client_with_secret.secret, secret);
     if !is_match {
-      return Err("WrongSecret".into());
+      self
+        .record_login_failure(None, Some(client_ref), ip, LoginFailureReason::WrongPassword)
+        .await?;
+      return Err(invalid_credentials("WrongSecret"));
     }
 
     Ok(SimpleOauthClient {
@@ -963,6 +1709,162 @@ where
     })
   }
 
+  /// Fails with `LoginThrottledError` if the key is currently locked out.
+  async fn check_login_throttle(&self, key: Option<LoginThrottleKey>) -> Result<(), AnyError> {
+    let key = match key {
+      Some(key) => key,
+      None => return Ok(()),
+    };
+    match self.auth_store.get_login_lockout(key).await? {
+      Some(retry_after) => {
+        self.logger.log(AuthEvent::LoginThrottled { key, retry_after });
+        Err(Box::new(LoginThrottledError { retry_after }))
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Records a failed password check, and locks out the subjects which reached their limit in the sliding window.
+  async fn record_login_failure(
+    &self,
+    user: Option<UserIdRef>,
+    oauth_client: Option<OauthClientIdRef>,
+    ip: Option<IpAddr>,
+    reason: LoginFailureReason,
+  ) -> Result<(), AnyError> {
+    self
+      .auth_store
+      .create_login_failure(&CreateLoginFailureOptions {
+        user,
+        oauth_client,
+        ip,
+        reason,
+      })
+      .await?;
+    self.logger.log(AuthEvent::LoginFailed {
+      user,
+      oauth_client,
+      ip,
+      reason,
+    });
+
+    let now = self.clock.now();
+    let limits = [
+      user.map(|user| (LoginThrottleKey::User(user), self.max_user_login_failures)),
+      oauth_client.map(|client| {
+        (
+          LoginThrottleKey::OauthClient(client),
+          self.max_oauth_client_login_failures,
+        )
+      }),
+      ip.map(|ip| (LoginThrottleKey::Ip(ip), self.max_ip_login_failures)),
+    ];
+    for (key, max_failures) in limits.into_iter().flatten() {
+      let failures = self
+        .auth_store
+        .count_login_failures(&CountLoginFailuresOptions {
+          key,
+          since: now - self.login_throttle_window,
+        })
+        .await?;
+      if failures >= max_failures {
+        let until = now + self.login_lockout_duration;
+        self
+          .auth_store
+          .create_login_lockout(&CreateLoginLockoutOptions { key, until })
+          .await?;
+        self.logger.log(AuthEvent::LoginLockedOut { key, until });
+      }
+    }
+    Ok(())
+  }
+
+  /// Returns `false` if the code is invalid or its time step was already used.
+  async fn consume_totp_code(&self, totp: &UserTotp, code: &TotpCode) -> Result<bool, AnyError> {
+    let step = match totp::verify(&totp.secret, code, self.clock.now()) {
+      Some(step) => step,
+      None => return Ok(false),
+    };
+    self
+      .user_store
+      .touch_user_totp_step(&TouchUserTotpStepOptions { user: totp.user, step })
+      .await
+  }
+
+  /// Returns `false` if the code does not match any of the remaining recovery codes.
+  async fn consume_recovery_code(&self, totp: &UserTotp, code: &RecoveryCode) -> Result<bool, AnyError> {
+    let hash = totp
+      .recovery_codes
+      .iter()
+      .find(|hash| {
+        self
+          .password_service
+          .verify((*hash).clone(), Password::from(code.as_str()))
+      })
+      .cloned();
+    let hash = match hash {
+      Some(hash) => hash,
+      None => return Ok(false),
+    };
+    self
+      .user_store
+      .delete_user_recovery_code(&DeleteUserRecoveryCodeOptions {
+        user: totp.user,
+        code: hash,
+      })
+      .await
+  }
+
+  async fn create_mfa_login_token(&self, user: UserId) -> Result<(String, Instant), AnyError> {
+    let now = self.clock.now();
+    let expires_at = now + self.mfa_login_validity;
+    let challenge = self
+      .auth_store
+      .create_mfa_login_challenge(&CreateMfaLoginChallengeOptions {
+        user: user.into(),
+        expires_at,
+      })
+      .await?;
+
+    let claims = MfaLoginJwtClaims {
+      exp: expires_at.into_posix_timestamp(),
+      iat: now.into_posix_timestamp(),
+      sub: user,
+      amr: vec![String::from("pwd")],
+      jti: challenge.id,
+    };
+
+    let key = jsonwebtoken::EncodingKey::from_secret(self.jwt_secret_key.as_slice());
+
+    let token = jsonwebtoken::encode(
+      &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+      &claims,
+      &key,
+    )?;
+    Ok((token, expires_at))
+  }
+
+  fn read_mfa_login_token(&self, token: &str) -> Result<MfaLoginJwtClaims, AnyError> {
+    let now = self.clock.now().into_posix_timestamp();
+    let key = jsonwebtoken::DecodingKey::from_secret(self.jwt_secret_key.as_slice());
+    let validation = jsonwebtoken::Validation {
+      leeway: 0,
+      validate_exp: false,
+      validate_nbf: false,
+      aud: None,
+      iss: None,
+      sub: None,
+      algorithms: vec![jsonwebtoken::Algorithm::HS256],
+    };
+
+    let token = jsonwebtoken::decode::<MfaLoginJwtClaims>(token, &key, &validation)?;
+    if !(token.claims.iat <= now && now < token.claims.exp) {
+      return Err("TokenIsNotValidAtThisTime".into());
+    }
+
+    Ok(token.claims)
+  }
+
   fn create_email_verification_token(&self, email: &EmailAddress) -> Result<String, AnyError> {
     let now = self.clock.now();
     let expires_at = now + self.email_verification_validity;
@@ -970,6 +1872,7 @@ where
     let claims = EmailJwtClaims {
       exp: expires_at.into_posix_timestamp(),
       iat: now.into_posix_timestamp(),
+      aud: None,
       email: email.clone(),
     };
 
@@ -997,6 +1900,63 @@ where
     };
 
     let token = jsonwebtoken::decode::<EmailJwtClaims>(token, &key, &validation)?;
+    if token.claims.aud.is_some() {
+      return Err("InvalidTokenAudience".into());
+    }
+    if !(token.claims.iat <= now && now < token.claims.exp) {
+      return Err("TokenIsNotValidAtThisTime".into());
+    }
+
+    Ok(token.claims)
+  }
+
+  fn create_email_change_token(
+    &self,
+    user: UserId,
+    email: &EmailAddress,
+    old_email: Option<&EmailAddress>,
+    locale: LocaleId,
+  ) -> Result<String, AnyError> {
+    let now = self.clock.now();
+    let expires_at = now + self.email_verification_validity;
+
+    let claims = EmailChangeJwtClaims {
+      aud: EMAIL_CHANGE_AUDIENCE.to_string(),
+      exp: expires_at.into_posix_timestamp(),
+      iat: now.into_posix_timestamp(),
+      sub: user,
+      email: email.clone(),
+      old_email: old_email.cloned(),
+      locale: Some(locale),
+    };
+
+    let key = jsonwebtoken::EncodingKey::from_secret(self.jwt_secret_key.as_slice());
+
+    let token = jsonwebtoken::encode(
+      &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+      &claims,
+      &key,
+    )?;
+    Ok(token)
+  }
+
+  fn read_email_change_token(&self, token: &str) -> Result<EmailChangeJwtClaims, AnyError> {
+    let now = self.clock.now().into_posix_timestamp();
+    let key = jsonwebtoken::DecodingKey::from_secret(self.jwt_secret_key.as_slice());
+    let validation = jsonwebtoken::Validation {
+      leeway: 0,
+      validate_exp: false,
+      validate_nbf: false,
+      aud: None,
+      iss: None,
+      sub: None,
+      algorithms: vec![jsonwebtoken::Algorithm::HS256],
+    };
+
+    let token = jsonwebtoken::decode::<EmailChangeJwtClaims>(token, &key, &validation)?;
+    if token.claims.aud != EMAIL_CHANGE_AUDIENCE {
+      return Err("InvalidTokenAudience".into());
+    }
     if !(token.claims.iat <= now && now < token.claims.exp) {
       return Err("TokenIsNotValidAtThisTime".into());
     }
@@ -1084,9 +2044,11 @@ impl<
     TyHammerfestClient,
     TyHammerfestStore,
     TyLinkStore,
+    TyLogger,
     TyMailer,
     TyOauthProviderStore,
     TyPasswordService,
+    TyTokenStore,
     TyTwinoidClient,
     TyTwinoidStore,
     TyUserStore,
@@ -1101,9 +2063,11 @@ impl<
     TyHammerfestClient,
     TyHammerfestStore,
     TyLinkStore,
+    TyLogger,
     TyMailer,
     TyOauthProviderStore,
     TyPasswordService,
+    TyTokenStore,
     TyTwinoidClient,
     TyTwinoidStore,
     TyUserStore,
@@ -1118,9 +2082,11 @@ where
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<AuthEvent>,
   TyMailer: Mailer,
   TyOauthProviderStore: OauthProviderStore,
   TyPasswordService: PasswordService,
+  TyTokenStore: TokenStore,
   TyTwinoidClient: TwinoidClient,
   TyTwinoidStore: TwinoidStore,
   TyUserStore: UserStore,
@@ -1128,6 +2094,10 @@ where
 {
 }
 
+fn invalid_credentials(reason: &'static str) -> AnyError {
+  Box::new(InvalidCredentialsError { reason })
+}
+
 trait DeriveUserDisplayName {
   fn derive_user_display_name(&self) -> UserDisplayName;
 }
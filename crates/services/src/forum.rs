@@ -3,19 +3,23 @@ use etwin_core::clock::Clock;
 use etwin_core::core::Listing;
 use etwin_core::forum::{
   AddModeratorOptions, CreatePostError, CreatePostOptions, CreateThreadOptions, DeleteModeratorOptions,
-  DeletePostError, DeletePostOptions, ForumActor, ForumPost, ForumPostListing, ForumPostRevision, ForumRole,
-  ForumRoleGrant, ForumSection, ForumSectionListing, ForumSectionMeta, ForumSectionSelf, ForumStore, ForumThread,
-  ForumThreadMetaWithSection, GetForumSectionMetaOptions, GetForumSectionOptions, GetThreadOptions,
-  LatestForumPostRevisionListing, RawAddModeratorOptions, RawCreatePostOptions, RawCreateThreadsOptions, RawForumActor,
-  RawForumSectionMeta, RawForumThreadMeta, RawGetForumThreadMetaOptions, RawGetPostsOptions, RawGetSectionsOptions,
-  RawGetThreadsOptions, ShortForumPost, UpsertSystemSectionError, UpsertSystemSectionOptions, UserForumActor,
+  DeletePostError, DeletePostOptions, ForumActor, ForumPost, ForumPostId, ForumPostListing, ForumPostRevision,
+  ForumRole, ForumRoleGrant, ForumSection, ForumSectionId, ForumSectionListing, ForumSectionMeta, ForumSectionSelf,
+  ForumStore, ForumThread, ForumThreadId, ForumThreadMetaWithSection, GetForumSectionMetaOptions,
+  GetForumSectionOptions, GetSectionMetaError, GetThreadMetaError, GetThreadOptions, LatestForumPostRevisionListing,
+  RawAddModeratorOptions, RawCreatePostOptions, RawCreateThreadsOptions, RawForumActor, RawForumSectionMeta,
+  RawForumThreadMeta, RawGetForumThreadMetaOptions, RawGetPostsOptions, RawGetSectionsOptions, RawGetThreadsOptions,
+  ShortForumPost, UpsertSystemSectionError, UpsertSystemSectionOptions, UserForumActor,
 };
 use etwin_core::types::AnyError;
-use etwin_core::user::{GetShortUserOptions, ShortUser, UserStore};
+use etwin_core::user::{GetShortUserOptions, ShortUser, UserId, UserStore};
+use etwin_log::Logger;
 use marktwin::emitter::emit_html;
 use marktwin::grammar::Grammar;
+use serde::Serialize;
 use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -74,29 +78,62 @@ pub enum GetThreadError {
   Other(AnyError),
 }
 
-pub struct ForumService<TyClock, TyForumStore, TyUserStore>
+/// Event emitted by the [`ForumService`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum ForumEvent {
+  /// A user created a thread, with its first post
+  ThreadCreated {
+    section: ForumSectionId,
+    thread: ForumThreadId,
+    post: ForumPostId,
+    author: UserId,
+  },
+  /// A user created a post in an existing thread
+  PostCreated {
+    section: ForumSectionId,
+    thread: ForumThreadId,
+    post: ForumPostId,
+    author: UserId,
+  },
+  /// An administrator granted the moderator role for a section
+  ModeratorAdded {
+    section: ForumSectionId,
+    grantee: UserId,
+    granter: UserId,
+  },
+}
+
+pub struct ForumService<TyClock, TyForumStore, TyLogger, TyUserStore>
 where
   TyClock: Clock,
   TyForumStore: ForumStore,
+  TyLogger: Logger<ForumEvent>,
   TyUserStore: UserStore,
 {
   #[allow(unused)]
   clock: TyClock,
   forum_store: TyForumStore,
+  logger: TyLogger,
   user_store: TyUserStore,
 }
 
-impl<TyClock, TyForumStore, TyUserStore> ForumService<TyClock, TyForumStore, TyUserStore>
+pub type DynForumService =
+  ForumService<Arc<dyn Clock>, Arc<dyn ForumStore>, Arc<dyn Logger<ForumEvent>>, Arc<dyn UserStore>>;
+
+impl<TyClock, TyForumStore, TyLogger, TyUserStore> ForumService<TyClock, TyForumStore, TyLogger, TyUserStore>
 where
   TyClock: Clock,
   TyForumStore: ForumStore,
+  TyLogger: Logger<ForumEvent>,
   TyUserStore: UserStore,
 {
   #[allow(clippy::too_many_arguments)]
-  pub fn new(clock: TyClock, forum_store: TyForumStore, user_store: TyUserStore) -> Self {
+  pub fn new(clock: TyClock, forum_store: TyForumStore, logger: TyLogger, user_store: TyUserStore) -> Self {
     Self {
       clock,
       forum_store,
+      logger,
       user_store,
     }
   }
@@ -118,6 +155,17 @@ where
       .map_err(AddModeratorError::Other)?;
     let grantee: ShortUser = grantee.ok_or(AddModeratorError::GranteeNotFound)?;
 
+    let section_meta = self
+      .forum_store
+      .get_section_meta(&GetForumSectionMetaOptions {
+        section: options.section.clone(),
+      })
+      .await
+      .map_err(|e| match e {
+        GetSectionMetaError::NotFound => AddModeratorError::SectionNotFound,
+        e => AddModeratorError::Other(Box::new(e)),
+      })?;
+
     self
       .forum_store
       .add_moderator(&RawAddModeratorOptions {
@@ -127,6 +175,11 @@ where
       })
       .await
       .map_err(AddModeratorError::Other)?;
+    self.logger.log(ForumEvent::ModeratorAdded {
+      section: section_meta.id,
+      grantee: grantee.id,
+      granter: granter.id,
+    });
     let section = self
       .get_section(
         acx,
@@ -159,9 +212,18 @@ where
         role: None,
         user: user.user.clone(),
       }),
-      AuthContext::Guest(_) => return Err(CreateThreadError::Forbidden),
-      _ => todo!(),
+      _ => return Err(CreateThreadError::Forbidden),
     };
+    self
+      .forum_store
+      .get_section_meta(&GetForumSectionMetaOptions {
+        section: options.section.clone(),
+      })
+      .await
+      .map_err(|e| match e {
+        GetSectionMetaError::NotFound => CreateThreadError::SectionNotFound,
+        e => CreateThreadError::Other(Box::new(e)),
+      })?;
     let grammar = Grammar {
       admin: false,
       depth: Some(4),
@@ -198,6 +260,14 @@ where
       })
       .await
       .map_err(CreateThreadError::Other)?;
+    if let AuthContext::User(acx) = acx {
+      self.logger.log(ForumEvent::ThreadCreated {
+        section: thread.section.id,
+        thread: thread.id,
+        post: thread.post_id,
+        author: acx.user.id,
+      });
+    }
 
     let section = self
       .forum_store
@@ -278,9 +348,18 @@ where
         role: None,
         user: user.user.clone(),
       }),
-      AuthContext::Guest(_) => return Err(CreatePostError::Forbidden),
-      _ => todo!(),
+      _ => return Err(CreatePostError::Forbidden),
     };
+    self
+      .forum_store
+      .get_thread_meta(&RawGetForumThreadMetaOptions {
+        thread: options.thread.clone(),
+      })
+      .await
+      .map_err(|e| match e {
+        GetThreadMetaError::NotFound => CreatePostError::ThreadNotFound,
+        e => CreatePostError::Other(Box::new(e)),
+      })?;
     let grammar = Grammar {
       admin: false,
       depth: Some(4),
@@ -316,6 +395,14 @@ where
       })
       .await
       .map_err(CreatePostError::Other)?;
+    if let AuthContext::User(acx) = acx {
+      self.logger.log(ForumEvent::PostCreated {
+        section: post.section.id,
+        thread: post.thread.id,
+        post: post.id,
+        author: acx.user.id,
+      });
+    }
 
     let thread: RawForumThreadMeta = self
       .forum_store
@@ -462,7 +549,10 @@ where
         section: options.section.clone(),
       })
       .await
-      .map_err(|e| GetSectionError::Other(Box::new(e)))?;
+      .map_err(|e| match e {
+        GetSectionMetaError::NotFound => GetSectionError::SectionNotFound,
+        e => GetSectionError::Other(Box::new(e)),
+      })?;
     let threads = self
       .forum_store
       .get_threads(&RawGetThreadsOptions {
@@ -534,7 +624,10 @@ where
         thread: options.thread.clone(),
       })
       .await
-      .map_err(|e| GetThreadError::Other(Box::new(e)))?;
+      .map_err(|e| match e {
+        GetThreadMetaError::NotFound => GetThreadError::ThreadNotFound,
+        e => GetThreadError::Other(Box::new(e)),
+      })?;
     let posts = self
       .forum_store
       .get_posts(&RawGetPostsOptions {
@@ -677,10 +770,12 @@ where
 }
 
 #[cfg(feature = "neon")]
-impl<TyClock, TyForumStore, TyUserStore> neon::prelude::Finalize for ForumService<TyClock, TyForumStore, TyUserStore>
+impl<TyClock, TyForumStore, TyLogger, TyUserStore> neon::prelude::Finalize
+  for ForumService<TyClock, TyForumStore, TyLogger, TyUserStore>
 where
   TyClock: Clock,
   TyForumStore: ForumStore,
+  TyLogger: Logger<ForumEvent>,
   TyUserStore: UserStore,
 {
 }
@@ -1,55 +1,84 @@
 use etwin_core::auth::AuthContext;
 use etwin_core::core::UserDot;
 use etwin_core::hammerfest::{
-  GetHammerfestUserOptions, HammerfestClient, HammerfestGetProfileByIdOptions, HammerfestProfile, HammerfestStore,
-  HammerfestUser, HammerfestUserIdRef, StoredHammerfestUser,
+  GetHammerfestUserOptions, HammerfestClient, HammerfestGetProfileByIdOptions, HammerfestProfile, HammerfestServer,
+  HammerfestStore, HammerfestUser, HammerfestUserId, HammerfestUserIdRef, StoredHammerfestUser,
 };
 use etwin_core::link::{EtwinLink, GetLinkOptions, LinkStore, VersionedEtwinLink, VersionedRawLink};
 use etwin_core::user::{GetShortUserOptions, ShortUser, UserRef, UserStore};
+use etwin_log::Logger;
+use serde::Serialize;
 use std::error::Error;
 use std::sync::Arc;
 
-pub struct HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>
+/// Event emitted by the [`HammerfestService`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum HammerfestEvent {
+  /// A profile missing from the store was fetched from Hammerfest
+  ProfileFetched {
+    server: HammerfestServer,
+    user: HammerfestUserId,
+  },
+  /// Hammerfest could not be queried for a profile
+  ProfileFetchFailed {
+    server: HammerfestServer,
+    user: HammerfestUserId,
+    error: String,
+  },
+}
+
+pub struct HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyLogger, TyUserStore>
 where
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<HammerfestEvent>,
   TyUserStore: UserStore,
 {
   hammerfest_client: TyHammerfestClient,
   hammerfest_store: TyHammerfestStore,
   link_store: TyLinkStore,
+  logger: TyLogger,
   user_store: TyUserStore,
 }
 
-pub type DynHammerfestService =
-  HammerfestService<Arc<dyn HammerfestClient>, Arc<dyn HammerfestStore>, Arc<dyn LinkStore>, Arc<dyn UserStore>>;
+pub type DynHammerfestService = HammerfestService<
+  Arc<dyn HammerfestClient>,
+  Arc<dyn HammerfestStore>,
+  Arc<dyn LinkStore>,
+  Arc<dyn Logger<HammerfestEvent>>,
+  Arc<dyn UserStore>,
+>;
 
-impl<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>
-  HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>
+impl<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyLogger, TyUserStore>
+  HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyLogger, TyUserStore>
 where
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<HammerfestEvent>,
   TyUserStore: UserStore,
 {
   pub fn new(
     hammerfest_client: TyHammerfestClient,
     hammerfest_store: TyHammerfestStore,
     link_store: TyLinkStore,
+    logger: TyLogger,
     user_store: TyUserStore,
   ) -> Self {
     Self {
       hammerfest_client,
       hammerfest_store,
       link_store,
+      logger,
       user_store,
     }
   }
 
   pub async fn get_user(
     &self,
-    _acx: &AuthContext,
+    acx: &AuthContext,
     options: &GetHammerfestUserOptions,
   ) -> Result<Option<HammerfestUser>, Box<dyn Error + Send + Sync + 'static>> {
     let user: Option<StoredHammerfestUser> = self.hammerfest_store.get_user(options).await?;
@@ -61,11 +90,25 @@ where
             server: options.server,
             user_id: options.id,
           };
-          self.hammerfest_client.get_profile_by_id(None, &options).await?.profile
+          match self.hammerfest_client.get_profile_by_id(None, &options).await {
+            Ok(response) => response.profile,
+            Err(e) => {
+              self.logger.log(HammerfestEvent::ProfileFetchFailed {
+                server: options.server,
+                user: options.user_id,
+                error: e.to_string(),
+              });
+              return Err(e);
+            }
+          }
         };
         match profile {
           Some(profile) => {
             let user = profile.user;
+            self.logger.log(HammerfestEvent::ProfileFetched {
+              server: user.server,
+              user: user.id,
+            });
             self.hammerfest_store.touch_short_user(&user).await?
           }
           None => return Ok(None),
@@ -114,6 +157,19 @@ where
       };
       VersionedEtwinLink { current, old: vec![] }
     };
+    let is_owner = match (acx, etwin_link.current.as_ref()) {
+      (AuthContext::User(acx), Some(link)) => acx.user.id == link.etwin.id,
+      _ => false,
+    };
+    let private = if is_owner {
+      let user = HammerfestUserIdRef {
+        server: user.server,
+        id: user.id,
+      };
+      Some(self.hammerfest_store.get_private_data(user).await?)
+    } else {
+      None
+    };
     let hf_user = HammerfestUser {
       server: user.server,
       id: user.id,
@@ -122,18 +178,20 @@ where
       profile: user.profile,
       items: user.items,
       etwin: etwin_link,
+      private,
     };
     Ok(Some(hf_user))
   }
 }
 
 #[cfg(feature = "neon")]
-impl<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore> neon::prelude::Finalize
-  for HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>
+impl<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyLogger, TyUserStore> neon::prelude::Finalize
+  for HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyLogger, TyUserStore>
 where
   TyHammerfestClient: HammerfestClient,
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
+  TyLogger: Logger<HammerfestEvent>,
   TyUserStore: UserStore,
 {
 }
@@ -1,4 +1,10 @@
+pub mod archive;
+pub mod archive_refresh;
 pub mod auth;
 pub mod dinoparc;
 pub mod forum;
 pub mod hammerfest;
+pub mod link;
+pub mod outbox;
+pub mod totp;
+pub mod twinoid_oauth;
@@ -0,0 +1,353 @@
+use etwin_core::auth::AuthContext;
+use etwin_core::dinoparc::{DinoparcClient, DinoparcStore, DinoparcUserIdRef};
+use etwin_core::hammerfest::{HammerfestClient, HammerfestStore, HammerfestUserIdRef};
+use etwin_core::link::{
+  DeleteLinkError, DeleteLinkOptions, LinkStore, LinkToDinoparcOptions, LinkToHammerfestOptions,
+  LinkToTwinoidOauthOptions, RemoteUserIdRef, TouchLinkError, TouchLinkOptions, UnlinkOptions, VersionedRawLink,
+};
+use etwin_core::twinoid::{
+  ShortTwinoidUser, TwinoidApiAuth, TwinoidClient, TwinoidStore, TwinoidUserId, TwinoidUserIdRef,
+};
+use etwin_core::types::AnyError;
+use etwin_core::user::{GetShortUserOptions, UserId, UserIdRef, UserRef, UserStore};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LinkError<T: RemoteUserIdRef> {
+  #[error("current actor does not have the permission to manage the links of this user")]
+  Forbidden,
+  #[error("user not found")]
+  UserNotFound,
+  #[error("failed to authenticate as the remote user")]
+  RemoteAuthentication(#[source] AnyError),
+  #[error("cannot link as the remote user is already linked to the etwin user {0:?}")]
+  ConflictEtwin(UserIdRef),
+  #[error("cannot link as the etwin user is already linked to the remote user {0:?}")]
+  ConflictRemote(T),
+  #[error("cannot link as the remote user is already linked to the etwin user {0:?} and the etwin user is already linked to the remote user {1:?}")]
+  ConflictBoth(UserIdRef, T),
+  #[error(transparent)]
+  Other(AnyError),
+}
+
+impl<T: RemoteUserIdRef> From<TouchLinkError<T>> for LinkError<T> {
+  fn from(e: TouchLinkError<T>) -> Self {
+    match e {
+      TouchLinkError::ConflictEtwin(etwin) => Self::ConflictEtwin(etwin),
+      TouchLinkError::ConflictRemote(remote) => Self::ConflictRemote(remote),
+      TouchLinkError::ConflictBoth(etwin, remote) => Self::ConflictBoth(etwin, remote),
+      TouchLinkError::Other(e) => Self::Other(e),
+    }
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum UnlinkError<T: RemoteUserIdRef> {
+  #[error("current actor does not have the permission to manage the links of this user")]
+  Forbidden,
+  #[error("link not found for the etwin user {0:?} and remote {1:?}")]
+  NotFound(UserIdRef, T),
+  #[error(transparent)]
+  Other(AnyError),
+}
+
+impl<T: RemoteUserIdRef> From<DeleteLinkError<T>> for UnlinkError<T> {
+  fn from(e: DeleteLinkError<T>) -> Self {
+    match e {
+      DeleteLinkError::NotFound(etwin, remote) => Self::NotFound(etwin, remote),
+      DeleteLinkError::Other(e) => Self::Other(e),
+    }
+  }
+}
+
+pub struct LinkService<
+  TyDinoparcClient,
+  TyDinoparcStore,
+  TyHammerfestClient,
+  TyHammerfestStore,
+  TyLinkStore,
+  TyTwinoidClient,
+  TyTwinoidStore,
+  TyUserStore,
+> where
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyLinkStore: LinkStore,
+  TyTwinoidClient: TwinoidClient,
+  TyTwinoidStore: TwinoidStore,
+  TyUserStore: UserStore,
+{
+  dinoparc_client: TyDinoparcClient,
+  dinoparc_store: TyDinoparcStore,
+  hammerfest_client: TyHammerfestClient,
+  hammerfest_store: TyHammerfestStore,
+  link_store: TyLinkStore,
+  twinoid_client: TyTwinoidClient,
+  twinoid_store: TyTwinoidStore,
+  user_store: TyUserStore,
+}
+
+pub type DynLinkService = LinkService<
+  Arc<dyn DinoparcClient>,
+  Arc<dyn DinoparcStore>,
+  Arc<dyn HammerfestClient>,
+  Arc<dyn HammerfestStore>,
+  Arc<dyn LinkStore>,
+  Arc<dyn TwinoidClient>,
+  Arc<dyn TwinoidStore>,
+  Arc<dyn UserStore>,
+>;
+
+impl<
+    TyDinoparcClient,
+    TyDinoparcStore,
+    TyHammerfestClient,
+    TyHammerfestStore,
+    TyLinkStore,
+    TyTwinoidClient,
+    TyTwinoidStore,
+    TyUserStore,
+  >
+  LinkService<
+    TyDinoparcClient,
+    TyDinoparcStore,
+    TyHammerfestClient,
+    TyHammerfestStore,
+    TyLinkStore,
+    TyTwinoidClient,
+    TyTwinoidStore,
+    TyUserStore,
+  >
+where
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyLinkStore: LinkStore,
+  TyTwinoidClient: TwinoidClient,
+  TyTwinoidStore: TwinoidStore,
+  TyUserStore: UserStore,
+{
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(
+    dinoparc_client: TyDinoparcClient,
+    dinoparc_store: TyDinoparcStore,
+    hammerfest_client: TyHammerfestClient,
+    hammerfest_store: TyHammerfestStore,
+    link_store: TyLinkStore,
+    twinoid_client: TyTwinoidClient,
+    twinoid_store: TyTwinoidStore,
+    user_store: TyUserStore,
+  ) -> Self {
+    Self {
+      dinoparc_client,
+      dinoparc_store,
+      hammerfest_client,
+      hammerfest_store,
+      link_store,
+      twinoid_client,
+      twinoid_store,
+      user_store,
+    }
+  }
+
+  pub async fn link_to_dinoparc(
+    &self,
+    acx: &AuthContext,
+    options: &LinkToDinoparcOptions,
+  ) -> Result<VersionedRawLink<DinoparcUserIdRef>, LinkError<DinoparcUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(LinkError::Forbidden)?;
+    self.check_user_exists(options.user_id).await?;
+    let session = self
+      .dinoparc_client
+      .create_session(&options.credentials)
+      .await
+      .map_err(LinkError::RemoteAuthentication)?;
+    self
+      .dinoparc_store
+      .touch_short_user(&session.user)
+      .await
+      .map_err(LinkError::Other)?;
+    let link = self
+      .link_store
+      .touch_dinoparc_link(&TouchLinkOptions {
+        etwin: options.user_id.into(),
+        remote: session.user.as_ref(),
+        linked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  pub async fn link_to_hammerfest(
+    &self,
+    acx: &AuthContext,
+    options: &LinkToHammerfestOptions,
+  ) -> Result<VersionedRawLink<HammerfestUserIdRef>, LinkError<HammerfestUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(LinkError::Forbidden)?;
+    self.check_user_exists(options.user_id).await?;
+    let session = self
+      .hammerfest_client
+      .create_session(&options.credentials)
+      .await
+      .map_err(LinkError::RemoteAuthentication)?;
+    self
+      .hammerfest_store
+      .touch_short_user(&session.user)
+      .await
+      .map_err(LinkError::Other)?;
+    let link = self
+      .link_store
+      .touch_hammerfest_link(&TouchLinkOptions {
+        etwin: options.user_id.into(),
+        remote: session.user.as_ref(),
+        linked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  pub async fn link_to_twinoid_oauth(
+    &self,
+    acx: &AuthContext,
+    options: &LinkToTwinoidOauthOptions,
+  ) -> Result<VersionedRawLink<TwinoidUserIdRef>, LinkError<TwinoidUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(LinkError::Forbidden)?;
+    self.check_user_exists(options.user_id).await?;
+    let tid_user = self
+      .twinoid_client
+      .get_me_short(TwinoidApiAuth::Token(options.access_token.clone()))
+      .await
+      .map_err(LinkError::RemoteAuthentication)?;
+    let tid_user = ShortTwinoidUser {
+      id: TwinoidUserId::new(tid_user.id).map_err(|e| LinkError::Other(Box::new(e)))?,
+      display_name: tid_user.name,
+    };
+    self
+      .twinoid_store
+      .touch_short_user(&tid_user)
+      .await
+      .map_err(LinkError::Other)?;
+    let link = self
+      .link_store
+      .touch_twinoid_link(&TouchLinkOptions {
+        etwin: options.user_id.into(),
+        remote: tid_user.id.as_ref(),
+        linked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  pub async fn unlink_from_dinoparc(
+    &self,
+    acx: &AuthContext,
+    options: &UnlinkOptions<DinoparcUserIdRef>,
+  ) -> Result<VersionedRawLink<DinoparcUserIdRef>, UnlinkError<DinoparcUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(UnlinkError::Forbidden)?;
+    let link = self
+      .link_store
+      .delete_dinoparc_link(&DeleteLinkOptions {
+        etwin: options.user_id.into(),
+        remote: options.remote,
+        unlinked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  pub async fn unlink_from_hammerfest(
+    &self,
+    acx: &AuthContext,
+    options: &UnlinkOptions<HammerfestUserIdRef>,
+  ) -> Result<VersionedRawLink<HammerfestUserIdRef>, UnlinkError<HammerfestUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(UnlinkError::Forbidden)?;
+    let link = self
+      .link_store
+      .delete_hammerfest_link(&DeleteLinkOptions {
+        etwin: options.user_id.into(),
+        remote: options.remote,
+        unlinked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  pub async fn unlink_from_twinoid(
+    &self,
+    acx: &AuthContext,
+    options: &UnlinkOptions<TwinoidUserIdRef>,
+  ) -> Result<VersionedRawLink<TwinoidUserIdRef>, UnlinkError<TwinoidUserIdRef>> {
+    let actor = get_link_actor(acx, options.user_id).ok_or(UnlinkError::Forbidden)?;
+    let link = self
+      .link_store
+      .delete_twinoid_link(&DeleteLinkOptions {
+        etwin: options.user_id.into(),
+        remote: options.remote,
+        unlinked_by: actor,
+      })
+      .await?;
+    Ok(link)
+  }
+
+  async fn check_user_exists<T: RemoteUserIdRef>(&self, user_id: UserId) -> Result<(), LinkError<T>> {
+    let user = self
+      .user_store
+      .get_short_user(&GetShortUserOptions {
+        r#ref: UserRef::Id(user_id.into()),
+        time: None,
+      })
+      .await
+      .map_err(LinkError::Other)?;
+    match user {
+      Some(_) => Ok(()),
+      None => Err(LinkError::UserNotFound),
+    }
+  }
+}
+
+/// Returns the user performing the (un)link operation, if they are allowed to manage the links of `target`.
+///
+/// Users can manage their own links, administrators can manage the links of any user.
+fn get_link_actor(acx: &AuthContext, target: UserId) -> Option<UserIdRef> {
+  match acx {
+    AuthContext::User(acx) if acx.user.id == target || acx.is_administrator => Some(acx.user.id.into()),
+    _ => None,
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<
+    TyDinoparcClient,
+    TyDinoparcStore,
+    TyHammerfestClient,
+    TyHammerfestStore,
+    TyLinkStore,
+    TyTwinoidClient,
+    TyTwinoidStore,
+    TyUserStore,
+  > neon::prelude::Finalize
+  for LinkService<
+    TyDinoparcClient,
+    TyDinoparcStore,
+    TyHammerfestClient,
+    TyHammerfestStore,
+    TyLinkStore,
+    TyTwinoidClient,
+    TyTwinoidStore,
+    TyUserStore,
+  >
+where
+  TyDinoparcClient: DinoparcClient,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestClient: HammerfestClient,
+  TyHammerfestStore: HammerfestStore,
+  TyLinkStore: LinkStore,
+  TyTwinoidClient: TwinoidClient,
+  TyTwinoidStore: TwinoidStore,
+  TyUserStore: UserStore,
+{
+}
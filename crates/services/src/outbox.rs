@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use etwin_core::email::{
+  ClaimDueEmailsOptions, EmailAddress, EmailContent, Mailer, OutboundEmail, OutboundEmailId, OutboxStore,
+  QueueEmailOptions, RecordEmailFailureOptions,
+};
+use etwin_core::types::AnyError;
+use etwin_log::Logger;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Mailer queuing the emails in the outbox instead of sending them.
+///
+/// The emails are delivered later by the [`OutboxService`].
+pub struct OutboxMailer<TyOutboxStore>
+where
+  TyOutboxStore: OutboxStore,
+{
+  outbox_store: TyOutboxStore,
+}
+
+impl<TyOutboxStore> OutboxMailer<TyOutboxStore>
+where
+  TyOutboxStore: OutboxStore,
+{
+  pub fn new(outbox_store: TyOutboxStore) -> Self {
+    Self { outbox_store }
+  }
+}
+
+#[async_trait]
+impl<TyOutboxStore> Mailer for OutboxMailer<TyOutboxStore>
+where
+  TyOutboxStore: OutboxStore,
+{
+  async fn send_email(&self, recipient: &EmailAddress, content: &EmailContent) -> Result<(), AnyError> {
+    self
+      .outbox_store
+      .queue_email(&QueueEmailOptions {
+        recipient: recipient.clone(),
+        content: content.clone(),
+      })
+      .await?;
+    Ok(())
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyOutboxStore> neon::prelude::Finalize for OutboxMailer<TyOutboxStore> where TyOutboxStore: OutboxStore {}
+
+/// Event emitted by the [`OutboxService`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum OutboxEvent {
+  /// An email was accepted by the mailer
+  EmailSent { email: OutboundEmailId, attempts: u32 },
+  /// The mailer rejected an email, it will be retried later
+  EmailRetryScheduled {
+    email: OutboundEmailId,
+    attempts: u32,
+    retry_at: Instant,
+    error: String,
+  },
+  /// The mailer rejected an email for the last time, it is no longer retried
+  EmailFailed {
+    email: OutboundEmailId,
+    attempts: u32,
+    error: String,
+  },
+  /// The outbox could not be read or updated
+  DeliveryPassFailed { error: String },
+}
+
+/// Outcome of a delivery pass over the due emails.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct OutboxDeliveryReport {
+  /// Emails accepted by the mailer
+  pub sent: Vec<OutboundEmailId>,
+  /// Emails rejected by the mailer and scheduled for a new attempt
+  pub retried: Vec<OutboundEmailId>,
+  /// Emails rejected by the mailer after their last allowed attempt
+  pub failed: Vec<OutboundEmailId>,
+}
+
+pub struct OutboxService<TyClock, TyLogger, TyMailer, TyOutboxStore>
+where
+  TyClock: Clock,
+  TyLogger: Logger<OutboxEvent>,
+  TyMailer: Mailer,
+  TyOutboxStore: OutboxStore,
+{
+  clock: TyClock,
+  logger: TyLogger,
+  mailer: TyMailer,
+  outbox_store: TyOutboxStore,
+  max_attempts: u32,
+  base_delay: Duration,
+  max_delay: Duration,
+  batch_size: u32,
+  lease: Duration,
+}
+
+pub type DynOutboxService =
+  OutboxService<Arc<dyn Clock>, Arc<dyn Logger<OutboxEvent>>, Arc<dyn Mailer>, Arc<dyn OutboxStore>>;
+
+impl<TyClock, TyLogger, TyMailer, TyOutboxStore> OutboxService<TyClock, TyLogger, TyMailer, TyOutboxStore>
+where
+  TyClock: Clock,
+  TyLogger: Logger<OutboxEvent>,
+  TyMailer: Mailer,
+  TyOutboxStore: OutboxStore,
+{
+  pub fn new(clock: TyClock, logger: TyLogger, mailer: TyMailer, outbox_store: TyOutboxStore) -> Self {
+    Self {
+      clock,
+      logger,
+      mailer,
+      outbox_store,
+      max_attempts: 8,
+      base_delay: Duration::minutes(1),
+      max_delay: Duration::hours(6),
+      batch_size: 50,
+      lease: Duration::minutes(5),
+    }
+  }
+
+  /// Set the number of delivery attempts after which an email is marked as failed.
+  pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+    self.max_attempts = max_attempts;
+    self
+  }
+
+  /// Set the delay before the first retry; it doubles after each failed attempt.
+  pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+    self.base_delay = base_delay;
+    self
+  }
+
+  /// Set the upper bound of the delay between two attempts.
+  pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+    self.max_delay = max_delay;
+    self
+  }
+
+  /// Set the maximum number of emails delivered per pass.
+  pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+    self.batch_size = batch_size;
+    self
+  }
+
+  /// Set how long claimed emails stay hidden from other workers.
+  ///
+  /// If the worker stops before recording an outcome, the emails are delivered again once the lease expires.
+  pub fn with_lease(mut self, lease: Duration) -> Self {
+    self.lease = lease;
+    self
+  }
+
+  /// Send the emails that are due, and record the outcome of each attempt.
+  pub async fn deliver_due_emails(&self) -> Result<OutboxDeliveryReport, AnyError> {
+    let emails = self
+      .outbox_store
+      .claim_due_emails(&ClaimDueEmailsOptions {
+        limit: self.batch_size,
+        lease_until: self.clock.now() + self.lease,
+      })
+      .await?;
+
+    let mut report = OutboxDeliveryReport::default();
+    for email in emails {
+      let attempts = email.attempts + 1;
+      match self.mailer.send_email(&email.recipient, &email.content).await {
+        Ok(()) => {
+          self.outbox_store.record_email_sent(email.id).await?;
+          self.logger.log(OutboxEvent::EmailSent {
+            email: email.id,
+            attempts,
+          });
+          report.sent.push(email.id);
+        }
+        Err(e) => {
+          let error = e.to_string();
+          let retry_at = self.retry_at(&email);
+          self
+            .outbox_store
+            .record_email_failure(&RecordEmailFailureOptions {
+              id: email.id,
+              error: error.clone(),
+              retry_at,
+            })
+            .await?;
+          match retry_at {
+            Some(retry_at) => {
+              self.logger.log(OutboxEvent::EmailRetryScheduled {
+                email: email.id,
+                attempts,
+                retry_at,
+                error,
+              });
+              report.retried.push(email.id);
+            }
+            None => {
+              self.logger.log(OutboxEvent::EmailFailed {
+                email: email.id,
+                attempts,
+                error,
+              });
+              report.failed.push(email.id);
+            }
+          }
+        }
+      }
+    }
+    Ok(report)
+  }
+
+  /// Deliver the due emails every `interval`, until the future is dropped.
+  pub async fn run(&self, interval: std::time::Duration) {
+    loop {
+      if let Err(e) = self.deliver_due_emails().await {
+        self
+          .logger
+          .log(OutboxEvent::DeliveryPassFailed { error: e.to_string() });
+      }
+      tokio::time::sleep(interval).await;
+    }
+  }
+
+  /// Time of the next attempt after a failure, or `None` if the email ran out of attempts.
+  fn retry_at(&self, email: &OutboundEmail) -> Option<Instant> {
+    if email.attempts + 1 >= self.max_attempts {
+      return None;
+    }
+    let mut delay = self.base_delay;
+    for _ in 0..email.attempts {
+      if delay >= self.max_delay {
+        break;
+      }
+      delay = delay + delay;
+    }
+    Some(self.clock.now() + std::cmp::min(delay, self.max_delay))
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyLogger, TyMailer, TyOutboxStore> neon::prelude::Finalize
+  for OutboxService<TyClock, TyLogger, TyMailer, TyOutboxStore>
+where
+  TyClock: Clock,
+  TyLogger: Logger<OutboxEvent>,
+  TyMailer: Mailer,
+  TyOutboxStore: OutboxStore,
+{
+}
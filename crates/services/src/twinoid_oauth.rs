@@ -0,0 +1,184 @@
+use chrono::Duration;
+use etwin_core::clock::Clock;
+use etwin_core::oauth::TwinoidRefreshToken;
+use etwin_core::token::{TokenStore, TouchOauthTokenOptions};
+use etwin_core::twinoid::{TwinoidOauthClient, TwinoidOauthError, TwinoidUserId};
+use etwin_core::types::AnyError;
+use etwin_log::Logger;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Event emitted by the [`TwinoidOauthService`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum TwinoidOauthEvent {
+  /// A new access token was retrieved for a user
+  AccessTokenRefreshed { twinoid_user: TwinoidUserId },
+  /// Twinoid rejected the refresh token of a user, their tokens were revoked
+  TokensRevoked { twinoid_user: TwinoidUserId },
+  /// The tokens of a user could not be refreshed, they are retried on the next pass
+  RefreshFailed { twinoid_user: TwinoidUserId, error: String },
+  /// The stored tokens could not be read
+  RefreshPassFailed { error: String },
+}
+
+#[derive(Debug)]
+pub struct TwinoidOauthRefreshFailure {
+  pub twinoid_user_id: TwinoidUserId,
+  pub error: AnyError,
+}
+
+/// Outcome of a refresh pass over the stored Twinoid tokens.
+#[derive(Debug, Default)]
+pub struct TwinoidOauthRefreshReport {
+  /// Users with a new access token
+  pub refreshed: Vec<TwinoidUserId>,
+  /// Users whose refresh token was rejected by Twinoid: their tokens were revoked
+  pub revoked: Vec<TwinoidUserId>,
+  /// Users whose tokens could not be refreshed because of a transient error: they are kept for the next pass
+  pub failures: Vec<TwinoidOauthRefreshFailure>,
+}
+
+pub struct TwinoidOauthService<TyClock, TyLogger, TyTokenStore, TyTwinoidOauthClient>
+where
+  TyClock: Clock,
+  TyLogger: Logger<TwinoidOauthEvent>,
+  TyTokenStore: TokenStore,
+  TyTwinoidOauthClient: TwinoidOauthClient,
+{
+  clock: TyClock,
+  logger: TyLogger,
+  token_store: TyTokenStore,
+  twinoid_oauth_client: TyTwinoidOauthClient,
+  refresh_margin: Duration,
+}
+
+pub type DynTwinoidOauthService = TwinoidOauthService<
+  Arc<dyn Clock>,
+  Arc<dyn Logger<TwinoidOauthEvent>>,
+  Arc<dyn TokenStore>,
+  Arc<dyn TwinoidOauthClient>,
+>;
+
+impl<TyClock, TyLogger, TyTokenStore, TyTwinoidOauthClient>
+  TwinoidOauthService<TyClock, TyLogger, TyTokenStore, TyTwinoidOauthClient>
+where
+  TyClock: Clock,
+  TyLogger: Logger<TwinoidOauthEvent>,
+  TyTokenStore: TokenStore,
+  TyTwinoidOauthClient: TwinoidOauthClient,
+{
+  pub fn new(
+    clock: TyClock,
+    logger: TyLogger,
+    token_store: TyTokenStore,
+    twinoid_oauth_client: TyTwinoidOauthClient,
+  ) -> Self {
+    Self {
+      clock,
+      logger,
+      token_store,
+      twinoid_oauth_client,
+      refresh_margin: Duration::minutes(5),
+    }
+  }
+
+  /// Set how long before their expiration access tokens are refreshed.
+  pub fn with_refresh_margin(mut self, refresh_margin: Duration) -> Self {
+    self.refresh_margin = refresh_margin;
+    self
+  }
+
+  /// Refresh the access tokens expiring within the refresh margin, and revoke the tokens of users whose refresh
+  /// token was rejected.
+  pub async fn refresh_tokens(&self) -> Result<TwinoidOauthRefreshReport, AnyError> {
+    let deadline = self.clock.now() + self.refresh_margin;
+    let refresh_tokens = self.token_store.get_twinoid_oauth_to_refresh(deadline).await?;
+    let mut report = TwinoidOauthRefreshReport::default();
+    for refresh_token in refresh_tokens {
+      let twinoid_user_id = refresh_token.twinoid_user_id;
+      match self.refresh_token(refresh_token).await {
+        Ok(true) => {
+          self.logger.log(TwinoidOauthEvent::AccessTokenRefreshed {
+            twinoid_user: twinoid_user_id,
+          });
+          report.refreshed.push(twinoid_user_id);
+        }
+        Ok(false) => {
+          self.logger.log(TwinoidOauthEvent::TokensRevoked {
+            twinoid_user: twinoid_user_id,
+          });
+          report.revoked.push(twinoid_user_id);
+        }
+        Err(error) => {
+          self.logger.log(TwinoidOauthEvent::RefreshFailed {
+            twinoid_user: twinoid_user_id,
+            error: error.to_string(),
+          });
+          report
+            .failures
+            .push(TwinoidOauthRefreshFailure { twinoid_user_id, error });
+        }
+      }
+    }
+    Ok(report)
+  }
+
+  /// Refresh the expiring tokens every `interval`, until the future is dropped.
+  pub async fn run(&self, interval: std::time::Duration) {
+    loop {
+      if let Err(e) = self.refresh_tokens().await {
+        self
+          .logger
+          .log(TwinoidOauthEvent::RefreshPassFailed { error: e.to_string() });
+      }
+      tokio::time::sleep(interval).await;
+    }
+  }
+
+  /// Returns `true` if the token was refreshed, `false` if it was revoked.
+  async fn refresh_token(&self, refresh_token: TwinoidRefreshToken) -> Result<bool, AnyError> {
+    match self.twinoid_oauth_client.refresh_token(&refresh_token.key).await {
+      Ok(token) => {
+        let now = self.clock.now();
+        self
+          .token_store
+          .touch_twinoid_oauth(&TouchOauthTokenOptions {
+            access_token: token.access_token,
+            // The refresh token is not always renewed
+            refresh_token: token.refresh_token.unwrap_or(refresh_token.key),
+            expiration_time: now + Duration::seconds(token.expires_in),
+            twinoid_user_id: refresh_token.twinoid_user_id,
+          })
+          .await?;
+        Ok(true)
+      }
+      Err(TwinoidOauthError::InvalidGrant) => {
+        let current = self
+          .token_store
+          .get_twinoid_oauth(refresh_token.twinoid_user_id.into())
+          .await?;
+        if let Some(access_token) = current.access_token {
+          self.token_store.revoke_twinoid_access_token(&access_token.key).await?;
+        }
+        self
+          .token_store
+          .revoke_twinoid_refresh_token(&refresh_token.key)
+          .await?;
+        Ok(false)
+      }
+      Err(TwinoidOauthError::Other(e)) => Err(e),
+    }
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock, TyLogger, TyTokenStore, TyTwinoidOauthClient> neon::prelude::Finalize
+  for TwinoidOauthService<TyClock, TyLogger, TyTokenStore, TyTwinoidOauthClient>
+where
+  TyClock: Clock,
+  TyLogger: Logger<TwinoidOauthEvent>,
+  TyTokenStore: TokenStore,
+  TyTwinoidOauthClient: TwinoidOauthClient,
+{
+}
@@ -0,0 +1,217 @@
+use etwin_core::clock::VirtualClock;
+use etwin_core::core::Instant;
+use etwin_core::dinoparc::{
+  DinoparcClient, DinoparcCredentials, DinoparcPassword, DinoparcServer, DinoparcSession, DinoparcSessionKey,
+  DinoparcStore, GetDinoparcUserOptions,
+};
+use etwin_core::hammerfest::{
+  HammerfestClient, HammerfestCredentials, HammerfestPassword, HammerfestServer, HammerfestSession,
+  HammerfestSessionKey, HammerfestStore,
+};
+use etwin_dinoparc_client::mem::MemDinoparcClient;
+use etwin_dinoparc_store::mem::MemDinoparcStore;
+use etwin_hammerfest_client::MemHammerfestClient;
+use etwin_hammerfest_store::mem::MemHammerfestStore;
+use etwin_log::{Logger, VecLogger};
+use etwin_services::archive::{
+  archive_dinoparc_session, archive_hammerfest_session, ArchiveQueue, ArchiveRequest, ArchiveStep, ArchiveWorker,
+  DynArchiveWorker,
+};
+use etwin_services::auth::AuthEvent;
+use std::sync::Arc;
+
+struct TestApi {
+  dinoparc_client: Arc<MemDinoparcClient<Arc<VirtualClock>>>,
+  dinoparc_store: Arc<MemDinoparcStore<Arc<VirtualClock>>>,
+  hammerfest_client: Arc<MemHammerfestClient<Arc<VirtualClock>>>,
+  hammerfest_store: Arc<MemHammerfestStore<Arc<VirtualClock>>>,
+}
+
+fn make_test_api() -> TestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0)));
+  TestApi {
+    dinoparc_client: Arc::new(MemDinoparcClient::new(Arc::clone(&clock))),
+    dinoparc_store: Arc::new(MemDinoparcStore::new(Arc::clone(&clock))),
+    hammerfest_client: Arc::new(MemHammerfestClient::new(Arc::clone(&clock))),
+    hammerfest_store: Arc::new(MemHammerfestStore::new(Arc::clone(&clock))),
+  }
+}
+
+fn make_archive_worker(api: &TestApi, logger: Arc<VecLogger<AuthEvent>>) -> (ArchiveQueue, DynArchiveWorker) {
+  ArchiveWorker::new(
+    10,
+    Arc::clone(&api.dinoparc_client) as Arc<dyn DinoparcClient>,
+    Arc::clone(&api.dinoparc_store) as Arc<dyn DinoparcStore>,
+    Arc::clone(&api.hammerfest_client) as Arc<dyn HammerfestClient>,
+    Arc::clone(&api.hammerfest_store) as Arc<dyn HammerfestStore>,
+    logger as Arc<dyn Logger<AuthEvent>>,
+  )
+}
+
+async fn create_dinoparc_session(api: &TestApi) -> DinoparcSession {
+  api.dinoparc_client.create_user(
+    DinoparcServer::DinoparcCom,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    DinoparcPassword::new("aaaaa".to_string()),
+  );
+  api
+    .dinoparc_client
+    .create_session(&DinoparcCredentials {
+      server: DinoparcServer::DinoparcCom,
+      username: "alice".parse().unwrap(),
+      password: DinoparcPassword::new("aaaaa".to_string()),
+    })
+    .await
+    .unwrap()
+}
+
+async fn create_hammerfest_session(api: &TestApi) -> HammerfestSession {
+  api.hammerfest_client.create_user(
+    HammerfestServer::HammerfestFr,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    HammerfestPassword::new("aaaaa".to_string()),
+  );
+  api
+    .hammerfest_client
+    .create_session(&HammerfestCredentials {
+      server: HammerfestServer::HammerfestFr,
+      username: "alice".parse().unwrap(),
+      password: HammerfestPassword::new("aaaaa".to_string()),
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_archive_hammerfest_session() {
+  let api = make_test_api();
+  let session = create_hammerfest_session(&api).await;
+
+  let actual = archive_hammerfest_session(&api.hammerfest_client, &api.hammerfest_store, &session).await;
+
+  assert!(actual.is_ok());
+  assert_eq!(
+    actual.archived,
+    vec![
+      ArchiveStep::HammerfestProfile,
+      ArchiveStep::HammerfestInventory,
+      ArchiveStep::HammerfestGodchildren,
+      ArchiveStep::HammerfestShop,
+    ]
+  );
+}
+
+#[tokio::test]
+async fn test_archive_hammerfest_session_reports_failures() {
+  let api = make_test_api();
+  let mut session = create_hammerfest_session(&api).await;
+  session.key = "0123456789abcdefghijklmnop".parse::<HammerfestSessionKey>().unwrap();
+
+  let actual = archive_hammerfest_session(&api.hammerfest_client, &api.hammerfest_store, &session).await;
+
+  // The profile is public: only the steps requiring a valid session fail.
+  assert_eq!(actual.archived, vec![ArchiveStep::HammerfestProfile]);
+  let failed: Vec<ArchiveStep> = actual.failures.iter().map(|f| f.step).collect();
+  assert_eq!(
+    failed,
+    vec![
+      ArchiveStep::HammerfestInventory,
+      ArchiveStep::HammerfestGodchildren,
+      ArchiveStep::HammerfestShop,
+    ]
+  );
+}
+
+#[tokio::test]
+async fn test_archive_dinoparc_session() {
+  let api = make_test_api();
+  let session = create_dinoparc_session(&api).await;
+
+  let actual = archive_dinoparc_session(&api.dinoparc_client, &api.dinoparc_store, &session).await;
+
+  assert!(actual.is_ok());
+  assert_eq!(
+    actual.archived,
+    vec![ArchiveStep::DinoparcInventory, ArchiveStep::DinoparcCollection]
+  );
+  let user = api
+    .dinoparc_store
+    .get_user(&GetDinoparcUserOptions {
+      server: DinoparcServer::DinoparcCom,
+      id: "123".parse().unwrap(),
+      time: None,
+    })
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(user.coins.is_some());
+}
+
+#[tokio::test]
+async fn test_archive_dinoparc_session_reports_failures() {
+  let api = make_test_api();
+  let mut session = create_dinoparc_session(&api).await;
+  session.key = "0123456789abcdefghijklmnopqrstuv"
+    .parse::<DinoparcSessionKey>()
+    .unwrap();
+
+  let actual = archive_dinoparc_session(&api.dinoparc_client, &api.dinoparc_store, &session).await;
+
+  assert!(actual.archived.is_empty());
+  let failed: Vec<ArchiveStep> = actual.failures.iter().map(|f| f.step).collect();
+  assert_eq!(
+    failed,
+    vec![ArchiveStep::DinoparcInventory, ArchiveStep::DinoparcCollection]
+  );
+}
+
+#[tokio::test]
+async fn test_archive_worker_runs_queued_requests() {
+  let api = make_test_api();
+  let logger = Arc::new(VecLogger::new());
+  let (queue, mut worker) = make_archive_worker(&api, Arc::clone(&logger));
+  let dinoparc_session = create_dinoparc_session(&api).await;
+  let mut hammerfest_session = create_hammerfest_session(&api).await;
+  hammerfest_session.key = "0123456789abcdefghijklmnop".parse::<HammerfestSessionKey>().unwrap();
+
+  queue.push(ArchiveRequest::Dinoparc(dinoparc_session)).unwrap();
+  queue.push(ArchiveRequest::Hammerfest(hammerfest_session)).unwrap();
+  assert_eq!(worker.archive_queued().await, 2);
+  assert_eq!(worker.archive_queued().await, 0);
+
+  let steps: Vec<String> = logger
+    .take()
+    .into_iter()
+    .map(|e| match e {
+      AuthEvent::ArchiveOnLoginFailed { step, .. } => step,
+      e => panic!("unexpected event: {:?}", e),
+    })
+    .collect();
+  assert_eq!(
+    steps,
+    vec!["HammerfestInventory", "HammerfestGodchildren", "HammerfestShop"]
+  );
+}
+
+#[tokio::test]
+async fn test_archive_queue_is_bounded() {
+  let api = make_test_api();
+  let (queue, worker) = ArchiveWorker::new(
+    1,
+    Arc::clone(&api.dinoparc_client) as Arc<dyn DinoparcClient>,
+    Arc::clone(&api.dinoparc_store) as Arc<dyn DinoparcStore>,
+    Arc::clone(&api.hammerfest_client) as Arc<dyn HammerfestClient>,
+    Arc::clone(&api.hammerfest_store) as Arc<dyn HammerfestStore>,
+    Arc::new(VecLogger::new()) as Arc<dyn Logger<AuthEvent>>,
+  );
+  let session = create_dinoparc_session(&api).await;
+
+  queue.push(ArchiveRequest::Dinoparc(session.clone())).unwrap();
+  let actual = queue.push(ArchiveRequest::Dinoparc(session.clone()));
+  assert_eq!(actual.unwrap_err().to_string(), "ArchiveQueueFull");
+  drop(worker);
+  let actual = queue.push(ArchiveRequest::Dinoparc(session));
+  assert_eq!(actual.unwrap_err().to_string(), "ArchiveQueueClosed");
+}
@@ -0,0 +1,239 @@
+use chrono::Duration;
+use etwin_core::clock::{Clock, VirtualClock};
+use etwin_core::core::Instant;
+use etwin_core::dinoparc::{
+  DinoparcClient, DinoparcCredentials, DinoparcPassword, DinoparcServer, DinoparcStore, GetDinoparcUserOptions,
+  ShortDinoparcUser,
+};
+use etwin_core::hammerfest::{HammerfestPassword, HammerfestServer, HammerfestStore, ShortHammerfestUser};
+use etwin_core::token::TokenStore;
+use etwin_dinoparc_client::mem::MemDinoparcClient;
+use etwin_dinoparc_store::mem::MemDinoparcStore;
+use etwin_hammerfest_client::MemHammerfestClient;
+use etwin_hammerfest_store::mem::MemHammerfestStore;
+use etwin_services::archive_refresh::{ArchiveRefreshService, ArchiveRefreshUser};
+use etwin_token_store::mem::MemTokenStore;
+use std::sync::Arc;
+
+struct TestApi {
+  clock: Arc<VirtualClock>,
+  dinoparc_client: Arc<MemDinoparcClient<Arc<VirtualClock>>>,
+  dinoparc_store: Arc<MemDinoparcStore<Arc<VirtualClock>>>,
+  hammerfest_client: Arc<MemHammerfestClient<Arc<VirtualClock>>>,
+  hammerfest_store: Arc<MemHammerfestStore<Arc<VirtualClock>>>,
+  token_store: Arc<MemTokenStore<Arc<VirtualClock>>>,
+}
+
+fn make_test_api() -> TestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0)));
+  TestApi {
+    dinoparc_client: Arc::new(MemDinoparcClient::new(Arc::clone(&clock))),
+    dinoparc_store: Arc::new(MemDinoparcStore::new(Arc::clone(&clock))),
+    hammerfest_client: Arc::new(MemHammerfestClient::new(Arc::clone(&clock))),
+    hammerfest_store: Arc::new(MemHammerfestStore::new(Arc::clone(&clock))),
+    token_store: Arc::new(MemTokenStore::new(Arc::clone(&clock))),
+    clock,
+  }
+}
+
+type TestRefreshService = ArchiveRefreshService<
+  Arc<VirtualClock>,
+  Arc<MemDinoparcClient<Arc<VirtualClock>>>,
+  Arc<MemDinoparcStore<Arc<VirtualClock>>>,
+  Arc<MemHammerfestClient<Arc<VirtualClock>>>,
+  Arc<MemHammerfestStore<Arc<VirtualClock>>>,
+  Arc<MemTokenStore<Arc<VirtualClock>>>,
+>;
+
+fn make_refresh_service(api: &TestApi) -> TestRefreshService {
+  ArchiveRefreshService::new(
+    Arc::clone(&api.clock),
+    Arc::clone(&api.dinoparc_client),
+    Arc::clone(&api.dinoparc_store),
+    Arc::clone(&api.hammerfest_client),
+    Arc::clone(&api.hammerfest_store),
+    Arc::clone(&api.token_store),
+  )
+  .with_max_age(Duration::days(7))
+  .with_retry_delay(Duration::days(1))
+  .with_request_interval(std::time::Duration::ZERO)
+}
+
+/// Create a user known to both the remote server and the archive.
+async fn create_user(api: &TestApi, server: HammerfestServer, id: &str, username: &str) -> ShortHammerfestUser {
+  let user = ShortHammerfestUser {
+    server,
+    id: id.parse().unwrap(),
+    username: username.parse().unwrap(),
+  };
+  api.hammerfest_client.create_user(
+    user.server,
+    user.id,
+    user.username.clone(),
+    HammerfestPassword::new("aaaaa".to_string()),
+  );
+  api.hammerfest_store.touch_short_user(&user).await.unwrap();
+  user
+}
+
+/// Create a Dinoparc user known to the remote server and the archive, with a stored session.
+async fn create_dinoparc_user(api: &TestApi, id: &str, username: &str) -> ShortDinoparcUser {
+  let user = ShortDinoparcUser {
+    server: DinoparcServer::DinoparcCom,
+    id: id.parse().unwrap(),
+    username: username.parse().unwrap(),
+  };
+  api.dinoparc_client.create_user(
+    user.server,
+    user.id,
+    user.username.clone(),
+    DinoparcPassword::new("aaaaa".to_string()),
+  );
+  let session = api
+    .dinoparc_client
+    .create_session(&DinoparcCredentials {
+      server: user.server,
+      username: user.username.clone(),
+      password: DinoparcPassword::new("aaaaa".to_string()),
+    })
+    .await
+    .unwrap();
+  api
+    .token_store
+    .touch_dinoparc(user.as_ref(), &session.key)
+    .await
+    .unwrap();
+  api.dinoparc_store.touch_short_user(&user).await.unwrap();
+  user
+}
+
+#[tokio::test]
+async fn test_refresh_stale_users() {
+  let api = make_test_api();
+  let alice = create_user(&api, HammerfestServer::HammerfestFr, "123", "alice").await;
+  let bob = create_user(&api, HammerfestServer::HfestNet, "456", "bob").await;
+  let refresh = make_refresh_service(&api);
+
+  api.clock.advance_by(Duration::days(1));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert!(report.refreshed.is_empty());
+  assert!(report.failures.is_empty());
+
+  api.clock.advance_by(Duration::days(7));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert_eq!(report.refreshed, vec![alice.into(), bob.into()]);
+  assert!(report.failures.is_empty());
+
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert!(report.refreshed.is_empty());
+}
+
+#[tokio::test]
+async fn test_refresh_stale_dinoparc_users() {
+  let api = make_test_api();
+  let alice = create_dinoparc_user(&api, "1", "alice").await;
+  // Archived, but without a stored session
+  let bob = ShortDinoparcUser {
+    server: DinoparcServer::DinoparcCom,
+    id: "2".parse().unwrap(),
+    username: "bob".parse().unwrap(),
+  };
+  api.dinoparc_store.touch_short_user(&bob).await.unwrap();
+  let refresh = make_refresh_service(&api);
+
+  api.clock.advance_by(Duration::days(8));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert_eq!(report.refreshed, vec![ArchiveRefreshUser::Dinoparc(alice.clone())]);
+  let failed: Vec<ArchiveRefreshUser> = report.failures.into_iter().map(|f| f.user).collect();
+  assert_eq!(failed, vec![ArchiveRefreshUser::Dinoparc(bob)]);
+
+  let archived = api
+    .dinoparc_store
+    .get_user(&GetDinoparcUserOptions {
+      server: alice.server,
+      id: alice.id,
+      time: None,
+    })
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(
+    archived.collection.map(|c| c.latest.retrieved.latest),
+    Some(api.clock.now())
+  );
+}
+
+#[tokio::test]
+async fn test_refresh_backs_off_failures() {
+  let api = make_test_api();
+  let alice = create_user(&api, HammerfestServer::HammerfestFr, "123", "alice").await;
+  // Archived, but unknown to the server (e.g. deleted account)
+  let bob = ShortHammerfestUser {
+    server: HammerfestServer::HammerfestFr,
+    id: "456".parse().unwrap(),
+    username: "bob".parse().unwrap(),
+  };
+  api.hammerfest_store.touch_short_user(&bob).await.unwrap();
+  let refresh = make_refresh_service(&api);
+
+  api.clock.advance_by(Duration::days(8));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert_eq!(report.refreshed, vec![alice.into()]);
+  let failed: Vec<ArchiveRefreshUser> = report.failures.into_iter().map(|f| f.user).collect();
+  assert_eq!(failed, vec![bob.clone().into()]);
+
+  // Failed users stay stale, but are not retried before the retry delay.
+  api.clock.advance_by(Duration::hours(23));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert!(report.refreshed.is_empty());
+  assert!(report.failures.is_empty());
+
+  api.clock.advance_by(Duration::hours(2));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert!(report.refreshed.is_empty());
+  let failed: Vec<ArchiveRefreshUser> = report.failures.into_iter().map(|f| f.user).collect();
+  assert_eq!(failed, vec![bob.into()]);
+}
+
+#[tokio::test]
+async fn test_refresh_prefers_users_never_attempted() {
+  let api = make_test_api();
+  let alice = create_user(&api, HammerfestServer::HammerfestFr, "123", "alice").await;
+  api.clock.advance_by(Duration::seconds(1));
+  let bob = create_user(&api, HammerfestServer::HammerfestFr, "456", "bob").await;
+  let refresh = make_refresh_service(&api).with_limit(1);
+
+  api.clock.advance_by(Duration::days(8));
+  // Alice was attempted recently, but her profile is still stale (e.g. the previous attempt failed).
+  api
+    .hammerfest_store
+    .touch_refresh_attempt(alice.as_ref())
+    .await
+    .unwrap();
+  api.clock.advance_by(Duration::days(2));
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert_eq!(report.refreshed, vec![bob.into()]);
+
+  let report = refresh.refresh_stale_users().await.unwrap();
+  assert_eq!(report.refreshed, vec![alice.into()]);
+}
+
+#[tokio::test]
+async fn test_refresh_respects_request_interval() {
+  tokio::time::pause();
+  let api = make_test_api();
+  create_user(&api, HammerfestServer::HammerfestFr, "123", "alice").await;
+  create_user(&api, HammerfestServer::HammerfestFr, "456", "bob").await;
+  create_user(&api, HammerfestServer::HfestNet, "789", "charlie").await;
+  let refresh = make_refresh_service(&api).with_request_interval(std::time::Duration::from_millis(200));
+
+  api.clock.advance_by(Duration::days(8));
+  let start = tokio::time::Instant::now();
+  let report = refresh.refresh_stale_users().await.unwrap();
+  let elapsed = start.elapsed();
+  assert_eq!(report.refreshed.len(), 3);
+  // Two requests to `hammerfest.fr`: one wait. `hfest.net` is visited in between without waiting.
+  // The time is paused, so the timer only rounds the wait up to the next millisecond.
+  assert!(elapsed >= std::time::Duration::from_millis(200));
+  assert!(elapsed < std::time::Duration::from_millis(210));
+}
@@ -3,10 +3,14 @@ use etwin_core::api::ApiRef;
 use etwin_core::clock::{Clock, VirtualClock};
 use etwin_core::core::{Instant, LocaleId, Secret};
 use etwin_core::hammerfest::{
-  HammerfestClient, HammerfestCredentials, HammerfestPassword, HammerfestServer, HammerfestStore,
+  HammerfestClient, HammerfestCredentials, HammerfestPassword, HammerfestServer, HammerfestStore, HammerfestUserIdRef,
 };
 use etwin_core::link::LinkStore;
-use etwin_core::user::{ShortUser, UserDisplayNameVersion, UserDisplayNameVersions, UserStore};
+use etwin_core::mfa::{ConfirmTotpEnrollmentOptions, DisableTotpOptions, MfaProof, TotpSecret};
+use etwin_core::user::{
+  CreateUserOptions, GetUserOptions, RawUpdateUserPatch, ShortUser, UpdateUserOptions, UpdateUserPatch,
+  UserDisplayNameVersion, UserDisplayNameVersions, UserFields, UserIdRef, UserRef, UserStore,
+};
 use etwin_core::uuid::{Uuid4Generator, UuidGenerator};
 use etwin_db_schema::force_create_latest;
 use etwin_hammerfest_client::MemHammerfestClient;
@@ -16,25 +20,37 @@ use etwin_user_store::pg::PgUserStore;
 use serial_test::serial;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::PgPool;
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 
 use etwin_auth_store::pg::PgAuthStore;
 use etwin_core::auth::{
-  AuthStore, RawUserCredentials, RegisterOrLoginWithEmailOptions, RegisterWithUsernameOptions,
-  RegisterWithVerifiedEmailOptions, Session, UserAndSession,
+  AuthContext, AuthScope, AuthStore, ChangeEmailWithTokenOptions, CompleteMfaLoginOptions, LoginFailureReason,
+  LoginResult, LoginThrottleKey, LoginThrottledError, PendingMfaLogin, RawCredentials, RawUserCredentials,
+  RegisterOrLoginWithEmailOptions, RegisterWithUsernameOptions, RegisterWithVerifiedEmailOptions,
+  RequestEmailChangeOptions, Session, UserAndSession, UserAuthContext,
 };
 use etwin_core::dinoparc::{DinoparcClient, DinoparcStore};
-use etwin_core::email::{EmailAddress, EmailFormatter, Mailer, VerifyRegistrationEmail};
-use etwin_core::oauth::OauthProviderStore;
+use etwin_core::email::{
+  EmailAddress, EmailFormatter, Mailer, NotifyEmailChangeEmail, VerifyEmailChangeEmail, VerifyRegistrationEmail,
+};
+use etwin_core::oauth::{OauthProviderStore, RfcOauthAccessToken, RfcOauthAccessTokenKey, RfcOauthTokenType};
 use etwin_core::password::{Password, PasswordService};
-use etwin_core::twinoid::{TwinoidClient, TwinoidStore};
+use etwin_core::token::TokenStore;
+use etwin_core::twinoid::{
+  self, ArchivedTwinoidUserProfile, GetTwinoidUserOptions, TwinoidClient, TwinoidStore, TwinoidUserProfile,
+};
 use etwin_dinoparc_client::mem::MemDinoparcClient;
 use etwin_dinoparc_store::pg::PgDinoparcStore;
 use etwin_email_formatter::json::{JsonBody, JsonEmailFormatter};
+use etwin_log::{Logger, VecLogger};
 use etwin_mailer::mem::MemMailer;
 use etwin_oauth_provider_store::pg::PgOauthProviderStore;
+use etwin_password::multi::MultiPasswordService;
 use etwin_password::scrypt::ScryptPasswordService;
-use etwin_services::auth::{AuthService, DynAuthService};
+use etwin_services::auth::{AuthEvent, AuthService, DynAuthService, PatchUserError};
+use etwin_services::totp;
+use etwin_token_store::pg::PgTokenStore;
 use etwin_twinoid_client::mem::MemTwinoidClient;
 use etwin_twinoid_store::pg::PgTwinoidStore;
 
@@ -89,7 +105,7 @@ async fn make_test_api(
   let hammerfest_client: Arc<MemHammerfestClient<Arc<VirtualClock>>> =
     Arc::new(MemHammerfestClient::new(Arc::clone(&clock)));
   let dinoparc_client: Arc<dyn DinoparcClient> = Arc::new(MemDinoparcClient::new(Arc::clone(&clock)));
-  let twinoid_client: Arc<dyn TwinoidClient> = Arc::new(MemTwinoidClient);
+  let twinoid_client = Arc::new(MemTwinoidClient::new());
 
   let hammerfest_store: Arc<dyn HammerfestStore> = Arc::new(
     PgHammerfestStore::new(
@@ -106,7 +122,16 @@ async fn make_test_api(
       .await
       .unwrap(),
   );
-  let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(Arc::clone(&clock), Arc::clone(&database)));
+  let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(
+    Arc::clone(&clock),
+    Arc::clone(&database),
+    Arc::clone(&uuid_generator),
+  ));
+  let token_store: Arc<dyn TokenStore> = Arc::new(
+    PgTokenStore::new(Arc::clone(&clock), Arc::clone(&database), database_secret.clone())
+      .await
+      .unwrap(),
+  );
 
   let link_store: Arc<dyn LinkStore> = Arc::new(PgLinkStore::new(Arc::clone(&clock), Arc::clone(&database)));
   let user_store: Arc<dyn UserStore> = Arc::new(PgUserStore::new(
@@ -118,8 +143,9 @@ async fn make_test_api(
 
   let email_formatter: Arc<JsonEmailFormatter> = Arc::new(JsonEmailFormatter);
   let mailer = Arc::new(MemMailer::new());
+  let logger = Arc::new(VecLogger::new());
 
-  let password_service = Arc::new(ScryptPasswordService::recommended_for_tests());
+  let password_service = Arc::new(MultiPasswordService::recommended_for_tests());
 
   let auth_store: Arc<dyn AuthStore> = Arc::new(PgAuthStore::new(
     Arc::clone(&clock),
@@ -144,11 +170,13 @@ async fn make_test_api(
     Arc::clone(&hammerfest_client) as Arc<dyn HammerfestClient>,
     Arc::clone(&hammerfest_store),
     Arc::clone(&link_store),
+    Arc::clone(&logger) as Arc<dyn Logger<AuthEvent>>,
     Arc::clone(&mailer) as Arc<dyn Mailer>,
     Arc::clone(&oauth_provider_store),
     Arc::clone(&password_service) as Arc<dyn PasswordService>,
+    Arc::clone(&token_store),
     Arc::clone(&user_store),
-    Arc::clone(&twinoid_client),
+    Arc::clone(&twinoid_client) as Arc<dyn TwinoidClient>,
     Arc::clone(&twinoid_store),
     Arc::clone(&uuid_generator) as Arc<dyn UuidGenerator>,
     auth_secret,
@@ -158,7 +186,12 @@ async fn make_test_api(
     auth,
     clock,
     hammerfest_client,
+    logger,
     mailer,
+    token_store,
+    twinoid_client,
+    twinoid_store,
+    user_store,
   }
 }
 
@@ -172,7 +205,12 @@ where
   pub(crate) auth: TyAuth,
   pub(crate) clock: TyClock,
   pub(crate) hammerfest_client: TyHammerfest,
+  pub(crate) logger: Arc<VecLogger<AuthEvent>>,
   pub(crate) mailer: TyMailer,
+  pub(crate) token_store: Arc<dyn TokenStore>,
+  pub(crate) twinoid_client: Arc<MemTwinoidClient>,
+  pub(crate) twinoid_store: Arc<dyn TwinoidStore>,
+  pub(crate) user_store: Arc<dyn UserStore>,
 }
 
 #[tokio::test]
@@ -181,6 +219,18 @@ async fn test_register_user_through_mail() {
   register_user_through_mail(make_test_api().await).await;
 }
 
+#[tokio::test]
+#[serial]
+async fn test_change_email_through_mail() {
+  change_email_through_mail(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_change_email_when_the_notice_fails() {
+  change_email_when_the_notice_fails(make_test_api().await).await;
+}
+
 #[tokio::test]
 #[serial]
 async fn test_register_user_with_username() {
@@ -193,6 +243,42 @@ async fn test_register_user_with_username_and_sign_in() {
   register_user_with_username_and_sign_in(make_test_api().await).await;
 }
 
+#[tokio::test]
+#[serial]
+async fn test_sign_in_with_totp() {
+  sign_in_with_totp(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_mfa_login_throttling() {
+  mfa_login_throttling(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_replace_totp() {
+  replace_totp(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_login_throttling() {
+  login_throttling(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rehash_legacy_password_on_login() {
+  rehash_legacy_password_on_login(make_test_api().await).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_update_user_password() {
+  update_user_password(make_test_api().await).await;
+}
+
 #[tokio::test]
 #[serial]
 async fn test_register_user_with_hammerfest() {
@@ -265,6 +351,194 @@ async fn register_user_through_mail<TyClock>(
   assert_eq!(actual, expected);
 }
 
+async fn change_email_through_mail<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice_email: EmailAddress = "alice@example.com".parse().unwrap();
+  let alicia_email: EmailAddress = "alicia@example.com".parse().unwrap();
+  api.mailer.as_ref().create_inbox(alice_email.clone());
+  api.mailer.as_ref().create_inbox(alicia_email.clone());
+  api
+    .auth
+    .as_ref()
+    .register_or_login_with_email(&RegisterOrLoginWithEmailOptions {
+      email: alice_email.clone(),
+      locale: Some(LocaleId::FrFr),
+    })
+    .await
+    .unwrap();
+  let token = {
+    let mail = api.mailer.as_ref().read_inbox(&alice_email).into_iter().next().unwrap();
+    let body: JsonBody<VerifyRegistrationEmail> = serde_json::from_str(mail.body_text.as_str()).unwrap();
+    body.data.token
+  };
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_verified_email(&RegisterWithVerifiedEmailOptions {
+      email_token: token,
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  let acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .auth
+    .as_ref()
+    .request_email_change(
+      &acx,
+      &RequestEmailChangeOptions {
+        email: alicia_email.clone(),
+        locale: Some(LocaleId::FrFr),
+      },
+    )
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let token = {
+    let mut mailbox = api.mailer.as_ref().read_inbox(&alicia_email).into_iter();
+    let mail = mailbox.next().unwrap();
+    assert!(mailbox.next().is_none());
+    assert_eq!(mail.title.as_str(), "verifyEmailChangeEmail");
+
+    let body: JsonBody<VerifyEmailChangeEmail> = serde_json::from_str(mail.body_text.as_str()).unwrap();
+    body.data.token
+  };
+  // An email change token is not a registration token
+  let actual = api
+    .auth
+    .as_ref()
+    .register_with_verified_email(&RegisterWithVerifiedEmailOptions {
+      email_token: token.clone(),
+      display_name: "Alicia".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidTokenAudience");
+  let actual = api
+    .auth
+    .as_ref()
+    .change_email_with_token(
+      &acx,
+      &ChangeEmailWithTokenOptions {
+        email_token: token.clone(),
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(actual.id, alice.user.id);
+  assert_eq!(actual.email_address, Some(alicia_email));
+  // The token is rejected once the change is applied
+  let actual = api
+    .auth
+    .as_ref()
+    .change_email_with_token(&acx, &ChangeEmailWithTokenOptions { email_token: token })
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "Conflict: EmailTokenOutdated");
+
+  let mut mailbox = api.mailer.as_ref().read_inbox(&alice_email).into_iter().skip(1);
+  let mail = mailbox.next().unwrap();
+  assert!(mailbox.next().is_none());
+  assert_eq!(mail.title.as_str(), "notifyEmailChangeEmail");
+  let body: JsonBody<NotifyEmailChangeEmail> = serde_json::from_str(mail.body_text.as_str()).unwrap();
+  assert_eq!(body.locale, LocaleId::FrFr);
+  assert_eq!(body.data.changed_at, Instant::ymd_hms(2021, 1, 1, 0, 0, 2));
+}
+
+async fn change_email_when_the_notice_fails<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  // No inbox for the old address: the mailer rejects the notice
+  let alice_email: EmailAddress = "alice@example.com".parse().unwrap();
+  let alicia_email: EmailAddress = "alicia@example.com".parse().unwrap();
+  api.mailer.as_ref().create_inbox(alicia_email.clone());
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .user_store
+    .update_user(&UpdateUserOptions {
+      r#ref: alice.user.id.into(),
+      actor: alice.user.id.into(),
+      patch: UpdateUserPatch {
+        display_name: None,
+        username: None,
+        password: None,
+        email: Some(Some(alice_email)),
+      },
+    })
+    .await
+    .unwrap();
+  let acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+  api
+    .auth
+    .as_ref()
+    .request_email_change(
+      &acx,
+      &RequestEmailChangeOptions {
+        email: alicia_email.clone(),
+        locale: None,
+      },
+    )
+    .await
+    .unwrap();
+  let token = {
+    let mail = api
+      .mailer
+      .as_ref()
+      .read_inbox(&alicia_email)
+      .into_iter()
+      .next()
+      .unwrap();
+    let body: JsonBody<VerifyEmailChangeEmail> = serde_json::from_str(mail.body_text.as_str()).unwrap();
+    body.data.token
+  };
+  api.logger.take();
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let actual = api
+    .auth
+    .as_ref()
+    .change_email_with_token(&acx, &ChangeEmailWithTokenOptions { email_token: token })
+    .await
+    .unwrap();
+  assert_eq!(actual.email_address, Some(alicia_email));
+  assert_eq!(
+    api.logger.take(),
+    vec![AuthEvent::EmailChangeNoticeFailed {
+      user: alice.user.id.into(),
+      error: "RecipientNotFound".to_string(),
+    }]
+  );
+}
+
 async fn register_user_with_username<TyClock>(
   api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
 ) where
@@ -328,12 +602,19 @@ async fn register_user_with_username_and_sign_in<TyClock>(
   let actual = api
     .auth
     .as_ref()
-    .raw_login_with_credentials(&RawUserCredentials {
-      login: "alice".to_string(),
-      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
-    })
+    .raw_login_with_credentials(
+      &RawUserCredentials {
+        login: "alice".to_string(),
+        password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+      },
+      None,
+    )
     .await
     .unwrap();
+  let actual = match actual {
+    LoginResult::Session(user_and_session) => user_and_session,
+    LoginResult::PendingMfa(_) => panic!("AssertionError: Expected login without second factor"),
+  };
   let expected = UserAndSession {
     user: ShortUser {
       id: actual.user.id,
@@ -361,6 +642,703 @@ async fn register_user_with_username_and_sign_in<TyClock>(
   assert_eq!(actual, expected);
 }
 
+async fn sign_in_with_totp<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  let acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+  let credentials = RawUserCredentials {
+    login: "alice".to_string(),
+    password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+  };
+  let current_code = |secret: &TotpSecret| totp::hotp(secret, totp::time_step(api.clock.as_ref().now()));
+
+  let enrollment = api.auth.as_ref().enroll_totp(&acx).await.unwrap();
+  assert_eq!(enrollment.otpauth_uri.scheme(), "otpauth");
+  let recovery_codes = api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: enrollment.secret.clone(),
+        code: current_code(&enrollment.secret),
+        current_code: None,
+      },
+    )
+    .await
+    .unwrap()
+    .recovery_codes;
+  assert_eq!(recovery_codes.len(), 10);
+
+  api.clock.as_ref().advance_by(Duration::seconds(30));
+  let pending = start_mfa_login(api.auth.as_ref(), &credentials).await;
+  assert_eq!(pending.expires_at, Instant::ymd_hms(2021, 1, 1, 0, 5, 30));
+  let actual = api
+    .auth
+    .as_ref()
+    .complete_mfa_login(
+      &CompleteMfaLoginOptions {
+        token: pending.token.clone(),
+        proof: MfaProof::Totp(current_code(&enrollment.secret)),
+      },
+      None,
+    )
+    .await
+    .unwrap();
+  assert_eq!(actual.user, alice.user);
+
+  // The token is consumed by the successful proof
+  let actual = api
+    .auth
+    .as_ref()
+    .complete_mfa_login(
+      &CompleteMfaLoginOptions {
+        token: pending.token,
+        proof: MfaProof::RecoveryCode(recovery_codes[1].clone()),
+      },
+      None,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidMfaLoginToken");
+
+  // Replayed TOTP code
+  let pending = start_mfa_login(api.auth.as_ref(), &credentials).await;
+  let actual = api
+    .auth
+    .as_ref()
+    .complete_mfa_login(
+      &CompleteMfaLoginOptions {
+        token: pending.token.clone(),
+        proof: MfaProof::Totp(current_code(&enrollment.secret)),
+      },
+      None,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidTotpCode");
+
+  let recovery = CompleteMfaLoginOptions {
+    token: pending.token,
+    proof: MfaProof::RecoveryCode(recovery_codes[0].clone()),
+  };
+  let actual = api.auth.as_ref().complete_mfa_login(&recovery, None).await.unwrap();
+  assert_eq!(actual.user, alice.user);
+  // Recovery codes are single-use
+  let pending = start_mfa_login(api.auth.as_ref(), &credentials).await;
+  let recovery = CompleteMfaLoginOptions {
+    token: pending.token,
+    proof: MfaProof::RecoveryCode(recovery_codes[0].clone()),
+  };
+  let actual = api.auth.as_ref().complete_mfa_login(&recovery, None).await.unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidRecoveryCode");
+
+  // HTTP Basic authentication has no second step
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_authenticate_credentials(
+      &RawCredentials {
+        login: "alice".to_string(),
+        password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+      },
+      None,
+    )
+    .await;
+  assert!(actual.is_err());
+}
+
+async fn mfa_login_throttling<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  let alice_ref: UserIdRef = alice.user.id.into();
+  let acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+  let credentials = RawUserCredentials {
+    login: "alice".to_string(),
+    password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+  };
+  let current_code = |secret: &TotpSecret| totp::hotp(secret, totp::time_step(api.clock.as_ref().now()));
+  let enrollment = api.auth.as_ref().enroll_totp(&acx).await.unwrap();
+  api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: enrollment.secret.clone(),
+        code: current_code(&enrollment.secret),
+        current_code: None,
+      },
+    )
+    .await
+    .unwrap();
+  api.logger.take();
+  let ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)));
+  let wrong_code = |token: &str| CompleteMfaLoginOptions {
+    token: token.to_string(),
+    proof: MfaProof::RecoveryCode("aaaaa-aaaaa".parse().unwrap()),
+  };
+
+  // A token accepts a limited number of proofs, even if the last one is valid
+  api.clock.as_ref().advance_by(Duration::seconds(30));
+  let pending = start_mfa_login(api.auth.as_ref(), &credentials).await;
+  for _ in 0..3 {
+    let actual = api
+      .auth
+      .as_ref()
+      .complete_mfa_login(&wrong_code(&pending.token), ip)
+      .await
+      .unwrap_err();
+    assert_eq!(actual.to_string(), "InvalidRecoveryCode");
+  }
+  let actual = api
+    .auth
+    .as_ref()
+    .complete_mfa_login(
+      &CompleteMfaLoginOptions {
+        token: pending.token,
+        proof: MfaProof::Totp(current_code(&enrollment.secret)),
+      },
+      ip,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "TooManyMfaLoginAttempts");
+  let failure = AuthEvent::LoginFailed {
+    user: Some(alice_ref),
+    oauth_client: None,
+    ip,
+    reason: LoginFailureReason::WrongSecondFactor,
+  };
+  assert_eq!(api.logger.take(), vec![failure.clone(); 3]);
+
+  // Wrong second factors count against the same limit as wrong passwords
+  let pending = start_mfa_login(api.auth.as_ref(), &credentials).await;
+  for _ in 0..2 {
+    let actual = api
+      .auth
+      .as_ref()
+      .complete_mfa_login(&wrong_code(&pending.token), ip)
+      .await;
+    assert!(actual.is_err());
+  }
+  let mut expected = vec![failure; 2];
+  expected.push(AuthEvent::LoginLockedOut {
+    key: LoginThrottleKey::User(alice_ref),
+    until: Instant::ymd_hms(2021, 1, 1, 0, 15, 30),
+  });
+  assert_eq!(api.logger.take(), expected);
+  let actual = api
+    .auth
+    .as_ref()
+    .complete_mfa_login(
+      &CompleteMfaLoginOptions {
+        token: pending.token,
+        proof: MfaProof::Totp(current_code(&enrollment.secret)),
+      },
+      ip,
+    )
+    .await
+    .unwrap_err();
+  assert!(actual.is::<LoginThrottledError>());
+}
+
+async fn replace_totp<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  let acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+  let current_code = |secret: &TotpSecret| totp::hotp(secret, totp::time_step(api.clock.as_ref().now()));
+
+  // Only secrets of the generated size are accepted
+  let short_secret = TotpSecret(vec![0u8; 4]);
+  let actual = api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: short_secret.clone(),
+        code: current_code(&short_secret),
+        current_code: None,
+      },
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidTotpSecret");
+
+  let first = api.auth.as_ref().enroll_totp(&acx).await.unwrap();
+  api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: first.secret.clone(),
+        code: current_code(&first.secret),
+        current_code: None,
+      },
+    )
+    .await
+    .unwrap();
+
+  // Replacing an enabled TOTP requires a code from the current authenticator
+  api.clock.as_ref().advance_by(Duration::seconds(30));
+  let second = api.auth.as_ref().enroll_totp(&acx).await.unwrap();
+  let actual = api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: second.secret.clone(),
+        code: current_code(&second.secret),
+        current_code: None,
+      },
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "TotpAlreadyEnabled");
+  let actual = api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: second.secret.clone(),
+        code: current_code(&second.secret),
+        current_code: Some(current_code(&second.secret)),
+      },
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidTotpCode");
+  api
+    .auth
+    .as_ref()
+    .confirm_totp_enrollment(
+      &acx,
+      &ConfirmTotpEnrollmentOptions {
+        secret: second.secret.clone(),
+        code: current_code(&second.secret),
+        current_code: Some(current_code(&first.secret)),
+      },
+    )
+    .await
+    .unwrap();
+
+  // Only the new authenticator is accepted afterwards
+  api.clock.as_ref().advance_by(Duration::seconds(30));
+  let actual = api
+    .auth
+    .as_ref()
+    .disable_totp(
+      &acx,
+      &DisableTotpOptions {
+        code: current_code(&first.secret),
+      },
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(actual.to_string(), "InvalidTotpCode");
+  api
+    .auth
+    .as_ref()
+    .disable_totp(
+      &acx,
+      &DisableTotpOptions {
+        code: current_code(&second.secret),
+      },
+    )
+    .await
+    .unwrap();
+}
+
+async fn start_mfa_login(auth: &DynAuthService, credentials: &RawUserCredentials) -> PendingMfaLogin {
+  match auth.raw_login_with_credentials(credentials, None).await.unwrap() {
+    LoginResult::PendingMfa(pending) => pending,
+    LoginResult::Session(_) => panic!("AssertionError: Expected pending second factor"),
+  }
+}
+
+async fn login_throttling<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice: UserIdRef = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap()
+    .user
+    .id
+    .into();
+  assert_eq!(api.logger.take(), vec![AuthEvent::UserRegistered { user: alice }]);
+  let good = RawUserCredentials {
+    login: "alice".to_string(),
+    password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+  };
+  let bad = RawUserCredentials {
+    login: "alice".to_string(),
+    password: Password("bbbbbbbbbb".as_bytes().to_vec()),
+  };
+  let ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)));
+
+  for _ in 0..5 {
+    api.clock.as_ref().advance_by(Duration::seconds(1));
+    let actual = api
+      .auth
+      .as_ref()
+      .raw_login_with_credentials(&bad, ip)
+      .await
+      .unwrap_err();
+    assert!(!actual.is::<LoginThrottledError>());
+  }
+
+  // The account is locked: even the right password is rejected, from any address
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_login_with_credentials(&good, None)
+    .await
+    .unwrap_err();
+  let actual = actual.downcast::<LoginThrottledError>().unwrap();
+  assert_eq!(actual.retry_after, Instant::ymd_hms(2021, 1, 1, 0, 15, 5));
+  {
+    let failure = AuthEvent::LoginFailed {
+      user: Some(alice),
+      oauth_client: None,
+      ip,
+      reason: LoginFailureReason::WrongPassword,
+    };
+    let mut expected = vec![failure; 5];
+    expected.push(AuthEvent::LoginLockedOut {
+      key: LoginThrottleKey::User(alice),
+      until: Instant::ymd_hms(2021, 1, 1, 0, 15, 5),
+    });
+    expected.push(AuthEvent::LoginThrottled {
+      key: LoginThrottleKey::User(alice),
+      retry_after: Instant::ymd_hms(2021, 1, 1, 0, 15, 5),
+    });
+    assert_eq!(api.logger.take(), expected);
+  }
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_authenticate_credentials(
+      &RawCredentials {
+        login: "alice".to_string(),
+        password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+      },
+      None,
+    )
+    .await
+    .unwrap_err();
+  assert!(actual.is::<LoginThrottledError>());
+
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 15, 5));
+  let actual = api.auth.as_ref().raw_login_with_credentials(&good, ip).await;
+  assert!(matches!(actual, Ok(LoginResult::Session(_))));
+
+  // Failures for unknown logins are counted against the client address
+  for _ in 0..20 {
+    let unknown = RawCredentials {
+      login: "bob".to_string(),
+      password: Password("bbbbbbbbbb".as_bytes().to_vec()),
+    };
+    let actual = api.auth.as_ref().raw_authenticate_credentials(&unknown, ip).await;
+    assert!(actual.is_err());
+  }
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_login_with_credentials(&good, ip)
+    .await
+    .unwrap_err();
+  assert!(actual.is::<LoginThrottledError>());
+  let other_ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 2)));
+  let actual = api.auth.as_ref().raw_login_with_credentials(&good, other_ip).await;
+  assert!(matches!(actual, Ok(LoginResult::Session(_))));
+
+  // A UUID matching neither a user nor a client is a single failed attempt
+  api.logger.take();
+  let unknown_uuid = RawCredentials {
+    login: "00000000-0000-0000-0000-000000000001".to_string(),
+    password: Password("bbbbbbbbbb".as_bytes().to_vec()),
+  };
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_authenticate_credentials(&unknown_uuid, other_ip)
+    .await;
+  assert!(actual.is_err());
+  assert_eq!(
+    api.logger.take(),
+    vec![AuthEvent::LoginFailed {
+      user: None,
+      oauth_client: None,
+      ip: other_ip,
+      reason: LoginFailureReason::UnknownLogin,
+    }]
+  );
+}
+
+async fn rehash_legacy_password_on_login<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let legacy = ScryptPasswordService::recommended_for_tests();
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      email: None,
+      username: Some("alice".parse().unwrap()),
+      password: Some(legacy.hash(Password("aaaaaaaaaa".as_bytes().to_vec()))),
+    })
+    .await
+    .unwrap();
+  // Wait for the end of the password lock period
+  api.clock.as_ref().advance_by(Duration::days(1));
+
+  let credentials = RawUserCredentials {
+    login: "alice".to_string(),
+    password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+  };
+  let actual = api.auth.as_ref().raw_login_with_credentials(&credentials, None).await;
+  assert!(matches!(actual, Ok(LoginResult::Session(_))));
+
+  let stored = api
+    .user_store
+    .get_user_with_password(&GetUserOptions {
+      r#ref: UserRef::Id(alice.id.into()),
+      fields: UserFields::Complete,
+      time: None,
+    })
+    .await
+    .unwrap()
+    .unwrap()
+    .password
+    .unwrap();
+  assert!(stored.as_slice().starts_with(b"argon2id"));
+
+  let actual = api.auth.as_ref().raw_login_with_credentials(&credentials, None).await;
+  assert!(matches!(actual, Ok(LoginResult::Session(_))));
+
+  // The upgrade is not a password change: it does not lock the password
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let actual = api
+    .user_store
+    .update_user(&UpdateUserOptions {
+      r#ref: alice.id.into(),
+      actor: alice.id.into(),
+      patch: UpdateUserPatch {
+        display_name: None,
+        username: None,
+        password: Some(Some(legacy.hash(Password("bbbbbbbbbb".as_bytes().to_vec())))),
+        email: None,
+      },
+    })
+    .await;
+  assert!(actual.is_ok());
+}
+
+async fn update_user_password<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "alice".parse().unwrap(),
+      display_name: "Alice".parse().unwrap(),
+      password: Password("aaaaaaaaaa".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  let bob = api
+    .auth
+    .as_ref()
+    .register_with_username(&RegisterWithUsernameOptions {
+      username: "bob".parse().unwrap(),
+      display_name: "Bob".parse().unwrap(),
+      password: Password("bbbbbbbbbb".as_bytes().to_vec()),
+    })
+    .await
+    .unwrap();
+  // Wait for the end of the password lock period
+  api.clock.as_ref().advance_by(Duration::days(1));
+
+  let bob_acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: bob.user.clone(),
+    is_administrator: bob.is_administrator,
+  });
+  let other_bob_session = match api
+    .auth
+    .as_ref()
+    .raw_login_with_credentials(
+      &RawUserCredentials {
+        login: "bob".to_string(),
+        password: Password("bbbbbbbbbb".as_bytes().to_vec()),
+      },
+      None,
+    )
+    .await
+  {
+    Ok(LoginResult::Session(session)) => session.session.id,
+    actual => panic!("unexpected login result: {:?}", actual),
+  };
+  let patch = RawUpdateUserPatch {
+    display_name: None,
+    username: None,
+    password: Some(Some(Password("cccccccccc".as_bytes().to_vec()))),
+    current_password: None,
+  };
+
+  let actual = api
+    .auth
+    .as_ref()
+    .update_user(&bob_acx, alice.user.id.into(), &patch, None)
+    .await;
+  assert!(matches!(actual, Err(PatchUserError::Forbidden)));
+
+  let actual = api
+    .auth
+    .as_ref()
+    .update_user(&bob_acx, bob.user.id.into(), &patch, Some(bob.session.id))
+    .await;
+  assert!(matches!(actual, Err(PatchUserError::CurrentPasswordRequired)));
+
+  let wrong_patch = RawUpdateUserPatch {
+    current_password: Some(Password("xxxxxxxxxx".as_bytes().to_vec())),
+    ..patch.clone()
+  };
+  let actual = api
+    .auth
+    .as_ref()
+    .update_user(&bob_acx, bob.user.id.into(), &wrong_patch, Some(bob.session.id))
+    .await;
+  assert!(matches!(actual, Err(PatchUserError::WrongCurrentPassword)));
+
+  let patch = RawUpdateUserPatch {
+    current_password: Some(Password("bbbbbbbbbb".as_bytes().to_vec())),
+    ..patch
+  };
+  let actual = api
+    .auth
+    .as_ref()
+    .update_user(&bob_acx, bob.user.id.into(), &patch, Some(bob.session.id))
+    .await;
+  assert!(actual.is_ok());
+
+  // The current session is kept, the other sessions of Bob are revoked.
+  let actual = api.auth.as_ref().authenticate_session(bob.session.id).await.unwrap();
+  assert!(actual.is_some());
+  let actual = api.auth.as_ref().authenticate_session(other_bob_session).await.unwrap();
+  assert!(actual.is_none());
+
+  let actual = api
+    .auth
+    .as_ref()
+    .raw_login_with_credentials(
+      &RawUserCredentials {
+        login: "bob".to_string(),
+        password: Password("cccccccccc".as_bytes().to_vec()),
+      },
+      None,
+    )
+    .await;
+  assert!(matches!(actual, Ok(LoginResult::Session(_))));
+
+  // Administrators change passwords without the current password, and revoke all the sessions of the user.
+  assert!(alice.is_administrator);
+  api.clock.as_ref().advance_by(Duration::days(1));
+  let alice_acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.user.clone(),
+    is_administrator: alice.is_administrator,
+  });
+  let admin_patch = RawUpdateUserPatch {
+    display_name: None,
+    username: None,
+    password: Some(Some(Password("dddddddddd".as_bytes().to_vec()))),
+    current_password: None,
+  };
+  let actual = api
+    .auth
+    .as_ref()
+    .update_user(&alice_acx, bob.user.id.into(), &admin_patch, Some(alice.session.id))
+    .await;
+  assert!(actual.is_ok());
+  let actual = api.auth.as_ref().authenticate_session(bob.session.id).await.unwrap();
+  assert!(actual.is_none());
+  let actual = api.auth.as_ref().authenticate_session(alice.session.id).await.unwrap();
+  assert!(actual.is_some());
+}
+
 async fn register_user_with_hammerfest<TyClock>(
   api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
 ) where
@@ -409,4 +1387,79 @@ async fn register_user_with_hammerfest<TyClock>(
     },
   };
   assert_eq!(actual, expected);
+  let session = api
+    .token_store
+    .get_hammerfest(HammerfestUserIdRef {
+      server: HammerfestServer::HammerfestFr,
+      id: "123".parse().unwrap(),
+    })
+    .await
+    .unwrap();
+  assert!(session.is_some());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_register_user_with_twinoid_oauth() {
+  register_user_with_twinoid_oauth(make_test_api().await).await;
+}
+
+async fn register_user_with_twinoid_oauth<TyClock>(
+  api: TestApi<impl ApiRef<DynAuthService>, TyClock, impl ApiRef<MemHammerfestClient<TyClock>>, impl ApiRef<MemMailer>>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let tid_user = api
+    .twinoid_client
+    .create_user(twinoid::api::PartialUser {
+      id: 123,
+      name: Some("alice".parse().unwrap()),
+      title: None,
+      picture: None,
+      locale: Some("fr".to_string()),
+      contacts: None,
+      sites: Some(vec![]),
+    })
+    .unwrap();
+  let access_token: RfcOauthAccessTokenKey = "aaaaa".parse().unwrap();
+  api.twinoid_client.create_token(access_token.clone(), tid_user);
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let actual = api
+    .auth
+    .as_ref()
+    .register_or_login_with_twinoid_oauth(&RfcOauthAccessToken {
+      token_type: RfcOauthTokenType::Bearer,
+      access_token,
+      expires_in: 3600,
+      refresh_token: None,
+    })
+    .await
+    .unwrap();
+  assert_eq!(actual.session.ctime, Instant::ymd_hms(2021, 1, 1, 0, 0, 1));
+  assert_eq!(
+    api.logger.take(),
+    vec![AuthEvent::UserRegistered {
+      user: actual.user.id.into()
+    }]
+  );
+  let actual = api
+    .twinoid_store
+    .get_user_profile(&GetTwinoidUserOptions {
+      id: tid_user,
+      time: None,
+    })
+    .await
+    .unwrap();
+  let expected = Some(ArchivedTwinoidUserProfile {
+    archived_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+    profile: TwinoidUserProfile {
+      id: tid_user,
+      display_name: "alice".parse().unwrap(),
+      title: None,
+      locale: Some("fr".to_string()),
+      sites: vec![],
+    },
+  });
+  assert_eq!(actual, expected);
 }
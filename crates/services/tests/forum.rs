@@ -4,27 +4,33 @@ use etwin_core::auth::{AuthContext, AuthScope, GuestAuthContext, UserAuthContext
 use etwin_core::clock::VirtualClock;
 use etwin_core::core::{Instant, Listing, ListingCount, LocaleId, Secret};
 use etwin_core::forum::{
-  AddModeratorOptions, CreatePostOptions, CreateThreadOptions, ForumActor, ForumPost, ForumPostListing,
-  ForumPostRevision, ForumPostRevisionContent, ForumRole, ForumRoleGrant, ForumSection, ForumSectionListing,
-  ForumSectionMeta, ForumSectionSelf, ForumStore, ForumThread, GetForumSectionOptions, GetThreadOptions,
-  LatestForumPostRevisionListing, ShortForumPost, UpsertSystemSectionOptions, UserForumActor,
+  AddModeratorOptions, CreatePostError, CreatePostOptions, CreateThreadOptions, ForumActor, ForumPost,
+  ForumPostListing, ForumPostRevision, ForumPostRevisionContent, ForumRole, ForumRoleGrant, ForumSection,
+  ForumSectionKeyRef, ForumSectionListing, ForumSectionMeta, ForumSectionRef, ForumSectionSelf, ForumStore,
+  ForumThread, ForumThreadId, ForumThreadRef, GetForumSectionOptions, GetThreadOptions, LatestForumPostRevisionListing,
+  ShortForumPost, UpsertSystemSectionOptions, UserForumActor,
 };
 use etwin_core::user::{CreateUserOptions, ShortUser, UserStore};
 use etwin_core::uuid::Uuid4Generator;
 use etwin_db_schema::force_create_latest;
+use etwin_forum_store::mem::MemForumStore;
 use etwin_forum_store::pg::PgForumStore;
-use etwin_services::forum::ForumService;
+use etwin_log::VecLogger;
+use etwin_services::forum::{ForumEvent, ForumService, GetSectionError, GetThreadError};
+use etwin_user_store::mem::MemUserStore;
 use etwin_user_store::pg::PgUserStore;
 use serial_test::serial;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::PgPool;
 use std::sync::Arc;
 
-async fn make_test_api() -> TestApi<
-  Arc<ForumService<Arc<VirtualClock>, Arc<dyn ForumStore>, Arc<dyn UserStore>>>,
+type ForumTestApi = TestApi<
+  Arc<ForumService<Arc<VirtualClock>, Arc<dyn ForumStore>, Arc<VecLogger<ForumEvent>>, Arc<dyn UserStore>>>,
   Arc<dyn ForumStore>,
   Arc<dyn UserStore>,
-> {
+>;
+
+async fn make_test_api() -> ForumTestApi {
   let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
   let admin_database: PgPool = PgPoolOptions::new()
     .max_connections(5)
@@ -69,28 +75,54 @@ async fn make_test_api() -> TestApi<
     Secret::new("dev_secret".to_string()),
     Arc::clone(&uuid),
   ));
+  let logger = Arc::new(VecLogger::new());
   let forum = Arc::new(ForumService::new(
     Arc::clone(&clock),
     Arc::clone(&forum_store),
+    Arc::clone(&logger),
     Arc::clone(&user_store),
   ));
 
   TestApi {
     clock,
     forum,
+    logger,
     _forum_store: Arc::clone(&forum_store),
     user_store: Arc::clone(&user_store),
   }
 }
 
+fn make_mem_test_api() -> ForumTestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+  let uuid_generator = Arc::new(Uuid4Generator);
+  let forum_store: Arc<dyn ForumStore> = Arc::new(MemForumStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator)));
+  let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Arc::clone(&uuid_generator)));
+  let logger = Arc::new(VecLogger::new());
+  let forum = Arc::new(ForumService::new(
+    Arc::clone(&clock),
+    Arc::clone(&forum_store),
+    Arc::clone(&logger),
+    Arc::clone(&user_store),
+  ));
+
+  TestApi {
+    clock,
+    forum,
+    logger,
+    _forum_store: forum_store,
+    user_store,
+  }
+}
+
 struct TestApi<TyForum, TyForumStore, TyUserStore>
 where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
   pub(crate) clock: Arc<VirtualClock>,
   pub(crate) forum: TyForum,
+  pub(crate) logger: Arc<VecLogger<ForumEvent>>,
   pub(crate) _forum_store: TyForumStore,
   pub(crate) user_store: TyUserStore,
 }
@@ -101,10 +133,15 @@ async fn test_create_main_forum_section() {
   inner_test_create_main_forum_section(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_create_main_forum_section() {
+  inner_test_create_main_forum_section(make_mem_test_api()).await;
+}
+
 async fn inner_test_create_main_forum_section<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -143,10 +180,15 @@ async fn test_upsert_forum_section_idempotent() {
   inner_test_upsert_forum_section_idempotent(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_upsert_forum_section_idempotent() {
+  inner_test_upsert_forum_section_idempotent(make_mem_test_api()).await;
+}
+
 async fn inner_test_upsert_forum_section_idempotent<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -196,10 +238,15 @@ async fn test_empty_get_all_sections_as_guest() {
   inner_test_empty_get_all_sections_as_guest(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_empty_get_all_sections_as_guest() {
+  inner_test_empty_get_all_sections_as_guest(make_mem_test_api()).await;
+}
+
 async fn inner_test_empty_get_all_sections_as_guest<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -227,10 +274,15 @@ async fn test_upsert_section_then_get_all_sections_as_guest() {
   inner_test_upsert_section_then_get_all_sections_as_guest(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_upsert_section_then_get_all_sections_as_guest() {
+  inner_test_upsert_section_then_get_all_sections_as_guest(make_mem_test_api()).await;
+}
+
 async fn inner_test_upsert_section_then_get_all_sections_as_guest<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -277,10 +329,15 @@ async fn test_upsert_section_then_get_it_as_guest() {
   inner_test_upsert_section_then_get_it_as_guest(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_upsert_section_then_get_it_as_guest() {
+  inner_test_upsert_section_then_get_it_as_guest(make_mem_test_api()).await;
+}
+
 async fn inner_test_upsert_section_then_get_it_as_guest<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -335,10 +392,15 @@ async fn test_create_thread_in_the_main_section() {
   inner_test_create_thread_in_the_main_section(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_create_thread_in_the_main_section() {
+  inner_test_create_thread_in_the_main_section(make_mem_test_api()).await;
+}
+
 async fn inner_test_create_thread_in_the_main_section<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -433,6 +495,15 @@ async fn inner_test_create_thread_in_the_main_section<TyForum, TyForumStore, TyU
     },
   };
   assert_eq!(actual, expected);
+  assert_eq!(
+    api.logger.take(),
+    vec![ForumEvent::ThreadCreated {
+      section: section.id,
+      thread: actual.id,
+      post: actual.posts.items[0].id,
+      author: alice.id,
+    }]
+  );
 }
 
 #[tokio::test]
@@ -441,10 +512,15 @@ async fn test_create_two_sections_but_create_a_thread_in_only_one_of_them() {
   inner_test_create_two_sections_but_create_a_thread_in_only_one_of_them(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_create_two_sections_but_create_a_thread_in_only_one_of_them() {
+  inner_test_create_two_sections_but_create_a_thread_in_only_one_of_them(make_mem_test_api()).await;
+}
+
 async fn inner_test_create_two_sections_but_create_a_thread_in_only_one_of_them<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -539,10 +615,15 @@ async fn test_create_thread_in_the_main_section_and_post_10_messages() {
   inner_test_create_thread_in_the_main_section_and_post_10_messages(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn test_mem_create_thread_in_the_main_section_and_post_10_messages() {
+  inner_test_create_thread_in_the_main_section_and_post_10_messages(make_mem_test_api()).await;
+}
+
 async fn inner_test_create_thread_in_the_main_section_and_post_10_messages<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -605,6 +686,21 @@ async fn inner_test_create_thread_in_the_main_section_and_post_10_messages<TyFor
     posts.push(post);
   }
   assert_eq!(posts.len(), 10);
+  {
+    let events = api.logger.take();
+    assert_eq!(events.len(), 11);
+    assert!(matches!(events[0], ForumEvent::ThreadCreated { thread: t, .. } if t == thread.id));
+    let expected: Vec<ForumEvent> = posts
+      .iter()
+      .map(|post| ForumEvent::PostCreated {
+        section: section.id,
+        thread: thread.id,
+        post: post.id,
+        author: alice.id,
+      })
+      .collect();
+    assert_eq!(&events[1..], expected.as_slice());
+  }
   let actual = api
     .forum
     .as_ref()
@@ -777,10 +873,15 @@ async fn administrators_can_add_moderators() {
   inner_administrators_can_add_moderators(make_test_api().await).await;
 }
 
+#[tokio::test]
+async fn mem_administrators_can_add_moderators() {
+  inner_administrators_can_add_moderators(make_mem_test_api()).await;
+}
+
 async fn inner_administrators_can_add_moderators<TyForum, TyForumStore, TyUserStore>(
   api: TestApi<TyForum, TyForumStore, TyUserStore>,
 ) where
-  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, TyUserStore>>,
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
   TyForumStore: ForumStore,
   TyUserStore: UserStore,
 {
@@ -862,4 +963,97 @@ async fn inner_administrators_can_add_moderators<TyForum, TyForumStore, TyUserSt
     },
   };
   assert_eq!(actual, expected);
+  assert_eq!(
+    api.logger.take(),
+    vec![ForumEvent::ModeratorAdded {
+      section: section.id,
+      grantee: bob.id,
+      granter: alice.id,
+    }]
+  );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_missing_section_and_thread() {
+  inner_test_missing_section_and_thread(make_test_api().await).await;
+}
+
+#[tokio::test]
+async fn test_mem_missing_section_and_thread() {
+  inner_test_missing_section_and_thread(make_mem_test_api()).await;
+}
+
+async fn inner_test_missing_section_and_thread<TyForum, TyForumStore, TyUserStore>(
+  api: TestApi<TyForum, TyForumStore, TyUserStore>,
+) where
+  TyForum: ApiRef<ForumService<Arc<VirtualClock>, TyForumStore, Arc<VecLogger<ForumEvent>>, TyUserStore>>,
+  TyForumStore: ForumStore,
+  TyUserStore: UserStore,
+{
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      email: None,
+      username: Some("alice".parse().unwrap()),
+      password: None,
+    })
+    .await
+    .unwrap();
+  let alice_acx = AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: alice.into(),
+    is_administrator: true,
+  });
+  let guest_acx = AuthContext::Guest(GuestAuthContext {
+    scope: AuthScope::Default,
+  });
+
+  let actual = api
+    .forum
+    .as_ref()
+    .get_section(
+      &guest_acx,
+      &GetForumSectionOptions {
+        section: ForumSectionRef::Key(ForumSectionKeyRef {
+          key: "missing".parse().unwrap(),
+        }),
+        thread_offset: 0,
+        thread_limit: 20,
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(GetSectionError::SectionNotFound)));
+
+  let thread: ForumThreadRef = "00000000-0000-0000-0000-000000000000"
+    .parse::<ForumThreadId>()
+    .unwrap()
+    .into();
+  let actual = api
+    .forum
+    .as_ref()
+    .get_thread(
+      &guest_acx,
+      &GetThreadOptions {
+        thread: thread.clone(),
+        post_offset: 0,
+        post_limit: 10,
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(GetThreadError::ThreadNotFound)));
+
+  let actual = api
+    .forum
+    .as_ref()
+    .create_post(
+      &alice_acx,
+      &CreatePostOptions {
+        thread,
+        body: "Hello".to_string(),
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(CreatePostError::ThreadNotFound)));
 }
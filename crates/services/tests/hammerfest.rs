@@ -1,6 +1,6 @@
 use etwin_core::api::ApiRef;
 use etwin_core::core::Secret;
-use etwin_core::hammerfest::{HammerfestClient, HammerfestStore, HammerfestUser};
+use etwin_core::hammerfest::{HammerfestClient, HammerfestPassword, HammerfestStore, HammerfestUser};
 use etwin_core::link::LinkStore;
 use etwin_core::user::UserStore;
 use etwin_core::uuid::Uuid4Generator;
@@ -22,13 +22,22 @@ use std::sync::Arc;
 
 use etwin_core::auth::{AuthScope, GuestAuthContext};
 use etwin_core::core::Instant;
-use etwin_services::hammerfest::HammerfestService;
+use etwin_log::{NoopLogger, VecLogger};
+use etwin_services::hammerfest::{HammerfestEvent, HammerfestService};
 
 async fn make_test_api() -> TestApi<
   Arc<VirtualClock>,
   Arc<dyn HammerfestClient>,
   Arc<dyn HammerfestStore>,
-  Arc<HammerfestService<Arc<dyn HammerfestClient>, Arc<dyn HammerfestStore>, Arc<dyn LinkStore>, Arc<dyn UserStore>>>,
+  Arc<
+    HammerfestService<
+      Arc<dyn HammerfestClient>,
+      Arc<dyn HammerfestStore>,
+      Arc<dyn LinkStore>,
+      Arc<VecLogger<HammerfestEvent>>,
+      Arc<dyn UserStore>,
+    >,
+  >,
   Arc<dyn LinkStore>,
   Arc<dyn UserStore>,
 > {
@@ -84,10 +93,12 @@ async fn make_test_api() -> TestApi<
     Secret::new("dev_secret".to_string()),
     Arc::clone(&uuid),
   ));
+  let logger = Arc::new(VecLogger::new());
   let hammerfest = Arc::new(HammerfestService::new(
     Arc::clone(&hammerfest_client),
     Arc::clone(&hammerfest_store),
     Arc::clone(&link_store),
+    Arc::clone(&logger),
     Arc::clone(&user_store),
   ));
 
@@ -97,6 +108,7 @@ async fn make_test_api() -> TestApi<
     _hammerfest_store: hammerfest_store,
     hammerfest,
     _link_store: PhantomData,
+    logger,
     _user_store: PhantomData,
   }
 }
@@ -108,13 +120,16 @@ where
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
   TyUserStore: UserStore,
-  TyHammerfest: ApiRef<HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>>,
+  TyHammerfest: ApiRef<
+    HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, Arc<VecLogger<HammerfestEvent>>, TyUserStore>,
+  >,
 {
   pub(crate) _clock: TyClock,
   pub(crate) _hammerfest_client: TyHammerfestClient,
   pub(crate) _hammerfest_store: TyHammerfestStore,
   pub(crate) hammerfest: TyHammerfest,
   pub(crate) _link_store: PhantomData<TyLinkStore>,
+  pub(crate) logger: Arc<VecLogger<HammerfestEvent>>,
   pub(crate) _user_store: PhantomData<TyUserStore>,
 }
 
@@ -132,7 +147,9 @@ async fn inner_test_empty<TyClock, TyHammerfestClient, TyHammerfestStore, TyHamm
   TyHammerfestStore: HammerfestStore,
   TyLinkStore: LinkStore,
   TyUserStore: UserStore,
-  TyHammerfest: ApiRef<HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, TyUserStore>>,
+  TyHammerfest: ApiRef<
+    HammerfestService<TyHammerfestClient, TyHammerfestStore, TyLinkStore, Arc<VecLogger<HammerfestEvent>>, TyUserStore>,
+  >,
 {
   let options = &GetHammerfestUserOptions {
     server: HammerfestServer::HammerfestFr,
@@ -152,6 +169,7 @@ async fn inner_test_empty<TyClock, TyHammerfestClient, TyHammerfestStore, TyHamm
     .unwrap();
   let expected: Option<HammerfestUser> = None;
   assert_eq!(actual, expected);
+  assert_eq!(api.logger.take(), vec![]);
 }
 
 #[tokio::test]
@@ -163,7 +181,8 @@ async fn test_reference_types() {
   let hammerfest_store = MemHammerfestStore::new(&clock);
   let link_store = MemLinkStore::new(&clock);
   let user_store = MemUserStore::new(&clock, &uuid);
-  let hammerfest = HammerfestService::new(&hammerfest_client, &hammerfest_store, &link_store, &user_store);
+  let logger = NoopLogger;
+  let hammerfest = HammerfestService::new(&hammerfest_client, &hammerfest_store, &link_store, &logger, &user_store);
 
   let options = &GetHammerfestUserOptions {
     server: HammerfestServer::HammerfestFr,
@@ -183,3 +202,79 @@ async fn test_reference_types() {
     None
   );
 }
+
+#[tokio::test]
+async fn test_mem_profile_fetch_events() {
+  let uuid = Uuid4Generator;
+  let clock = VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0));
+  let mut hammerfest_client = MemHammerfestClient::new(&clock);
+  hammerfest_client.create_user(
+    HammerfestServer::HammerfestFr,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    HammerfestPassword::new("aaaaa".to_string()),
+  );
+  hammerfest_client.disable_server(HammerfestServer::HammerfestEs);
+  let hammerfest_store = MemHammerfestStore::new(&clock);
+  let link_store = MemLinkStore::new(&clock);
+  let user_store = MemUserStore::new(&clock, &uuid);
+  let logger = VecLogger::new();
+  let hammerfest = HammerfestService::new(&hammerfest_client, &hammerfest_store, &link_store, &logger, &user_store);
+  let guest = AuthContext::Guest(GuestAuthContext {
+    scope: AuthScope::Default,
+  });
+
+  let actual = hammerfest
+    .get_user(
+      &guest,
+      &GetHammerfestUserOptions {
+        server: HammerfestServer::HammerfestFr,
+        id: "123".parse().unwrap(),
+        time: None,
+      },
+    )
+    .await
+    .unwrap();
+  assert!(actual.is_some());
+  assert_eq!(
+    logger.take(),
+    vec![HammerfestEvent::ProfileFetched {
+      server: HammerfestServer::HammerfestFr,
+      user: "123".parse().unwrap(),
+    }]
+  );
+
+  // The profile is now in the store: Hammerfest is not queried again
+  hammerfest
+    .get_user(
+      &guest,
+      &GetHammerfestUserOptions {
+        server: HammerfestServer::HammerfestFr,
+        id: "123".parse().unwrap(),
+        time: None,
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(logger.take(), vec![]);
+
+  let actual = hammerfest
+    .get_user(
+      &guest,
+      &GetHammerfestUserOptions {
+        server: HammerfestServer::HammerfestEs,
+        id: "456".parse().unwrap(),
+        time: None,
+      },
+    )
+    .await;
+  assert!(actual.is_err());
+  let events = logger.take();
+  assert!(matches!(
+    events.as_slice(),
+    [HammerfestEvent::ProfileFetchFailed {
+      server: HammerfestServer::HammerfestEs,
+      ..
+    }]
+  ));
+}
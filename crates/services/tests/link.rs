@@ -0,0 +1,332 @@
+use etwin_core::auth::{AuthContext, AuthScope, UserAuthContext};
+use etwin_core::clock::VirtualClock;
+use etwin_core::core::{Instant, RawUserDot};
+use etwin_core::dinoparc::{
+  DinoparcClient, DinoparcCredentials, DinoparcPassword, DinoparcServer, DinoparcStore, DinoparcUserIdRef,
+};
+use etwin_core::hammerfest::{
+  HammerfestClient, HammerfestCredentials, HammerfestPassword, HammerfestServer, HammerfestStore, HammerfestUserIdRef,
+};
+use etwin_core::link::{
+  GetLinkOptions, LinkStore, LinkToDinoparcOptions, LinkToHammerfestOptions, LinkToTwinoidOauthOptions, RawLink,
+  UnlinkOptions, VersionedRawLink,
+};
+use etwin_core::twinoid::api::PartialUser;
+use etwin_core::twinoid::{TwinoidClient, TwinoidStore, TwinoidUserIdRef};
+use etwin_core::user::{CompleteSimpleUser, CreateUserOptions, ShortUser, UserStore};
+use etwin_core::uuid::Uuid4Generator;
+use etwin_dinoparc_client::mem::MemDinoparcClient;
+use etwin_dinoparc_store::mem::MemDinoparcStore;
+use etwin_hammerfest_client::MemHammerfestClient;
+use etwin_hammerfest_store::mem::MemHammerfestStore;
+use etwin_link_store::mem::MemLinkStore;
+use etwin_services::link::{DynLinkService, LinkError, LinkService, UnlinkError};
+use etwin_twinoid_client::mem::MemTwinoidClient;
+use etwin_twinoid_store::mem::MemTwinoidStore;
+use etwin_user_store::mem::MemUserStore;
+use std::sync::Arc;
+
+struct TestApi {
+  dinoparc_client: Arc<MemDinoparcClient<Arc<VirtualClock>>>,
+  hammerfest_client: Arc<MemHammerfestClient<Arc<VirtualClock>>>,
+  link: DynLinkService,
+  link_store: Arc<dyn LinkStore>,
+  twinoid_client: Arc<MemTwinoidClient>,
+  user_store: Arc<dyn UserStore>,
+}
+
+fn make_test_api() -> TestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
+  let dinoparc_client = Arc::new(MemDinoparcClient::new(Arc::clone(&clock)));
+  let dinoparc_store: Arc<dyn DinoparcStore> = Arc::new(MemDinoparcStore::new(Arc::clone(&clock)));
+  let hammerfest_client = Arc::new(MemHammerfestClient::new(Arc::clone(&clock)));
+  let hammerfest_store: Arc<dyn HammerfestStore> = Arc::new(MemHammerfestStore::new(Arc::clone(&clock)));
+  let link_store: Arc<dyn LinkStore> = Arc::new(MemLinkStore::new(Arc::clone(&clock)));
+  let twinoid_client = Arc::new(MemTwinoidClient::new());
+  let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(MemTwinoidStore::new(Arc::clone(&clock)));
+  let user_store: Arc<dyn UserStore> = Arc::new(MemUserStore::new(Arc::clone(&clock), Uuid4Generator));
+
+  let link = LinkService::new(
+    Arc::clone(&dinoparc_client) as Arc<dyn DinoparcClient>,
+    dinoparc_store,
+    Arc::clone(&hammerfest_client) as Arc<dyn HammerfestClient>,
+    hammerfest_store,
+    Arc::clone(&link_store),
+    Arc::clone(&twinoid_client) as Arc<dyn TwinoidClient>,
+    twinoid_store,
+    Arc::clone(&user_store),
+  );
+
+  TestApi {
+    dinoparc_client,
+    hammerfest_client,
+    link,
+    link_store,
+    twinoid_client,
+    user_store,
+  }
+}
+
+async fn create_user(api: &TestApi, name: &str) -> CompleteSimpleUser {
+  api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: name.parse().unwrap(),
+      email: None,
+      username: Some(name.to_lowercase().parse().unwrap()),
+      password: None,
+    })
+    .await
+    .unwrap()
+}
+
+fn user_acx(user: &CompleteSimpleUser, is_administrator: bool) -> AuthContext {
+  AuthContext::User(UserAuthContext {
+    scope: AuthScope::Default,
+    user: ShortUser::from(user.clone()),
+    is_administrator,
+  })
+}
+
+fn alice_hammerfest_credentials(password: &str) -> HammerfestCredentials {
+  HammerfestCredentials {
+    server: HammerfestServer::HammerfestFr,
+    username: "alice".parse().unwrap(),
+    password: HammerfestPassword::new(password.to_string()),
+  }
+}
+
+#[tokio::test]
+async fn link_and_unlink_hammerfest() {
+  let api = make_test_api();
+  let alice = create_user(&api, "Alice").await;
+  api.hammerfest_client.create_user(
+    HammerfestServer::HammerfestFr,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    HammerfestPassword::new("aaaaa".to_string()),
+  );
+  let remote = HammerfestUserIdRef {
+    server: HammerfestServer::HammerfestFr,
+    id: "123".parse().unwrap(),
+  };
+
+  let actual = api
+    .link
+    .link_to_hammerfest(
+      &user_acx(&alice, false),
+      &LinkToHammerfestOptions {
+        user_id: alice.id,
+        credentials: alice_hammerfest_credentials("bad password"),
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(LinkError::RemoteAuthentication(_))));
+
+  let actual = api
+    .link
+    .link_to_hammerfest(
+      &user_acx(&alice, false),
+      &LinkToHammerfestOptions {
+        user_id: alice.id,
+        credentials: alice_hammerfest_credentials("aaaaa"),
+      },
+    )
+    .await
+    .unwrap();
+  let expected = VersionedRawLink {
+    current: Some(RawLink {
+      link: RawUserDot {
+        time: Instant::ymd_hms(2020, 1, 1, 0, 0, 0),
+        user: alice.id.into(),
+      },
+      unlink: (),
+      etwin: alice.id.into(),
+      remote,
+    }),
+    old: vec![],
+  };
+  assert_eq!(actual, expected);
+
+  let actual = api
+    .link
+    .unlink_from_hammerfest(
+      &user_acx(&alice, false),
+      &UnlinkOptions {
+        user_id: alice.id,
+        remote,
+      },
+    )
+    .await;
+  assert!(actual.is_ok());
+  let actual = api
+    .link_store
+    .get_link_from_hammerfest(&GetLinkOptions { remote, time: None })
+    .await
+    .unwrap();
+  assert_eq!(actual.current, None);
+
+  let actual = api
+    .link
+    .unlink_from_hammerfest(
+      &user_acx(&alice, false),
+      &UnlinkOptions {
+        user_id: alice.id,
+        remote,
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(UnlinkError::NotFound(_, _))));
+}
+
+#[tokio::test]
+async fn reject_linking_for_other_users() {
+  let api = make_test_api();
+  let alice = create_user(&api, "Alice").await;
+  let bob = create_user(&api, "Bob").await;
+  api.hammerfest_client.create_user(
+    HammerfestServer::HammerfestFr,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    HammerfestPassword::new("aaaaa".to_string()),
+  );
+
+  let options = LinkToHammerfestOptions {
+    user_id: alice.id,
+    credentials: alice_hammerfest_credentials("aaaaa"),
+  };
+  let actual = api.link.link_to_hammerfest(&user_acx(&bob, false), &options).await;
+  assert!(matches!(actual, Err(LinkError::Forbidden)));
+
+  let actual = api
+    .link
+    .unlink_from_hammerfest(
+      &user_acx(&bob, false),
+      &UnlinkOptions {
+        user_id: alice.id,
+        remote: HammerfestUserIdRef {
+          server: HammerfestServer::HammerfestFr,
+          id: "123".parse().unwrap(),
+        },
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(UnlinkError::Forbidden)));
+
+  // Administrators may act on behalf of other users, the link records who created it
+  let actual = api
+    .link
+    .link_to_hammerfest(&user_acx(&bob, true), &options)
+    .await
+    .unwrap();
+  let link = actual.current.unwrap();
+  assert_eq!(link.etwin, alice.id.into());
+  assert_eq!(link.link.user, bob.id.into());
+}
+
+#[tokio::test]
+async fn reject_conflicting_dinoparc_link() {
+  let api = make_test_api();
+  let alice = create_user(&api, "Alice").await;
+  let bob = create_user(&api, "Bob").await;
+  api.dinoparc_client.create_user(
+    DinoparcServer::EnDinoparcCom,
+    "123".parse().unwrap(),
+    "alice".parse().unwrap(),
+    DinoparcPassword::new("aaaaa".to_string()),
+  );
+  let credentials = DinoparcCredentials {
+    server: DinoparcServer::EnDinoparcCom,
+    username: "alice".parse().unwrap(),
+    password: DinoparcPassword::new("aaaaa".to_string()),
+  };
+
+  api
+    .link
+    .link_to_dinoparc(
+      &user_acx(&alice, false),
+      &LinkToDinoparcOptions {
+        user_id: alice.id,
+        credentials: credentials.clone(),
+      },
+    )
+    .await
+    .unwrap();
+
+  let actual = api
+    .link
+    .link_to_dinoparc(
+      &user_acx(&bob, false),
+      &LinkToDinoparcOptions {
+        user_id: bob.id,
+        credentials,
+      },
+    )
+    .await;
+  match actual {
+    Err(LinkError::ConflictEtwin(etwin)) => assert_eq!(etwin, alice.id.into()),
+    actual => panic!("expected `ConflictEtwin` error, got {:?}", actual),
+  }
+
+  let actual = api
+    .link_store
+    .get_link_from_dinoparc(&GetLinkOptions {
+      remote: DinoparcUserIdRef {
+        server: DinoparcServer::EnDinoparcCom,
+        id: "123".parse().unwrap(),
+      },
+      time: None,
+    })
+    .await
+    .unwrap();
+  assert_eq!(actual.current.map(|l| l.etwin), Some(alice.id.into()));
+}
+
+#[tokio::test]
+async fn link_twinoid_with_oauth_token() {
+  let api = make_test_api();
+  let alice = create_user(&api, "Alice").await;
+  let tid_alice = api
+    .twinoid_client
+    .create_user(PartialUser {
+      id: 38,
+      name: Some("alice".parse().unwrap()),
+      title: None,
+      picture: None,
+      locale: None,
+      contacts: None,
+      sites: None,
+    })
+    .unwrap();
+  api
+    .twinoid_client
+    .create_token("HfuvI3PaHGN9ka7xKXd2tCQpqqhkxUjq".parse().unwrap(), tid_alice);
+
+  let actual = api
+    .link
+    .link_to_twinoid_oauth(
+      &user_acx(&alice, false),
+      &LinkToTwinoidOauthOptions {
+        user_id: alice.id,
+        access_token: "invalid".parse().unwrap(),
+      },
+    )
+    .await;
+  assert!(matches!(actual, Err(LinkError::RemoteAuthentication(_))));
+
+  let actual = api
+    .link
+    .link_to_twinoid_oauth(
+      &user_acx(&alice, false),
+      &LinkToTwinoidOauthOptions {
+        user_id: alice.id,
+        access_token: "HfuvI3PaHGN9ka7xKXd2tCQpqqhkxUjq".parse().unwrap(),
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    actual.current.map(|l| l.remote),
+    Some(TwinoidUserIdRef { id: tid_alice })
+  );
+}
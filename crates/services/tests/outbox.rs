@@ -0,0 +1,229 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use etwin_core::clock::VirtualClock;
+use etwin_core::core::Instant;
+use etwin_core::email::{EmailAddress, EmailContent, Mailer, OutboundEmailStatus, OutboxStore};
+use etwin_core::types::AnyError;
+use etwin_core::uuid::Uuid4Generator;
+use etwin_log::VecLogger;
+use etwin_mailer::mem::MemMailer;
+use etwin_outbox_store::mem::MemOutboxStore;
+use etwin_services::outbox::{OutboxDeliveryReport, OutboxEvent, OutboxMailer, OutboxService};
+use std::sync::Arc;
+
+struct TestApi {
+  clock: Arc<VirtualClock>,
+  logger: Arc<VecLogger<OutboxEvent>>,
+  mailer: Arc<MemMailer>,
+  outbox_store: Arc<MemOutboxStore<Arc<VirtualClock>, Uuid4Generator>>,
+}
+
+fn make_test_api() -> TestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0)));
+  TestApi {
+    logger: Arc::new(VecLogger::new()),
+    mailer: Arc::new(MemMailer::new()),
+    outbox_store: Arc::new(MemOutboxStore::new(Arc::clone(&clock), Uuid4Generator)),
+    clock,
+  }
+}
+
+fn make_outbox_service(
+  api: &TestApi,
+) -> OutboxService<
+  Arc<VirtualClock>,
+  Arc<VecLogger<OutboxEvent>>,
+  Arc<MemMailer>,
+  Arc<MemOutboxStore<Arc<VirtualClock>, Uuid4Generator>>,
+> {
+  OutboxService::new(
+    Arc::clone(&api.clock),
+    Arc::clone(&api.logger),
+    Arc::clone(&api.mailer),
+    Arc::clone(&api.outbox_store),
+  )
+  .with_max_attempts(3)
+  .with_base_delay(Duration::minutes(1))
+}
+
+fn content() -> EmailContent {
+  EmailContent {
+    title: "Hello".parse().unwrap(),
+    body_text: "Hello Alice".parse().unwrap(),
+    body_html: None,
+  }
+}
+
+#[tokio::test]
+async fn test_queued_emails_are_delivered_once() {
+  let api = make_test_api();
+  let service = make_outbox_service(&api);
+  let alice: EmailAddress = "alice@example.com".parse().unwrap();
+  api.mailer.create_inbox(alice.clone());
+
+  OutboxMailer::new(Arc::clone(&api.outbox_store))
+    .send_email(&alice, &content())
+    .await
+    .unwrap();
+  assert_eq!(api.mailer.read_inbox(&alice), vec![]);
+
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report.sent.len(), 1);
+  assert_eq!(api.mailer.read_inbox(&alice), vec![content()]);
+  let email = api.outbox_store.get_email(report.sent[0]).await.unwrap().unwrap();
+  assert_eq!(email.status, OutboundEmailStatus::Sent);
+  assert_eq!(
+    api.logger.take(),
+    vec![OutboxEvent::EmailSent {
+      email: email.id,
+      attempts: 1
+    }]
+  );
+
+  api.clock.advance_by(Duration::hours(1));
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report, OutboxDeliveryReport::default());
+  assert_eq!(api.mailer.read_inbox(&alice), vec![content()]);
+}
+
+#[tokio::test]
+async fn test_rejected_emails_are_retried_with_backoff() {
+  let api = make_test_api();
+  let service = make_outbox_service(&api);
+  // No inbox: the mailer rejects the email
+  let bob: EmailAddress = "bob@example.com".parse().unwrap();
+
+  OutboxMailer::new(Arc::clone(&api.outbox_store))
+    .send_email(&bob, &content())
+    .await
+    .unwrap();
+
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report.retried.len(), 1);
+  let id = report.retried[0];
+  assert_eq!(
+    api.logger.take(),
+    vec![OutboxEvent::EmailRetryScheduled {
+      email: id,
+      attempts: 1,
+      retry_at: Instant::ymd_hms(2021, 1, 1, 0, 1, 0),
+      error: "RecipientNotFound".to_string(),
+    }]
+  );
+
+  // Not due yet
+  api.clock.advance_by(Duration::seconds(59));
+  assert_eq!(
+    service.deliver_due_emails().await.unwrap(),
+    OutboxDeliveryReport::default()
+  );
+
+  api.clock.advance_to(Instant::ymd_hms(2021, 1, 1, 0, 1, 0));
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report.retried, vec![id]);
+  assert_eq!(
+    api.logger.take(),
+    vec![OutboxEvent::EmailRetryScheduled {
+      email: id,
+      attempts: 2,
+      retry_at: Instant::ymd_hms(2021, 1, 1, 0, 3, 0),
+      error: "RecipientNotFound".to_string(),
+    }]
+  );
+
+  // The inbox now exists, but the email is still waiting for its backoff
+  api.mailer.create_inbox(bob.clone());
+  api.clock.advance_to(Instant::ymd_hms(2021, 1, 1, 0, 3, 0));
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report.sent, vec![id]);
+  assert_eq!(api.mailer.read_inbox(&bob), vec![content()]);
+  let email = api.outbox_store.get_email(id).await.unwrap().unwrap();
+  assert_eq!(email.status, OutboundEmailStatus::Sent);
+  assert_eq!(email.attempts, 2);
+}
+
+#[tokio::test]
+async fn test_emails_fail_after_max_attempts() {
+  let api = make_test_api();
+  let service = make_outbox_service(&api);
+  let bob: EmailAddress = "bob@example.com".parse().unwrap();
+
+  OutboxMailer::new(Arc::clone(&api.outbox_store))
+    .send_email(&bob, &content())
+    .await
+    .unwrap();
+
+  let mut failed = Vec::new();
+  for _ in 0..3 {
+    let report = service.deliver_due_emails().await.unwrap();
+    failed.extend(report.failed);
+    api.clock.advance_by(Duration::hours(1));
+  }
+  assert_eq!(failed.len(), 1);
+  let id = failed[0];
+  let events = api.logger.take();
+  assert_eq!(
+    events.last(),
+    Some(&OutboxEvent::EmailFailed {
+      email: id,
+      attempts: 3,
+      error: "RecipientNotFound".to_string(),
+    })
+  );
+
+  let email = api.outbox_store.get_email(id).await.unwrap().unwrap();
+  assert_eq!(email.status, OutboundEmailStatus::Failed);
+  assert_eq!(email.attempts, 3);
+
+  api.mailer.create_inbox(bob.clone());
+  api.clock.advance_by(Duration::days(1));
+  assert_eq!(
+    service.deliver_due_emails().await.unwrap(),
+    OutboxDeliveryReport::default()
+  );
+  assert_eq!(api.mailer.read_inbox(&bob), vec![]);
+}
+
+/// Mailer whose transport is unreachable, like an SMTP relay refusing connections
+struct UnreachableMailer;
+
+#[async_trait]
+impl Mailer for UnreachableMailer {
+  async fn send_email(&self, _recipient: &EmailAddress, _content: &EmailContent) -> Result<(), AnyError> {
+    Err("ConnectionRefused".into())
+  }
+}
+
+#[tokio::test]
+async fn test_transport_errors_keep_emails_pending() {
+  let api = make_test_api();
+  let service = OutboxService::new(
+    Arc::clone(&api.clock),
+    Arc::clone(&api.logger),
+    UnreachableMailer,
+    Arc::clone(&api.outbox_store),
+  )
+  .with_max_attempts(3)
+  .with_base_delay(Duration::minutes(1));
+  let alice: EmailAddress = "alice@example.com".parse().unwrap();
+
+  OutboxMailer::new(Arc::clone(&api.outbox_store))
+    .send_email(&alice, &content())
+    .await
+    .unwrap();
+
+  let report = service.deliver_due_emails().await.unwrap();
+  assert_eq!(report.retried.len(), 1);
+  let email = api.outbox_store.get_email(report.retried[0]).await.unwrap().unwrap();
+  assert_eq!(email.status, OutboundEmailStatus::Pending);
+  assert_eq!(email.attempts, 1);
+  assert_eq!(
+    api.logger.take(),
+    vec![OutboxEvent::EmailRetryScheduled {
+      email: email.id,
+      attempts: 1,
+      retry_at: Instant::ymd_hms(2021, 1, 1, 0, 1, 0),
+      error: "ConnectionRefused".to_string(),
+    }]
+  );
+}
@@ -0,0 +1,115 @@
+use chrono::Duration;
+use etwin_core::clock::{Clock, VirtualClock};
+use etwin_core::core::Instant;
+use etwin_core::token::{TokenStore, TouchOauthTokenOptions};
+use etwin_core::twinoid::{TwinoidOauthClient, TwinoidUserId};
+use etwin_log::VecLogger;
+use etwin_services::twinoid_oauth::{TwinoidOauthEvent, TwinoidOauthService};
+use etwin_token_store::mem::MemTokenStore;
+use etwin_twinoid_client::mem::MemTwinoidOauthClient;
+use std::sync::Arc;
+
+struct TestApi {
+  clock: Arc<VirtualClock>,
+  logger: Arc<VecLogger<TwinoidOauthEvent>>,
+  token_store: Arc<MemTokenStore<Arc<VirtualClock>>>,
+  twinoid_oauth_client: Arc<MemTwinoidOauthClient<Arc<VirtualClock>>>,
+  twinoid_oauth: TwinoidOauthService<
+    Arc<VirtualClock>,
+    Arc<VecLogger<TwinoidOauthEvent>>,
+    Arc<MemTokenStore<Arc<VirtualClock>>>,
+    Arc<MemTwinoidOauthClient<Arc<VirtualClock>>>,
+  >,
+}
+
+fn make_test_api() -> TestApi {
+  let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0)));
+  let token_store = Arc::new(MemTokenStore::new(Arc::clone(&clock)));
+  let twinoid_oauth_client = Arc::new(MemTwinoidOauthClient::new(Arc::clone(&clock)));
+  let logger = Arc::new(VecLogger::new());
+  let twinoid_oauth = TwinoidOauthService::new(
+    Arc::clone(&clock),
+    Arc::clone(&logger),
+    Arc::clone(&token_store),
+    Arc::clone(&twinoid_oauth_client),
+  );
+  TestApi {
+    clock,
+    logger,
+    token_store,
+    twinoid_oauth_client,
+    twinoid_oauth,
+  }
+}
+
+/// Simulate a Twinoid login for `user`, storing the tokens it returns.
+async fn login(api: &TestApi, user: TwinoidUserId) {
+  api.twinoid_oauth_client.create_code("code".to_string(), user);
+  let token = api.twinoid_oauth_client.exchange_code("code").await.unwrap();
+  api
+    .token_store
+    .touch_twinoid_oauth(&TouchOauthTokenOptions {
+      access_token: token.access_token,
+      refresh_token: token.refresh_token.unwrap(),
+      expiration_time: api.clock.now() + Duration::seconds(token.expires_in),
+      twinoid_user_id: user,
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_refresh_near_expiry_tokens() {
+  let api = make_test_api();
+  let alice: TwinoidUserId = "38".parse().unwrap();
+  login(&api, alice).await;
+  let before = api.token_store.get_twinoid_oauth(alice.as_ref()).await.unwrap();
+
+  api.clock.advance_by(Duration::minutes(30));
+  let report = api.twinoid_oauth.refresh_tokens().await.unwrap();
+  assert!(report.refreshed.is_empty());
+
+  api.clock.advance_by(Duration::minutes(27));
+  let report = api.twinoid_oauth.refresh_tokens().await.unwrap();
+  assert_eq!(report.refreshed, vec![alice]);
+  assert!(report.revoked.is_empty());
+  assert!(report.failures.is_empty());
+  assert_eq!(
+    api.logger.take(),
+    vec![TwinoidOauthEvent::AccessTokenRefreshed { twinoid_user: alice }]
+  );
+
+  let after = api.token_store.get_twinoid_oauth(alice.as_ref()).await.unwrap();
+  let access_token = after.access_token.unwrap();
+  assert_ne!(Some(&access_token.key), before.access_token.as_ref().map(|t| &t.key));
+  assert_eq!(access_token.expires_at, Instant::ymd_hms(2021, 1, 1, 1, 57, 0));
+  assert_eq!(after.refresh_token.map(|t| t.key), before.refresh_token.map(|t| t.key));
+  assert_eq!(
+    api.twinoid_oauth_client.get_access_token_user(&access_token.key),
+    Some(alice)
+  );
+}
+
+#[tokio::test]
+async fn test_revoke_rejected_tokens() {
+  let api = make_test_api();
+  let alice: TwinoidUserId = "38".parse().unwrap();
+  login(&api, alice).await;
+  let before = api.token_store.get_twinoid_oauth(alice.as_ref()).await.unwrap();
+  api
+    .twinoid_oauth_client
+    .revoke_refresh_token(&before.refresh_token.unwrap().key);
+
+  api.clock.advance_by(Duration::minutes(57));
+  let report = api.twinoid_oauth.refresh_tokens().await.unwrap();
+  assert!(report.refreshed.is_empty());
+  assert_eq!(report.revoked, vec![alice]);
+  assert_eq!(
+    api.logger.take(),
+    vec![TwinoidOauthEvent::TokensRevoked { twinoid_user: alice }]
+  );
+
+  let after = api.token_store.get_twinoid_oauth(alice.as_ref()).await.unwrap();
+  assert_eq!(after.access_token, None);
+  assert_eq!(after.refresh_token, None);
+}
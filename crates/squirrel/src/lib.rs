@@ -1,3 +1,4 @@
+use futures_util::future::BoxFuture;
 use futures_util::stream::StreamExt;
 use include_dir::Dir;
 use once_cell::sync::Lazy;
@@ -5,12 +6,13 @@ use petgraph::algo::astar;
 use petgraph::graphmap::DiGraphMap;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::postgres::PgDatabaseError;
 use sqlx::{Connection, Executor};
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use std::cmp::{max, Ordering};
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto;
 use std::error::Error;
 use std::fmt;
 use std::fmt::Debug;
@@ -19,10 +21,82 @@ use std::str::FromStr;
 use std::sync::atomic::AtomicU16;
 use std::sync::RwLock;
 
+/// Error of a Rust data migration.
+pub type DataMigrationError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Data transformation attached to a migration step, for changes that SQL can't express.
+///
+/// It runs in the transaction of the migration, after the schema and SQL data scripts of the step.
+pub trait DataMigration: Send + Sync {
+  fn run<'a>(
+    &'a self,
+    db: &'a mut PgConnection,
+    progress: &'a DataProgress<'a>,
+  ) -> BoxFuture<'a, Result<(), DataMigrationError>>;
+}
+
+impl<F> DataMigration for F
+where
+  F: for<'a> Fn(&'a mut PgConnection, &'a DataProgress<'a>) -> BoxFuture<'a, Result<(), DataMigrationError>>
+    + Send
+    + Sync,
+{
+  fn run<'a>(
+    &'a self,
+    db: &'a mut PgConnection,
+    progress: &'a DataProgress<'a>,
+  ) -> BoxFuture<'a, Result<(), DataMigrationError>> {
+    (self)(db, progress)
+  }
+}
+
+/// Event emitted while a migration is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationProgress {
+  /// The schema script of a step started
+  Schema { start: Option<NonZeroU32>, end: NonZeroU32 },
+  /// The SQL data script of a step started
+  SqlData { start: Option<NonZeroU32>, end: NonZeroU32 },
+  /// A Rust data migration reported its progress
+  RustData {
+    start: Option<NonZeroU32>,
+    end: NonZeroU32,
+    done: u64,
+    total: Option<u64>,
+  },
+}
+
+/// Progress reporter passed to Rust data migrations.
+pub struct DataProgress<'a> {
+  start: Option<NonZeroU32>,
+  end: NonZeroU32,
+  observer: &'a (dyn Fn(MigrationProgress) + Send + Sync),
+}
+
+impl<'a> DataProgress<'a> {
+  /// Report that `done` items out of `total` (if known) were processed.
+  pub fn report(&self, done: u64, total: Option<u64>) {
+    (self.observer)(MigrationProgress::RustData {
+      start: self.start,
+      end: self.end,
+      done,
+      total,
+    })
+  }
+}
+
 static SQL_NODE_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([0-9]{1,4})\.sql$").unwrap());
 static SQL_EDGE_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([0-9]{1,4})-([0-9]{1,4})\.sql$").unwrap());
 const DEFAULT_SCHEMA_COMMENT: &str = "standard public schema";
 
+/// Key of the transaction-level advisory lock held while the schema is changed.
+///
+/// It serializes migrations started concurrently by multiple instances.
+const MIGRATION_LOCK_KEY: i64 = 0x7371_7569_7272_656c;
+
+/// Objects storing the schema meta, excluded from snapshots.
+const META_OBJECTS: [&str; 3] = ["raw_schema_meta", "schema_meta", "get_schema_meta"];
+
 /// Opaque handle representing a database state recognized by the issuing resolver.
 #[derive(Clone, Copy)]
 pub struct SchemaStateRef<'r> {
@@ -38,6 +112,13 @@ impl<'r> PartialEq for SchemaStateRef<'r> {
 
 impl<'r> Eq for SchemaStateRef<'r> {}
 
+impl<'r> SchemaStateRef<'r> {
+  /// Schema version, or `None` for the empty state.
+  pub fn version(&self) -> Option<NonZeroU32> {
+    state_version(self.state)
+  }
+}
+
 impl<'r> Debug for SchemaStateRef<'r> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
@@ -63,6 +144,12 @@ impl<'r> PartialEq for SchemaVersionRef<'r> {
 
 impl<'r> Eq for SchemaVersionRef<'r> {}
 
+impl<'r> SchemaVersionRef<'r> {
+  pub fn get(&self) -> NonZeroU32 {
+    self.version.0
+  }
+}
+
 impl<'r> Debug for SchemaVersionRef<'r> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
@@ -126,6 +213,91 @@ impl<'r> Debug for SchemaMigration<'r> {
   }
 }
 
+impl<'a> SchemaMigration<'a> {
+  /// Iterate over the scripts applied by this migration, in order.
+  pub fn steps(&self) -> impl Iterator<Item = MigrationStep> + '_ {
+    self.states.windows(2).map(move |w| {
+      let (start, end) = (w[0], w[1]);
+      let edge = self.resolver.graph.edge_weight(start, end).unwrap();
+      MigrationStep {
+        start: state_version(start),
+        end: state_version(end),
+        schema: edge.schema,
+        data: edge.data,
+        has_rust_data: edge.rust_data.is_some(),
+      }
+    })
+  }
+}
+
+/// Result of [`SchemaResolver::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaVerification {
+  /// Current version, or `None` for the empty state
+  pub version: Option<NonZeroU32>,
+  /// Checksum of the scripts recorded when the current version was reached
+  pub recorded_checksum: Option<String>,
+  /// Checksum of the current scripts for this version
+  pub expected_checksum: Option<String>,
+  /// Snapshot lines produced by the scripts, but missing from the database
+  pub missing: Vec<String>,
+  /// Snapshot lines found in the database, but not produced by the scripts
+  pub unexpected: Vec<String>,
+}
+
+impl SchemaVerification {
+  /// The scripts of the current version did not change since it was reached
+  pub fn checksum_matches(&self) -> bool {
+    self.recorded_checksum == self.expected_checksum
+  }
+
+  /// The live schema differs from the schema produced by the scripts
+  pub fn has_drift(&self) -> bool {
+    !self.missing.is_empty() || !self.unexpected.is_empty()
+  }
+
+  pub fn is_ok(&self) -> bool {
+    self.checksum_matches() && !self.has_drift()
+  }
+}
+
+/// Single script of a migration, between two adjacent states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MigrationStep {
+  /// Start version, or `None` for the empty state
+  pub start: Option<NonZeroU32>,
+  /// End version, or `None` for the empty state
+  pub end: Option<NonZeroU32>,
+  /// SQL script transitioning the schema
+  pub schema: &'static str,
+  /// SQL script migrating the data
+  pub data: Option<&'static str>,
+  /// A Rust data migration runs after the SQL scripts
+  pub has_rust_data: bool,
+}
+
+/// Append a script to a squashed script, terminating its last statement.
+fn push_script(squashed: &mut String, script: &str) {
+  squashed.push_str(script.trim_end());
+  // The last statement of a script may omit its semicolon
+  let last_line = script
+    .lines()
+    .rev()
+    .map(str::trim)
+    .find(|l| !l.is_empty() && !l.starts_with("--"));
+  if !matches!(last_line, Some(l) if l.ends_with(';')) {
+    squashed.push(';');
+  }
+  squashed.push('\n');
+}
+
+fn state_version(state: SchemaState) -> Option<NonZeroU32> {
+  match state {
+    SchemaState::Empty => None,
+    SchemaState::Version(v) => Some(v.0),
+  }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 struct SaturatingU32(u32);
 
@@ -144,6 +316,9 @@ impl core::ops::Add for SaturatingU32 {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct SchemaMeta {
   version: NonZeroU32,
+  /// Checksum of the scripts defining `version`, missing for databases migrated by older releases
+  #[serde(default)]
+  checksum: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -152,12 +327,23 @@ pub enum MigrationDirection {
   DowngradeOnly,
 }
 
-#[derive(Debug)]
 struct EdgeState {
   /// SQL script to transition the schema version
   schema: &'static str,
   /// SQL script to populate data
   data: Option<&'static str>,
+  /// Rust data migration, running after the data script
+  rust_data: Option<Box<dyn DataMigration>>,
+}
+
+impl Debug for EdgeState {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("EdgeState")
+      .field("schema", &self.schema)
+      .field("data", &self.data)
+      .field("rust_data", &self.rust_data.is_some())
+      .finish()
+  }
 }
 
 #[derive(Debug)]
@@ -188,7 +374,11 @@ impl SchemaResolver {
           continue;
         };
         let schema = f.contents_utf8().unwrap();
-        let edge = EdgeState { schema, data: None };
+        let edge = EdgeState {
+          schema,
+          data: None,
+          rust_data: None,
+        };
         let state: SchemaState = caps[1].parse::<SchemaVersion>().unwrap().into();
         graph.add_node(state);
         assert!(states.insert(state, ()).is_none());
@@ -206,7 +396,11 @@ impl SchemaResolver {
           continue;
         };
         let schema = f.contents_utf8().unwrap();
-        let edge = EdgeState { schema, data: None };
+        let edge = EdgeState {
+          schema,
+          data: None,
+          rust_data: None,
+        };
         let start: SchemaState = caps[1].parse::<SchemaVersion>().unwrap().into();
         let end: SchemaState = caps[2].parse::<SchemaVersion>().unwrap().into();
         assert!(start < end);
@@ -218,6 +412,47 @@ impl SchemaResolver {
         latest = max(latest, end);
       }
     }
+    if let Some(downgrade_dir) = d.get_dir("downgrade") {
+      for f in downgrade_dir.files() {
+        let file_name: &str = f.path().file_name().unwrap().to_str().unwrap();
+        let caps = if let Some(caps) = SQL_EDGE_PATTERN.captures(file_name) {
+          caps
+        } else {
+          eprintln!("Unexpected file name format: {:?}", f.path());
+          continue;
+        };
+        let schema = f.contents_utf8().unwrap();
+        let edge = EdgeState {
+          schema,
+          data: None,
+          rust_data: None,
+        };
+        let start: SchemaState = caps[1].parse::<SchemaVersion>().unwrap().into();
+        let end: SchemaState = caps[2].parse::<SchemaVersion>().unwrap().into();
+        assert!(start > end);
+        // Downgrades only connect versions reachable through upgrades
+        assert!(states.contains_key(&start), "Unknown downgrade start: {:?}", f.path());
+        assert!(states.contains_key(&end), "Unknown downgrade end: {:?}", f.path());
+        graph.add_edge(start, end, edge);
+      }
+    }
+    if let Some(data_dir) = d.get_dir("data") {
+      for f in data_dir.files() {
+        let file_name: &str = f.path().file_name().unwrap().to_str().unwrap();
+        let caps = if let Some(caps) = SQL_EDGE_PATTERN.captures(file_name) {
+          caps
+        } else {
+          eprintln!("Unexpected file name format: {:?}", f.path());
+          continue;
+        };
+        let start: SchemaState = caps[1].parse::<SchemaVersion>().unwrap().into();
+        let end: SchemaState = caps[2].parse::<SchemaVersion>().unwrap().into();
+        let edge = graph
+          .edge_weight_mut(start, end)
+          .unwrap_or_else(|| panic!("Data script without schema script: {:?}", f.path()));
+        edge.data = Some(f.contents_utf8().unwrap());
+      }
+    }
     let drop = d
       .get_file("drop.sql")
       .map(|f| f.contents_utf8().expect("Invalid drop script encoding"));
@@ -239,6 +474,24 @@ impl SchemaResolver {
     }
   }
 
+  /// Attach a Rust data migration to the step from `start` to `end`.
+  ///
+  /// Panics if there is no script for this step, or if it already has a Rust data migration.
+  pub fn with_rust_data(mut self, start: NonZeroU32, end: NonZeroU32, migration: impl DataMigration + 'static) -> Self {
+    let edge = self
+      .graph
+      .edge_weight_mut(SchemaVersion(start).into(), SchemaVersion(end).into())
+      .unwrap_or_else(|| panic!("Unknown migration step: {} -> {}", start, end));
+    assert!(
+      edge.rust_data.is_none(),
+      "Duplicate Rust data migration: {} -> {}",
+      start,
+      end
+    );
+    edge.rust_data = Some(Box::new(migration));
+    self
+  }
+
   pub fn get_version(&self, v: NonZeroU32) -> Option<SchemaVersionRef> {
     let version = SchemaVersion(v);
     if self.states.contains_key(&version.into()) {
@@ -277,6 +530,90 @@ impl SchemaResolver {
     }
   }
 
+  /// List the `(start, end)` versions of the downgrade scripts, ordered by start version.
+  pub fn get_downgrades(&self) -> Vec<(SchemaVersionRef, SchemaVersionRef)> {
+    let mut downgrades: Vec<(SchemaVersion, SchemaVersion)> = self
+      .graph
+      .all_edges()
+      .filter_map(|(start, end, _)| match (start, end) {
+        (SchemaState::Version(start), SchemaState::Version(end)) if start > end => Some((start, end)),
+        _ => None,
+      })
+      .collect();
+    downgrades.sort();
+    downgrades
+      .into_iter()
+      .map(|(start, end)| (self.issue_version(start), self.issue_version(end)))
+      .collect()
+  }
+
+  /// Migration from the empty state to `end`, starting with the oldest creation script and then using only upgrade
+  /// scripts.
+  ///
+  /// Unlike [`SchemaResolver::create_migration`], it ignores squashed creation scripts.
+  pub fn create_full_migration(&self, end: SchemaVersionRef) -> Option<SchemaMigration> {
+    self.inner_create_full_migration(self.validate_version(end))
+  }
+
+  fn inner_create_full_migration(&self, end: SchemaVersion) -> Option<SchemaMigration> {
+    let base: SchemaState = self.graph.neighbors(SchemaState::Empty).min()?;
+    let mut migration = self.inner_create_migration(base, end.into(), MigrationDirection::UpgradeOnly)?;
+    migration.states.insert(0, SchemaState::Empty);
+    Some(migration)
+  }
+
+  /// List the versions with a squashed creation script: all the creation scripts except the oldest one.
+  pub fn get_squashed(&self) -> Vec<SchemaVersionRef> {
+    let mut created: Vec<SchemaVersion> = self
+      .graph
+      .neighbors(SchemaState::Empty)
+      .filter_map(|state| match state {
+        SchemaState::Version(v) => Some(v),
+        SchemaState::Empty => None,
+      })
+      .collect();
+    created.sort();
+    created.into_iter().skip(1).map(|v| self.issue_version(v)).collect()
+  }
+
+  /// Get the creation script of `version`, if any.
+  pub fn get_create_script(&self, version: SchemaVersionRef) -> Option<&'static str> {
+    self
+      .graph
+      .edge_weight(SchemaState::Empty, self.validate_version(version).into())
+      .map(|edge| edge.schema)
+  }
+
+  /// Generate the squashed creation script of `version`, from the scripts of its full migration.
+  ///
+  /// Returns `None` if a step has a Rust data migration, as it can't be expressed in SQL.
+  pub fn squash(&self, version: SchemaVersionRef) -> Option<String> {
+    let version = self.validate_version(version);
+    let migration = self.inner_create_full_migration(version)?;
+    let mut squashed = format!(
+      "-- Squashed creation script for version {:03}, generated by `etwin db squash --to {}`.\n\
+       -- Do not edit: it must match the `create` and `upgrade` scripts it replaces.\n",
+      version.0, version.0
+    );
+    for step in migration.steps() {
+      if step.has_rust_data {
+        return None;
+      }
+      let start = step
+        .start
+        .map(|v| format!("{:03}", v))
+        .unwrap_or_else(|| "empty".to_string());
+      let end = step.end.unwrap();
+      squashed.push_str(&format!("\n-- Migration step: {} -> {:03}\n", start, end));
+      push_script(&mut squashed, step.schema);
+      if let Some(data) = step.data {
+        squashed.push_str(&format!("\n-- Data migration: {} -> {:03}\n", start, end));
+        push_script(&mut squashed, data);
+      }
+    }
+    Some(squashed)
+  }
+
   pub fn create_migration(
     &self,
     start: SchemaStateRef,
@@ -289,12 +626,62 @@ impl SchemaResolver {
   }
 
   pub async fn apply_migration(&self, db: &PgPool, migration: &'_ SchemaMigration<'_>) -> Result<(), Box<dyn Error>> {
+    self.apply_migration_with_progress(db, migration, &|_| {}).await
+  }
+
+  /// Apply a migration in a single transaction, reporting its progress to `observer`.
+  pub async fn apply_migration_with_progress(
+    &self,
+    db: &PgPool,
+    migration: &'_ SchemaMigration<'_>,
+    observer: &(dyn Fn(MigrationProgress) + Send + Sync),
+  ) -> Result<(), Box<dyn Error>> {
     let mut tx = db.begin().await?;
-    self.tx_apply_migration(&mut tx, migration).await?;
+    self.tx_lock(&mut tx).await?;
+    let start = *migration.states.first().unwrap();
+    let cur = self.inner_get_state(&mut tx).await?;
+    if cur != start {
+      return Err(
+        format!(
+          "UnexpectedSchemaState: the migration starts from {:?}, but the database is at {:?}",
+          start, cur
+        )
+        .into(),
+      );
+    }
+    self.tx_apply_migration(&mut tx, migration, observer).await?;
     tx.commit().await?;
     Ok(())
   }
 
+  /// Wait for the migration lock, held until the end of the transaction.
+  async fn tx_lock(&self, tx: &mut Transaction<'_, Postgres>) -> Result<(), Box<dyn Error>> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1);")
+      .bind(MIGRATION_LOCK_KEY)
+      .execute(&mut *tx)
+      .await?;
+    Ok(())
+  }
+
+  /// Checksum of the scripts defining the schema at `version`.
+  ///
+  /// It covers the schema and SQL data scripts of the full migration from the empty state (see
+  /// [`SchemaResolver::create_full_migration`]), whichever path was used to reach `version`: adding a squashed creation
+  /// script does not change it. Rust data migrations are not included.
+  fn checksum(&self, version: SchemaVersion) -> String {
+    let migration = self
+      .inner_create_full_migration(version)
+      .expect("Unreachable version from empty DB");
+    let mut hasher = Sha256::new();
+    for step in migration.steps() {
+      hasher.update(step.schema.as_bytes());
+      hasher.update([0u8]);
+      hasher.update(step.data.unwrap_or_default().as_bytes());
+      hasher.update([0u8]);
+    }
+    hex::encode(hasher.finalize())
+  }
+
   fn inner_create_migration(
     &self,
     start: SchemaState,
@@ -344,16 +731,28 @@ impl SchemaResolver {
   }
 
   async fn inner_get_state(&self, tx: &mut Transaction<'_, Postgres>) -> Result<SchemaState, Box<dyn Error>> {
-    let mut meta: Option<SchemaMeta> = self.inner_get_schema_meta_from_fn(&mut *tx).await?;
-    if meta.is_none() {
-      meta = self.inner_get_schema_meta_from_comment(&mut *tx).await?;
+    let meta = self.inner_get_schema_meta(&mut *tx).await?;
+    Ok(self.meta_state(meta.as_ref()))
+  }
+
+  async fn inner_get_schema_meta(
+    &self,
+    tx: &mut Transaction<'_, Postgres>,
+  ) -> Result<Option<SchemaMeta>, Box<dyn Error>> {
+    let meta: Option<SchemaMeta> = self.inner_get_schema_meta_from_fn(&mut *tx).await?;
+    match meta {
+      Some(meta) => Ok(Some(meta)),
+      None => self.inner_get_schema_meta_from_comment(&mut *tx).await,
     }
+  }
+
+  fn meta_state(&self, meta: Option<&SchemaMeta>) -> SchemaState {
     let state: SchemaState = match meta {
       None => SchemaState::Empty,
       Some(meta) => SchemaVersion(meta.version).into(),
     };
     assert!(self.states.contains_key(&state));
-    Ok(state)
+    state
   }
 
   async fn inner_get_schema_meta_from_comment<'e, E: Executor<'e, Database = Postgres>>(
@@ -384,30 +783,18 @@ impl SchemaResolver {
     &self,
     db: &mut Transaction<'_, Postgres>,
   ) -> Result<Option<SchemaMeta>, Box<dyn Error>> {
-    #[derive(Debug, sqlx::FromRow)]
-    struct Row {
-      version: i32,
-    }
-
-    impl TryFrom<Row> for SchemaMeta {
-      type Error = Box<dyn Error>;
-
-      fn try_from(row: Row) -> Result<Self, Self::Error> {
-        Ok(Self {
-          version: NonZeroU32::try_from(u32::try_from(row.version)?)?,
-        })
-      }
-    }
-
     let mut tx = db.begin().await?;
 
-    let res: Result<Row, _> = sqlx::query_as("SELECT version FROM get_schema_meta();")
+    // Read the meta as JSON to support the fields missing from older meta types
+    let res: Result<(serde_json::Value,), _> = sqlx::query_as("SELECT to_jsonb(meta) FROM get_schema_meta() AS meta;")
       .fetch_one(&mut tx)
       .await;
 
     let mut commit_tx: bool = true;
     let res: Result<Option<SchemaMeta>, Box<dyn Error>> = match res {
-      Ok(row) => SchemaMeta::try_from(row).map(Some),
+      Ok((meta,)) => serde_json::from_value(meta)
+        .map(Some)
+        .map_err(|e| Box::new(e) as Box<dyn Error>),
       Err(sqlx::Error::Database(e)) => match e.try_downcast::<PgDatabaseError>() {
         Ok(e) => match e.code() {
           "42883" => {
@@ -445,13 +832,20 @@ impl SchemaResolver {
       cache_buster
     );
 
-    let create_meta_fn = format!("CREATE FUNCTION get_schema_meta() RETURNS schema_meta LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT ROW({version}); $$;", version = meta.version);
+    let checksum = match meta.checksum.as_deref() {
+      Some(checksum) => {
+        assert!(checksum.chars().all(|c| c.is_ascii_hexdigit()));
+        format!("'{}'", checksum)
+      }
+      None => "NULL".to_string(),
+    };
+    let create_meta_fn = format!("CREATE FUNCTION get_schema_meta() RETURNS schema_meta LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$ SELECT ROW({version}, {checksum}); $$;", version = meta.version, checksum = checksum);
 
     let queries = [
       "DROP FUNCTION IF EXISTS get_schema_meta;",
       "DROP TYPE IF EXISTS schema_meta;",
       "DROP TYPE IF EXISTS raw_schema_meta;",
-      "CREATE TYPE raw_schema_meta AS (version int4);",
+      "CREATE TYPE raw_schema_meta AS (version int4, checksum text);",
       create_type.as_str(),
       create_meta_fn.as_str(),
     ];
@@ -463,8 +857,153 @@ impl SchemaResolver {
     Ok(())
   }
 
+  /// Compare the live schema with the schema produced by the scripts of its current version.
+  ///
+  /// The expected schema is created in `scratch`, a separate database used only for the comparison: its current schema
+  /// is dropped and the migration from the empty state is applied inside a transaction, then rolled back. The live
+  /// database is only read, while holding the migration lock.
+  pub async fn verify(&self, db: &PgPool, scratch: &PgPool) -> Result<SchemaVerification, Box<dyn Error>> {
+    let (live_name,): (String,) = sqlx::query_as("SELECT CURRENT_DATABASE()::TEXT;").fetch_one(db).await?;
+    let (scratch_name,): (String,) = sqlx::query_as("SELECT CURRENT_DATABASE()::TEXT;")
+      .fetch_one(scratch)
+      .await?;
+    if live_name == scratch_name {
+      return Err(
+        format!(
+          "The scratch database must differ from the verified database: {}",
+          live_name
+        )
+        .into(),
+      );
+    }
+
+    let mut tx = db.begin().await?;
+    self.tx_lock(&mut tx).await?;
+    let meta = self.inner_get_schema_meta(&mut tx).await?;
+    let state = self.meta_state(meta.as_ref());
+    let actual = self.tx_get_snapshot(&mut tx).await?;
+    let migration = self
+      .inner_create_migration(SchemaState::Empty, state, MigrationDirection::UpgradeOnly)
+      .expect("Unreachable state from empty DB");
+    let mut scratch_tx = scratch.begin().await?;
+    self.tx_empty(&mut scratch_tx).await?;
+    self.tx_apply_migration(&mut scratch_tx, &migration, &|_| {}).await?;
+    let expected = self.tx_get_snapshot(&mut scratch_tx).await?;
+    scratch_tx.rollback().await?;
+    tx.rollback().await?;
+
+    let actual: BTreeSet<&str> = actual.lines().collect();
+    let expected: BTreeSet<&str> = expected.lines().collect();
+    Ok(SchemaVerification {
+      version: state_version(state),
+      recorded_checksum: meta.and_then(|meta| meta.checksum),
+      expected_checksum: match state {
+        SchemaState::Empty => None,
+        SchemaState::Version(v) => Some(self.checksum(v)),
+      },
+      missing: expected.difference(&actual).map(|l| l.to_string()).collect(),
+      unexpected: actual.difference(&expected).map(|l| l.to_string()).collect(),
+    })
+  }
+
+  /// Describe the objects of the current schema: types, tables, constraints, indexes and functions.
+  ///
+  /// The objects storing the schema meta are not included.
+  ///
+  /// The description does not depend on object ids or creation order: two databases migrated through different paths
+  /// to the same version must have the same snapshot.
+  pub async fn get_snapshot(&self, db: &PgPool) -> Result<String, Box<dyn Error>> {
+    let mut tx = db.begin().await?;
+    let snapshot = self.tx_get_snapshot(&mut tx).await?;
+    tx.commit().await?;
+    Ok(snapshot)
+  }
+
+  async fn tx_get_snapshot(&self, tx: &mut Transaction<'_, Postgres>) -> Result<String, Box<dyn Error>> {
+    const QUERIES: [(&str, &str); 6] = [
+      (
+        "type",
+        r"
+        SELECT typname::TEXT, typtype::TEXT, format_type(typbasetype, typtypmod), NULL::TEXT
+        FROM pg_catalog.pg_type
+        WHERE typnamespace = CURRENT_SCHEMA()::REGNAMESPACE
+        ORDER BY typname;
+      ",
+      ),
+      (
+        "enum",
+        r"
+        SELECT typname::TEXT, enumlabel::TEXT, NULL::TEXT, NULL::TEXT
+        FROM pg_catalog.pg_enum INNER JOIN pg_catalog.pg_type ON (pg_type.oid = enumtypid)
+        WHERE typnamespace = CURRENT_SCHEMA()::REGNAMESPACE
+        ORDER BY typname, enumsortorder;
+      ",
+      ),
+      (
+        "column",
+        r"
+        SELECT table_name::TEXT, column_name::TEXT, CONCAT_WS(' ', data_type, domain_name, is_nullable), column_default::TEXT
+        FROM information_schema.columns
+        WHERE table_schema = CURRENT_SCHEMA()
+        ORDER BY table_name, ordinal_position;
+      ",
+      ),
+      (
+        "constraint",
+        r"
+        SELECT conrelid::REGCLASS::TEXT, contypid::REGTYPE::TEXT, conname::TEXT, pg_get_constraintdef(oid)
+        FROM pg_catalog.pg_constraint
+        WHERE connamespace = CURRENT_SCHEMA()::REGNAMESPACE
+        ORDER BY 1, 2, 3;
+      ",
+      ),
+      (
+        "index",
+        r"
+        SELECT tablename::TEXT, indexname::TEXT, indexdef, NULL::TEXT
+        FROM pg_catalog.pg_indexes
+        WHERE schemaname = CURRENT_SCHEMA()
+        ORDER BY tablename, indexname;
+      ",
+      ),
+      (
+        "function",
+        r"
+        SELECT proname::TEXT, pg_get_function_identity_arguments(oid), pg_get_function_result(oid), prosrc
+        FROM pg_catalog.pg_proc
+        WHERE pronamespace = CURRENT_SCHEMA()::REGNAMESPACE
+        ORDER BY 1, 2;
+      ",
+      ),
+    ];
+
+    type Row = (Option<String>, Option<String>, Option<String>, Option<String>);
+
+    let mut snapshot = String::new();
+    for (kind, query) in QUERIES.iter() {
+      let rows: Vec<Row> = sqlx::query_as(query).fetch_all(&mut *tx).await?;
+      for row in rows {
+        let is_meta = [&row.0, &row.1]
+          .iter()
+          .any(|name| matches!(name, Some(name) if META_OBJECTS.contains(&name.trim_start_matches('_'))));
+        if is_meta {
+          continue;
+        }
+        let fields = [row.0, row.1, row.2, row.3];
+        snapshot.push_str(kind);
+        for field in fields.iter() {
+          snapshot.push('\t');
+          snapshot.push_str(field.as_deref().unwrap_or("NULL"));
+        }
+        snapshot.push('\n');
+      }
+    }
+    Ok(snapshot)
+  }
+
   pub async fn empty(&self, db: &PgPool) -> Result<(), Box<dyn Error>> {
     let mut tx = db.begin().await?;
+    self.tx_lock(&mut tx).await?;
     self.tx_empty(&mut tx).await?;
     tx.commit().await?;
     Ok(())
@@ -530,6 +1069,7 @@ impl SchemaResolver {
 
   async fn inner_force_create(&self, db: &PgPool, state: SchemaState, void: bool) -> Result<(), Box<dyn Error>> {
     let mut tx = db.begin().await?;
+    self.tx_lock(&mut tx).await?;
     let mut latest_force_created_state = self.latest_force_created_state.write().unwrap();
     if void && *latest_force_created_state == Some(state) {
       let cur = self.inner_get_state(&mut tx).await?;
@@ -544,7 +1084,7 @@ impl SchemaResolver {
       .inner_create_migration(SchemaState::Empty, state, MigrationDirection::UpgradeOnly)
       .expect("Unreachable state from empty DB");
     self.tx_empty(&mut tx).await?;
-    self.tx_apply_migration(&mut tx, &migration).await?;
+    self.tx_apply_migration(&mut tx, &migration, &|_| {}).await?;
     *latest_force_created_state = Some(state);
     tx.commit().await?;
     Ok(())
@@ -554,10 +1094,11 @@ impl SchemaResolver {
     &self,
     tx: &mut Transaction<'_, Postgres>,
     migration: &'_ SchemaMigration<'_>,
+    observer: &(dyn Fn(MigrationProgress) + Send + Sync),
   ) -> Result<(), Box<dyn Error>> {
     for w in migration.states.windows(2) {
       let [start, end] = *TryInto::<&[SchemaState; 2]>::try_into(w).unwrap();
-      self.tx_apply_edge(tx, start, end).await?;
+      self.tx_apply_edge(tx, start, end, observer).await?;
     }
     Ok(())
   }
@@ -567,24 +1108,235 @@ impl SchemaResolver {
     tx: &mut Transaction<'_, Postgres>,
     start: SchemaState,
     end: SchemaState,
+    observer: &(dyn Fn(MigrationProgress) + Send + Sync),
   ) -> Result<(), Box<dyn Error>> {
     let old_state = self.inner_get_state(&mut *tx).await?;
     assert_eq!(start, old_state);
+    let end_version = match end {
+      SchemaState::Empty => panic!("UnexpectedEmptyEndState"),
+      SchemaState::Version(v) => v,
+    };
+    let start = state_version(start);
+    let edge = self.graph.edge_weight(old_state, end).unwrap();
+    observer(MigrationProgress::Schema {
+      start,
+      end: end_version.0,
+    });
     {
-      let edge = self.graph.edge_weight(start, end).unwrap();
       let mut stream = tx.execute_many(edge.schema);
       while let Some(r) = stream.next().await {
         r.unwrap();
       }
     }
-    match end {
-      SchemaState::Empty => panic!("UnexpectedEmptyEndState"),
-      SchemaState::Version(v) => {
-        self.tx_set_schema_meta(&mut *tx, &SchemaMeta { version: v.0 }).await?;
+    if let Some(data) = edge.data {
+      observer(MigrationProgress::SqlData {
+        start,
+        end: end_version.0,
+      });
+      let mut stream = tx.execute_many(data);
+      while let Some(r) = stream.next().await {
+        r?;
       }
     }
+    if let Some(rust_data) = edge.rust_data.as_ref() {
+      let progress = DataProgress {
+        start,
+        end: end_version.0,
+        observer,
+      };
+      rust_data
+        .run(tx, &progress)
+        .await
+        .map_err(|e| -> Box<dyn Error> { e })?;
+    }
+    self
+      .tx_set_schema_meta(
+        &mut *tx,
+        &SchemaMeta {
+          version: end_version.0,
+          checksum: Some(self.checksum(end_version)),
+        },
+      )
+      .await?;
     let new_state = self.inner_get_state(&mut *tx).await?;
     debug_assert_eq!(end, new_state);
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::{DataMigrationError, DataProgress, MigrationDirection, MigrationProgress, SchemaResolver};
+  use futures_util::future::BoxFuture;
+  use include_dir::{include_dir, Dir};
+  use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+  use sqlx::{PgConnection, PgPool};
+  use std::num::NonZeroU32;
+  use std::sync::Mutex;
+
+  const DATA_MIGRATION_SCRIPTS: Dir = include_dir!("./test-resources/data-migration");
+
+  async fn connect_admin() -> PgPool {
+    let config = etwin_config::find_config(std::env::current_dir().unwrap()).unwrap();
+    PgPoolOptions::new()
+      .max_connections(2)
+      .connect_with(
+        PgConnectOptions::new()
+          .host(&config.db.host)
+          .port(config.db.port)
+          .database(&config.db.name)
+          .username(&config.db.admin_user)
+          .password(&config.db.admin_password),
+      )
+      .await
+      .unwrap()
+  }
+
+  fn v(version: u32) -> NonZeroU32 {
+    NonZeroU32::new(version).unwrap()
+  }
+
+  fn render_html<'a>(
+    db: &'a mut PgConnection,
+    progress: &'a DataProgress<'a>,
+  ) -> BoxFuture<'a, Result<(), DataMigrationError>> {
+    Box::pin(async move {
+      let posts: Vec<(i32, String)> = sqlx::query_as("SELECT post_id, body FROM posts ORDER BY post_id;")
+        .fetch_all(&mut *db)
+        .await?;
+      let total = posts.len() as u64;
+      progress.report(0, Some(total));
+      for (i, (post_id, body)) in posts.into_iter().enumerate() {
+        sqlx::query("UPDATE posts SET html = $2 WHERE post_id = $1;")
+          .bind(post_id)
+          .bind(format!("<p>{}</p>", body))
+          .execute(&mut *db)
+          .await?;
+        progress.report(i as u64 + 1, Some(total));
+      }
+      Ok(())
+    })
+  }
+
+  fn fail<'a>(
+    _db: &'a mut PgConnection,
+    _progress: &'a DataProgress<'a>,
+  ) -> BoxFuture<'a, Result<(), DataMigrationError>> {
+    Box::pin(async move { Err("DataMigrationFailure".into()) })
+  }
+
+  async fn insert_posts(db: &PgPool) {
+    for (post_id, body) in [(1, "Hello"), (2, "Hello, World!")] {
+      sqlx::query("INSERT INTO posts(post_id, body) VALUES ($1, $2);")
+        .bind(post_id)
+        .bind(body)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+  }
+
+  /// A failing data migration rolls back the schema change of its step.
+  async fn check_failed_data_migration_is_rolled_back(db: &PgPool) {
+    let resolver = SchemaResolver::new(&DATA_MIGRATION_SCRIPTS).with_rust_data(v(1), v(2), fail);
+    let version1 = resolver.get_version(v(1)).unwrap();
+    resolver.force_create(db, version1.into(), false).await.unwrap();
+    insert_posts(db).await;
+
+    let migration = resolver
+      .create_migration(version1.into(), resolver.get_latest(), MigrationDirection::UpgradeOnly)
+      .unwrap();
+    assert!(resolver.apply_migration(db, &migration).await.is_err());
+    assert_eq!(resolver.get_state(db).await.unwrap(), version1.into());
+    let columns: Vec<(String,)> = sqlx::query_as(
+      "SELECT column_name::TEXT FROM information_schema.columns WHERE table_name = 'posts' ORDER BY column_name;",
+    )
+    .fetch_all(db)
+    .await
+    .unwrap();
+    assert_eq!(columns, vec![("body".to_string(),), ("post_id".to_string(),)]);
+  }
+
+  /// Concurrent migrations are serialized: the second one fails instead of applying the scripts again.
+  async fn check_concurrent_migrations(db: &PgPool) {
+    let resolver = SchemaResolver::new(&DATA_MIGRATION_SCRIPTS);
+    let version1 = resolver.get_version(v(1)).unwrap();
+    resolver.force_create(db, version1.into(), false).await.unwrap();
+
+    let migration = resolver
+      .create_migration(version1.into(), resolver.get_latest(), MigrationDirection::UpgradeOnly)
+      .unwrap();
+    let (first, second) = tokio::join!(
+      resolver.apply_migration(db, &migration),
+      resolver.apply_migration(db, &migration)
+    );
+    assert_eq!(first.is_ok() as u8 + second.is_ok() as u8, 1);
+    assert_eq!(resolver.get_state(db).await.unwrap(), resolver.get_latest().into());
+  }
+
+  #[tokio::test]
+  async fn test_migrations() {
+    let db = connect_admin().await;
+    check_concurrent_migrations(&db).await;
+    check_failed_data_migration_is_rolled_back(&db).await;
+    let resolver = SchemaResolver::new(&DATA_MIGRATION_SCRIPTS).with_rust_data(v(1), v(2), render_html);
+    resolver
+      .force_create(&db, resolver.get_version(v(1)).unwrap().into(), false)
+      .await
+      .unwrap();
+    insert_posts(&db).await;
+
+    let migration = resolver
+      .create_migration(
+        resolver.get_state(&db).await.unwrap(),
+        resolver.get_latest(),
+        MigrationDirection::UpgradeOnly,
+      )
+      .unwrap();
+    let step = migration.steps().next().unwrap();
+    assert!(step.data.is_some());
+    assert!(step.has_rust_data);
+
+    let events: Mutex<Vec<MigrationProgress>> = Mutex::new(Vec::new());
+    resolver
+      .apply_migration_with_progress(&db, &migration, &|e| events.lock().unwrap().push(e))
+      .await
+      .unwrap();
+    assert_eq!(resolver.get_state(&db).await.unwrap(), resolver.get_latest().into());
+    let posts: Vec<(i32, Option<String>, Option<i32>)> =
+      sqlx::query_as("SELECT post_id, html, word_count FROM posts ORDER BY post_id;")
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(
+      posts,
+      vec![
+        (1, Some("<p>Hello</p>".to_string()), Some(1)),
+        (2, Some("<p>Hello, World!</p>".to_string()), Some(2)),
+      ]
+    );
+    let rust_data = |done, total| MigrationProgress::RustData {
+      start: Some(v(1)),
+      end: v(2),
+      done,
+      total,
+    };
+    assert_eq!(
+      events.into_inner().unwrap(),
+      vec![
+        MigrationProgress::Schema {
+          start: Some(v(1)),
+          end: v(2)
+        },
+        MigrationProgress::SqlData {
+          start: Some(v(1)),
+          end: v(2)
+        },
+        rust_data(0, Some(2)),
+        rust_data(1, Some(2)),
+        rust_data(2, Some(2)),
+      ]
+    );
+    resolver.empty(&db).await.unwrap();
+  }
+}
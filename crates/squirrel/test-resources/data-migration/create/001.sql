@@ -0,0 +1,4 @@
+CREATE TABLE public.posts (
+  post_id INT PRIMARY KEY NOT NULL,
+  body TEXT NOT NULL
+);
@@ -0,0 +1,2 @@
+UPDATE public.posts
+SET word_count = array_length(regexp_split_to_array(body, '\s+'), 1);
@@ -0,0 +1,2 @@
+DROP SCHEMA public CASCADE;
+CREATE SCHEMA IF NOT EXISTS public;
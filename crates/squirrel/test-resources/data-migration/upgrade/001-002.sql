@@ -0,0 +1,3 @@
+ALTER TABLE public.posts
+  ADD COLUMN html TEXT NULL,
+  ADD COLUMN word_count INT NULL;
@@ -254,6 +254,27 @@ impl StoreState {
     }
   }
 
+  fn get_twinoid_oauth_to_refresh(&self, time: Instant) -> Vec<TwinoidRefreshToken> {
+    let mut tokens: Vec<TwinoidRefreshToken> = self
+      .twinoid_refresh_tokens
+      .values()
+      .filter(|rt| {
+        let access_token = self
+          .twinoid_user_to_access_token
+          .get(&rt.twinoid_user_id)
+          .and_then(|access_key| self.twinoid_access_tokens.get(access_key));
+        match access_token {
+          Some(access_token) => time >= access_token.expires_at,
+          // Without a (valid) access token, the user needs a new one
+          None => true,
+        }
+      })
+      .cloned()
+      .collect();
+    tokens.sort_by_key(|rt| rt.twinoid_user_id);
+    tokens
+  }
+
   fn touch_twinoid_oauth(&mut self, now: Instant, options: &TouchOauthTokenOptions) {
     {
       let old_user_id = self
@@ -386,6 +407,11 @@ where
     Ok(state.get_twinoid_oauth(now, options))
   }
 
+  async fn get_twinoid_oauth_to_refresh(&self, time: Instant) -> Result<Vec<TwinoidRefreshToken>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.get_twinoid_oauth_to_refresh(time))
+  }
+
   async fn touch_dinoparc(
     &self,
     user: DinoparcUserIdRef,
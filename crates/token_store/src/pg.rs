@@ -6,7 +6,7 @@ use etwin_core::dinoparc::{DinoparcServer, DinoparcSessionKey, DinoparcUserIdRef
 use etwin_core::hammerfest::{HammerfestServer, HammerfestSessionKey, HammerfestUserIdRef, StoredHammerfestSession};
 use etwin_core::oauth::{RfcOauthAccessTokenKey, RfcOauthRefreshTokenKey, TwinoidAccessToken, TwinoidRefreshToken};
 use etwin_core::token::{TokenStore, TouchOauthTokenOptions, TwinoidOauth};
-use etwin_core::twinoid::TwinoidUserIdRef;
+use etwin_core::twinoid::{TwinoidUserId, TwinoidUserIdRef};
 use etwin_core::types::AnyError;
 use etwin_populate::dinoparc::populate_dinoparc;
 use etwin_populate::hammerfest::populate_hammerfest;
@@ -296,6 +296,43 @@ where
     Ok(result)
   }
 
+  async fn get_twinoid_oauth_to_refresh(&self, time: Instant) -> Result<Vec<TwinoidRefreshToken>, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      twinoid_refresh_token: RfcOauthRefreshTokenKey,
+      twinoid_user_id: TwinoidUserId,
+      ctime: Instant,
+      atime: Instant,
+    }
+
+    let rows: Vec<Row> = sqlx::query_as::<_, Row>(
+      r"
+      SELECT pgp_sym_decrypt(trt.twinoid_refresh_token, $2::TEXT) AS twinoid_refresh_token, trt.twinoid_user_id,
+        trt.ctime, trt.atime
+      FROM twinoid_refresh_tokens AS trt
+        LEFT OUTER JOIN twinoid_access_tokens AS tat USING (twinoid_user_id)
+      WHERE tat.expiration_time IS NULL OR tat.expiration_time <= $1::INSTANT
+      ORDER BY trt.twinoid_user_id;
+    ",
+    )
+    .bind(time)
+    .bind(self.database_secret.as_str())
+    .fetch_all(self.database.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| TwinoidRefreshToken {
+          key: row.twinoid_refresh_token,
+          created_at: row.ctime,
+          accessed_at: row.atime,
+          twinoid_user_id: row.twinoid_user_id,
+        })
+        .collect(),
+    )
+  }
+
   async fn touch_dinoparc(
     &self,
     user: DinoparcUserIdRef,
@@ -648,7 +685,11 @@ mod test {
       .await
       .unwrap(),
     );
-    let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(Arc::clone(&clock), Arc::clone(&database)));
+    let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      Arc::clone(&uuid_generator),
+    ));
     let token_store: Arc<dyn TokenStore> = Arc::new(
       PgTokenStore::new(Arc::clone(&clock), Arc::clone(&database), database_secret)
         .await
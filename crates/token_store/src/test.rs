@@ -20,6 +20,7 @@ macro_rules! test_token_store {
     register_test!($(#[$meta])*, $api, test_touch_twinoid_oauth_twice);
     register_test!($(#[$meta])*, $api, test_revoke_twinoid_access_token);
     register_test!($(#[$meta])*, $api, test_revoke_twinoid_refresh_token);
+    register_test!($(#[$meta])*, $api, test_get_twinoid_oauth_to_refresh);
     register_test!($(#[$meta])*, $api, test_touch_hammerfest_session);
     register_test!($(#[$meta])*, $api, test_touch_hammerfest_session_to_update_atime_but_not_ctime);
     register_test!($(#[$meta])*, $api, test_touch_hammerfest_session_and_retrieve_it_without_atime_change);
@@ -318,6 +319,91 @@ pub(crate) async fn test_revoke_twinoid_refresh_token<
   assert_eq!(actual, expected);
 }
 
+pub(crate) async fn test_get_twinoid_oauth_to_refresh<
+  TyClock,
+  TyDinoparcStore,
+  TyHammerfestStore,
+  TyTokenStore,
+  TyTwinoidStore,
+>(
+  api: TestApi<TyClock, TyDinoparcStore, TyHammerfestStore, TyTokenStore, TyTwinoidStore>,
+) where
+  TyClock: ApiRef<VirtualClock>,
+  TyDinoparcStore: DinoparcStore,
+  TyHammerfestStore: HammerfestStore,
+  TyTokenStore: TokenStore,
+  TyTwinoidStore: TwinoidStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  for (id, name) in [("1", "alice"), ("2", "bob")] {
+    api
+      .twinoid_store
+      .touch_short_user(&ShortTwinoidUser {
+        id: id.parse().unwrap(),
+        display_name: name.parse().unwrap(),
+      })
+      .await
+      .unwrap();
+  }
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  api
+    .token_store
+    .touch_twinoid_oauth(&TouchOauthTokenOptions {
+      access_token: "X6nhMR2zwwfLNOR6EoQ9cM03BI3i66Q6".parse().unwrap(),
+      refresh_token: "HfznfQUg1C2p87ESIp6WRq945ppG6swD".parse().unwrap(),
+      expiration_time: Instant::ymd_hms(2021, 1, 1, 1, 0, 0),
+      twinoid_user_id: "1".parse().unwrap(),
+    })
+    .await
+    .unwrap();
+  api
+    .token_store
+    .touch_twinoid_oauth(&TouchOauthTokenOptions {
+      access_token: "BD8AbmGl6Y3cRRm5wBd0kSdbhVB1Yr0H".parse().unwrap(),
+      refresh_token: "d9LJCUUv7sGHnqa1zcUyz1AW3UGgMRqK".parse().unwrap(),
+      expiration_time: Instant::ymd_hms(2021, 1, 1, 2, 0, 0),
+      twinoid_user_id: "2".parse().unwrap(),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  let actual = api
+    .token_store
+    .get_twinoid_oauth_to_refresh(Instant::ymd_hms(2021, 1, 1, 0, 30, 0))
+    .await
+    .unwrap();
+  assert_eq!(actual, Vec::<TwinoidRefreshToken>::new());
+  let actual = api
+    .token_store
+    .get_twinoid_oauth_to_refresh(Instant::ymd_hms(2021, 1, 1, 1, 30, 0))
+    .await
+    .unwrap();
+  let expected = vec![TwinoidRefreshToken {
+    key: "HfznfQUg1C2p87ESIp6WRq945ppG6swD".parse().unwrap(),
+    created_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+    accessed_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+    twinoid_user_id: "1".parse().unwrap(),
+  }];
+  assert_eq!(actual, expected);
+  api
+    .token_store
+    .revoke_twinoid_access_token(&"BD8AbmGl6Y3cRRm5wBd0kSdbhVB1Yr0H".parse().unwrap())
+    .await
+    .unwrap();
+  let actual = api
+    .token_store
+    .get_twinoid_oauth_to_refresh(Instant::ymd_hms(2021, 1, 1, 0, 30, 0))
+    .await
+    .unwrap();
+  let expected = vec![TwinoidRefreshToken {
+    key: "d9LJCUUv7sGHnqa1zcUyz1AW3UGgMRqK".parse().unwrap(),
+    created_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+    accessed_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+    twinoid_user_id: "2".parse().unwrap(),
+  }];
+  assert_eq!(actual, expected);
+}
+
 pub(crate) async fn test_touch_hammerfest_session<
   TyClock,
   TyDinoparcStore,
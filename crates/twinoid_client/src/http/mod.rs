@@ -1,14 +1,18 @@
 mod errors;
+mod oauth;
 mod url;
 
+pub use oauth::HttpTwinoidOauthClient;
+
 use crate::http::url::TwinoidUrls;
 use async_trait::async_trait;
 use etwin_core::clock::Clock;
-use etwin_core::core::HtmlFragment;
-use etwin_core::twinoid::{api, TwinoidUserDisplayName};
+use etwin_core::twinoid::{api, TwinoidUserId};
 use etwin_core::twinoid::{TwinoidApiAuth, TwinoidClient};
 use etwin_core::types::AnyError;
-use reqwest::Client;
+use reqwest::{Client, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::time::Duration;
 
 const USER_AGENT: &str = "EtwinTwinoidClient";
@@ -34,47 +38,92 @@ where
       clock,
     })
   }
-}
 
-#[async_trait]
-impl<TyClock> TwinoidClient for HttpTwinoidClient<TyClock>
-where
-  TyClock: Clock,
-{
-  async fn get_me<Query: api::UserQuery>(
+  /// Query the Twinoid Graph API, returns `None` if the resource does not exist.
+  async fn get_graph<T: DeserializeOwned>(
     &self,
+    mut url: Url,
     auth: TwinoidApiAuth,
-    query: &Query,
-  ) -> Result<Query::Output, AnyError> {
-    let mut url = TwinoidUrls::new().me();
+    fields: &str,
+  ) -> Result<Option<T>, AnyError> {
     {
       let mut qs = url.query_pairs_mut();
       if let TwinoidApiAuth::Token(ref token) = &auth {
         qs.append_pair("access_token", token.as_str());
       };
-      qs.append_pair("fields", query.to_fields().as_ref());
+      qs.append_pair("fields", fields);
     }
 
     let req = self.client.get(url);
     let res = req.send().await?;
+    if res.status() == StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
 
     let body = res.bytes().await?;
 
-    match serde_json::from_slice::<Query::Output>(&body) {
-      Ok(res) => Ok(res),
-      Err(_e) => {
-        // TODO: Handle errors such as `{"error":"invalid_token"}`
-        let body = String::from_utf8_lossy(body.as_ref());
-        Err(body.as_ref().into())
-      }
+    #[derive(Deserialize)]
+    struct GraphError {
+      error: String,
+    }
+
+    match serde_json::from_slice::<T>(&body) {
+      Ok(res) => Ok(Some(res)),
+      Err(e) => match serde_json::from_slice::<GraphError>(&body) {
+        Ok(GraphError { error }) => Err(format!("Twinoid API error: {}", error).into()),
+        Err(_) => Err(
+          format!(
+            "invalid Twinoid API response: {}: {}",
+            e,
+            String::from_utf8_lossy(&body)
+          )
+          .into(),
+        ),
+      },
     }
   }
+}
+
+#[async_trait]
+impl<TyClock> TwinoidClient for HttpTwinoidClient<TyClock>
+where
+  TyClock: Clock,
+{
+  async fn get_me<Query: api::UserQuery>(
+    &self,
+    auth: TwinoidApiAuth,
+    query: &Query,
+  ) -> Result<Query::Output, AnyError> {
+    let url = TwinoidUrls::new().me();
+    let fields = query.to_fields().as_ref().to_string();
+    self
+      .get_graph(url, auth, &fields)
+      .await?
+      .ok_or_else(|| "Twinoid API error: current user not found".into())
+  }
+
+  async fn get_user<Query: api::UserQuery>(
+    &self,
+    auth: TwinoidApiAuth,
+    id: TwinoidUserId,
+    query: &Query,
+  ) -> Result<Option<Query::Output>, AnyError> {
+    let url = TwinoidUrls::new().user(id);
+    let fields = query.to_fields().as_ref().to_string();
+    self.get_graph(url, auth, &fields).await
+  }
+
+  async fn get_me_fields(&self, auth: TwinoidApiAuth, fields: &api::UserFields) -> Result<api::PartialUser, AnyError> {
+    self.get_me(auth, fields).await
+  }
 
-  async fn get_me_short(
+  async fn get_user_fields(
     &self,
     auth: TwinoidApiAuth,
-  ) -> Result<api::User<TwinoidUserDisplayName, Option<HtmlFragment>>, AnyError> {
-    self.get_me(auth, &api::ConstUserQuery::<true, true>).await
+    id: TwinoidUserId,
+    fields: &api::UserFields,
+  ) -> Result<Option<api::PartialUser>, AnyError> {
+    self.get_user(auth, id, fields).await
   }
 }
 
@@ -0,0 +1,125 @@
+use crate::http::url::TwinoidUrls;
+use crate::http::{TIMEOUT, USER_AGENT};
+use async_trait::async_trait;
+use etwin_core::core::Secret;
+use etwin_core::oauth::{RfcOauthAccessToken, RfcOauthGrantType, RfcOauthRefreshTokenKey, RfcOauthTokenType};
+use etwin_core::twinoid::{TwinoidOauthClient, TwinoidOauthError};
+use etwin_core::types::AnyError;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+
+/// Client for the Twinoid OAuth token endpoint, authenticated with the credentials of the Eternal-Twin application.
+pub struct HttpTwinoidOauthClient {
+  client: Client,
+  token_endpoint: Url,
+  callback_endpoint: Url,
+  client_id: String,
+  client_secret: Secret,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+  access_token: String,
+  token_type: String,
+  expires_in: i64,
+  refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+  error: String,
+}
+
+impl HttpTwinoidOauthClient {
+  pub fn new(client_id: String, client_secret: Secret, callback_endpoint: Url) -> Result<Self, AnyError> {
+    Ok(Self {
+      client: Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?,
+      token_endpoint: TwinoidUrls::new().oauth_token(),
+      callback_endpoint,
+      client_id,
+      client_secret,
+    })
+  }
+
+  /// Send a token request, `form` must include the client credentials (Twinoid reads them from the body).
+  async fn request_token(&self, form: &[(&str, &str)]) -> Result<RfcOauthAccessToken, TwinoidOauthError> {
+    let req = self.client.post(self.token_endpoint.clone()).form(form);
+    let res = req.send().await.map_err(|e| TwinoidOauthError::Other(Box::new(e)))?;
+    let body = res.bytes().await.map_err(|e| TwinoidOauthError::Other(Box::new(e)))?;
+
+    // Twinoid sends JSON with a `text/html` content type: parse the body regardless of the headers.
+    match serde_json::from_slice::<TokenResponse>(&body) {
+      Ok(res) => {
+        if !res.token_type.eq_ignore_ascii_case("bearer") {
+          return Err(TwinoidOauthError::Other(
+            format!("unsupported Twinoid token type: {:?}", res.token_type).into(),
+          ));
+        }
+        Ok(RfcOauthAccessToken {
+          token_type: RfcOauthTokenType::Bearer,
+          access_token: res
+            .access_token
+            .parse()
+            .map_err(|e| TwinoidOauthError::Other(Box::new(e)))?,
+          expires_in: res.expires_in,
+          refresh_token: match res.refresh_token {
+            Some(rt) => Some(rt.parse().map_err(|e| TwinoidOauthError::Other(Box::new(e)))?),
+            None => None,
+          },
+        })
+      }
+      Err(e) => match serde_json::from_slice::<ErrorResponse>(&body) {
+        Ok(ErrorResponse { error }) if error == "invalid_grant" => Err(TwinoidOauthError::InvalidGrant),
+        Ok(ErrorResponse { error }) => Err(TwinoidOauthError::Other(
+          format!("Twinoid OAuth error: {}", error).into(),
+        )),
+        Err(_) => Err(TwinoidOauthError::Other(
+          format!(
+            "invalid Twinoid OAuth response: {}: {}",
+            e,
+            String::from_utf8_lossy(&body)
+          )
+          .into(),
+        )),
+      },
+    }
+  }
+}
+
+#[async_trait]
+impl TwinoidOauthClient for HttpTwinoidOauthClient {
+  async fn exchange_code(&self, code: &str) -> Result<RfcOauthAccessToken, TwinoidOauthError> {
+    let grant_type = RfcOauthGrantType::AuthorizationCode.as_str();
+    self
+      .request_token(&[
+        ("client_id", self.client_id.as_str()),
+        ("client_secret", self.client_secret.as_str()),
+        ("redirect_uri", self.callback_endpoint.as_str()),
+        ("code", code),
+        ("grant_type", grant_type),
+      ])
+      .await
+  }
+
+  async fn refresh_token(
+    &self,
+    refresh_token: &RfcOauthRefreshTokenKey,
+  ) -> Result<RfcOauthAccessToken, TwinoidOauthError> {
+    let grant_type = RfcOauthGrantType::RefreshToken.as_str();
+    self
+      .request_token(&[
+        ("client_id", self.client_id.as_str()),
+        ("client_secret", self.client_secret.as_str()),
+        ("refresh_token", refresh_token.as_str()),
+        ("grant_type", grant_type),
+      ])
+      .await
+  }
+}
+
+#[cfg(feature = "neon")]
+impl neon::prelude::Finalize for HttpTwinoidOauthClient {}
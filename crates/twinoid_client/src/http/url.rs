@@ -1,3 +1,4 @@
+use etwin_core::twinoid::TwinoidUserId;
 use reqwest::Url;
 
 pub struct TwinoidUrls {
@@ -20,4 +21,12 @@ impl TwinoidUrls {
   pub fn me(&self) -> Url {
     self.make_url(&["me"])
   }
+
+  pub fn user(&self, id: TwinoidUserId) -> Url {
+    self.make_url(&["user", id.to_string().as_str()])
+  }
+
+  pub fn oauth_token(&self) -> Url {
+    Url::parse("https://twinoid.com/oauth/token").expect("failed to parse twinoid token URL")
+  }
 }
@@ -1,24 +1,449 @@
 use async_trait::async_trait;
-use etwin_core::core::HtmlFragment;
-use etwin_core::twinoid::api::{User, UserQuery};
-use etwin_core::twinoid::{TwinoidApiAuth, TwinoidClient, TwinoidUserDisplayName};
+use chrono::Duration;
+use etwin_core::clock::Clock;
+use etwin_core::core::Instant;
+use etwin_core::oauth::{RfcOauthAccessToken, RfcOauthAccessTokenKey, RfcOauthRefreshTokenKey, RfcOauthTokenType};
+use etwin_core::twinoid::api::{PartialUser, UserFields};
+use etwin_core::twinoid::{TwinoidApiAuth, TwinoidClient, TwinoidOauthClient, TwinoidOauthError, TwinoidUserId};
 use etwin_core::types::AnyError;
+use std::collections::HashMap;
+use std::sync::RwLock;
 
-pub struct MemTwinoidClient;
+struct MemUser {
+  user: PartialUser,
+  /// Contacts of the user: `(contact id, is friend)`
+  contacts: Vec<(TwinoidUserId, bool)>,
+}
+
+struct StoreState {
+  users: HashMap<TwinoidUserId, MemUser>,
+  tokens: HashMap<RfcOauthAccessTokenKey, TwinoidUserId>,
+}
+
+impl StoreState {
+  fn new() -> Self {
+    Self {
+      users: HashMap::new(),
+      tokens: HashMap::new(),
+    }
+  }
+
+  fn get_user(&self, id: TwinoidUserId, fields: &UserFields) -> Option<PartialUser> {
+    let mem_user = self.users.get(&id)?;
+    let mut user = fields.select(&mem_user.user);
+    if let Some(contact_fields) = &fields.contacts {
+      let contacts = mem_user
+        .contacts
+        .iter()
+        .filter_map(|(contact_id, friend)| {
+          let contact = self.get_user(*contact_id, &contact_fields.user)?;
+          Some(etwin_core::twinoid::api::Contact {
+            user: contact,
+            friend: Some(*friend).filter(|_| contact_fields.friend),
+          })
+        })
+        .collect();
+      user.contacts = Some(contacts);
+    }
+    Some(user)
+  }
+}
+
+/// In-memory Twinoid Graph API, for tests.
+pub struct MemTwinoidClient {
+  state: RwLock<StoreState>,
+}
+
+impl MemTwinoidClient {
+  pub fn new() -> Self {
+    Self {
+      state: RwLock::new(StoreState::new()),
+    }
+  }
+
+  /// Create or replace a user.
+  ///
+  /// The `contacts` field of `user` is ignored: use `add_contact` instead.
+  pub fn create_user(&self, user: PartialUser) -> Result<TwinoidUserId, AnyError> {
+    let id = TwinoidUserId::new(user.id)?;
+    let mut state = self.state.write().unwrap();
+    state.users.insert(
+      id,
+      MemUser {
+        user: PartialUser { contacts: None, ..user },
+        contacts: Vec::new(),
+      },
+    );
+    Ok(id)
+  }
+
+  pub fn add_contact(&self, user: TwinoidUserId, contact: TwinoidUserId, friend: bool) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    if !state.users.contains_key(&contact) {
+      return Err(format!("unknown Twinoid user: {}", contact).into());
+    }
+    match state.users.get_mut(&user) {
+      Some(user) => {
+        user.contacts.push((contact, friend));
+        Ok(())
+      }
+      None => Err(format!("unknown Twinoid user: {}", user).into()),
+    }
+  }
+
+  /// Create an access token resolving to `user` for `me` queries.
+  pub fn create_token(&self, token: RfcOauthAccessTokenKey, user: TwinoidUserId) {
+    let mut state = self.state.write().unwrap();
+    state.tokens.insert(token, user);
+  }
+}
+
+impl Default for MemTwinoidClient {
+  fn default() -> Self {
+    Self::new()
+  }
+}
 
 #[async_trait]
 impl TwinoidClient for MemTwinoidClient {
-  async fn get_me<Query: UserQuery>(&self, _auth: TwinoidApiAuth, _query: &Query) -> Result<Query::Output, AnyError>
-  where
-    Self: Sized,
-  {
-    todo!()
+  async fn get_me_fields(&self, auth: TwinoidApiAuth, fields: &UserFields) -> Result<PartialUser, AnyError> {
+    let state = self.state.read().unwrap();
+    let id = match &auth {
+      TwinoidApiAuth::Guest => return Err("Twinoid API error: missing access token".into()),
+      TwinoidApiAuth::Token(token) => match state.tokens.get(token) {
+        Some(id) => *id,
+        None => return Err("Twinoid API error: invalid_token".into()),
+      },
+    };
+    state
+      .get_user(id, fields)
+      .ok_or_else(|| format!("unknown Twinoid user: {}", id).into())
   }
 
-  async fn get_me_short(
+  async fn get_user_fields(
     &self,
     _auth: TwinoidApiAuth,
-  ) -> Result<User<TwinoidUserDisplayName, Option<HtmlFragment>>, AnyError> {
-    todo!()
+    id: TwinoidUserId,
+    fields: &UserFields,
+  ) -> Result<Option<PartialUser>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.get_user(id, fields))
+  }
+}
+
+#[cfg(feature = "neon")]
+impl neon::prelude::Finalize for MemTwinoidClient {}
+
+/// Lifetime of the access tokens issued by [`MemTwinoidOauthClient`], in seconds.
+const ACCESS_TOKEN_DURATION: i64 = 3600;
+
+struct OauthState {
+  next_token: u32,
+  codes: HashMap<String, TwinoidUserId>,
+  access_tokens: HashMap<RfcOauthAccessTokenKey, (TwinoidUserId, Instant)>,
+  refresh_tokens: HashMap<RfcOauthRefreshTokenKey, TwinoidUserId>,
+}
+
+impl OauthState {
+  fn issue_access_token(&mut self, now: Instant, user: TwinoidUserId) -> RfcOauthAccessTokenKey {
+    self.next_token += 1;
+    let key: RfcOauthAccessTokenKey = format!("access_token_{}", self.next_token).parse().unwrap();
+    let expires_at = now + Duration::seconds(ACCESS_TOKEN_DURATION);
+    self.access_tokens.insert(key.clone(), (user, expires_at));
+    key
+  }
+}
+
+/// In-memory Twinoid OAuth token endpoint, for tests.
+///
+/// Codes are single-use. Refreshing a token issues a new access token and
+/// keeps the refresh token.
+pub struct MemTwinoidOauthClient<TyClock> {
+  clock: TyClock,
+  state: RwLock<OauthState>,
+}
+
+impl<TyClock> MemTwinoidOauthClient<TyClock>
+where
+  TyClock: Clock,
+{
+  pub fn new(clock: TyClock) -> Self {
+    Self {
+      clock,
+      state: RwLock::new(OauthState {
+        next_token: 0,
+        codes: HashMap::new(),
+        access_tokens: HashMap::new(),
+        refresh_tokens: HashMap::new(),
+      }),
+    }
+  }
+
+  /// Create an authorization code granting tokens for `user`.
+  pub fn create_code(&self, code: String, user: TwinoidUserId) {
+    let mut state = self.state.write().unwrap();
+    state.codes.insert(code, user);
+  }
+
+  /// Revoke a refresh token, as if the user removed the authorization from Twinoid.
+  pub fn revoke_refresh_token(&self, refresh_token: &RfcOauthRefreshTokenKey) {
+    let mut state = self.state.write().unwrap();
+    state.refresh_tokens.remove(refresh_token);
+  }
+
+  /// Get the user of an access token, if it is still valid.
+  pub fn get_access_token_user(&self, access_token: &RfcOauthAccessTokenKey) -> Option<TwinoidUserId> {
+    let state = self.state.read().unwrap();
+    let now = self.clock.now();
+    match state.access_tokens.get(access_token) {
+      Some((user, expires_at)) if now < *expires_at => Some(*user),
+      _ => None,
+    }
+  }
+}
+
+#[async_trait]
+impl<TyClock> TwinoidOauthClient for MemTwinoidOauthClient<TyClock>
+where
+  TyClock: Clock,
+{
+  async fn exchange_code(&self, code: &str) -> Result<RfcOauthAccessToken, TwinoidOauthError> {
+    let mut state = self.state.write().unwrap();
+    let now = self.clock.now();
+    let user = state.codes.remove(code).ok_or(TwinoidOauthError::InvalidGrant)?;
+    let access_token = state.issue_access_token(now, user);
+    let refresh_token: RfcOauthRefreshTokenKey = format!("refresh_token_{}", state.next_token).parse().unwrap();
+    state.refresh_tokens.insert(refresh_token.clone(), user);
+    Ok(RfcOauthAccessToken {
+      token_type: RfcOauthTokenType::Bearer,
+      access_token,
+      expires_in: ACCESS_TOKEN_DURATION,
+      refresh_token: Some(refresh_token),
+    })
+  }
+
+  async fn refresh_token(
+    &self,
+    refresh_token: &RfcOauthRefreshTokenKey,
+  ) -> Result<RfcOauthAccessToken, TwinoidOauthError> {
+    let mut state = self.state.write().unwrap();
+    let now = self.clock.now();
+    let user = *state
+      .refresh_tokens
+      .get(refresh_token)
+      .ok_or(TwinoidOauthError::InvalidGrant)?;
+    let access_token = state.issue_access_token(now, user);
+    Ok(RfcOauthAccessToken {
+      token_type: RfcOauthTokenType::Bearer,
+      access_token,
+      expires_in: ACCESS_TOKEN_DURATION,
+      refresh_token: Some(refresh_token.clone()),
+    })
+  }
+}
+
+#[cfg(feature = "neon")]
+impl<TyClock> neon::prelude::Finalize for MemTwinoidOauthClient<TyClock> where TyClock: Clock {}
+
+#[cfg(test)]
+mod test {
+  use crate::mem::{MemTwinoidClient, MemTwinoidOauthClient};
+  use chrono::Duration;
+  use etwin_core::clock::VirtualClock;
+  use etwin_core::core::Instant;
+  use etwin_core::twinoid::api::{
+    Achievement, ConstUserQuery, ContactFields, PartialUser, Site, SiteUser, Stat, User, UserFields,
+  };
+  use etwin_core::twinoid::{
+    TwinoidApiAuth, TwinoidClient, TwinoidOauthClient, TwinoidOauthError, TwinoidSite, TwinoidSiteAchievement,
+    TwinoidSiteStat, TwinoidSiteUser, TwinoidUserId, TwinoidUserProfile,
+  };
+  use std::convert::TryFrom;
+  use std::sync::Arc;
+
+  fn make_user(id: u32, name: &str) -> PartialUser {
+    PartialUser {
+      id,
+      name: Some(name.parse().unwrap()),
+      title: None,
+      picture: None,
+      locale: Some("fr".to_string()),
+      contacts: None,
+      sites: Some(vec![SiteUser {
+        site: Site {
+          id: 1,
+          name: Some("Hammerfest".to_string()),
+          host: Some("www.hammerfest.fr".to_string()),
+          icon: None,
+          lang: Some("fr".to_string()),
+        },
+        real_id: Some(127),
+        link: None,
+        stats: Some(vec![Stat {
+          id: "maxLevel".to_string(),
+          score: 42,
+          name: Some("Niveau max".to_string()),
+          icon: None,
+          description: None,
+          rare: None,
+          social: None,
+        }]),
+        achievements: Some(vec![Achievement {
+          id: "level10".to_string(),
+          name: Some("Niveau 10".to_string()),
+          stat: Some("maxLevel".to_string()),
+          score: Some(10),
+          points: Some(5),
+          description: None,
+          date: Some("2012-03-14".to_string()),
+        }]),
+      }]),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_get_me() {
+    let client = MemTwinoidClient::new();
+    let alice = client.create_user(make_user(38, "alice")).unwrap();
+    let token = "HfuvI3PaHGN9ka7xKXd2tCQpqqhkxUjq".parse().unwrap();
+    client.create_token(token, alice);
+    let client: Arc<dyn TwinoidClient> = Arc::new(client);
+
+    let actual = client
+      .get_me_short(TwinoidApiAuth::Token(
+        "HfuvI3PaHGN9ka7xKXd2tCQpqqhkxUjq".parse().unwrap(),
+      ))
+      .await
+      .unwrap();
+    let expected = User {
+      id: 38,
+      name: "alice".parse().unwrap(),
+      title: None,
+    };
+    assert_eq!(actual, expected);
+
+    let actual = client
+      .get_me(
+        TwinoidApiAuth::Token("HfuvI3PaHGN9ka7xKXd2tCQpqqhkxUjq".parse().unwrap()),
+        &ConstUserQuery::<false, false>,
+      )
+      .await
+      .unwrap();
+    assert_eq!(
+      actual,
+      User {
+        id: 38,
+        name: (),
+        title: ()
+      }
+    );
+
+    assert!(client.get_me_short(TwinoidApiAuth::Guest).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_get_user_with_contacts() {
+    let client = MemTwinoidClient::new();
+    let alice = client.create_user(make_user(38, "alice")).unwrap();
+    let bob = client.create_user(make_user(39, "bob")).unwrap();
+    client.add_contact(alice, bob, true).unwrap();
+
+    let fields = UserFields::new().contacts(ContactFields::new().user(UserFields::new().name()).friend());
+    let actual = client.get_user(TwinoidApiAuth::Guest, alice, &fields).await.unwrap();
+    let expected = Some(PartialUser {
+      id: 38,
+      name: None,
+      title: None,
+      picture: None,
+      locale: None,
+      contacts: Some(vec![etwin_core::twinoid::api::Contact {
+        user: PartialUser {
+          id: 39,
+          name: Some("bob".parse().unwrap()),
+          title: None,
+          picture: None,
+          locale: None,
+          contacts: None,
+          sites: None,
+        },
+        friend: Some(true),
+      }]),
+      sites: None,
+    });
+    assert_eq!(actual, expected);
+
+    let missing = client
+      .get_user(TwinoidApiAuth::Guest, "40".parse().unwrap(), &fields)
+      .await
+      .unwrap();
+    assert_eq!(missing, None);
+  }
+
+  #[tokio::test]
+  async fn test_get_user_profile() {
+    let client = MemTwinoidClient::new();
+    let alice = client.create_user(make_user(38, "alice")).unwrap();
+
+    let user = client
+      .get_user(TwinoidApiAuth::Guest, alice, &TwinoidUserProfile::fields())
+      .await
+      .unwrap()
+      .unwrap();
+    let actual = TwinoidUserProfile::try_from(user).unwrap();
+    let expected = TwinoidUserProfile {
+      id: alice,
+      display_name: "alice".parse().unwrap(),
+      title: None,
+      locale: Some("fr".to_string()),
+      sites: vec![TwinoidSiteUser {
+        site: TwinoidSite {
+          id: "1".parse().unwrap(),
+          name: "Hammerfest".to_string(),
+          host: "www.hammerfest.fr".to_string(),
+        },
+        real_id: Some(127),
+        stats: vec![TwinoidSiteStat {
+          key: "maxLevel".to_string(),
+          score: 42,
+        }],
+        achievements: vec![TwinoidSiteAchievement {
+          key: "level10".to_string(),
+          name: "Niveau 10".to_string(),
+          stat: "maxLevel".to_string(),
+          score: 10,
+          points: 5,
+        }],
+      }],
+    };
+    assert_eq!(actual, expected);
+  }
+
+  #[tokio::test]
+  async fn test_exchange_code_and_refresh_token() {
+    let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2021, 1, 1, 0, 0, 0)));
+    let client = MemTwinoidOauthClient::new(Arc::clone(&clock));
+    let alice: TwinoidUserId = "38".parse().unwrap();
+    client.create_code("code".to_string(), alice);
+
+    let token = client.exchange_code("code").await.unwrap();
+    assert!(matches!(
+      client.exchange_code("code").await,
+      Err(TwinoidOauthError::InvalidGrant)
+    ));
+    assert_eq!(client.get_access_token_user(&token.access_token), Some(alice));
+
+    clock.advance_by(Duration::seconds(token.expires_in));
+    assert_eq!(client.get_access_token_user(&token.access_token), None);
+
+    let refresh_token = token.refresh_token.unwrap();
+    let refreshed = client.refresh_token(&refresh_token).await.unwrap();
+    assert_ne!(refreshed.access_token, token.access_token);
+    assert_eq!(client.get_access_token_user(&refreshed.access_token), Some(alice));
+
+    client.revoke_refresh_token(&refresh_token);
+    assert!(matches!(
+      client.refresh_token(&refresh_token).await,
+      Err(TwinoidOauthError::InvalidGrant)
+    ));
   }
 }
@@ -1,17 +1,33 @@
 use async_trait::async_trait;
 use etwin_core::clock::Clock;
-use etwin_core::twinoid::{ArchivedTwinoidUser, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidStore, TwinoidUserId};
+use etwin_core::core::Instant;
+use etwin_core::twinoid::{
+  ArchivedTwinoidUser, ArchivedTwinoidUserProfile, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidStore,
+  TwinoidUserId, TwinoidUserProfile,
+};
 use etwin_core::types::AnyError;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
+/// A period where the profile of a user kept the same content
+struct MemProfilePeriod {
+  start: Instant,
+  retrieved_at: Instant,
+  profile: TwinoidUserProfile,
+}
+
 struct StoreState {
   users: HashMap<TwinoidUserId, ArchivedTwinoidUser>,
+  /// Profile history of each user, ordered by `start`
+  profiles: HashMap<TwinoidUserId, Vec<MemProfilePeriod>>,
 }
 
 impl StoreState {
   fn new() -> Self {
-    Self { users: HashMap::new() }
+    Self {
+      users: HashMap::new(),
+      profiles: HashMap::new(),
+    }
   }
 
   fn get_user(&self, id: &TwinoidUserId) -> Option<&ArchivedTwinoidUser> {
@@ -21,6 +37,27 @@ impl StoreState {
   fn touch_user(&mut self, user: ArchivedTwinoidUser) {
     self.users.insert(user.id, user);
   }
+
+  fn get_profile(&self, id: &TwinoidUserId, time: Instant) -> Option<ArchivedTwinoidUserProfile> {
+    let history = self.profiles.get(id)?;
+    let period = history.iter().rev().find(|p| p.start <= time)?;
+    Some(ArchivedTwinoidUserProfile {
+      archived_at: period.retrieved_at,
+      profile: period.profile.clone(),
+    })
+  }
+
+  fn touch_profile(&mut self, now: Instant, profile: TwinoidUserProfile) {
+    let history = self.profiles.entry(profile.id).or_default();
+    match history.last_mut() {
+      Some(current) if current.profile == profile => current.retrieved_at = now,
+      _ => history.push(MemProfilePeriod {
+        start: now,
+        retrieved_at: now,
+        profile,
+      }),
+    }
+  }
 }
 
 pub struct MemTwinoidStore<TyClock: Clock> {
@@ -66,6 +103,36 @@ where
     state.touch_user(user.clone());
     Ok(user)
   }
+
+  async fn get_user_profile(
+    &self,
+    options: &GetTwinoidUserOptions,
+  ) -> Result<Option<ArchivedTwinoidUserProfile>, AnyError> {
+    let state = self.state.read().unwrap();
+    let time = options.time.unwrap_or_else(|| self.clock.now());
+    Ok(state.get_profile(&options.id, time))
+  }
+
+  async fn touch_user_profile(&self, profile: &TwinoidUserProfile) -> Result<ArchivedTwinoidUserProfile, AnyError> {
+    let mut state = self.state.write().unwrap();
+    let now = self.clock.now();
+    let archived_at = match state.get_user(&profile.id) {
+      Some(user) => user.archived_at,
+      None => now,
+    };
+    state.touch_user(ArchivedTwinoidUser {
+      id: profile.id,
+      archived_at,
+      display_name: profile.display_name.clone(),
+    });
+    let mut profile = profile.clone();
+    profile.normalize();
+    state.touch_profile(now, profile.clone());
+    Ok(ArchivedTwinoidUserProfile {
+      archived_at: now,
+      profile,
+    })
+  }
 }
 
 #[cfg(feature = "neon")]
@@ -101,4 +168,14 @@ mod test {
   async fn test_get_missing_user() {
     crate::test::test_get_missing_user(make_test_api()).await;
   }
+
+  #[tokio::test]
+  async fn test_touch_user_profile() {
+    crate::test::test_touch_user_profile(make_test_api()).await;
+  }
+
+  #[tokio::test]
+  async fn test_user_profile_history() {
+    crate::test::test_user_profile_history(make_test_api()).await;
+  }
 }
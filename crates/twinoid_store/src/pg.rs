@@ -1,37 +1,52 @@
 use async_trait::async_trait;
 use etwin_core::api::ApiRef;
 use etwin_core::clock::Clock;
-use etwin_core::core::Instant;
+use etwin_core::core::{HtmlFragment, Instant};
+use etwin_core::pg_num::PgU32;
 use etwin_core::twinoid::{
-  ArchivedTwinoidUser, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidStore, TwinoidUserDisplayName, TwinoidUserId,
+  ArchivedTwinoidUser, ArchivedTwinoidUserProfile, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidSite,
+  TwinoidSiteAchievement, TwinoidSiteId, TwinoidSiteStat, TwinoidSiteUser, TwinoidStore, TwinoidUserDisplayName,
+  TwinoidUserId, TwinoidUserProfile,
 };
 use etwin_core::types::AnyError;
-use sqlx::PgPool;
+use etwin_core::uuid::UuidGenerator;
+use etwin_postgres_tools::upsert_archive_query;
+use sqlx::postgres::PgQueryResult;
+use sqlx::types::Uuid;
+use sqlx::{PgPool, Postgres, Transaction};
 
-pub struct PgTwinoidStore<TyClock, TyDatabase>
+pub struct PgTwinoidStore<TyClock, TyDatabase, TyUuidGenerator>
 where
   TyClock: Clock,
   TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
 {
   clock: TyClock,
   database: TyDatabase,
+  uuid_generator: TyUuidGenerator,
 }
 
-impl<TyClock, TyDatabase> PgTwinoidStore<TyClock, TyDatabase>
+impl<TyClock, TyDatabase, TyUuidGenerator> PgTwinoidStore<TyClock, TyDatabase, TyUuidGenerator>
 where
   TyClock: Clock,
   TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
 {
-  pub fn new(clock: TyClock, database: TyDatabase) -> Self {
-    Self { clock, database }
+  pub fn new(clock: TyClock, database: TyDatabase, uuid_generator: TyUuidGenerator) -> Self {
+    Self {
+      clock,
+      database,
+      uuid_generator,
+    }
   }
 }
 
 #[async_trait]
-impl<TyClock, TyDatabase> TwinoidStore for PgTwinoidStore<TyClock, TyDatabase>
+impl<TyClock, TyDatabase, TyUuidGenerator> TwinoidStore for PgTwinoidStore<TyClock, TyDatabase, TyUuidGenerator>
 where
   TyClock: Clock,
   TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
 {
   async fn get_short_user(&self, options: &GetTwinoidUserOptions) -> Result<Option<ShortTwinoidUser>, AnyError> {
     #[derive(Debug, sqlx::FromRow)]
@@ -104,13 +119,354 @@ where
       display_name: short.display_name.clone(),
     })
   }
+
+  async fn get_user_profile(
+    &self,
+    options: &GetTwinoidUserOptions,
+  ) -> Result<Option<ArchivedTwinoidUserProfile>, AnyError> {
+    let time = options.time.unwrap_or_else(|| self.clock.now());
+    let mut tx = self.database.as_ref().begin().await?;
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      archived_at: Instant,
+      name: TwinoidUserDisplayName,
+      snapshot: Uuid,
+    }
+
+    // The snapshot valid at `time`, with its latest retrieval
+    let row: Option<Row> = sqlx::query_as::<_, Row>(
+      r"
+      SELECT p.retrieved_at[CARDINALITY(p.retrieved_at)] AS archived_at, u.name, p.snapshot
+      FROM twinoid_user_profiles AS p
+        INNER JOIN twinoid_users AS u USING (twinoid_user_id)
+      WHERE p.twinoid_user_id = $1::TWINOID_USER_ID AND lower(p.period) <= $2::INSTANT
+      ORDER BY lower(p.period) DESC
+      LIMIT 1;
+    ",
+    )
+    .bind(&options.id)
+    .bind(time)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let row = match row {
+      Some(row) => row,
+      None => return Ok(None),
+    };
+    let profile = get_twinoid_user_profile_snapshot(&mut tx, options.id, row.name, row.snapshot).await?;
+    tx.commit().await?;
+
+    Ok(Some(ArchivedTwinoidUserProfile {
+      archived_at: row.archived_at,
+      profile,
+    }))
+  }
+
+  async fn touch_user_profile(&self, profile: &TwinoidUserProfile) -> Result<ArchivedTwinoidUserProfile, AnyError> {
+    let now = self.clock.now();
+    let mut profile = profile.clone();
+    profile.normalize();
+    let mut tx = self.database.as_ref().begin().await?;
+    touch_twinoid_user(&mut tx, now, profile.id, &profile.display_name).await?;
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      snapshot: Uuid,
+    }
+
+    let current: Option<Row> = sqlx::query_as::<_, Row>(
+      r"
+      SELECT snapshot
+      FROM twinoid_user_profiles
+      WHERE twinoid_user_id = $1::TWINOID_USER_ID AND upper_inf(period);
+    ",
+    )
+    .bind(&profile.id)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    // Reuse the current snapshot if the profile did not change, so that only its retrieval time is updated
+    let mut snapshot: Option<Uuid> = None;
+    if let Some(current) = current {
+      let current_profile =
+        get_twinoid_user_profile_snapshot(&mut tx, profile.id, profile.display_name.clone(), current.snapshot).await?;
+      if current_profile == profile {
+        snapshot = Some(current.snapshot);
+      }
+    }
+    let snapshot = match snapshot {
+      Some(snapshot) => snapshot,
+      None => create_twinoid_user_profile_snapshot(&mut tx, now, &profile, self.uuid_generator.next()).await?,
+    };
+
+    let res: PgQueryResult = sqlx::query(upsert_archive_query!(
+      twinoid_user_profiles(
+        time($1 period, retrieved_at),
+        primary($2 twinoid_user_id::TWINOID_USER_ID),
+        data($3 snapshot::TWINOID_USER_PROFILE_SNAPSHOT_ID),
+      )
+    ))
+    .bind(now)
+    .bind(&profile.id)
+    .bind(snapshot)
+    .execute(&mut tx)
+    .await?;
+    // Affected row counts:
+    // 1 : 1 updated (matching data)
+    // 1 : 1 inserted (first insert)
+    // 2 : 1 inserted (data change), 1 invalidated (primary)
+    assert!((1..=2u64).contains(&res.rows_affected()));
+    tx.commit().await?;
+
+    Ok(ArchivedTwinoidUserProfile {
+      archived_at: now,
+      profile,
+    })
+  }
+}
+
+async fn get_twinoid_user_profile_snapshot(
+  tx: &mut Transaction<'_, Postgres>,
+  id: TwinoidUserId,
+  display_name: TwinoidUserDisplayName,
+  snapshot: Uuid,
+) -> Result<TwinoidUserProfile, AnyError> {
+  #[derive(Debug, sqlx::FromRow)]
+  struct ProfileRow {
+    title: Option<HtmlFragment>,
+    locale: Option<String>,
+  }
+
+  let profile: ProfileRow = sqlx::query_as::<_, ProfileRow>(
+    r"
+    SELECT title, locale
+    FROM twinoid_user_profile_snapshots
+    WHERE twinoid_user_profile_snapshot_id = $1::TWINOID_USER_PROFILE_SNAPSHOT_ID;
+  ",
+  )
+  .bind(snapshot)
+  .fetch_one(&mut *tx)
+  .await?;
+
+  #[derive(Debug, sqlx::FromRow)]
+  struct SiteRow {
+    twinoid_site_id: TwinoidSiteId,
+    name: String,
+    host: String,
+    real_id: Option<PgU32>,
+  }
+
+  let site_rows: Vec<SiteRow> = sqlx::query_as::<_, SiteRow>(
+    r"
+    SELECT su.twinoid_site_id, s.name, s.host, su.real_id
+    FROM twinoid_site_users AS su
+      INNER JOIN twinoid_sites AS s USING (twinoid_site_id)
+    WHERE su.twinoid_user_profile_snapshot_id = $1::TWINOID_USER_PROFILE_SNAPSHOT_ID;
+  ",
+  )
+  .bind(snapshot)
+  .fetch_all(&mut *tx)
+  .await?;
+
+  #[derive(Debug, sqlx::FromRow)]
+  struct StatRow {
+    twinoid_site_id: TwinoidSiteId,
+    stat_key: String,
+    score: i64,
+  }
+
+  let stat_rows: Vec<StatRow> = sqlx::query_as::<_, StatRow>(
+    r"
+    SELECT twinoid_site_id, stat_key, score
+    FROM twinoid_site_user_stats
+    WHERE twinoid_user_profile_snapshot_id = $1::TWINOID_USER_PROFILE_SNAPSHOT_ID;
+  ",
+  )
+  .bind(snapshot)
+  .fetch_all(&mut *tx)
+  .await?;
+
+  #[derive(Debug, sqlx::FromRow)]
+  struct AchievementRow {
+    twinoid_site_id: TwinoidSiteId,
+    achievement_key: String,
+    name: String,
+    stat_key: String,
+    score: i64,
+    points: i64,
+  }
+
+  let achievement_rows: Vec<AchievementRow> = sqlx::query_as::<_, AchievementRow>(
+    r"
+    SELECT twinoid_site_id, achievement_key, name, stat_key, score, points
+    FROM twinoid_site_user_achievements
+    WHERE twinoid_user_profile_snapshot_id = $1::TWINOID_USER_PROFILE_SNAPSHOT_ID;
+  ",
+  )
+  .bind(snapshot)
+  .fetch_all(&mut *tx)
+  .await?;
+
+  let sites = site_rows
+    .into_iter()
+    .map(|site| TwinoidSiteUser {
+      site: TwinoidSite {
+        id: site.twinoid_site_id,
+        name: site.name,
+        host: site.host,
+      },
+      real_id: site.real_id.map(u32::from),
+      stats: stat_rows
+        .iter()
+        .filter(|r| r.twinoid_site_id == site.twinoid_site_id)
+        .map(|r| TwinoidSiteStat {
+          key: r.stat_key.clone(),
+          score: r.score,
+        })
+        .collect(),
+      achievements: achievement_rows
+        .iter()
+        .filter(|r| r.twinoid_site_id == site.twinoid_site_id)
+        .map(|r| TwinoidSiteAchievement {
+          key: r.achievement_key.clone(),
+          name: r.name.clone(),
+          stat: r.stat_key.clone(),
+          score: r.score,
+          points: r.points,
+        })
+        .collect(),
+    })
+    .collect();
+
+  let mut user_profile = TwinoidUserProfile {
+    id,
+    display_name,
+    title: profile.title,
+    locale: profile.locale,
+    sites,
+  };
+  user_profile.normalize();
+  Ok(user_profile)
+}
+
+async fn create_twinoid_user_profile_snapshot(
+  tx: &mut Transaction<'_, Postgres>,
+  now: Instant,
+  profile: &TwinoidUserProfile,
+  snapshot: Uuid,
+) -> Result<Uuid, AnyError> {
+  sqlx::query(
+    r"
+    INSERT INTO twinoid_user_profile_snapshots(twinoid_user_profile_snapshot_id, twinoid_user_id, title, locale)
+    VALUES ($1::TWINOID_USER_PROFILE_SNAPSHOT_ID, $2::TWINOID_USER_ID, $3::TEXT, $4::VARCHAR);
+  ",
+  )
+  .bind(snapshot)
+  .bind(&profile.id)
+  .bind(&profile.title)
+  .bind(&profile.locale)
+  .execute(&mut *tx)
+  .await?;
+  for site_user in profile.sites.iter() {
+    touch_twinoid_site(&mut *tx, now, &site_user.site).await?;
+    sqlx::query(
+      r"
+      INSERT INTO twinoid_site_users(twinoid_user_profile_snapshot_id, twinoid_site_id, real_id)
+      VALUES ($1::TWINOID_USER_PROFILE_SNAPSHOT_ID, $2::TWINOID_SITE_ID, $3::U32);
+    ",
+    )
+    .bind(snapshot)
+    .bind(&site_user.site.id)
+    .bind(site_user.real_id.map(PgU32::from))
+    .execute(&mut *tx)
+    .await?;
+    for stat in site_user.stats.iter() {
+      sqlx::query(
+        r"
+        INSERT INTO twinoid_site_user_stats(twinoid_user_profile_snapshot_id, twinoid_site_id, stat_key, score)
+        VALUES ($1::TWINOID_USER_PROFILE_SNAPSHOT_ID, $2::TWINOID_SITE_ID, $3::TWINOID_STAT_KEY, $4::I64);
+      ",
+      )
+      .bind(snapshot)
+      .bind(&site_user.site.id)
+      .bind(&stat.key)
+      .bind(stat.score)
+      .execute(&mut *tx)
+      .await?;
+    }
+    for achievement in site_user.achievements.iter() {
+      sqlx::query(
+        r"
+        INSERT INTO twinoid_site_user_achievements(twinoid_user_profile_snapshot_id, twinoid_site_id, achievement_key, name, stat_key, score, points)
+        VALUES ($1::TWINOID_USER_PROFILE_SNAPSHOT_ID, $2::TWINOID_SITE_ID, $3::TWINOID_ACHIEVEMENT_KEY, $4::TEXT, $5::TWINOID_STAT_KEY, $6::I64, $7::I64);
+      ",
+      )
+      .bind(snapshot)
+      .bind(&site_user.site.id)
+      .bind(&achievement.key)
+      .bind(&achievement.name)
+      .bind(&achievement.stat)
+      .bind(achievement.score)
+      .bind(achievement.points)
+      .execute(&mut *tx)
+      .await?;
+    }
+  }
+  Ok(snapshot)
+}
+
+async fn touch_twinoid_user(
+  tx: &mut Transaction<'_, Postgres>,
+  now: Instant,
+  id: TwinoidUserId,
+  display_name: &TwinoidUserDisplayName,
+) -> Result<(), AnyError> {
+  sqlx::query(
+    r"
+    INSERT INTO twinoid_users(twinoid_user_id, name, archived_at)
+    VALUES ($1::TWINOID_USER_ID, $2::TWINOID_USER_DISPLAY_NAME, $3::INSTANT)
+      ON CONFLICT (twinoid_user_id)
+        DO UPDATE SET name = $2::TWINOID_USER_DISPLAY_NAME;
+  ",
+  )
+  .bind(id)
+  .bind(display_name)
+  .bind(now)
+  .execute(tx)
+  .await?;
+  Ok(())
+}
+
+async fn touch_twinoid_site(
+  tx: &mut Transaction<'_, Postgres>,
+  now: Instant,
+  site: &TwinoidSite,
+) -> Result<(), AnyError> {
+  sqlx::query(
+    r"
+    INSERT INTO twinoid_sites(twinoid_site_id, archived_at, name, host)
+    VALUES ($1::TWINOID_SITE_ID, $2::INSTANT, $3::VARCHAR, $4::VARCHAR)
+      ON CONFLICT (twinoid_site_id)
+        DO UPDATE SET name = $3::VARCHAR, host = $4::VARCHAR;
+  ",
+  )
+  .bind(site.id)
+  .bind(now)
+  .bind(&site.name)
+  .bind(&site.host)
+  .execute(tx)
+  .await?;
+  Ok(())
 }
 
 #[cfg(feature = "neon")]
-impl<TyClock, TyDatabase> neon::prelude::Finalize for PgTwinoidStore<TyClock, TyDatabase>
+impl<TyClock, TyDatabase, TyUuidGenerator> neon::prelude::Finalize
+  for PgTwinoidStore<TyClock, TyDatabase, TyUuidGenerator>
 where
   TyClock: Clock,
   TyDatabase: ApiRef<PgPool>,
+  TyUuidGenerator: UuidGenerator,
 {
 }
 
@@ -121,6 +477,7 @@ mod test {
   use etwin_core::clock::VirtualClock;
   use etwin_core::core::Instant;
   use etwin_core::twinoid::TwinoidStore;
+  use etwin_core::uuid::Uuid4Generator;
   use etwin_db_schema::force_create_latest;
   use serial_test::serial;
   use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
@@ -159,7 +516,12 @@ mod test {
     let database = Arc::new(database);
 
     let clock = Arc::new(VirtualClock::new(Instant::ymd_hms(2020, 1, 1, 0, 0, 0)));
-    let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(Arc::clone(&clock), Arc::clone(&database)));
+    let uuid_generator = Arc::new(Uuid4Generator);
+    let twinoid_store: Arc<dyn TwinoidStore> = Arc::new(PgTwinoidStore::new(
+      Arc::clone(&clock),
+      Arc::clone(&database),
+      uuid_generator,
+    ));
 
     TestApi { clock, twinoid_store }
   }
@@ -181,4 +543,16 @@ mod test {
   async fn test_get_missing_user() {
     crate::test::test_get_missing_user(make_test_api().await).await;
   }
+
+  #[tokio::test]
+  #[serial]
+  async fn test_touch_user_profile() {
+    crate::test::test_touch_user_profile(make_test_api().await).await;
+  }
+
+  #[tokio::test]
+  #[serial]
+  async fn test_user_profile_history() {
+    crate::test::test_user_profile_history(make_test_api().await).await;
+  }
 }
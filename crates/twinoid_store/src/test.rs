@@ -2,7 +2,10 @@ use chrono::Duration;
 use etwin_core::api::ApiRef;
 use etwin_core::clock::VirtualClock;
 use etwin_core::core::Instant;
-use etwin_core::twinoid::{ArchivedTwinoidUser, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidStore};
+use etwin_core::twinoid::{
+  ArchivedTwinoidUser, ArchivedTwinoidUserProfile, GetTwinoidUserOptions, ShortTwinoidUser, TwinoidSite,
+  TwinoidSiteAchievement, TwinoidSiteStat, TwinoidSiteUser, TwinoidStore, TwinoidUserProfile,
+};
 
 pub(crate) struct TestApi<TyClock, TyTwinoidStore>
 where
@@ -114,3 +117,254 @@ where
     assert_eq!(actual, expected);
   }
 }
+
+pub(crate) async fn test_touch_user_profile<TyClock, TyTwinoidStore>(api: TestApi<TyClock, TyTwinoidStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyTwinoidStore: TwinoidStore,
+{
+  let hammerfest = TwinoidSite {
+    id: "1".parse().unwrap(),
+    name: "Hammerfest".to_string(),
+    host: "www.hammerfest.fr".to_string(),
+  };
+  let alphabounce = TwinoidSite {
+    id: "12".parse().unwrap(),
+    name: "AlphaBounce".to_string(),
+    host: "www.alphabounce.com".to_string(),
+  };
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  {
+    let actual = api
+      .twinoid_store
+      .touch_user_profile(&TwinoidUserProfile {
+        id: "123".parse().unwrap(),
+        display_name: "alice".parse().unwrap(),
+        title: Some("Apprentie".to_string()),
+        locale: Some("fr".to_string()),
+        sites: vec![
+          TwinoidSiteUser {
+            site: alphabounce.clone(),
+            real_id: None,
+            stats: vec![],
+            achievements: vec![],
+          },
+          TwinoidSiteUser {
+            site: hammerfest.clone(),
+            real_id: Some(127),
+            stats: vec![
+              TwinoidSiteStat {
+                key: "maxLevel".to_string(),
+                score: 42,
+              },
+              TwinoidSiteStat {
+                key: "igor".to_string(),
+                score: 3,
+              },
+            ],
+            achievements: vec![TwinoidSiteAchievement {
+              key: "level10".to_string(),
+              name: "Niveau 10".to_string(),
+              stat: "maxLevel".to_string(),
+              score: 10,
+              points: 5,
+            }],
+          },
+        ],
+      })
+      .await
+      .unwrap();
+    let expected = ArchivedTwinoidUserProfile {
+      archived_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+      profile: TwinoidUserProfile {
+        id: "123".parse().unwrap(),
+        display_name: "alice".parse().unwrap(),
+        title: Some("Apprentie".to_string()),
+        locale: Some("fr".to_string()),
+        sites: vec![
+          TwinoidSiteUser {
+            site: hammerfest.clone(),
+            real_id: Some(127),
+            stats: vec![
+              TwinoidSiteStat {
+                key: "igor".to_string(),
+                score: 3,
+              },
+              TwinoidSiteStat {
+                key: "maxLevel".to_string(),
+                score: 42,
+              },
+            ],
+            achievements: vec![TwinoidSiteAchievement {
+              key: "level10".to_string(),
+              name: "Niveau 10".to_string(),
+              stat: "maxLevel".to_string(),
+              score: 10,
+              points: 5,
+            }],
+          },
+          TwinoidSiteUser {
+            site: alphabounce.clone(),
+            real_id: None,
+            stats: vec![],
+            achievements: vec![],
+          },
+        ],
+      },
+    };
+    assert_eq!(actual, expected);
+  }
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+  {
+    let actual = api
+      .twinoid_store
+      .get_user_profile(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: None,
+      })
+      .await
+      .unwrap();
+    assert_eq!(
+      actual.map(|p| (p.archived_at, p.profile.sites.len())),
+      Some((Instant::ymd_hms(2021, 1, 1, 0, 0, 0), 2))
+    );
+  }
+  {
+    let actual = api
+      .twinoid_store
+      .get_short_user(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: None,
+      })
+      .await
+      .unwrap();
+    let expected = Some(ShortTwinoidUser {
+      id: "123".parse().unwrap(),
+      display_name: "alice".parse().unwrap(),
+    });
+    assert_eq!(actual, expected);
+  }
+  {
+    api
+      .twinoid_store
+      .touch_user_profile(&TwinoidUserProfile {
+        id: "123".parse().unwrap(),
+        display_name: "alice".parse().unwrap(),
+        title: None,
+        locale: Some("fr".to_string()),
+        sites: vec![TwinoidSiteUser {
+          site: hammerfest.clone(),
+          real_id: Some(127),
+          stats: vec![TwinoidSiteStat {
+            key: "maxLevel".to_string(),
+            score: 50,
+          }],
+          achievements: vec![],
+        }],
+      })
+      .await
+      .unwrap();
+    let actual = api
+      .twinoid_store
+      .get_user_profile(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: None,
+      })
+      .await
+      .unwrap();
+    let expected = Some(ArchivedTwinoidUserProfile {
+      archived_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+      profile: TwinoidUserProfile {
+        id: "123".parse().unwrap(),
+        display_name: "alice".parse().unwrap(),
+        title: None,
+        locale: Some("fr".to_string()),
+        sites: vec![TwinoidSiteUser {
+          site: hammerfest,
+          real_id: Some(127),
+          stats: vec![TwinoidSiteStat {
+            key: "maxLevel".to_string(),
+            score: 50,
+          }],
+          achievements: vec![],
+        }],
+      },
+    });
+    assert_eq!(actual, expected);
+  }
+}
+
+pub(crate) async fn test_user_profile_history<TyClock, TyTwinoidStore>(api: TestApi<TyClock, TyTwinoidStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyTwinoidStore: TwinoidStore,
+{
+  let hammerfest = TwinoidSite {
+    id: "1".parse().unwrap(),
+    name: "Hammerfest".to_string(),
+    host: "www.hammerfest.fr".to_string(),
+  };
+  let make_profile = |score: i64| TwinoidUserProfile {
+    id: "123".parse().unwrap(),
+    display_name: "alice".parse().unwrap(),
+    title: None,
+    locale: Some("fr".to_string()),
+    sites: vec![TwinoidSiteUser {
+      site: hammerfest.clone(),
+      real_id: Some(127),
+      stats: vec![TwinoidSiteStat {
+        key: "maxLevel".to_string(),
+        score,
+      }],
+      achievements: vec![],
+    }],
+  };
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  api.twinoid_store.touch_user_profile(&make_profile(42)).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 1));
+  api.twinoid_store.touch_user_profile(&make_profile(42)).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 2));
+  api.twinoid_store.touch_user_profile(&make_profile(50)).await.unwrap();
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 3));
+  {
+    let actual = api
+      .twinoid_store
+      .get_user_profile(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: Some(Instant::ymd_hms(2021, 1, 1, 0, 0, 1)),
+      })
+      .await
+      .unwrap();
+    let expected = Some(ArchivedTwinoidUserProfile {
+      archived_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+      profile: make_profile(42),
+    });
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .twinoid_store
+      .get_user_profile(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: None,
+      })
+      .await
+      .unwrap();
+    let expected = Some(ArchivedTwinoidUserProfile {
+      archived_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 2),
+      profile: make_profile(50),
+    });
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .twinoid_store
+      .get_user_profile(&GetTwinoidUserOptions {
+        id: "123".parse().unwrap(),
+        time: Some(Instant::ymd_hms(2020, 12, 31, 0, 0, 0)),
+      })
+      .await
+      .unwrap();
+    assert_eq!(actual, None);
+  }
+}
@@ -2,14 +2,15 @@ use async_trait::async_trait;
 use etwin_core::clock::Clock;
 use etwin_core::core::Instant;
 use etwin_core::email::EmailAddress;
+use etwin_core::mfa::{DeleteUserRecoveryCodeOptions, SetUserTotpOptions, TouchUserTotpStepOptions, UserTotp};
 use etwin_core::password::PasswordHash;
 use etwin_core::temporal::Temporal;
 use etwin_core::types::AnyError;
 use etwin_core::user::{
   CompleteSimpleUser, CreateUserOptions, DeleteUserError, GetShortUserOptions, GetUserOptions, GetUserResult,
-  ShortUser, ShortUserWithPassword, SimpleUser, UpdateUserError, UpdateUserOptions, UserDisplayName,
-  UserDisplayNameVersion, UserDisplayNameVersions, UserFields, UserId, UserIdRef, UserRef, UserStore, Username,
-  USERNAME_LOCK_DURATION, USER_DISPLAY_NAME_LOCK_DURATION, USER_PASSWORD_LOCK_DURATION,
+  ReplaceUserPasswordHashOptions, ShortUser, ShortUserWithPassword, SimpleUser, UpdateUserError, UpdateUserOptions,
+  UserDisplayName, UserDisplayNameVersion, UserDisplayNameVersions, UserFields, UserId, UserIdRef, UserRef, UserStore,
+  Username, USERNAME_LOCK_DURATION, USER_DISPLAY_NAME_LOCK_DURATION, USER_PASSWORD_LOCK_DURATION,
 };
 use etwin_core::uuid::UuidGenerator;
 use std::collections::hash_map::Entry;
@@ -73,6 +74,7 @@ pub(crate) struct StoreState {
   users: HashMap<UserId, MemUser>,
   users_by_username: HashMap<Username, Temporal<Option<UserId>>>,
   users_by_email: HashMap<EmailAddress, Temporal<Option<UserId>>>,
+  totp: HashMap<UserId, UserTotp>,
 }
 
 impl StoreState {
@@ -81,6 +83,7 @@ impl StoreState {
       users: HashMap::new(),
       users_by_username: HashMap::new(),
       users_by_email: HashMap::new(),
+      totp: HashMap::new(),
     }
   }
 
@@ -175,6 +178,15 @@ impl StoreState {
         return Err(UpdateUserError::LockedPassword(options.r#ref, lock_period.into(), now));
       }
     }
+    if let Some(Some(email)) = &options.patch.email {
+      let owner = self
+        .users_by_email
+        .get(email)
+        .and_then(|history| *history.current_value());
+      if matches!(owner, Some(owner) if owner != user.id) {
+        return Err(UpdateUserError::Other("EmailAddressAlreadyInUse".into()));
+      }
+    }
     if let Some(display_name) = &options.patch.display_name {
       user.display_name.set(now, display_name.clone());
     }
@@ -184,6 +196,24 @@ impl StoreState {
     if let Some(password) = &options.patch.password {
       user.password.set(now, password.clone());
     }
+    if let Some(email) = &options.patch.email {
+      if email != user.email_address.current_value() {
+        if let Some(old_email) = user.email_address.current_value() {
+          if let Some(history) = self.users_by_email.get_mut(old_email) {
+            history.set(now, None);
+          }
+        }
+        if let Some(new_email) = email {
+          match self.users_by_email.entry(new_email.clone()) {
+            Entry::Occupied(mut e) => e.get_mut().set(now, Some(user.id)),
+            Entry::Vacant(e) => {
+              e.insert(Temporal::new(now, Some(user.id)));
+            }
+          };
+        }
+        user.email_address.set(now, email.clone());
+      }
+    }
     Ok(user)
   }
 
@@ -193,6 +223,7 @@ impl StoreState {
       Some(u) => u,
       None => return Err(DeleteUserError::NotFound(user_ref)),
     };
+    self.totp.remove(&user.id);
     let mut usernames: HashSet<&Username> = HashSet::new();
     for snapshot in user.username.iter() {
       if let Some(username) = snapshot.value() {
@@ -308,11 +339,75 @@ where
     Ok(user.at(None).into())
   }
 
+  async fn replace_user_password_hash(&self, options: &ReplaceUserPasswordHashOptions) -> Result<bool, AnyError> {
+    let mut state = self.state.write().unwrap();
+    let user = match state.users.get_mut(&options.r#ref.id) {
+      Some(user) => user,
+      None => return Ok(false),
+    };
+    if user.password.current_value().as_ref() != Some(&options.old) {
+      return Ok(false);
+    }
+    user.password.replace_current_value(Some(options.new.clone()));
+    Ok(true)
+  }
+
   async fn hard_delete_user(&self, user_ref: UserIdRef) -> Result<(), DeleteUserError> {
     let mut state = self.state.write().unwrap();
     let _user = state.hard_delete(user_ref)?;
     Ok(())
   }
+
+  async fn get_user_totp(&self, user_ref: UserIdRef) -> Result<Option<UserTotp>, AnyError> {
+    let state = self.state.read().unwrap();
+    Ok(state.totp.get(&user_ref.id).cloned())
+  }
+
+  async fn set_user_totp(&self, options: &SetUserTotpOptions) -> Result<UserTotp, AnyError> {
+    let mut state = self.state.write().unwrap();
+    if !state.users.contains_key(&options.user.id) {
+      return Err("UserNotFound".into());
+    }
+    let totp = UserTotp {
+      user: options.user,
+      secret: options.secret.clone(),
+      enabled_at: self.clock.now(),
+      last_used_step: None,
+      recovery_codes: options.recovery_codes.clone(),
+    };
+    state.totp.insert(options.user.id, totp.clone());
+    Ok(totp)
+  }
+
+  async fn touch_user_totp_step(&self, options: &TouchUserTotpStepOptions) -> Result<bool, AnyError> {
+    let mut state = self.state.write().unwrap();
+    let totp = match state.totp.get_mut(&options.user.id) {
+      Some(totp) => totp,
+      None => return Ok(false),
+    };
+    if matches!(totp.last_used_step, Some(last) if last >= options.step) {
+      return Ok(false);
+    }
+    totp.last_used_step = Some(options.step);
+    Ok(true)
+  }
+
+  async fn delete_user_recovery_code(&self, options: &DeleteUserRecoveryCodeOptions) -> Result<bool, AnyError> {
+    let mut state = self.state.write().unwrap();
+    let totp = match state.totp.get_mut(&options.user.id) {
+      Some(totp) => totp,
+      None => return Ok(false),
+    };
+    let old_len = totp.recovery_codes.len();
+    totp.recovery_codes.retain(|code| *code != options.code);
+    Ok(totp.recovery_codes.len() < old_len)
+  }
+
+  async fn delete_user_totp(&self, user_ref: UserIdRef) -> Result<(), AnyError> {
+    let mut state = self.state.write().unwrap();
+    state.totp.remove(&user_ref.id);
+    Ok(())
+  }
 }
 
 #[cfg(feature = "neon")]
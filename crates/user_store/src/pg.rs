@@ -3,13 +3,16 @@ use etwin_core::api::ApiRef;
 use etwin_core::clock::Clock;
 use etwin_core::core::{Instant, Secret};
 use etwin_core::email::{touch_email_address, EmailAddress};
+use etwin_core::mfa::{
+  DeleteUserRecoveryCodeOptions, SetUserTotpOptions, TotpSecret, TouchUserTotpStepOptions, UserTotp,
+};
 use etwin_core::password::PasswordHash;
 use etwin_core::types::AnyError;
 use etwin_core::user::{
   CompleteSimpleUser, CreateUserOptions, DeleteUserError, GetShortUserOptions, GetUserOptions, GetUserResult,
-  ShortUser, ShortUserWithPassword, UpdateUserError, UpdateUserOptions, UserDisplayName, UserDisplayNameVersion,
-  UserDisplayNameVersions, UserFields, UserId, UserIdRef, UserRef, UserStore, Username, USERNAME_LOCK_DURATION,
-  USER_DISPLAY_NAME_LOCK_DURATION, USER_PASSWORD_LOCK_DURATION,
+  ReplaceUserPasswordHashOptions, ShortUser, ShortUserWithPassword, UpdateUserError, UpdateUserOptions,
+  UserDisplayName, UserDisplayNameVersion, UserDisplayNameVersions, UserFields, UserId, UserIdRef, UserRef, UserStore,
+  Username, USERNAME_LOCK_DURATION, USER_DISPLAY_NAME_LOCK_DURATION, USER_PASSWORD_LOCK_DURATION,
 };
 use etwin_core::uuid::UuidGenerator;
 use sqlx::postgres::PgPool;
@@ -123,7 +126,7 @@ where
       is_administrator: row.is_administrator,
       created_at: row.created_at,
       username: options.username.clone(),
-      email_address: None,
+      email_address: options.email.clone(),
     };
 
     Ok(user)
@@ -137,6 +140,7 @@ where
       is_administrator: bool,
       display_name: UserDisplayName,
       username: Option<Username>,
+      email: Option<EmailAddress>,
     }
 
     let mut ref_id: Option<UserId> = None;
@@ -147,19 +151,18 @@ where
       UserRef::Username(r) => ref_username = Some(r.username.clone()),
       UserRef::Email(r) => ref_email = Some(r.email.clone()),
     }
-    if ref_email.is_some() {
-      return Ok(None);
-    }
 
     let row = sqlx::query_as::<_, Row>(
       r"
-      SELECT user_id, created_at, is_administrator, display_name, username
+      SELECT user_id, created_at, is_administrator, display_name, username, pgp_sym_decrypt(email, $4::TEXT)::EMAIL_ADDRESS AS email
       FROM users_current
-      WHERE user_id = $1::USER_ID OR username = $2::USERNAME;
+      WHERE user_id = $1::USER_ID OR username = $2::USERNAME OR _email_hash = digest($3::EMAIL_ADDRESS, 'sha256');
       ",
     )
     .bind(ref_id)
     .bind(ref_username)
+    .bind(ref_email)
+    .bind(self.database_secret.as_str())
     .fetch_optional(self.database.as_ref())
     .await?;
 
@@ -179,7 +182,7 @@ where
       is_administrator: row.is_administrator,
       created_at: row.created_at,
       username: row.username,
-      email_address: None,
+      email_address: row.email,
     };
 
     let user = match options.fields {
@@ -214,20 +217,18 @@ where
       UserRef::Username(r) => ref_username = Some(r.username.clone()),
       UserRef::Email(r) => ref_email = Some(r.email.clone()),
     }
-    if ref_email.is_some() {
-      return Ok(None);
-    }
 
     let row = sqlx::query_as::<_, Row>(
       r"
       SELECT user_id, display_name, pgp_sym_decrypt_bytea(password, $1::TEXT) AS password
       FROM users_current
-      WHERE user_id = $2::USER_ID OR username = $3::USERNAME;
+      WHERE user_id = $2::USER_ID OR username = $3::USERNAME OR _email_hash = digest($4::EMAIL_ADDRESS, 'sha256');
       ",
     )
     .bind(self.database_secret.as_str())
     .bind(ref_id)
     .bind(ref_username)
+    .bind(ref_email)
     .fetch_optional(self.database.as_ref())
     .await?;
 
@@ -265,19 +266,17 @@ where
       UserRef::Username(r) => ref_username = Some(r.username.clone()),
       UserRef::Email(r) => ref_email = Some(r.email.clone()),
     }
-    if ref_email.is_some() {
-      return Ok(None);
-    }
 
     let row = sqlx::query_as::<_, Row>(
       r"
       SELECT user_id, display_name
       FROM users_current
-      WHERE user_id = $1::USER_ID OR username = $2::USERNAME;
+      WHERE user_id = $1::USER_ID OR username = $2::USERNAME OR _email_hash = digest($3::EMAIL_ADDRESS, 'sha256');
       ",
     )
     .bind(ref_id)
     .bind(ref_username)
+    .bind(ref_email)
     .fetch_optional(self.database.as_ref())
     .await?;
 
@@ -299,6 +298,25 @@ where
     Ok(Some(user))
   }
 
+  async fn replace_user_password_hash(&self, options: &ReplaceUserPasswordHashOptions) -> Result<bool, AnyError> {
+    let res = sqlx::query(
+      r"
+      UPDATE users_history
+      SET password = pgp_sym_encrypt_bytea($3::PASSWORD_HASH, $4::TEXT)
+      WHERE
+        user_id = $1::USER_ID AND upper_inf(period)
+        AND pgp_sym_decrypt_bytea(password, $4::TEXT) = $2::PASSWORD_HASH;
+      ",
+    )
+    .bind(options.r#ref.id)
+    .bind(&options.old)
+    .bind(&options.new)
+    .bind(self.database_secret.as_str())
+    .execute(self.database.as_ref())
+    .await?;
+    Ok(res.rows_affected() == 1)
+  }
+
   async fn update_user(&self, options: &UpdateUserOptions) -> Result<CompleteSimpleUser, UpdateUserError> {
     let now = self.clock.now();
 
@@ -421,6 +439,15 @@ where
       }
     }
 
+    let email_hash: Option<Vec<u8>> = match &options.patch.email {
+      Some(Some(email)) => Some(
+        touch_email_address(&mut tx, &self.database_secret, email, now)
+          .await
+          .map_err(UpdateUserError::Other)?,
+      ),
+      _ => None,
+    };
+
     {
       #[derive(Debug, sqlx::FromRow)]
       struct Row {
@@ -432,18 +459,20 @@ where
       WITH prev_state AS (
         UPDATE users_history SET period = PERIOD(lower(period), $1::INSTANT), _is_current = NULL
         WHERE user_id = $2::USER_ID AND upper_inf(period)
-        RETURNING display_name, username, password
+        RETURNING display_name, username, email, password
       )
       INSERT INTO users_history(
         user_id, period, _is_current, updated_by,
         display_name,
         username,
+        email,
         password
       )
       SELECT
         $2::USER_ID, PERIOD($1::INSTANT, NULL), TRUE, $3::USER_ID,
         CASE WHEN $4::BOOLEAN THEN $5::USER_DISPLAY_NAME ELSE prev_state.display_name END,
         CASE WHEN $6::BOOLEAN THEN $7::USERNAME ELSE prev_state.username END,
+        CASE WHEN $11::BOOLEAN THEN $12::EMAIL_ADDRESS_HASH ELSE prev_state.email END,
         CASE WHEN $8::BOOLEAN THEN pgp_sym_encrypt_bytea($9::PASSWORD_HASH, $10::TEXT) ELSE prev_state.password END
       FROM prev_state
       RETURNING user_id;
@@ -459,6 +488,8 @@ where
       .bind(options.patch.password.is_some())
       .bind(options.patch.password.as_ref())
       .bind(self.database_secret.as_str())
+      .bind(options.patch.email.is_some())
+      .bind(email_hash)
       .execute(&mut tx)
       .await
       .map_err(UpdateUserError::other)?;
@@ -473,16 +504,18 @@ where
         is_administrator: bool,
         display_name: UserDisplayName,
         username: Option<Username>,
+        email: Option<EmailAddress>,
       }
 
       let row = sqlx::query_as::<_, Row>(
         r"
-      SELECT user_id, created_at, is_administrator, display_name, username
+      SELECT user_id, created_at, is_administrator, display_name, username, pgp_sym_decrypt(email, $2::TEXT)::EMAIL_ADDRESS AS email
       FROM users_current
       WHERE user_id = $1::USER_ID;
       ",
       )
       .bind(options.r#ref.id)
+      .bind(self.database_secret.as_str())
       .fetch_one(&mut tx)
       .await
       .map_err(UpdateUserError::other)?;
@@ -501,7 +534,7 @@ where
       is_administrator: row.is_administrator,
       created_at: row.created_at,
       username: row.username,
-      email_address: None,
+      email_address: row.email,
     };
 
     Ok(user)
@@ -526,6 +559,167 @@ where
       _ => panic!("AssertionError: Expected 0 or 1 rows to be affected"),
     }
   }
+
+  async fn get_user_totp(&self, user_ref: UserIdRef) -> Result<Option<UserTotp>, AnyError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct Row {
+      secret: Vec<u8>,
+      enabled_at: Instant,
+      last_used_step: Option<i64>,
+    }
+
+    let row: Option<Row> = sqlx::query_as::<_, Row>(
+      r"
+        SELECT pgp_sym_decrypt_bytea(secret, $1::TEXT) AS secret, enabled_at, last_used_step
+        FROM user_totp
+        WHERE user_id = $2::USER_ID;
+    ",
+    )
+    .bind(self.database_secret.as_str())
+    .bind(user_ref.id)
+    .fetch_optional(self.database.as_ref())
+    .await?;
+
+    let row = match row {
+      Some(row) => row,
+      None => return Ok(None),
+    };
+
+    #[derive(Debug, sqlx::FromRow)]
+    struct CodeRow {
+      code: PasswordHash,
+    }
+
+    let codes: Vec<CodeRow> = sqlx::query_as::<_, CodeRow>(
+      r"
+        SELECT code
+        FROM user_recovery_codes
+        WHERE user_id = $1::USER_ID;
+    ",
+    )
+    .bind(user_ref.id)
+    .fetch_all(self.database.as_ref())
+    .await?;
+
+    Ok(Some(UserTotp {
+      user: user_ref,
+      secret: TotpSecret(row.secret),
+      enabled_at: row.enabled_at,
+      last_used_step: row.last_used_step.map(u64::try_from).transpose()?,
+      recovery_codes: codes.into_iter().map(|row| row.code).collect(),
+    }))
+  }
+
+  async fn set_user_totp(&self, options: &SetUserTotpOptions) -> Result<UserTotp, AnyError> {
+    let now = self.clock.now();
+    let mut tx = self.database.as_ref().begin().await?;
+
+    sqlx::query(
+      r"
+        DELETE
+        FROM user_totp
+        WHERE user_id = $1::USER_ID;
+    ",
+    )
+    .bind(options.user.id)
+    .execute(&mut tx)
+    .await?;
+
+    let res = sqlx::query(
+      r"
+        INSERT
+        INTO user_totp(user_id, secret, enabled_at, last_used_step)
+        VALUES ($2::USER_ID, pgp_sym_encrypt_bytea($3::BYTEA, $1::TEXT), $4::INSTANT, NULL);
+    ",
+    )
+    .bind(self.database_secret.as_str())
+    .bind(options.user.id)
+    .bind(options.secret.as_slice())
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+    assert_eq!(res.rows_affected(), 1);
+
+    for code in options.recovery_codes.iter() {
+      let res = sqlx::query(
+        r"
+          INSERT
+          INTO user_recovery_codes(user_id, code)
+          VALUES ($1::USER_ID, $2::PASSWORD_HASH);
+      ",
+      )
+      .bind(options.user.id)
+      .bind(code)
+      .execute(&mut tx)
+      .await?;
+      assert_eq!(res.rows_affected(), 1);
+    }
+
+    tx.commit().await?;
+
+    Ok(UserTotp {
+      user: options.user,
+      secret: options.secret.clone(),
+      enabled_at: now,
+      last_used_step: None,
+      recovery_codes: options.recovery_codes.clone(),
+    })
+  }
+
+  async fn touch_user_totp_step(&self, options: &TouchUserTotpStepOptions) -> Result<bool, AnyError> {
+    let res = sqlx::query(
+      r"
+        UPDATE user_totp
+        SET last_used_step = $2::INT8
+        WHERE user_id = $1::USER_ID AND (last_used_step IS NULL OR last_used_step < $2::INT8);
+    ",
+    )
+    .bind(options.user.id)
+    .bind(i64::try_from(options.step)?)
+    .execute(self.database.as_ref())
+    .await?;
+
+    match res.rows_affected() {
+      0 => Ok(false),
+      1 => Ok(true),
+      _ => panic!("AssertionError: Expected 0 or 1 rows to be affected"),
+    }
+  }
+
+  async fn delete_user_recovery_code(&self, options: &DeleteUserRecoveryCodeOptions) -> Result<bool, AnyError> {
+    let res = sqlx::query(
+      r"
+        DELETE
+        FROM user_recovery_codes
+        WHERE user_id = $1::USER_ID AND code = $2::PASSWORD_HASH;
+    ",
+    )
+    .bind(options.user.id)
+    .bind(&options.code)
+    .execute(self.database.as_ref())
+    .await?;
+
+    match res.rows_affected() {
+      0 => Ok(false),
+      1 => Ok(true),
+      _ => panic!("AssertionError: Expected 0 or 1 rows to be affected"),
+    }
+  }
+
+  async fn delete_user_totp(&self, user_ref: UserIdRef) -> Result<(), AnyError> {
+    sqlx::query(
+      r"
+        DELETE
+        FROM user_totp
+        WHERE user_id = $1::USER_ID;
+    ",
+    )
+    .bind(user_ref.id)
+    .execute(self.database.as_ref())
+    .await?;
+
+    Ok(())
+  }
 }
 
 #[cfg(feature = "neon")]
@@ -2,10 +2,14 @@ use chrono::Duration;
 use etwin_core::api::ApiRef;
 use etwin_core::clock::{Clock, VirtualClock};
 use etwin_core::core::Instant;
+use etwin_core::mfa::{
+  DeleteUserRecoveryCodeOptions, SetUserTotpOptions, TotpSecret, TouchUserTotpStepOptions, UserTotp,
+};
+use etwin_core::password::PasswordHash;
 use etwin_core::user::{
-  CompleteSimpleUser, CreateUserOptions, GetUserOptions, GetUserResult, ShortUser, SimpleUser, UpdateUserError,
-  UpdateUserOptions, UpdateUserPatch, UserDisplayNameVersion, UserDisplayNameVersions, UserFields, UserIdRef, UserRef,
-  UserStore, USER_DISPLAY_NAME_LOCK_DURATION,
+  CompleteSimpleUser, CreateUserOptions, GetUserOptions, GetUserResult, ReplaceUserPasswordHashOptions, ShortUser,
+  SimpleUser, UpdateUserError, UpdateUserOptions, UpdateUserPatch, UserDisplayNameVersion, UserDisplayNameVersions,
+  UserEmailRef, UserFields, UserIdRef, UserRef, UserStore, USER_DISPLAY_NAME_LOCK_DURATION,
 };
 
 #[macro_export]
@@ -20,7 +24,10 @@ macro_rules! test_user_store {
     register_test!($(#[$meta])*, $api, test_update_display_after_unlock);
     register_test!($(#[$meta])*, $api, test_update_locked_display_name_after_update);
     register_test!($(#[$meta])*, $api, test_update_display_name_afte_multiple_unlocks);
+    register_test!($(#[$meta])*, $api, test_update_email_address);
     register_test!($(#[$meta])*, $api, test_hard_delete_user);
+    register_test!($(#[$meta])*, $api, test_user_totp);
+    register_test!($(#[$meta])*, $api, test_replace_user_password_hash);
   };
 }
 
@@ -218,6 +225,7 @@ where
         display_name: Some("Alicia".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -267,6 +275,7 @@ where
         display_name: Some("Alicia".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -285,6 +294,7 @@ where
         display_name: Some("Allison".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await;
@@ -326,6 +336,7 @@ where
         display_name: Some("Alicia".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -342,6 +353,7 @@ where
         display_name: Some("Allison".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -392,6 +404,7 @@ pub(crate) async fn test_update_locked_display_name_after_update<TyClock, TyUser
         display_name: Some("Alicia".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -408,6 +421,7 @@ pub(crate) async fn test_update_locked_display_name_after_update<TyClock, TyUser
         display_name: Some("Allison".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -426,6 +440,7 @@ pub(crate) async fn test_update_locked_display_name_after_update<TyClock, TyUser
         display_name: Some("Alexandra".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await;
@@ -468,6 +483,7 @@ pub(crate) async fn test_update_display_name_afte_multiple_unlocks<TyClock, TyUs
         display_name: Some("Alicia".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -484,6 +500,7 @@ pub(crate) async fn test_update_display_name_afte_multiple_unlocks<TyClock, TyUs
         display_name: Some("Allison".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -500,6 +517,7 @@ pub(crate) async fn test_update_display_name_afte_multiple_unlocks<TyClock, TyUs
         display_name: Some("Alexandra".parse().unwrap()),
         username: None,
         password: None,
+        email: None,
       },
     })
     .await
@@ -520,6 +538,92 @@ pub(crate) async fn test_update_display_name_afte_multiple_unlocks<TyClock, TyUs
   assert_eq!(actual, expected);
 }
 
+pub(crate) async fn test_update_email_address<TyClock, TyUserStore>(api: TestApi<TyClock, TyUserStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: Some("alice@example.com".parse().unwrap()),
+      password: None,
+    })
+    .await
+    .unwrap();
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+
+  let actual = api
+    .user_store
+    .update_user(&UpdateUserOptions {
+      r#ref: alice.id.into(),
+      actor: alice.id.into(),
+      patch: UpdateUserPatch {
+        display_name: None,
+        username: None,
+        password: None,
+        email: Some(Some("alicia@example.com".parse().unwrap())),
+      },
+    })
+    .await
+    .unwrap();
+  let expected = CompleteSimpleUser {
+    id: alice.id,
+    display_name: UserDisplayNameVersions {
+      current: UserDisplayNameVersion {
+        value: "Alice".parse().unwrap(),
+      },
+    },
+    is_administrator: true,
+    created_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 0),
+    username: Some("alice".parse().unwrap()),
+    email_address: Some("alicia@example.com".parse().unwrap()),
+  };
+  assert_eq!(actual, expected);
+
+  {
+    let actual = api
+      .user_store
+      .get_user(&GetUserOptions {
+        fields: UserFields::Short,
+        r#ref: UserRef::Email(UserEmailRef {
+          email: "alicia@example.com".parse().unwrap(),
+        }),
+        time: None,
+      })
+      .await
+      .unwrap();
+    let expected = Some(GetUserResult::Short(ShortUser {
+      id: alice.id,
+      display_name: UserDisplayNameVersions {
+        current: UserDisplayNameVersion {
+          value: "Alice".parse().unwrap(),
+        },
+      },
+    }));
+    assert_eq!(actual, expected);
+  }
+  {
+    let actual = api
+      .user_store
+      .get_user(&GetUserOptions {
+        fields: UserFields::Short,
+        r#ref: UserRef::Email(UserEmailRef {
+          email: "alice@example.com".parse().unwrap(),
+        }),
+        time: None,
+      })
+      .await
+      .unwrap();
+    let expected = None;
+    assert_eq!(actual, expected);
+  }
+}
+
 pub(crate) async fn test_hard_delete_user<TyClock, TyUserStore>(api: TestApi<TyClock, TyUserStore>)
 where
   TyClock: ApiRef<VirtualClock>,
@@ -556,3 +660,136 @@ where
   let expected = None;
   assert_eq!(actual, expected);
 }
+
+pub(crate) async fn test_user_totp<TyClock, TyUserStore>(api: TestApi<TyClock, TyUserStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: None,
+      password: None,
+    })
+    .await
+    .unwrap();
+
+  {
+    let actual = api.user_store.get_user_totp(alice.id.into()).await.unwrap();
+    assert_eq!(actual, None);
+  }
+
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+
+  api
+    .user_store
+    .set_user_totp(&SetUserTotpOptions {
+      user: alice.id.into(),
+      secret: TotpSecret(b"12345678901234567890".to_vec()),
+      recovery_codes: vec![PasswordHash(b"code-a".to_vec()), PasswordHash(b"code-b".to_vec())],
+    })
+    .await
+    .unwrap();
+
+  for (step, expected) in [(10, true), (10, false), (9, false), (11, true)] {
+    let actual = api
+      .user_store
+      .touch_user_totp_step(&TouchUserTotpStepOptions {
+        user: alice.id.into(),
+        step,
+      })
+      .await
+      .unwrap();
+    assert_eq!(actual, expected);
+  }
+
+  for expected in [true, false] {
+    let actual = api
+      .user_store
+      .delete_user_recovery_code(&DeleteUserRecoveryCodeOptions {
+        user: alice.id.into(),
+        code: PasswordHash(b"code-a".to_vec()),
+      })
+      .await
+      .unwrap();
+    assert_eq!(actual, expected);
+  }
+
+  {
+    let actual = api.user_store.get_user_totp(alice.id.into()).await.unwrap();
+    let expected = Some(UserTotp {
+      user: alice.id.into(),
+      secret: TotpSecret(b"12345678901234567890".to_vec()),
+      enabled_at: Instant::ymd_hms(2021, 1, 1, 0, 0, 1),
+      last_used_step: Some(11),
+      recovery_codes: vec![PasswordHash(b"code-b".to_vec())],
+    });
+    assert_eq!(actual, expected);
+  }
+
+  api.user_store.delete_user_totp(alice.id.into()).await.unwrap();
+
+  {
+    let actual = api.user_store.get_user_totp(alice.id.into()).await.unwrap();
+    assert_eq!(actual, None);
+  }
+}
+
+pub(crate) async fn test_replace_user_password_hash<TyClock, TyUserStore>(api: TestApi<TyClock, TyUserStore>)
+where
+  TyClock: ApiRef<VirtualClock>,
+  TyUserStore: UserStore,
+{
+  api.clock.as_ref().advance_to(Instant::ymd_hms(2021, 1, 1, 0, 0, 0));
+  let legacy = PasswordHash::from("legacy".as_bytes());
+  let upgraded = PasswordHash::from("upgraded".as_bytes());
+  let alice = api
+    .user_store
+    .create_user(&CreateUserOptions {
+      display_name: "Alice".parse().unwrap(),
+      username: Some("alice".parse().unwrap()),
+      email: None,
+      password: Some(legacy.clone()),
+    })
+    .await
+    .unwrap();
+  api.clock.as_ref().advance_by(Duration::seconds(1));
+
+  let actual = api
+    .user_store
+    .replace_user_password_hash(&ReplaceUserPasswordHashOptions {
+      r#ref: alice.id.into(),
+      old: upgraded.clone(),
+      new: legacy.clone(),
+    })
+    .await
+    .unwrap();
+  assert!(!actual);
+
+  let actual = api
+    .user_store
+    .replace_user_password_hash(&ReplaceUserPasswordHashOptions {
+      r#ref: alice.id.into(),
+      old: legacy.clone(),
+      new: upgraded.clone(),
+    })
+    .await
+    .unwrap();
+  assert!(actual);
+
+  let actual = api
+    .user_store
+    .get_user_with_password(&GetUserOptions {
+      r#ref: UserRef::Id(alice.id.into()),
+      fields: UserFields::Complete,
+      time: None,
+    })
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(actual.password, Some(upgraded));
+}
@@ -0,0 +1,2218 @@
+-- Squashed creation script for version 029, generated by `etwin db squash --to 29`.
+-- Do not edit: it must match the `create` and `upgrade` scripts it replaces.
+
+-- Migration step: empty -> 001
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+-- A user
+CREATE TABLE public.users (
+  -- User id
+  user_id UUID PRIMARY KEY NOT NULL,
+  -- User creation time
+  ctime TIMESTAMP(0),
+  -- Value to use when displaying the user's name. May be different from `username` or `email_address`.
+  display_name VARCHAR(64) NOT NULL,
+  -- Time of the last change to `display_name`
+  display_name_mtime TIMESTAMP(0) NOT NULL,
+  -- Encrypted email address (using pgp_sym_encrypt)
+  -- This may be `NULL` if the value was never set, or if the value was removed.
+  email_address BYTEA NULL,
+  -- Time of the last change to `email_address`
+  email_address_mtime TIMESTAMP(0) NOT NULL,
+  -- Unique username, mainly used for authentication
+  -- This may be `NULL` if the value was never set, or if the value was removed.
+  username VARCHAR(64) NULL,
+  -- Time of the last change to `username`
+  username_mtime TIMESTAMP(0) NOT NULL,
+  -- Encrypted password hash (hashed with `scrypt`, encrypted with `pgp_sym_encrypt_bytea`)
+  password BYTEA NULL,
+  -- Time of the last change to `password`
+  password_mtime TIMESTAMP(0) NOT NULL,
+  -- Flag indicating that this user is an administrator (has elevated permissions)
+  is_administrator BOOLEAN NOT NULL,
+  CHECK (display_name_mtime >= ctime),
+  CHECK (email_address_mtime >= ctime),
+  CHECK (username_mtime >= ctime),
+  CHECK (password_mtime >= ctime),
+  UNIQUE (email_address)
+);
+
+-- Table of email verifications: they may be validated or not
+CREATE TABLE public.email_verifications (
+  -- User id for this email
+  user_id UUID NOT NULL,
+  -- Encrypted email address (using pgp_sym_encrypt)
+  email_address BYTEA NOT NULL,
+  -- Date when the verification email was sent
+  ctime TIMESTAMP(0) NOT NULL,
+  -- Date when the email was validated. `null` if the email was not validated.
+  validation_time TIMESTAMP(0) NULL,
+  CHECK (validation_time >= ctime),
+  CONSTRAINT email_verification__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- All the user sessions (active or expired)
+CREATE TABLE public.sessions (
+  -- Session id
+  session_id UUID PRIMARY KEY NOT NULL,
+  -- Id of the user authenticated by this session
+  user_id UUID,
+  -- Session creation time
+  ctime TIMESTAMP(0) NOT NULL,
+  -- Session access time
+  atime TIMESTAMP(0) NOT NULL,
+  -- Free-form session data
+  data JSON NOT NULL,
+  CHECK (atime >= ctime),
+  CONSTRAINT session__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Known Hammerfest servers
+CREATE TABLE public.hammerfest_servers (
+  -- Domain name for the Hammerfest server
+  domain VARCHAR(64) PRIMARY KEY NOT NULL,
+  CHECK (domain IN ('hammerfest.fr', 'hfest.net', 'hammerfest.es'))
+);
+
+-- Known Hammerfest users
+CREATE TABLE public.hammerfest_users (
+  -- Hammerfest server
+  server VARCHAR(64) NOT NULL,
+  -- User ID on the Hammerfest server
+  user_id INT NOT NULL,
+  -- Hammerfest username
+  username VARCHAR(20) NOT NULL,
+  PRIMARY KEY (server, user_id),
+  CONSTRAINT hammerfest_user__hammerfest_server__fk FOREIGN KEY (server) REFERENCES hammerfest_servers(domain) ON DELETE RESTRICT ON UPDATE CASCADE,
+  UNIQUE (server, username)
+);
+
+-- Active links between Eternal-Twin and Hammerfest users
+CREATE TABLE public.hammerfest_user_links (
+  -- Eternal-Twin user id
+  user_id UUID NOT NULL,
+  -- Hammerfest server
+  hammerfest_server VARCHAR(64) NOT NULL,
+  -- User ID on the Hammerfest server
+  hammerfest_user_id INT NOT NULL,
+  -- Link creation time
+  ctime TIMESTAMP(0) NOT NULL,
+  PRIMARY KEY (user_id, hammerfest_server, hammerfest_user_id),
+  CONSTRAINT hammerfest_user_link__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_user_link__hammerfest_user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(server, user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+INSERT INTO hammerfest_servers("domain")
+VALUES
+  ('hammerfest.fr'),
+  ('hfest.net'),
+  ('hammerfest.es');
+
+-- Migration step: 001 -> 002
+ALTER TABLE users
+  ADD CONSTRAINT username__uniq UNIQUE (username);
+
+-- OAuth clients
+CREATE TABLE public.oauth_clients (
+  -- OAuth client id
+  oauth_client_id UUID PRIMARY KEY NOT NULL,
+  -- The key is a small string serving as a secondary identifier for system clients
+  -- Its goal is to provide an easier way to identify the app: while the `oauth_client_id` is generated by the server,
+  -- the `key` is defined by the client (and can be known before the registration of the client).
+  key VARCHAR(32) NULL,
+  -- OAuth client creation time
+  ctime TIMESTAMP(0),
+  -- Display name for the OAuth client
+  display_name VARCHAR(64) NOT NULL,
+  -- Time of the last change to `display_name`
+  display_name_mtime TIMESTAMP(0) NOT NULL,
+  -- URI to the homepage of the app
+  app_uri VARCHAR(512) NOT NULL,
+  -- Time of the last change to `app_uri`
+  app_uri_mtime TIMESTAMP(0) NOT NULL,
+  -- Redirection URI (matched exactly against `redirect_uri` during the OAuth flow)
+  callback_uri VARCHAR(512) NOT NULL,
+  -- Time of the last change to `callback_uri_mtime`
+  callback_uri_mtime TIMESTAMP(0) NOT NULL,
+  -- Encrypted password hash (hashed with `scrypt`, encrypted with `pgp_sym_encrypt_bytea`)
+  secret BYTEA NOT NULL,
+  -- Time of the last change to `password`
+  secret_mtime TIMESTAMP(0) NOT NULL,
+  -- ID of the user owning this client. `null` indicates that it is a pre-defined client owned by the system.
+  owner_id UUID NULL,
+  CHECK (display_name_mtime >= ctime),
+  CHECK (app_uri_mtime >= ctime),
+  CHECK (callback_uri_mtime >= ctime),
+  CHECK (secret_mtime >= ctime),
+  -- System apps have a key and no owner, third-party apps have an owner but no key
+  CHECK ((key IS NULL) <> (owner_id IS NULL)),
+  UNIQUE (key)
+);
+
+CREATE TABLE public.old_oauth_client_display_names (
+  -- Oauth client ID
+  oauth_client_id UUID NOT NULL,
+  -- Time when this was value was initially set.
+  start_time TIMESTAMP(0),
+  display_name VARCHAR(64) NOT NULL,
+  PRIMARY KEY (oauth_client_id, start_time),
+  CONSTRAINT old_oauth_client_display_name__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE public.old_oauth_client_app_uris (
+  -- Oauth client ID
+  oauth_client_id UUID NOT NULL,
+  start_time TIMESTAMP(0),
+  app_uri VARCHAR(512) NOT NULL,
+  PRIMARY KEY (oauth_client_id, start_time),
+  CONSTRAINT old_oauth_client_app_uri__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE public.old_oauth_client_callback_uris (
+  oauth_client_id UUID NOT NULL,
+  start_time TIMESTAMP(0),
+  callback_uri VARCHAR(512) NOT NULL,
+  PRIMARY KEY (oauth_client_id, start_time),
+  CONSTRAINT old_oauth_client_callback_uri__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE public.old_oauth_client_secrets (
+  oauth_client_id UUID NOT NULL,
+  start_time TIMESTAMP(0),
+  secret BYTEA NOT NULL,
+  PRIMARY KEY (oauth_client_id, start_time),
+  CONSTRAINT old_oauth_client_secret__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Oauth Access token grant access to one user's data for one client app.
+CREATE TABLE public.oauth_access_tokens (
+  oauth_access_token_id UUID PRIMARY KEY NOT NULL,
+  -- OAuth client app id
+  oauth_client_id UUID NOT NULL,
+  -- Id for the corresponding user
+  user_id UUID NOT NULL,
+  -- Token creation time
+  ctime TIMESTAMP(0) NOT NULL,
+  -- Token use time
+  atime TIMESTAMP(0) NOT NULL,
+  -- TODO: Add encrypted part so DB dumps don't provide full access to tokens
+  -- Encrypted password hash (hashed with `scrypt`, encrypted with `pgp_sym_encrypt_bytea`)
+  -- secret BYTEA NOT NULL,
+  CHECK (atime >= ctime),
+  CONSTRAINT oauth_access_token__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT oauth_access_token__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Migration step: 002 -> 003
+CREATE TABLE public.forum_sections (
+  forum_section_id UUID PRIMARY KEY NOT NULL,
+  key VARCHAR(32) NULL,
+  ctime TIMESTAMP(3),
+  display_name VARCHAR(64) NOT NULL,
+  display_name_mtime TIMESTAMP(3) NOT NULL,
+  locale VARCHAR(10) NULL,
+  locale_mtime TIMESTAMP(3) NOT NULL,
+  CHECK (display_name_mtime >= ctime),
+  UNIQUE (key)
+);
+
+CREATE TABLE public.forum_threads (
+  forum_thread_id UUID PRIMARY KEY NOT NULL,
+  key VARCHAR(32) NULL,
+  ctime TIMESTAMP(3),
+  title VARCHAR(64) NOT NULL,
+  title_mtime TIMESTAMP(3) NOT NULL,
+  forum_section_id UUID NOT NULL,
+  is_pinned BOOLEAN NOT NULL,
+  is_pinned_mtime TIMESTAMP(3) NOT NULL,
+  is_locked BOOLEAN NOT NULL,
+  is_locked_mtime TIMESTAMP(3) NOT NULL,
+  CONSTRAINT forum_thread__forum_section__fk FOREIGN KEY (forum_section_id) REFERENCES forum_sections(forum_section_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CHECK (title_mtime >= ctime),
+  CHECK (is_pinned_mtime >= ctime),
+  CHECK (is_locked_mtime >= ctime),
+  UNIQUE (key)
+);
+
+CREATE TABLE public.forum_posts (
+  forum_post_id UUID PRIMARY KEY NOT NULL,
+  ctime TIMESTAMP(3),
+  forum_thread_id UUID NOT NULL,
+  CONSTRAINT forum_post__forum_thread__fk FOREIGN KEY (forum_thread_id) REFERENCES forum_threads(forum_thread_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE public.forum_post_revisions (
+  forum_post_revision_id UUID PRIMARY KEY NOT NULL,
+  time TIMESTAMP(3),
+  -- Post body in Marktwin format. `null` indicates that the post was deleted/hidden.
+  body TEXT NULL,
+  _html_body TEXT NULL,
+  mod_body TEXT NULL,
+  _html_mod_body TEXT NULL,
+  forum_post_id UUID NOT NULL,
+  author_id UUID NOT NULL,
+  -- -- Optional comment describing the changes in this revision
+  comment VARCHAR(200) NULL,
+  CHECK ((body IS NULL) = (_html_body IS NULL)),
+  CHECK ((mod_body IS NULL) = (_html_mod_body IS NULL)),
+  CONSTRAINT forum_post_revision__forum_revision__fk FOREIGN KEY (forum_post_id) REFERENCES forum_posts(forum_post_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT forum_post_revision__user__fk FOREIGN KEY (author_id) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE public._post_formatting_costs (
+  forum_post_revision_id UUID NOT NULL,
+  formatting VARCHAR(20) NOT NULL,
+  cost INTEGER,
+  PRIMARY KEY (forum_post_revision_id, formatting),
+  CONSTRAINT forum_post_revision__forum_revision__fk FOREIGN KEY (forum_post_revision_id) REFERENCES forum_post_revisions(forum_post_revision_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CHECK (cost > 0)
+);
+
+CREATE TABLE public.forum_role_grants (
+  forum_section_id UUID NOT NULL,
+  user_id UUID NOT NULL,
+  start_time TIMESTAMP(3),
+  -- User who granted the moderator permissions
+  granted_by UUID NOT NULL,
+  CONSTRAINT forum_moderator__forum_section__fk FOREIGN KEY (forum_section_id) REFERENCES forum_sections(forum_section_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT forum_moderator__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT forum_moderator_granter__user__fk FOREIGN KEY (granted_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  PRIMARY KEY (forum_section_id, user_id)
+);
+
+CREATE TABLE public.forum_role_revocations (
+  forum_section_id UUID NOT NULL,
+  user_id UUID NOT NULL,
+  start_time TIMESTAMP(3),
+  end_time TIMESTAMP(3),
+  -- User who granted the moderator permissions
+  granted_by UUID NOT NULL,
+  -- User who revoked the moderator permissions
+  revoked_by UUID NOT NULL,
+  CONSTRAINT forum_role_revocation__forum_section__fk FOREIGN KEY (forum_section_id) REFERENCES forum_sections(forum_section_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT forum_role_revocation_user__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT forum_moderator_granter__user__fk FOREIGN KEY (granted_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT forum_moderator_revoker__user__fk FOREIGN KEY (revoked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  PRIMARY KEY (forum_section_id, user_id, start_time),
+  CHECK (start_time < end_time)
+);
+
+-- Migration step: 003 -> 004
+CREATE DOMAIN hammerfest_server AS VARCHAR(13) CHECK (value IN ('hammerfest.es', 'hammerfest.fr', 'hfest.net'));
+CREATE DOMAIN hammerfest_session_key AS VARCHAR(26) CHECK (value ~ '^[0-9a-z]{26}$');
+CREATE DOMAIN hammerfest_user_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN twinoid_user_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+-- Represents a point in time, with millisecond precision
+CREATE DOMAIN instant AS TIMESTAMP(3) WITH TIME ZONE;
+
+CREATE TABLE public.twinoid_users (
+  -- User ID on the Twinoid server
+  twinoid_user_id TWINOID_USER_ID PRIMARY KEY NOT NULL,
+  -- Twinoid name
+  name VARCHAR(50) NOT NULL
+);
+
+-- Active links between Eternal-Twin and Twinoid users
+CREATE TABLE public.twinoid_user_links (
+  -- Eternal-Twin user id
+  user_id UUID NOT NULL,
+  -- User ID on the Twinoid server
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  -- Link creation time
+  ctime INSTANT NOT NULL,
+  PRIMARY KEY (user_id, twinoid_user_id),
+  CONSTRAINT twinoid_user_link__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT twinoid_user_link__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Cancelled links between Eternal-Twin and Twinoid users
+CREATE TABLE public.old_twinoid_user_links (
+  -- Eternal-Twin user id
+  user_id UUID NOT NULL,
+  -- Twinoid user id
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  start_time INSTANT,
+  end_time INSTANT,
+  PRIMARY KEY (user_id, twinoid_user_id),
+  CONSTRAINT twinoid_user_link__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT twinoid_user_link__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+ALTER TABLE hammerfest_user_links
+  RENAME hammerfest_user_id TO old_hammerfest_user_id;
+ALTER TABLE hammerfest_user_links
+  ADD COLUMN hammerfest_user_id HAMMERFEST_USER_ID;
+ALTER TABLE hammerfest_user_links
+  ALTER COLUMN hammerfest_server TYPE HAMMERFEST_SERVER;
+-- noinspection SqlWithoutWhere
+UPDATE hammerfest_user_links
+SET hammerfest_user_id = old_hammerfest_user_id::VARCHAR;
+
+ALTER TABLE hammerfest_users
+  ADD COLUMN hammerfest_user_id HAMMERFEST_USER_ID;
+ALTER TABLE hammerfest_users
+  RENAME "server" TO hammerfest_server;
+ALTER TABLE hammerfest_users
+  ALTER COLUMN hammerfest_server TYPE HAMMERFEST_SERVER;
+-- noinspection SqlWithoutWhere
+UPDATE hammerfest_users
+SET hammerfest_user_id = user_id::VARCHAR;
+
+ALTER TABLE hammerfest_user_links
+  DROP CONSTRAINT hammerfest_user_link__hammerfest_user__fk;
+ALTER TABLE hammerfest_users
+  DROP CONSTRAINT hammerfest_users_pkey;
+ALTER TABLE hammerfest_users
+  ADD PRIMARY KEY (hammerfest_server, hammerfest_user_id);
+ALTER TABLE hammerfest_user_links
+  ADD CONSTRAINT hammerfest_user_link__hammerfest_user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE hammerfest_servers
+  RENAME domain TO hammerfest_server;
+ALTER TABLE hammerfest_servers
+  ALTER COLUMN hammerfest_server TYPE HAMMERFEST_SERVER;
+
+ALTER TABLE hammerfest_users
+  DROP COLUMN user_id;
+ALTER TABLE hammerfest_user_links
+  DROP COLUMN old_hammerfest_user_id;
+
+-- Active Hammerfest sessions
+CREATE TABLE public.hammerfest_sessions (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_session_key BYTEA NOT NULL,
+  _hammerfest_session_key_hash BYTEA NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  -- Session creation time
+  ctime INSTANT NOT NULL,
+  -- Session access time
+  atime INSTANT NOT NULL,
+  CHECK (atime >= ctime),
+  PRIMARY KEY (hammerfest_server, _hammerfest_session_key_hash),
+  UNIQUE (hammerfest_server, hammerfest_user_id),
+  CONSTRAINT hammerfest_session__hammerfest_user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Revoked Hammerfest sessions
+CREATE TABLE public.old_hammerfest_sessions (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_session_key BYTEA NOT NULL,
+  _hammerfest_session_key_hash BYTEA NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  -- Session creation time
+  ctime INSTANT NOT NULL,
+  -- Session access time
+  atime INSTANT NOT NULL,
+  -- Session deletion time
+  dtime INSTANT NOT NULL,
+  CHECK (atime >= ctime),
+  CHECK (dtime >= atime),
+  CHECK (dtime > ctime),
+  PRIMARY KEY (hammerfest_server, _hammerfest_session_key_hash, ctime),
+  CONSTRAINT old_hammerfest_session__hammerfest_user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Active Twinoid access tokens
+CREATE TABLE public.twinoid_access_tokens (
+  twinoid_access_token BYTEA NOT NULL,
+  _twinoid_access_token_hash BYTEA NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  -- Access token creation time
+  ctime INSTANT NOT NULL,
+  -- Access token access time
+  atime INSTANT NOT NULL,
+  -- Access token expiration time
+  expiration_time INSTANT NOT NULL,
+  CHECK (ctime <= atime),
+  CHECK (atime <= expiration_time),
+  CHECK (ctime < expiration_time),
+  PRIMARY KEY (_twinoid_access_token_hash),
+  UNIQUE (twinoid_user_id),
+  CONSTRAINT twinoid_access_token__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Revoked or expired Twinoid access tokens
+CREATE TABLE public.old_twinoid_access_tokens (
+  twinoid_access_token BYTEA NOT NULL,
+  _twinoid_access_token_hash BYTEA NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  -- Access token creation time
+  ctime INSTANT NOT NULL,
+  -- Access token access time
+  atime INSTANT NOT NULL,
+  -- Refresh token deletion time
+  dtime INSTANT NOT NULL,
+  -- Access token expiration time
+  expiration_time INSTANT NOT NULL,
+  CHECK (ctime <= atime),
+  CHECK (atime <= expiration_time),
+  CHECK (ctime < expiration_time),
+  CHECK (atime <= dtime),
+  CHECK (ctime < dtime),
+  PRIMARY KEY (_twinoid_access_token_hash, ctime),
+  CONSTRAINT twinoid_access_token__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Active Twinoid refresh tokens
+CREATE TABLE public.twinoid_refresh_tokens (
+  twinoid_refresh_token BYTEA NOT NULL,
+  _twinoid_refresh_token_hash BYTEA NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  -- Refresh token creation time
+  ctime INSTANT NOT NULL,
+  -- Refresh token access time
+  atime INSTANT NOT NULL,
+  CHECK (ctime <= atime),
+  PRIMARY KEY (_twinoid_refresh_token_hash),
+  UNIQUE (twinoid_user_id),
+  CONSTRAINT twinoid_refresh_token__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Revoked Twinoid refresh tokens
+CREATE TABLE public.old_twinoid_refresh_tokens (
+  twinoid_refresh_token BYTEA NOT NULL,
+  _twinoid_refresh_token_hash BYTEA NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  -- Refresh token creation time
+  ctime INSTANT NOT NULL,
+  -- Refresh token access time
+  atime INSTANT NOT NULL,
+  -- Refresh token deletion time
+  dtime INSTANT NOT NULL,
+  CHECK (ctime <= atime),
+  CHECK (atime <= dtime),
+  CHECK (ctime < dtime),
+  PRIMARY KEY (_twinoid_refresh_token_hash, ctime),
+  CONSTRAINT old_twinoid_refresh_token__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+ALTER TABLE users
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN display_name_mtime TYPE INSTANT,
+  ALTER COLUMN email_address_mtime TYPE INSTANT,
+  ALTER COLUMN username_mtime TYPE INSTANT,
+  ALTER COLUMN password_mtime TYPE INSTANT;
+
+ALTER TABLE email_verifications
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN validation_time TYPE INSTANT;
+
+ALTER TABLE sessions
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN atime TYPE INSTANT;
+
+ALTER TABLE oauth_clients
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN display_name_mtime TYPE INSTANT,
+  ALTER COLUMN app_uri_mtime TYPE INSTANT,
+  ALTER COLUMN callback_uri_mtime TYPE INSTANT,
+  ALTER COLUMN secret_mtime TYPE INSTANT;
+
+ALTER TABLE old_oauth_client_display_names
+  ALTER COLUMN start_time TYPE INSTANT;
+
+ALTER TABLE old_oauth_client_app_uris
+  ALTER COLUMN start_time TYPE INSTANT;
+
+ALTER TABLE old_oauth_client_callback_uris
+  ALTER COLUMN start_time TYPE INSTANT;
+
+ALTER TABLE old_oauth_client_secrets
+  ALTER COLUMN start_time TYPE INSTANT;
+
+ALTER TABLE oauth_access_tokens
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN atime TYPE INSTANT;
+
+ALTER TABLE hammerfest_user_links
+  ALTER COLUMN ctime TYPE INSTANT;
+
+ALTER TABLE forum_sections
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN display_name_mtime TYPE INSTANT,
+  ALTER COLUMN locale_mtime TYPE INSTANT;
+
+ALTER TABLE forum_threads
+  ALTER COLUMN ctime TYPE INSTANT,
+  ALTER COLUMN title_mtime TYPE INSTANT,
+  ALTER COLUMN is_pinned_mtime TYPE INSTANT,
+  ALTER COLUMN is_locked_mtime TYPE INSTANT;
+
+ALTER TABLE forum_posts
+  ALTER COLUMN ctime TYPE INSTANT;
+
+ALTER TABLE forum_post_revisions
+  ALTER COLUMN time TYPE INSTANT;
+
+ALTER TABLE forum_role_grants
+  ALTER COLUMN start_time TYPE INSTANT;
+
+ALTER TABLE forum_role_revocations
+  ALTER COLUMN start_time TYPE INSTANT,
+  ALTER COLUMN end_time TYPE INSTANT;
+
+-- Migration step: 004 -> 005
+CREATE DOMAIN dinoparc_server AS VARCHAR(15) CHECK (value IN ('dinoparc.com', 'en.dinoparc.com', 'sp.dinoparc.com'));
+CREATE DOMAIN dinoparc_session_key AS VARCHAR(32) CHECK (value ~ '^[0-9a-zA-Z]{32}$');
+CREATE DOMAIN dinoparc_user_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN dinoparc_username AS VARCHAR(20);
+CREATE DOMAIN user_id AS UUID;
+
+-- Known Dinoparc servers
+CREATE TABLE public.dinoparc_servers (
+  -- Domain name for the Dinoparc server
+  dinoparc_server DINOPARC_SERVER PRIMARY KEY NOT NULL
+);
+
+-- Known Dinoparc users
+CREATE TABLE public.dinoparc_users (
+  -- Dinoparc server
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  -- User ID on the Dinoparc server
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  -- Dinoparc username
+  username DINOPARC_USERNAME NOT NULL,
+  PRIMARY KEY (dinoparc_server, dinoparc_user_id),
+  CONSTRAINT dinoparc_user__dinoparc_server__fk FOREIGN KEY (dinoparc_server) REFERENCES dinoparc_servers(dinoparc_server) ON DELETE RESTRICT ON UPDATE CASCADE,
+  UNIQUE (dinoparc_server, username)
+);
+
+-- Active links between Eternal-Twin and Dinoparc users
+CREATE TABLE public.dinoparc_user_links (
+  -- Eternal-Twin user id
+  user_id USER_ID NOT NULL,
+  -- Dinoparc server
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  -- User ID on the Dinoparc server
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  -- Link creation time
+  linked_at INSTANT NOT NULL,
+  -- Link creation author
+  linked_by USER_ID NOT NULL,
+  PRIMARY KEY (user_id, dinoparc_server, dinoparc_user_id),
+  -- An Eternal-Twin user can only be linked to one dinoparc user per server
+  UNIQUE (user_id, dinoparc_server),
+  -- A Dinoparc user can only be linked to one Eternal-Twin user
+  UNIQUE (dinoparc_server, dinoparc_user_id),
+  CONSTRAINT dinoparc_user_link__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_user_link_linked_by__user__fk FOREIGN KEY (linked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_user_link__dinoparc_user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Active Dinoparc sessions
+CREATE TABLE public.dinoparc_sessions (
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_session_key BYTEA NOT NULL,
+  _dinoparc_session_key_hash BYTEA NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  -- Session creation time
+  ctime INSTANT NOT NULL,
+  -- Session access time
+  atime INSTANT NOT NULL,
+  CHECK (atime >= ctime),
+  PRIMARY KEY (dinoparc_server, _dinoparc_session_key_hash),
+  UNIQUE (dinoparc_server, dinoparc_user_id),
+  CONSTRAINT dinoparc_session__dinoparc_user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Revoked Dinoparc sessions
+CREATE TABLE public.old_dinoparc_sessions (
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_session_key BYTEA NOT NULL,
+  _dinoparc_session_key_hash BYTEA NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  -- Session creation time
+  ctime INSTANT NOT NULL,
+  -- Session access time
+  atime INSTANT NOT NULL,
+  -- Session deletion time
+  dtime INSTANT NOT NULL,
+  CHECK (atime >= ctime),
+  CHECK (dtime >= atime),
+  CHECK (dtime > ctime),
+  PRIMARY KEY (dinoparc_server, _dinoparc_session_key_hash, ctime),
+  CONSTRAINT old_dinoparc_session__dinoparc_user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+INSERT INTO dinoparc_servers(dinoparc_server)
+VALUES
+  ('dinoparc.com'),
+  ('en.dinoparc.com'),
+  ('sp.dinoparc.com');
+
+-- Migration step: 005 -> 006
+ALTER TABLE hammerfest_user_links
+  ADD PRIMARY KEY (user_id, hammerfest_server, hammerfest_user_id);
+ALTER TABLE hammerfest_user_links
+  ADD UNIQUE (user_id, hammerfest_server);
+ALTER TABLE hammerfest_user_links
+  ADD UNIQUE (hammerfest_server, hammerfest_user_id);
+
+ALTER TABLE twinoid_user_links
+  ADD UNIQUE (user_id);
+ALTER TABLE twinoid_user_links
+  ADD UNIQUE (twinoid_user_id);
+
+-- Migration step: 006 -> 007
+ALTER TABLE hammerfest_user_links
+  ALTER COLUMN user_id TYPE USER_ID;
+ALTER TABLE hammerfest_user_links
+  RENAME ctime TO linked_at;
+ALTER TABLE hammerfest_user_links
+  ADD COLUMN linked_by USER_ID NULL;
+
+UPDATE hammerfest_user_links
+SET linked_by = user_id;
+
+ALTER TABLE hammerfest_user_links
+  ALTER COLUMN linked_by SET NOT NULL;
+
+ALTER TABLE hammerfest_user_links
+  ADD CONSTRAINT hammerfest_user_link_linked_by__user__fk FOREIGN KEY (linked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE twinoid_user_links
+  ALTER COLUMN user_id TYPE USER_ID;
+ALTER TABLE twinoid_user_links
+  RENAME ctime TO linked_at;
+ALTER TABLE twinoid_user_links
+  ADD COLUMN linked_by USER_ID NULL;
+
+UPDATE twinoid_user_links
+SET linked_by = user_id;
+
+ALTER TABLE twinoid_user_links
+  ALTER COLUMN linked_by SET NOT NULL;
+
+ALTER TABLE twinoid_user_links
+  ADD CONSTRAINT twinoid_user_link_linked_by__user__fk FOREIGN KEY (linked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+-- Migration step: 007 -> 008
+CREATE DOMAIN announcement_id AS UUID;
+CREATE DOMAIN forum_thread_id AS UUID;
+CREATE DOMAIN locale_id AS VARCHAR(10);
+
+CREATE TABLE public.announcements (
+  announcement_id ANNOUNCEMENT_ID PRIMARY KEY NOT NULL,
+  forum_thread_id FORUM_THREAD_ID NOT NULL,
+  locale LOCALE_ID NULL,
+  created_at INSTANT NOT NULL,
+  created_by USER_ID NOT NULL,
+  -- TODO: game id
+  CONSTRAINT announcement__forum_thread__fk FOREIGN KEY (forum_thread_id) REFERENCES forum_threads(forum_thread_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT announcement__user__fk FOREIGN KEY (created_by) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Migration step: 008 -> 009
+-- Hammerfest
+
+CREATE DOMAIN hammerfest_username AS VARCHAR(20) CHECK (value ~ '^[0-9A-Za-z]{1,12}$');
+
+ALTER TABLE hammerfest_users
+  ADD COLUMN archived_at INSTANT NULL;
+
+UPDATE hammerfest_users
+SET archived_at = NOW();
+
+ALTER TABLE hammerfest_users
+  ALTER COLUMN archived_at SET NOT NULL;
+
+UPDATE hammerfest_users
+SET archived_at = linked_at
+FROM hammerfest_user_links
+WHERE hammerfest_user_links.hammerfest_server = hammerfest_users.hammerfest_server AND
+  hammerfest_user_links.hammerfest_user_id = hammerfest_users.hammerfest_user_id;
+
+-- Dinoparc
+
+ALTER TABLE dinoparc_users
+  ADD COLUMN archived_at INSTANT NULL;
+
+UPDATE dinoparc_users
+SET archived_at = NOW();
+
+ALTER TABLE dinoparc_users
+  ALTER COLUMN archived_at SET NOT NULL;
+
+UPDATE dinoparc_users
+SET archived_at = linked_at
+FROM dinoparc_user_links
+WHERE dinoparc_user_links.dinoparc_server = dinoparc_users.dinoparc_server AND
+  dinoparc_user_links.dinoparc_user_id = dinoparc_users.dinoparc_user_id;
+
+-- Twinoid
+
+ALTER TABLE twinoid_users
+  ADD COLUMN archived_at INSTANT NULL;
+
+UPDATE twinoid_users
+SET archived_at = NOW();
+
+ALTER TABLE twinoid_users
+  ALTER COLUMN archived_at SET NOT NULL;
+
+UPDATE twinoid_users
+SET archived_at = linked_at
+FROM twinoid_user_links
+WHERE twinoid_user_links.twinoid_user_id = twinoid_users.twinoid_user_id;
+
+-- Migration step: 009 -> 010
+CREATE DOMAIN user_display_name AS VARCHAR(64);
+CREATE DOMAIN username AS VARCHAR(64);
+
+-- Migration step: 010 -> 011
+CREATE DOMAIN twinoid_user_display_name AS VARCHAR(50);
+
+-- Migration step: 011 -> 012
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+
+CREATE DOMAIN password_hash AS bytea;
+CREATE DOMAIN email_address_enc AS bytea;
+CREATE DOMAIN email_address_hash AS bytea;
+CREATE DOMAIN email_address AS text;
+
+CREATE TYPE PERIOD AS RANGE (
+  subtype = INSTANT
+);
+
+CREATE DOMAIN PERIOD_FROM AS PERIOD CHECK (NOT lower_inf(VALUE) AND lower_inc(VALUE) AND NOT upper_inc(VALUE));
+
+CREATE TABLE email_addresses(
+  email_address EMAIL_ADDRESS_ENC NOT NULL,
+  _hash EMAIL_ADDRESS_HASH NOT NULL,
+  created_at INSTANT NOT NULL,
+  PRIMARY KEY (_hash)
+);
+
+-- Transaction-time state table for time-varying `users` fields.
+CREATE TABLE users_history(
+  user_id USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  -- `NULL`: not current, `TRUE`: current
+  _is_current BOOLEAN NULL,
+  updated_by USER_ID NOT NULL,
+  display_name USER_DISPLAY_NAME NOT NULL,
+  username USERNAME NULL,
+  email EMAIL_ADDRESS_HASH NULL,
+  password PASSWORD_HASH NULL,
+  PRIMARY KEY (user_id, period),
+  CHECK ((NOT upper_inf(period) AND _is_current IS NULL) OR (upper_inf(period) AND _is_current IS NOT NULL AND _is_current)),
+  UNIQUE (user_id, _is_current),
+  -- No overlapping rows for a given user
+  EXCLUDE USING gist (user_id WITH =, period WITH &&),
+  -- Sequential username uniqueness
+  EXCLUDE USING gist (username WITH =, period WITH &&),
+  EXCLUDE USING gist (email WITH =, period WITH &&),
+  CONSTRAINT user_history_user_id__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT user_history_updated_by__fk FOREIGN KEY (updated_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT user_history_email__fk FOREIGN KEY (email) REFERENCES email_addresses(_hash) ON DELETE SET NULL ON UPDATE CASCADE
+);
+
+INSERT INTO users_history(user_id, period, _is_current, updated_by, display_name, username, email, password)
+SELECT user_id, PERIOD(ctime, NULL), TRUE, user_id, display_name, username, NULL, password
+FROM users;
+
+ALTER TABLE users
+  DROP COLUMN display_name,
+  DROP COLUMN display_name_mtime,
+  DROP COLUMN email_address,
+  DROP COLUMN email_address_mtime,
+  DROP COLUMN username,
+  DROP COLUMN username_mtime,
+  DROP COLUMN password,
+  DROP COLUMN password_mtime;
+
+ALTER TABLE users RENAME COLUMN ctime TO created_at;
+
+ALTER TABLE users ALTER COLUMN user_id TYPE USER_ID;
+
+ALTER TABLE users ADD COLUMN _is_current BOOLEAN NOT NULL DEFAULT TRUE;
+
+ALTER TABLE users
+  ADD CHECK (_is_current),
+  ADD CONSTRAINT users_history__fk FOREIGN KEY (user_id, _is_current) REFERENCES users_history(user_id, _is_current) ON DELETE RESTRICT ON UPDATE NO ACTION DEFERRABLE INITIALLY DEFERRED;
+
+CREATE VIEW users_current AS
+  SELECT user_id, users.created_at, lower(period) AS updated_at, updated_by, is_administrator, display_name, username, email_addresses.email_address AS email, users_history.email AS _email_hash, password
+  FROM users INNER JOIN users_history USING (user_id, _is_current) LEFT OUTER JOIN email_addresses ON users_history.email = email_addresses._hash;
+
+-- Migration step: 012 -> 013
+ALTER TABLE hammerfest_user_links
+  ADD COLUMN period PERIOD_FROM NULL,
+  ADD COLUMN unlinked_by USER_ID NULL;
+
+UPDATE hammerfest_user_links SET period = PERIOD(linked_at, NULL);
+
+ALTER TABLE hammerfest_user_links
+  ALTER COLUMN period SET NOT NULL,
+  DROP COLUMN linked_at;
+
+ALTER TABLE hammerfest_user_links
+  ADD CHECK ((upper_inf(period) AND unlinked_by IS NULL) OR (NOT upper_inf(period) AND unlinked_by IS NOT NULL)),
+  ADD CONSTRAINT hammerfest_user_link_unlinked_by__user__fk FOREIGN KEY (unlinked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE hammerfest_user_links
+  DROP CONSTRAINT hammerfest_user_links_pkey;
+ALTER TABLE hammerfest_user_links
+  ADD PRIMARY KEY (user_id, hammerfest_server, hammerfest_user_id, period);
+
+ALTER TABLE hammerfest_user_links
+  DROP CONSTRAINT hammerfest_user_links_user_id_hammerfest_server_key;
+ALTER TABLE hammerfest_user_links
+  ADD EXCLUDE USING gist (user_id WITH =, hammerfest_server WITH =, period WITH &&);
+
+ALTER TABLE hammerfest_user_links
+  DROP CONSTRAINT hammerfest_user_links_hammerfest_server_hammerfest_user_id_key;
+ALTER TABLE hammerfest_user_links
+  ADD EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&);
+
+
+ALTER TABLE dinoparc_user_links
+  ADD COLUMN period PERIOD_FROM NULL,
+  ADD COLUMN unlinked_by USER_ID NULL;
+
+UPDATE dinoparc_user_links SET period = PERIOD(linked_at, NULL);
+
+ALTER TABLE dinoparc_user_links
+  ALTER COLUMN period SET NOT NULL,
+  DROP COLUMN linked_at;
+
+ALTER TABLE dinoparc_user_links
+  ADD CHECK ((upper_inf(period) AND unlinked_by IS NULL) OR (NOT upper_inf(period) AND unlinked_by IS NOT NULL)),
+  ADD CONSTRAINT dinoparc_user_link_unlinked_by__user__fk FOREIGN KEY (unlinked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE dinoparc_user_links
+  DROP CONSTRAINT dinoparc_user_links_pkey;
+ALTER TABLE dinoparc_user_links
+  ADD PRIMARY KEY (user_id, dinoparc_server, dinoparc_user_id, period);
+
+ALTER TABLE dinoparc_user_links
+  DROP CONSTRAINT dinoparc_user_links_user_id_dinoparc_server_key;
+ALTER TABLE dinoparc_user_links
+  ADD EXCLUDE USING gist (user_id WITH =, dinoparc_server WITH =, period WITH &&);
+
+ALTER TABLE dinoparc_user_links
+  DROP CONSTRAINT dinoparc_user_links_dinoparc_server_dinoparc_user_id_key;
+ALTER TABLE dinoparc_user_links
+  ADD EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&);
+
+
+ALTER TABLE twinoid_user_links
+  ADD COLUMN period PERIOD_FROM NULL,
+  ADD COLUMN unlinked_by USER_ID NULL;
+
+UPDATE twinoid_user_links SET period = PERIOD(linked_at, NULL);
+
+ALTER TABLE twinoid_user_links
+  ALTER COLUMN period SET NOT NULL,
+  DROP COLUMN linked_at;
+
+ALTER TABLE twinoid_user_links
+  ADD CHECK ((upper_inf(period) AND unlinked_by IS NULL) OR (NOT upper_inf(period) AND unlinked_by IS NOT NULL)),
+  ADD CONSTRAINT twinoid_user_link_unlinked_by__user__fk FOREIGN KEY (unlinked_by) REFERENCES users(user_id) ON DELETE RESTRICT ON UPDATE CASCADE;
+
+ALTER TABLE twinoid_user_links
+  DROP CONSTRAINT twinoid_user_links_pkey;
+ALTER TABLE twinoid_user_links
+  ADD PRIMARY KEY (user_id, twinoid_user_id, period);
+
+ALTER TABLE twinoid_user_links
+  DROP CONSTRAINT twinoid_user_links_user_id_key;
+ALTER TABLE twinoid_user_links
+  ADD EXCLUDE USING gist (user_id WITH =, period WITH &&);
+
+ALTER TABLE twinoid_user_links
+  DROP CONSTRAINT twinoid_user_links_twinoid_user_id_key;
+ALTER TABLE twinoid_user_links
+  ADD EXCLUDE USING gist (twinoid_user_id WITH =, period WITH &&);
+
+DROP TABLE old_twinoid_user_links;
+
+-- Migration step: 013 -> 014
+CREATE DOMAIN valid_period AS PERIOD CHECK (NOT LOWER_INF(value) AND NOT UPPER_INF(value));
+
+CREATE DOMAIN i8 AS INT2 CHECK (-128 <= value AND value < 128);
+CREATE DOMAIN u8 AS INT2 CHECK (0 <= value AND value < 256);
+CREATE DOMAIN i16 AS INT2;
+CREATE DOMAIN u16 AS INT4 CHECK (0 <= value AND value < 65536);
+CREATE DOMAIN i32 AS INT4;
+CREATE DOMAIN u32 AS INT8 CHECK (0 <= value AND value < 4294967296);
+CREATE DOMAIN i64 AS INT8;
+
+CREATE DOMAIN hammerfest_item_count_map_id AS UUID;
+CREATE DOMAIN hammerfest_quest_status_map_id AS UUID;
+CREATE DOMAIN hammerfest_unlocked_item_set_id AS UUID;
+
+CREATE DOMAIN hammerfest_forum_theme_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN hammerfest_forum_thread_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN hammerfest_forum_message_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN hammerfest_item_id AS VARCHAR(4) CHECK (value ~ '^(?:0|[1-9]\d{0,3})$');
+CREATE DOMAIN hammerfest_quest_id AS VARCHAR(4) CHECK (value ~ '^(?:0|[1-9]\d{0,3})$');
+-- Pyramid level: 0 (Hall of Fame) to 4 (Level 4).
+CREATE DOMAIN hammerfest_ladder_level AS U8 CHECK (value < 5);
+CREATE DOMAIN hammerfest_forum_theme_title AS VARCHAR(100);
+CREATE DOMAIN hammerfest_forum_theme_description AS VARCHAR(500);
+CREATE DOMAIN hammerfest_forum_thread_title AS VARCHAR(100);
+
+CREATE DOMAIN rfc_oauth_access_token_key AS TEXT;
+CREATE DOMAIN rfc_oauth_refresh_token_key AS TEXT;
+
+--- Checks that arr is ascendingly-sorted array of unique non-null values
+CREATE OR REPLACE FUNCTION array_is_ordered_set(
+  IN arr ANYARRAY
+) RETURNS BOOLEAN
+  LANGUAGE sql
+  IMMUTABLE STRICT PARALLEL SAFE AS
+$$
+SELECT arr = (
+  SELECT ARRAY_AGG(item)
+  FROM (
+    SELECT DISTINCT UNNEST(arr) AS item
+    ORDER BY item ASC
+  ) AS items
+  WHERE item IS NOT NULL
+);
+$$;
+
+--- Checks that arr is ascendingly-sorted array of unique non-null instants with at most 2 instants in any period of duration `sampling_window`
+CREATE OR REPLACE FUNCTION array_is_sampled_instant_set(
+  IN arr INSTANT ARRAY,
+  IN sampling_window INTERVAL
+) RETURNS BOOLEAN
+  LANGUAGE sql
+  IMMUTABLE STRICT PARALLEL SAFE AS
+$$
+SELECT array_is_ordered_set(arr) AND (
+  SELECT MAX(sample_count_in_window)
+  FROM (
+    SELECT COUNT(item) OVER (ORDER BY item RANGE sampling_window PRECEDING) AS sample_count_in_window
+    FROM (
+      SELECT UNNEST(arr) AS item
+    ) AS items
+  ) AS counts
+) <= 2;
+$$;
+
+--- Insert a value at the end of a `samplied_instant_set`, see `array_is_sampled_instant_set`
+CREATE OR REPLACE FUNCTION sampled_instant_set_insert_back(
+  IN arr INSTANT ARRAY,
+  IN sampling_window INTERVAL,
+  IN new_value INSTANT
+) RETURNS INSTANT ARRAY
+  LANGUAGE sql
+  IMMUTABLE STRICT PARALLEL SAFE AS
+$$
+SELECT CASE WHEN ARRAY_LENGTH(arr, 1) = 0
+              THEN ARRAY [new_value]
+            WHEN ARRAY_LENGTH(arr, 1) = 1 AND arr[1] <> new_value
+              THEN arr || new_value
+            WHEN ARRAY_LENGTH(arr, 1) >= 1 AND arr[ARRAY_LENGTH(arr, 1)] = new_value
+              THEN arr
+            WHEN ARRAY_LENGTH(arr, 1) >= 2 AND new_value - arr[ARRAY_LENGTH(arr, 1) - 1] < sampling_window
+              THEN arr[1:ARRAY_LENGTH(arr, 1) - 1] || new_value
+            ELSE arr || new_value END
+$$;
+
+-- Ordered set of instants, such as for each period of time T, there are at most 2 values
+-- Where `T` depen
+CREATE DOMAIN sampled_instant_set AS INSTANT ARRAY CHECK (array_is_ordered_set(value));
+
+CREATE TYPE HAMMERFEST_FORUM_ROLE AS ENUM ('None', 'Moderator', 'Administrator');
+CREATE TYPE HAMMERFEST_QUEST_STATUS AS ENUM ('None', 'Pending', 'Complete');
+
+CREATE TYPE RAW_HAMMERFEST_DATE AS (
+  -- 1-12
+  month U8,
+  -- 1-31
+  day U8,
+  -- Day of week: Monday(1) to Sunday(7)
+  isodow U8
+);
+
+CREATE DOMAIN hammerfest_date AS RAW_HAMMERFEST_DATE;
+
+CREATE TYPE RAW_HAMMERFEST_DATETIME AS (
+  -- 1-12
+  month U8,
+  -- 1-31
+  day U8,
+  -- Day of week: Monday(1) to Sunday(7)
+  isodow U8,
+  -- 0-23
+  hour U8,
+  -- 0-59
+  minute U8
+);
+
+CREATE DOMAIN hammerfest_datetime AS RAW_HAMMERFEST_DATETIME CHECK ( (value).month IS NOT NULL AND 1 <= (value).month AND (value).month <= 12 AND (value).day IS NOT NULL AND 1 <= (value).day
+  AND (value).day <= 31 AND (value).isodow IS NOT NULL AND 1 <= (value).isodow AND (value).isodow <= 7 AND (value).hour IS NOT NULL AND 1 <= (value).hour AND (value).hour <= 23
+  AND (value).minute IS NOT NULL AND 1 <= (value).minute AND (value).minute <= 59 );
+
+-- The list of items in Hammerfest (official game)
+CREATE TABLE hammerfest_items (
+  hammerfest_item_id HAMMERFEST_ITEM_ID NOT NULL,
+  is_hidden BOOLEAN NOT NULL,
+  PRIMARY KEY (hammerfest_item_id)
+);
+
+-- Global constant: sampling window size for sampled instant sets
+CREATE OR REPLACE FUNCTION const_sampling_window() RETURNS INTERVAL
+  LANGUAGE sql
+  IMMUTABLE STRICT PARALLEL SAFE AS
+$$
+SELECT '1day'::INTERVAL
+$$;
+
+-- The list of quests in Hammerfest (official game)
+CREATE TABLE hammerfest_quests (
+  hammerfest_quest_id HAMMERFEST_QUEST_ID NOT NULL,
+  PRIMARY KEY (hammerfest_quest_id)
+);
+
+-- Immutable quest status maps (may be shared by different users)
+CREATE TABLE hammerfest_quest_status_maps (
+  hammerfest_quest_status_map_id HAMMERFEST_QUEST_STATUS_MAP_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is a map from the id to the status, sorted by id and json does not use any whitespace
+-- {"0":"None","1":"Pending",2:"Complete"}
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (hammerfest_quest_status_map_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of hammerfest_quest_status_maps
+CREATE TABLE hammerfest_quest_status_map_items (
+  hammerfest_quest_status_map_id HAMMERFEST_QUEST_STATUS_MAP_ID NOT NULL,
+  hammerfest_quest_id HAMMERFEST_QUEST_ID NOT NULL,
+  status HAMMERFEST_QUEST_STATUS NOT NULL,
+  PRIMARY KEY (hammerfest_quest_status_map_id, hammerfest_quest_id),
+  CONSTRAINT hammerfest_quest_status_map_item__map__fk FOREIGN KEY (hammerfest_quest_status_map_id) REFERENCES hammerfest_quest_status_maps(hammerfest_quest_status_map_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_quest_status_map_item__quest__fk FOREIGN KEY (hammerfest_quest_id) REFERENCES hammerfest_quests(hammerfest_quest_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Immutable unlocked items state (may be shared by different users)
+CREATE TABLE hammerfest_unlocked_item_sets (
+  hammerfest_unlocked_item_set_id HAMMERFEST_UNLOCKED_ITEM_SET_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is a sorted list of item ids and json does not use any whitespace
+-- [0,2,100]
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (hammerfest_unlocked_item_set_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of hammerfest_unlocked_items_snapshots
+CREATE TABLE hammerfest_unlocked_item_set_items (
+  hammerfest_unlocked_item_set_id HAMMERFEST_UNLOCKED_ITEM_SET_ID NOT NULL,
+  hammerfest_item_id HAMMERFEST_ITEM_ID NOT NULL,
+  PRIMARY KEY (hammerfest_unlocked_item_set_id, hammerfest_item_id),
+  CONSTRAINT hammerfest_unlocked_item_set_item__map__fk FOREIGN KEY (hammerfest_unlocked_item_set_id) REFERENCES hammerfest_unlocked_item_sets(hammerfest_unlocked_item_set_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_unlocked_item_set_item__item__fk FOREIGN KEY (hammerfest_item_id) REFERENCES hammerfest_items(hammerfest_item_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Immutable item counts (may be shared by different users)
+CREATE TABLE hammerfest_item_count_maps (
+  hammerfest_item_count_map_id HAMMERFEST_ITEM_COUNT_MAP_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is a map from the item id to the count, sorted by id and json does not use any whitespace
+-- {"0":0,"2":9,2:5}
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (hammerfest_item_count_map_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of hammerfest_quest_statuses_snapshots
+CREATE TABLE hammerfest_item_count_map_items (
+  hammerfest_item_count_map_id HAMMERFEST_ITEM_COUNT_MAP_ID NOT NULL,
+  hammerfest_item_id HAMMERFEST_ITEM_ID NOT NULL,
+  count U32 NOT NULL,
+  PRIMARY KEY (hammerfest_item_count_map_id, hammerfest_item_id),
+  CONSTRAINT hammerfest_item_count_map_item__map__fk FOREIGN KEY (hammerfest_item_count_map_id) REFERENCES hammerfest_item_count_maps(hammerfest_item_count_map_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_item_count_map_item__item__fk FOREIGN KEY (hammerfest_item_id) REFERENCES hammerfest_items(hammerfest_item_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for hammerfest inventories
+CREATE TABLE hammerfest_inventories (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  item_counts HAMMERFEST_ITEM_COUNT_MAP_ID NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_inventory__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_inventory__item_counts__fk FOREIGN KEY (item_counts) REFERENCES hammerfest_item_count_maps(hammerfest_item_count_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data unique to the public profile
+CREATE TABLE hammerfest_profiles (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  best_score U32 NOT NULL,
+  best_level U8 NOT NULL CHECK (best_level < 120),
+-- Null if not played
+  season_score U32 NULL,
+  quest_statuses HAMMERFEST_QUEST_STATUS_MAP_ID NOT NULL,
+  unlocked_items HAMMERFEST_UNLOCKED_ITEM_SET_ID NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_profiles__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_profiles__quest_statuses__fk FOREIGN KEY (quest_statuses) REFERENCES hammerfest_quest_status_maps(hammerfest_quest_status_map_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_profiles__unlocked_items__fk FOREIGN KEY (unlocked_items) REFERENCES hammerfest_unlocked_item_sets(hammerfest_unlocked_item_set_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant linked email
+CREATE TABLE hammerfest_emails (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  email EMAIL_ADDRESS_HASH NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, email WITH =, period WITH &&),
+  CONSTRAINT hammerfest_email__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_email__email__fk FOREIGN KEY (email) REFERENCES email_addresses(_hash) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data shared by the public profile and forum author
+CREATE TABLE hammerfest_user_achievements (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  has_carrot BOOLEAN NOT NULL,
+  ladder_level HAMMERFEST_LADDER_LEVEL NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_user_achievements__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant best season rank, as displayed on the forum
+CREATE TABLE hammerfest_best_season_rank (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+--   Null if the forum displayed `--`
+  best_season_rank U32 NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_user_achievements__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data unique to the forum author
+CREATE TABLE hammerfest_forum_roles (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  role HAMMERFEST_FORUM_ROLE NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_user_ranks__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data unique to the shop
+CREATE TABLE hammerfest_shop_history (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  weekly_tokens U8 NOT NULL,
+--   0-249 is exact, 250 or more is represented with NULL (inf)
+  purchased_tokens U8 NULL,
+  has_quest_bonus BOOL NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_shop_history__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant game tokens
+CREATE TABLE hammerfest_tokens (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  tokens U32 NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_tokens__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant Hammerfest godfather links
+CREATE TABLE hammerfest_godfathers (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  godfather_id HAMMERFEST_USER_ID NOT NULL,
+-- Tokens granted to the godfather
+  tokens U32 NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_godfathers__child__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_godfathers__father__fk FOREIGN KEY (hammerfest_server, godfather_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Permanent data for forum themes
+CREATE TABLE hammerfest_forum_themes (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_theme_id HAMMERFEST_FORUM_THEME_ID NOT NULL,
+--
+  archived_at INSTANT NOT NULL,
+  title HAMMERFEST_FORUM_THEME_TITLE NOT NULL,
+  description HAMMERFEST_FORUM_THEME_DESCRIPTION NULL,
+  is_public BOOLEAN NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_theme_id),
+  CONSTRAINT hammerfest_forum_themes__servers__fk FOREIGN KEY (hammerfest_server) REFERENCES hammerfest_servers(hammerfest_server) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads, shared by the thread list and thread page
+CREATE TABLE hammerfest_forum_theme_page_counts (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_theme_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  page_count U32 NOT NULL CHECK (page_count > 0),
+  PRIMARY KEY (hammerfest_server, hammerfest_theme_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_theme_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_threads_history__theme__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Permanent data for forum threads
+CREATE TABLE hammerfest_forum_threads (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  archived_at INSTANT NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_thread_id),
+  CONSTRAINT hammerfest_forum_threads__servers__fk FOREIGN KEY (hammerfest_server) REFERENCES hammerfest_servers(hammerfest_server) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads, shared by the thread list and thread page
+CREATE TABLE hammerfest_forum_thread_shared_meta (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  hammerfest_theme_id HAMMERFEST_FORUM_THEME_ID NOT NULL,
+  title HAMMERFEST_FORUM_THREAD_TITLE NOT NULL,
+  is_closed BOOLEAN NOT NULL,
+  page_count U32 NOT NULL CHECK (page_count > 0),
+  PRIMARY KEY (hammerfest_server, hammerfest_thread_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_threads_history__theme__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads unique to the thread list
+CREATE TABLE hammerfest_forum_thread_list_meta (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  -- Current theme page for this thread
+  page U16 NOT NULL CHECK (page > 0),
+  is_sticky BOOLEAN NOT NULL,
+  latest_message_at HAMMERFEST_DATE NULL,
+  author hammerfest_user_id NOT NULL,
+  reply_count U16 NOT NULL,
+  CHECK ((is_sticky AND latest_message_at IS NULL) OR (NOT is_sticky AND latest_message_at IS NOT NULL)),
+  PRIMARY KEY (hammerfest_server, hammerfest_thread_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, period WITH &&)
+);
+
+-- Time-variant data for forum messages
+CREATE TABLE hammerfest_forum_messages_history (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  page U16 NOT NULL CHECK (page > 0),
+  offset_in_page U8 NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  author HAMMERFEST_USER_ID NOT NULL,
+  posted_at HAMMERFEST_DATETIME NOT NULL,
+  -- Raw HTML content as found on the remote website
+  remote_html_body TEXT NOT NULL,
+  -- Marktwin body
+  _mkt_body TEXT NULL,
+  -- Rendered Marktwin body
+  _html_body TEXT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_thread_id, page, offset_in_page, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, page WITH =, offset_in_page WITH =, period WITH &&),
+  CONSTRAINT hammerfest_messages_history__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_messages_history__author__fk FOREIGN KEY (hammerfest_server, author) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for message-position/message id relationship
+CREATE TABLE hammerfest_forum_message_ids (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  page U16 NOT NULL CHECK (page > 0),
+  offset_in_page U8 NOT NULL,
+  period PERIOD_FROM NOT NULL,
+  retrieved_at SAMPLED_INSTANT_SET NOT NULL CHECK (array_is_sampled_instant_set(retrieved_at, const_sampling_window())),
+--
+  hammerfest_message_id HAMMERFEST_FORUM_MESSAGE_ID NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_thread_id, page, offset_in_page, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, page WITH =, offset_in_page WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_message_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_messages_history__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Migration step: 014 -> 015
+CREATE DOMAIN dinoparc_dinoz_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+
+-- Migration step: 015 -> 016
+DROP TABLE hammerfest_tokens;
+DROP TABLE hammerfest_profiles;
+DROP TABLE hammerfest_emails;
+DROP TABLE hammerfest_best_season_rank;
+DROP TABLE hammerfest_user_achievements;
+DROP TABLE hammerfest_forum_roles;
+DROP TABLE hammerfest_shop_history;
+DROP TABLE hammerfest_godfathers;
+DROP TABLE hammerfest_forum_theme_page_counts;
+DROP TABLE hammerfest_forum_thread_list_meta;
+DROP TABLE hammerfest_forum_thread_shared_meta;
+DROP TABLE hammerfest_forum_messages_history;
+DROP TABLE hammerfest_forum_message_ids;
+DROP TABLE hammerfest_inventories;
+
+ALTER DOMAIN hammerfest_forum_message_id RENAME TO hammerfest_forum_post_id;
+ALTER DOMAIN PERIOD_FROM RENAME TO PERIOD_LOWER;
+
+DROP DOMAIN valid_period;
+DROP DOMAIN hammerfest_date;
+
+CREATE DOMAIN hammerfest_date AS raw_hammerfest_date CHECK (
+  value IS NULL OR ((value).month IS NOT NULL AND (value).day IS NOT NULL AND (value).isodow IS NOT NULL)
+);
+
+DROP DOMAIN hammerfest_datetime;
+
+ALTER TYPE raw_hammerfest_datetime RENAME TO raw_hammerfest_date_time;
+
+CREATE DOMAIN hammerfest_date_time AS RAW_HAMMERFEST_DATE_TIME CHECK (
+  value IS NULL OR (
+  (value).month IS NOT NULL AND 1 <= (value).month
+  AND (value).month <= 12 AND (value).day IS NOT NULL AND 1 <= (value).day AND (value).day <= 31
+  AND (value).isodow IS NOT NULL AND 1 <= (value).isodow AND (value).isodow <= 7 AND (value).hour IS NOT NULL
+  AND 0 <= (value).hour AND (value).hour <= 23 AND (value).minute IS NOT NULL AND 0 <= (value).minute
+  AND (value).minute <= 59
+));
+
+DROP DOMAIN sampled_instant_set;
+CREATE DOMAIN instant_set AS INSTANT ARRAY CHECK (array_is_ordered_set(value));
+
+--- Insert a value to an ordered set
+CREATE OR REPLACE FUNCTION ordered_set_insert(
+  IN arr ANYARRAY,
+  IN new_value ANYELEMENT
+) RETURNS ANYARRAY
+  LANGUAGE sql
+  IMMUTABLE STRICT PARALLEL SAFE AS
+$$
+SELECT ARRAY_AGG(item)
+FROM (
+  SELECT DISTINCT UNNEST(array_append(arr, new_value)) AS item
+  ORDER BY item ASC
+) AS items;
+$$;
+
+-- Time-variant game tokens <any(logged)>
+CREATE TABLE hammerfest_tokens (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  tokens U32 NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_tokens__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data unique to the shop <shop>
+CREATE TABLE hammerfest_shops (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  weekly_tokens U8 NOT NULL,
+--   0-249 is exact, 250 or more is represented with NULL (inf)
+  purchased_tokens U8 NULL,
+  has_quest_bonus BOOL NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_shops__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant Hammerfest godchild list meta <godChildren>
+CREATE TABLE hammerfest_godchild_lists (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  godchild_count U32 NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_godchild_lists__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant Hammerfest godchild list items (<godChildren>)
+CREATE TABLE hammerfest_godchildren (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  offset_in_list U32 NOT NULL,
+--
+  godchild_id HAMMERFEST_USER_ID NOT NULL,
+-- Tokens granted to the godfather
+  tokens U32 NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id, offset_in_list),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, offset_in_list WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, godchild_id WITH =, period WITH &&),
+  CHECK (godchild_id <> hammerfest_godchildren.hammerfest_user_id),
+  CONSTRAINT hammerfest_godchildren__father__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_godchildren__child__fk FOREIGN KEY (hammerfest_server, godchild_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data unique to the public profile <profile>
+CREATE TABLE hammerfest_profiles (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  best_score U32 NOT NULL,
+  best_level U8 NOT NULL CHECK (best_level < 120),
+-- Null if not played
+  season_score U32 NULL,
+  quest_statuses HAMMERFEST_QUEST_STATUS_MAP_ID NOT NULL,
+  unlocked_items HAMMERFEST_UNLOCKED_ITEM_SET_ID NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_profiles__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_profiles__quest_statuses__fk FOREIGN KEY (quest_statuses) REFERENCES hammerfest_quest_status_maps(hammerfest_quest_status_map_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_profiles__unlocked_items__fk FOREIGN KEY (unlocked_items) REFERENCES hammerfest_unlocked_item_sets(hammerfest_unlocked_item_set_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant linked email <profile(logged)>
+CREATE TABLE hammerfest_emails (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  email EMAIL_ADDRESS_HASH NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, email WITH =, period WITH &&),
+  CONSTRAINT hammerfest_email__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_email__email__fk FOREIGN KEY (email) REFERENCES email_addresses(_hash) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data shared by the public profile and forum author <profile + forumThread>
+CREATE TABLE hammerfest_user_achievements (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  has_carrot BOOLEAN NOT NULL,
+  ladder_level HAMMERFEST_LADDER_LEVEL NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_user_achievements__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for hammerfest inventories <inventory>
+CREATE TABLE hammerfest_inventories (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  item_counts HAMMERFEST_ITEM_COUNT_MAP_ID NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_user_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_inventory__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_inventory__item_counts__fk FOREIGN KEY (item_counts) REFERENCES hammerfest_item_count_maps(hammerfest_item_count_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant page count (in a theme page, number of pages for the thread list) <forumTheme>
+CREATE TABLE hammerfest_forum_theme_counts (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_theme_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+--
+  page_count U16 NOT NULL CHECK (page_count > 0),
+  PRIMARY KEY (period, hammerfest_server, hammerfest_theme_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_theme_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_theme_counts__theme__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant counts of regular threads in a page <forumTheme>
+CREATE TABLE hammerfest_forum_theme_page_counts (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_theme_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+-- Page 0 is the sticky thread list, page >= 1  is a regular thread list
+  page U16 NOT NULL,
+--
+  thread_count U8 NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_theme_id, page),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_theme_id WITH =, page WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_theme_page_counts__themes__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant regular thread list items <forumTheme>
+CREATE TABLE hammerfest_forum_theme_threads (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_theme_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+-- Page 0 is the sticky thread list, page >= 1  is a regular thread list
+  page U16 NOT NULL,
+  offset_in_list U8 NOT NULL,
+--
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_theme_id, page, offset_in_list),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_theme_id WITH =, page WITH =, offset_in_list WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_theme_threads__theme__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_forum_theme_threads__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads unique to the thread list <forumTheme>
+CREATE TABLE hammerfest_forum_thread_theme_meta (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+--
+  is_sticky BOOLEAN NOT NULL,
+  latest_post_at HAMMERFEST_DATE NULL,
+  author HAMMERFEST_USER_ID NOT NULL,
+  reply_count U16 NOT NULL,
+  CHECK ((is_sticky AND latest_post_at IS NULL) OR (NOT is_sticky AND latest_post_at IS NOT NULL)),
+  PRIMARY KEY (period, hammerfest_server, hammerfest_thread_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_thread_theme_meta__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads, shared by the thread list and thread page <forumTheme + forumThread>
+CREATE TABLE hammerfest_forum_thread_shared_meta (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+--
+  hammerfest_theme_id HAMMERFEST_FORUM_THEME_ID NOT NULL,
+  title HAMMERFEST_FORUM_THREAD_TITLE NOT NULL,
+  is_closed BOOLEAN NOT NULL,
+  page_count U32 NOT NULL CHECK (page_count > 0),
+  PRIMARY KEY (period, hammerfest_server, hammerfest_thread_id),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_thread_shared_meta__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_forum_thread_shared_meta__theme__fk FOREIGN KEY (hammerfest_server, hammerfest_theme_id) REFERENCES hammerfest_forum_themes(hammerfest_server, hammerfest_theme_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant meta for forum threads, shared by the thread list and thread page <forumTheme + forumThread>
+CREATE TABLE hammerfest_forum_roles (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+  role HAMMERFEST_FORUM_ROLE NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_roles__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant post counts for a thread page <threadPage>
+CREATE TABLE hammerfest_forum_thread_page_counts (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  page U16 NOT NULL CHECK (page > 0),
+--
+  post_count U8 NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_thread_id, page),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, page WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_thread_page_counts__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for forum posts <threadPage>
+CREATE TABLE hammerfest_forum_posts (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  page U16 NOT NULL CHECK (page > 0),
+  offset_in_list U8 NOT NULL,
+--
+  author HAMMERFEST_USER_ID NOT NULL,
+  posted_at HAMMERFEST_DATE_TIME NOT NULL,
+  -- Raw HTML content as found on the remote website
+  remote_html_body TEXT NOT NULL,
+  -- Marktwin body
+  _mkt_body TEXT NULL,
+  -- Rendered Marktwin body
+  _html_body TEXT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_thread_id, page, offset_in_list),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, page WITH =, offset_in_list WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_posts__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT hammerfest_forum_posts__author__fk FOREIGN KEY (hammerfest_server, author) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for post-position/post id relationship <threadPage>
+CREATE TABLE hammerfest_forum_post_ids (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_thread_id HAMMERFEST_FORUM_THREAD_ID NOT NULL,
+  page U16 NOT NULL CHECK (page > 0),
+  offset_in_list U8 NOT NULL,
+--
+  hammerfest_post_id HAMMERFEST_FORUM_POST_ID NOT NULL,
+  PRIMARY KEY (period, hammerfest_server, hammerfest_thread_id, page, offset_in_list),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_thread_id WITH =, page WITH =, offset_in_list WITH =, period WITH &&),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_post_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_forum_post_ids__thread__fk FOREIGN KEY (hammerfest_server, hammerfest_thread_id) REFERENCES hammerfest_forum_threads(hammerfest_server, hammerfest_thread_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant best season rank, as displayed on the forum <threadPage>
+CREATE TABLE hammerfest_best_season_ranks (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+--
+--   Null if the forum displayed `--`
+  best_season_rank U32 NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id, period),
+  EXCLUDE USING gist (hammerfest_server WITH =, hammerfest_user_id WITH =, period WITH &&),
+  CONSTRAINT hammerfest_best_season_rank__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Migration step: 016 -> 017
+CREATE DOMAIN int_percentage AS U8 CHECK (value <= 100);
+
+CREATE DOMAIN dinoparc_location_id AS VARCHAR(2) CHECK (value ~ '^\d{1,2}$');
+CREATE DOMAIN dinoparc_item_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN dinoparc_item_count_map_id AS UUID;
+CREATE DOMAIN dinoparc_skill_level_map_id AS UUID;
+
+CREATE DOMAIN dinoparc_dinoz_name AS VARCHAR(100);
+
+CREATE DOMAIN dinoparc_skill AS VARCHAR(50) CHECK (value IN ('Bargain', 'Camouflage', 'Climb', 'Cook', 'Counterattack', 'Dexterity', 'Dig', 'EarthApprentice', 'FireApprentice', 'FireProtection', 'Intelligence', 'Juggle', 'Jump', 'Luck', 'MartialArts', 'Medicine', 'Mercenary', 'Music', 'Navigation', 'Perception', 'Provoke', 'Run', 'Saboteur', 'ShadowPower', 'Spy', 'Stamina', 'Steal', 'Strategy', 'Strength', 'Survival', 'Swim', 'TotemThief', 'ThunderApprentice', 'WaterApprentice'));
+CREATE DOMAIN dinoparc_skill_level AS U8 CHECK (value <= 5);
+
+CREATE DOMAIN dinoparc_dinoz_race AS VARCHAR(50) CHECK (value IN ('Cargou', 'Castivore', 'Gorriloz', 'Gluon', 'Hippoclamp', 'Kabuki', 'Korgon', 'Kump', 'Moueffe', 'Ouistiti', 'Picori', 'Pigmou', 'Pteroz', 'Rokky', 'Santaz', 'Serpantin', 'Sirain', 'Wanwan', 'Winks'));
+CREATE DOMAIN dinoparc_dinoz_skin AS VARCHAR(100);
+
+CREATE TYPE RAW_DINOPARC_DINOZ_ELEMENTS AS (
+  fire U16,
+  earth U16,
+  water U16,
+  thunder U16,
+  air U16
+);
+
+CREATE DOMAIN DINOPARC_DINOZ_ELEMENTS AS RAW_DINOPARC_DINOZ_ELEMENTS CHECK (
+  value IS NULL OR (
+        (value).fire IS NOT NULL
+    AND (value).earth IS NOT NULL
+    AND (value).water IS NOT NULL
+    AND (value).thunder IS NOT NULL
+    AND (value).air IS NOT NULL
+  )
+);
+
+-- Permanent data for dinoparc dinoz
+CREATE TABLE dinoparc_dinoz (
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+  archived_at INSTANT NOT NULL,
+  PRIMARY KEY (dinoparc_server, dinoparc_dinoz_id),
+  CONSTRAINT dinoparc_dinoz__servers__fk FOREIGN KEY (dinoparc_server) REFERENCES dinoparc_servers(dinoparc_server) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE dinoparc_locations (
+  -- Dinoparc location id, in practice `0 <= loc_id < 23`.
+  dinoparc_location_id DINOPARC_LOCATION_ID PRIMARY KEY NOT NULL
+);
+
+-- Immutable item counts (may be shared by different users)
+CREATE TABLE dinoparc_item_count_maps (
+  dinoparc_item_count_map_id DINOPARC_ITEM_COUNT_MAP_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is a map from the item id to the count, sorted by id and json does not use any whitespace
+-- {"0":0,"2":9,"30":5}
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (dinoparc_item_count_map_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of dinoparc_item_count_maps
+CREATE TABLE dinoparc_item_count_map_items (
+  dinoparc_item_count_map_id DINOPARC_ITEM_COUNT_MAP_ID NOT NULL,
+  dinoparc_item_id DINOPARC_ITEM_ID NOT NULL,
+  count U32 NOT NULL,
+  PRIMARY KEY (dinoparc_item_count_map_id, dinoparc_item_id),
+  CONSTRAINT dinoparc_item_count_map_item__map__fk FOREIGN KEY (dinoparc_item_count_map_id) REFERENCES dinoparc_item_count_maps(dinoparc_item_count_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Immutable skill levels (may be shared by different dinoz)
+CREATE TABLE dinoparc_skill_level_maps (
+  dinoparc_skill_level_map_id DINOPARC_SKILL_LEVEL_MAP_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is a map from the skill to the level, sorted by skill and json does not use any whitespace
+-- {"Bargain":1,"Camouflage":2}
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (dinoparc_skill_level_map_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of dinoparc_skill_level_maps
+CREATE TABLE dinoparc_skill_level_map_items (
+  dinoparc_skill_level_map_id DINOPARC_SKILL_LEVEL_MAP_ID NOT NULL,
+  dinoparc_skill DINOPARC_SKILL NOT NULL,
+  level DINOPARC_SKILL_LEVEL NOT NULL,
+  PRIMARY KEY (dinoparc_skill_level_map_id, dinoparc_skill),
+  CONSTRAINT dinoparc_skill_level_map_item__map__fk FOREIGN KEY (dinoparc_skill_level_map_id) REFERENCES dinoparc_skill_level_maps(dinoparc_skill_level_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant game coins <any(logged)>
+CREATE TABLE dinoparc_coins (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+--
+  coins U32 NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_coins__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant dinoz names (may change following a fusion) <any(logged) + exchangeWith>
+CREATE TABLE dinoparc_dinoz_names (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  name DINOPARC_DINOZ_NAME NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_names__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant dinoz owners (Dinoz may be exchanged) <any(logged) + exchangeWith>
+CREATE TABLE dinoparc_dinoz_owners (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  owner DINOPARC_USER_ID NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_names__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_dinoz_names__owner__fk FOREIGN KEY (dinoparc_server, owner) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant dinoz locations <any(logged)>
+CREATE TABLE dinoparc_dinoz_locations (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  location DINOPARC_LOCATION_ID NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_locations__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_dinoz_locations__location__fk FOREIGN KEY (location) REFERENCES dinoparc_locations(dinoparc_location_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant dinoz levels <dinoz + exchangeWith>
+CREATE TABLE dinoparc_dinoz_levels (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  level U16 NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_levels__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant dinoz data unique to their profile <dinoz>
+CREATE TABLE dinoparc_dinoz_profiles (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  race DINOPARC_DINOZ_RACE NOT NULL,
+  skin DINOPARC_DINOZ_SKIN NOT NULL,
+  life int_percentage NOT NULL,
+  experience int_percentage NOT NULL,
+  danger i16 NOT NULL,
+  in_tournament BOOLEAN NOT NULL,
+  elements DINOPARC_DINOZ_ELEMENTS NOT NULL,
+  skills dinoparc_skill_level_map_id NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_profiles__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_dinoz_profiles__skills__fk FOREIGN KEY (skills) REFERENCES dinoparc_skill_level_maps(dinoparc_skill_level_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for dinoparc inventories <inventory>
+CREATE TABLE dinoparc_inventories (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+--
+  item_counts DINOPARC_ITEM_COUNT_MAP_ID NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_inventories__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_inventories__item_counts__fk FOREIGN KEY (item_counts) REFERENCES dinoparc_item_count_maps(dinoparc_item_count_map_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant counts of Dinoz owned by a user <any(logged) + exchange>
+CREATE TABLE dinoparc_user_dinoz_counts (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+--
+  dinoz_count U32 NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_user_dinoz_counts__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant Dinoz list items <any(logged) + exchange>
+CREATE TABLE dinoparc_user_dinoz (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  offset_in_list U32 NOT NULL,
+--
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id, offset_in_list),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, offset_in_list WITH =, period WITH &&),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_user_dinoz__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_user_dinoz__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Migration step: 017 -> 018
+CREATE DOMAIN dinoparc_reward_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d?$');
+CREATE DOMAIN dinoparc_epic_reward_key AS VARCHAR(10) CHECK (value ~ '^[a-z0-9_]{1,30}$');
+CREATE DOMAIN dinoparc_reward_set_id AS UUID;
+CREATE DOMAIN dinoparc_epic_reward_set_id AS UUID;
+
+-- Immutable regular reward set (may be shared by different users)
+CREATE TABLE dinoparc_reward_sets (
+  dinoparc_reward_set_id DINOPARC_REWARD_SET_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is an array of reward ids, sorted by id and json does not use any whitespace
+-- [2,11,15,20]
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (dinoparc_reward_set_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of dinoparc_reward_sets
+CREATE TABLE dinoparc_reward_set_items (
+  dinoparc_reward_set_id DINOPARC_REWARD_SET_ID NOT NULL,
+  dinoparc_reward_id DINOPARC_REWARD_ID NOT NULL,
+  PRIMARY KEY (dinoparc_reward_set_id, dinoparc_reward_id),
+  CONSTRAINT dinoparc_reward_set_items__set__fk FOREIGN KEY (dinoparc_reward_set_id) REFERENCES dinoparc_reward_sets(dinoparc_reward_set_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Immutable epic reward set (may be shared by different users)
+CREATE TABLE dinoparc_epic_reward_sets (
+  dinoparc_epic_reward_set_id DINOPARC_EPIC_REWARD_SET_ID NOT NULL,
+-- sha3_256(utf8(json(value)))
+-- Where `value` is an array of reward keys, sorted by key and json does not use any whitespace
+-- ["a", "bc", "ca", "d"]
+  _sha3_256 BYTEA NOT NULL,
+  PRIMARY KEY (dinoparc_epic_reward_set_id),
+  UNIQUE (_sha3_256)
+);
+
+-- Content of dinoparc_reward_sets
+CREATE TABLE dinoparc_epic_reward_set_items (
+  dinoparc_epic_reward_set_id DINOPARC_EPIC_REWARD_SET_ID NOT NULL,
+  dinoparc_epic_reward_key DINOPARC_EPIC_REWARD_KEY NOT NULL,
+  PRIMARY KEY (dinoparc_epic_reward_set_id, dinoparc_epic_reward_key),
+  CONSTRAINT dinoparc_epic_reward_sets__set__fk FOREIGN KEY (dinoparc_epic_reward_set_id) REFERENCES dinoparc_epic_reward_sets(dinoparc_epic_reward_set_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Time-variant data for dinoparc collections <collection>
+CREATE TABLE dinoparc_collections (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+--
+  dinoparc_reward_set_id DINOPARC_REWARD_SET_ID NOT NULL,
+  dinoparc_epic_reward_set_id DINOPARC_EPIC_REWARD_SET_ID NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_collections__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_collections__rewards__fk FOREIGN KEY (dinoparc_reward_set_id) REFERENCES dinoparc_reward_sets(dinoparc_reward_set_id) ON DELETE RESTRICT ON UPDATE CASCADE,
+  CONSTRAINT dinoparc_collections__epic_rewards__fk FOREIGN KEY (dinoparc_epic_reward_set_id) REFERENCES dinoparc_epic_reward_sets(dinoparc_epic_reward_set_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Migration step: 018 -> 019
+ALTER TABLE dinoparc_dinoz_profiles
+  ALTER COLUMN race TYPE VARCHAR(50);
+
+DROP DOMAIN dinoparc_dinoz_race;
+
+UPDATE dinoparc_dinoz_profiles SET race = 'Gorilloz' WHERE race = 'Gorriloz';
+
+CREATE DOMAIN dinoparc_dinoz_race AS VARCHAR(50) CHECK (value IN ('Cargou', 'Castivore', 'Gorriloz', 'Gorilloz', 'Gluon', 'Hippoclamp', 'Kabuki', 'Korgon', 'Kump', 'Moueffe', 'Ouistiti', 'Picori', 'Pigmou', 'Pteroz', 'Rokky', 'Santaz', 'Serpantin', 'Sirain', 'Wanwan', 'Winks'));
+
+ALTER TABLE dinoparc_dinoz_profiles
+  ALTER COLUMN race TYPE dinoparc_dinoz_race;
+
+ALTER DOMAIN dinoparc_user_id DROP CONSTRAINT dinoparc_user_id_check;
+ALTER DOMAIN dinoparc_user_id ADD CONSTRAINT dinoparc_user_id_check CHECK (value ~ '^(?:0|[1-9]\d{0,9})$');
+
+ALTER DOMAIN dinoparc_dinoz_id DROP CONSTRAINT dinoparc_dinoz_id_check;
+ALTER DOMAIN dinoparc_dinoz_id ADD CONSTRAINT dinoparc_dinoz_id_check CHECK (value ~ '^(?:0|[1-9]\d{0,9})$');
+
+-- Time-variant game bills <any(exchange)>
+CREATE TABLE dinoparc_bills (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+--
+  bills U32 NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_user_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_user_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_bills__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Migration step: 019 -> 020
+ALTER TABLE dinoparc_epic_reward_set_items
+  ALTER COLUMN dinoparc_epic_reward_key TYPE VARCHAR(30);
+
+DROP DOMAIN dinoparc_epic_reward_key;
+CREATE DOMAIN dinoparc_epic_reward_key AS VARCHAR(30) CHECK (value ~ '^[a-z0-9_]{1,30}$');
+
+ALTER TABLE dinoparc_epic_reward_set_items
+  ALTER COLUMN dinoparc_epic_reward_key TYPE dinoparc_epic_reward_key;
+
+-- Migration step: 020 -> 021
+ALTER TABLE dinoparc_dinoz_names
+  ALTER COLUMN name DROP NOT NULL;
+
+CREATE TABLE dinoparc_dinoz_skins (
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_dinoz_id DINOPARC_DINOZ_ID NOT NULL,
+--
+  race DINOPARC_DINOZ_RACE NOT NULL,
+  skin DINOPARC_DINOZ_SKIN NOT NULL,
+  PRIMARY KEY (period, dinoparc_server, dinoparc_dinoz_id),
+  EXCLUDE USING gist (dinoparc_server WITH =, dinoparc_dinoz_id WITH =, period WITH &&),
+  CONSTRAINT dinoparc_dinoz_profiles__dinoz__fk FOREIGN KEY (dinoparc_server, dinoparc_dinoz_id) REFERENCES dinoparc_dinoz(dinoparc_server, dinoparc_dinoz_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+INSERT INTO dinoparc_dinoz_skins(period, retrieved_at, dinoparc_server, dinoparc_dinoz_id, race, skin) SELECT period, retrieved_at, dinoparc_server, dinoparc_dinoz_id, race, skin FROM dinoparc_dinoz_profiles;
+
+ALTER TABLE dinoparc_dinoz_profiles
+  DROP COLUMN race,
+  DROP COLUMN skin;
+
+-- Migration step: 021 -> 022
+CREATE DOMAIN oauth_client_id AS UUID;
+CREATE DOMAIN session_id AS UUID;
+CREATE DOMAIN oauth_client_key AS VARCHAR(40) CHECK (value ~ '^[a-z_][a-z0-9_]{1,31}@clients$');
+
+-- Migration step: 022 -> 023
+CREATE DOMAIN etwin_oauth_access_token_id AS UUID;
+
+-- Migration step: 023 -> 024
+CREATE DOMAIN forum_section_id AS UUID;
+CREATE DOMAIN forum_section_key AS VARCHAR(32) CHECK (value ~ '^[a-z_][a-z0-9_]{1,31}$');
+CREATE DOMAIN forum_section_display_name AS VARCHAR(64);
+
+-- Migration step: 024 -> 025
+CREATE TYPE forum_role_grant_by_section AS (
+  user_id USER_ID,
+  start_time INSTANT,
+  granted_by USER_ID
+);
+
+CREATE VIEW forum_section_meta AS
+WITH
+  thread_count AS (
+    SELECT forum_section_id, COUNT(*)::U32 AS thread_count FROM forum_threads GROUP BY forum_section_id
+  ),
+  role_grants AS (
+    SELECT forum_section_id,
+      ARRAY_AGG(ROW (user_id, start_time, granted_by)::forum_role_grant_by_section) AS role_grants
+    FROM forum_role_grants
+    GROUP BY forum_section_id
+  )
+SELECT forum_section_id, key, ctime, display_name, locale, COALESCE(thread_count, 0) AS thread_count,
+  COALESCE(role_grants, '{}') AS role_grants
+FROM forum_sections
+  LEFT OUTER JOIN thread_count USING (forum_section_id)
+  LEFT OUTER JOIN role_grants USING (forum_section_id);
+
+CREATE VIEW forum_thread_meta AS
+WITH
+  post_count AS (
+    SELECT forum_thread_id, COUNT(*)::U32 AS post_count FROM forum_posts GROUP BY forum_thread_id
+  )
+SELECT forum_thread_id, key, ctime, title, title_mtime, forum_section_id, is_pinned, is_locked, COALESCE(post_count, 0) AS post_count
+FROM forum_threads
+  LEFT OUTER JOIN post_count USING (forum_thread_id);
+
+CREATE DOMAIN forum_thread_title AS VARCHAR(64);
+CREATE DOMAIN forum_post_id AS UUID;
+CREATE DOMAIN forum_post_revision_id AS UUID;
+
+-- Migration step: 025 -> 026
+CREATE DOMAIN forum_thread_key AS VARCHAR(32) CHECK (value ~ '^[a-z_][a-z0-9_]{1,31}$');
+
+-- Migration step: 026 -> 027
+CREATE DOMAIN totp_secret_enc AS BYTEA;
+
+-- TOTP second factor of a user (at most one active secret per user)
+CREATE TABLE user_totp(
+  user_id USER_ID NOT NULL,
+  secret TOTP_SECRET_ENC NOT NULL,
+  enabled_at INSTANT NOT NULL,
+  -- Last accepted RFC 6238 time step, used to reject replayed codes
+  last_used_step INT8 NULL,
+  PRIMARY KEY (user_id),
+  CHECK (last_used_step >= 0),
+  CONSTRAINT user_totp__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Unused recovery codes, allowing to pass the second factor without the authenticator
+CREATE TABLE user_recovery_codes(
+  user_id USER_ID NOT NULL,
+  code PASSWORD_HASH NOT NULL,
+  PRIMARY KEY (user_id, code),
+  CONSTRAINT user_recovery_code__user_totp__fk FOREIGN KEY (user_id) REFERENCES user_totp(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Migration step: 027 -> 028
+CREATE DOMAIN login_failure_reason AS VARCHAR(20) CHECK (value IN ('UnknownLogin', 'NoPassword', 'WrongPassword'));
+CREATE DOMAIN login_throttle_key AS VARCHAR(100);
+
+-- Audit log of failed password checks, also used to count attempts in the throttling window
+CREATE TABLE login_failures(
+  time INSTANT NOT NULL,
+  user_id USER_ID NULL,
+  oauth_client_id OAUTH_CLIENT_ID NULL,
+  ip INET NULL,
+  reason LOGIN_FAILURE_REASON NOT NULL,
+  CONSTRAINT login_failure__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT login_failure__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX login_failure__user__idx ON login_failures(user_id, time);
+CREATE INDEX login_failure__oauth_client__idx ON login_failures(oauth_client_id, time);
+CREATE INDEX login_failure__ip__idx ON login_failures(ip, time);
+
+-- Temporary lockouts, keyed by the string representation of the throttled subject (e.g. `user:<uuid>`, `ip:<addr>`)
+CREATE TABLE login_lockouts(
+  key LOGIN_THROTTLE_KEY NOT NULL,
+  until INSTANT NOT NULL,
+  PRIMARY KEY (key)
+);
+
+-- Migration step: 028 -> 029
+CREATE DOMAIN twinoid_site_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN twinoid_stat_key AS VARCHAR(100);
+CREATE DOMAIN twinoid_achievement_key AS VARCHAR(100);
+
+-- Twinoid sites (games), as seen from user profiles
+CREATE TABLE twinoid_sites(
+  twinoid_site_id TWINOID_SITE_ID PRIMARY KEY NOT NULL,
+  archived_at INSTANT NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  host VARCHAR(100) NOT NULL
+);
+
+-- Latest archived Twinoid profile of each user
+CREATE TABLE twinoid_user_profiles(
+  twinoid_user_id TWINOID_USER_ID PRIMARY KEY NOT NULL,
+  archived_at INSTANT NOT NULL,
+  title TEXT NULL,
+  locale VARCHAR(10) NULL,
+  CONSTRAINT twinoid_user_profile__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Sites present on a Twinoid profile
+CREATE TABLE twinoid_site_users(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  -- User id on the site itself, if known
+  real_id U32 NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id),
+  CONSTRAINT twinoid_site_user__twinoid_user_profile__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_user_profiles(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT twinoid_site_user__twinoid_site__fk FOREIGN KEY (twinoid_site_id) REFERENCES twinoid_sites(twinoid_site_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_stats(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, stat_key),
+  CONSTRAINT twinoid_site_user_stat__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_achievements(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  achievement_key TWINOID_ACHIEVEMENT_KEY NOT NULL,
+  name TEXT NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  points I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, achievement_key),
+  CONSTRAINT twinoid_site_user_achievement__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
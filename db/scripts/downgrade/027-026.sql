@@ -0,0 +1,4 @@
+DROP TABLE user_recovery_codes;
+DROP TABLE user_totp;
+
+DROP DOMAIN totp_secret_enc;
@@ -0,0 +1,5 @@
+DROP TABLE login_lockouts;
+DROP TABLE login_failures;
+
+DROP DOMAIN login_throttle_key;
+DROP DOMAIN login_failure_reason;
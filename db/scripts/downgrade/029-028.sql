@@ -0,0 +1,9 @@
+DROP TABLE twinoid_site_user_achievements;
+DROP TABLE twinoid_site_user_stats;
+DROP TABLE twinoid_site_users;
+DROP TABLE twinoid_user_profiles;
+DROP TABLE twinoid_sites;
+
+DROP DOMAIN twinoid_achievement_key;
+DROP DOMAIN twinoid_stat_key;
+DROP DOMAIN twinoid_site_id;
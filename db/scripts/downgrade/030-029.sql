@@ -0,0 +1,4 @@
+DROP TABLE outbound_emails;
+
+DROP DOMAIN outbound_email_status;
+DROP DOMAIN outbound_email_id;
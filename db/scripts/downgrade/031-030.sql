@@ -0,0 +1,7 @@
+DROP TABLE mfa_login_challenges;
+
+DELETE FROM login_failures WHERE reason = 'WrongSecondFactor';
+ALTER DOMAIN login_failure_reason DROP CONSTRAINT login_failure_reason_check;
+ALTER DOMAIN login_failure_reason ADD CONSTRAINT login_failure_reason_check CHECK (value IN ('UnknownLogin', 'NoPassword', 'WrongPassword'));
+
+DROP DOMAIN mfa_login_challenge_id;
@@ -0,0 +1,75 @@
+-- Only the current snapshot of each profile is kept
+CREATE TEMPORARY TABLE _twinoid_user_profiles AS
+  SELECT p.twinoid_user_id, p.retrieved_at[cardinality(p.retrieved_at)] AS archived_at, s.title, s.locale, p.snapshot
+  FROM twinoid_user_profiles AS p
+    INNER JOIN twinoid_user_profile_snapshots AS s ON (s.twinoid_user_profile_snapshot_id = p.snapshot)
+  WHERE upper_inf(p.period);
+CREATE TEMPORARY TABLE _twinoid_site_users AS SELECT * FROM twinoid_site_users;
+CREATE TEMPORARY TABLE _twinoid_site_user_stats AS SELECT * FROM twinoid_site_user_stats;
+CREATE TEMPORARY TABLE _twinoid_site_user_achievements AS SELECT * FROM twinoid_site_user_achievements;
+
+DROP TABLE twinoid_site_user_achievements;
+DROP TABLE twinoid_site_user_stats;
+DROP TABLE twinoid_site_users;
+DROP TABLE twinoid_user_profiles;
+DROP TABLE twinoid_user_profile_snapshots;
+
+-- Latest archived Twinoid profile of each user
+CREATE TABLE twinoid_user_profiles(
+  twinoid_user_id TWINOID_USER_ID PRIMARY KEY NOT NULL,
+  archived_at INSTANT NOT NULL,
+  title TEXT NULL,
+  locale VARCHAR(10) NULL,
+  CONSTRAINT twinoid_user_profile__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Sites present on a Twinoid profile
+CREATE TABLE twinoid_site_users(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  -- User id on the site itself, if known
+  real_id U32 NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id),
+  CONSTRAINT twinoid_site_user__twinoid_user_profile__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_user_profiles(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT twinoid_site_user__twinoid_site__fk FOREIGN KEY (twinoid_site_id) REFERENCES twinoid_sites(twinoid_site_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_stats(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, stat_key),
+  CONSTRAINT twinoid_site_user_stat__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_achievements(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  achievement_key TWINOID_ACHIEVEMENT_KEY NOT NULL,
+  name TEXT NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  points I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, achievement_key),
+  CONSTRAINT twinoid_site_user_achievement__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+INSERT INTO twinoid_user_profiles(twinoid_user_id, archived_at, title, locale)
+  SELECT twinoid_user_id, archived_at, title, locale FROM _twinoid_user_profiles;
+INSERT INTO twinoid_site_users(twinoid_user_id, twinoid_site_id, real_id)
+  SELECT p.twinoid_user_id, su.twinoid_site_id, su.real_id
+  FROM _twinoid_site_users AS su INNER JOIN _twinoid_user_profiles AS p ON (p.snapshot = su.twinoid_user_profile_snapshot_id);
+INSERT INTO twinoid_site_user_stats(twinoid_user_id, twinoid_site_id, stat_key, score)
+  SELECT p.twinoid_user_id, s.twinoid_site_id, s.stat_key, s.score
+  FROM _twinoid_site_user_stats AS s INNER JOIN _twinoid_user_profiles AS p ON (p.snapshot = s.twinoid_user_profile_snapshot_id);
+INSERT INTO twinoid_site_user_achievements(twinoid_user_id, twinoid_site_id, achievement_key, name, stat_key, score, points)
+  SELECT p.twinoid_user_id, a.twinoid_site_id, a.achievement_key, a.name, a.stat_key, a.score, a.points
+  FROM _twinoid_site_user_achievements AS a INNER JOIN _twinoid_user_profiles AS p ON (p.snapshot = a.twinoid_user_profile_snapshot_id);
+
+DROP TABLE _twinoid_site_user_achievements;
+DROP TABLE _twinoid_site_user_stats;
+DROP TABLE _twinoid_site_users;
+DROP TABLE _twinoid_user_profiles;
+
+DROP DOMAIN twinoid_user_profile_snapshot_id;
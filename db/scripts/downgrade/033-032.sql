@@ -0,0 +1,2 @@
+DROP TABLE dinoparc_user_refresh_attempts;
+DROP TABLE hammerfest_user_refresh_attempts;
@@ -0,0 +1,21 @@
+CREATE DOMAIN totp_secret_enc AS BYTEA;
+
+-- TOTP second factor of a user (at most one active secret per user)
+CREATE TABLE user_totp(
+  user_id USER_ID NOT NULL,
+  secret TOTP_SECRET_ENC NOT NULL,
+  enabled_at INSTANT NOT NULL,
+  -- Last accepted RFC 6238 time step, used to reject replayed codes
+  last_used_step INT8 NULL,
+  PRIMARY KEY (user_id),
+  CHECK (last_used_step >= 0),
+  CONSTRAINT user_totp__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Unused recovery codes, allowing to pass the second factor without the authenticator
+CREATE TABLE user_recovery_codes(
+  user_id USER_ID NOT NULL,
+  code PASSWORD_HASH NOT NULL,
+  PRIMARY KEY (user_id, code),
+  CONSTRAINT user_recovery_code__user_totp__fk FOREIGN KEY (user_id) REFERENCES user_totp(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,24 @@
+CREATE DOMAIN login_failure_reason AS VARCHAR(20) CHECK (value IN ('UnknownLogin', 'NoPassword', 'WrongPassword'));
+CREATE DOMAIN login_throttle_key AS VARCHAR(100);
+
+-- Audit log of failed password checks, also used to count attempts in the throttling window
+CREATE TABLE login_failures(
+  time INSTANT NOT NULL,
+  user_id USER_ID NULL,
+  oauth_client_id OAUTH_CLIENT_ID NULL,
+  ip INET NULL,
+  reason LOGIN_FAILURE_REASON NOT NULL,
+  CONSTRAINT login_failure__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT login_failure__oauth_client__fk FOREIGN KEY (oauth_client_id) REFERENCES oauth_clients(oauth_client_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX login_failure__user__idx ON login_failures(user_id, time);
+CREATE INDEX login_failure__oauth_client__idx ON login_failures(oauth_client_id, time);
+CREATE INDEX login_failure__ip__idx ON login_failures(ip, time);
+
+-- Temporary lockouts, keyed by the string representation of the throttled subject (e.g. `user:<uuid>`, `ip:<addr>`)
+CREATE TABLE login_lockouts(
+  key LOGIN_THROTTLE_KEY NOT NULL,
+  until INSTANT NOT NULL,
+  PRIMARY KEY (key)
+);
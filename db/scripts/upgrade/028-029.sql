@@ -0,0 +1,52 @@
+CREATE DOMAIN twinoid_site_id AS VARCHAR(10) CHECK (value ~ '^[1-9]\d{0,9}$');
+CREATE DOMAIN twinoid_stat_key AS VARCHAR(100);
+CREATE DOMAIN twinoid_achievement_key AS VARCHAR(100);
+
+-- Twinoid sites (games), as seen from user profiles
+CREATE TABLE twinoid_sites(
+  twinoid_site_id TWINOID_SITE_ID PRIMARY KEY NOT NULL,
+  archived_at INSTANT NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  host VARCHAR(100) NOT NULL
+);
+
+-- Latest archived Twinoid profile of each user
+CREATE TABLE twinoid_user_profiles(
+  twinoid_user_id TWINOID_USER_ID PRIMARY KEY NOT NULL,
+  archived_at INSTANT NOT NULL,
+  title TEXT NULL,
+  locale VARCHAR(10) NULL,
+  CONSTRAINT twinoid_user_profile__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Sites present on a Twinoid profile
+CREATE TABLE twinoid_site_users(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  -- User id on the site itself, if known
+  real_id U32 NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id),
+  CONSTRAINT twinoid_site_user__twinoid_user_profile__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_user_profiles(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT twinoid_site_user__twinoid_site__fk FOREIGN KEY (twinoid_site_id) REFERENCES twinoid_sites(twinoid_site_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_stats(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, stat_key),
+  CONSTRAINT twinoid_site_user_stat__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_achievements(
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  achievement_key TWINOID_ACHIEVEMENT_KEY NOT NULL,
+  name TEXT NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  points I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_id, twinoid_site_id, achievement_key),
+  CONSTRAINT twinoid_site_user_achievement__twinoid_site_user__fk FOREIGN KEY (twinoid_user_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,23 @@
+CREATE DOMAIN outbound_email_id AS UUID;
+CREATE DOMAIN outbound_email_status AS VARCHAR(20) CHECK (value IN ('Pending', 'Sent', 'Failed'));
+
+-- Outbox of emails waiting for delivery, kept once delivered or failed to record their status
+CREATE TABLE outbound_emails(
+  outbound_email_id OUTBOUND_EMAIL_ID PRIMARY KEY NOT NULL,
+  -- Encrypted recipient address
+  recipient BYTEA NOT NULL,
+  title TEXT NOT NULL,
+  -- Encrypted bodies: they may contain secret tokens
+  body_text BYTEA NOT NULL,
+  body_html BYTEA NULL,
+  ctime INSTANT NOT NULL,
+  status OUTBOUND_EMAIL_STATUS NOT NULL,
+  -- Number of failed delivery attempts
+  attempts U32 NOT NULL,
+  next_attempt_at INSTANT NOT NULL,
+  sent_at INSTANT NULL,
+  last_error TEXT NULL,
+  CHECK ((status = 'Sent') = (sent_at IS NOT NULL))
+);
+
+CREATE INDEX outbound_email__pending__idx ON outbound_emails(next_attempt_at) WHERE status = 'Pending';
@@ -0,0 +1,16 @@
+CREATE DOMAIN mfa_login_challenge_id AS UUID;
+
+ALTER DOMAIN login_failure_reason DROP CONSTRAINT login_failure_reason_check;
+ALTER DOMAIN login_failure_reason ADD CONSTRAINT login_failure_reason_check CHECK (value IN ('UnknownLogin', 'NoPassword', 'WrongPassword', 'WrongSecondFactor'));
+
+-- Pending second login steps: a challenge is consumed by its first successful proof
+CREATE TABLE mfa_login_challenges(
+  mfa_login_challenge_id MFA_LOGIN_CHALLENGE_ID PRIMARY KEY NOT NULL,
+  user_id USER_ID NOT NULL,
+  ctime INSTANT NOT NULL,
+  expires_at INSTANT NOT NULL,
+  -- Number of proofs submitted for this challenge
+  attempts U32 NOT NULL,
+  consumed_at INSTANT NULL,
+  CONSTRAINT mfa_login_challenge__user__fk FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
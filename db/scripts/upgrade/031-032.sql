@@ -0,0 +1,85 @@
+CREATE DOMAIN twinoid_user_profile_snapshot_id AS UUID;
+
+-- Keep the current profiles while the tables are rebuilt: each one becomes the first snapshot of its user
+CREATE TEMPORARY TABLE _twinoid_user_profiles AS
+  SELECT twinoid_user_id, archived_at, title, locale, gen_random_uuid() AS snapshot FROM twinoid_user_profiles;
+CREATE TEMPORARY TABLE _twinoid_site_users AS SELECT * FROM twinoid_site_users;
+CREATE TEMPORARY TABLE _twinoid_site_user_stats AS SELECT * FROM twinoid_site_user_stats;
+CREATE TEMPORARY TABLE _twinoid_site_user_achievements AS SELECT * FROM twinoid_site_user_achievements;
+
+DROP TABLE twinoid_site_user_achievements;
+DROP TABLE twinoid_site_user_stats;
+DROP TABLE twinoid_site_users;
+DROP TABLE twinoid_user_profiles;
+
+-- Immutable content of an archived Twinoid profile
+CREATE TABLE twinoid_user_profile_snapshots(
+  twinoid_user_profile_snapshot_id TWINOID_USER_PROFILE_SNAPSHOT_ID PRIMARY KEY NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  title TEXT NULL,
+  locale VARCHAR(10) NULL,
+  CONSTRAINT twinoid_user_profile_snapshot__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Time-variant data for Twinoid profiles: a new period starts when the content of the profile changes
+CREATE TABLE twinoid_user_profiles(
+  period PERIOD_LOWER NOT NULL,
+  retrieved_at INSTANT_SET NOT NULL,
+  twinoid_user_id TWINOID_USER_ID NOT NULL,
+  snapshot TWINOID_USER_PROFILE_SNAPSHOT_ID NOT NULL,
+  PRIMARY KEY (period, twinoid_user_id),
+  EXCLUDE USING gist (twinoid_user_id WITH =, period WITH &&),
+  CONSTRAINT twinoid_user_profile__twinoid_user__fk FOREIGN KEY (twinoid_user_id) REFERENCES twinoid_users(twinoid_user_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT twinoid_user_profile__snapshot__fk FOREIGN KEY (snapshot) REFERENCES twinoid_user_profile_snapshots(twinoid_user_profile_snapshot_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Sites present on a Twinoid profile snapshot
+CREATE TABLE twinoid_site_users(
+  twinoid_user_profile_snapshot_id TWINOID_USER_PROFILE_SNAPSHOT_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  -- User id on the site itself, if known
+  real_id U32 NULL,
+  PRIMARY KEY (twinoid_user_profile_snapshot_id, twinoid_site_id),
+  CONSTRAINT twinoid_site_user__snapshot__fk FOREIGN KEY (twinoid_user_profile_snapshot_id) REFERENCES twinoid_user_profile_snapshots(twinoid_user_profile_snapshot_id) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT twinoid_site_user__twinoid_site__fk FOREIGN KEY (twinoid_site_id) REFERENCES twinoid_sites(twinoid_site_id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_stats(
+  twinoid_user_profile_snapshot_id TWINOID_USER_PROFILE_SNAPSHOT_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_profile_snapshot_id, twinoid_site_id, stat_key),
+  CONSTRAINT twinoid_site_user_stat__twinoid_site_user__fk FOREIGN KEY (twinoid_user_profile_snapshot_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_profile_snapshot_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE twinoid_site_user_achievements(
+  twinoid_user_profile_snapshot_id TWINOID_USER_PROFILE_SNAPSHOT_ID NOT NULL,
+  twinoid_site_id TWINOID_SITE_ID NOT NULL,
+  achievement_key TWINOID_ACHIEVEMENT_KEY NOT NULL,
+  name TEXT NOT NULL,
+  stat_key TWINOID_STAT_KEY NOT NULL,
+  score I64 NOT NULL,
+  points I64 NOT NULL,
+  PRIMARY KEY (twinoid_user_profile_snapshot_id, twinoid_site_id, achievement_key),
+  CONSTRAINT twinoid_site_user_achievement__twinoid_site_user__fk FOREIGN KEY (twinoid_user_profile_snapshot_id, twinoid_site_id) REFERENCES twinoid_site_users(twinoid_user_profile_snapshot_id, twinoid_site_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+INSERT INTO twinoid_user_profile_snapshots(twinoid_user_profile_snapshot_id, twinoid_user_id, title, locale)
+  SELECT snapshot, twinoid_user_id, title, locale FROM _twinoid_user_profiles;
+INSERT INTO twinoid_user_profiles(period, retrieved_at, twinoid_user_id, snapshot)
+  SELECT PERIOD(archived_at, NULL), ARRAY[archived_at], twinoid_user_id, snapshot FROM _twinoid_user_profiles;
+INSERT INTO twinoid_site_users(twinoid_user_profile_snapshot_id, twinoid_site_id, real_id)
+  SELECT p.snapshot, su.twinoid_site_id, su.real_id
+  FROM _twinoid_site_users AS su INNER JOIN _twinoid_user_profiles AS p USING (twinoid_user_id);
+INSERT INTO twinoid_site_user_stats(twinoid_user_profile_snapshot_id, twinoid_site_id, stat_key, score)
+  SELECT p.snapshot, s.twinoid_site_id, s.stat_key, s.score
+  FROM _twinoid_site_user_stats AS s INNER JOIN _twinoid_user_profiles AS p USING (twinoid_user_id);
+INSERT INTO twinoid_site_user_achievements(twinoid_user_profile_snapshot_id, twinoid_site_id, achievement_key, name, stat_key, score, points)
+  SELECT p.snapshot, a.twinoid_site_id, a.achievement_key, a.name, a.stat_key, a.score, a.points
+  FROM _twinoid_site_user_achievements AS a INNER JOIN _twinoid_user_profiles AS p USING (twinoid_user_id);
+
+DROP TABLE _twinoid_site_user_achievements;
+DROP TABLE _twinoid_site_user_stats;
+DROP TABLE _twinoid_site_users;
+DROP TABLE _twinoid_user_profiles;
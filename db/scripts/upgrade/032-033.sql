@@ -0,0 +1,17 @@
+-- Latest attempt to refresh the archived profile of a Hammerfest user, successful or not
+CREATE TABLE hammerfest_user_refresh_attempts (
+  hammerfest_server HAMMERFEST_SERVER NOT NULL,
+  hammerfest_user_id HAMMERFEST_USER_ID NOT NULL,
+  attempted_at INSTANT NOT NULL,
+  PRIMARY KEY (hammerfest_server, hammerfest_user_id),
+  CONSTRAINT hammerfest_user_refresh_attempt__user__fk FOREIGN KEY (hammerfest_server, hammerfest_user_id) REFERENCES hammerfest_users(hammerfest_server, hammerfest_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Latest attempt to refresh the archived data of a Dinoparc user, successful or not
+CREATE TABLE dinoparc_user_refresh_attempts (
+  dinoparc_server DINOPARC_SERVER NOT NULL,
+  dinoparc_user_id DINOPARC_USER_ID NOT NULL,
+  attempted_at INSTANT NOT NULL,
+  PRIMARY KEY (dinoparc_server, dinoparc_user_id),
+  CONSTRAINT dinoparc_user_refresh_attempt__user__fk FOREIGN KEY (dinoparc_server, dinoparc_user_id) REFERENCES dinoparc_users(dinoparc_server, dinoparc_user_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
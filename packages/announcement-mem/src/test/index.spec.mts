@@ -15,6 +15,7 @@ import { MemOauthProviderStore } from "@eternal-twin/native/oauth-provider-store
 import { ScryptPasswordService } from "@eternal-twin/native/password";
 import { NativeAuthService } from "@eternal-twin/native/services/auth";
 import { HttpTwinoidClient } from "@eternal-twin/native/twinoid-client";
+import { MemTokenStore } from "@eternal-twin/native/token-store";
 import { MemTwinoidStore } from "@eternal-twin/native/twinoid-store";
 import { MemUserStore } from "@eternal-twin/native/user-store";
 import { Uuid4Generator } from "@eternal-twin/native/uuid";
@@ -35,6 +36,7 @@ async function withMemAnnouncementService<R>(fn: (api: Api) => Promise<R>): Prom
   const dinoparcStore = new MemDinoparcStore({clock});
   const hammerfestStore = new MemHammerfestStore({clock});
   const twinoidStore = new MemTwinoidStore({clock});
+  const tokenStore = new MemTokenStore({clock});
   const dinoparcClient = new MemDinoparcClient({clock});
   const hammerfestClient = new MemHammerfestClient({clock});
   const twinoidClient = new HttpTwinoidClient({clock});
@@ -43,7 +45,7 @@ async function withMemAnnouncementService<R>(fn: (api: Api) => Promise<R>): Prom
   const oauthProviderStore = await MemOauthProviderStore.create({clock, passwordService, uuidGenerator});
   const authStore = await MemAuthStore.create({clock, uuidGenerator});
 
-  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
 
   const forumConfig: ForumConfig = {postsPerPage: 10, threadsPerPage: 20};
   const forum = new InMemoryForumService(uuidGenerator, userStore, forumConfig);
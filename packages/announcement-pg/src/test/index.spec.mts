@@ -17,6 +17,7 @@ import { PgOauthProviderStore } from "@eternal-twin/native/oauth-provider-store"
 import { ScryptPasswordService } from "@eternal-twin/native/password";
 import { NativeAuthService } from "@eternal-twin/native/services/auth";
 import { HttpTwinoidClient } from "@eternal-twin/native/twinoid-client";
+import { PgTokenStore } from "@eternal-twin/native/token-store";
 import { PgTwinoidStore } from "@eternal-twin/native/twinoid-store";
 import { PgUserStore } from "@eternal-twin/native/user-store";
 import { Uuid4Generator } from "@eternal-twin/native/uuid";
@@ -61,7 +62,8 @@ async function withPgAnnouncementService<R>(fn: (api: Api) => Promise<R>): Promi
     const userStore = new PgUserStore({clock, database:  nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
     const dinoparcStore = await PgDinoparcStore.create({clock, database: nativeDatabase, uuidGenerator});
     const hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
-    const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+    const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
+    const tokenStore = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
     const linkStore = new PgLinkStore({clock, database: nativeDatabase});
     const dinoparcClient = new MemDinoparcClient({clock});
     const hammerfestClient = new MemHammerfestClient({clock});
@@ -69,7 +71,7 @@ async function withPgAnnouncementService<R>(fn: (api: Api) => Promise<R>): Promi
     const oauthProviderStore = await PgOauthProviderStore.create({clock, database: nativeDatabase, passwordService, uuidGenerator, secret: secretKeyStr});
     const authStore = await PgAuthStore.create({clock, database: nativeDatabase, uuidGenerator, secret: secretKeyStr});
 
-    const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+    const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
 
     const forumConfig: ForumConfig = {postsPerPage: 10, threadsPerPage: 20};
     const forum = new PgForumService(database, uuidGenerator, userStore, forumConfig);
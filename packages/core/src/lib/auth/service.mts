@@ -19,7 +19,6 @@ import { EtwinOauthAccessTokenKey } from "../oauth/etwin-oauth-access-token-key.
 import { GrantOauthAuthorizationOptions } from "../oauth/grant-oauth-authorization-options.mjs";
 import { OauthAccessToken } from "../oauth/oauth-access-token.mjs";
 import { OauthProviderService } from "../oauth/provider-service.mjs";
-import { RfcOauthAccessTokenKey } from "../oauth/rfc-oauth-access-token-key.mjs";
 import { PasswordService } from "../password/service.mjs";
 import { TwinoidClient } from "../twinoid/client.mjs";
 import { TwinoidStore } from "../twinoid/store.mjs";
@@ -103,7 +102,7 @@ export interface AuthService {
    *
    * Automatically creates an etwin user if the tid user isn't linked to any user yet.
    */
-  registerOrLoginWithTwinoidOauth(acx: AuthContext, accessToken: RfcOauthAccessTokenKey): Promise<UserAndSession>;
+  registerOrLoginWithTwinoidOauth(acx: AuthContext, accessToken: OauthAccessToken): Promise<UserAndSession>;
 
   /**
    * Authenticate a user or Oauth client using its credentials (basic oauth scheme)
@@ -10,6 +10,9 @@ export interface Config {
   clients: Map<string, ClientConfig>
   auth: AuthConfig;
   forum: ForumConfig;
+  password: PasswordConfig | null;
+  log: LogConfig | null;
+  mailer: MailerConfig | null;
 }
 
 export enum ApiType {
@@ -73,6 +76,75 @@ export interface ForumSectionConfig {
   locale: "en-US" | "eo" | "es-SP" | "fr-FR" | null;
 }
 
+/**
+ * Argon2id cost parameters used to hash new passwords.
+ */
+export interface PasswordConfig {
+  /**
+   * Memory cost, in KiB
+   */
+  argon2MemoryCost: number;
+
+  /**
+   * Number of passes
+   */
+  argon2TimeCost: number;
+
+  /**
+   * Degree of parallelism
+   */
+  argon2Parallelism: number;
+}
+
+/**
+ * Log receiving the events of the services, as JSON lines.
+ */
+export type LogConfig = StdoutLogConfig | FileLogConfig;
+
+export interface StdoutLogConfig {
+  type: "stdout";
+}
+
+export interface FileLogConfig {
+  type: "file";
+
+  /**
+   * Log file, relative to the working directory
+   */
+  path: string;
+
+  /**
+   * Size limit of the file, in bytes
+   */
+  maxSize: number | null;
+
+  /**
+   * Age limit of the file, in hours
+   */
+  maxAge: number | null;
+}
+
+/**
+ * Mailer used to deliver the emails, selected by the `type` field.
+ *
+ * The Node servers only support SMTP relays: the `file` mailer is only available in the Rust server.
+ */
+export type MailerConfig = SmtpMailerConfig;
+
+export interface SmtpMailerConfig {
+  type: "smtp";
+  host: string;
+  username: string;
+  password: string;
+  sender: string;
+  headers: MailerHeader[];
+}
+
+export interface MailerHeader {
+  name: string;
+  value: string;
+}
+
 function parseConfig(input: string): Config {
   const raw: unknown = toml.parse(input);
   return readConfig(raw);
@@ -98,7 +170,13 @@ function readConfig(raw: unknown): Config {
   const auth: AuthConfig = readAuthConfig(rawAuth);
   const rawForum: object = readObj(raw, "forum", "forum");
   const forum: ForumConfig = readForumConfig(rawForum);
-  return {etwin, db, clients, auth, forum};
+  const rawPassword: object | null = readOptObj(raw, "password", "password");
+  const password: PasswordConfig | null = rawPassword !== null ? readPasswordConfig(rawPassword) : null;
+  const rawLog: object | null = readOptObj(raw, "log", "log");
+  const log: LogConfig | null = rawLog !== null ? readLogConfig(rawLog) : null;
+  const rawMailer: object | null = readOptObj(raw, "mailer", "mailer");
+  const mailer: MailerConfig | null = rawMailer !== null ? readMailerConfig(rawMailer) : null;
+  return {etwin, db, clients, auth, forum, password, log, mailer};
 }
 
 function readEtwinConfig(raw: object): EtwinConfig {
@@ -171,6 +249,61 @@ function readForumConfig(raw: object): ForumConfig {
   return {postsPerPage, threadsPerPage, sections};
 }
 
+function readPasswordConfig(raw: object): PasswordConfig {
+  const argon2MemoryCost: number = readUint(raw, "argon2_memory_cost", "password.argon2_memory_cost");
+  const argon2TimeCost: number = readUint(raw, "argon2_time_cost", "password.argon2_time_cost");
+  const argon2Parallelism: number = readUint(raw, "argon2_parallelism", "password.argon2_parallelism");
+  return {argon2MemoryCost, argon2TimeCost, argon2Parallelism};
+}
+
+function readLogConfig(raw: object): LogConfig {
+  const type: string = readString(raw, "type", "log.type");
+  switch (type) {
+    case "stdout":
+      return {type: "stdout"};
+    case "file": {
+      const path: string = readString(raw, "path", "log.path");
+      const maxSize: number | null = readOptUint(raw, "max_size", "log.max_size");
+      const maxAge: number | null = readOptUint(raw, "max_age", "log.max_age");
+      return {type: "file", path, maxSize, maxAge};
+    }
+    default:
+      throw new Error("Invalid log type, expected \"stdout\" or \"file\"");
+  }
+}
+
+function readMailerConfig(raw: object): MailerConfig {
+  const type: string = readString(raw, "type", "mailer.type");
+  switch (type) {
+    case "smtp": {
+      const host: string = readString(raw, "host", "mailer.host");
+      const username: string = readString(raw, "username", "mailer.username");
+      const password: string = readString(raw, "password", "mailer.password");
+      const sender: string = readString(raw, "sender", "mailer.sender");
+      const headers: MailerHeader[] = [];
+      if (Reflect.has(raw, "headers")) {
+        const rawHeaders: unknown = Reflect.get(raw, "headers");
+        if (!Array.isArray(rawHeaders)) {
+          throw new Error("Invalid config type, expected array: mailer.headers");
+        }
+        for (const [i, rawHeader] of rawHeaders.entries()) {
+          if (typeof rawHeader !== "object" || rawHeader === null) {
+            throw new Error(`Invalid config type, expected object: mailer.headers.${i}`);
+          }
+          const name: string = readString(rawHeader, "name", `mailer.headers.${i}.name`);
+          const value: string = readString(rawHeader, "value", `mailer.headers.${i}.value`);
+          headers.push({name, value});
+        }
+      }
+      return {type: "smtp", host, username, password, sender, headers};
+    }
+    case "file":
+      throw new Error("Unsupported mailer type \"file\": the Node servers only support \"smtp\"");
+    default:
+      throw new Error("Invalid mailer type, expected \"smtp\" or \"file\"");
+  }
+}
+
 const supportedLocales: ReadonlySet<"en-US" | "eo" | "es-SP" | "fr-FR"> = new Set(["en-US", "eo", "es-SP", "fr-FR"]);
 
 function readForumSectionConfig(raw: unknown, prefix: string): ForumSectionConfig {
@@ -240,6 +373,13 @@ function readUint(rawObj: object, key: string, fullKey: string): number {
   return value;
 }
 
+function readOptUint(rawObj: object, key: string, fullKey: string): number | null {
+  if (!Reflect.has(rawObj, key)) {
+    return null;
+  }
+  return readUint(rawObj, key, fullKey);
+}
+
 export async function getLocalConfig(): Promise<Config> {
   const cwd: string = process.cwd();
   const configPath: string | undefined = await findUp("etwin.toml", {cwd});
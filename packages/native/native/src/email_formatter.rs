@@ -38,6 +38,7 @@ pub mod html {
   use etwin_email_formatter::html::HtmlEmailFormatter;
   use neon::prelude::*;
   use std::sync::Arc;
+  use url::Url;
 
   pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
     let ns = cx.empty_object();
@@ -48,8 +49,13 @@ pub mod html {
   pub type JsHtmlEmailFormatter = JsBox<Arc<HtmlEmailFormatter>>;
 
   pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
-    let cb = cx.argument::<JsFunction>(0)?.root(&mut cx);
-    let inner: Arc<HtmlEmailFormatter> = Arc::new(HtmlEmailFormatter);
+    let external_uri = cx.argument::<JsString>(0)?;
+    let cb = cx.argument::<JsFunction>(1)?.root(&mut cx);
+    let external_uri = match Url::parse(&external_uri.value(&mut cx)) {
+      Ok(uri) => uri,
+      Err(e) => return cx.throw_type_error(e.to_string()),
+    };
+    let inner: Arc<HtmlEmailFormatter> = Arc::new(HtmlEmailFormatter::new(external_uri));
     let res = async move { inner };
     resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
   }
@@ -9,9 +9,11 @@ mod email_formatter;
 mod hammerfest_client;
 mod hammerfest_store;
 mod link_store;
+mod logger;
 mod mailer;
 mod neon_helpers;
 mod oauth_provider_store;
+mod outbox_store;
 mod password;
 mod rest;
 mod services;
@@ -33,8 +35,10 @@ fn export(mut cx: ModuleContext) -> NeonResult<()> {
   cx.export_with("hammerfestClient", crate::hammerfest_client::create_namespace)?;
   cx.export_with("hammerfestStore", crate::hammerfest_store::create_namespace)?;
   cx.export_with("linkStore", crate::link_store::create_namespace)?;
+  cx.export_with("logger", crate::logger::create_namespace)?;
   cx.export_with("mailer", crate::mailer::create_namespace)?;
   cx.export_with("oauthProviderStore", crate::oauth_provider_store::create_namespace)?;
+  cx.export_with("outboxStore", crate::outbox_store::create_namespace)?;
   cx.export_with("password", crate::password::create_namespace)?;
   cx.export_with("rest", crate::rest::create_namespace)?;
   cx.export_with("services", crate::services::create_namespace)?;
@@ -0,0 +1,100 @@
+use crate::logger::json::JsJsonLogger;
+use crate::neon_helpers::NeonNamespace;
+use etwin_core::clock::Clock;
+use etwin_log::json::JsonLogger;
+use etwin_log::{Logger, NoopLogger};
+use neon::prelude::*;
+use std::io::Write;
+use std::sync::Arc;
+
+pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+  let ns = cx.empty_object();
+  ns.set_with(cx, "json", json::create_namespace)?;
+  Ok(ns)
+}
+
+/// Logger writing the events of the services as JSON lines
+pub type EventLogger = JsonLogger<Arc<dyn Clock>, Box<dyn Write + Send>>;
+
+/// Read an optional logger argument: `null` or `undefined` discard the events.
+pub fn get_native_logger<'a, C: Context<'a>, T>(cx: &mut C, value: Handle<JsValue>) -> NeonResult<Arc<dyn Logger<T>>>
+where
+  EventLogger: Logger<T>,
+{
+  if value.is_a::<JsNull, _>(cx) || value.is_a::<JsUndefined, _>(cx) {
+    return Ok(Arc::new(NoopLogger));
+  }
+  match value.downcast::<JsJsonLogger, _>(cx) {
+    Ok(val) => {
+      let val = Arc::clone(&**val);
+      Ok(val)
+    }
+    Err(_) => cx.throw_type_error::<_, Arc<dyn Logger<T>>>("JsJsonLogger | null".to_string()),
+  }
+}
+
+pub mod json {
+  use crate::clock::get_native_clock;
+  use crate::logger::EventLogger;
+  use crate::neon_helpers::{resolve_callback_with, NeonNamespace};
+  use etwin_core::clock::Clock;
+  use etwin_log::file::RotatingFile;
+  use etwin_log::json::JsonLogger;
+  use neon::prelude::*;
+  use std::io::{self, Write};
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "stdout", stdout)?;
+    ns.set_function(cx, "file", file)?;
+    Ok(ns)
+  }
+
+  pub type JsJsonLogger = JsBox<Arc<EventLogger>>;
+
+  pub fn stdout(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let clock = cx.argument::<JsValue>(0)?;
+    let cb = cx.argument::<JsFunction>(1)?.root(&mut cx);
+
+    let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
+    let writer: Box<dyn Write + Send> = Box::new(io::stdout());
+    let inner: Arc<EventLogger> = Arc::new(JsonLogger::new(clock, writer));
+    let res = async move { inner };
+    resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
+  }
+
+  pub fn file(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let clock = cx.argument::<JsValue>(0)?;
+    let path = cx.argument::<JsString>(1)?;
+    let max_size = cx.argument::<JsValue>(2)?;
+    let max_age = cx.argument::<JsValue>(3)?;
+    let cb = cx.argument::<JsFunction>(4)?.root(&mut cx);
+
+    let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
+    let path = path.value(&mut cx);
+    let max_size: Option<u64> = match max_size.downcast::<JsNumber, _>(&mut cx) {
+      Ok(max_size) => Some(max_size.value(&mut cx) as u64),
+      Err(_) => None,
+    };
+    let max_age: Option<i64> = match max_age.downcast::<JsNumber, _>(&mut cx) {
+      Ok(max_age) => Some(max_age.value(&mut cx) as i64),
+      Err(_) => None,
+    };
+
+    let mut file = match RotatingFile::open(path, Arc::clone(&clock)) {
+      Ok(file) => file,
+      Err(e) => return cx.throw_error(e.to_string()),
+    };
+    if let Some(max_size) = max_size {
+      file = file.with_max_size(max_size);
+    }
+    if let Some(max_age) = max_age {
+      file = file.with_max_age(chrono::Duration::hours(max_age));
+    }
+    let writer: Box<dyn Write + Send> = Box::new(file);
+    let inner: Arc<EventLogger> = Arc::new(JsonLogger::new(clock, writer));
+    let res = async move { inner };
+    resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
+  }
+}
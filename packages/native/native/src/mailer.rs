@@ -1,4 +1,6 @@
 use crate::mailer::mem::JsMemMailer;
+use crate::mailer::noop::JsNoopMailer;
+use crate::mailer::outbox::JsOutboxMailer;
 use crate::mailer::smtp::JsSmtpMailer;
 use crate::neon_helpers::NeonNamespace;
 use etwin_core::email::Mailer;
@@ -8,6 +10,8 @@ use std::sync::Arc;
 pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
   let ns = cx.empty_object();
   ns.set_with(cx, "mem", mem::create_namespace)?;
+  ns.set_with(cx, "noop", noop::create_namespace)?;
+  ns.set_with(cx, "outbox", outbox::create_namespace)?;
   ns.set_with(cx, "smtp", smtp::create_namespace)?;
   Ok(ns)
 }
@@ -23,7 +27,21 @@ pub fn get_native_mailer<'a, C: Context<'a>>(cx: &mut C, value: Handle<JsValue>)
         let val = Arc::clone(&**val);
         Ok(val)
       }
-      Err(_) => cx.throw_type_error::<_, Arc<dyn Mailer>>("JsMemMailer | JsSmtpMailer".to_string()),
+      Err(_) => match value.downcast::<JsOutboxMailer, _>(cx) {
+        Ok(val) => {
+          let val = Arc::clone(&**val);
+          Ok(val)
+        }
+        Err(_) => match value.downcast::<JsNoopMailer, _>(cx) {
+          Ok(val) => {
+            let val = Arc::clone(&**val);
+            Ok(val)
+          }
+          Err(_) => cx.throw_type_error::<_, Arc<dyn Mailer>>(
+            "JsMemMailer | JsSmtpMailer | JsOutboxMailer | JsNoopMailer".to_string(),
+          ),
+        },
+      },
     },
   }
 }
@@ -50,6 +68,55 @@ pub mod mem {
   }
 }
 
+pub mod noop {
+  use crate::neon_helpers::{resolve_callback_with, NeonNamespace};
+  use etwin_mailer::noop::NoopMailer;
+  use neon::prelude::*;
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "new", new)?;
+    Ok(ns)
+  }
+
+  pub type JsNoopMailer = JsBox<Arc<NoopMailer>>;
+
+  pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let cb = cx.argument::<JsFunction>(0)?.root(&mut cx);
+    let inner: Arc<NoopMailer> = Arc::new(NoopMailer);
+    let res = async move { inner };
+    resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
+  }
+}
+
+pub mod outbox {
+  use crate::neon_helpers::{resolve_callback_with, NeonNamespace};
+  use crate::outbox_store::get_native_outbox_store;
+  use etwin_core::email::OutboxStore;
+  use etwin_services::outbox::OutboxMailer;
+  use neon::prelude::*;
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "new", new)?;
+    Ok(ns)
+  }
+
+  pub type JsOutboxMailer = JsBox<Arc<OutboxMailer<Arc<dyn OutboxStore>>>>;
+
+  pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let outbox_store = cx.argument::<JsValue>(0)?;
+    let cb = cx.argument::<JsFunction>(1)?.root(&mut cx);
+
+    let outbox_store: Arc<dyn OutboxStore> = get_native_outbox_store(&mut cx, outbox_store)?;
+    let inner: Arc<OutboxMailer<Arc<dyn OutboxStore>>> = Arc::new(OutboxMailer::new(outbox_store));
+    let res = async move { inner };
+    resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
+  }
+}
+
 pub mod smtp {
   use crate::neon_helpers::{resolve_callback_with, NeonNamespace};
   use etwin_mailer::smtp::{HeaderName, RawHeader, SmtpMailer};
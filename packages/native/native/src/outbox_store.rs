@@ -0,0 +1,99 @@
+use crate::neon_helpers::NeonNamespace;
+use crate::outbox_store::mem::JsMemOutboxStore;
+use crate::outbox_store::pg::JsPgOutboxStore;
+use etwin_core::email::OutboxStore;
+use neon::prelude::*;
+use std::sync::Arc;
+
+pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+  let ns = cx.empty_object();
+  ns.set_with(cx, "mem", mem::create_namespace)?;
+  ns.set_with(cx, "pg", pg::create_namespace)?;
+  Ok(ns)
+}
+
+pub fn get_native_outbox_store<'a, C: Context<'a>>(
+  cx: &mut C,
+  value: Handle<JsValue>,
+) -> NeonResult<Arc<dyn OutboxStore>> {
+  match value.downcast::<JsMemOutboxStore, _>(cx) {
+    Ok(val) => {
+      let val = Arc::clone(&**val);
+      Ok(val)
+    }
+    Err(_) => match value.downcast::<JsPgOutboxStore, _>(cx) {
+      Ok(val) => {
+        let val = Arc::clone(&**val);
+        Ok(val)
+      }
+      Err(_) => cx.throw_type_error::<_, Arc<dyn OutboxStore>>("JsMemOutboxStore | JsPgOutboxStore".to_string()),
+    },
+  }
+}
+
+pub mod mem {
+  use crate::clock::get_native_clock;
+  use crate::neon_helpers::NeonNamespace;
+  use crate::uuid::get_native_uuid_generator;
+  use etwin_core::clock::Clock;
+  use etwin_core::uuid::UuidGenerator;
+  use etwin_outbox_store::mem::MemOutboxStore;
+  use neon::prelude::*;
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "new", new)?;
+    Ok(ns)
+  }
+
+  pub type JsMemOutboxStore = JsBox<Arc<MemOutboxStore<Arc<dyn Clock>, Arc<dyn UuidGenerator>>>>;
+
+  pub fn new(mut cx: FunctionContext) -> JsResult<JsMemOutboxStore> {
+    let clock = cx.argument::<JsValue>(0)?;
+    let uuid_generator = cx.argument::<JsValue>(1)?;
+    let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
+    let uuid_generator: Arc<dyn UuidGenerator> = get_native_uuid_generator(&mut cx, uuid_generator)?;
+    let inner: Arc<MemOutboxStore<Arc<dyn Clock>, Arc<dyn UuidGenerator>>> =
+      Arc::new(MemOutboxStore::new(clock, uuid_generator));
+    Ok(cx.boxed(inner))
+  }
+}
+
+pub mod pg {
+  use crate::clock::get_native_clock;
+  use crate::database::JsPgPool;
+  use crate::neon_helpers::NeonNamespace;
+  use crate::uuid::get_native_uuid_generator;
+  use etwin_core::clock::Clock;
+  use etwin_core::core::Secret;
+  use etwin_core::uuid::UuidGenerator;
+  use etwin_outbox_store::pg::PgOutboxStore;
+  use neon::prelude::*;
+  use sqlx::PgPool;
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "new", new)?;
+    Ok(ns)
+  }
+
+  pub type JsPgOutboxStore = JsBox<Arc<PgOutboxStore<Arc<dyn Clock>, Arc<PgPool>, Arc<dyn UuidGenerator>>>>;
+
+  pub fn new(mut cx: FunctionContext) -> JsResult<JsPgOutboxStore> {
+    let clock = cx.argument::<JsValue>(0)?;
+    let database = cx.argument::<JsPgPool>(1)?;
+    let database_secret = cx.argument::<JsString>(2)?;
+    let uuid_generator = cx.argument::<JsValue>(3)?;
+    let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
+    let database = Arc::new(PgPool::clone(&database));
+    let database_secret: String = database_secret.value(&mut cx);
+    let database_secret = Secret::new(database_secret);
+    let uuid_generator: Arc<dyn UuidGenerator> = get_native_uuid_generator(&mut cx, uuid_generator)?;
+    #[allow(clippy::type_complexity)]
+    let inner: Arc<PgOutboxStore<Arc<dyn Clock>, Arc<PgPool>, Arc<dyn UuidGenerator>>> =
+      Arc::new(PgOutboxStore::new(clock, database, database_secret, uuid_generator));
+    Ok(cx.boxed(inner))
+  }
+}
@@ -1,4 +1,5 @@
 use crate::neon_helpers::{resolve_callback_with, NeonNamespace};
+use crate::password::multi::JsMultiPasswordService;
 use crate::password::scrypt::JsScryptPasswordService;
 use etwin_core::password::{Password, PasswordHash, PasswordService};
 use neon::borrow::Ref;
@@ -9,6 +10,7 @@ use tokio::task::JoinError;
 
 pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
   let ns = cx.empty_object();
+  ns.set_with(cx, "multi", multi::create_namespace)?;
   ns.set_with(cx, "scrypt", scrypt::create_namespace)?;
   ns.set_function(cx, "hash", hash)?;
   ns.set_function(cx, "verify", verify)?;
@@ -19,12 +21,20 @@ pub fn get_native_password<'a, C: Context<'a>>(
   cx: &mut C,
   value: Handle<JsValue>,
 ) -> NeonResult<Arc<dyn PasswordService>> {
-  match value.downcast::<JsScryptPasswordService, _>(cx) {
+  match value.downcast::<JsMultiPasswordService, _>(cx) {
     Ok(val) => {
       let val = Arc::clone(&**val);
       Ok(val)
     }
-    Err(_) => cx.throw_type_error::<_, Arc<dyn PasswordService>>("JsScryptPasswordService".to_string()),
+    Err(_) => match value.downcast::<JsScryptPasswordService, _>(cx) {
+      Ok(val) => {
+        let val = Arc::clone(&**val);
+        Ok(val)
+      }
+      Err(_) => cx.throw_type_error::<_, Arc<dyn PasswordService>>(
+        "JsMultiPasswordService | JsScryptPasswordService".to_string(),
+      ),
+    },
   }
 }
 
@@ -92,6 +102,40 @@ pub fn verify(mut cx: FunctionContext) -> JsResult<JsUndefined> {
   })
 }
 
+pub mod multi {
+  use crate::neon_helpers::NeonNamespace;
+  use etwin_password::argon2::Argon2Params;
+  use etwin_password::multi::{MultiPasswordService, OsRng};
+  use neon::prelude::*;
+  use std::sync::Arc;
+
+  pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+    let ns = cx.empty_object();
+    ns.set_function(cx, "withOsRng", with_os_rng)?;
+    ns.set_function(cx, "recommendedForTests", recommended_for_tests)?;
+    Ok(ns)
+  }
+
+  pub type JsMultiPasswordService = JsBox<Arc<MultiPasswordService<OsRng>>>;
+
+  pub fn with_os_rng(mut cx: FunctionContext) -> JsResult<JsMultiPasswordService> {
+    let memory_cost = cx.argument::<JsNumber>(0)?.value(&mut cx) as u32;
+    let time_cost = cx.argument::<JsNumber>(1)?.value(&mut cx) as u32;
+    let parallelism = cx.argument::<JsNumber>(2)?.value(&mut cx) as u32;
+    let params = match Argon2Params::new(memory_cost, time_cost, parallelism) {
+      Ok(params) => params,
+      Err(e) => return cx.throw_range_error(e.to_string()),
+    };
+    let inner: Arc<MultiPasswordService<OsRng>> = Arc::new(MultiPasswordService::with_os_rng(params));
+    Ok(cx.boxed(inner))
+  }
+
+  pub fn recommended_for_tests(mut cx: FunctionContext) -> JsResult<JsMultiPasswordService> {
+    let inner: Arc<MultiPasswordService<OsRng>> = Arc::new(MultiPasswordService::recommended_for_tests());
+    Ok(cx.boxed(inner))
+  }
+}
+
 pub mod scrypt {
   use crate::neon_helpers::NeonNamespace;
   use etwin_password::scrypt::{OsRng, ScryptPasswordService};
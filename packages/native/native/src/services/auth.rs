@@ -6,17 +6,19 @@ use crate::email_formatter::get_native_email_formatter;
 use crate::hammerfest_client::get_native_hammerfest_client;
 use crate::hammerfest_store::get_native_hammerfest_store;
 use crate::link_store::get_native_link_store;
+use crate::logger::get_native_logger;
 use crate::mailer::get_native_mailer;
 use crate::neon_helpers::{resolve_callback_serde, resolve_callback_with, NeonNamespace};
 use crate::oauth_provider_store::get_native_oauth_provider_store;
 use crate::password::get_native_password;
+use crate::token_store::get_native_token_store;
 use crate::twinoid_client::get_native_twinoid_client;
 use crate::twinoid_store::get_native_twinoid_store;
 use crate::user_store::get_native_user_store;
 use crate::uuid::get_native_uuid_generator;
 use etwin_core::auth::{
   AuthContext, AuthStore, CreateAccessTokenOptions, EtwinOauthAccessTokenKey, GrantOauthAuthorizationOptions,
-  RawCredentials, RawUserCredentials, RegisterOrLoginWithEmailOptions, RegisterWithUsernameOptions,
+  LoginResult, RawCredentials, RawUserCredentials, RegisterOrLoginWithEmailOptions, RegisterWithUsernameOptions,
   RegisterWithVerifiedEmailOptions, SessionId,
 };
 use etwin_core::clock::Clock;
@@ -24,13 +26,15 @@ use etwin_core::dinoparc::{DinoparcClient, DinoparcCredentials, DinoparcStore};
 use etwin_core::email::{EmailFormatter, Mailer};
 use etwin_core::hammerfest::{HammerfestClient, HammerfestCredentials, HammerfestStore};
 use etwin_core::link::LinkStore;
-use etwin_core::oauth::{OauthProviderStore, RfcOauthAccessTokenKey};
+use etwin_core::oauth::{OauthProviderStore, RfcOauthAccessToken};
 use etwin_core::password::PasswordService;
+use etwin_core::token::TokenStore;
 use etwin_core::twinoid::{TwinoidClient, TwinoidStore};
 use etwin_core::types::AnyError;
 use etwin_core::user::UserStore;
 use etwin_core::uuid::UuidGenerator;
-use etwin_services::auth::{AuthService, DynAuthService};
+use etwin_log::Logger;
+use etwin_services::auth::{AuthEvent, AuthService, DynAuthService};
 use neon::borrow::Ref;
 use neon::prelude::*;
 use std::sync::Arc;
@@ -85,12 +89,14 @@ pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
   let mailer = cx.argument::<JsValue>(8)?;
   let oauth_provider_store = cx.argument::<JsValue>(9)?;
   let password_service = cx.argument::<JsValue>(10)?;
-  let user_store = cx.argument::<JsValue>(11)?;
-  let twinoid_client = cx.argument::<JsValue>(12)?;
-  let twinoid_store = cx.argument::<JsValue>(13)?;
-  let uuid_generator = cx.argument::<JsValue>(14)?;
-  let auth_secret = cx.argument::<JsBuffer>(15)?;
-  let cb = cx.argument::<JsFunction>(16)?.root(&mut cx);
+  let token_store = cx.argument::<JsValue>(11)?;
+  let user_store = cx.argument::<JsValue>(12)?;
+  let twinoid_client = cx.argument::<JsValue>(13)?;
+  let twinoid_store = cx.argument::<JsValue>(14)?;
+  let uuid_generator = cx.argument::<JsValue>(15)?;
+  let auth_secret = cx.argument::<JsBuffer>(16)?;
+  let logger = cx.argument::<JsValue>(17)?;
+  let cb = cx.argument::<JsFunction>(18)?.root(&mut cx);
 
   let auth_store: Arc<dyn AuthStore> = get_native_auth_store(&mut cx, auth_store)?;
   let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
@@ -104,10 +110,12 @@ pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
   let oauth_provider_store: Arc<dyn OauthProviderStore> =
     get_native_oauth_provider_store(&mut cx, oauth_provider_store)?;
   let password_service: Arc<dyn PasswordService> = get_native_password(&mut cx, password_service)?;
+  let token_store: Arc<dyn TokenStore> = get_native_token_store(&mut cx, token_store)?;
   let user_store: Arc<dyn UserStore> = get_native_user_store(&mut cx, user_store)?;
   let twinoid_client: Arc<dyn TwinoidClient> = get_native_twinoid_client(&mut cx, twinoid_client)?;
   let twinoid_store: Arc<dyn TwinoidStore> = get_native_twinoid_store(&mut cx, twinoid_store)?;
   let uuid_generator: Arc<dyn UuidGenerator> = get_native_uuid_generator(&mut cx, uuid_generator)?;
+  let logger: Arc<dyn Logger<AuthEvent>> = get_native_logger(&mut cx, logger)?;
   let auth_secret = Context::borrow(&cx, &auth_secret, |auth_secret: Ref<BinaryData>| {
     let auth_secret: &[u8] = auth_secret.as_slice::<u8>();
     auth_secret.to_vec()
@@ -122,9 +130,11 @@ pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
     hammerfest_client,
     hammerfest_store,
     link_store,
+    logger,
     mailer,
     oauth_provider_store,
     password_service,
+    token_store,
     user_store,
     twinoid_client,
     twinoid_store,
@@ -233,7 +243,14 @@ pub fn raw_login_with_credentials(mut cx: FunctionContext) -> JsResult<JsUndefin
 
   let credentials: RawUserCredentials = serde_json::from_str(&credentials_json.value(&mut cx)).unwrap();
 
-  let res = async move { inner.raw_login_with_credentials(&credentials).await };
+  let res = async move {
+    // The JS API does not support the second login step yet
+    match inner.raw_login_with_credentials(&credentials, None).await {
+      Ok(LoginResult::Session(user_and_session)) => Ok(user_and_session),
+      Ok(LoginResult::PendingMfa(_)) => Err(AnyError::from("MfaRequired")),
+      Err(e) => Err(e),
+    }
+  };
   resolve_callback_serde(&mut cx, res, cb)
 }
 
@@ -267,7 +284,7 @@ pub fn register_or_login_with_twinoid_oauth(mut cx: FunctionContext) -> JsResult
   let credentials_json = cx.argument::<JsString>(1)?;
   let cb = cx.argument::<JsFunction>(2)?.root(&mut cx);
 
-  let credentials: RfcOauthAccessTokenKey = serde_json::from_str(&credentials_json.value(&mut cx)).unwrap();
+  let credentials: RfcOauthAccessToken = serde_json::from_str(&credentials_json.value(&mut cx)).unwrap();
 
   let res = async move { inner.register_or_login_with_twinoid_oauth(&credentials).await };
   resolve_callback_serde(&mut cx, res, cb)
@@ -293,6 +310,6 @@ pub fn raw_authenticate_credentials(mut cx: FunctionContext) -> JsResult<JsUndef
 
   let credentials: RawCredentials = serde_json::from_str(&credentials_json.value(&mut cx)).unwrap();
 
-  let res = async move { inner.raw_authenticate_credentials(&credentials).await };
+  let res = async move { inner.raw_authenticate_credentials(&credentials, None).await };
   resolve_callback_serde(&mut cx, res, cb)
 }
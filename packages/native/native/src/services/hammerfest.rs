@@ -1,13 +1,15 @@
 use crate::hammerfest_client::get_native_hammerfest_client;
 use crate::hammerfest_store::get_native_hammerfest_store;
 use crate::link_store::get_native_link_store;
+use crate::logger::get_native_logger;
 use crate::neon_helpers::{resolve_callback_serde, resolve_callback_with, NeonNamespace};
 use crate::user_store::get_native_user_store;
 use etwin_core::auth::AuthContext;
 use etwin_core::hammerfest::{GetHammerfestUserOptions, HammerfestClient, HammerfestStore};
 use etwin_core::link::LinkStore;
 use etwin_core::user::UserStore;
-use etwin_services::hammerfest::HammerfestService;
+use etwin_log::Logger;
+use etwin_services::hammerfest::{DynHammerfestService, HammerfestEvent, HammerfestService};
 use neon::prelude::*;
 use std::sync::Arc;
 
@@ -18,25 +20,18 @@ pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject
   Ok(ns)
 }
 
-pub type JsHammerfestService = JsBox<
-  Arc<HammerfestService<Arc<dyn HammerfestClient>, Arc<dyn HammerfestStore>, Arc<dyn LinkStore>, Arc<dyn UserStore>>>,
->;
+pub type JsHammerfestService = JsBox<Arc<DynHammerfestService>>;
 
-#[allow(clippy::type_complexity)]
 pub fn get_native_hammerfest_service<'a, C: Context<'a>>(
   cx: &mut C,
   value: Handle<JsValue>,
-) -> NeonResult<
-  Arc<HammerfestService<Arc<dyn HammerfestClient>, Arc<dyn HammerfestStore>, Arc<dyn LinkStore>, Arc<dyn UserStore>>>,
-> {
+) -> NeonResult<Arc<DynHammerfestService>> {
   match value.downcast::<JsHammerfestService, _>(cx) {
     Ok(val) => {
       let val = Arc::clone(&**val);
       Ok(val)
     }
-    Err(_) => cx.throw_type_error::<_, Arc<
-      HammerfestService<Arc<dyn HammerfestClient>, Arc<dyn HammerfestStore>, Arc<dyn LinkStore>, Arc<dyn UserStore>>,
-    >>("JsHammerfestService".to_string()),
+    Err(_) => cx.throw_type_error::<_, Arc<DynHammerfestService>>("JsHammerfestService".to_string()),
   }
 }
 
@@ -45,18 +40,21 @@ pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
   let hammerfest_store = cx.argument::<JsValue>(1)?;
   let link_store = cx.argument::<JsValue>(2)?;
   let user_store = cx.argument::<JsValue>(3)?;
-  let cb = cx.argument::<JsFunction>(4)?.root(&mut cx);
+  let logger = cx.argument::<JsValue>(4)?;
+  let cb = cx.argument::<JsFunction>(5)?.root(&mut cx);
 
   let hammerfest_client: Arc<dyn HammerfestClient> = get_native_hammerfest_client(&mut cx, hammerfest_client)?;
   let hammerfest_store: Arc<dyn HammerfestStore> = get_native_hammerfest_store(&mut cx, hammerfest_store)?;
   let link_store: Arc<dyn LinkStore> = get_native_link_store(&mut cx, link_store)?;
   let user_store: Arc<dyn UserStore> = get_native_user_store(&mut cx, user_store)?;
+  let logger: Arc<dyn Logger<HammerfestEvent>> = get_native_logger(&mut cx, logger)?;
 
   let res = async move {
     Arc::new(HammerfestService::new(
       hammerfest_client,
       hammerfest_store,
       link_store,
+      logger,
       user_store,
     ))
   };
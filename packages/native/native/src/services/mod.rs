@@ -4,11 +4,13 @@ use neon::prelude::*;
 pub mod auth;
 pub mod dinoparc;
 pub mod hammerfest;
+pub mod outbox;
 
 pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
   let ns = cx.empty_object();
   ns.set_with(cx, "auth", auth::create_namespace)?;
   ns.set_with(cx, "dinoparc", dinoparc::create_namespace)?;
   ns.set_with(cx, "hammerfest", hammerfest::create_namespace)?;
+  ns.set_with(cx, "outbox", outbox::create_namespace)?;
   Ok(ns)
 }
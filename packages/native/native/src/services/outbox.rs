@@ -0,0 +1,59 @@
+use crate::clock::get_native_clock;
+use crate::logger::get_native_logger;
+use crate::mailer::get_native_mailer;
+use crate::neon_helpers::{resolve_callback_serde, resolve_callback_with, NeonNamespace};
+use crate::outbox_store::get_native_outbox_store;
+use etwin_core::clock::Clock;
+use etwin_core::email::{Mailer, OutboxStore};
+use etwin_log::Logger;
+use etwin_services::outbox::{DynOutboxService, OutboxEvent, OutboxService};
+use neon::prelude::*;
+use std::sync::Arc;
+
+pub fn create_namespace<'a, C: Context<'a>>(cx: &mut C) -> JsResult<'a, JsObject> {
+  let ns = cx.empty_object();
+  ns.set_function(cx, "new", new)?;
+  ns.set_function(cx, "deliverDueEmails", deliver_due_emails)?;
+  Ok(ns)
+}
+
+pub type JsOutboxService = JsBox<Arc<DynOutboxService>>;
+
+pub fn get_native_outbox_service<'a, C: Context<'a>>(
+  cx: &mut C,
+  value: Handle<JsValue>,
+) -> NeonResult<Arc<DynOutboxService>> {
+  match value.downcast::<JsOutboxService, _>(cx) {
+    Ok(val) => {
+      let val = Arc::clone(&**val);
+      Ok(val)
+    }
+    Err(_) => cx.throw_type_error::<_, Arc<DynOutboxService>>("JsOutboxService".to_string()),
+  }
+}
+
+pub fn new(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let clock = cx.argument::<JsValue>(0)?;
+  let logger = cx.argument::<JsValue>(1)?;
+  let mailer = cx.argument::<JsValue>(2)?;
+  let outbox_store = cx.argument::<JsValue>(3)?;
+  let cb = cx.argument::<JsFunction>(4)?.root(&mut cx);
+
+  let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
+  let logger: Arc<dyn Logger<OutboxEvent>> = get_native_logger(&mut cx, logger)?;
+  let mailer: Arc<dyn Mailer> = get_native_mailer(&mut cx, mailer)?;
+  let outbox_store: Arc<dyn OutboxStore> = get_native_outbox_store(&mut cx, outbox_store)?;
+
+  let res = async move { Arc::new(OutboxService::new(clock, logger, mailer, outbox_store)) };
+
+  resolve_callback_with(&mut cx, res, cb, |c: &mut TaskContext, res| Ok(c.boxed(res).upcast()))
+}
+
+pub fn deliver_due_emails(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let inner = cx.argument::<JsValue>(0)?;
+  let inner = get_native_outbox_service(&mut cx, inner)?;
+  let cb = cx.argument::<JsFunction>(1)?.root(&mut cx);
+
+  let res = async move { inner.deliver_due_emails().await };
+  resolve_callback_serde(&mut cx, res, cb)
+}
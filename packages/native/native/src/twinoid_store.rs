@@ -73,7 +73,9 @@ pub fn touch_short_user(mut cx: FunctionContext) -> JsResult<JsUndefined> {
 pub mod mem {
   use crate::clock::get_native_clock;
   use crate::neon_helpers::NeonNamespace;
+  use crate::uuid::get_native_uuid_generator;
   use etwin_core::clock::Clock;
+  use etwin_core::uuid::UuidGenerator;
   use etwin_twinoid_store::mem::MemTwinoidStore;
   use neon::prelude::*;
   use std::sync::Arc;
@@ -110,14 +112,18 @@ pub mod pg {
     Ok(ns)
   }
 
-  pub type JsPgTwinoidStore = JsBox<Arc<PgTwinoidStore<Arc<dyn Clock>, Arc<PgPool>>>>;
+  pub type JsPgTwinoidStore = JsBox<Arc<PgTwinoidStore<Arc<dyn Clock>, Arc<PgPool>, Arc<dyn UuidGenerator>>>>;
 
   pub fn new(mut cx: FunctionContext) -> JsResult<JsPgTwinoidStore> {
     let clock = cx.argument::<JsValue>(0)?;
     let database = cx.argument::<JsPgPool>(1)?;
+    let uuid_generator = cx.argument::<JsValue>(2)?;
     let clock: Arc<dyn Clock> = get_native_clock(&mut cx, clock)?;
     let database = Arc::new(PgPool::clone(&database));
-    let inner: Arc<PgTwinoidStore<Arc<dyn Clock>, Arc<PgPool>>> = Arc::new(PgTwinoidStore::new(clock, database));
+    let uuid_generator: Arc<dyn UuidGenerator> = get_native_uuid_generator(&mut cx, uuid_generator)?;
+    #[allow(clippy::type_complexity)]
+    let inner: Arc<PgTwinoidStore<Arc<dyn Clock>, Arc<PgPool>, Arc<dyn UuidGenerator>>> =
+      Arc::new(PgTwinoidStore::new(clock, database, uuid_generator));
     Ok(cx.boxed(inner))
   }
 }
@@ -21,8 +21,8 @@ export class HtmlEmailFormatter extends NativeEmailFormatter {
     super(box);
   }
 
-  static async create(): Promise<HtmlEmailFormatter> {
-    return new HtmlEmailFormatter(await HtmlEmailFormatter.NEW());
+  static async create(externalUri: string): Promise<HtmlEmailFormatter> {
+    return new HtmlEmailFormatter(await HtmlEmailFormatter.NEW(externalUri));
   }
 }
 
@@ -0,0 +1,66 @@
+import { promisify } from "util";
+
+import native from "#native";
+
+import { NativeClock } from "./clock.mjs";
+
+declare const JsonLoggerBox: unique symbol;
+export type NativeLoggerBox = typeof JsonLoggerBox;
+
+export abstract class NativeLogger {
+  public readonly box: NativeLoggerBox;
+
+  constructor(box: NativeLoggerBox) {
+    this.box = box;
+  }
+}
+
+export interface StdoutLoggerOptions {
+  clock: NativeClock;
+}
+
+/**
+ * Logger writing the events of the services as JSON lines to the standard output.
+ */
+export class StdoutLogger extends NativeLogger {
+  private static NEW = promisify(native.logger.json.stdout);
+
+  private constructor(box: typeof JsonLoggerBox) {
+    super(box);
+  }
+
+  static async create(options: Readonly<StdoutLoggerOptions>): Promise<StdoutLogger> {
+    return new StdoutLogger(await StdoutLogger.NEW(options.clock.box));
+  }
+}
+
+export interface FileLoggerOptions {
+  clock: NativeClock;
+  /**
+   * Log file, created if missing
+   */
+  path: string;
+  /**
+   * Size limit of the file, in bytes
+   */
+  maxSize: number | null;
+  /**
+   * Age limit of the file, in hours
+   */
+  maxAge: number | null;
+}
+
+/**
+ * Logger writing the events of the services as JSON lines to a file, rotated when it gets too large or too old.
+ */
+export class FileLogger extends NativeLogger {
+  private static NEW = promisify(native.logger.json.file);
+
+  private constructor(box: typeof JsonLoggerBox) {
+    super(box);
+  }
+
+  static async create(options: Readonly<FileLoggerOptions>): Promise<FileLogger> {
+    return new FileLogger(await FileLogger.NEW(options.clock.box, options.path, options.maxSize, options.maxAge));
+  }
+}
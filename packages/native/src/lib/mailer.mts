@@ -2,9 +2,13 @@ import { promisify } from "util";
 
 import native from "#native";
 
+import { NativeOutboxStore } from "./outbox-store.mjs";
+
 declare const MemMailerBox: unique symbol;
+declare const NoopMailerBox: unique symbol;
+declare const OutboxMailerBox: unique symbol;
 declare const SmtpMailerBox: unique symbol;
-export type NativeMailerBox = typeof MemMailerBox | typeof SmtpMailerBox;
+export type NativeMailerBox = typeof MemMailerBox | typeof NoopMailerBox | typeof OutboxMailerBox | typeof SmtpMailerBox;
 
 export abstract class NativeMailer {
   public readonly box: NativeMailerBox;
@@ -56,3 +60,37 @@ export class SmtpMailer extends NativeMailer {
     ));
   }
 }
+
+/**
+ * Mailer accepting and discarding every email, for deployments without email delivery.
+ */
+export class NoopMailer extends NativeMailer {
+  private static NEW = promisify(native.mailer.noop.new);
+
+  private constructor(box: typeof NoopMailerBox) {
+    super(box);
+  }
+
+  static async create(): Promise<NoopMailer> {
+    return new NoopMailer(await NoopMailer.NEW());
+  }
+}
+
+export interface OutboxMailerOptions {
+  outboxStore: NativeOutboxStore;
+}
+
+/**
+ * Mailer queuing the emails in the outbox, they are delivered later by the outbox service.
+ */
+export class OutboxMailer extends NativeMailer {
+  private static NEW = promisify(native.mailer.outbox.new);
+
+  private constructor(box: typeof OutboxMailerBox) {
+    super(box);
+  }
+
+  static async create(options: Readonly<OutboxMailerOptions>): Promise<OutboxMailer> {
+    return new OutboxMailer(await OutboxMailer.NEW(options.outboxStore.box));
+  }
+}
@@ -0,0 +1,44 @@
+import native from "#native";
+
+import { NativeClock } from "./clock.mjs";
+import { Database } from "./database.mjs";
+import { NativeUuidGenerator } from "./uuid.mjs";
+
+declare const MemOutboxStoreBox: unique symbol;
+declare const PgOutboxStoreBox: unique symbol;
+export type NativeOutboxStoreBox = typeof MemOutboxStoreBox | typeof PgOutboxStoreBox;
+
+/**
+ * Store of the emails waiting to be delivered by the outbox service.
+ */
+export abstract class NativeOutboxStore {
+  public readonly box: NativeOutboxStoreBox;
+
+  constructor(box: NativeOutboxStoreBox) {
+    this.box = box;
+  }
+}
+
+export interface MemOutboxStoreOptions {
+  clock: NativeClock;
+  uuidGenerator: NativeUuidGenerator;
+}
+
+export class MemOutboxStore extends NativeOutboxStore {
+  constructor(options: Readonly<MemOutboxStoreOptions>) {
+    super(native.outboxStore.mem.new(options.clock.box, options.uuidGenerator.box));
+  }
+}
+
+export interface PgOutboxStoreOptions {
+  clock: NativeClock;
+  database: Database;
+  databaseSecret: string;
+  uuidGenerator: NativeUuidGenerator;
+}
+
+export class PgOutboxStore extends NativeOutboxStore {
+  constructor(options: Readonly<PgOutboxStoreOptions>) {
+    super(native.outboxStore.pg.new(options.clock.box, options.database.box, options.databaseSecret, options.uuidGenerator.box));
+  }
+}
@@ -6,8 +6,9 @@ import { promisify } from "util";
 
 import native from "#native";
 
+declare const MultiPasswordServiceBox: unique symbol;
 declare const ScryptPasswordServiceBox: unique symbol;
-export type NativePasswordServiceBox = typeof ScryptPasswordServiceBox;
+export type NativePasswordServiceBox = typeof MultiPasswordServiceBox | typeof ScryptPasswordServiceBox;
 
 export abstract class NativePasswordService implements PasswordService {
   public readonly box: NativePasswordServiceBox;
@@ -29,6 +30,38 @@ export abstract class NativePasswordService implements PasswordService {
   }
 }
 
+export interface Argon2Params {
+  /**
+   * Memory cost, in KiB
+   */
+  memoryCost: number;
+  /**
+   * Number of passes
+   */
+  timeCost: number;
+  /**
+   * Degree of parallelism
+   */
+  parallelism: number;
+}
+
+/**
+ * Password service hashing new passwords with Argon2id, and still verifying legacy scrypt hashes.
+ */
+export class MultiPasswordService extends NativePasswordService {
+  private constructor(box: typeof MultiPasswordServiceBox) {
+    super(box);
+  }
+
+  public static recommendedForTests(): MultiPasswordService {
+    return new MultiPasswordService(native.password.multi.recommendedForTests());
+  }
+
+  public static withOsRng(params: Readonly<Argon2Params> = {memoryCost: 19456, timeCost: 2, parallelism: 1}): MultiPasswordService {
+    return new MultiPasswordService(native.password.multi.withOsRng(params.memoryCost, params.timeCost, params.parallelism));
+  }
+}
+
 export class ScryptPasswordService extends NativePasswordService {
   private constructor(box: typeof ScryptPasswordServiceBox) {
     super(box);
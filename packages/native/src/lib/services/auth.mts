@@ -34,10 +34,6 @@ import {
   GrantOauthAuthorizationOptions
 } from "@eternal-twin/core/oauth/grant-oauth-authorization-options";
 import { $OauthAccessToken, OauthAccessToken } from "@eternal-twin/core/oauth/oauth-access-token";
-import {
-  $RfcOauthAccessTokenKey,
-  RfcOauthAccessTokenKey
-} from "@eternal-twin/core/oauth/rfc-oauth-access-token-key";
 import { JSON_READER } from "kryo-json/json-reader";
 import { JSON_WRITER } from "kryo-json/json-writer";
 import { promisify } from "util";
@@ -52,9 +48,11 @@ import { NativeEmailFormatter } from "../email-formatter.mjs";
 import { NativeHammerfestClient } from "../hammerfest-client.mjs";
 import { NativeHammerfestStore } from "../hammerfest-store.mjs";
 import { NativeLinkStore } from "../link-store.mjs";
+import { NativeLogger } from "../logger.mjs";
 import { NativeMailer } from "../mailer.mjs";
 import { NativeOauthProviderStore } from "../oauth-provider-store.mjs";
 import { NativePasswordService } from "../password.mjs";
+import { NativeTokenStore } from "../token-store.mjs";
 import { NativeTwinoidClient } from "../twinoid-client.mjs";
 import { NativeTwinoidStore } from "../twinoid-store.mjs";
 import { NativeUserStore } from "../user-store.mjs";
@@ -76,11 +74,16 @@ export interface NativeAuthServiceOptions {
   mailer: NativeMailer;
   oauthProviderStore: NativeOauthProviderStore;
   passwordService: NativePasswordService;
+  tokenStore: NativeTokenStore;
   userStore: NativeUserStore;
   twinoidClient: NativeTwinoidClient;
   twinoidStore: NativeTwinoidStore;
   uuidGenerator: NativeUuidGenerator;
   authSecret: Uint8Array;
+  /**
+   * Logger receiving the auth events, they are discarded if missing
+   */
+  logger?: NativeLogger | null;
 }
 
 export class NativeAuthService implements AuthService {
@@ -117,11 +120,13 @@ export class NativeAuthService implements AuthService {
       options.mailer.box,
       options.oauthProviderStore.box,
       options.passwordService.box,
+      options.tokenStore.box,
       options.userStore.box,
       options.twinoidClient.box,
       options.twinoidStore.box,
       options.uuidGenerator.box,
       options.authSecret,
+      options.logger?.box ?? null,
     ));
   }
 
@@ -180,8 +185,8 @@ export class NativeAuthService implements AuthService {
     return $UserAndSession.read(JSON_READER, rawOut);
   }
 
-  async registerOrLoginWithTwinoidOauth(_acx: AuthContext, accessToken: RfcOauthAccessTokenKey): Promise<UserAndSession> {
-    const rawAccessToken: string = $RfcOauthAccessTokenKey.write(JSON_WRITER, accessToken);
+  async registerOrLoginWithTwinoidOauth(_acx: AuthContext, accessToken: OauthAccessToken): Promise<UserAndSession> {
+    const rawAccessToken: string = $OauthAccessToken.write(JSON_WRITER, accessToken);
     const rawOut = await NativeAuthService.REGISTER_OR_LOGIN_WITH_TWINOID_OAUTH(this.box, rawAccessToken);
     return $UserAndSession.read(JSON_READER, rawOut);
   }
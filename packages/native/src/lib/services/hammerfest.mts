@@ -14,6 +14,7 @@ import native from "#native";
 import { NativeHammerfestClient } from "../hammerfest-client.mjs";
 import { NativeHammerfestStore } from "../hammerfest-store.mjs";
 import { NativeLinkStore } from "../link-store.mjs";
+import { NativeLogger } from "../logger.mjs";
 import { NativeUserStore } from "../user-store.mjs";
 
 declare const NativeHammerfestServiceBox: unique symbol;
@@ -23,6 +24,10 @@ export interface NativeHammerfestServiceOptions {
   hammerfestStore: NativeHammerfestStore;
   linkStore: NativeLinkStore;
   userStore: NativeUserStore;
+  /**
+   * Logger receiving the Hammerfest events, they are discarded if missing
+   */
+  logger?: NativeLogger | null;
 }
 
 export class NativeHammerfestService implements HammerfestService {
@@ -36,7 +41,7 @@ export class NativeHammerfestService implements HammerfestService {
   }
 
   public static async create(options: Readonly<NativeHammerfestServiceOptions>): Promise<NativeHammerfestService> {
-    const box = await NativeHammerfestService.NEW(options.hammerfestClient.box, options.hammerfestStore.box, options.linkStore.box, options.userStore.box);
+    const box = await NativeHammerfestService.NEW(options.hammerfestClient.box, options.hammerfestStore.box, options.linkStore.box, options.userStore.box, options.logger?.box ?? null);
     return new NativeHammerfestService(box);
   }
 
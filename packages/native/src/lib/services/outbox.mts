@@ -0,0 +1,95 @@
+import { promisify } from "util";
+
+import native from "#native";
+
+import { NativeClock } from "../clock.mjs";
+import { NativeLogger } from "../logger.mjs";
+import { NativeMailer } from "../mailer.mjs";
+import { NativeOutboxStore } from "../outbox-store.mjs";
+
+declare const NativeOutboxServiceBox: unique symbol;
+
+export interface NativeOutboxServiceOptions {
+  clock: NativeClock;
+  /**
+   * Mailer delivering the emails, it must not be an `OutboxMailer`
+   */
+  mailer: NativeMailer;
+  outboxStore: NativeOutboxStore;
+  /**
+   * Logger receiving the outbox events, they are discarded if missing
+   */
+  logger?: NativeLogger | null;
+}
+
+/**
+ * Outcome of a delivery pass, as lists of email ids.
+ */
+export interface OutboxDeliveryReport {
+  /**
+   * Emails accepted by the mailer
+   */
+  sent: string[];
+  /**
+   * Emails rejected by the mailer and scheduled for a new attempt
+   */
+  retried: string[];
+  /**
+   * Emails rejected by the mailer after their last allowed attempt
+   */
+  failed: string[];
+}
+
+export class NativeOutboxService {
+  private static NEW = promisify(native.services.outbox.new);
+  private static DELIVER_DUE_EMAILS = promisify(native.services.outbox.deliverDueEmails);
+
+  public readonly box: typeof NativeOutboxServiceBox;
+
+  private constructor(box: typeof NativeOutboxServiceBox) {
+    this.box = box;
+  }
+
+  public static async create(options: Readonly<NativeOutboxServiceOptions>): Promise<NativeOutboxService> {
+    const box = await NativeOutboxService.NEW(options.clock.box, options.logger?.box ?? null, options.mailer.box, options.outboxStore.box);
+    return new NativeOutboxService(box);
+  }
+
+  /**
+   * Send the emails that are due, and record the outcome of each attempt.
+   */
+  async deliverDueEmails(): Promise<OutboxDeliveryReport> {
+    const rawOut: string = await NativeOutboxService.DELIVER_DUE_EMAILS(this.box);
+    return JSON.parse(rawOut);
+  }
+
+  /**
+   * Deliver the due emails every `interval` milliseconds, until the returned function is called.
+   *
+   * Failed passes are reported to `onError`, the next pass is scheduled anyway.
+   */
+  start(interval: number, onError: (e: Error) => void): () => void {
+    let stopped: boolean = false;
+    let timer: NodeJS.Timeout | null = null;
+    const schedule = (): void => {
+      timer = setTimeout(async () => {
+        try {
+          await this.deliverDueEmails();
+        } catch (e) {
+          onError(e as Error);
+        }
+        if (!stopped) {
+          schedule();
+        }
+      }, interval);
+      timer.unref();
+    };
+    schedule();
+    return (): void => {
+      stopped = true;
+      if (timer !== null) {
+        clearTimeout(timer);
+      }
+    };
+  }
+}
@@ -21,6 +21,7 @@ import native from "#native";
 
 import { NativeClock } from "./clock.mjs";
 import { Database } from "./database.mjs";
+import { NativeUuidGenerator } from "./uuid.mjs";
 
 declare const MemTwinoidStoreBox: unique symbol;
 declare const PgTwinoidStoreBox: unique symbol;
@@ -68,10 +69,11 @@ export class MemTwinoidStore extends NativeTwinoidStore {
 export interface PgTwinoidStoreOptions {
   clock: NativeClock;
   database: Database;
+  uuidGenerator: NativeUuidGenerator;
 }
 
 export class PgTwinoidStore extends NativeTwinoidStore {
   constructor(options: Readonly<PgTwinoidStoreOptions>) {
-    super(native.twinoidStore.pg.new(options.clock.box, options.database.box));
+    super(native.twinoidStore.pg.new(options.clock.box, options.database.box, options.uuidGenerator.box));
   }
 }
@@ -32,6 +32,7 @@ import { MemMailer } from "../lib/mailer.mjs";
 import { MemOauthProviderStore, PgOauthProviderStore } from "../lib/oauth-provider-store.mjs";
 import { ScryptPasswordService } from "../lib/password.mjs";
 import { NativeAuthService } from "../lib/services/auth.mjs";
+import { MemTokenStore, PgTokenStore } from "../lib/token-store.mjs";
 import { MemTwinoidStore, PgTwinoidStore } from "../lib/twinoid-store.mjs";
 import { MemUserStore, PgUserStore } from "../lib/user-store.mjs";
 import { Uuid4Generator } from "../lib/uuid.mjs";
@@ -52,6 +53,7 @@ describe("NativeLinkStore", function () {
       const dinoparcStore = new MemDinoparcStore({clock});
       const hammerfestStore = new MemHammerfestStore({clock});
       const twinoidStore = new MemTwinoidStore({clock});
+      const tokenStore = new MemTokenStore({clock});
       const linkStore = new MemLinkStore({clock});
       const link = new DefaultLinkService({dinoparcStore, hammerfestStore, linkStore, twinoidStore, userStore});
       const dinoparcClient = new MemDinoparcClient({clock});
@@ -60,7 +62,7 @@ describe("NativeLinkStore", function () {
       const oauthProviderStore = await MemOauthProviderStore.create({clock, passwordService, uuidGenerator});
       const authStore = await MemAuthStore.create({clock, uuidGenerator});
 
-      const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+      const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
 
       return fn({auth, twinoidStore, link});
     }
@@ -104,7 +106,8 @@ describe("NativeLinkStore", function () {
         const userStore = new PgUserStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
         const dinoparcStore = await PgDinoparcStore.create({clock, database: nativeDatabase, uuidGenerator});
         const hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
-        const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+        const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
+        const tokenStore = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
         const linkStore = new PgLinkStore({clock, database: nativeDatabase});
         const link = new DefaultLinkService({dinoparcStore, hammerfestStore, linkStore, twinoidStore, userStore});
         const dinoparcClient = new MemDinoparcClient({clock});
@@ -113,7 +116,7 @@ describe("NativeLinkStore", function () {
         const oauthProviderStore = await PgOauthProviderStore.create({clock, database: nativeDatabase, passwordService, uuidGenerator, secret: secretKeyStr});
         const authStore = await PgAuthStore.create({clock, database: nativeDatabase, uuidGenerator, secret: secretKeyStr});
 
-        const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+        const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
         try {
           return await fn({auth, twinoidStore, link});
         } finally {
@@ -3,7 +3,7 @@ import { PasswordService } from "@eternal-twin/core/password/service";
 import { Buffer } from "buffer";
 import chai from "chai";
 
-import { ScryptPasswordService } from "../lib/password.mjs";
+import { MultiPasswordService, ScryptPasswordService } from "../lib/password.mjs";
 
 describe("NativePasswordService", function () {
   interface TestApi {
@@ -70,6 +70,28 @@ describe("NativePasswordService", function () {
     });
   }
 
+  describe("MultiPasswordService", function () {
+    async function withMultiPasswordService<R>(fn: (api: TestApi) => Promise<R>): Promise<R> {
+      const password = MultiPasswordService.recommendedForTests();
+      return fn({password});
+    }
+
+    testPasswordService(withMultiPasswordService);
+
+    it("verifies legacy scrypt hashes", async function (this: Mocha.Context) {
+      this.timeout(30000);
+      const password = MultiPasswordService.recommendedForTests();
+      const scryptHash: PasswordHash = await ScryptPasswordService.recommendedForTests().hash(Buffer.from("hunter2"));
+      chai.assert.isTrue(await password.verify(Uint8Array.from(scryptHash), Buffer.from("hunter2")));
+      chai.assert.isFalse(await password.verify(Uint8Array.from(scryptHash), Buffer.from("foo")));
+    });
+
+    it("rejects malformed hashes", async function (this: Mocha.Context) {
+      const password = MultiPasswordService.recommendedForTests();
+      chai.assert.isFalse(await password.verify(Buffer.from("scrypt"), Buffer.from("hunter2")));
+    });
+  });
+
   describe("ScryptPasswordService", function () {
     async function withScryptPasswordService<R>(fn: (api: TestApi) => Promise<R>): Promise<R> {
       const password = ScryptPasswordService.recommendedForTests();
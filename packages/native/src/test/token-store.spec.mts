@@ -60,7 +60,7 @@ describe("NativeTokenStore", function () {
       const uuidGenerator = new Uuid4Generator();
       const secretKeyStr: string = config.etwin.secret;
       const hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
-      const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+      const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
       const token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
       try {
         return await fn({hammerfestStore, twinoidStore, token});
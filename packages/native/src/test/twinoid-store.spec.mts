@@ -9,11 +9,13 @@ import chai from "chai";
 import { SystemClock } from "../lib/clock.mjs";
 import { Database as NativeDatabase } from "../lib/database.mjs";
 import { MemTwinoidStore, PgTwinoidStore } from "../lib/twinoid-store.mjs";
+import { Uuid4Generator } from "../lib/uuid.mjs";
 
 describe("NativeTwinoidStore", function () {
   describe("MemTwinoidStore", function () {
     async function withMemTwinoidStore<R>(fn: (api: TestApi) => Promise<R>): Promise<R> {
       const clock = new SystemClock();
+      const uuidGenerator = new Uuid4Generator();
       const twinoidStore = new MemTwinoidStore({clock});
       return fn({twinoidStore});
     }
@@ -47,7 +49,7 @@ describe("NativeTwinoidStore", function () {
       const nativeDatabase = await NativeDatabase.create(dbConfig);
 
       const clock = new SystemClock();
-      const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+      const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
       try {
         return await fn({twinoidStore});
       } finally {
@@ -11,17 +11,20 @@ import { SystemClock } from "@eternal-twin/native/clock";
 import { Database as NativeDatabase } from "@eternal-twin/native/database";
 import { HttpDinoparcClient } from "@eternal-twin/native/dinoparc-client";
 import { PgDinoparcStore } from "@eternal-twin/native/dinoparc-store";
-import { JsonEmailFormatter } from "@eternal-twin/native/email-formatter";
+import { HtmlEmailFormatter } from "@eternal-twin/native/email-formatter";
 import { HttpHammerfestClient } from "@eternal-twin/native/hammerfest-client";
 import { PgHammerfestStore } from "@eternal-twin/native/hammerfest-store";
 import { PgLinkStore } from "@eternal-twin/native/link-store";
-import { MemMailer } from "@eternal-twin/native/mailer";
+import { FileLogger, NativeLogger, StdoutLogger } from "@eternal-twin/native/logger";
+import { NativeMailer, NoopMailer, OutboxMailer, SmtpMailer } from "@eternal-twin/native/mailer";
 import { PgOauthProviderStore } from "@eternal-twin/native/oauth-provider-store";
-import { ScryptPasswordService } from "@eternal-twin/native/password";
+import { PgOutboxStore } from "@eternal-twin/native/outbox-store";
+import { MultiPasswordService, NativePasswordService } from "@eternal-twin/native/password";
 import { NativeRestRouter } from "@eternal-twin/native/rest";
 import { NativeAuthService } from "@eternal-twin/native/services/auth";
 import { NativeDinoparcService } from "@eternal-twin/native/services/dinoparc";
 import { NativeHammerfestService } from "@eternal-twin/native/services/hammerfest";
+import { NativeOutboxService } from "@eternal-twin/native/services/outbox";
 import { PgTokenStore } from "@eternal-twin/native/token-store";
 import { HttpTwinoidClient } from "@eternal-twin/native/twinoid-client";
 import { PgTwinoidStore } from "@eternal-twin/native/twinoid-store";
@@ -33,6 +36,53 @@ import { Buffer } from "buffer";
 import { KoaAuth } from "../lib/helpers/koa-auth.mjs";
 import { Api } from "../lib/index.mjs";
 
+/**
+ * Password service from the config, with the recommended Argon2id parameters if none are configured.
+ *
+ * Legacy scrypt hashes are still verified, so users upgraded by the Rust server can log in here too.
+ */
+function createPasswordService(config: Config): NativePasswordService {
+  if (config.password === null) {
+    return MultiPasswordService.withOsRng();
+  }
+  return MultiPasswordService.withOsRng({
+    memoryCost: config.password.argon2MemoryCost,
+    timeCost: config.password.argon2TimeCost,
+    parallelism: config.password.argon2Parallelism,
+  });
+}
+
+/**
+ * Logger from the config, or `null` (discarding the events) if no log is configured.
+ */
+async function createLogger(config: Config, clock: SystemClock): Promise<NativeLogger | null> {
+  if (config.log === null) {
+    return null;
+  }
+  switch (config.log.type) {
+    case "stdout":
+      return StdoutLogger.create({clock});
+    case "file":
+      return FileLogger.create({clock, path: config.log.path, maxSize: config.log.maxSize, maxAge: config.log.maxAge});
+  }
+}
+
+/**
+ * Mailer delivering the emails, from the config, or a mailer discarding them if no mailer is configured.
+ */
+async function createMailer(config: Config): Promise<NativeMailer> {
+  if (config.mailer === null) {
+    return NoopMailer.create();
+  }
+  return SmtpMailer.create({
+    relay: config.mailer.host,
+    username: config.mailer.username,
+    password: config.mailer.password,
+    sender: config.mailer.sender,
+    headers: config.mailer.headers,
+  });
+}
+
 export async function createApi(config: Config): Promise<{ api: Api; teardown(): Promise<void>; nativeRouter: HttpRouter }> {
   const {pool, teardown: teardownPool} = createPgPool({
     host: config.db.host,
@@ -54,21 +104,27 @@ export async function createApi(config: Config): Promise<{ api: Api; teardown():
   const database = new Database(pool);
   const secretKeyStr: string = config.etwin.secret;
   const secretKeyBytes: Uint8Array = Buffer.from(secretKeyStr);
-  const mailer = await MemMailer.create();
-  const emailFormatter = await JsonEmailFormatter.create();
-  const passwordService = ScryptPasswordService.withOsRng();
+  const emailFormatter = await HtmlEmailFormatter.create(config.etwin.externalUri.toString());
+  const logger = await createLogger(config, clock);
+  const passwordService = createPasswordService(config);
   const userStore = new PgUserStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
   const dinoparcClient = new HttpDinoparcClient({clock});
   const dinoparcStore = await PgDinoparcStore.create({clock, database: nativeDatabase, uuidGenerator});
   const hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
   const hammerfestClient = new HttpHammerfestClient({clock});
-  const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+  const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
   const twinoidClient = new HttpTwinoidClient({clock});
   const linkStore = new PgLinkStore({clock, database: nativeDatabase});
   const link = new DefaultLinkService({dinoparcStore, hammerfestStore, linkStore, twinoidStore, userStore});
   const oauthProviderStore = await PgOauthProviderStore.create({clock, database: nativeDatabase, passwordService, uuidGenerator, secret: secretKeyStr});
   const authStore = await PgAuthStore.create({clock, database: nativeDatabase, uuidGenerator, secret: secretKeyStr});
-  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+  const token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
+  const outboxStore = new PgOutboxStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
+  // Services queue their emails in the outbox, the outbox service delivers them in the background
+  const mailer = await OutboxMailer.create({outboxStore});
+  const outbox = await NativeOutboxService.create({clock, mailer: await createMailer(config), outboxStore, logger});
+  const stopOutbox = outbox.start(10_000, (e: Error) => console.error(e));
+  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore: token, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes, logger});
 
   const koaAuth = new KoaAuth(auth);
   const forumConfig: ForumConfig = {
@@ -78,9 +134,8 @@ export async function createApi(config: Config): Promise<{ api: Api; teardown():
   const forum = new PgForumService(database, uuidGenerator, userStore, forumConfig);
   const announcement = new PgAnnouncementService({database, uuidGenerator, forum});
 
-  const token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
   const dinoparc = await NativeDinoparcService.create({dinoparcStore, linkStore, userStore});
-  const hammerfest = await NativeHammerfestService.create({hammerfestClient, hammerfestStore, linkStore, userStore});
+  const hammerfest = await NativeHammerfestService.create({hammerfestClient, hammerfestStore, linkStore, userStore, logger});
   const twinoid = new DefaultTwinoidService({twinoidStore, link});
   const user = new DefaultUserService({
     dinoparcClient,
@@ -109,6 +164,7 @@ export async function createApi(config: Config): Promise<{ api: Api; teardown():
   const nativeRouter = await NativeRestRouter.create({dinoparc, hammerfest});
 
   async function teardown(): Promise<void> {
+    stopOutbox();
     await teardownPool();
     await nativeDatabase.close();
   }
@@ -17,7 +17,7 @@ import { PgHammerfestStore } from "@eternal-twin/native/hammerfest-store";
 import { PgLinkStore } from "@eternal-twin/native/link-store";
 import { MemMailer } from "@eternal-twin/native/mailer";
 import { PgOauthProviderStore } from "@eternal-twin/native/oauth-provider-store";
-import { ScryptPasswordService } from "@eternal-twin/native/password";
+import { MultiPasswordService } from "@eternal-twin/native/password";
 import { NativeRestRouter } from "@eternal-twin/native/rest";
 import { NativeAuthService } from "@eternal-twin/native/services/auth";
 import { NativeDinoparcService } from "@eternal-twin/native/services/dinoparc";
@@ -81,14 +81,14 @@ export async function withTestServer<R>(isDev: boolean, fn: (server: TestServer)
     const secretKeyBytes: Uint8Array = Buffer.from(secretKeyStr);
     const mailer = await MemMailer.create();
     const emailFormatter = await JsonEmailFormatter.create();
-    const passwordService = ScryptPasswordService.recommendedForTests();
+    const passwordService = MultiPasswordService.recommendedForTests();
     const userStore = new PgUserStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
     const dinoparcClient = new MemDinoparcClient({clock});
     const dinoparcStore = await PgDinoparcStore.create({clock, database: nativeDatabase, uuidGenerator});
     const hammerfestClient = new MemHammerfestClient({clock});
     const hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
     const twinoidClient = new HttpTwinoidClient({clock});
-    const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+    const twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
     const linkStore = new PgLinkStore({clock, database: nativeDatabase});
     const link = new DefaultLinkService({dinoparcStore, hammerfestStore, linkStore, twinoidStore, userStore});
     const dinoparc = await NativeDinoparcService.create({dinoparcStore, linkStore, userStore});
@@ -96,10 +96,10 @@ export async function withTestServer<R>(isDev: boolean, fn: (server: TestServer)
     const twinoid = new DefaultTwinoidService({twinoidStore, link});
     const oauthProviderStore = await PgOauthProviderStore.create({clock, database: nativeDatabase, passwordService, uuidGenerator, secret: secretKeyStr});
     const authStore = await PgAuthStore.create({clock, database: nativeDatabase, uuidGenerator, secret: secretKeyStr});
-    const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+    const token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
+    const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore: token, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
 
     const koaAuth = new KoaAuth(auth);
-    const token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
     const forumConfig: ForumConfig = {
       postsPerPage: config.forum.postsPerPage,
       threadsPerPage: config.forum.threadsPerPage
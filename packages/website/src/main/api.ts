@@ -12,7 +12,6 @@ import { HammerfestClient } from "@eternal-twin/core/hammerfest/client";
 import { HammerfestStore } from "@eternal-twin/core/hammerfest/store";
 import { DefaultLinkService, LinkService } from "@eternal-twin/core/link/service";
 import { OauthClientService } from "@eternal-twin/core/oauth/client-service";
-import { TokenService } from "@eternal-twin/core/token/service";
 import { TwinoidClient } from "@eternal-twin/core/twinoid/client";
 import { DefaultTwinoidService, TwinoidService } from "@eternal-twin/core/twinoid/service";
 import { DefaultUserService, UserService } from "@eternal-twin/core/user/service";
@@ -25,21 +24,24 @@ import { SystemClock } from "@eternal-twin/native/clock";
 import { Database as NativeDatabase } from "@eternal-twin/native/database";
 import { HttpDinoparcClient } from "@eternal-twin/native/dinoparc-client";
 import { MemDinoparcStore, NativeDinoparcStore, PgDinoparcStore } from "@eternal-twin/native/dinoparc-store";
-import { JsonEmailFormatter } from "@eternal-twin/native/email-formatter";
+import { HtmlEmailFormatter } from "@eternal-twin/native/email-formatter";
 import { HttpHammerfestClient } from "@eternal-twin/native/hammerfest-client";
 import { MemHammerfestStore, NativeHammerfestStore, PgHammerfestStore } from "@eternal-twin/native/hammerfest-store";
 import { MemLinkStore, NativeLinkStore, PgLinkStore } from "@eternal-twin/native/link-store";
-import { MemMailer } from "@eternal-twin/native/mailer";
+import { FileLogger, NativeLogger, StdoutLogger } from "@eternal-twin/native/logger";
+import { NativeMailer, NoopMailer, OutboxMailer, SmtpMailer } from "@eternal-twin/native/mailer";
 import {
   MemOauthProviderStore,
   NativeOauthProviderStore,
   PgOauthProviderStore
 } from "@eternal-twin/native/oauth-provider-store";
-import { ScryptPasswordService } from "@eternal-twin/native/password";
+import { MemOutboxStore, NativeOutboxStore, PgOutboxStore } from "@eternal-twin/native/outbox-store";
+import { MultiPasswordService, NativePasswordService } from "@eternal-twin/native/password";
 import { NativeAuthService } from "@eternal-twin/native/services/auth";
 import { NativeDinoparcService } from "@eternal-twin/native/services/dinoparc";
 import { NativeHammerfestService } from "@eternal-twin/native/services/hammerfest";
-import { MemTokenStore, PgTokenStore } from "@eternal-twin/native/token-store";
+import { NativeOutboxService } from "@eternal-twin/native/services/outbox";
+import { MemTokenStore, NativeTokenStore, PgTokenStore } from "@eternal-twin/native/token-store";
 import { HttpTwinoidClient } from "@eternal-twin/native/twinoid-client";
 import { MemTwinoidStore, NativeTwinoidStore, PgTwinoidStore } from "@eternal-twin/native/twinoid-store";
 import { MemUserStore, NativeUserStore, PgUserStore } from "@eternal-twin/native/user-store";
@@ -70,14 +72,61 @@ export interface Api {
   user: UserService;
 }
 
+/**
+ * Password service from the config, with the recommended Argon2id parameters if none are configured.
+ *
+ * Legacy scrypt hashes are still verified, so users upgraded by the Rust server can log in here too.
+ */
+function createPasswordService(config: Config): NativePasswordService {
+  if (config.password === null) {
+    return MultiPasswordService.withOsRng();
+  }
+  return MultiPasswordService.withOsRng({
+    memoryCost: config.password.argon2MemoryCost,
+    timeCost: config.password.argon2TimeCost,
+    parallelism: config.password.argon2Parallelism,
+  });
+}
+
+/**
+ * Logger from the config, or `null` (discarding the events) if no log is configured.
+ */
+async function createLogger(config: Config, clock: SystemClock): Promise<NativeLogger | null> {
+  if (config.log === null) {
+    return null;
+  }
+  switch (config.log.type) {
+    case "stdout":
+      return StdoutLogger.create({clock});
+    case "file":
+      return FileLogger.create({clock, path: config.log.path, maxSize: config.log.maxSize, maxAge: config.log.maxAge});
+  }
+}
+
+/**
+ * Mailer delivering the emails, from the config, or a mailer discarding them if no mailer is configured.
+ */
+async function createMailer(config: Config): Promise<NativeMailer> {
+  if (config.mailer === null) {
+    return NoopMailer.create();
+  }
+  return SmtpMailer.create({
+    relay: config.mailer.host,
+    username: config.mailer.username,
+    password: config.mailer.password,
+    sender: config.mailer.sender,
+    headers: config.mailer.headers,
+  });
+}
+
 async function createApi(config: Config): Promise<{ api: Api; teardown(): Promise<void> }> {
   const clock = new SystemClock();
   const uuidGenerator = new Uuid4Generator();
   const secretKeyStr: string = config.etwin.secret;
   const secretKeyBytes: Uint8Array = Buffer.from(secretKeyStr);
-  const mailer = await MemMailer.create();
-  const emailFormatter = await JsonEmailFormatter.create();
-  const passwordService = ScryptPasswordService.withOsRng();
+  const emailFormatter = await HtmlEmailFormatter.create(config.etwin.externalUri.toString());
+  const logger = await createLogger(config, clock);
+  const passwordService = createPasswordService(config);
   const dinoparcClient = new HttpDinoparcClient({clock});
   const hammerfestClient = new HttpHammerfestClient({clock});
   const twinoidClient = new HttpTwinoidClient({clock});
@@ -96,7 +145,8 @@ async function createApi(config: Config): Promise<{ api: Api; teardown(): Promis
   let authStore: NativeAuthStore;
   let twinoidStore: NativeTwinoidStore;
   let userStore: NativeUserStore;
-  let token: TokenService;
+  let token: NativeTokenStore;
+  let outboxStore: NativeOutboxStore;
 
   let teardown: () => Promise<void>;
 
@@ -111,6 +161,7 @@ async function createApi(config: Config): Promise<{ api: Api; teardown(): Promis
     authStore = await MemAuthStore.create({clock, uuidGenerator});
     forum = new InMemoryForumService(uuidGenerator, userStore, forumConfig);
     token = new MemTokenStore({clock});
+    outboxStore = new MemOutboxStore({clock, uuidGenerator});
     announcement = new MemAnnouncementService({uuidGenerator, forum});
 
     teardown = async function (): Promise<void> {
@@ -133,7 +184,7 @@ async function createApi(config: Config): Promise<{ api: Api; teardown(): Promis
     });
     dinoparcStore = await PgDinoparcStore.create({clock, database: nativeDatabase, uuidGenerator});
     hammerfestStore = await PgHammerfestStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
-    twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase});
+    twinoidStore = new PgTwinoidStore({clock, database: nativeDatabase, uuidGenerator});
     userStore = new PgUserStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
     linkStore = new PgLinkStore({clock, database: nativeDatabase});
     link = new DefaultLinkService({dinoparcStore, hammerfestStore, linkStore, twinoidStore, userStore});
@@ -142,6 +193,7 @@ async function createApi(config: Config): Promise<{ api: Api; teardown(): Promis
 
     forum = new PgForumService(database, uuidGenerator, userStore, forumConfig);
     token = await PgTokenStore.create({clock, database: nativeDatabase, databaseSecret: secretKeyStr});
+    outboxStore = new PgOutboxStore({clock, database: nativeDatabase, databaseSecret: secretKeyStr, uuidGenerator});
     announcement = new PgAnnouncementService({database, uuidGenerator, forum});
 
     teardown = async function (): Promise<void> {
@@ -149,9 +201,19 @@ async function createApi(config: Config): Promise<{ api: Api; teardown(): Promis
     };
   }
 
-  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes});
+  // Services queue their emails in the outbox, the outbox service delivers them in the background
+  const mailer = await OutboxMailer.create({outboxStore});
+  const outbox = await NativeOutboxService.create({clock, mailer: await createMailer(config), outboxStore, logger});
+  const stopOutbox = outbox.start(10_000, (e: Error) => console.error(e));
+  const teardownStores = teardown;
+  teardown = async function (): Promise<void> {
+    stopOutbox();
+    await teardownStores();
+  };
+
+  const auth = await NativeAuthService.create({authStore, clock, dinoparcClient, dinoparcStore, emailFormatter, hammerfestClient, hammerfestStore, linkStore, mailer, oauthProviderStore, passwordService, tokenStore: token, userStore, twinoidClient, twinoidStore, uuidGenerator, authSecret: secretKeyBytes, logger});
   const dinoparc = await NativeDinoparcService.create({dinoparcStore, linkStore, userStore});
-  const hammerfest = await NativeHammerfestService.create({hammerfestClient, hammerfestStore, linkStore, userStore});
+  const hammerfest = await NativeHammerfestService.create({hammerfestClient, hammerfestStore, linkStore, userStore, logger});
   const twinoid = new DefaultTwinoidService({twinoidStore, link});
   const user = new DefaultUserService({
     dinoparcClient,
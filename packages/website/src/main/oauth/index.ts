@@ -119,7 +119,7 @@ export async function createOauthRouter(api: Api): Promise<Router> {
     const {state, accessToken} = stateAndAccessToken;
     switch (state.action.type) {
       case EtwinOauthActionType.Login: {
-        const {isAdministrator, user, session}: UserAndSession = await api.auth.registerOrLoginWithTwinoidOauth(GUEST_ACX, accessToken.accessToken);
+        const {isAdministrator, user, session}: UserAndSession = await api.auth.registerOrLoginWithTwinoidOauth(GUEST_ACX, accessToken);
         cx.cookies.set(SESSION_COOKIE, session.id);
         const auth: UserAuthContext = {
           type: AuthType.User,